@@ -0,0 +1,29 @@
+#!/bin/sh
+# Regenerate src/netcdf/cf_standard_names.txt from the official CF Standard
+# Name Table: every standard name and alias, one per line.
+#
+# Usage: scripts/update_cf_standard_names.sh [table URL or XML file]
+set -eu
+
+source=${1:-https://cfconventions.org/Data/cf-standard-names/current/src/cf-standard-name-table.xml}
+out="$(dirname "$0")/../src/netcdf/cf_standard_names.txt"
+
+if [ -f "$source" ]; then
+    xml=$(cat "$source")
+else
+    xml=$(curl -fsSL "$source")
+fi
+
+version=$(printf '%s\n' "$xml" | grep -oE '<version_number>[0-9]+' | head -n 1 | sed 's/.*>//')
+names=$(printf '%s\n' "$xml" | grep -oE '<(entry|alias) id="[^"]+"' | sed -E 's/.*id="([^"]+)"/\1/' | sort -u)
+if [ -z "$version" ] || [ -z "$names" ]; then
+    echo "no standard names found in $source" >&2
+    exit 1
+fi
+
+{
+    echo "# CF Standard Name Table version $version: all standard names and aliases."
+    echo "# Generated by scripts/update_cf_standard_names.sh; do not edit by hand."
+    printf '%s\n' "$names"
+} > "$out"
+echo "wrote $(printf '%s\n' "$names" | wc -l | tr -d ' ') names of version $version to $out"
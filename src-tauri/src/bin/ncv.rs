@@ -0,0 +1,966 @@
+//! `ncv`: command-line access to the viewer's readers, statistics and exporters
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use netcdf_viewer_lib::errors::NetCDFError;
+use netcdf_viewer_lib::models::{
+    BandSelection, CatalogQuery, CdlDumpOptions, ChangeKind, DerivedVariableDefinition, DiffOptions,
+    DiffReport, DimensionSlice, ExportSummary, FileMetadata, GeoExtent, GeoTiffExportRequest,
+    NetCDFExportRequest, NetCDFFormat, PointMethod, RegionExportRequest, RegionSelection,
+    RegionSeriesRequest, RegridExportRequest, RegridMethod, RegridOptions,
+    RegridTarget, StationExportRequest, StationRequest, TableExportRequest, TimeSeriesRequest, VariableData, WatchChange, WatchEvent,
+};
+use netcdf_viewer_lib::netcdf;
+use netcdf_viewer_lib::server::{serve, ServerConfig, DEFAULT_WORKERS};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "ncv", version, about = "Inspect and convert NetCDF, HDF5, Zarr and GRIB2 data")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Add a variable computed from others, as NAME=EXPRESSION,
+    /// e.g. "wind=sqrt(u10**2 + v10**2)" (repeatable)
+    #[arg(long = "derive", global = true, value_parser = parse_derived)]
+    derive: Vec<(String, String)>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show dimensions, variables and global attributes
+    Info {
+        file: String,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the file as CDL text, like ncdump
+    Dump {
+        file: String,
+        /// Include data for these variables (comma separated)
+        #[arg(short = 'v', long = "variables", value_delimiter = ',')]
+        variables: Vec<String>,
+        /// Maximum number of values shown per variable
+        #[arg(long)]
+        max_values: Option<usize>,
+    },
+    /// Summary statistics of a variable or hyperslab
+    Stats {
+        file: String,
+        variable: String,
+        #[command(flatten)]
+        selection: Selection,
+        #[command(flatten)]
+        region: RegionArgs,
+        /// Convert the values to these units, e.g. degC or "mm/day"
+        #[arg(long)]
+        units: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the values of a hyperslab
+    Subset {
+        file: String,
+        variable: String,
+        #[command(flatten)]
+        selection: Selection,
+        /// Convert the values to these units, e.g. degC or "mm/day"
+        #[arg(long)]
+        units: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write variables to another format
+    Export {
+        file: String,
+        /// Variables to export
+        #[arg(required = true)]
+        variables: Vec<String>,
+        /// Output file
+        #[arg(short, long)]
+        output: String,
+        #[arg(short, long, value_enum)]
+        format: ExportFormat,
+        #[command(flatten)]
+        selection: Selection,
+        /// Keep time coordinates as raw offsets in tables
+        #[arg(long)]
+        raw_times: bool,
+        /// Deflate level for NetCDF-4 output
+        #[arg(long)]
+        deflate: Option<i32>,
+    },
+    /// Regrid variables onto a regular or another file's lat/lon grid and write NetCDF
+    Regrid {
+        file: String,
+        /// Variables to regrid
+        #[arg(required = true)]
+        variables: Vec<String>,
+        /// Output file
+        #[arg(short, long)]
+        output: String,
+        /// Regular target grid as SOUTH,WEST,NORTH,EAST,STEP[,LON_STEP] in degrees
+        #[arg(long, required_unless_present = "like", conflicts_with = "like", allow_hyphen_values = true)]
+        grid: Option<String>,
+        /// Regrid onto the latitude/longitude grid of this file
+        #[arg(long)]
+        like: Option<String>,
+        #[arg(short, long, value_enum, default_value = "bilinear")]
+        method: Method,
+        #[command(flatten)]
+        selection: Selection,
+        /// Write a classic (64-bit offset) file instead of NetCDF-4
+        #[arg(long)]
+        classic: bool,
+    },
+    /// Time series of a variable at one grid point
+    Timeseries {
+        file: String,
+        variable: String,
+        /// Latitude of the point (nearest grid cell)
+        #[arg(long, requires = "lon", allow_negative_numbers = true)]
+        lat: Option<f64>,
+        /// Longitude of the point
+        #[arg(long, requires = "lat", allow_negative_numbers = true)]
+        lon: Option<f64>,
+        /// Index along another dimension, as DIM=INDEX
+        #[arg(long = "at", value_parser = parse_index)]
+        indices: Vec<(String, usize)>,
+        /// Convert the values to these units, e.g. degC or "mm/day"
+        #[arg(long)]
+        units: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Time series of a variable at a list of stations
+    Stations {
+        file: String,
+        variable: String,
+        /// CSV file of stations (id, lat, lon[, elevation], with or without a header)
+        #[arg(long, required_unless_present = "points")]
+        stations: Option<String>,
+        /// A station given inline, as ID,LAT,LON[,ELEVATION] (may be repeated)
+        #[arg(long = "point", allow_hyphen_values = true)]
+        points: Vec<String>,
+        #[arg(short, long, value_enum, default_value = "bilinear")]
+        method: Interpolation,
+        /// Use the nearest valid cell within this distance (km) where values are missing
+        #[arg(long)]
+        radius: Option<f64>,
+        /// Index along another dimension, as DIM=INDEX
+        #[arg(long = "at", value_parser = parse_index)]
+        indices: Vec<(String, usize)>,
+        /// Write a CSV table instead of printing the values
+        #[arg(short, long)]
+        output: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// List the polygon features of a GeoJSON file or shapefile
+    Regions {
+        file: String,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Area-weighted mean of a variable over polygons at every time step
+    RegionMean {
+        file: String,
+        variable: String,
+        /// GeoJSON file or shapefile holding the polygons
+        regions: String,
+        /// Feature to use, by name or 0-based position (may be repeated; default all)
+        #[arg(long = "feature")]
+        features: Vec<String>,
+        /// Weight cells by the fraction of their area inside the polygons
+        #[arg(long)]
+        fractional: bool,
+        /// Index along another dimension, as DIM=INDEX
+        #[arg(long = "at", value_parser = parse_index)]
+        indices: Vec<(String, usize)>,
+        /// Write a CSV table instead of printing the values
+        #[arg(short, long)]
+        output: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compare two files; exits with status 1 when they differ and 2 on errors
+    Diff {
+        file_a: String,
+        file_b: String,
+        /// Absolute difference up to which values count as equal
+        #[arg(long, default_value_t = 0.0)]
+        abs_tol: f64,
+        /// Relative difference up to which values count as equal
+        #[arg(long, default_value_t = 0.0)]
+        rel_tol: f64,
+        /// Only compare the data of these variables (comma separated)
+        #[arg(short = 'v', long = "variables", value_delimiter = ',')]
+        variables: Vec<String>,
+        /// Compare dimensions, variables and attributes only
+        #[arg(long)]
+        metadata_only: bool,
+        /// Convert the second file's values to the first file's units where they differ
+        #[arg(long)]
+        convert_units: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Convert a value between two units
+    Units {
+        #[arg(allow_negative_numbers = true)]
+        value: f64,
+        from: String,
+        to: String,
+    },
+    /// Check metadata against the CF conventions; exits with status 1 when
+    /// the check finds errors and 2 when the file cannot be checked
+    CheckCf {
+        file: String,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Index directories of files and search the index
+    Catalog {
+        #[command(subcommand)]
+        action: CatalogAction,
+        /// Index file (default: $NCV_CATALOG or ~/.ncv/catalog.sqlite)
+        #[arg(long, global = true)]
+        index: Option<String>,
+    },
+    /// Report records appended to a file, or files added to a directory, until interrupted
+    Watch {
+        path: String,
+        /// Seconds between polls
+        #[arg(long, default_value_t = 2.0)]
+        interval: f64,
+        /// Print one JSON event per line
+        #[arg(long)]
+        json: bool,
+    },
+    /// Serve the read-only commands over HTTP for remote viewers
+    Serve {
+        /// Directory whose files are served
+        #[arg(long)]
+        root: PathBuf,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8765")]
+        address: String,
+        /// Web origin allowed to call the API from a browser, e.g.
+        /// "http://tauri.localhost" (repeatable); browsers are refused otherwise
+        #[arg(long = "allow-origin")]
+        allowed_origins: Vec<String>,
+        /// Number of requests handled at the same time
+        #[arg(long, default_value_t = DEFAULT_WORKERS)]
+        workers: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum CatalogAction {
+    /// Scan a directory recursively and update the index
+    Scan { dir: String },
+    /// List indexed files matching all given filters
+    Query {
+        /// Variable name or standard_name
+        #[arg(long)]
+        variable: Option<String>,
+        /// Start of the period the file must overlap (ISO 8601)
+        #[arg(long)]
+        from: Option<String>,
+        /// End of the period (a date includes the whole day)
+        #[arg(long)]
+        to: Option<String>,
+        /// Area the file must intersect, as SOUTH,WEST,NORTH,EAST
+        #[arg(long, value_parser = parse_bbox, allow_hyphen_values = true)]
+        bbox: Option<GeoExtent>,
+        /// Global attribute containing a text, as NAME=TEXT (may be repeated)
+        #[arg(long = "attr", value_parser = parse_attribute)]
+        attributes: Vec<(String, String)>,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Hyperslab selection shared by several subcommands
+#[derive(Args)]
+struct Selection {
+    /// Range along a dimension, as DIM=START:COUNT (may be repeated)
+    #[arg(long = "slice", value_parser = parse_slice)]
+    slices: Vec<(String, DimensionSlice)>,
+}
+
+impl Selection {
+    fn slices(&self) -> HashMap<String, DimensionSlice> {
+        self.slices.iter().cloned().collect()
+    }
+}
+
+/// Polygon region restricting statistics
+#[derive(Args)]
+struct RegionArgs {
+    /// Only count cells inside the polygons of this GeoJSON file or shapefile
+    #[arg(long)]
+    region: Option<String>,
+    /// Feature of the region file to use, by name or 0-based position (may be repeated)
+    #[arg(long = "feature", requires = "region")]
+    features: Vec<String>,
+    /// Weight cells by the fraction of their area inside the polygons
+    #[arg(long, requires = "region")]
+    fractional: bool,
+}
+
+impl RegionArgs {
+    fn selection(&self) -> Option<RegionSelection> {
+        self.region.as_ref().map(|path| RegionSelection {
+            path: path.clone(),
+            features: self.features.clone(),
+            fractional: self.fractional,
+        })
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Tsv,
+    Parquet,
+    Arrow,
+    Netcdf4,
+    Classic,
+    /// One lat/lon slice; a --slice with a count above 1 writes that dimension's steps as bands
+    Geotiff,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Method {
+    Nearest,
+    Bilinear,
+    Conservative,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Interpolation {
+    Nearest,
+    Bilinear,
+}
+
+impl Command {
+    /// The dataset a command reads, which derived variables are added to
+    fn dataset(&self) -> Option<&str> {
+        match self {
+            Command::Info { file, .. }
+            | Command::Dump { file, .. }
+            | Command::Stats { file, .. }
+            | Command::Subset { file, .. }
+            | Command::Export { file, .. }
+            | Command::Regrid { file, .. }
+            | Command::Timeseries { file, .. }
+            | Command::Stations { file, .. }
+            | Command::RegionMean { file, .. }
+            | Command::CheckCf { file, .. } => Some(file),
+            _ => None,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match define_derived(&cli).and_then(|()| run(cli.command)) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("ncv: {}", e);
+            // 1 is reserved for "files differ" and "CF errors found"
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn define_derived(cli: &Cli) -> Result<(), NetCDFError> {
+    if cli.derive.is_empty() {
+        return Ok(());
+    }
+    let file = cli.command.dataset().ok_or_else(|| {
+        NetCDFError::ExpressionError("--derive needs a command that reads one file".to_string())
+    })?;
+    for (name, expression) in &cli.derive {
+        let definition = DerivedVariableDefinition {
+            name: name.clone(),
+            expression: expression.clone(),
+            units: None,
+            long_name: None,
+        };
+        netcdf::derived::define_variable(file, definition)?;
+    }
+    Ok(())
+}
+
+fn run(command: Command) -> Result<ExitCode, NetCDFError> {
+    let result = match command {
+        Command::Info { file, json } => {
+            let mut metadata = netcdf::open_netcdf(&file)?;
+            metadata.coordinates = Some(netcdf::detect_coordinates(&metadata));
+            if json {
+                print_json(&metadata)
+            } else {
+                print_info(&metadata);
+                Ok(())
+            }
+        }
+        Command::Dump { file, variables, max_values } => {
+            let options = CdlDumpOptions {
+                data_variables: variables,
+                max_values,
+            };
+            print!("{}", netcdf::dump_cdl(&file, &options)?);
+            Ok(())
+        }
+        Command::Stats { file, variable, selection, region, units, json } => {
+            let (region, slices) = (region.selection(), selection.slices());
+            let stats = netcdf::read_in_units(&file, &variable, units.as_deref(), |dataset| match &region {
+                Some(region) => netcdf::read_region_stats(dataset, &variable, &slices, region),
+                None => netcdf::read_stats(dataset, &variable, &slices),
+            })?;
+            if json {
+                return print_json(&stats).map(|()| ExitCode::SUCCESS);
+            }
+            let format = |v: Option<f64>| v.map_or("-".to_string(), |x| x.to_string());
+            println!("variable\t{}", stats.var_name);
+            println!("count\t{}", stats.count);
+            println!("missing\t{}", stats.missing_count);
+            println!("min\t{}", format(stats.min));
+            println!("max\t{}", format(stats.max));
+            println!("mean\t{}", format(stats.mean));
+            println!("std_dev\t{}", format(stats.std_dev));
+            Ok(())
+        }
+        Command::Subset { file, variable, selection, units, json } => {
+            let metadata = netcdf::open_netcdf(&file)?;
+            let var = metadata
+                .variables
+                .iter()
+                .find(|v| v.name == variable)
+                .ok_or_else(|| NetCDFError::VariableNotFound(variable.clone()))?;
+            let slices = selection.slices();
+            let (start, count): (Vec<usize>, Vec<usize>) = var
+                .dimensions
+                .iter()
+                .zip(&var.shape)
+                .map(|(dim, &size)| slices.get(dim).map_or((0, size), |s| (s.start, s.count)))
+                .unzip();
+
+            let response = netcdf::read_in_units(&file, &variable, units.as_deref(), |dataset| {
+                netcdf::read_variable_subset(dataset, &variable, &start, &count)
+            })?;
+            if json {
+                return print_json(&response).map(|()| ExitCode::SUCCESS);
+            }
+            print_subset(&var.dimensions, &start, &count, &response.values);
+            Ok(())
+        }
+        Command::Export {
+            file,
+            variables,
+            output,
+            format,
+            selection,
+            raw_times,
+            deflate,
+        } => {
+            let table = |delimiter| TableExportRequest {
+                output_path: output.clone(),
+                variables: variables.clone(),
+                slices: selection.slices(),
+                decode_times: !raw_times,
+                delimiter,
+                missing_value: None,
+            };
+            let netcdf_request = |format| NetCDFExportRequest {
+                output_path: output.clone(),
+                variables: variables.clone(),
+                slices: selection.slices(),
+                format,
+                deflate_level: deflate,
+            };
+            let summary = match format {
+                ExportFormat::Csv => netcdf::export_csv(&file, &table(None))?,
+                ExportFormat::Tsv => netcdf::export_csv(&file, &table(Some('\t')))?,
+                ExportFormat::Parquet => netcdf::export_parquet(&file, &table(None))?,
+                ExportFormat::Arrow => netcdf::export_arrow(&file, &table(None))?,
+                ExportFormat::Netcdf4 => netcdf::export_netcdf(&file, &netcdf_request(NetCDFFormat::Netcdf4))?,
+                ExportFormat::Classic => netcdf::export_netcdf(&file, &netcdf_request(NetCDFFormat::Classic))?,
+                ExportFormat::Geotiff => {
+                    let [var_name] = variables.as_slice() else {
+                        return Err(NetCDFError::ExportError(
+                            "GeoTIFF export takes exactly one variable".to_string(),
+                        ));
+                    };
+                    netcdf::export_geotiff(&file, &geotiff_request(&output, var_name, &selection)?)?
+                }
+            };
+            print_summary(&summary);
+            Ok(())
+        }
+        Command::Regrid {
+            file,
+            variables,
+            output,
+            grid,
+            like,
+            method,
+            selection,
+            classic,
+        } => {
+            let target = match (grid, like) {
+                (Some(grid), _) => parse_grid(&grid)?,
+                (None, Some(path)) => RegridTarget::File { path },
+                (None, None) => unreachable!("clap requires --grid or --like"),
+            };
+            let method = match method {
+                Method::Nearest => RegridMethod::Nearest,
+                Method::Bilinear => RegridMethod::Bilinear,
+                Method::Conservative => RegridMethod::Conservative,
+            };
+            let request = RegridExportRequest {
+                output_path: output,
+                variables,
+                regrid: RegridOptions { target, method },
+                slices: selection.slices(),
+                format: if classic { NetCDFFormat::Classic } else { NetCDFFormat::Netcdf4 },
+            };
+            print_summary(&netcdf::export_regridded(&file, &request)?);
+            Ok(())
+        }
+        Command::Timeseries {
+            file,
+            variable,
+            lat,
+            lon,
+            indices,
+            units,
+            json,
+        } => {
+            let request = TimeSeriesRequest {
+                var_name: variable,
+                indices: indices.into_iter().collect(),
+                lat,
+                lon,
+            };
+            let points = netcdf::read_in_units(&file, &request.var_name, units.as_deref(), |dataset| {
+                netcdf::read_timeseries(dataset, &request)
+            })?;
+            if json {
+                return print_json(&points).map(|()| ExitCode::SUCCESS);
+            }
+            println!("time\tvalue");
+            for point in points {
+                println!("{}\t{}", point.time, point.value);
+            }
+            Ok(())
+        }
+        Command::Stations {
+            file,
+            variable,
+            stations,
+            points,
+            method,
+            radius,
+            indices,
+            output,
+            json,
+        } => {
+            let mut list = match stations {
+                Some(path) => netcdf::stations::read_stations(&path)?,
+                None => Vec::new(),
+            };
+            if !points.is_empty() {
+                list.extend(netcdf::stations::parse_stations(&points.join("\n"))?);
+            }
+            let request = StationRequest {
+                var_name: variable,
+                stations: list,
+                method: match method {
+                    Interpolation::Nearest => PointMethod::Nearest,
+                    Interpolation::Bilinear => PointMethod::Bilinear,
+                },
+                indices: indices.into_iter().collect(),
+                search_radius_km: radius,
+            };
+            if let Some(output_path) = output {
+                let request = StationExportRequest { output_path, extraction: request };
+                print_summary(&netcdf::export_stations(&file, &request)?);
+                return Ok(ExitCode::SUCCESS);
+            }
+            let extraction = netcdf::extract_stations(&file, &request)?;
+            if json {
+                return print_json(&extraction).map(|()| ExitCode::SUCCESS);
+            }
+            println!("station\ttime\tvalue");
+            for record in extraction.records {
+                println!("{}\t{}\t{}", record.station_id, record.time, record.value);
+            }
+            Ok(())
+        }
+        Command::Regions { file, json } => {
+            let features = netcdf::polygons::list_regions(&file)?;
+            if json {
+                return print_json(&features).map(|()| ExitCode::SUCCESS);
+            }
+            println!("index\tname\tsouth\twest\tnorth\teast");
+            for f in features {
+                let e = &f.extent;
+                println!("{}\t{}\t{}\t{}\t{}\t{}", f.index, f.name, e.south, e.west, e.north, e.east);
+            }
+            Ok(())
+        }
+        Command::RegionMean {
+            file,
+            variable,
+            regions,
+            features,
+            fractional,
+            indices,
+            output,
+            json,
+        } => {
+            let request = RegionSeriesRequest {
+                var_name: variable,
+                region: RegionSelection { path: regions, features, fractional },
+                indices: indices.into_iter().collect(),
+            };
+            if let Some(output_path) = output {
+                let request = RegionExportRequest { output_path, series: request };
+                print_summary(&netcdf::export_region_series(&file, &request)?);
+                return Ok(ExitCode::SUCCESS);
+            }
+            let series = netcdf::region_series(&file, &request)?;
+            if json {
+                return print_json(&series).map(|()| ExitCode::SUCCESS);
+            }
+            eprintln!("{} cells, {:.0} km2", series.cells, series.area_km2);
+            let format = |v: Option<f64>| v.map_or("-".to_string(), |x| x.to_string());
+            println!("time\tmean\tmin\tmax\tstd_dev\tvalid_fraction");
+            for step in series.steps {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    step.time,
+                    format(step.mean),
+                    format(step.min),
+                    format(step.max),
+                    format(step.std_dev),
+                    step.valid_fraction
+                );
+            }
+            Ok(())
+        }
+        Command::Diff {
+            file_a,
+            file_b,
+            abs_tol,
+            rel_tol,
+            variables,
+            metadata_only,
+            convert_units,
+            json,
+        } => {
+            let options = DiffOptions {
+                abs_tolerance: abs_tol,
+                rel_tolerance: rel_tol,
+                variables,
+                metadata_only,
+                convert_units,
+            };
+            let report = netcdf::diff_files(&file_a, &file_b, &options)?;
+            if json {
+                print_json(&report)?;
+            } else {
+                print_diff(&report);
+            }
+            return Ok(if report.identical { ExitCode::SUCCESS } else { ExitCode::from(1) });
+        }
+        Command::Units { value, from, to } => {
+            println!("{}", netcdf::units::conversion(&from, &to)?.apply(value));
+            Ok(())
+        }
+        Command::CheckCf { file, json } => {
+            let report = netcdf::check_cf(&file)?;
+            if json {
+                print_json(&report)?;
+            } else {
+                print!("{}", netcdf::cf_check::format_report(&report));
+            }
+            return Ok(if report.errors == 0 { ExitCode::SUCCESS } else { ExitCode::from(1) });
+        }
+        Command::Catalog { action, index } => {
+            let index = index.unwrap_or_else(netcdf::catalog::default_index_path);
+            match action {
+                CatalogAction::Scan { dir } => {
+                    let summary = netcdf::scan_directory(&dir, &index)?;
+                    println!(
+                        "Indexed {} files ({} unchanged, {} removed) into {}",
+                        summary.indexed, summary.unchanged, summary.removed, summary.index_path
+                    );
+                    for failure in &summary.failed {
+                        eprintln!("Skipped {}", failure);
+                    }
+                    Ok(())
+                }
+                CatalogAction::Query {
+                    variable,
+                    from,
+                    to,
+                    bbox,
+                    attributes,
+                    json,
+                } => {
+                    let query = CatalogQuery {
+                        variable,
+                        time_start: from,
+                        time_end: to,
+                        extent: bbox,
+                        attributes: attributes.into_iter().collect(),
+                    };
+                    let entries = netcdf::query_catalog(&index, &query)?;
+                    if json {
+                        return print_json(&entries).map(|()| ExitCode::SUCCESS);
+                    }
+                    for entry in entries {
+                        let show = |t: &Option<String>| t.clone().unwrap_or_else(|| "-".to_string());
+                        println!("{}\t{}\t{}", entry.path, show(&entry.time_start), show(&entry.time_end));
+                    }
+                    Ok(())
+                }
+            }
+        }
+        Command::Watch { path, interval, json } => {
+            let mut state = netcdf::watch::WatchState::new(&path)?;
+            eprintln!("Watching {}", path);
+            loop {
+                std::thread::sleep(Duration::from_secs_f64(interval.max(0.1)));
+                for event in state.poll() {
+                    if json {
+                        let line = serde_json::to_string(&event)
+                            .map_err(|e| NetCDFError::ConversionError(e.to_string()))?;
+                        println!("{}", line);
+                    } else {
+                        print_watch_event(&event);
+                    }
+                }
+            }
+        }
+        Command::Serve { root, address, allowed_origins, workers } => {
+            eprintln!("Serving {} on http://{}", root.display(), address);
+            serve(&ServerConfig { address, root, allowed_origins, workers })
+        }
+    };
+    result.map(|()| ExitCode::SUCCESS)
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), NetCDFError> {
+    let text = serde_json::to_string_pretty(value)
+        .map_err(|e| NetCDFError::ConversionError(e.to_string()))?;
+    println!("{}", text);
+    Ok(())
+}
+
+fn print_watch_event(event: &WatchEvent) {
+    let change = match event.change {
+        WatchChange::Created => "created",
+        WatchChange::Changed => "changed",
+        WatchChange::Removed => "removed",
+    };
+    let sizes: Vec<String> = event.dimensions.iter().map(|d| format!("{}={}", d.name, d.size)).collect();
+    println!("{}\t{}\t{}", change, event.path, sizes.join(" "));
+}
+
+fn print_info(metadata: &FileMetadata) {
+    println!("{}", metadata.file_path);
+
+    println!("\nDimensions:");
+    for dim in &metadata.dimensions {
+        let unlimited = if dim.is_unlimited { " (unlimited)" } else { "" };
+        println!("  {} = {}{}", dim.name, dim.size, unlimited);
+    }
+
+    println!("\nVariables:");
+    for var in &metadata.variables {
+        let dims: Vec<String> = var
+            .dimensions
+            .iter()
+            .zip(&var.shape)
+            .map(|(d, n)| format!("{}={}", d, n))
+            .collect();
+        let units = var.attributes.get("units").map(|u| format!(" [{}]", u)).unwrap_or_default();
+        println!("  {} {}({}){}", var.data_type, var.name, dims.join(", "), units);
+    }
+
+    if let Some(coords) = &metadata.coordinates {
+        println!("\nCoordinates:");
+        let show = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+        println!("  time = {}", show(&coords.time_var));
+        println!("  lat = {}", show(&coords.lat_var));
+        println!("  lon = {}", show(&coords.lon_var));
+    }
+
+    let mut attrs: Vec<_> = metadata.global_attrs.iter().collect();
+    attrs.sort();
+    println!("\nGlobal attributes:");
+    for (name, value) in attrs {
+        println!("  {} = {}", name, value);
+    }
+}
+
+/// One row per element: the index along each dimension, then the value
+fn print_subset(dimensions: &[String], start: &[usize], count: &[usize], values: &VariableData) {
+    let mut header = dimensions.to_vec();
+    header.push("value".to_string());
+    println!("{}", header.join("\t"));
+
+    let mut index = start.to_vec();
+    let mut offset = vec![0; count.len()];
+    let items: Vec<String> = match values {
+        VariableData::Numeric(v) => v.iter().map(|x| x.to_string()).collect(),
+        VariableData::Text(v) => v.clone(),
+    };
+    for item in items {
+        for (d, position) in index.iter_mut().enumerate() {
+            *position = start[d] + offset[d];
+        }
+        let mut row: Vec<String> = index.iter().map(|i| i.to_string()).collect();
+        row.push(item);
+        println!("{}", row.join("\t"));
+        netcdf::table::next_index(&mut offset, count);
+    }
+}
+
+/// One line per metadata change, then one per variable whose data differs
+fn print_diff(report: &DiffReport) {
+    let show = |v: &Option<String>| v.clone().unwrap_or_default();
+    for change in &report.metadata {
+        let name = match &change.variable {
+            Some(var) => format!("{}:{}", var, change.name),
+            None => change.name.clone(),
+        };
+        match change.change {
+            ChangeKind::Added => println!("+ {} {} = {}", change.item, name, show(&change.new_value)),
+            ChangeKind::Removed => println!("- {} {} = {}", change.item, name, show(&change.old_value)),
+            ChangeKind::Changed => println!(
+                "~ {} {}: {} -> {}",
+                change.item,
+                name,
+                show(&change.old_value),
+                show(&change.new_value)
+            ),
+        }
+    }
+
+    let format = |v: Option<f64>| v.map_or("-".to_string(), |x| x.to_string());
+    for diff in report.variables.iter().filter(|d| d.differing > 0) {
+        let first = diff.first_difference.as_ref().map(|i| format!("{:?}", i)).unwrap_or_default();
+        println!(
+            "{}: {} of {} values differ (first at {}), max abs {}, max rel {}, rmse {}",
+            diff.var_name,
+            diff.differing,
+            diff.compared,
+            first,
+            format(diff.max_abs_diff),
+            format(diff.max_rel_diff),
+            format(diff.rmse)
+        );
+    }
+    for skipped in &report.skipped {
+        println!("{}: not compared ({})", skipped.var_name, skipped.reason);
+    }
+    if report.identical {
+        println!("Files are identical within tolerance");
+    }
+}
+
+fn print_summary(summary: &ExportSummary) {
+    println!("Wrote {} ({} records)", summary.output_path, summary.records);
+    if !summary.skipped.is_empty() {
+        eprintln!("Skipped: {}", summary.skipped.join(", "));
+    }
+    for warning in &summary.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+/// GeoTIFF export of the slice picked by `selection`: a count of 1 fixes a
+/// dimension's index, a larger count makes its steps the bands
+fn geotiff_request(output: &str, var_name: &str, selection: &Selection) -> Result<GeoTiffExportRequest, NetCDFError> {
+    let mut indices = HashMap::new();
+    let mut bands = None;
+    for (dim, slice) in &selection.slices {
+        if slice.count == 1 {
+            indices.insert(dim.clone(), slice.start);
+            continue;
+        }
+        if let Some(BandSelection { dimension, .. }) = &bands {
+            return Err(NetCDFError::ExportError(format!(
+                "GeoTIFF bands can only step along one dimension, not both {} and {}; give the others a count of 1",
+                dimension, dim
+            )));
+        }
+        bands = Some(BandSelection { dimension: dim.clone(), start: slice.start, count: slice.count });
+    }
+    Ok(GeoTiffExportRequest { output_path: output.to_string(), var_name: var_name.to_string(), indices, bands })
+}
+
+fn parse_grid(arg: &str) -> Result<RegridTarget, NetCDFError> {
+    let invalid = || {
+        NetCDFError::InvalidSubsetRequest(format!(
+            "Invalid grid '{}', expected SOUTH,WEST,NORTH,EAST,STEP[,LON_STEP]",
+            arg
+        ))
+    };
+    let numbers: Vec<f64> = arg
+        .split(',')
+        .map(|part| part.trim().parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    let (lat_step, lon_step) = match numbers[..] {
+        [_, _, _, _, step] => (step, step),
+        [_, _, _, _, lat_step, lon_step] => (lat_step, lon_step),
+        _ => return Err(invalid()),
+    };
+    let extent = GeoExtent { south: numbers[0], west: numbers[1], north: numbers[2], east: numbers[3] };
+    Ok(RegridTarget::Regular { extent, lat_step, lon_step })
+}
+
+fn parse_slice(arg: &str) -> Result<(String, DimensionSlice), String> {
+    let (dim, range) = arg.split_once('=').ok_or("expected DIM=START:COUNT")?;
+    let (start, count) = range.split_once(':').ok_or("expected DIM=START:COUNT")?;
+    let slice = DimensionSlice {
+        start: start.parse().map_err(|_| format!("invalid start '{}'", start))?,
+        count: count.parse().map_err(|_| format!("invalid count '{}'", count))?,
+    };
+    Ok((dim.to_string(), slice))
+}
+
+fn parse_bbox(arg: &str) -> Result<GeoExtent, String> {
+    let values: Vec<f64> = arg
+        .split(',')
+        .map(|v| v.trim().parse().map_err(|_| format!("invalid coordinate '{}'", v)))
+        .collect::<Result<_, _>>()?;
+    let [south, west, north, east] = values[..] else {
+        return Err("expected SOUTH,WEST,NORTH,EAST".to_string());
+    };
+    Ok(GeoExtent { south, west, north, east })
+}
+
+fn parse_attribute(arg: &str) -> Result<(String, String), String> {
+    let (name, text) = arg.split_once('=').ok_or("expected NAME=TEXT")?;
+    Ok((name.to_string(), text.to_string()))
+}
+
+fn parse_derived(arg: &str) -> Result<(String, String), String> {
+    let (name, expression) = arg.split_once('=').ok_or("expected NAME=EXPRESSION")?;
+    Ok((name.trim().to_string(), expression.to_string()))
+}
+
+fn parse_index(arg: &str) -> Result<(String, usize), String> {
+    let (dim, index) = arg.split_once('=').ok_or("expected DIM=INDEX")?;
+    let index = index.parse().map_err(|_| format!("invalid index '{}'", index))?;
+    Ok((dim.to_string(), index))
+}
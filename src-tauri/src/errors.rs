@@ -23,11 +23,29 @@ pub enum NetCDFError {
     #[error("NetCDF library error: {0}")]
     NetCDFLibError(String),
 
+    #[error("HDF5 library error: {0}")]
+    Hdf5LibError(String),
+
     #[error("Data conversion error: {0}")]
     ConversionError(String),
 
     #[error("Invalid subset request: {0}")]
     InvalidSubsetRequest(String),
+
+    #[error("Rendering error: {0}")]
+    RenderError(String),
+
+    #[error("Export error: {0}")]
+    ExportError(String),
+
+    #[error("Catalog error: {0}")]
+    CatalogError(String),
+
+    #[error("Unit error: {0}")]
+    UnitError(String),
+
+    #[error("Expression error: {0}")]
+    ExpressionError(String),
 }
 
 impl From<netcdf::Error> for NetCDFError {
@@ -36,6 +54,18 @@ impl From<netcdf::Error> for NetCDFError {
     }
 }
 
+impl From<hdf5::Error> for NetCDFError {
+    fn from(err: hdf5::Error) -> Self {
+        NetCDFError::Hdf5LibError(err.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for NetCDFError {
+    fn from(err: rusqlite::Error) -> Self {
+        NetCDFError::CatalogError(err.to_string())
+    }
+}
+
 // Make the error Serialize-able for Tauri
 impl serde::Serialize for NetCDFError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
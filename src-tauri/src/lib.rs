@@ -1,30 +1,36 @@
-// Modules
-mod errors;
-mod models;
-mod netcdf;
+// Modules (public so the `ncv` command-line tool can share them)
+pub mod errors;
+pub mod models;
+pub mod netcdf;
+pub mod server;
 
 use errors::NetCDFError;
-use models::{FileMetadata, VariableDataResponse};
+use models::{
+    CatalogEntry, CatalogQuery, CatalogScanSummary, CdlDumpOptions, CdlGenerateRequest, CfReport,
+    DataPoint, DerivedVariableDefinition, DiffOptions, DiffReport, DimensionSlice, ExportSummary,
+    FileMetadata, GeoTiffExportRequest, NetCDFExportRequest, RegridExportRequest, RegridOptions,
+    RegionExportRequest, RegionFeature, RegionSelection, RegionSeries, RegionSeriesRequest,
+    RenderOptions, RenderedImage, Station, StationExportRequest, StationExtraction, StationRequest,
+    TableExportRequest, TimeSeriesRequest, VariableDataResponse, VariableStats,
+};
+use netcdf::watch::{OpenFiles, Watcher};
 use std::collections::HashMap;
 use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
 
-// Global state to cache opened files
+// Global state to track opened files
 struct AppState {
-    open_files: Mutex<HashMap<String, ()>>, // For now, just track paths
+    open_files: OpenFiles,
+    watchers: Mutex<HashMap<String, Watcher>>,
 }
 
 // Tauri commands
 
 /// Open a NetCDF file and return metadata
 #[tauri::command]
-fn open_netcdf_file(path: String) -> Result<FileMetadata, NetCDFError> {
-    let mut metadata = netcdf::open_netcdf(&path)?;
-
-    // Detect coordinates
-    let coords = netcdf::detect_coordinates(&metadata);
-    metadata.coordinates = Some(coords);
-
-    Ok(metadata)
+fn open_netcdf_file(path: String, state: tauri::State<AppState>) -> Result<FileMetadata, NetCDFError> {
+    state.open_files.open(&path)
 }
 
 /// Get all data for a variable
@@ -32,8 +38,11 @@ fn open_netcdf_file(path: String) -> Result<FileMetadata, NetCDFError> {
 fn get_variable_data(
     path: String,
     var_name: String,
+    units: Option<String>,
 ) -> Result<VariableDataResponse, NetCDFError> {
-    netcdf::get_variable_data(&path, &var_name)
+    netcdf::read_in_units(&path, &var_name, units.as_deref(), |dataset| {
+        netcdf::read_variable_data(dataset, &var_name)
+    })
 }
 
 /// Get a subset of variable data
@@ -43,15 +52,267 @@ fn get_variable_subset(
     var_name: String,
     start: Vec<usize>,
     count: Vec<usize>,
+    units: Option<String>,
 ) -> Result<VariableDataResponse, NetCDFError> {
-    netcdf::get_variable_subset(&path, &var_name, &start, &count)
+    netcdf::read_in_units(&path, &var_name, units.as_deref(), |dataset| {
+        netcdf::read_variable_subset(dataset, &var_name, &start, &count)
+    })
+}
+
+/// Compute summary statistics of a variable or hyperslab, optionally over a polygon region
+#[tauri::command]
+fn get_variable_stats(
+    path: String,
+    var_name: String,
+    slices: HashMap<String, DimensionSlice>,
+    units: Option<String>,
+    region: Option<RegionSelection>,
+) -> Result<VariableStats, NetCDFError> {
+    netcdf::read_in_units(&path, &var_name, units.as_deref(), |dataset| match &region {
+        Some(region) => netcdf::read_region_stats(dataset, &var_name, &slices, region),
+        None => netcdf::read_stats(dataset, &var_name, &slices),
+    })
+}
+
+/// Compare the metadata and data of two files
+#[tauri::command]
+fn diff_files(path_a: String, path_b: String, options: DiffOptions) -> Result<DiffReport, NetCDFError> {
+    netcdf::diff_files(&path_a, &path_b, &options)
+}
+
+/// Check a file's metadata against the CF conventions
+#[tauri::command]
+fn check_cf(path: String) -> Result<CfReport, NetCDFError> {
+    netcdf::check_cf(&path)
+}
+
+/// Scan a directory recursively into the file catalog, on a worker thread
+/// since large trees take minutes
+#[tauri::command]
+async fn scan_catalog(root: String, index_path: Option<String>) -> Result<CatalogScanSummary, NetCDFError> {
+    let index_path = index_path.unwrap_or_else(netcdf::catalog::default_index_path);
+    tauri::async_runtime::spawn_blocking(move || netcdf::scan_directory(&root, &index_path))
+        .await
+        .map_err(|e| NetCDFError::CatalogError(format!("Catalog scan failed: {}", e)))?
+}
+
+/// Find catalogued files by variable, time coverage, area and attributes
+#[tauri::command]
+fn query_catalog(index_path: Option<String>, query: CatalogQuery) -> Result<Vec<CatalogEntry>, NetCDFError> {
+    let index_path = index_path.unwrap_or_else(netcdf::catalog::default_index_path);
+    netcdf::query_catalog(&index_path, &query)
+}
+
+/// Extract the time series of a variable at one grid point
+#[tauri::command]
+fn get_timeseries(
+    path: String,
+    request: TimeSeriesRequest,
+    units: Option<String>,
+) -> Result<Vec<DataPoint>, NetCDFError> {
+    netcdf::read_in_units(&path, &request.var_name, units.as_deref(), |dataset| {
+        netcdf::read_timeseries(dataset, &request)
+    })
+}
+
+/// Extract the time series of a variable at a list of stations
+#[tauri::command]
+fn extract_stations(path: String, request: StationRequest) -> Result<StationExtraction, NetCDFError> {
+    netcdf::extract_stations(&path, &request)
+}
+
+/// Read a station list (id, lat, lon, elevation) from a CSV file
+#[tauri::command]
+fn read_station_file(path: String) -> Result<Vec<Station>, NetCDFError> {
+    netcdf::stations::read_stations(&path)
+}
+
+/// Parse a station list typed or pasted as CSV text
+#[tauri::command]
+fn parse_station_list(text: String) -> Result<Vec<Station>, NetCDFError> {
+    netcdf::stations::parse_stations(&text)
+}
+
+/// Names and extents of the polygon features in a GeoJSON file or shapefile
+#[tauri::command]
+fn list_regions(path: String) -> Result<Vec<RegionFeature>, NetCDFError> {
+    netcdf::polygons::list_regions(&path)
+}
+
+/// Area-weighted statistics of a variable over a polygon region per time step
+#[tauri::command]
+fn region_series(path: String, request: RegionSeriesRequest) -> Result<RegionSeries, NetCDFError> {
+    netcdf::region_series(&path, &request)
+}
+
+/// Add a variable computed from an expression and return the updated metadata
+#[tauri::command]
+fn define_derived_variable(
+    path: String,
+    definition: DerivedVariableDefinition,
+) -> Result<FileMetadata, NetCDFError> {
+    let mut metadata = netcdf::derived::define_variable(&path, definition)?;
+    metadata.coordinates = Some(netcdf::detect_coordinates(&metadata));
+    Ok(metadata)
+}
+
+/// Remove a derived variable and return the updated metadata
+#[tauri::command]
+fn remove_derived_variable(path: String, name: String) -> Result<FileMetadata, NetCDFError> {
+    let mut metadata = netcdf::derived::remove_variable(&path, &name)?;
+    metadata.coordinates = Some(netcdf::detect_coordinates(&metadata));
+    Ok(metadata)
+}
+
+/// Convert a value between two units, e.g. to preview a conversion
+#[tauri::command]
+fn convert_units(value: f64, from: String, to: String) -> Result<f64, NetCDFError> {
+    Ok(netcdf::units::conversion(&from, &to)?.apply(value))
+}
+
+/// Render a 2-D lat/lon slice of a variable as a colormapped PNG
+#[tauri::command]
+fn render_variable_slice(
+    path: String,
+    var_name: String,
+    indices: HashMap<String, usize>,
+    options: RenderOptions,
+    regrid: Option<RegridOptions>,
+) -> Result<RenderedImage, NetCDFError> {
+    netcdf::render_slice(&path, &var_name, &indices, &options, regrid.as_ref())
+}
+
+/// List the colormaps available for rendering
+#[tauri::command]
+fn list_colormaps() -> Vec<String> {
+    netcdf::colormap::COLORMAP_NAMES
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Write selected variables and hyperslabs to a new NetCDF file
+#[tauri::command]
+fn export_netcdf(
+    path: String,
+    request: NetCDFExportRequest,
+) -> Result<ExportSummary, NetCDFError> {
+    netcdf::export_netcdf(&path, &request)
+}
+
+/// Write variables regridded onto another lat/lon grid to a new NetCDF file
+#[tauri::command]
+fn export_regridded(
+    path: String,
+    request: RegridExportRequest,
+) -> Result<ExportSummary, NetCDFError> {
+    netcdf::export_regridded(&path, &request)
+}
+
+/// Stream variables to a long-form CSV/TSV table with coordinate columns
+#[tauri::command]
+fn export_csv(
+    path: String,
+    request: TableExportRequest,
+) -> Result<ExportSummary, NetCDFError> {
+    netcdf::export_csv(&path, &request)
+}
+
+/// Write variables to a long-form Parquet file
+#[tauri::command]
+fn export_parquet(
+    path: String,
+    request: TableExportRequest,
+) -> Result<ExportSummary, NetCDFError> {
+    netcdf::export_parquet(&path, &request)
+}
+
+/// Write variables to a long-form Arrow IPC file
+#[tauri::command]
+fn export_arrow(
+    path: String,
+    request: TableExportRequest,
+) -> Result<ExportSummary, NetCDFError> {
+    netcdf::export_arrow(&path, &request)
+}
+
+/// Write a 2-D slice (optionally several bands) to a GeoTIFF
+#[tauri::command]
+fn export_geotiff(
+    path: String,
+    request: GeoTiffExportRequest,
+) -> Result<ExportSummary, NetCDFError> {
+    netcdf::export_geotiff(&path, &request)
+}
+
+/// Write station time series to a CSV table
+#[tauri::command]
+fn export_stations(
+    path: String,
+    request: StationExportRequest,
+) -> Result<ExportSummary, NetCDFError> {
+    netcdf::export_stations(&path, &request)
+}
+
+/// Write regional statistics over time to a CSV table
+#[tauri::command]
+fn export_region_series(
+    path: String,
+    request: RegionExportRequest,
+) -> Result<ExportSummary, NetCDFError> {
+    netcdf::export_region_series(&path, &request)
+}
+
+/// Render a file's structure (and optionally data) as CDL text, like ncdump
+#[tauri::command]
+fn dump_cdl(path: String, options: CdlDumpOptions) -> Result<String, NetCDFError> {
+    netcdf::dump_cdl(&path, &options)
+}
+
+/// Parse CDL text into the metadata of the file it describes
+#[tauri::command]
+fn cdl_metadata(cdl: String) -> Result<FileMetadata, NetCDFError> {
+    netcdf::cdl_metadata(&cdl)
+}
+
+/// Create a NetCDF file from CDL text, like ncgen
+#[tauri::command]
+fn generate_netcdf(cdl: String, request: CdlGenerateRequest) -> Result<ExportSummary, NetCDFError> {
+    netcdf::generate_netcdf(&cdl, &request)
+}
+
+/// Watch a file or directory and emit `dataset-changed` events as it changes
+#[tauri::command]
+fn watch_path(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    path: String,
+    interval_ms: Option<u64>,
+) -> Result<(), NetCDFError> {
+    let interval = interval_ms.map_or(netcdf::watch::DEFAULT_INTERVAL, Duration::from_millis);
+    let watcher = Watcher::start(&path, interval, move |event| {
+        app.state::<AppState>().open_files.refresh(&event);
+        let _ = app.emit("dataset-changed", event);
+    })?;
+    state.watchers.lock().unwrap().insert(path, watcher);
+    Ok(())
+}
+
+/// Stop watching a file or directory
+#[tauri::command]
+fn unwatch_path(path: String, state: tauri::State<AppState>) -> Result<(), String> {
+    state.watchers.lock().unwrap().remove(&path);
+    Ok(())
 }
 
 /// Close a NetCDF file (for cleanup)
 #[tauri::command]
 fn close_netcdf_file(path: String, state: tauri::State<AppState>) -> Result<(), String> {
-    let mut files = state.open_files.lock().unwrap();
-    files.remove(&path);
+    state.open_files.close(&path);
+    // Dropping a watcher joins its thread, whose callback may be waiting for
+    // `open_files`, so its lock must not be held here
+    let watcher = state.watchers.lock().unwrap().remove(&path);
+    drop(watcher);
     Ok(())
 }
 
@@ -62,12 +323,42 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .manage(AppState {
-            open_files: Mutex::new(HashMap::new()),
+            open_files: OpenFiles::default(),
+            watchers: Mutex::new(HashMap::new()),
         })
         .invoke_handler(tauri::generate_handler![
             open_netcdf_file,
             get_variable_data,
             get_variable_subset,
+            get_variable_stats,
+            diff_files,
+            check_cf,
+            scan_catalog,
+            query_catalog,
+            get_timeseries,
+            extract_stations,
+            read_station_file,
+            parse_station_list,
+            list_regions,
+            region_series,
+            convert_units,
+            define_derived_variable,
+            remove_derived_variable,
+            render_variable_slice,
+            list_colormaps,
+            export_netcdf,
+            export_regridded,
+            export_csv,
+            export_parquet,
+            export_arrow,
+            export_geotiff,
+            export_stations,
+            export_region_series,
+            dump_cdl,
+            cdl_metadata,
+            generate_netcdf,
+            watch_path,
+            unwatch_path,
             close_netcdf_file,
         ])
         .run(tauri::generate_context!())
@@ -99,3 +99,613 @@ pub struct VariableDataResponse {
     /// Number of missing/fill values (only applicable for numeric data)
     pub missing_count: usize,
 }
+
+/// Geographic bounding box in degrees
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoExtent {
+    /// Southern edge (minimum latitude)
+    pub south: f64,
+    /// Western edge (minimum longitude)
+    pub west: f64,
+    /// Northern edge (maximum latitude)
+    pub north: f64,
+    /// Eastern edge (maximum longitude)
+    pub east: f64,
+}
+
+/// Options for rendering a 2-D slice to an image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderOptions {
+    /// Colormap name (e.g., "viridis", "RdBu"; append "_r" to reverse)
+    pub colormap: String,
+    /// Value mapped to the low end of the colormap (defaults to the data minimum)
+    pub vmin: Option<f64>,
+    /// Value mapped to the high end of the colormap (defaults to the data maximum)
+    pub vmax: Option<f64>,
+    /// RGBA colour for missing values (defaults to fully transparent)
+    pub nan_color: Option<[u8; 4]>,
+}
+
+/// A colormapped PNG rendering of a 2-D slice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedImage {
+    /// Variable name
+    pub var_name: String,
+    /// Base64-encoded PNG data
+    pub png_base64: String,
+    /// Image width in pixels
+    pub width: usize,
+    /// Image height in pixels
+    pub height: usize,
+    /// Geographic extent covered by the image
+    pub extent: GeoExtent,
+    /// Value mapped to the low end of the colormap
+    pub vmin: f64,
+    /// Value mapped to the high end of the colormap
+    pub vmax: f64,
+    /// Evenly spaced legend colours (CSS hex) from vmin to vmax
+    pub legend: Vec<String>,
+}
+
+/// Start/count selection along a single dimension
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DimensionSlice {
+    /// First index to include
+    pub start: usize,
+    /// Number of elements to include
+    pub count: usize,
+}
+
+/// On-disk format for NetCDF export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetCDFFormat {
+    /// NetCDF-4 (HDF5-based)
+    Netcdf4,
+    /// NetCDF classic data model with 64-bit offsets
+    Classic,
+}
+
+/// Request for writing a subset of a file to a new NetCDF file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetCDFExportRequest {
+    /// Path of the file to create
+    pub output_path: String,
+    /// Variables to export (coordinate variables are added automatically)
+    pub variables: Vec<String>,
+    /// Hyperslab per dimension name; dimensions not listed are exported whole
+    pub slices: HashMap<String, DimensionSlice>,
+    /// Output format
+    pub format: NetCDFFormat,
+    /// Deflate level (0-9) for NetCDF-4 output
+    pub deflate_level: Option<i32>,
+}
+
+/// Request for creating a NetCDF file from CDL text (like `ncgen`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CdlGenerateRequest {
+    /// Path of the file to create
+    pub output_path: String,
+    /// Output format; taken from the `_Format` attribute when unset (NetCDF-4 by default)
+    pub format: Option<NetCDFFormat>,
+}
+
+/// Summary of a completed export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSummary {
+    /// Path of the written file
+    pub output_path: String,
+    /// Variables that were written
+    pub variables: Vec<String>,
+    /// Variables that were requested but could not be written
+    pub skipped: Vec<String>,
+    /// Number of values (or table rows) written
+    pub records: usize,
+    /// What the output does not show as asked, such as times left undecoded
+    pub warnings: Vec<String>,
+}
+
+/// Request for writing variables as a long-form table (one row per grid point)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableExportRequest {
+    /// Path of the file to create
+    pub output_path: String,
+    /// Variables to export; all must share the same dimensions
+    pub variables: Vec<String>,
+    /// Hyperslab per dimension name; dimensions not listed are exported whole
+    pub slices: HashMap<String, DimensionSlice>,
+    /// Decode CF time coordinates to dates instead of raw offsets
+    pub decode_times: bool,
+    /// Field delimiter for CSV output (defaults to ','; use '\t' for TSV)
+    pub delimiter: Option<char>,
+    /// Text written for missing values in CSV output (defaults to empty)
+    pub missing_value: Option<String>,
+}
+
+/// Steps of a dimension written as separate raster bands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandSelection {
+    /// Dimension to step through (e.g., "time")
+    pub dimension: String,
+    /// First index along the dimension
+    pub start: usize,
+    /// Number of bands to write
+    pub count: usize,
+}
+
+/// Request for writing a 2-D slice as a georeferenced GeoTIFF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoTiffExportRequest {
+    /// Path of the file to create
+    pub output_path: String,
+    /// Variable to export
+    pub var_name: String,
+    /// Index along each non-spatial dimension (keyed by name, defaults to 0)
+    pub indices: HashMap<String, usize>,
+    /// Optional dimension whose steps become bands
+    pub bands: Option<BandSelection>,
+}
+
+/// Options for rendering a file as CDL text (like `ncdump`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CdlDumpOptions {
+    /// Variables whose data to include (like `ncdump -v`); empty for the header only
+    pub data_variables: Vec<String>,
+    /// Maximum number of values shown per variable (all when unset)
+    pub max_values: Option<usize>,
+}
+
+/// Summary statistics of a numeric variable (fill values excluded)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariableStats {
+    /// Variable name
+    pub var_name: String,
+    /// Number of valid values
+    pub count: usize,
+    /// Number of fill/NaN values
+    pub missing_count: usize,
+    /// Smallest valid value
+    pub min: Option<f64>,
+    /// Largest valid value
+    pub max: Option<f64>,
+    /// Mean of the valid values
+    pub mean: Option<f64>,
+    /// Population standard deviation of the valid values
+    pub std_dev: Option<f64>,
+}
+
+/// Tolerances and scope of a comparison between two files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiffOptions {
+    /// Absolute difference up to which values count as equal
+    #[serde(default)]
+    pub abs_tolerance: f64,
+    /// Difference relative to the larger magnitude up to which values count as equal
+    #[serde(default)]
+    pub rel_tolerance: f64,
+    /// Variables whose data is compared (all shared variables if empty)
+    #[serde(default)]
+    pub variables: Vec<String>,
+    /// Compare dimensions, variables and attributes only
+    #[serde(default)]
+    pub metadata_only: bool,
+    /// Convert the second file's values to the first file's units where the
+    /// `units` attributes differ; variables with incompatible units are skipped
+    #[serde(default)]
+    pub convert_units: bool,
+}
+
+/// How an item differs between the two files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Only in the second file
+    Added,
+    /// Only in the first file
+    Removed,
+    /// In both files with different values
+    Changed,
+}
+
+/// One structural difference between two files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataChange {
+    /// What differs: `dimension`, `variable`, `attribute`, `data_type` or `dimensions`
+    pub item: String,
+    /// Name of the dimension, variable or attribute
+    pub name: String,
+    /// Variable the attribute belongs to; `None` for global attributes and other items
+    pub variable: Option<String>,
+    pub change: ChangeKind,
+    /// Value in the first file
+    pub old_value: Option<String>,
+    /// Value in the second file
+    pub new_value: Option<String>,
+}
+
+/// Element-wise comparison of a variable present in both files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariableDiff {
+    /// Variable name
+    pub var_name: String,
+    /// Number of elements compared
+    pub compared: usize,
+    /// Elements outside the tolerances, including values missing in only one file
+    pub differing: usize,
+    /// Largest absolute difference between valid values
+    pub max_abs_diff: Option<f64>,
+    /// Largest difference relative to the larger magnitude
+    pub max_rel_diff: Option<f64>,
+    /// Root-mean-square difference between valid values
+    pub rmse: Option<f64>,
+    /// Index of the first differing element
+    pub first_difference: Option<Vec<usize>>,
+}
+
+/// Shared variable whose data was not compared
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedVariable {
+    pub var_name: String,
+    /// Why the data could not be compared, e.g. "shapes differ: [4] vs [5]"
+    pub reason: String,
+}
+
+/// Result of comparing two files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffReport {
+    pub file_a: String,
+    pub file_b: String,
+    /// Added, removed and changed dimensions, variables, attributes and types
+    pub metadata: Vec<MetadataChange>,
+    /// Data comparison of the shared variables
+    pub variables: Vec<VariableDiff>,
+    /// Shared variables whose data could not be compared, with the reason
+    pub skipped: Vec<SkippedVariable>,
+    /// Whether the files are equal within the tolerances
+    pub identical: bool,
+}
+
+/// Request for the time series of a variable at one grid point
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimeSeriesRequest {
+    /// Variable to extract
+    pub var_name: String,
+    /// Index along each non-time dimension (keyed by name, defaults to 0)
+    pub indices: HashMap<String, usize>,
+    /// Latitude of the point; selects the nearest grid cell together with `lon`
+    pub lat: Option<f64>,
+    /// Longitude of the point
+    pub lon: Option<f64>,
+}
+
+/// Outcome of scanning a directory into the catalog index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogScanSummary {
+    /// Directory that was scanned
+    pub root: String,
+    /// Index file the results were written to
+    pub index_path: String,
+    /// Files read and (re)indexed
+    pub indexed: usize,
+    /// Files skipped because their size and modification time are unchanged
+    pub unchanged: usize,
+    /// Index entries dropped because the file no longer exists
+    pub removed: usize,
+    /// Files that could not be opened, as "path: error"
+    pub failed: Vec<String>,
+}
+
+/// Filters of a catalog query; every filter given must match
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogQuery {
+    /// Variable name or `standard_name` the file must contain
+    #[serde(default)]
+    pub variable: Option<String>,
+    /// Start of the period the file's time coverage must overlap (ISO 8601)
+    #[serde(default)]
+    pub time_start: Option<String>,
+    /// End of the period; a date without a time includes that whole day
+    #[serde(default)]
+    pub time_end: Option<String>,
+    /// Area the file's bounding box must intersect
+    #[serde(default)]
+    pub extent: Option<GeoExtent>,
+    /// Global attributes whose values must contain the given text (case-insensitive)
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+}
+
+/// A variable recorded in the catalog
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogVariable {
+    pub name: String,
+    pub standard_name: Option<String>,
+    pub long_name: Option<String>,
+    pub units: Option<String>,
+    pub dimensions: Vec<String>,
+}
+
+/// A file matching a catalog query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    /// Absolute path of the file
+    pub path: String,
+    /// First time value (ISO 8601)
+    pub time_start: Option<String>,
+    /// Last time value (ISO 8601)
+    pub time_end: Option<String>,
+    /// Latitude/longitude bounding box, longitudes in -180..180 where possible
+    pub extent: Option<GeoExtent>,
+    pub variables: Vec<CatalogVariable>,
+    pub global_attrs: HashMap<String, String>,
+}
+
+/// A file or directory offered to remote clients by the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryEntry {
+    /// File name
+    pub name: String,
+    /// Path relative to the served root, with '/' separators
+    pub path: String,
+    /// Whether the entry is a directory (Zarr stores are directories too)
+    pub is_dir: bool,
+    /// Size in bytes (0 for directories)
+    pub size: u64,
+}
+
+/// What happened to a watched dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchChange {
+    /// A new file appeared in a watched directory
+    Created,
+    /// The file was written to (records appended or values rewritten)
+    Changed,
+    /// The file disappeared
+    Removed,
+}
+
+/// Change to a watched dataset, emitted as the `dataset-changed` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchEvent {
+    /// Path of the dataset that changed
+    pub path: String,
+    pub change: WatchChange,
+    /// Dimensions whose size changed, with their new sizes (all dimensions for new files)
+    pub dimensions: Vec<Dimension>,
+}
+
+/// How serious a CF compliance finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CfSeverity {
+    /// Violates a "must" of the conventions
+    Error,
+    /// Violates a "should", or could not be verified
+    Warning,
+}
+
+/// One finding of the CF compliance checker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CfIssue {
+    pub severity: CfSeverity,
+    /// Section of the CF conventions document, e.g. "3.3"
+    pub section: String,
+    /// Variable the finding is about; `None` for global attributes
+    pub variable: Option<String>,
+    pub message: String,
+}
+
+/// Result of checking a file against the CF conventions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CfReport {
+    pub file_path: String,
+    /// Value of the global `Conventions` attribute
+    pub conventions: Option<String>,
+    /// Standard name table the names were checked against
+    pub standard_name_table: String,
+    pub issues: Vec<CfIssue>,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+/// A virtual variable computed from other variables of a dataset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DerivedVariableDefinition {
+    /// Name the variable appears under
+    pub name: String,
+    /// Expression over other variables, e.g. "sqrt(u10**2 + v10**2)"
+    pub expression: String,
+    /// Units of the result, stored as its `units` attribute
+    #[serde(default)]
+    pub units: Option<String>,
+    /// Stored as the `long_name` attribute
+    #[serde(default)]
+    pub long_name: Option<String>,
+}
+
+/// Interpolation method for regridding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegridMethod {
+    /// Value of the source cell containing the target cell centre
+    Nearest,
+    /// Bilinear interpolation between the four surrounding source cell centres
+    Bilinear,
+    /// Area-weighted average of the source cells overlapping the target cell
+    Conservative,
+}
+
+/// Grid to regrid onto
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RegridTarget {
+    /// Regular grid covering `extent` with cells of the given size in degrees
+    Regular { extent: GeoExtent, lat_step: f64, lon_step: f64 },
+    /// The latitude/longitude grid of another file
+    File { path: String },
+}
+
+/// Target grid and method for regridding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegridOptions {
+    pub target: RegridTarget,
+    pub method: RegridMethod,
+}
+
+/// Request for writing variables regridded onto another grid to a new NetCDF file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegridExportRequest {
+    /// Path of the file to create
+    pub output_path: String,
+    /// Variables to regrid; each must have latitude/longitude dimensions
+    pub variables: Vec<String>,
+    pub regrid: RegridOptions,
+    /// Hyperslab per non-spatial dimension name; dimensions not listed are exported whole
+    #[serde(default)]
+    pub slices: HashMap<String, DimensionSlice>,
+    /// Output format
+    pub format: NetCDFFormat,
+}
+
+/// A point to extract values at, such as a weather station
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Station {
+    pub id: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// Station elevation in metres, carried through to the results
+    #[serde(default)]
+    pub elevation: Option<f64>,
+}
+
+/// How values at a point are taken from the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PointMethod {
+    /// Value of the nearest grid cell
+    Nearest,
+    /// Bilinear interpolation between the four surrounding grid cells
+    Bilinear,
+}
+
+/// Request for time series at a list of stations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationRequest {
+    pub var_name: String,
+    pub stations: Vec<Station>,
+    pub method: PointMethod,
+    /// Index along dimensions other than time and lat/lon (keyed by name, defaults to 0)
+    #[serde(default)]
+    pub indices: HashMap<String, usize>,
+    /// Replace missing values by the nearest valid cell within this distance (km)
+    #[serde(default)]
+    pub search_radius_km: Option<f64>,
+}
+
+/// Where a station fell on the grid
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationMatch {
+    pub id: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub elevation: Option<f64>,
+    /// Latitude of the nearest grid cell
+    pub grid_lat: f64,
+    /// Longitude of the nearest grid cell
+    pub grid_lon: f64,
+    /// Distance from the station to the nearest grid cell centre in km
+    pub distance_km: f64,
+}
+
+/// One value of a station time series
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationRecord {
+    pub station_id: String,
+    pub time: String,
+    pub value: f64,
+    /// Whether the value came from a neighbour because the station's cells were missing
+    pub from_neighbour: bool,
+}
+
+/// Time series of a variable at a list of stations, one record per station and time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationExtraction {
+    pub var_name: String,
+    pub method: PointMethod,
+    pub stations: Vec<StationMatch>,
+    pub records: Vec<StationRecord>,
+}
+
+/// Request to write station time series to a CSV table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationExportRequest {
+    pub output_path: String,
+    pub extraction: StationRequest,
+}
+
+/// Polygons from a GeoJSON file or shapefile used to mask a variable's grid
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionSelection {
+    /// GeoJSON (`.geojson`/`.json`) or shapefile (`.shp`) path
+    pub path: String,
+    /// Names (or 0-based positions) of the features to use; all features when empty
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Weight cells by the fraction of their area inside the polygons,
+    /// rather than counting cells whose centre is inside
+    #[serde(default)]
+    pub fractional: bool,
+}
+
+/// One polygon feature of a region file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionFeature {
+    pub index: usize,
+    pub name: String,
+    pub extent: GeoExtent,
+}
+
+/// Request for the area-weighted mean of a variable over a region per time step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionSeriesRequest {
+    pub var_name: String,
+    pub region: RegionSelection,
+    /// Index along dimensions other than time and lat/lon (keyed by name, defaults to 0)
+    #[serde(default)]
+    pub indices: HashMap<String, usize>,
+}
+
+/// Statistics of a variable over a region at one time step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionStep {
+    pub time: String,
+    /// Area-weighted mean of the valid cells
+    pub mean: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Area-weighted standard deviation of the valid cells
+    pub std_dev: Option<f64>,
+    /// Share of the region's area covered by valid (non-missing) cells
+    pub valid_fraction: f64,
+}
+
+/// Regional statistics of a variable over time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionSeries {
+    pub var_name: String,
+    /// Names of the features making up the region
+    pub features: Vec<String>,
+    /// Number of grid cells at least partly inside the region
+    pub cells: usize,
+    /// Area of the region as covered by the grid, in km²
+    pub area_km2: f64,
+    pub steps: Vec<RegionStep>,
+}
+
+/// Request to write regional statistics over time to a CSV table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionExportRequest {
+    pub output_path: String,
+    pub series: RegionSeriesRequest,
+}
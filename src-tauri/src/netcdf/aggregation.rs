@@ -0,0 +1,571 @@
+use crate::errors::NetCDFError;
+use crate::models::{FileMetadata, Variable};
+use super::backend::{open_dataset, DatasetBackend};
+use super::cdl::VARIABLE_TYPED_ATTRS;
+use super::coordinates::detect_coordinates;
+use super::data_access::{find_variable, is_text_type, NativeValues};
+use super::loader::{attribute_numbers, numbers_attribute};
+use super::time::{is_standard_calendar, CfTimeUnits};
+use netcdf::AttributeValue;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Whether a path stands for several files: a newline-separated list or a
+/// glob pattern such as `/data/run/out_*.nc`
+pub fn is_aggregation(path: &str) -> bool {
+    path.contains('\n')
+        || (!path.contains("://") && path.contains(['*', '?', '[']) && !Path::new(path).exists())
+}
+
+/// Member files of an aggregation path, in the order given
+fn member_paths(path: &str) -> Result<Vec<String>, NetCDFError> {
+    if path.contains('\n') {
+        return Ok(path
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect());
+    }
+
+    let entries = glob::glob(path)
+        .map_err(|e| NetCDFError::FileOpenError(format!("Invalid pattern {}: {}", path, e)))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| NetCDFError::FileOpenError(e.to_string()))?;
+        paths.push(entry.to_string_lossy().to_string());
+    }
+    Ok(paths)
+}
+
+/// How an aggregation joins its members
+#[derive(Debug, Clone, Default)]
+pub struct JoinOptions {
+    /// Dimension to join along; the unlimited or time dimension if `None`
+    pub dimension: Option<String>,
+    /// Keep the members in the given order instead of sorting them by time
+    pub keep_order: bool,
+}
+
+/// One file of an aggregation
+struct Member {
+    path: String,
+    dataset: Box<dyn DatasetBackend>,
+    /// Position of the member's first record along the aggregation dimension
+    offset: usize,
+    length: usize,
+    /// `value * scale + offset` converting this member's time values to the
+    /// time units of the first member, for variables whose units differ
+    time_rebase: HashMap<String, (f64, f64)>,
+}
+
+/// Several files concatenated along their unlimited (or time) dimension,
+/// read as one dataset.
+///
+/// All files must have the same dimensions, variables, types and fill
+/// values; only the length of the aggregation dimension may differ. Files
+/// are ordered by their first time value, and time values of files whose
+/// units use another epoch or unit are converted to the first file's units.
+pub struct AggregatedDataset {
+    metadata: FileMetadata,
+    members: Vec<Member>,
+    dimension: String,
+}
+
+impl AggregatedDataset {
+    /// Open a glob pattern or newline-separated list of files
+    pub fn open(path: &str) -> Result<Self, NetCDFError> {
+        let paths = member_paths(path)?;
+        if paths.is_empty() {
+            return Err(NetCDFError::FileOpenError(format!("No files match {}", path)));
+        }
+
+        let mut datasets = Vec::with_capacity(paths.len());
+        for member_path in paths {
+            if is_aggregation(&member_path) {
+                return Err(NetCDFError::FileOpenError(format!(
+                    "Aggregations cannot be nested: {}",
+                    member_path
+                )));
+            }
+            let dataset = open_dataset(&member_path)?;
+            datasets.push((member_path, dataset));
+        }
+        Self::join(datasets, path, &JoinOptions::default())
+    }
+
+    /// Join opened datasets, labelled by their paths in error messages;
+    /// `path` becomes the file path of the joined dataset
+    pub fn join(
+        datasets: Vec<(String, Box<dyn DatasetBackend>)>,
+        path: &str,
+        options: &JoinOptions,
+    ) -> Result<Self, NetCDFError> {
+        if datasets.is_empty() {
+            return Err(NetCDFError::FileOpenError(format!("No datasets to join in {}", path)));
+        }
+        let mut opened = Vec::with_capacity(datasets.len());
+        for (member_path, dataset) in datasets {
+            let metadata = dataset.metadata()?;
+            opened.push((member_path, dataset, metadata));
+        }
+
+        let first = &opened[0].2;
+        let dimension = match &options.dimension {
+            Some(name) if first.dimensions.iter().any(|d| &d.name == name) => name.clone(),
+            Some(name) => return Err(NetCDFError::DimensionNotFound(name.clone())),
+            None => aggregation_dimension(first)?,
+        };
+        let time_var = detect_coordinates(first)
+            .time_var
+            .filter(|name| find_variable(first, name).is_ok_and(|v| v.dimensions == [dimension.clone()]));
+
+        // Order the files by their first time value
+        if let (Some(time_name), false) = (&time_var, options.keep_order) {
+            let mut keyed = Vec::with_capacity(opened.len());
+            for (member_path, dataset, metadata) in opened {
+                let var = find_variable(&metadata, time_name)?;
+                let first_time = match var.shape[0] {
+                    0 => None,
+                    _ => dataset.read_masked(time_name, &[0], &[1])?.first().copied(),
+                };
+                let key = first_time.and_then(|t| time_units(&var, &metadata).and_then(|u| u.decode(t)));
+                keyed.push((key, (member_path, dataset, metadata)));
+            }
+            keyed.sort_by_key(|a| a.0);
+            opened = keyed.into_iter().map(|(_, member)| member).collect();
+        }
+
+        let reference = opened[0].2.clone();
+        let mut members: Vec<Member> = Vec::with_capacity(opened.len());
+        let mut offset = 0;
+        for (member_path, dataset, metadata) in opened {
+            check_consistent(&reference, &metadata, &dimension, &member_path)?;
+            let length = metadata
+                .dimensions
+                .iter()
+                .find(|d| d.name == dimension)
+                .map_or(0, |d| d.size);
+            let time_rebase = rebase_factors(&reference, &metadata, &dimension, &member_path)?;
+            if let Some(first) = members.first() {
+                for var in reference.variables.iter().filter(|v| !is_text_type(&v.data_type)) {
+                    if !same_fill(dataset.fill_value(&var.name), first.dataset.fill_value(&var.name)) {
+                        return Err(inconsistent(&member_path, &format!("variable '{}' has another _FillValue", var.name)));
+                    }
+                }
+            }
+            members.push(Member {
+                path: member_path,
+                dataset,
+                offset,
+                length,
+                time_rebase,
+            });
+            offset += length;
+        }
+
+        if let (Some(time_name), false) = (&time_var, options.keep_order) {
+            check_time_order(&members, time_name)?;
+        }
+
+        // The reference metadata with the aggregation dimension lengthened
+        let mut metadata = reference;
+        metadata.file_path = path.to_string();
+        for dim in metadata.dimensions.iter_mut().filter(|d| d.name == dimension) {
+            dim.size = offset;
+        }
+        for var in metadata.variables.iter_mut() {
+            if let Some(d) = var.dimensions.iter().position(|name| name == &dimension) {
+                var.shape[d] = offset;
+            }
+            if members.iter().any(|m| m.time_rebase.contains_key(&var.name)) {
+                var.data_type = "Basic(Double)".to_string();
+            }
+        }
+
+        Ok(AggregatedDataset {
+            metadata,
+            members,
+            dimension,
+        })
+    }
+
+    /// Read a hyperslab piece by piece from the members it spans, returning
+    /// the pieces and how to join them
+    fn read_pieces<T>(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+        read: impl Fn(&Member, &[usize], &[usize]) -> Result<T, NetCDFError>,
+    ) -> Result<(Vec<T>, Layout), NetCDFError> {
+        let var = find_variable(&self.metadata, var_name)?;
+        let Some(d) = var.dimensions.iter().position(|name| name == &self.dimension) else {
+            let layout = Layout { outer: 1, inner: 1, lengths: vec![1] };
+            return Ok((vec![read(&self.members[0], start, count)?], layout));
+        };
+        if start.len() != var.shape.len() || count.len() != var.shape.len() || start[d] + count[d] > var.shape[d] {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Hyperslab start={:?} count={:?} does not fit variable '{}' of shape {:?}",
+                start, count, var_name, var.shape
+            )));
+        }
+
+        let (lo, hi) = (start[d], start[d] + count[d]);
+        let mut pieces = Vec::new();
+        let mut lengths = Vec::new();
+        for member in &self.members {
+            let from = lo.max(member.offset);
+            let to = hi.min(member.offset + member.length);
+            if from >= to {
+                continue;
+            }
+            let mut local_start = start.to_vec();
+            let mut local_count = count.to_vec();
+            local_start[d] = from - member.offset;
+            local_count[d] = to - from;
+            pieces.push(read(member, &local_start, &local_count)?);
+            lengths.push(to - from);
+        }
+        let layout = Layout {
+            outer: count[..d].iter().product(),
+            inner: count[d + 1..].iter().product(),
+            lengths,
+        };
+        Ok((pieces, layout))
+    }
+}
+
+/// How pieces read from consecutive members interleave: each piece holds
+/// `outer` blocks of `length * inner` values
+pub(crate) struct Layout {
+    pub outer: usize,
+    pub inner: usize,
+    pub lengths: Vec<usize>,
+}
+
+impl Layout {
+    pub fn join<T: Clone>(&self, mut pieces: Vec<Vec<T>>) -> Vec<T> {
+        if pieces.len() == 1 {
+            return pieces.pop().unwrap_or_default();
+        }
+        if self.outer == 1 {
+            return pieces.concat();
+        }
+        let total: usize = pieces.iter().map(Vec::len).sum();
+        let mut out = Vec::with_capacity(total);
+        for o in 0..self.outer {
+            for (piece, &length) in pieces.iter().zip(&self.lengths) {
+                let block = length * self.inner;
+                out.extend_from_slice(&piece[o * block..(o + 1) * block]);
+            }
+        }
+        out
+    }
+
+    /// Join native pieces, which must all have the same type
+    pub fn join_native(&self, pieces: Vec<NativeValues>, var_name: &str) -> Result<NativeValues, NetCDFError> {
+        let mismatch = || {
+            NetCDFError::VariableReadError(var_name.to_string(), "member files returned different types".to_string())
+        };
+
+        macro_rules! join {
+            ($($variant:ident),*) => {
+                match pieces.first() {
+                    $(Some(NativeValues::$variant(_)) => {
+                        let values = pieces
+                            .into_iter()
+                            .map(|piece| match piece {
+                                NativeValues::$variant(v) => Ok(v),
+                                _ => Err(mismatch()),
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        NativeValues::$variant(self.join(values))
+                    })*
+                    None => NativeValues::F64(Vec::new()),
+                }
+            };
+        }
+        Ok(join!(I8, U8, I16, U16, I32, U32, I64, U64, F32, F64))
+    }
+}
+
+impl DatasetBackend for AggregatedDataset {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        if self.members.iter().any(|m| m.time_rebase.contains_key(var_name)) {
+            return Ok(NativeValues::F64(self.read_f64(var_name, start, count)?));
+        }
+
+        let (pieces, layout) = self.read_pieces(var_name, start, count, |member, s, c| {
+            member.dataset.read_native(var_name, s, c)
+        })?;
+        layout.join_native(pieces, var_name)
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        let (pieces, layout) = self.read_pieces(var_name, start, count, |member, s, c| {
+            member.dataset.read_text(var_name, s, c)
+        })?;
+        Ok(layout.join(pieces))
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        self.members[0].dataset.fill_value(var_name)
+    }
+
+    /// Attributes of the first member; rebased times are doubles, so their
+    /// fill and range attributes become doubles too
+    fn attributes(&self, var_name: Option<&str>) -> Result<Vec<(String, AttributeValue)>, NetCDFError> {
+        let mut attrs = self.members[0].dataset.attributes(var_name)?;
+        let rebased = var_name.is_some_and(|name| self.members.iter().any(|m| m.time_rebase.contains_key(name)));
+        if rebased {
+            for (_, value) in attrs.iter_mut().filter(|(attr, _)| VARIABLE_TYPED_ATTRS.contains(&attr.as_str())) {
+                *value = numbers_attribute("Basic(Double)", &attribute_numbers(value));
+            }
+        }
+        Ok(attrs)
+    }
+
+    fn read_f64(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<f64>, NetCDFError> {
+        let (pieces, layout) = self.read_pieces(var_name, start, count, |member, s, c| {
+            let mut values = member.dataset.read_f64(var_name, s, c)?;
+            if let Some(&(scale, offset)) = member.time_rebase.get(var_name) {
+                let fill = member.dataset.fill_value(var_name);
+                for x in values.iter_mut().filter(|x| !fill.is_some_and(|fv| (**x - fv).abs() < 1e-10)) {
+                    *x = *x * scale + offset;
+                }
+            }
+            Ok(values)
+        })?;
+        Ok(layout.join(pieces))
+    }
+}
+
+/// The unlimited dimension, or else the dimension of the time coordinate
+fn aggregation_dimension(metadata: &FileMetadata) -> Result<String, NetCDFError> {
+    if let Some(dim) = metadata.dimensions.iter().find(|d| d.is_unlimited) {
+        return Ok(dim.name.clone());
+    }
+    detect_coordinates(metadata)
+        .time_var
+        .and_then(|name| find_variable(metadata, &name).ok())
+        .filter(|var| var.dimensions.len() == 1)
+        .map(|var| var.dimensions[0].clone())
+        .ok_or_else(|| {
+            NetCDFError::InvalidFormat(format!(
+                "{} has no unlimited or time dimension to aggregate along",
+                metadata.file_path
+            ))
+        })
+}
+
+/// Check a member has the reference's dimensions and variables
+fn check_consistent(
+    reference: &FileMetadata,
+    metadata: &FileMetadata,
+    dimension: &str,
+    path: &str,
+) -> Result<(), NetCDFError> {
+    if metadata.dimensions.len() != reference.dimensions.len() {
+        return Err(inconsistent(path, "different number of dimensions"));
+    }
+    for dim in &reference.dimensions {
+        match metadata.dimensions.iter().find(|d| d.name == dim.name) {
+            None => return Err(inconsistent(path, &format!("dimension '{}' is missing", dim.name))),
+            Some(other) if dim.name != dimension && other.size != dim.size => {
+                return Err(inconsistent(
+                    path,
+                    &format!("dimension '{}' has size {} instead of {}", dim.name, other.size, dim.size),
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    if metadata.variables.len() != reference.variables.len() {
+        return Err(inconsistent(path, "different number of variables"));
+    }
+    for var in &reference.variables {
+        let other = find_variable(metadata, &var.name)
+            .map_err(|_| inconsistent(path, &format!("variable '{}' is missing", var.name)))?;
+        if other.data_type != var.data_type || other.dimensions != var.dimensions {
+            return Err(inconsistent(
+                path,
+                &format!("variable '{}' has another type or dimensions", var.name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Conversions of a member's time variables to the reference's units
+fn rebase_factors(
+    reference: &FileMetadata,
+    metadata: &FileMetadata,
+    dimension: &str,
+    path: &str,
+) -> Result<HashMap<String, (f64, f64)>, NetCDFError> {
+    let mut factors = HashMap::new();
+    for var in reference.variables.iter().filter(|v| v.dimensions.iter().any(|d| d == dimension)) {
+        let Ok(other) = find_variable(metadata, &var.name) else {
+            continue;
+        };
+        let (Some(ref_units), Some(units)) = (units_text(var, reference), units_text(&other, metadata)) else {
+            continue;
+        };
+        if ref_units == units {
+            continue;
+        }
+
+        let (Some(target), Some(source)) = (CfTimeUnits::parse(&ref_units), CfTimeUnits::parse(&units)) else {
+            // Only time units are reconciled
+            if CfTimeUnits::parse(&ref_units).is_some() || CfTimeUnits::parse(&units).is_some() {
+                return Err(inconsistent(path, &format!("'{}' has units '{}' instead of '{}'", var.name, units, ref_units)));
+            }
+            continue;
+        };
+
+        let calendar = var.attributes.get("calendar").map(String::as_str);
+        if source.epoch != target.epoch && !is_standard_calendar(calendar) {
+            return Err(inconsistent(
+                path,
+                &format!("'{}' uses another epoch in the '{}' calendar", var.name, calendar.unwrap_or_default()),
+            ));
+        }
+        let epoch_shift = (source.epoch - target.epoch).num_milliseconds() as f64 / 1000.0;
+        factors.insert(
+            var.name.clone(),
+            (source.unit_seconds / target.unit_seconds, epoch_shift / target.unit_seconds),
+        );
+    }
+    Ok(factors)
+}
+
+/// Units of a variable, or of the variable it holds the bounds of
+fn units_text(var: &Variable, metadata: &FileMetadata) -> Option<String> {
+    var.attributes.get("units").cloned().or_else(|| {
+        metadata
+            .variables
+            .iter()
+            .find(|v| v.attributes.get("bounds") == Some(&var.name))
+            .and_then(|parent| parent.attributes.get("units").cloned())
+    })
+}
+
+/// Parsed time units of a variable in the standard calendar
+fn time_units(var: &Variable, metadata: &FileMetadata) -> Option<CfTimeUnits> {
+    if !is_standard_calendar(var.attributes.get("calendar").map(String::as_str)) {
+        return None;
+    }
+    units_text(var, metadata).and_then(|u| CfTimeUnits::parse(&u))
+}
+
+/// Fail if the time ranges of consecutive members overlap
+fn check_time_order(members: &[Member], time_name: &str) -> Result<(), NetCDFError> {
+    let mut previous: Option<(f64, &str)> = None;
+    for member in members.iter().filter(|m| m.length > 0) {
+        let rebase = |t: f64| match member.time_rebase.get(time_name) {
+            Some((scale, offset)) => t * scale + offset,
+            None => t,
+        };
+        let first = rebase(member.dataset.read_masked(time_name, &[0], &[1])?[0]);
+        let last = rebase(member.dataset.read_masked(time_name, &[member.length - 1], &[1])?[0]);
+        if let Some((end, previous_path)) = previous {
+            if first <= end {
+                return Err(NetCDFError::InvalidFormat(format!(
+                    "Time ranges of {} and {} overlap",
+                    previous_path, member.path
+                )));
+            }
+        }
+        previous = Some((last, &member.path));
+    }
+    Ok(())
+}
+
+fn same_fill(a: Option<f64>, b: Option<f64>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn inconsistent(path: &str, what: &str) -> NetCDFError {
+    NetCDFError::InvalidFormat(format!("Cannot aggregate {}: {}", path, what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netcdf::test_util::write_store;
+
+    #[test]
+    fn test_layout_interleaves_pieces() {
+        // A 2x3 hyperslab split 1 + 2 along the second dimension
+        let layout = Layout { outer: 2, inner: 1, lengths: vec![1, 2] };
+        let joined = layout.join(vec![vec![0, 3], vec![1, 2, 4, 5]]);
+        assert_eq!(joined, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_aggregate_stores_with_different_epochs() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (jan, feb) = (dir.join("jan.zarr"), dir.join("feb.zarr"));
+        write_store(&jan, "days since 2000-01-01", &[0.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+        write_store(&feb, "hours since 2000-01-03", &[0.0, 24.0, 48.0], &[5.0, 6.0, 7.0, 8.0, 9.0, 10.0]);
+
+        // Listed out of order; sorted by time when opened
+        let path = format!("{}\n{}", feb.display(), jan.display());
+        let dataset = AggregatedDataset::open(&path).unwrap();
+        let metadata = dataset.metadata().unwrap();
+        let tas = find_variable(&metadata, "tas").unwrap();
+        assert_eq!(tas.shape, vec![5, 2]);
+
+        assert_eq!(dataset.read_f64("time", &[0], &[5]).unwrap(), vec![0.0, 1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(dataset.read_f64("tas", &[1, 1], &[2, 1]).unwrap(), vec![4.0, 6.0]);
+        let NativeValues::F32(values) = dataset.read_native("tas", &[1, 0], &[3, 2]).unwrap() else {
+            panic!("tas should stay float");
+        };
+        assert_eq!(values, vec![3.0, 4.0, 5.0, 6.0, 7.0, 8.0]);
+
+        // Files whose other dimensions differ are rejected
+        write_store(&feb, "days since 2000-01-01", &[2.0], &[5.0, 6.0]);
+        std::fs::write(
+            feb.join("tas/.zarray"),
+            r#"{"zarr_format": 2, "shape": [1, 1], "chunks": [1, 1], "dtype": "<f4",
+                "compressor": null, "fill_value": null, "order": "C", "filters": null}"#,
+        )
+        .unwrap();
+        let error = AggregatedDataset::open(&path).err().unwrap();
+        assert!(error.to_string().contains("dimension 'x'"), "{}", error);
+    }
+
+    #[test]
+    fn test_aggregation_paths() {
+        assert!(is_aggregation("/data/out_*.nc"));
+        assert!(is_aggregation("/data/a.nc\n/data/b.nc"));
+        assert!(!is_aggregation("/data/out.nc"));
+        assert!(!is_aggregation("https://host/dodsC/x.nc?time"));
+    }
+}
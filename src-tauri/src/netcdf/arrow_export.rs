@@ -0,0 +1,247 @@
+use crate::errors::NetCDFError;
+use crate::models::{ExportSummary, FileMetadata, TableExportRequest};
+use super::backend::{open_dataset, DatasetBackend};
+use super::data_access::NativeValues;
+use super::table::{CoordinateValues, TablePlan};
+use arrow_array::{
+    ArrayRef, Float32Array, Float64Array, Int16Array, Int32Array, Int64Array, Int8Array,
+    RecordBatch, TimestampMillisecondArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Write variables as a long-form Parquet file.
+///
+/// Columns keep the variables' native dtypes, CF time coordinates become
+/// UTC timestamps, and NaN/fill values are stored as nulls. Variable
+/// attributes are attached as field metadata and global attributes as
+/// schema metadata.
+pub fn export_parquet(path: &str, request: &TableExportRequest) -> Result<ExportSummary, NetCDFError> {
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    let plan = TablePlan::new(dataset.as_ref(), &metadata, &request.variables, &request.slices, request.decode_times)?;
+    let schema = build_schema(&metadata, &plan)?;
+
+    let output = std::fs::File::create(&request.output_path)?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(output, schema.clone(), Some(props)).map_err(export_error)?;
+
+    let records = write_batches(dataset.as_ref(), &plan, &schema, |batch| {
+        writer.write(batch).map_err(export_error)
+    })?;
+    writer.close().map_err(export_error)?;
+
+    Ok(summary(request, &plan, records))
+}
+
+/// Write variables as a long-form Arrow IPC (Feather v2) file.
+///
+/// Uses the same schema as `export_parquet`.
+pub fn export_arrow(path: &str, request: &TableExportRequest) -> Result<ExportSummary, NetCDFError> {
+    use arrow_ipc::writer::FileWriter;
+
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    let plan = TablePlan::new(dataset.as_ref(), &metadata, &request.variables, &request.slices, request.decode_times)?;
+    let schema = build_schema(&metadata, &plan)?;
+
+    let output = std::fs::File::create(&request.output_path)?;
+    let mut writer = FileWriter::try_new(output, &schema).map_err(export_error)?;
+
+    let records = write_batches(dataset.as_ref(), &plan, &schema, |batch| {
+        writer.write(batch).map_err(export_error)
+    })?;
+    writer.finish().map_err(export_error)?;
+
+    Ok(summary(request, &plan, records))
+}
+
+/// Arrow schema for a table: dimension, then auxiliary coordinate, then variable columns
+fn build_schema(metadata: &FileMetadata, plan: &TablePlan) -> Result<SchemaRef, NetCDFError> {
+    let mut fields = Vec::with_capacity(plan.coordinates.len() + plan.auxiliary.len() + plan.variables.len());
+
+    for coord in &plan.coordinates {
+        let data_type = match coord.values {
+            CoordinateValues::Index => DataType::UInt64,
+            CoordinateValues::Numeric(_) => DataType::Float64,
+            CoordinateValues::Time(_) => DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+        };
+        let attributes = metadata
+            .variables
+            .iter()
+            .find(|v| v.name == coord.name)
+            .map(|v| v.attributes.clone())
+            .unwrap_or_default();
+        fields.push(Field::new(&coord.name, data_type, true).with_metadata(attributes));
+    }
+
+    for aux in &plan.auxiliary {
+        let attributes = metadata
+            .variables
+            .iter()
+            .find(|v| v.name == aux.name)
+            .map(|v| v.attributes.clone())
+            .unwrap_or_default();
+        fields.push(Field::new(&aux.name, DataType::Float64, true).with_metadata(attributes));
+    }
+
+    for meta in &plan.variables {
+        let data_type = arrow_type(&meta.data_type).ok_or_else(|| {
+            NetCDFError::ExportError(format!(
+                "Variable '{}' has non-numeric type {}",
+                meta.name, meta.data_type
+            ))
+        })?;
+        fields.push(Field::new(&meta.name, data_type, true).with_metadata(meta.attributes.clone()));
+    }
+
+    let mut schema_metadata: HashMap<String, String> = metadata.global_attrs.clone();
+    schema_metadata.insert("source_file".to_string(), metadata.file_path.clone());
+
+    Ok(Arc::new(Schema::new_with_metadata(fields, schema_metadata)))
+}
+
+/// Arrow type matching a NetCDF numeric type name (as in `Variable::data_type`)
+fn arrow_type(data_type: &str) -> Option<DataType> {
+    let data_type = match data_type {
+        "Basic(Byte)" => DataType::Int8,
+        "Basic(Ubyte)" => DataType::UInt8,
+        "Basic(Short)" => DataType::Int16,
+        "Basic(Ushort)" => DataType::UInt16,
+        "Basic(Int)" => DataType::Int32,
+        "Basic(Uint)" => DataType::UInt32,
+        "Basic(Int64)" => DataType::Int64,
+        "Basic(Uint64)" => DataType::UInt64,
+        "Basic(Float)" => DataType::Float32,
+        "Basic(Double)" => DataType::Float64,
+        _ => return None,
+    };
+    Some(data_type)
+}
+
+/// Read the table block by block and pass each record batch to `sink`
+fn write_batches<F>(
+    dataset: &dyn DatasetBackend,
+    plan: &TablePlan,
+    schema: &SchemaRef,
+    mut sink: F,
+) -> Result<usize, NetCDFError>
+where
+    F: FnMut(&RecordBatch) -> Result<(), NetCDFError>,
+{
+    let mut records = 0;
+
+    for (start, count) in plan.blocks() {
+        let rows: usize = count.iter().product();
+        let mut columns: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+
+        for (d, coord) in plan.coordinates.iter().enumerate() {
+            let stride: usize = count[d + 1..].iter().product();
+            let offset = start[d] - plan.start[d];
+            let index = |row: usize| offset + (row / stride) % count[d];
+
+            let column: ArrayRef = match &coord.values {
+                CoordinateValues::Index => Arc::new(
+                    (0..rows).map(|r| (coord.start + index(r)) as u64).collect::<UInt64Array>(),
+                ),
+                CoordinateValues::Numeric(values) => Arc::new(
+                    (0..rows)
+                        .map(|r| Some(values[index(r)]).filter(|v| !v.is_nan()))
+                        .collect::<Float64Array>(),
+                ),
+                CoordinateValues::Time(values) => Arc::new(
+                    (0..rows)
+                        .map(|r| values[index(r)].map(|t| t.and_utc().timestamp_millis()))
+                        .collect::<TimestampMillisecondArray>()
+                        .with_timezone("UTC"),
+                ),
+            };
+            columns.push(column);
+        }
+
+        for values in plan.read_auxiliary(dataset, &start, &count)? {
+            columns.push(Arc::new(values.into_iter().map(|v| Some(v).filter(|v| !v.is_nan())).collect::<Float64Array>()));
+        }
+
+        for meta in &plan.variables {
+            let values = dataset.read_native(&meta.name, &start, &count)?;
+            columns.push(masked_array(values, dataset.fill_value(&meta.name)));
+        }
+
+        let batch = RecordBatch::try_new(schema.clone(), columns).map_err(export_error)?;
+        sink(&batch)?;
+        records += rows;
+    }
+
+    Ok(records)
+}
+
+/// Convert native values to an Arrow array with NaN/fill values as nulls
+fn masked_array(values: NativeValues, fill_value: Option<f64>) -> ArrayRef {
+    macro_rules! masked {
+        ($values:expr, $array:ty) => {
+            Arc::new(
+                $values
+                    .into_iter()
+                    .map(|x| {
+                        let v = x as f64;
+                        let missing = v.is_nan() || fill_value.is_some_and(|fv| (v - fv).abs() < 1e-10);
+                        if missing { None } else { Some(x) }
+                    })
+                    .collect::<$array>(),
+            ) as ArrayRef
+        };
+    }
+
+    match values {
+        NativeValues::I8(v) => masked!(v, Int8Array),
+        NativeValues::U8(v) => masked!(v, UInt8Array),
+        NativeValues::I16(v) => masked!(v, Int16Array),
+        NativeValues::U16(v) => masked!(v, UInt16Array),
+        NativeValues::I32(v) => masked!(v, Int32Array),
+        NativeValues::U32(v) => masked!(v, UInt32Array),
+        NativeValues::I64(v) => masked!(v, Int64Array),
+        NativeValues::U64(v) => masked!(v, UInt64Array),
+        NativeValues::F32(v) => masked!(v, Float32Array),
+        NativeValues::F64(v) => masked!(v, Float64Array),
+    }
+}
+
+fn summary(request: &TableExportRequest, plan: &TablePlan, records: usize) -> ExportSummary {
+    ExportSummary {
+        output_path: request.output_path.clone(),
+        variables: plan.variables.iter().map(|v| v.name.clone()).collect(),
+        skipped: vec![],
+        records,
+        warnings: plan.warnings.clone(),
+    }
+}
+
+fn export_error<E: std::fmt::Display>(err: E) -> NetCDFError {
+    NetCDFError::ExportError(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+
+    #[test]
+    fn test_masked_array_nulls_fill_and_nan() {
+        let array = masked_array(NativeValues::F32(vec![1.0, -999.0, f32::NAN, 4.0]), Some(-999.0));
+        assert_eq!(array.data_type(), &DataType::Float32);
+        assert_eq!(array.null_count(), 2);
+        assert!(array.is_null(1));
+        assert!(array.is_null(2));
+
+        let array = masked_array(NativeValues::I16(vec![1, 2, 3]), None);
+        assert_eq!(array.null_count(), 0);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::errors::NetCDFError;
+use crate::models::{FileMetadata, Variable};
+use super::aggregation::{is_aggregation, AggregatedDataset};
+use super::data_access::{
+    get_fill_value, read_native_subset, read_variable_subset_as_f64,
+    read_variable_subset_as_string, NativeValues,
+};
+use super::data_access::find_variable;
+use super::derived::with_derived;
+use super::grib::{is_grib, GribBackend};
+use super::hdf5_loader::{is_hdf5, Hdf5Backend};
+use super::loader::{read_metadata, read_typed_attributes, text_attributes};
+use super::ncml::{is_ncml, NcmlDataset};
+use super::object_store::is_object_url;
+use super::opendap::{is_opendap_url, OpendapBackend};
+use super::remote_file::open_object;
+use super::zarr::ZarrStore;
+use netcdf::AttributeValue;
+use std::path::Path;
+
+/// A source of dimensions, variables and hyperslabs.
+///
+/// Every on-disk format the viewer understands implements this trait, so
+/// metadata, data access, rendering and exports work the same way for all
+/// of them.
+pub trait DatasetBackend {
+    /// Dimensions, variables and global attributes of the dataset
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError>;
+
+    /// Read a hyperslab of a numeric variable in its native type
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError>;
+
+    /// Read a hyperslab of a text variable.
+    ///
+    /// Character arrays yield one string per row of the last dimension.
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError>;
+
+    /// Fill value of a variable, if it has one
+    fn fill_value(&self, var_name: &str) -> Option<f64>;
+
+    /// Attributes of a variable, or the global attributes when `var_name` is
+    /// `None`, with their stored types.
+    ///
+    /// Backends that keep no attribute types report the metadata text (see
+    /// [`text_attributes`]).
+    fn attributes(&self, var_name: Option<&str>) -> Result<Vec<(String, AttributeValue)>, NetCDFError> {
+        let metadata = self.metadata()?;
+        Ok(match var_name {
+            Some(name) => {
+                let var = find_variable(&metadata, name)?;
+                text_attributes(&var.attributes, Some(&var.data_type))
+            }
+            None => text_attributes(&metadata.global_attrs, None),
+        })
+    }
+
+    /// Read a hyperslab of a numeric variable converted to f64
+    fn read_f64(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<f64>, NetCDFError> {
+        Ok(self.read_native(var_name, start, count)?.into_f64())
+    }
+
+    /// Read a hyperslab as f64 with fill values replaced by NaN
+    fn read_masked(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<f64>, NetCDFError> {
+        let mut data = self.read_f64(var_name, start, count)?;
+
+        if let Some(fv) = self.fill_value(var_name) {
+            for x in data.iter_mut() {
+                if (*x - fv).abs() < 1e-10 {
+                    *x = f64::NAN;
+                }
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Read a whole variable as f64 with fill values replaced by NaN
+    fn read_masked_all(&self, var: &Variable) -> Result<Vec<f64>, NetCDFError> {
+        let start = vec![0; var.shape.len()];
+        self.read_masked(&var.name, &start, &var.shape)
+    }
+}
+
+/// Open a dataset with the backend matching its format.
+///
+/// Glob patterns and newline-separated lists of files open as one dataset
+/// concatenated along the time dimension, and NcML documents as the virtual
+/// dataset they describe.
+/// `s3://` URLs and `http(s)://` URLs ending in `#mode=bytes` are read with
+/// range requests; other `http(s)://` and `dap4://` URLs from OPeNDAP servers.
+/// Directories holding Zarr metadata are read as Zarr stores and files of
+/// GRIB messages by the GRIB2 reader. HDF5 files go to the NetCDF library
+/// unless it fails or shows fewer variables than the file has datasets, in
+/// which case they are read as plain HDF5. Everything else is handed to the
+/// NetCDF library.
+///
+/// Derived variables defined for `path` are added to whatever opens.
+pub fn open_dataset(path: &str) -> Result<Box<dyn DatasetBackend>, NetCDFError> {
+    with_derived(path, open_source(path)?)
+}
+
+/// Open a dataset as stored, without derived variables
+pub(crate) fn open_source(path: &str) -> Result<Box<dyn DatasetBackend>, NetCDFError> {
+    if is_aggregation(path) {
+        return Ok(Box::new(AggregatedDataset::open(path)?));
+    }
+    if is_ncml(path) {
+        return Ok(Box::new(NcmlDataset::open(path)?));
+    }
+    if is_object_url(path) {
+        return open_object(path);
+    }
+    if is_opendap_url(path) {
+        return Ok(Box::new(OpendapBackend::open(path)?));
+    }
+
+    let store_path = Path::new(path);
+    if !store_path.exists() {
+        return Err(NetCDFError::FileOpenError(format!(
+            "File not found: {}",
+            path
+        )));
+    }
+
+    if ZarrStore::is_store(store_path) {
+        return Ok(Box::new(ZarrStore::open(store_path)?));
+    }
+
+    if is_grib(store_path) {
+        return Ok(Box::new(GribBackend::open(path)?));
+    }
+
+    if is_hdf5(store_path) {
+        if let Ok(hdf5) = Hdf5Backend::open(path) {
+            // The NetCDF library lists the root group only, so compare it with
+            // the root group's datasets; files with data only in subgroups
+            // are left to the HDF5 backend, which reads every group
+            let root_count = hdf5.root_variable_count();
+            let netcdf = NetCDFBackend::open(path).ok().filter(|backend| {
+                backend.metadata().is_ok_and(|m| {
+                    m.variables.len() >= root_count && (root_count > 0 || hdf5.data_variable_count() == 0)
+                })
+            });
+            return match netcdf {
+                Some(backend) => Ok(Box::new(backend)),
+                None => Ok(Box::new(hdf5)),
+            };
+        }
+    }
+
+    Ok(Box::new(NetCDFBackend::open(path)?))
+}
+
+/// Kind of number a storage format describes an element as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NumberKind {
+    Signed,
+    Unsigned,
+    Float,
+}
+
+/// Data type name of a number of `size` bytes in the form the NetCDF backend
+/// reports it ("Basic(Short)", "Basic(Double)", ...), so that variables of
+/// every backend are typed alike
+pub(crate) fn numeric_type_name(kind: NumberKind, size: usize) -> &'static str {
+    match (kind, size) {
+        (NumberKind::Signed, 1) => "Basic(Byte)",
+        (NumberKind::Signed, 2) => "Basic(Short)",
+        (NumberKind::Signed, 4) => "Basic(Int)",
+        (NumberKind::Signed, _) => "Basic(Int64)",
+        (NumberKind::Unsigned, 1) => "Basic(Ubyte)",
+        (NumberKind::Unsigned, 2) => "Basic(Ushort)",
+        (NumberKind::Unsigned, 4) => "Basic(Uint)",
+        (NumberKind::Unsigned, _) => "Basic(Uint64)",
+        (NumberKind::Float, 4) => "Basic(Float)",
+        (NumberKind::Float, _) => "Basic(Double)",
+    }
+}
+
+/// NetCDF-3/4 files read through the NetCDF C library
+pub struct NetCDFBackend {
+    path: String,
+    file: netcdf::File,
+}
+
+impl NetCDFBackend {
+    /// Open a NetCDF file
+    pub fn open(path: &str) -> Result<Self, NetCDFError> {
+        let file = netcdf::open(path).map_err(|e| {
+            NetCDFError::FileOpenError(format!("Failed to open {}: {}", path, e))
+        })?;
+
+        Ok(NetCDFBackend {
+            path: path.to_string(),
+            file,
+        })
+    }
+
+    fn variable(&self, var_name: &str) -> Result<netcdf::Variable<'_>, NetCDFError> {
+        self.file
+            .variable(var_name)
+            .ok_or_else(|| NetCDFError::VariableNotFound(var_name.to_string()))
+    }
+}
+
+impl DatasetBackend for NetCDFBackend {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        read_metadata(&self.path, &self.file)
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        read_native_subset(&self.variable(var_name)?, start, count)
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        read_variable_subset_as_string(&self.variable(var_name)?, start, count)
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        self.file.variable(var_name).and_then(|var| get_fill_value(&var))
+    }
+
+    fn attributes(&self, var_name: Option<&str>) -> Result<Vec<(String, AttributeValue)>, NetCDFError> {
+        Ok(match var_name {
+            Some(name) => read_typed_attributes(self.variable(name)?.attributes()),
+            None => read_typed_attributes(self.file.attributes()),
+        })
+    }
+
+    fn read_f64(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<f64>, NetCDFError> {
+        let (data, _) = read_variable_subset_as_f64(&self.variable(var_name)?, start, count)?;
+        Ok(data)
+    }
+}
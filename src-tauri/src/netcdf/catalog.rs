@@ -0,0 +1,497 @@
+use crate::errors::NetCDFError;
+use crate::models::{
+    CatalogEntry, CatalogQuery, CatalogScanSummary, CatalogVariable, FileMetadata, GeoExtent, Variable,
+};
+use super::backend::{open_dataset, DatasetBackend};
+use super::coordinates::detect_coordinates;
+use super::data_access::{find_variable, is_text_type};
+use super::table::outer_blocks;
+use super::time::{format_iso, is_standard_calendar, parse_iso, CfTimeUnits};
+use super::walk::walk_dir;
+use super::zarr::ZarrStore;
+use chrono::{DateTime, Duration, NaiveDateTime};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Transaction};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Extensions of the files a scan opens; Zarr stores are found by their metadata
+const EXTENSIONS: &[&str] = &[
+    "nc", "nc4", "netcdf", "cdf", "h5", "hdf5", "he5", "grib2", "grb2", "grib", "grb", "ncml",
+];
+
+/// Times are stored as seconds since 1970-01-01 UTC, bounding boxes in degrees
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS files (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    size INTEGER NOT NULL,
+    modified INTEGER NOT NULL,
+    time_start REAL,
+    time_end REAL,
+    south REAL,
+    west REAL,
+    north REAL,
+    east REAL
+);
+CREATE TABLE IF NOT EXISTS variables (
+    file_id INTEGER NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    standard_name TEXT,
+    long_name TEXT,
+    units TEXT,
+    dimensions TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS global_attributes (
+    file_id INTEGER NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS variables_file ON variables(file_id);
+CREATE INDEX IF NOT EXISTS variables_name ON variables(name);
+CREATE INDEX IF NOT EXISTS variables_standard_name ON variables(standard_name);
+CREATE INDEX IF NOT EXISTS global_attributes_file ON global_attributes(file_id);
+";
+
+/// Index used when none is given: `$NCV_CATALOG`, else
+/// `~/.ncv/catalog.sqlite`
+pub fn default_index_path() -> String {
+    if let Some(path) = std::env::var_os("NCV_CATALOG") {
+        return path.to_string_lossy().into_owned();
+    }
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    home.join(".ncv").join("catalog.sqlite").to_string_lossy().into_owned()
+}
+
+/// Recursively scan a directory and record every dataset in the index.
+///
+/// Files whose size and modification time match the index are not reopened,
+/// and entries for files under `root` that have disappeared are dropped.
+/// Files that fail to open are reported and left out of the index.
+pub fn scan_directory(root: &str, index_path: &str) -> Result<CatalogScanSummary, NetCDFError> {
+    let root_path = std::fs::canonicalize(root)
+        .map_err(|e| NetCDFError::FileOpenError(format!("Cannot scan {}: {}", root, e)))?;
+    let mut summary = CatalogScanSummary {
+        root: root_path.to_string_lossy().into_owned(),
+        index_path: index_path.to_string(),
+        indexed: 0,
+        unchanged: 0,
+        removed: 0,
+        failed: Vec::new(),
+    };
+    let mut candidates = Vec::new();
+    find_datasets(&root_path, &mut candidates, &mut summary.failed);
+
+    let mut connection = open_index(index_path)?;
+    let transaction = connection.transaction()?;
+    let mut seen = HashSet::new();
+    for path in candidates {
+        let path_text = path.to_string_lossy().into_owned();
+        let stamp = file_stamp(&path);
+        seen.insert(path_text.clone());
+
+        let indexed: Option<(i64, i64)> = transaction
+            .query_row("SELECT size, modified FROM files WHERE path = ?1", [&path_text], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .optional()?;
+        if indexed == Some(stamp) {
+            summary.unchanged += 1;
+            continue;
+        }
+
+        transaction.execute("DELETE FROM files WHERE path = ?1", [&path_text])?;
+        match summarize(&path_text) {
+            Ok(file) => {
+                insert_file(&transaction, &path_text, stamp, &file)?;
+                summary.indexed += 1;
+            }
+            Err(e) => summary.failed.push(format!("{}: {}", path_text, e)),
+        }
+    }
+
+    // Entries below the root that the scan no longer found
+    let stale: Vec<i64> = {
+        let mut statement = transaction.prepare("SELECT id, path FROM files")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+        let mut stale = Vec::new();
+        for row in rows {
+            let (id, path) = row?;
+            if Path::new(&path).starts_with(&root_path) && !seen.contains(&path) {
+                stale.push(id);
+            }
+        }
+        stale
+    };
+    for id in &stale {
+        transaction.execute("DELETE FROM files WHERE id = ?1", [id])?;
+    }
+    summary.removed = stale.len();
+
+    transaction.commit()?;
+    Ok(summary)
+}
+
+/// Files in the index matching every filter of the query, ordered by path
+pub fn query_catalog(index_path: &str, query: &CatalogQuery) -> Result<Vec<CatalogEntry>, NetCDFError> {
+    if !Path::new(index_path).exists() {
+        return Err(NetCDFError::CatalogError(format!(
+            "No catalog at {}; scan a directory first",
+            index_path
+        )));
+    }
+    let connection = open_index(index_path)?;
+
+    let mut sql = String::from("SELECT id, path, time_start, time_end, south, west, north, east FROM files f WHERE 1 = 1");
+    let mut args: Vec<Value> = Vec::new();
+    if let Some(variable) = &query.variable {
+        sql.push_str(
+            " AND EXISTS (SELECT 1 FROM variables v WHERE v.file_id = f.id AND (v.name = ? OR v.standard_name = ?))",
+        );
+        args.push(Value::Text(variable.clone()));
+        args.push(Value::Text(variable.clone()));
+    }
+    if let Some(start) = &query.time_start {
+        sql.push_str(" AND f.time_end >= ?");
+        args.push(Value::Real(query_time(start, false)?));
+    }
+    if let Some(end) = &query.time_end {
+        sql.push_str(" AND f.time_start <= ?");
+        args.push(Value::Real(query_time(end, true)?));
+    }
+    if let Some(extent) = &query.extent {
+        sql.push_str(" AND f.north >= ? AND f.south <= ? AND f.east >= ? AND f.west <= ?");
+        args.extend([extent.south, extent.north, extent.west, extent.east].map(Value::Real));
+    }
+    for (name, text) in query.attributes.iter().collect::<BTreeMap<_, _>>() {
+        sql.push_str(
+            " AND EXISTS (SELECT 1 FROM global_attributes a WHERE a.file_id = f.id \
+             AND a.name = ? AND instr(lower(a.value), lower(?)) > 0)",
+        );
+        args.push(Value::Text(name.clone()));
+        args.push(Value::Text(text.clone()));
+    }
+    sql.push_str(" ORDER BY f.path");
+
+    let mut statement = connection.prepare(&sql)?;
+    let rows = statement.query_map(params_from_iter(args), |row| {
+        let bounds: [Option<f64>; 4] = [row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?];
+        let extent = match bounds {
+            [Some(south), Some(west), Some(north), Some(east)] => Some(GeoExtent { south, west, north, east }),
+            _ => None,
+        };
+        let entry = CatalogEntry {
+            path: row.get(1)?,
+            time_start: row.get::<_, Option<f64>>(2)?.and_then(iso_time),
+            time_end: row.get::<_, Option<f64>>(3)?.and_then(iso_time),
+            extent,
+            variables: Vec::new(),
+            global_attrs: Default::default(),
+        };
+        Ok((row.get::<_, i64>(0)?, entry))
+    })?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let (id, mut entry) = row?;
+        let mut variables = connection.prepare_cached(
+            "SELECT name, standard_name, long_name, units, dimensions FROM variables WHERE file_id = ?1 ORDER BY rowid",
+        )?;
+        entry.variables = variables
+            .query_map([id], |row| {
+                let dimensions: String = row.get(4)?;
+                Ok(CatalogVariable {
+                    name: row.get(0)?,
+                    standard_name: row.get(1)?,
+                    long_name: row.get(2)?,
+                    units: row.get(3)?,
+                    dimensions: serde_json::from_str(&dimensions).unwrap_or_default(),
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        let mut attributes = connection.prepare_cached("SELECT name, value FROM global_attributes WHERE file_id = ?1")?;
+        entry.global_attrs = attributes
+            .query_map([id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// What the index records about one dataset
+struct FileSummary {
+    metadata: FileMetadata,
+    /// First and last time, in seconds since 1970-01-01 UTC
+    time: Option<(f64, f64)>,
+    extent: Option<GeoExtent>,
+}
+
+fn summarize(path: &str) -> Result<FileSummary, NetCDFError> {
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    let coords = detect_coordinates(&metadata);
+
+    let time = coords
+        .time_var
+        .as_deref()
+        .and_then(|name| time_coverage(dataset.as_ref(), &metadata, name));
+    let extent = match (coords.lat_var.as_deref(), coords.lon_var.as_deref()) {
+        (Some(lat), Some(lon)) => bounding_box(dataset.as_ref(), &metadata, lat, lon),
+        _ => None,
+    };
+    Ok(FileSummary { metadata, time, extent })
+}
+
+fn time_coverage(dataset: &dyn DatasetBackend, metadata: &FileMetadata, name: &str) -> Option<(f64, f64)> {
+    let var = find_variable(metadata, name).ok()?;
+    if !is_standard_calendar(var.attributes.get("calendar").map(String::as_str)) {
+        return None;
+    }
+    let units = CfTimeUnits::parse(var.attributes.get("units")?)?;
+    let (first, last) = value_range(dataset, &var)?;
+    Some((unix_seconds(units.decode(first)?), unix_seconds(units.decode(last)?)))
+}
+
+fn bounding_box(dataset: &dyn DatasetBackend, metadata: &FileMetadata, lat: &str, lon: &str) -> Option<GeoExtent> {
+    let (south, north) = value_range(dataset, &find_variable(metadata, lat).ok()?)?;
+    let (west, east) = value_range(dataset, &find_variable(metadata, lon).ok()?)?;
+    let (west, east) = normalize_longitudes(west, east);
+    Some(GeoExtent { south, west, north, east })
+}
+
+/// Bring a 0..360 longitude range into -180..180, widening ranges that
+/// straddle 180° to the whole globe
+fn normalize_longitudes(west: f64, east: f64) -> (f64, f64) {
+    match (west, east) {
+        (w, e) if e <= 180.0 => (w, e),
+        (w, e) if w >= 180.0 => (w - 360.0, e - 360.0),
+        _ => (-180.0, 180.0),
+    }
+}
+
+/// Smallest and largest valid value of a numeric variable, read block by block
+fn value_range(dataset: &dyn DatasetBackend, var: &Variable) -> Option<(f64, f64)> {
+    if is_text_type(&var.data_type) || var.shape.contains(&0) {
+        return None;
+    }
+    let start = vec![0; var.shape.len()];
+    let mut range: Option<(f64, f64)> = None;
+    for (block_start, block_count) in outer_blocks(&start, &var.shape) {
+        let values = dataset.read_masked(&var.name, &block_start, &block_count).ok()?;
+        for x in values.into_iter().filter(|x| x.is_finite()) {
+            range = Some(match range {
+                Some((lo, hi)) => (lo.min(x), hi.max(x)),
+                None => (x, x),
+            });
+        }
+    }
+    range
+}
+
+fn insert_file(
+    transaction: &Transaction,
+    path: &str,
+    (size, modified): (i64, i64),
+    file: &FileSummary,
+) -> Result<(), NetCDFError> {
+    let (time_start, time_end) = file.time.unzip();
+    let extent = file.extent.as_ref();
+    transaction.execute(
+        "INSERT INTO files (path, size, modified, time_start, time_end, south, west, north, east)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            path,
+            size,
+            modified,
+            time_start,
+            time_end,
+            extent.map(|e| e.south),
+            extent.map(|e| e.west),
+            extent.map(|e| e.north),
+            extent.map(|e| e.east),
+        ],
+    )?;
+    let id = transaction.last_insert_rowid();
+
+    let mut variables = transaction.prepare_cached(
+        "INSERT INTO variables (file_id, name, standard_name, long_name, units, dimensions)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for var in &file.metadata.variables {
+        let dimensions = serde_json::to_string(&var.dimensions).unwrap_or_default();
+        variables.execute(params![
+            id,
+            var.name,
+            var.attributes.get("standard_name"),
+            var.attributes.get("long_name"),
+            var.attributes.get("units"),
+            dimensions,
+        ])?;
+    }
+
+    let mut attributes =
+        transaction.prepare_cached("INSERT INTO global_attributes (file_id, name, value) VALUES (?1, ?2, ?3)")?;
+    for (name, value) in &file.metadata.global_attrs {
+        attributes.execute(params![id, name, value])?;
+    }
+    Ok(())
+}
+
+fn open_index(index_path: &str) -> Result<Connection, NetCDFError> {
+    if let Some(parent) = Path::new(index_path).parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let connection = Connection::open(index_path)?;
+    connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+    connection.execute_batch(SCHEMA)?;
+    Ok(connection)
+}
+
+/// Datasets below a directory, in path order; hidden entries are skipped
+/// and unreadable directories reported
+pub(crate) fn find_datasets(dir: &Path, found: &mut Vec<PathBuf>, failed: &mut Vec<String>) {
+    walk_dir(
+        dir,
+        &mut |path| {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            if name.starts_with('.') {
+                return false;
+            }
+            if path.is_dir() {
+                if ZarrStore::is_store(path) {
+                    found.push(path.to_path_buf());
+                    return false;
+                }
+                return true;
+            }
+            if path.extension().is_some_and(|ext| EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known))) {
+                found.push(path.to_path_buf());
+            }
+            false
+        },
+        &mut |path, e| failed.push(format!("{}: {}", path.display(), e)),
+    );
+}
+
+/// Size and modification time (seconds since 1970) that tell whether a
+/// dataset changed. For directory stores the newest top-level entry counts.
+fn file_stamp(path: &Path) -> (i64, i64) {
+    let modified = |p: &Path| {
+        std::fs::metadata(p)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs() as i64)
+    };
+    if path.is_dir() {
+        let newest = std::fs::read_dir(path)
+            .map(|entries| entries.filter_map(|e| e.ok()).map(|e| modified(&e.path())).max().unwrap_or(0))
+            .unwrap_or(0);
+        return (0, newest.max(modified(path)));
+    }
+    let size = std::fs::metadata(path).map_or(0, |m| m.len() as i64);
+    (size, modified(path))
+}
+
+fn unix_seconds(time: NaiveDateTime) -> f64 {
+    time.and_utc().timestamp_millis() as f64 / 1000.0
+}
+
+fn iso_time(seconds: f64) -> Option<String> {
+    DateTime::from_timestamp_millis((seconds * 1000.0).round() as i64).map(|t| format_iso(t.naive_utc()))
+}
+
+/// Seconds since 1970 of a query date; a date without a time of day
+/// ending a period includes that whole day
+fn query_time(text: &str, end: bool) -> Result<f64, NetCDFError> {
+    let time = parse_iso(text)
+        .ok_or_else(|| NetCDFError::ConversionError(format!("Invalid date '{}'", text)))?;
+    let date_only = !text.trim().contains(['T', 't', ' ', ':']);
+    let time = match (end, date_only) {
+        (true, true) => time + Duration::days(1) - Duration::milliseconds(1),
+        _ => time,
+    };
+    Ok(unix_seconds(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netcdf::test_util::{self, write_array};
+
+    /// The shared fixture plus `lat(lat)`, `lon(lon)` and `sst(time, lat, lon)`
+    fn write_store(root: &Path, time_units: &str, times: &[f64], lats: &[f64], lons: &[f64]) {
+        let bytes = |values: &[f64]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        test_util::write_store(root, time_units, times, &vec![0.0; times.len() * 2]);
+        write_array(root, "lat", &["lat"], &[lats.len()], "<f8", r#""units": "degrees_north""#, bytes(lats));
+        write_array(root, "lon", &["lon"], &[lons.len()], "<f8", r#""units": "degrees_east""#, bytes(lons));
+        write_array(
+            root,
+            "sst",
+            &["time", "lat", "lon"],
+            &[times.len(), lats.len(), lons.len()],
+            "<f8",
+            r#""standard_name": "sea_surface_temperature", "units": "K""#,
+            bytes(&vec![290.0; times.len() * lats.len() * lons.len()]),
+        );
+    }
+
+    #[test]
+    fn test_scan_and_query() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let data = dir.join("data");
+        write_store(&data.join("2015/sst.zarr"), "days since 2015-01-01", &[0.0, 180.0], &[10.0, 20.0], &[350.0, 355.0]);
+        write_store(&data.join("2016/sst.zarr"), "days since 2016-01-01", &[0.0, 180.0], &[-60.0, -50.0], &[10.0, 20.0]);
+        std::fs::write(data.join("broken.nc"), b"not a netcdf file").unwrap();
+        let index = dir.join("catalog.sqlite").to_string_lossy().into_owned();
+
+        let summary = scan_directory(&data.to_string_lossy(), &index).unwrap();
+        assert_eq!((summary.indexed, summary.unchanged, summary.failed.len()), (2, 0, 1));
+
+        let query = CatalogQuery {
+            variable: Some("sea_surface_temperature".to_string()),
+            time_start: Some("2015-01-01".to_string()),
+            time_end: Some("2015-12-31".to_string()),
+            extent: Some(GeoExtent { south: 0.0, west: -20.0, north: 30.0, east: 0.0 }),
+            ..Default::default()
+        };
+        let entries = query_catalog(&index, &query).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert!(entry.path.ends_with("sst.zarr") && entry.path.contains("2015"), "{}", entry.path);
+        assert_eq!(entry.time_end.as_deref(), Some("2015-06-30T00:00:00"));
+        let extent = entry.extent.as_ref().unwrap();
+        assert_eq!((extent.west, extent.east), (-10.0, -5.0));
+        let sst = entry.variables.iter().find(|v| v.name == "sst").unwrap();
+        assert_eq!(sst.dimensions, vec!["time", "lat", "lon"]);
+
+        // Both years without the spatial filter; nothing in 2017
+        let all_years = CatalogQuery { extent: None, time_end: Some("2016-12-31".to_string()), ..query.clone() };
+        assert_eq!(query_catalog(&index, &all_years).unwrap().len(), 2);
+        let later = CatalogQuery { time_start: Some("2017-01-01".to_string()), ..all_years };
+        assert!(query_catalog(&index, &later).unwrap().is_empty());
+
+        // Rescans skip unchanged files and drop deleted ones
+        std::fs::remove_dir_all(data.join("2016")).unwrap();
+        let summary = scan_directory(&data.to_string_lossy(), &index).unwrap();
+        assert_eq!((summary.indexed, summary.unchanged, summary.removed), (0, 1, 1));
+    }
+
+    #[test]
+    fn test_query_times_and_longitudes() {
+        let day = query_time("2015-12-31", true).unwrap() - query_time("2015-12-31", false).unwrap();
+        assert!((day - 86399.999).abs() < 1e-6);
+        assert_eq!(query_time("2015-12-31T06:00:00", true).unwrap(), query_time("2015-12-31 06:00", false).unwrap());
+        assert!(query_time("last year", false).is_err());
+
+        assert_eq!(normalize_longitudes(-20.0, 40.0), (-20.0, 40.0));
+        assert_eq!(normalize_longitudes(200.0, 250.0), (-160.0, -110.0));
+        assert_eq!(normalize_longitudes(0.0, 359.5), (-180.0, 180.0));
+    }
+}
@@ -0,0 +1,492 @@
+use crate::errors::NetCDFError;
+use crate::models::{CdlDumpOptions, FileMetadata, Variable};
+use super::backend::{open_dataset, DatasetBackend};
+use super::data_access::{find_variable, is_text_type};
+use netcdf::AttributeValue;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Attributes whose values have the type of their variable
+pub(crate) const VARIABLE_TYPED_ATTRS: &[&str] = &["_FillValue", "missing_value", "valid_min", "valid_max", "valid_range"];
+
+/// Data lines are wrapped after this many characters
+const LINE_WIDTH: usize = 80;
+
+/// Render a file's structure as CDL text, like `ncdump -h`.
+///
+/// Data of the variables in `options.data_variables` is appended like
+/// `ncdump -v`, up to `options.max_values` values per variable. Dimensions,
+/// variables and attributes whose names contain '/' are shown inside nested
+/// groups. Attributes are written with the types the backend reports.
+pub fn dump_cdl(path: &str, options: &CdlDumpOptions) -> Result<String, NetCDFError> {
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    for name in &options.data_variables {
+        find_variable(&metadata, name)?;
+    }
+
+    let mut out = String::new();
+    writeln!(out, "netcdf {} {{", cdl_name(&dataset_name(path))).expect("write to String");
+    write_group(&mut out, dataset.as_ref(), &metadata, options, "", 0)?;
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// Name shown after `netcdf`: the file name without its extension
+fn dataset_name(path: &str) -> String {
+    let path = Path::new(path);
+    path.file_stem()
+        .or_else(|| path.file_name())
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "dataset".to_string())
+}
+
+/// Write the contents of the group at `prefix` ("" for the root, else "a/b/")
+fn write_group(
+    out: &mut String,
+    dataset: &dyn DatasetBackend,
+    metadata: &FileMetadata,
+    options: &CdlDumpOptions,
+    prefix: &str,
+    depth: usize,
+) -> Result<(), NetCDFError> {
+    let indent = "  ".repeat(depth);
+    let in_group = |name: &str| -> Option<String> {
+        name.strip_prefix(prefix).filter(|rest| !rest.contains('/')).map(str::to_string)
+    };
+
+    let mut subgroups = BTreeSet::new();
+    let names = metadata
+        .variables
+        .iter()
+        .map(|v| v.name.as_str())
+        .chain(metadata.dimensions.iter().map(|d| d.name.as_str()))
+        .chain(metadata.global_attrs.keys().map(String::as_str));
+    for name in names {
+        if let Some((group, _)) = name.strip_prefix(prefix).and_then(|rest| rest.split_once('/')) {
+            subgroups.insert(group.to_string());
+        }
+    }
+
+    let dimensions: Vec<_> = metadata
+        .dimensions
+        .iter()
+        .filter_map(|d| in_group(&d.name).map(|short| (d, short)))
+        .collect();
+    if !dimensions.is_empty() {
+        writeln!(out, "{}dimensions:", indent).expect("write to String");
+        for (dim, short) in dimensions {
+            if dim.is_unlimited {
+                writeln!(out, "{}\t{} = UNLIMITED ; // ({} currently)", indent, cdl_name(&short), dim.size)
+            } else {
+                writeln!(out, "{}\t{} = {} ;", indent, cdl_name(&short), dim.size)
+            }
+            .expect("write to String");
+        }
+    }
+
+    let variables: Vec<(&Variable, String)> = metadata
+        .variables
+        .iter()
+        .filter_map(|v| in_group(&v.name).map(|short| (v, short)))
+        .collect();
+    if !variables.is_empty() {
+        writeln!(out, "{}variables:", indent).expect("write to String");
+    }
+    for (var, short) in &variables {
+        let dims = if var.dimensions.is_empty() {
+            String::new()
+        } else {
+            let names: Vec<String> = var.dimensions.iter().map(|d| dimension_reference(d, prefix)).collect();
+            format!("({})", names.join(", "))
+        };
+        writeln!(out, "{}\t{} {}{} ;", indent, cdl_type(&var.data_type), cdl_name(short), dims)
+            .expect("write to String");
+        for (name, value) in sorted(dataset.attributes(Some(&var.name))?) {
+            write_attribute(out, &format!("{}\t\t", indent), short, &name, &value);
+        }
+    }
+
+    let globals: Vec<(String, AttributeValue)> = sorted(dataset.attributes(None)?)
+        .into_iter()
+        .filter_map(|(name, value)| in_group(&name).map(|short| (short, value)))
+        .collect();
+    if !globals.is_empty() {
+        let scope = if depth == 0 { "global" } else { "group" };
+        writeln!(out, "\n{}// {} attributes:", indent, scope).expect("write to String");
+        for (name, value) in globals {
+            write_attribute(out, &format!("{}\t\t", indent), "", &name, &value);
+        }
+    }
+
+    let data: Vec<&(&Variable, String)> = variables
+        .iter()
+        .filter(|(v, _)| options.data_variables.contains(&v.name))
+        .collect();
+    if !data.is_empty() {
+        writeln!(out, "{}data:", indent).expect("write to String");
+        for (var, short) in data {
+            out.push('\n');
+            write_data(out, dataset, var, short, options.max_values, &indent)?;
+        }
+    }
+
+    for group in subgroups {
+        writeln!(out, "\n{}group: {} {{", indent, cdl_name(&group)).expect("write to String");
+        write_group(out, dataset, metadata, options, &format!("{}{}/", prefix, group), depth + 1)?;
+        writeln!(out, "{}  }} // group {}", indent, cdl_name(&group)).expect("write to String");
+    }
+
+    Ok(())
+}
+
+/// How a variable in the group at `prefix` refers to a dimension: by its
+/// name when defined in that group or an enclosing one, else by its full path
+fn dimension_reference(dim: &str, prefix: &str) -> String {
+    let (group, name) = match dim.rsplit_once('/') {
+        Some((group, name)) => (format!("{}/", group), name),
+        None => (String::new(), dim),
+    };
+    if prefix.starts_with(&group) {
+        cdl_name(name)
+    } else {
+        let parts: Vec<String> = dim.split('/').map(cdl_name).collect();
+        format!("/{}", parts.join("/"))
+    }
+}
+
+/// Write one `var:name = value ;` line
+fn write_attribute(out: &mut String, indent: &str, var: &str, name: &str, value: &AttributeValue) {
+    let (prefix, literal) = attribute_literal(value);
+    writeln!(out, "{}{}{}:{} = {} ;", indent, prefix, cdl_name(var), cdl_name(name), literal)
+        .expect("write to String");
+}
+
+/// CDL literal for an attribute value, with the type keyword needed in
+/// front of the name (only for string lists)
+fn attribute_literal(value: &AttributeValue) -> (&'static str, String) {
+    fn integers<T: std::fmt::Display>(values: &[T], data_type: &str) -> String {
+        let suffix = type_suffix(data_type);
+        let text: Vec<String> = values.iter().map(|v| format!("{}{}", v, suffix)).collect();
+        text.join(", ")
+    }
+    fn floats<T: Copy + Into<f64>>(values: &[T], data_type: &str) -> String {
+        let text: Vec<String> = values.iter().map(|&v| format_value(v.into(), data_type)).collect();
+        text.join(", ")
+    }
+
+    let literal = match value {
+        AttributeValue::Str(s) => quote(s),
+        AttributeValue::Strs(strings) => {
+            let quoted: Vec<String> = strings.iter().map(|s| quote(s)).collect();
+            return ("string ", quoted.join(", "));
+        }
+        AttributeValue::Schar(v) => integers(&[*v], "Basic(Byte)"),
+        AttributeValue::Schars(v) => integers(v, "Basic(Byte)"),
+        AttributeValue::Uchar(v) => integers(&[*v], "Basic(Ubyte)"),
+        AttributeValue::Uchars(v) => integers(v, "Basic(Ubyte)"),
+        AttributeValue::Short(v) => integers(&[*v], "Basic(Short)"),
+        AttributeValue::Shorts(v) => integers(v, "Basic(Short)"),
+        AttributeValue::Ushort(v) => integers(&[*v], "Basic(Ushort)"),
+        AttributeValue::Ushorts(v) => integers(v, "Basic(Ushort)"),
+        AttributeValue::Int(v) => integers(&[*v], "Basic(Int)"),
+        AttributeValue::Ints(v) => integers(v, "Basic(Int)"),
+        AttributeValue::Uint(v) => integers(&[*v], "Basic(Uint)"),
+        AttributeValue::Uints(v) => integers(v, "Basic(Uint)"),
+        AttributeValue::Longlong(v) => integers(&[*v], "Basic(Int64)"),
+        AttributeValue::Longlongs(v) => integers(v, "Basic(Int64)"),
+        AttributeValue::Ulonglong(v) => integers(&[*v], "Basic(Uint64)"),
+        AttributeValue::Ulonglongs(v) => integers(v, "Basic(Uint64)"),
+        AttributeValue::Float(v) => floats(&[*v], "Basic(Float)"),
+        AttributeValue::Floats(v) => floats(v, "Basic(Float)"),
+        AttributeValue::Double(v) => floats(&[*v], "Basic(Double)"),
+        AttributeValue::Doubles(v) => floats(v, "Basic(Double)"),
+    };
+    ("", literal)
+}
+
+/// Write the `name = v1, v2, ... ;` block of a variable
+fn write_data(
+    out: &mut String,
+    dataset: &dyn DatasetBackend,
+    var: &Variable,
+    short: &str,
+    max_values: Option<usize>,
+    indent: &str,
+) -> Result<(), NetCDFError> {
+    let text = is_text_type(&var.data_type);
+    // Character arrays give one string per row of their last dimension
+    let value_dims = if var.data_type.contains("Char") {
+        var.shape.len().saturating_sub(1)
+    } else {
+        var.shape.len()
+    };
+    let total: usize = var.shape[..value_dims].iter().product();
+    let take = max_values.map_or(total, |m| m.min(total));
+
+    // Read whole rows of the first dimension until enough values are covered
+    let mut count = var.shape.clone();
+    if value_dims > 0 {
+        let inner: usize = var.shape[1..value_dims].iter().product();
+        count[0] = take.div_ceil(inner.max(1)).min(var.shape[0]);
+    }
+    let start = vec![0; var.shape.len()];
+
+    let mut values: Vec<String> = if text {
+        dataset
+            .read_text(&var.name, &start, &count)?
+            .iter()
+            .map(|s| quote(s))
+            .collect()
+    } else {
+        let fill = dataset.fill_value(&var.name);
+        dataset
+            .read_f64(&var.name, &start, &count)?
+            .into_iter()
+            .map(|x| match fill {
+                Some(fv) if (x - fv).abs() < 1e-10 => "_".to_string(),
+                _ => format_value(x, &var.data_type),
+            })
+            .collect()
+    };
+    values.truncate(take);
+
+    let lead = format!("{} {} = ", indent, cdl_name(short));
+    let mut line = lead.clone();
+    for (i, value) in values.iter().enumerate() {
+        let piece = if i + 1 < values.len() { format!("{}, ", value) } else { value.clone() };
+        if line.len() + piece.len() > LINE_WIDTH && line.len() > lead.len() {
+            out.push_str(line.trim_end());
+            out.push('\n');
+            line = format!("{}    ", indent);
+        }
+        line.push_str(&piece);
+    }
+    out.push_str(&line);
+    if take < total {
+        writeln!(out, " ; // first {} of {} values", take, total).expect("write to String");
+    } else {
+        out.push_str(" ;\n");
+    }
+    Ok(())
+}
+
+/// CDL type keyword for a data type string such as "Basic(Float)"
+fn cdl_type(data_type: &str) -> String {
+    let keyword = match data_type {
+        "Basic(Byte)" => "byte",
+        "Basic(Ubyte)" => "ubyte",
+        "Basic(Char)" => "char",
+        "Basic(Short)" => "short",
+        "Basic(Ushort)" => "ushort",
+        "Basic(Int)" => "int",
+        "Basic(Uint)" => "uint",
+        "Basic(Int64)" => "int64",
+        "Basic(Uint64)" => "uint64",
+        "Basic(Float)" => "float",
+        "Basic(Double)" => "double",
+        "String" => "string",
+        other => return other.to_lowercase(),
+    };
+    keyword.to_string()
+}
+
+/// Suffix of a CDL number literal of the given type
+fn type_suffix(data_type: &str) -> &'static str {
+    match data_type {
+        "Basic(Byte)" => "b",
+        "Basic(Ubyte)" => "ub",
+        "Basic(Short)" => "s",
+        "Basic(Ushort)" => "us",
+        "Basic(Uint)" => "u",
+        "Basic(Int64)" => "LL",
+        "Basic(Uint64)" => "ULL",
+        "Basic(Float)" => "f",
+        _ => "",
+    }
+}
+
+/// A number as a CDL literal of the given type (e.g. `1.5f`, `3s`, `2.`)
+fn format_value(x: f64, data_type: &str) -> String {
+    let suffix = type_suffix(data_type);
+
+    match data_type {
+        "Basic(Float)" | "Basic(Double)" => {
+            if x.is_nan() {
+                return format!("NaN{}", suffix);
+            }
+            if x.is_infinite() {
+                let name = if x > 0.0 { "Infinity" } else { "-Infinity" };
+                return format!("{}{}", name, suffix);
+            }
+            let mut text = if data_type == "Basic(Float)" { float_text(x as f32 as f64, true) } else { float_text(x, false) };
+            if !text.contains(['.', 'e']) {
+                text.push('.');
+            }
+            format!("{}{}", text, suffix)
+        }
+        "Basic(Uint64)" => format!("{}{}", x as u64, suffix),
+        _ => format!("{}{}", x as i64, suffix),
+    }
+}
+
+/// Shortest text of a float, in exponent form when very large or small
+fn float_text(x: f64, single: bool) -> String {
+    let exponent = x != 0.0 && (x.abs() >= 1e7 || x.abs() < 1e-4);
+    match (exponent, single) {
+        (true, true) => format!("{:e}", x as f32),
+        (true, false) => format!("{:e}", x),
+        (false, true) => format!("{}", x as f32),
+        (false, false) => format!("{}", x),
+    }
+}
+
+/// A CDL string literal
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\0' => out.push_str("\\0"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Escape characters CDL does not allow in names (e.g. spaces)
+fn cdl_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for (i, c) in name.chars().enumerate() {
+        let plain = c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '+' | '@');
+        if !plain || (i == 0 && c.is_ascii_digit()) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn sorted(mut attrs: Vec<(String, AttributeValue)>) -> Vec<(String, AttributeValue)> {
+    attrs.sort_by(|a, b| a.0.cmp(&b.0));
+    attrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Dimension;
+    use crate::netcdf::data_access::NativeValues;
+    use std::collections::HashMap;
+
+    /// A dataset whose variable `t` holds 0, 1, 2, ... with -1 as fill value
+    struct MemoryDataset(FileMetadata);
+
+    impl DatasetBackend for MemoryDataset {
+        fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+            Ok(self.0.clone())
+        }
+
+        fn read_native(&self, _: &str, _: &[usize], count: &[usize]) -> Result<NativeValues, NetCDFError> {
+            let n: usize = count.iter().product();
+            Ok(NativeValues::F32((0..n).map(|i| if i == 1 { -1.0 } else { i as f32 }).collect()))
+        }
+
+        fn read_text(&self, _: &str, _: &[usize], _: &[usize]) -> Result<Vec<String>, NetCDFError> {
+            Ok(Vec::new())
+        }
+
+        fn fill_value(&self, _: &str) -> Option<f64> {
+            Some(-1.0)
+        }
+    }
+
+    #[test]
+    fn test_write_groups_and_data() {
+        let variable = |name: &str, attributes: &[(&str, &str)]| Variable {
+            name: name.to_string(),
+            data_type: "Basic(Float)".to_string(),
+            dimensions: vec!["time".to_string(), "x".to_string()],
+            shape: vec![2, 3],
+            attributes: attributes.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        };
+        let metadata = FileMetadata {
+            file_path: "test.nc".to_string(),
+            dimensions: vec![
+                Dimension { name: "time".to_string(), size: 2, is_unlimited: true },
+                Dimension { name: "x".to_string(), size: 3, is_unlimited: false },
+                Dimension { name: "obs/n".to_string(), size: 4, is_unlimited: false },
+            ],
+            variables: vec![
+                variable("t", &[("units", "K"), ("_FillValue", "-1")]),
+                Variable {
+                    dimensions: vec!["time".to_string(), "obs/n".to_string()],
+                    shape: vec![2, 4],
+                    ..variable("obs/t", &[])
+                },
+            ],
+            global_attrs: HashMap::from([
+                ("title".to_string(), "test".to_string()),
+                ("obs/source".to_string(), "station".to_string()),
+            ]),
+            coordinates: None,
+        };
+        let options = CdlDumpOptions {
+            data_variables: vec!["t".to_string()],
+            max_values: Some(4),
+        };
+
+        let mut out = String::new();
+        let dataset = MemoryDataset(metadata.clone());
+        write_group(&mut out, &dataset, &metadata, &options, "", 0).unwrap();
+        assert_eq!(
+            out,
+            "dimensions:\n\ttime = UNLIMITED ; // (2 currently)\n\tx = 3 ;\n\
+             variables:\n\tfloat t(time, x) ;\n\t\tt:_FillValue = -1.f ;\n\t\tt:units = \"K\" ;\n\
+             \n// global attributes:\n\t\t:title = \"test\" ;\n\
+             data:\n\n t = 0.f, _, 2.f, 3.f ; // first 4 of 6 values\n\
+             \ngroup: obs {\n  dimensions:\n  \tn = 4 ;\n  variables:\n  \tfloat t(time, n) ;\n\
+             \n  // group attributes:\n  \t\t:source = \"station\" ;\n  } // group obs\n"
+        );
+    }
+
+    #[test]
+    fn test_attribute_literals() {
+        let literal = |value: AttributeValue| attribute_literal(&value);
+        assert_eq!(literal(AttributeValue::Str("K".to_string())), ("", "\"K\"".to_string()));
+        // Numeric-looking text stays a string
+        assert_eq!(literal(AttributeValue::Str("1".to_string())), ("", "\"1\"".to_string()));
+        assert_eq!(literal(AttributeValue::Int(3)), ("", "3".to_string()));
+        assert_eq!(literal(AttributeValue::Double(0.5)), ("", "0.5".to_string()));
+        assert_eq!(literal(AttributeValue::Float(-999.0)), ("", "-999.f".to_string()));
+        assert_eq!(literal(AttributeValue::Shorts(vec![0, 100])), ("", "0s, 100s".to_string()));
+        assert_eq!(literal(AttributeValue::Longlong(i64::MAX)), ("", "9223372036854775807LL".to_string()));
+        assert_eq!(
+            literal(AttributeValue::Strs(vec!["a".to_string(), "b,c".to_string()])),
+            ("string ", "\"a\", \"b,c\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_dimension_references() {
+        assert_eq!(dimension_reference("time", "obs/"), "time");
+        assert_eq!(dimension_reference("obs/n", "obs/inner/"), "n");
+        assert_eq!(dimension_reference("obs/n", ""), "/obs/n");
+        assert_eq!(dimension_reference("other/n", "obs/"), "/other/n");
+    }
+
+    #[test]
+    fn test_value_formatting() {
+        assert_eq!(format_value(9.969209968386869e36, "Basic(Float)"), "9.96921e36f");
+        assert_eq!(format_value(1.0, "Basic(Double)"), "1.");
+        assert_eq!(format_value(-3.0, "Basic(Byte)"), "-3b");
+        assert_eq!(cdl_name("Data Fields"), "Data\\ Fields");
+        assert_eq!(cdl_name("2m_temp"), "\\2m_temp");
+    }
+}
@@ -0,0 +1,724 @@
+//! CF conventions compliance checks on file metadata.
+//!
+//! Only metadata is inspected, no data is read. Fill values and valid ranges
+//! are checked against the attribute types the backend reports, and by
+//! whether the variable's type can hold their values.
+
+use crate::errors::NetCDFError;
+use crate::models::{CfIssue, CfReport, CfSeverity, FileMetadata, Variable};
+use super::backend::open_dataset;
+use super::data_access::is_text_type;
+use super::time::CfTimeUnits;
+use netcdf::AttributeValue;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+const BUNDLED_NAMES: &str = include_str!("cf_standard_names.txt");
+
+/// Calendars defined in section 4.4.1
+const CALENDARS: &[&str] = &[
+    "standard", "gregorian", "proleptic_gregorian", "julian", "noleap", "365_day", "all_leap",
+    "366_day", "360_day", "none", "utc", "tai",
+];
+
+/// Methods allowed in `cell_methods` (appendix E)
+const CELL_METHODS: &[&str] = &[
+    "point", "sum", "maximum", "maximum_absolute_value", "median", "mid_range", "minimum",
+    "minimum_absolute_value", "mean", "mean_absolute_value", "mean_of_lower_decile",
+    "mean_of_upper_decile", "mode", "range", "root_mean_square", "standard_deviation",
+    "sum_of_squares", "variance",
+];
+
+/// Standard name modifiers (appendix C)
+const NAME_MODIFIERS: &[&str] = &["detection_minimum", "number_of_observations", "standard_error", "status_flag"];
+
+/// Attributes that must be representable in the variable's type (section 2.5.1)
+const TYPED_ATTRIBUTES: &[&str] = &["_FillValue", "missing_value", "valid_min", "valid_max", "valid_range"];
+
+struct StandardNames {
+    source: String,
+    names: HashSet<String>,
+}
+
+/// The bundled table, or the table named by `NCV_CF_STANDARD_NAMES`
+fn standard_names() -> &'static StandardNames {
+    static NAMES: OnceLock<StandardNames> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        // The first line names the table version ("# CF Standard Name Table version 84: ...")
+        let header = BUNDLED_NAMES.lines().next().unwrap_or_default().trim_start_matches('#').trim();
+        let bundled = format!("bundled: {}", header.split(':').next().unwrap_or(header));
+        let source = match std::env::var("NCV_CF_STANDARD_NAMES") {
+            Ok(path) => match std::fs::read_to_string(&path) {
+                Ok(text) => {
+                    return StandardNames { source: path, names: parse_name_table(&text) };
+                }
+                Err(e) => format!("{} ({} could not be read: {})", bundled, path, e),
+            },
+            Err(_) => bundled,
+        };
+        StandardNames { source, names: parse_name_table(BUNDLED_NAMES) }
+    })
+}
+
+/// Names from the official XML table (entries and aliases) or a list of one name per line
+fn parse_name_table(text: &str) -> HashSet<String> {
+    if text.trim_start().starts_with('<') {
+        let mut names = HashSet::new();
+        for tag in ["<entry id=\"", "<alias id=\""] {
+            for (pos, _) in text.match_indices(tag) {
+                let rest = &text[pos + tag.len()..];
+                if let Some(end) = rest.find('"') {
+                    names.insert(rest[..end].to_string());
+                }
+            }
+        }
+        return names;
+    }
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Typed attributes of the variables, by variable name
+pub type VariableAttributes = HashMap<String, Vec<(String, AttributeValue)>>;
+
+/// Check a file against the CF conventions
+pub fn check_cf(path: &str) -> Result<CfReport, NetCDFError> {
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    // Only variables with attributes that must match their type are read
+    let mut typed = VariableAttributes::new();
+    for variable in &metadata.variables {
+        if TYPED_ATTRIBUTES.iter().any(|attr| variable.attributes.contains_key(*attr)) {
+            typed.insert(variable.name.clone(), dataset.attributes(Some(&variable.name))?);
+        }
+    }
+    let mut report = cf_report(&metadata, &typed);
+    report.file_path = path.to_string();
+    Ok(report)
+}
+
+/// Check already loaded metadata and count the findings
+pub fn cf_report(metadata: &FileMetadata, typed: &VariableAttributes) -> CfReport {
+    let issues = check_metadata(metadata, typed);
+    let errors = issues.iter().filter(|issue| issue.severity == CfSeverity::Error).count();
+    CfReport {
+        file_path: metadata.file_path.clone(),
+        conventions: metadata.global_attrs.get("Conventions").cloned(),
+        standard_name_table: standard_names().source.clone(),
+        warnings: issues.len() - errors,
+        errors,
+        issues,
+    }
+}
+
+/// All findings for a file, global attributes first, then variable by variable.
+/// Variables missing from `typed` have their attribute types checked only
+/// through the metadata text.
+pub fn check_metadata(metadata: &FileMetadata, typed: &VariableAttributes) -> Vec<CfIssue> {
+    let mut findings = Findings::default();
+    check_conventions(metadata, &mut findings);
+
+    // Bounds variables inherit units and other metadata from their parent
+    let boundaries: HashSet<&str> = metadata
+        .variables
+        .iter()
+        .flat_map(|v| ["bounds", "climatology"].into_iter().filter_map(|attr| v.attributes.get(attr)))
+        .map(|name| name.trim())
+        .collect();
+
+    for variable in &metadata.variables {
+        check_name(variable, &mut findings);
+        if !boundaries.contains(variable.name.as_str()) {
+            check_units(variable, &mut findings);
+        }
+        check_standard_name(variable, &mut findings);
+        check_typed_attributes(variable, typed.get(&variable.name), &mut findings);
+        check_references(metadata, variable, &mut findings);
+        check_bounds(metadata, variable, &mut findings);
+        check_cell_methods(metadata, variable, &mut findings);
+    }
+    findings.issues
+}
+
+/// Render a report as text, one finding per line
+pub fn format_report(report: &CfReport) -> String {
+    let mut text = format!(
+        "{}: {} error{}, {} warning{}\n  Conventions: {}\n  Standard names: {}\n",
+        report.file_path,
+        report.errors,
+        if report.errors == 1 { "" } else { "s" },
+        report.warnings,
+        if report.warnings == 1 { "" } else { "s" },
+        report.conventions.as_deref().unwrap_or("(none)"),
+        report.standard_name_table,
+    );
+    for issue in &report.issues {
+        let severity = match issue.severity {
+            CfSeverity::Error => "ERROR",
+            CfSeverity::Warning => "WARNING",
+        };
+        text.push_str(&format!(
+            "{:<8}§{:<6}{}: {}\n",
+            severity,
+            issue.section,
+            issue.variable.as_deref().unwrap_or("(global)"),
+            issue.message
+        ));
+    }
+    text
+}
+
+#[derive(Default)]
+struct Findings {
+    issues: Vec<CfIssue>,
+}
+
+impl Findings {
+    fn add(&mut self, severity: CfSeverity, section: &str, variable: Option<&Variable>, message: String) {
+        self.issues.push(CfIssue {
+            severity,
+            section: section.to_string(),
+            variable: variable.map(|v| v.name.clone()),
+            message,
+        });
+    }
+
+    fn error(&mut self, section: &str, variable: &Variable, message: String) {
+        self.add(CfSeverity::Error, section, Some(variable), message);
+    }
+
+    fn warning(&mut self, section: &str, variable: &Variable, message: String) {
+        self.add(CfSeverity::Warning, section, Some(variable), message);
+    }
+}
+
+fn find<'a>(metadata: &'a FileMetadata, name: &str) -> Option<&'a Variable> {
+    metadata.variables.iter().find(|v| v.name == name)
+}
+
+fn check_conventions(metadata: &FileMetadata, findings: &mut Findings) {
+    match metadata.global_attrs.get("Conventions") {
+        None => findings.add(
+            CfSeverity::Warning,
+            "2.6.1",
+            None,
+            "no global Conventions attribute; CF files should declare e.g. \"CF-1.11\"".to_string(),
+        ),
+        Some(conventions) => {
+            let declares_cf = conventions
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .any(|token| token.starts_with("CF-"));
+            if !declares_cf {
+                findings.add(
+                    CfSeverity::Warning,
+                    "2.6.1",
+                    None,
+                    format!("Conventions \"{}\" does not name a CF version", conventions),
+                );
+            }
+        }
+    }
+}
+
+fn check_name(variable: &Variable, findings: &mut Findings) {
+    // Group members are named by their path; only the last component matters
+    let name = variable.name.rsplit('/').next().unwrap_or(&variable.name);
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        findings.warning(
+            "2.3",
+            variable,
+            "names should begin with a letter and contain only letters, digits and underscores".to_string(),
+        );
+    }
+}
+
+fn is_time_coordinate(variable: &Variable) -> bool {
+    let attr = |name: &str| variable.attributes.get(name).map(|value| value.trim());
+    attr("standard_name") == Some("time")
+        || attr("axis").is_some_and(|axis| axis.eq_ignore_ascii_case("T"))
+        || attr("units").is_some_and(|units| units.to_lowercase().contains(" since "))
+}
+
+/// Whether time units parse, allowing a non-zero UTC offset after the reference time
+fn valid_time_units(units: &str) -> bool {
+    if CfTimeUnits::parse(units).is_some() {
+        return true;
+    }
+    let Some((rest, offset)) = units.trim().rsplit_once(' ') else { return false };
+    let is_offset = offset.starts_with(['+', '-'])
+        && offset[1..].chars().all(|c| c.is_ascii_digit() || c == ':')
+        && offset.len() > 1;
+    is_offset && CfTimeUnits::parse(rest).is_some()
+}
+
+fn check_units(variable: &Variable, findings: &mut Findings) {
+    if is_text_type(&variable.data_type) {
+        return;
+    }
+    let units = variable.attributes.get("units").map(|u| u.trim());
+
+    if is_time_coordinate(variable) {
+        match units {
+            None => findings.error("4.4", variable, "time coordinate has no units".to_string()),
+            Some(units) if !valid_time_units(units) => findings.error(
+                "4.4",
+                variable,
+                format!("malformed time units \"{}\"; expected \"<unit> since <reference date>\"", units),
+            ),
+            Some(_) => {}
+        }
+        if let Some(calendar) = variable.attributes.get("calendar") {
+            let known = CALENDARS.contains(&calendar.trim().to_lowercase().as_str());
+            if !known && !variable.attributes.contains_key("month_lengths") {
+                findings.error(
+                    "4.4.1",
+                    variable,
+                    format!("unknown calendar \"{}\" without month_lengths", calendar),
+                );
+            }
+        }
+        return;
+    }
+
+    // Flags, grid mappings and the index variables of compressed or ragged
+    // arrays have no physical units
+    let exempt = ["flag_values", "flag_masks", "grid_mapping_name", "compress", "sample_dimension", "instance_dimension"]
+        .iter()
+        .any(|attr| variable.attributes.contains_key(*attr));
+    if exempt {
+        return;
+    }
+    match units {
+        None => findings.warning(
+            "3.1",
+            variable,
+            "no units attribute; dimensional quantities must have units (\"1\" for dimensionless ones)".to_string(),
+        ),
+        Some("") => findings.warning("3.1", variable, "units attribute is empty".to_string()),
+        Some(_) => {}
+    }
+}
+
+fn check_standard_name(variable: &Variable, findings: &mut Findings) {
+    let Some(text) = variable.attributes.get("standard_name") else { return };
+    let mut words = text.split_whitespace();
+    let Some(name) = words.next() else {
+        findings.error("3.3", variable, "standard_name is empty".to_string());
+        return;
+    };
+    let modifier = words.next();
+    if words.next().is_some() {
+        findings.error(
+            "3.3",
+            variable,
+            format!("standard_name \"{}\" must be a name optionally followed by one modifier", text),
+        );
+        return;
+    }
+    if let Some(modifier) = modifier {
+        if !NAME_MODIFIERS.contains(&modifier) {
+            findings.error("3.3", variable, format!("unknown standard_name modifier \"{}\"", modifier));
+        }
+    }
+
+    let table = standard_names();
+    let well_formed = name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !well_formed {
+        findings.error(
+            "3.3",
+            variable,
+            format!("standard_name \"{}\" may only contain lower-case letters, digits and underscores", name),
+        );
+    } else if !table.names.contains(name) {
+        findings.error(
+            "3.3",
+            variable,
+            format!("standard_name \"{}\" is not in the standard name table ({})", name, table.source),
+        );
+    }
+}
+
+/// Smallest and largest value a numeric type can hold
+fn type_range(data_type: &str) -> Option<(f64, f64)> {
+    let range = match data_type {
+        "Basic(Byte)" => (i8::MIN as f64, i8::MAX as f64),
+        "Basic(Ubyte)" => (0.0, u8::MAX as f64),
+        "Basic(Short)" => (i16::MIN as f64, i16::MAX as f64),
+        "Basic(Ushort)" => (0.0, u16::MAX as f64),
+        "Basic(Int)" => (i32::MIN as f64, i32::MAX as f64),
+        "Basic(Uint)" => (0.0, u32::MAX as f64),
+        "Basic(Int64)" => (i64::MIN as f64, i64::MAX as f64),
+        "Basic(Uint64)" => (0.0, u64::MAX as f64),
+        "Basic(Float)" => (f32::MIN as f64, f32::MAX as f64),
+        "Basic(Double)" => (f64::MIN, f64::MAX),
+        _ => return None,
+    };
+    Some(range)
+}
+
+/// Data type string of an attribute value, as used for variables
+fn attribute_type(value: &AttributeValue) -> &'static str {
+    match value {
+        AttributeValue::Schar(_) | AttributeValue::Schars(_) => "Basic(Byte)",
+        AttributeValue::Uchar(_) | AttributeValue::Uchars(_) => "Basic(Ubyte)",
+        AttributeValue::Short(_) | AttributeValue::Shorts(_) => "Basic(Short)",
+        AttributeValue::Ushort(_) | AttributeValue::Ushorts(_) => "Basic(Ushort)",
+        AttributeValue::Int(_) | AttributeValue::Ints(_) => "Basic(Int)",
+        AttributeValue::Uint(_) | AttributeValue::Uints(_) => "Basic(Uint)",
+        AttributeValue::Longlong(_) | AttributeValue::Longlongs(_) => "Basic(Int64)",
+        AttributeValue::Ulonglong(_) | AttributeValue::Ulonglongs(_) => "Basic(Uint64)",
+        AttributeValue::Float(_) | AttributeValue::Floats(_) => "Basic(Float)",
+        AttributeValue::Double(_) | AttributeValue::Doubles(_) => "Basic(Double)",
+        AttributeValue::Str(_) => "Basic(Char)",
+        AttributeValue::Strs(_) => "String",
+    }
+}
+
+fn check_typed_attributes(variable: &Variable, typed: Option<&Vec<(String, AttributeValue)>>, findings: &mut Findings) {
+    let Some((min, max)) = type_range(&variable.data_type) else { return };
+    let integer = !matches!(variable.data_type.as_str(), "Basic(Float)" | "Basic(Double)");
+
+    for &attr in TYPED_ATTRIBUTES {
+        let Some(text) = variable.attributes.get(attr) else { continue };
+        let stored_type = typed
+            .and_then(|attrs| attrs.iter().find(|(name, _)| name == attr))
+            .map(|(_, value)| attribute_type(value));
+        if let Some(stored_type) = stored_type.filter(|t| *t != variable.data_type) {
+            findings.error(
+                "2.5.1",
+                variable,
+                format!(
+                    "{} is stored as {} but the variable is {}; it must have the variable's type",
+                    attr, stored_type, variable.data_type
+                ),
+            );
+            continue;
+        }
+
+        let values: Vec<&str> = match text.trim().strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            Some(list) => list.split(',').map(str::trim).collect(),
+            None => vec![text.trim()],
+        };
+        let expected = match attr {
+            "valid_range" => Some(2),
+            "missing_value" => None,
+            _ => Some(1),
+        };
+        if expected.is_some_and(|n| n != values.len()) {
+            findings.error(
+                "2.5.1",
+                variable,
+                format!("{} must have {} value(s), found {}", attr, expected.unwrap_or(1), values.len()),
+            );
+            continue;
+        }
+
+        for value in values {
+            let fits = match value.parse::<f64>() {
+                Err(_) => false,
+                Ok(v) if v.is_nan() || v.is_infinite() => !integer,
+                Ok(v) => v >= min && v <= max && (!integer || v.fract() == 0.0),
+            };
+            if !fits {
+                findings.error(
+                    "2.5.1",
+                    variable,
+                    format!(
+                        "{} value {} cannot be stored as {}; it must have the variable's type",
+                        attr, value, variable.data_type
+                    ),
+                );
+                break;
+            }
+        }
+    }
+}
+
+fn check_references(metadata: &FileMetadata, variable: &Variable, findings: &mut Findings) {
+    for (attr, section) in [("coordinates", "5"), ("ancillary_variables", "3.4"), ("grid_mapping", "5.6")] {
+        let Some(text) = variable.attributes.get(attr) else { continue };
+        // The extended grid_mapping form is "crs: lat lon crs2: x y"
+        for name in text.split_whitespace().map(|word| word.trim_end_matches(':')) {
+            if find(metadata, name).is_none() {
+                findings.error(section, variable, format!("{} names \"{}\", which is not a variable", attr, name));
+            }
+        }
+    }
+}
+
+fn check_bounds(metadata: &FileMetadata, variable: &Variable, findings: &mut Findings) {
+    for (attr, section) in [("bounds", "7.1"), ("climatology", "7.4")] {
+        let Some(name) = variable.attributes.get(attr).map(|n| n.trim()) else { continue };
+        let Some(bounds) = find(metadata, name) else {
+            findings.error(section, variable, format!("{} variable \"{}\" does not exist", attr, name));
+            continue;
+        };
+
+        let n = variable.dimensions.len();
+        if bounds.dimensions.len() != n + 1 || bounds.dimensions[..n] != variable.dimensions[..] {
+            findings.error(
+                section,
+                variable,
+                format!(
+                    "{} variable \"{}\" has dimensions ({}); expected ({}) plus a vertex dimension",
+                    attr,
+                    name,
+                    bounds.dimensions.join(", "),
+                    variable.dimensions.join(", ")
+                ),
+            );
+        } else if bounds.shape[..n] != variable.shape[..] {
+            findings.error(
+                section,
+                variable,
+                format!("{} variable \"{}\" has shape {:?}, not {:?} plus vertices", attr, name, bounds.shape, variable.shape),
+            );
+        } else if n == 1 && bounds.shape[1] != 2 {
+            findings.error(
+                section,
+                variable,
+                format!("{} of a 1-D coordinate must have 2 vertices, \"{}\" has {}", attr, name, bounds.shape[1]),
+            );
+        }
+    }
+}
+
+fn check_cell_methods(metadata: &FileMetadata, variable: &Variable, findings: &mut Findings) {
+    let Some(text) = variable.attributes.get("cell_methods") else { return };
+    let entries = match parse_cell_methods(text) {
+        Ok(entries) => entries,
+        Err(reason) => {
+            findings.error("7.3", variable, format!("invalid cell_methods \"{}\": {}", text, reason));
+            return;
+        }
+    };
+
+    let table = standard_names();
+    for name in entries.iter().flat_map(|(names, _)| names) {
+        let known = variable.dimensions.contains(name)
+            || name == "area"
+            || find(metadata, name).is_some()
+            || table.names.contains(name);
+        if !known {
+            findings.warning(
+                "7.3",
+                variable,
+                format!("cell_methods names \"{}\", which is neither a dimension, a variable nor a standard name", name),
+            );
+        }
+    }
+}
+
+/// Split `cell_methods` into its "name: [name: ...] method" entries.
+/// Qualifiers (`where`, `over`, `within`) and comments are checked but dropped.
+fn parse_cell_methods(text: &str) -> Result<Vec<(Vec<String>, String)>, String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            let mut comment = String::new();
+            for c in chars.by_ref() {
+                comment.push(c);
+                if c == ')' {
+                    break;
+                }
+            }
+            if !comment.ends_with(')') {
+                return Err("unclosed parenthesis".to_string());
+            }
+            tokens.push(comment);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '(' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if word.trim_end_matches(':').contains(':') {
+                return Err(format!("missing space after ':' in \"{}\"", word));
+            }
+            tokens.push(word);
+        }
+    }
+    if tokens.is_empty() {
+        return Err("it is empty".to_string());
+    }
+
+    let mut entries = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let mut names = Vec::new();
+        while let Some(name) = tokens.get(i).and_then(|t| t.strip_suffix(':')) {
+            if name.is_empty() {
+                return Err("empty name before ':'".to_string());
+            }
+            names.push(name.to_string());
+            i += 1;
+        }
+        let Some(method) = tokens.get(i) else {
+            return Err("missing method at the end".to_string());
+        };
+        if names.is_empty() {
+            return Err(format!("expected \"name:\" before \"{}\"", method));
+        }
+        if !CELL_METHODS.contains(&method.as_str()) {
+            return Err(format!("unknown method \"{}\"", method));
+        }
+        i += 1;
+
+        while let Some(keyword) = tokens.get(i).filter(|t| matches!(t.as_str(), "where" | "over" | "within")) {
+            match tokens.get(i + 1) {
+                Some(operand) if !operand.ends_with(':') && !operand.starts_with('(') => i += 2,
+                _ => return Err(format!("\"{}\" must be followed by a name", keyword)),
+            }
+        }
+        if tokens.get(i).is_some_and(|t| t.starts_with('(')) {
+            i += 1;
+        }
+        entries.push((names, method.clone()));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Dimension;
+    use std::collections::HashMap;
+
+    fn variable(name: &str, data_type: &str, dims: &[(&str, usize)], attrs: &[(&str, &str)]) -> Variable {
+        Variable {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            dimensions: dims.iter().map(|(d, _)| d.to_string()).collect(),
+            shape: dims.iter().map(|(_, n)| *n).collect(),
+            attributes: attrs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        }
+    }
+
+    fn metadata(variables: Vec<Variable>) -> FileMetadata {
+        FileMetadata {
+            file_path: "test.nc".to_string(),
+            dimensions: vec![
+                Dimension { name: "time".to_string(), size: 4, is_unlimited: true },
+                Dimension { name: "lat".to_string(), size: 3, is_unlimited: false },
+                Dimension { name: "nv".to_string(), size: 2, is_unlimited: false },
+            ],
+            variables,
+            global_attrs: HashMap::from([("Conventions".to_string(), "CF-1.8".to_string())]),
+            coordinates: None,
+        }
+    }
+
+    fn sections(issues: &[CfIssue], variable: &str) -> Vec<(CfSeverity, String)> {
+        issues
+            .iter()
+            .filter(|issue| issue.variable.as_deref() == Some(variable))
+            .map(|issue| (issue.severity, issue.section.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_compliant_metadata_has_no_issues() {
+        let issues = check_metadata(&metadata(vec![
+            variable("time", "Basic(Double)", &[("time", 4)], &[
+                ("standard_name", "time"),
+                ("units", "hours since 2000-01-01 00:00:00"),
+                ("calendar", "noleap"),
+                ("bounds", "time_bnds"),
+            ]),
+            variable("time_bnds", "Basic(Double)", &[("time", 4), ("nv", 2)], &[]),
+            variable("lat", "Basic(Float)", &[("lat", 3)], &[("standard_name", "latitude"), ("units", "degrees_north")]),
+            variable("tas", "Basic(Short)", &[("time", 4), ("lat", 3)], &[
+                ("standard_name", "air_temperature"),
+                ("units", "K"),
+                ("_FillValue", "-32767"),
+                ("valid_range", "[-1000, 1000]"),
+                ("cell_methods", "time: mean (interval: 1 hour) area: mean where land"),
+            ]),
+        ]), &VariableAttributes::new());
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_reports_each_kind_of_violation() {
+        let issues = check_metadata(&metadata(vec![
+            variable("time", "Basic(Double)", &[("time", 4)], &[("axis", "T"), ("units", "days after 2000"), ("bounds", "time_bnds")]),
+            variable("time_bnds", "Basic(Double)", &[("nv", 2), ("time", 4)], &[]),
+            variable("tas", "Basic(Short)", &[("time", 4), ("lat", 3)], &[
+                ("standard_name", "Air Temperature"),
+                ("coordinates", "height"),
+                ("_FillValue", "1e20"),
+                ("cell_methods", "time: average"),
+            ]),
+            variable("pr", "Basic(Float)", &[("time", 4)], &[("cell_methods", "time:mean")]),
+        ]), &VariableAttributes::new());
+        assert_eq!(
+            sections(&issues, "time"),
+            vec![(CfSeverity::Error, "4.4".to_string()), (CfSeverity::Error, "7.1".to_string())]
+        );
+        let tas = sections(&issues, "tas");
+        for section in ["3.3", "5", "2.5.1", "7.3"] {
+            assert!(tas.contains(&(CfSeverity::Error, section.to_string())), "{} missing in {:?}", section, tas);
+        }
+        assert_eq!(
+            sections(&issues, "pr"),
+            vec![(CfSeverity::Warning, "3.1".to_string()), (CfSeverity::Error, "7.3".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_unknown_standard_names_are_errors() {
+        let issues = check_metadata(&metadata(vec![
+            variable("tas", "Basic(Float)", &[("time", 4)], &[("units", "K"), ("standard_name", "air_temperatur")]),
+            variable("sst", "Basic(Float)", &[("time", 4)], &[
+                ("units", "K"),
+                ("standard_name", "sea_surface_temperature standard_error"),
+            ]),
+        ]), &VariableAttributes::new());
+        assert_eq!(sections(&issues, "tas"), vec![(CfSeverity::Error, "3.3".to_string())]);
+        assert!(sections(&issues, "sst").is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_attribute_types_come_from_the_backend() {
+        let tas = variable("tas", "Basic(Short)", &[("time", 4)], &[
+            ("units", "K"),
+            ("_FillValue", "-32767"),
+            ("valid_range", "[0, 100]"),
+        ]);
+        let typed = VariableAttributes::from([(
+            "tas".to_string(),
+            vec![
+                ("_FillValue".to_string(), AttributeValue::Int(-32767)),
+                ("valid_range".to_string(), AttributeValue::Shorts(vec![0, 100])),
+            ],
+        )]);
+        let metadata = metadata(vec![tas]);
+
+        // The text alone fits a short; the stored int does not match it
+        assert!(check_metadata(&metadata, &VariableAttributes::new()).is_empty());
+        let issues = check_metadata(&metadata, &typed);
+        assert_eq!(sections(&issues, "tas"), vec![(CfSeverity::Error, "2.5.1".to_string())]);
+        assert!(issues[0].message.starts_with("_FillValue is stored as Basic(Int)"), "{}", issues[0].message);
+    }
+
+    #[test]
+    fn test_parse_cell_methods() {
+        let entries = parse_cell_methods("lat: lon: standard_deviation time: maximum within days time: mean over days").unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], (vec!["lat".to_string(), "lon".to_string()], "standard_deviation".to_string()));
+        assert!(parse_cell_methods("mean").is_err());
+        assert!(parse_cell_methods("time: mean (comment").is_err());
+        assert!(parse_cell_methods("area: mean where").is_err());
+    }
+}
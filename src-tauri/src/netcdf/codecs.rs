@@ -0,0 +1,230 @@
+use crate::errors::NetCDFError;
+use std::io::Read;
+
+/// Blosc header flags
+const BLOSC_DOSHUFFLE: u8 = 0x1;
+const BLOSC_MEMCPYED: u8 = 0x2;
+const BLOSC_DOBITSHUFFLE: u8 = 0x4;
+const BLOSC_DONTSPLIT: u8 = 0x10;
+
+/// Blosc header size and block splitting limits
+const BLOSC_HEADER_SIZE: usize = 16;
+const BLOSC_MAX_SPLITS: usize = 16;
+const BLOSC_MIN_BUFFERSIZE: usize = 128;
+
+/// Bytes-to-bytes codecs used by Zarr chunk stores
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    Blosc,
+    Zstd,
+    Gzip,
+    Zlib,
+    /// numcodecs LZ4: a little-endian length prefix followed by an LZ4 block
+    Lz4,
+    /// Trailing CRC-32C checksum (stripped, not verified)
+    Crc32c,
+}
+
+impl Codec {
+    /// Codec from a Zarr v2 compressor `id` or Zarr v3 codec `name`
+    pub fn from_name(name: &str) -> Result<Self, NetCDFError> {
+        match name.trim_start_matches("numcodecs.") {
+            "blosc" => Ok(Codec::Blosc),
+            "zstd" => Ok(Codec::Zstd),
+            "gzip" => Ok(Codec::Gzip),
+            "zlib" => Ok(Codec::Zlib),
+            "lz4" => Ok(Codec::Lz4),
+            "crc32c" => Ok(Codec::Crc32c),
+            other => Err(NetCDFError::InvalidFormat(format!(
+                "Unsupported Zarr codec '{}'",
+                other
+            ))),
+        }
+    }
+
+    /// Decode one encoded chunk
+    pub fn decode(&self, data: Vec<u8>) -> Result<Vec<u8>, NetCDFError> {
+        match self {
+            Codec::Blosc => blosc_decompress(&data),
+            Codec::Zstd => zstd::stream::decode_all(data.as_slice()).map_err(codec_error),
+            Codec::Gzip => read_all(flate2::read::GzDecoder::new(data.as_slice())),
+            Codec::Zlib => read_all(flate2::read::ZlibDecoder::new(data.as_slice())),
+            Codec::Lz4 => {
+                if data.len() < 4 {
+                    return Err(codec_error("truncated LZ4 chunk"));
+                }
+                let size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+                lz4_flex::block::decompress(&data[4..], size).map_err(codec_error)
+            }
+            Codec::Crc32c => {
+                let len = data.len().checked_sub(4).ok_or_else(|| codec_error("truncated CRC-32C chunk"))?;
+                let mut data = data;
+                data.truncate(len);
+                Ok(data)
+            }
+        }
+    }
+}
+
+/// Decompress a Blosc (v1 format) frame.
+///
+/// Supports byte shuffle and the LZ4, Zlib and Zstd internal compressors,
+/// which cover the numcodecs and zarr-python defaults.
+pub fn blosc_decompress(data: &[u8]) -> Result<Vec<u8>, NetCDFError> {
+    if data.len() < BLOSC_HEADER_SIZE {
+        return Err(codec_error("truncated Blosc header"));
+    }
+
+    let flags = data[2];
+    let typesize = data[3] as usize;
+    let nbytes = read_u32(data, 4)? as usize;
+    let blocksize = read_u32(data, 8)? as usize;
+
+    if flags & BLOSC_MEMCPYED != 0 {
+        return data
+            .get(BLOSC_HEADER_SIZE..BLOSC_HEADER_SIZE + nbytes)
+            .map(|bytes| bytes.to_vec())
+            .ok_or_else(|| codec_error("truncated Blosc frame"));
+    }
+    if flags & BLOSC_DOBITSHUFFLE != 0 {
+        return Err(codec_error("Blosc bit-shuffle is not supported"));
+    }
+    if nbytes == 0 {
+        return Ok(Vec::new());
+    }
+    if blocksize == 0 {
+        return Err(codec_error("invalid Blosc block size"));
+    }
+
+    let compressor = flags >> 5;
+    let nblocks = nbytes.div_ceil(blocksize);
+    let mut out = vec![0u8; nbytes];
+
+    for j in 0..nblocks {
+        let bsize = blocksize.min(nbytes - j * blocksize);
+        let leftover = bsize < blocksize;
+        let split = flags & BLOSC_DONTSPLIT == 0
+            && !leftover
+            && typesize > 0
+            && typesize <= BLOSC_MAX_SPLITS
+            && bsize / typesize >= BLOSC_MIN_BUFFERSIZE;
+        let nsplits = if split { typesize } else { 1 };
+        let neblock = bsize / nsplits;
+
+        let mut pos = read_u32(data, BLOSC_HEADER_SIZE + 4 * j)? as usize;
+        let mut block = Vec::with_capacity(bsize);
+        for _ in 0..nsplits {
+            let csize = read_u32(data, pos)? as usize;
+            pos += 4;
+            let src = data
+                .get(pos..pos + csize)
+                .ok_or_else(|| codec_error("truncated Blosc block"))?;
+            pos += csize;
+
+            if csize == neblock {
+                block.extend_from_slice(src);
+            } else {
+                block.extend(blosc_decompress_split(compressor, src, neblock)?);
+            }
+        }
+        if block.len() != bsize {
+            return Err(codec_error("Blosc block decoded to the wrong size"));
+        }
+
+        if flags & BLOSC_DOSHUFFLE != 0 && typesize > 1 {
+            block = unshuffle(&block, typesize);
+        }
+        out[j * blocksize..j * blocksize + bsize].copy_from_slice(&block);
+    }
+
+    Ok(out)
+}
+
+/// Decompress one split of a Blosc block with its internal compressor
+fn blosc_decompress_split(compressor: u8, src: &[u8], size: usize) -> Result<Vec<u8>, NetCDFError> {
+    match compressor {
+        1 => lz4_flex::block::decompress(src, size).map_err(codec_error),
+        3 => read_all(flate2::read::ZlibDecoder::new(src)),
+        4 => zstd::bulk::decompress(src, size).map_err(codec_error),
+        0 => Err(codec_error("Blosc compressor 'blosclz' is not supported")),
+        2 => Err(codec_error("Blosc compressor 'snappy' is not supported")),
+        other => Err(codec_error(format!("unknown Blosc compressor {}", other))),
+    }
+}
+
+/// Undo Blosc byte shuffle: byte `j` of element `i` is stored at `j * n + i`
+fn unshuffle(block: &[u8], typesize: usize) -> Vec<u8> {
+    let n = block.len() / typesize;
+    let mut out = vec![0u8; block.len()];
+    for i in 0..n {
+        for j in 0..typesize {
+            out[i * typesize + j] = block[j * n + i];
+        }
+    }
+    // Trailing bytes that do not fill a whole element are not shuffled
+    out[n * typesize..].copy_from_slice(&block[n * typesize..]);
+    out
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, NetCDFError> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| codec_error("truncated Blosc frame"))
+}
+
+fn read_all<R: Read>(mut reader: R) -> Result<Vec<u8>, NetCDFError> {
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).map_err(codec_error)?;
+    Ok(out)
+}
+
+fn codec_error<E: std::fmt::Display>(err: E) -> NetCDFError {
+    NetCDFError::InvalidFormat(format!("Chunk decoding failed: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Byte-shuffle `data` the way Blosc does before compressing
+    fn shuffle(data: &[u8], typesize: usize) -> Vec<u8> {
+        let n = data.len() / typesize;
+        let mut out = vec![0u8; data.len()];
+        for i in 0..n {
+            for j in 0..typesize {
+                out[j * n + i] = data[i * typesize + j];
+            }
+        }
+        out[n * typesize..].copy_from_slice(&data[n * typesize..]);
+        out
+    }
+
+    #[test]
+    fn test_blosc_lz4_shuffled_frame() {
+        let values: Vec<u8> = (0..64u32).flat_map(|v| (v as f32).to_le_bytes()).collect();
+        let compressed = lz4_flex::block::compress(&shuffle(&values, 4));
+
+        let mut frame = vec![2, 1, BLOSC_DOSHUFFLE | (1 << 5), 4];
+        frame.extend((values.len() as u32).to_le_bytes());
+        frame.extend((values.len() as u32).to_le_bytes());
+        frame.extend(((BLOSC_HEADER_SIZE + 8 + compressed.len()) as u32).to_le_bytes());
+        frame.extend(((BLOSC_HEADER_SIZE + 4) as u32).to_le_bytes());
+        frame.extend((compressed.len() as u32).to_le_bytes());
+        frame.extend(&compressed);
+
+        assert_eq!(blosc_decompress(&frame).unwrap(), values);
+    }
+
+    #[test]
+    fn test_blosc_memcpyed_frame() {
+        let mut frame = vec![2, 1, BLOSC_MEMCPYED, 1, 3, 0, 0, 0, 3, 0, 0, 0, 19, 0, 0, 0];
+        frame.extend([7, 8, 9]);
+        assert_eq!(blosc_decompress(&frame).unwrap(), vec![7, 8, 9]);
+    }
+
+    #[test]
+    fn test_unshuffle_keeps_trailing_bytes() {
+        let data = vec![1, 2, 3, 4, 5, 6, 7];
+        assert_eq!(unshuffle(&shuffle(&data, 2), 2), data);
+    }
+}
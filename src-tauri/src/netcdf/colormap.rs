@@ -0,0 +1,193 @@
+/// Evenly spaced RGB control points for each supported colormap
+const VIRIDIS: &[[u8; 3]] = &[
+    [68, 1, 84],
+    [72, 40, 120],
+    [62, 73, 137],
+    [49, 104, 142],
+    [38, 130, 142],
+    [31, 158, 137],
+    [53, 183, 121],
+    [110, 206, 88],
+    [253, 231, 37],
+];
+
+const PLASMA: &[[u8; 3]] = &[
+    [13, 8, 135],
+    [65, 4, 157],
+    [106, 0, 168],
+    [143, 13, 164],
+    [177, 42, 144],
+    [204, 71, 120],
+    [225, 100, 98],
+    [242, 132, 75],
+    [252, 166, 54],
+    [252, 206, 37],
+    [240, 249, 33],
+];
+
+const MAGMA: &[[u8; 3]] = &[
+    [0, 0, 4],
+    [20, 14, 54],
+    [59, 15, 112],
+    [100, 26, 128],
+    [140, 41, 129],
+    [183, 55, 121],
+    [222, 73, 104],
+    [247, 112, 92],
+    [254, 159, 109],
+    [254, 207, 146],
+    [252, 253, 191],
+];
+
+const INFERNO: &[[u8; 3]] = &[
+    [0, 0, 4],
+    [22, 11, 57],
+    [66, 10, 104],
+    [106, 23, 110],
+    [147, 38, 103],
+    [188, 55, 84],
+    [221, 81, 58],
+    [243, 120, 25],
+    [252, 165, 10],
+    [246, 215, 70],
+    [252, 255, 164],
+];
+
+const RDBU: &[[u8; 3]] = &[
+    [103, 0, 31],
+    [178, 24, 43],
+    [214, 96, 77],
+    [244, 165, 130],
+    [253, 219, 199],
+    [247, 247, 247],
+    [209, 229, 240],
+    [146, 197, 222],
+    [67, 147, 195],
+    [33, 102, 172],
+    [5, 48, 97],
+];
+
+const COOLWARM: &[[u8; 3]] = &[
+    [59, 76, 192],
+    [98, 130, 234],
+    [141, 176, 254],
+    [184, 208, 249],
+    [221, 221, 221],
+    [245, 196, 173],
+    [244, 154, 123],
+    [222, 96, 77],
+    [180, 4, 38],
+];
+
+const JET: &[[u8; 3]] = &[
+    [0, 0, 128],
+    [0, 0, 255],
+    [0, 128, 255],
+    [0, 255, 255],
+    [128, 255, 128],
+    [255, 255, 0],
+    [255, 128, 0],
+    [255, 0, 0],
+    [128, 0, 0],
+];
+
+const GRAY: &[[u8; 3]] = &[[0, 0, 0], [255, 255, 255]];
+
+/// Names accepted by `Colormap::from_name` (each also accepts an `_r` suffix)
+pub const COLORMAP_NAMES: &[&str] = &[
+    "viridis", "plasma", "magma", "inferno", "RdBu", "coolwarm", "jet", "gray",
+];
+
+/// A linearly interpolated colormap
+#[derive(Debug, Clone, Copy)]
+pub struct Colormap {
+    stops: &'static [[u8; 3]],
+    reversed: bool,
+}
+
+impl Colormap {
+    /// Look up a colormap by name (case-insensitive, `_r` reverses it)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let lower = name.to_lowercase();
+        let (base, reversed) = match lower.strip_suffix("_r") {
+            Some(base) => (base, true),
+            None => (lower.as_str(), false),
+        };
+
+        let stops = match base {
+            "viridis" => VIRIDIS,
+            "plasma" => PLASMA,
+            "magma" => MAGMA,
+            "inferno" => INFERNO,
+            "rdbu" => RDBU,
+            "coolwarm" => COOLWARM,
+            "jet" => JET,
+            "gray" | "grey" => GRAY,
+            _ => return None,
+        };
+
+        Some(Colormap { stops, reversed })
+    }
+
+    /// Colour at position `t` in [0, 1] (values outside are clamped)
+    pub fn sample(&self, t: f64) -> [u8; 3] {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let t = if self.reversed { 1.0 - t } else { t };
+
+        let segments = (self.stops.len() - 1) as f64;
+        let pos = t * segments;
+        let lower = (pos.floor() as usize).min(self.stops.len() - 2);
+        let frac = pos - lower as f64;
+
+        let a = self.stops[lower];
+        let b = self.stops[lower + 1];
+        let mut rgb = [0u8; 3];
+        for c in 0..3 {
+            rgb[c] = (a[c] as f64 + (b[c] as f64 - a[c] as f64) * frac).round() as u8;
+        }
+        rgb
+    }
+
+    /// `n` evenly spaced CSS hex colours, for drawing legends
+    pub fn hex_stops(&self, n: usize) -> Vec<String> {
+        let n = n.max(2);
+        (0..n)
+            .map(|k| {
+                let [r, g, b] = self.sample(k as f64 / (n - 1) as f64);
+                format!("#{:02x}{:02x}{:02x}", r, g, b)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoints_and_reversal() {
+        let viridis = Colormap::from_name("viridis").unwrap();
+        assert_eq!(viridis.sample(0.0), [68, 1, 84]);
+        assert_eq!(viridis.sample(1.0), [253, 231, 37]);
+
+        let reversed = Colormap::from_name("Viridis_r").unwrap();
+        assert_eq!(reversed.sample(0.0), [253, 231, 37]);
+    }
+
+    #[test]
+    fn test_interpolation_and_clamping() {
+        let gray = Colormap::from_name("gray").unwrap();
+        assert_eq!(gray.sample(0.5), [128, 128, 128]);
+        assert_eq!(gray.sample(-1.0), [0, 0, 0]);
+        assert_eq!(gray.sample(2.0), [255, 255, 255]);
+        assert_eq!(gray.hex_stops(2), vec!["#000000", "#ffffff"]);
+    }
+
+    #[test]
+    fn test_unknown_colormap() {
+        assert!(Colormap::from_name("rainbow-ish").is_none());
+        for name in COLORMAP_NAMES {
+            assert!(Colormap::from_name(name).is_some());
+        }
+    }
+}
@@ -133,7 +133,7 @@ fn detect_time_units(metadata: &FileMetadata) -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{Dimension, Variable};
+    use crate::models::Variable;
     use std::collections::HashMap;
 
     #[test]
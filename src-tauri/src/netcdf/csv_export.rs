@@ -0,0 +1,93 @@
+use crate::errors::NetCDFError;
+use crate::models::{ExportSummary, TableExportRequest};
+use super::backend::open_dataset;
+use super::table::{next_index, CoordinateColumn, CoordinateValues, TablePlan};
+use super::time::format_iso;
+
+/// Write variables as a long-form CSV/TSV table.
+///
+/// The table has one column per dimension (holding decoded coordinate
+/// values), one per auxiliary coordinate such as a curvilinear latitude,
+/// then one per variable. Rows are streamed to disk
+/// block by block, so the output may be far larger than memory.
+pub fn export_csv(path: &str, request: &TableExportRequest) -> Result<ExportSummary, NetCDFError> {
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    let plan = TablePlan::new(dataset.as_ref(), &metadata, &request.variables, &request.slices, request.decode_times)?;
+
+    let delimiter = request.delimiter.unwrap_or(',');
+    if !delimiter.is_ascii() {
+        return Err(NetCDFError::ExportError(format!(
+            "Delimiter must be a single ASCII character, got '{}'",
+            delimiter
+        )));
+    }
+    let missing = request.missing_value.clone().unwrap_or_default();
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter as u8)
+        .from_path(&request.output_path)
+        .map_err(csv_error)?;
+
+    let header = plan
+        .coordinates
+        .iter()
+        .map(|c| c.name.as_str())
+        .chain(plan.auxiliary.iter().map(|a| a.name.as_str()))
+        .chain(plan.variables.iter().map(|v| v.name.as_str()));
+    writer.write_record(header).map_err(csv_error)?;
+
+    let mut records = 0;
+    for (start, count) in plan.blocks() {
+        let auxiliary = plan.read_auxiliary(dataset.as_ref(), &start, &count)?;
+        let columns = plan.read_block(dataset.as_ref(), &start, &count)?;
+        let rows: usize = count.iter().product();
+
+        let mut index = vec![0; count.len()];
+        for row in 0..rows {
+            for (d, coord) in plan.coordinates.iter().enumerate() {
+                let offset = start[d] - plan.start[d] + index[d];
+                writer
+                    .write_field(format_coordinate(coord, offset, &missing))
+                    .map_err(csv_error)?;
+            }
+            for column in auxiliary.iter().chain(&columns) {
+                let value = column[row];
+                let field = if value.is_nan() { missing.clone() } else { value.to_string() };
+                writer.write_field(field).map_err(csv_error)?;
+            }
+            writer.write_record(None::<&[u8]>).map_err(csv_error)?;
+            next_index(&mut index, &count);
+        }
+        records += rows;
+    }
+
+    writer.flush()?;
+
+    Ok(ExportSummary {
+        output_path: request.output_path.clone(),
+        variables: plan.variables.iter().map(|v| v.name.clone()).collect(),
+        skipped: vec![],
+        records,
+        warnings: plan.warnings.clone(),
+    })
+}
+
+/// Text for a dimension column at `offset` within the selection
+fn format_coordinate(coord: &CoordinateColumn, offset: usize, missing: &str) -> String {
+    match &coord.values {
+        CoordinateValues::Index => (coord.start + offset).to_string(),
+        CoordinateValues::Numeric(values) => {
+            let value = values[offset];
+            if value.is_nan() { missing.to_string() } else { value.to_string() }
+        }
+        CoordinateValues::Time(values) => match values[offset] {
+            Some(time) => format_iso(time),
+            None => missing.to_string(),
+        },
+    }
+}
+
+pub(crate) fn csv_error(err: csv::Error) -> NetCDFError {
+    NetCDFError::ExportError(err.to_string())
+}
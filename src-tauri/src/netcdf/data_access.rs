@@ -1,37 +1,31 @@
 use crate::errors::NetCDFError;
-use crate::models::{VariableDataResponse, VariableData};
+use crate::models::{DimensionSlice, FileMetadata, Variable, VariableDataResponse, VariableData};
+use super::backend::{open_dataset, DatasetBackend};
 use netcdf::types::{VariableType, BasicType};
-use std::path::Path;
+use std::collections::HashMap;
 
 /// Get all data for a variable
 pub fn get_variable_data(
     path: &str,
     var_name: &str,
 ) -> Result<VariableDataResponse, NetCDFError> {
-    if !Path::new(path).exists() {
-        return Err(NetCDFError::FileOpenError(format!(
-            "File not found: {}",
-            path
-        )));
-    }
+    read_variable_data(open_dataset(path)?.as_ref(), var_name)
+}
 
-    let file = netcdf::open(path)?;
-    let var = file
-        .variable(var_name)
-        .ok_or_else(|| NetCDFError::VariableNotFound(var_name.to_string()))?;
+/// Read all data of a variable of an open dataset
+pub fn read_variable_data(
+    dataset: &dyn DatasetBackend,
+    var_name: &str,
+) -> Result<VariableDataResponse, NetCDFError> {
+    let var = find_variable(&dataset.metadata()?, var_name)?;
 
     // Get the shape
-    let shape: Vec<usize> = var.dimensions().iter().map(|d| d.len()).collect();
+    let shape = var.shape.clone();
+    let start = vec![0; shape.len()];
 
-    // Determine if variable is string or numeric based on type
-    let is_string_type = matches!(
-        var.vartype(),
-        VariableType::Basic(BasicType::Char) | VariableType::String
-    );
-
-    if is_string_type {
+    if is_text_type(&var.data_type) {
         // Read as string data
-        let string_values = read_variable_as_string(&var)?;
+        let string_values = dataset.read_text(var_name, &start, &shape)?;
         Ok(VariableDataResponse {
             var_name: var_name.to_string(),
             values: VariableData::Text(string_values),
@@ -40,7 +34,8 @@ pub fn get_variable_data(
         })
     } else {
         // Read as numeric data and convert to f64
-        let (numeric_values, missing_count) = read_variable_as_f64(&var)?;
+        let numeric_values = dataset.read_f64(var_name, &start, &shape)?;
+        let missing_count = count_missing(&numeric_values, dataset.fill_value(var_name));
         Ok(VariableDataResponse {
             var_name: var_name.to_string(),
             values: VariableData::Numeric(numeric_values),
@@ -57,20 +52,20 @@ pub fn get_variable_subset(
     start: &[usize],
     count: &[usize],
 ) -> Result<VariableDataResponse, NetCDFError> {
-    if !Path::new(path).exists() {
-        return Err(NetCDFError::FileOpenError(format!(
-            "File not found: {}",
-            path
-        )));
-    }
+    read_variable_subset(open_dataset(path)?.as_ref(), var_name, start, count)
+}
 
-    let file = netcdf::open(path)?;
-    let var = file
-        .variable(var_name)
-        .ok_or_else(|| NetCDFError::VariableNotFound(var_name.to_string()))?;
+/// Read a subset of a variable of an open dataset
+pub fn read_variable_subset(
+    dataset: &dyn DatasetBackend,
+    var_name: &str,
+    start: &[usize],
+    count: &[usize],
+) -> Result<VariableDataResponse, NetCDFError> {
+    let var = find_variable(&dataset.metadata()?, var_name)?;
 
     // Validate subset request
-    let ndims = var.dimensions().len();
+    let ndims = var.dimensions.len();
     if start.len() != ndims || count.len() != ndims {
         return Err(NetCDFError::InvalidSubsetRequest(format!(
             "Variable has {} dimensions, but got start={} and count={}",
@@ -80,15 +75,9 @@ pub fn get_variable_subset(
         )));
     }
 
-    // Determine if variable is string or numeric based on type
-    let is_string_type = matches!(
-        var.vartype(),
-        VariableType::Basic(BasicType::Char) | VariableType::String
-    );
-
-    if is_string_type {
+    if is_text_type(&var.data_type) {
         // Read subset as string data
-        let string_values = read_variable_subset_as_string(&var, start, count)?;
+        let string_values = dataset.read_text(var_name, start, count)?;
         Ok(VariableDataResponse {
             var_name: var_name.to_string(),
             values: VariableData::Text(string_values),
@@ -97,7 +86,8 @@ pub fn get_variable_subset(
         })
     } else {
         // Read subset as numeric data
-        let (numeric_values, missing_count) = read_variable_subset_as_f64(&var, start, count)?;
+        let numeric_values = dataset.read_f64(var_name, start, count)?;
+        let missing_count = count_missing(&numeric_values, dataset.fill_value(var_name));
         Ok(VariableDataResponse {
             var_name: var_name.to_string(),
             values: VariableData::Numeric(numeric_values),
@@ -107,102 +97,23 @@ pub fn get_variable_subset(
     }
 }
 
-/// Read entire variable as f64 array, handling different data types
-fn read_variable_as_f64(
-    var: &netcdf::Variable,
-) -> Result<(Vec<f64>, usize), NetCDFError> {
-    // Get fill value if it exists
-    let fill_value = get_fill_value(var);
+/// Look up a variable's metadata by name
+pub(crate) fn find_variable(metadata: &FileMetadata, var_name: &str) -> Result<Variable, NetCDFError> {
+    metadata
+        .variables
+        .iter()
+        .find(|v| v.name == var_name)
+        .cloned()
+        .ok_or_else(|| NetCDFError::VariableNotFound(var_name.to_string()))
+}
 
-    match var.vartype() {
-        VariableType::Basic(BasicType::Double) => {
-            let data: Vec<f64> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let missing_count = count_missing(&data, fill_value);
-            Ok((data, missing_count))
-        }
-        VariableType::Basic(BasicType::Float) => {
-            let data: Vec<f32> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let converted: Vec<f64> = data.iter().map(|&x| x as f64).collect();
-            let missing_count = count_missing(&converted, fill_value);
-            Ok((converted, missing_count))
-        }
-        VariableType::Basic(BasicType::Int) => {
-            let data: Vec<i32> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let converted: Vec<f64> = data.iter().map(|&x| x as f64).collect();
-            let missing_count = count_missing(&converted, fill_value);
-            Ok((converted, missing_count))
-        }
-        VariableType::Basic(BasicType::Short) => {
-            let data: Vec<i16> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let converted: Vec<f64> = data.iter().map(|&x| x as f64).collect();
-            let missing_count = count_missing(&converted, fill_value);
-            Ok((converted, missing_count))
-        }
-        VariableType::Basic(BasicType::Byte) => {
-            let data: Vec<i8> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let converted: Vec<f64> = data.iter().map(|&x| x as f64).collect();
-            let missing_count = count_missing(&converted, fill_value);
-            Ok((converted, missing_count))
-        }
-        VariableType::Basic(BasicType::Uint) => {
-            let data: Vec<u32> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let converted: Vec<f64> = data.iter().map(|&x| x as f64).collect();
-            let missing_count = count_missing(&converted, fill_value);
-            Ok((converted, missing_count))
-        }
-        VariableType::Basic(BasicType::Ushort) => {
-            let data: Vec<u16> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let converted: Vec<f64> = data.iter().map(|&x| x as f64).collect();
-            let missing_count = count_missing(&converted, fill_value);
-            Ok((converted, missing_count))
-        }
-        VariableType::Basic(BasicType::Ubyte) => {
-            let data: Vec<u8> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let converted: Vec<f64> = data.iter().map(|&x| x as f64).collect();
-            let missing_count = count_missing(&converted, fill_value);
-            Ok((converted, missing_count))
-        }
-        VariableType::Basic(BasicType::Int64) => {
-            let data: Vec<i64> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let converted: Vec<f64> = data.iter().map(|&x| x as f64).collect();
-            let missing_count = count_missing(&converted, fill_value);
-            Ok((converted, missing_count))
-        }
-        VariableType::Basic(BasicType::Uint64) => {
-            let data: Vec<u64> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
-            let converted: Vec<f64> = data.iter().map(|&x| x as f64).collect();
-            let missing_count = count_missing(&converted, fill_value);
-            Ok((converted, missing_count))
-        }
-        _ => Err(NetCDFError::ConversionError(format!(
-            "Unsupported variable type: {:?}",
-            var.vartype()
-        ))),
-    }
+/// Whether a variable's data type holds characters or strings
+pub(crate) fn is_text_type(data_type: &str) -> bool {
+    data_type.contains("Char") || data_type.contains("String")
 }
 
 /// Read variable subset as f64 array
-fn read_variable_subset_as_f64(
+pub(crate) fn read_variable_subset_as_f64(
     var: &netcdf::Variable,
     start: &[usize],
     count: &[usize],
@@ -302,7 +213,7 @@ fn read_variable_subset_as_f64(
 }
 
 /// Get the fill value for a variable
-fn get_fill_value(var: &netcdf::Variable) -> Option<f64> {
+pub(crate) fn get_fill_value(var: &netcdf::Variable) -> Option<f64> {
     use netcdf::AttributeValue;
 
     if let Some(attr) = var.attribute("_FillValue") {
@@ -333,55 +244,109 @@ fn count_missing(data: &[f64], fill_value: Option<f64>) -> usize {
         .count()
 }
 
-/// Read entire variable as string array
-fn read_variable_as_string(
-    var: &netcdf::Variable,
-) -> Result<Vec<String>, NetCDFError> {
-    match var.vartype() {
-        VariableType::Basic(BasicType::Char) => {
-            // For char arrays, read as bytes and convert to strings
-            let data: Vec<u8> = var
-                .get_values(..)
-                .map_err(|e| NetCDFError::VariableReadError(var.name().to_string(), e.to_string()))?;
+/// Turn per-dimension slices into start/count for a variable.
+///
+/// Dimensions without an entry in `slices` are selected whole.
+pub(crate) fn resolve_hyperslab(
+    var: &Variable,
+    slices: &HashMap<String, DimensionSlice>,
+) -> Result<(Vec<usize>, Vec<usize>), NetCDFError> {
+    let mut start = Vec::with_capacity(var.dimensions.len());
+    let mut count = Vec::with_capacity(var.dimensions.len());
 
-            // Convert bytes to string, handling multi-dimensional char arrays
-            let shape: Vec<usize> = var.dimensions().iter().map(|d| d.len()).collect();
-
-            if shape.is_empty() {
-                // Scalar char - convert single byte to string
-                Ok(vec![String::from_utf8_lossy(&data).to_string()])
-            } else if shape.len() == 1 {
-                // 1D char array - single string
-                Ok(vec![String::from_utf8_lossy(&data).trim_end_matches('\0').to_string()])
-            } else {
-                // Multi-dimensional char array - last dimension is string length
-                let str_len = shape[shape.len() - 1];
-                let num_strings = data.len() / str_len;
-                let strings: Vec<String> = (0..num_strings)
-                    .map(|i| {
-                        let start = i * str_len;
-                        let end = start + str_len;
-                        String::from_utf8_lossy(&data[start..end])
-                            .trim_end_matches('\0')
-                            .to_string()
-                    })
-                    .collect();
-                Ok(strings)
+    for (dim_name, &size) in var.dimensions.iter().zip(var.shape.iter()) {
+        match slices.get(dim_name) {
+            Some(slice) => {
+                if slice.start + slice.count > size {
+                    return Err(NetCDFError::InvalidSubsetRequest(format!(
+                        "Slice {}..{} out of range for dimension '{}' of size {}",
+                        slice.start,
+                        slice.start + slice.count,
+                        dim_name,
+                        size
+                    )));
+                }
+                start.push(slice.start);
+                count.push(slice.count);
+            }
+            None => {
+                start.push(0);
+                count.push(size);
             }
         }
+    }
+
+    Ok((start, count))
+}
+
+/// Hyperslab values in the variable's native numeric type
+#[derive(Debug, Clone)]
+pub enum NativeValues {
+    I8(Vec<i8>),
+    U8(Vec<u8>),
+    I16(Vec<i16>),
+    U16(Vec<u16>),
+    I32(Vec<i32>),
+    U32(Vec<u32>),
+    I64(Vec<i64>),
+    U64(Vec<u64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+impl NativeValues {
+    /// Convert the values to f64
+    pub fn into_f64(self) -> Vec<f64> {
+        match self {
+            NativeValues::I8(v) => v.into_iter().map(|x| x as f64).collect(),
+            NativeValues::U8(v) => v.into_iter().map(|x| x as f64).collect(),
+            NativeValues::I16(v) => v.into_iter().map(|x| x as f64).collect(),
+            NativeValues::U16(v) => v.into_iter().map(|x| x as f64).collect(),
+            NativeValues::I32(v) => v.into_iter().map(|x| x as f64).collect(),
+            NativeValues::U32(v) => v.into_iter().map(|x| x as f64).collect(),
+            NativeValues::I64(v) => v.into_iter().map(|x| x as f64).collect(),
+            NativeValues::U64(v) => v.into_iter().map(|x| x as f64).collect(),
+            NativeValues::F32(v) => v.into_iter().map(|x| x as f64).collect(),
+            NativeValues::F64(v) => v,
+        }
+    }
+}
+
+/// Read a variable subset without converting it to f64
+pub(crate) fn read_native_subset(
+    var: &netcdf::Variable,
+    start: &[usize],
+    count: &[usize],
+) -> Result<NativeValues, NetCDFError> {
+    let extents: Vec<_> = start.iter().zip(count.iter())
+        .map(|(&s, &c)| s..(s + c))
+        .collect();
+    let read_error = |e: netcdf::Error| NetCDFError::VariableReadError(var.name().to_string(), e.to_string());
+
+    let values = match var.vartype() {
+        VariableType::Basic(BasicType::Double) => NativeValues::F64(var.get_values(extents).map_err(read_error)?),
+        VariableType::Basic(BasicType::Float) => NativeValues::F32(var.get_values(extents).map_err(read_error)?),
+        VariableType::Basic(BasicType::Int) => NativeValues::I32(var.get_values(extents).map_err(read_error)?),
+        VariableType::Basic(BasicType::Short) => NativeValues::I16(var.get_values(extents).map_err(read_error)?),
+        VariableType::Basic(BasicType::Byte) => NativeValues::I8(var.get_values(extents).map_err(read_error)?),
+        VariableType::Basic(BasicType::Uint) => NativeValues::U32(var.get_values(extents).map_err(read_error)?),
+        VariableType::Basic(BasicType::Ushort) => NativeValues::U16(var.get_values(extents).map_err(read_error)?),
+        VariableType::Basic(BasicType::Ubyte) => NativeValues::U8(var.get_values(extents).map_err(read_error)?),
+        VariableType::Basic(BasicType::Int64) => NativeValues::I64(var.get_values(extents).map_err(read_error)?),
+        VariableType::Basic(BasicType::Uint64) => NativeValues::U64(var.get_values(extents).map_err(read_error)?),
         _ => {
-            // For true string types (NetCDF-4), try to read as strings directly
-            // Note: The netcdf crate may not fully support string type reading yet
-            Err(NetCDFError::ConversionError(format!(
-                "String variable reading not yet implemented for type: {:?}",
+            return Err(NetCDFError::ConversionError(format!(
+                "Unsupported variable type for subset: {:?}",
                 var.vartype()
             )))
         }
-    }
+    };
+
+    Ok(values)
 }
 
 /// Read variable subset as string array
-fn read_variable_subset_as_string(
+pub(crate) fn read_variable_subset_as_string(
     var: &netcdf::Variable,
     start: &[usize],
     count: &[usize],
@@ -0,0 +1,389 @@
+//! Virtual variables computed from the variables of a dataset.
+//!
+//! Definitions are kept per dataset path for the lifetime of the process.
+//! Datasets with definitions open wrapped in a [`DerivedDataset`], which lists
+//! the derived variables next to the real ones and evaluates them only for
+//! the hyperslab being read, one block of outer rows at a time.
+
+use crate::errors::NetCDFError;
+use crate::models::{DerivedVariableDefinition, FileMetadata, Variable};
+use super::backend::{open_source, DatasetBackend};
+use super::data_access::{find_variable, is_text_type, NativeValues};
+use super::expression::{parse_expression, Expr};
+use super::loader::text_attributes;
+use super::table::{next_index, outer_blocks};
+use netcdf::AttributeValue;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// Derived variable definitions by dataset path, in definition order
+fn registry() -> MutexGuard<'static, HashMap<String, Vec<DerivedVariableDefinition>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, Vec<DerivedVariableDefinition>>>> = OnceLock::new();
+    REGISTRY
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Derived variables defined for a dataset
+pub fn derived_variables(path: &str) -> Vec<DerivedVariableDefinition> {
+    registry().get(path).cloned().unwrap_or_default()
+}
+
+/// Define (or redefine) a derived variable and return the dataset's metadata
+/// including it.
+///
+/// The expression may use real variables and earlier derived ones. Operands
+/// broadcast by dimension name: every operand's dimensions must appear, in
+/// the same order and with the same sizes, in the operand with the most
+/// dimensions, which gives the result its shape.
+pub fn define_variable(path: &str, definition: DerivedVariableDefinition) -> Result<FileMetadata, NetCDFError> {
+    if definition.name.trim().is_empty() {
+        return Err(NetCDFError::ExpressionError("derived variable needs a name".to_string()));
+    }
+    let mut definitions = derived_variables(path);
+    for other in definitions.iter().filter(|other| other.name != definition.name) {
+        let uses = parse_expression(&other.expression)
+            .is_ok_and(|expr| expr.variables().contains(&definition.name.as_str()));
+        if uses {
+            return Err(NetCDFError::ExpressionError(format!(
+                "'{}' is used by derived variable '{}'; remove that first",
+                definition.name, other.name
+            )));
+        }
+    }
+    definitions.retain(|other| other.name != definition.name);
+    definitions.push(definition);
+
+    let metadata = DerivedDataset::new(open_source(path)?, &definitions)?.metadata()?;
+    registry().insert(path.to_string(), definitions);
+    Ok(metadata)
+}
+
+/// Remove a derived variable and return the dataset's remaining metadata
+pub fn remove_variable(path: &str, name: &str) -> Result<FileMetadata, NetCDFError> {
+    let mut definitions = derived_variables(path);
+    if !definitions.iter().any(|definition| definition.name == name) {
+        return Err(NetCDFError::VariableNotFound(name.to_string()));
+    }
+    if let Some(user) = definitions.iter().find(|definition| {
+        parse_expression(&definition.expression).is_ok_and(|expr| expr.variables().contains(&name))
+    }) {
+        return Err(NetCDFError::ExpressionError(format!(
+            "'{}' is used by derived variable '{}'; remove that first",
+            name, user.name
+        )));
+    }
+    definitions.retain(|definition| definition.name != name);
+
+    let mut registry = registry();
+    if definitions.is_empty() {
+        registry.remove(path);
+    } else {
+        registry.insert(path.to_string(), definitions.clone());
+    }
+    drop(registry);
+    DerivedDataset::new(open_source(path)?, &definitions)?.metadata()
+}
+
+/// Wrap `dataset` with the derived variables defined for `path`, if any
+pub fn with_derived(path: &str, dataset: Box<dyn DatasetBackend>) -> Result<Box<dyn DatasetBackend>, NetCDFError> {
+    let definitions = derived_variables(path);
+    if definitions.is_empty() {
+        return Ok(dataset);
+    }
+    Ok(Box::new(DerivedDataset::new(dataset, &definitions)?))
+}
+
+/// An operand of a derived variable and where its dimensions sit among the
+/// result's dimensions
+struct Operand {
+    name: String,
+    positions: Vec<usize>,
+}
+
+struct Derived {
+    expr: Expr,
+    operands: Vec<Operand>,
+}
+
+/// A dataset with derived variables added to its own
+pub struct DerivedDataset {
+    source: Box<dyn DatasetBackend>,
+    metadata: FileMetadata,
+    derived: HashMap<String, Derived>,
+}
+
+impl DerivedDataset {
+    pub fn new(source: Box<dyn DatasetBackend>, definitions: &[DerivedVariableDefinition]) -> Result<Self, NetCDFError> {
+        let mut metadata = source.metadata()?;
+        let mut derived = HashMap::new();
+        for definition in definitions {
+            let error = |reason: String| {
+                NetCDFError::ExpressionError(format!("derived variable '{}': {}", definition.name, reason))
+            };
+            if metadata.variables.iter().any(|var| var.name == definition.name) {
+                return Err(error("a variable with this name already exists".to_string()));
+            }
+            let expr = parse_expression(&definition.expression)?;
+            let mut variables = Vec::new();
+            for name in expr.variables() {
+                let var = find_variable(&metadata, name)?;
+                if is_text_type(&var.data_type) {
+                    return Err(error(format!("'{}' is a text variable", name)));
+                }
+                variables.push(var);
+            }
+            let Broadcast { dimensions, shape, positions } = broadcast(&variables).map_err(error)?;
+
+            let mut attributes = HashMap::new();
+            attributes.insert("expression".to_string(), definition.expression.clone());
+            if let Some(units) = &definition.units {
+                attributes.insert("units".to_string(), units.clone());
+            }
+            if let Some(long_name) = &definition.long_name {
+                attributes.insert("long_name".to_string(), long_name.clone());
+            }
+            metadata.variables.push(Variable {
+                name: definition.name.clone(),
+                data_type: "Basic(Double)".to_string(),
+                dimensions,
+                shape,
+                attributes,
+            });
+
+            let operands = variables
+                .into_iter()
+                .zip(positions)
+                .map(|(var, positions)| Operand { name: var.name, positions })
+                .collect();
+            derived.insert(definition.name.clone(), Derived { expr, operands });
+        }
+        Ok(DerivedDataset { source, metadata, derived })
+    }
+
+    fn evaluate(&self, derived: &Derived, start: &[usize], count: &[usize]) -> Result<Vec<f64>, NetCDFError> {
+        let mut values = Vec::with_capacity(count.iter().product());
+        for (block_start, block_count) in outer_blocks(start, count) {
+            let len = block_count.iter().product();
+            let mut operands = HashMap::new();
+            for operand in &derived.operands {
+                let start: Vec<usize> = operand.positions.iter().map(|&p| block_start[p]).collect();
+                let count: Vec<usize> = operand.positions.iter().map(|&p| block_count[p]).collect();
+                let data = self.read_masked(&operand.name, &start, &count)?;
+                operands.insert(operand.name.clone(), expand(data, &operand.positions, &block_count));
+            }
+            values.extend(derived.expr.evaluate(&operands, len));
+        }
+        Ok(values)
+    }
+}
+
+/// Shape of a derived variable and how its operands map onto it
+#[derive(Default)]
+struct Broadcast {
+    dimensions: Vec<String>,
+    shape: Vec<usize>,
+    /// Positions of each operand's dimensions among `dimensions`
+    positions: Vec<Vec<usize>>,
+}
+
+fn broadcast(variables: &[Variable]) -> Result<Broadcast, String> {
+    let Some(widest) = variables.iter().max_by_key(|var| var.dimensions.len()) else {
+        return Ok(Broadcast::default());
+    };
+    let mut all_positions = Vec::new();
+    for var in variables {
+        let mut positions = Vec::new();
+        let mut next = 0;
+        for (dim, size) in var.dimensions.iter().zip(&var.shape) {
+            let found = widest.dimensions[next..].iter().position(|d| d == dim).map(|p| p + next);
+            match found {
+                Some(p) if widest.shape[p] == *size => {
+                    positions.push(p);
+                    next = p + 1;
+                }
+                _ => {
+                    return Err(format!(
+                        "cannot broadcast {}({}) against {}({})",
+                        var.name,
+                        var.dimensions.join(", "),
+                        widest.name,
+                        widest.dimensions.join(", ")
+                    ))
+                }
+            }
+        }
+        all_positions.push(positions);
+    }
+    Ok(Broadcast {
+        dimensions: widest.dimensions.clone(),
+        shape: widest.shape.clone(),
+        positions: all_positions,
+    })
+}
+
+/// Repeat an operand's values along the result dimensions it lacks
+fn expand(values: Vec<f64>, positions: &[usize], count: &[usize]) -> Vec<f64> {
+    if positions.len() == count.len() {
+        return values;
+    }
+    let mut strides = vec![0; count.len()];
+    let mut stride = 1;
+    for &p in positions.iter().rev() {
+        strides[p] = stride;
+        stride *= count[p];
+    }
+    let len: usize = count.iter().product();
+    let mut expanded = Vec::with_capacity(len);
+    if len == 0 {
+        return expanded;
+    }
+    let mut index = vec![0; count.len()];
+    loop {
+        let offset: usize = index.iter().zip(&strides).map(|(i, s)| i * s).sum();
+        expanded.push(values[offset]);
+        if !next_index(&mut index, count) {
+            return expanded;
+        }
+    }
+}
+
+impl DatasetBackend for DerivedDataset {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        match self.derived.contains_key(var_name) {
+            true => Ok(NativeValues::F64(self.read_f64(var_name, start, count)?)),
+            false => self.source.read_native(var_name, start, count),
+        }
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        if self.derived.contains_key(var_name) {
+            return Err(NetCDFError::ConversionError(format!("'{}' is numeric", var_name)));
+        }
+        self.source.read_text(var_name, start, count)
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        // Missing values of derived variables are NaN
+        match self.derived.contains_key(var_name) {
+            true => None,
+            false => self.source.fill_value(var_name),
+        }
+    }
+
+    fn attributes(&self, var_name: Option<&str>) -> Result<Vec<(String, AttributeValue)>, NetCDFError> {
+        match var_name {
+            Some(name) if self.derived.contains_key(name) => {
+                let var = find_variable(&self.metadata, name)?;
+                Ok(text_attributes(&var.attributes, Some(&var.data_type)))
+            }
+            _ => self.source.attributes(var_name),
+        }
+    }
+
+    fn read_f64(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<f64>, NetCDFError> {
+        let Some(derived) = self.derived.get(var_name) else {
+            return self.source.read_f64(var_name, start, count);
+        };
+        let var = find_variable(&self.metadata, var_name)?;
+        if start.len() != var.shape.len() || count.len() != var.shape.len() {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Variable has {} dimensions, but got start={} and count={}",
+                var.shape.len(),
+                start.len(),
+                count.len()
+            )));
+        }
+        self.evaluate(derived, start, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netcdf::test_util::write_store;
+    use crate::netcdf::data_access::get_variable_subset;
+    use crate::models::VariableData;
+
+    fn define(path: &str, name: &str, expression: &str) -> Result<FileMetadata, NetCDFError> {
+        let definition = DerivedVariableDefinition {
+            name: name.to_string(),
+            expression: expression.to_string(),
+            units: None,
+            long_name: None,
+        };
+        define_variable(path, definition)
+    }
+
+    #[test]
+    fn test_derived_variables_read_like_real_ones() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let store = dir.join("run.zarr");
+        write_store(&store, "days since 2000-01-01", &[0.0, 1.0, 2.0], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let path = store.to_string_lossy().into_owned();
+
+        // time(time) broadcasts over tas(time, x); defined variables chain
+        let metadata = define(&path, "shifted", "tas * 10 + time").unwrap();
+        let shifted = find_variable(&metadata, "shifted").unwrap();
+        assert_eq!(shifted.dimensions, vec!["time", "x"]);
+        assert_eq!(shifted.attributes.get("expression").unwrap(), "tas * 10 + time");
+        define(&path, "half", "shifted / 2").unwrap();
+
+        let response = get_variable_subset(&path, "half", &[1, 0], &[2, 2]).unwrap();
+        match response.values {
+            VariableData::Numeric(values) => assert_eq!(values, vec![15.5, 20.5, 26.0, 31.0]),
+            _ => panic!("expected numeric data"),
+        }
+
+        assert!(define(&path, "bad", "tas + nope").is_err());
+        assert!(define(&path, "tas", "time").is_err());
+        assert!(define(&path, "shifted", "tas").is_err(), "redefining a used variable");
+        assert!(remove_variable(&path, "shifted").is_err());
+        remove_variable(&path, "half").unwrap();
+        let metadata = remove_variable(&path, "shifted").unwrap();
+        assert!(find_variable(&metadata, "shifted").is_err());
+        assert!(derived_variables(&path).is_empty());
+
+    }
+
+    #[test]
+    fn test_broadcast_rejects_mismatched_dimensions() {
+        let var = |name: &str, dims: &[&str], shape: &[usize]| Variable {
+            name: name.to_string(),
+            data_type: "Basic(Float)".to_string(),
+            dimensions: dims.iter().map(|d| d.to_string()).collect(),
+            shape: shape.to_vec(),
+            attributes: HashMap::new(),
+        };
+        let t = var("t", &["time", "lat", "lon"], &[4, 3, 2]);
+        let shape = broadcast(&[t.clone(), var("lsm", &["lat", "lon"], &[3, 2])]).unwrap();
+        assert_eq!(shape.dimensions, vec!["time", "lat", "lon"]);
+        assert_eq!(shape.positions[1], vec![1, 2]);
+        assert!(broadcast(&[t.clone(), var("w", &["lon", "lat"], &[2, 3])]).is_err());
+        assert!(broadcast(&[t, var("v", &["lat"], &[5])]).is_err());
+
+        let expanded = expand(vec![1.0, 2.0], &[1], &[3, 2]);
+        assert_eq!(expanded, vec![1.0, 2.0, 1.0, 2.0, 1.0, 2.0]);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::errors::NetCDFError;
+use crate::models::{
+    ChangeKind, DiffOptions, DiffReport, Dimension, FileMetadata, MetadataChange, SkippedVariable, Variable,
+    VariableDiff,
+};
+use super::backend::{open_dataset, DatasetBackend};
+use super::data_access::is_text_type;
+use super::table::outer_blocks;
+use super::units::{conversion, Conversion};
+use std::collections::{BTreeMap, HashMap};
+
+/// Compare two files: their dimensions, variables, attributes and data
+/// types, and the data of the variables both have.
+///
+/// Both files are read in the same blocks along the outermost dimension and
+/// compared block against block, so only one block of each is held at once.
+pub fn diff_files(path_a: &str, path_b: &str, options: &DiffOptions) -> Result<DiffReport, NetCDFError> {
+    let (a, b) = (open_dataset(path_a)?, open_dataset(path_b)?);
+    let (meta_a, meta_b) = (a.metadata()?, b.metadata()?);
+    let metadata = metadata_changes(&meta_a, &meta_b);
+
+    let mut variables = Vec::new();
+    let mut skipped = Vec::new();
+    if !options.metadata_only {
+        for name in &options.variables {
+            for meta in [&meta_a, &meta_b] {
+                if !meta.variables.iter().any(|v| &v.name == name) {
+                    return Err(NetCDFError::VariableNotFound(format!("{} in {}", name, meta.file_path)));
+                }
+            }
+        }
+
+        for var_a in &meta_a.variables {
+            if !options.variables.is_empty() && !options.variables.contains(&var_a.name) {
+                continue;
+            }
+            let Some(var_b) = meta_b.variables.iter().find(|v| v.name == var_a.name) else {
+                continue;
+            };
+            let mut skip = |reason| skipped.push(SkippedVariable { var_name: var_a.name.clone(), reason });
+            if var_a.shape != var_b.shape {
+                skip(format!("shapes differ: {:?} vs {:?}", var_a.shape, var_b.shape));
+                continue;
+            }
+            if is_text_type(&var_a.data_type) != is_text_type(&var_b.data_type) {
+                skip("text in one file and numeric in the other".to_string());
+                continue;
+            }
+            let units = (var_a.attributes.get("units"), var_b.attributes.get("units"));
+            let convert = match units {
+                (Some(units_a), Some(units_b)) if options.convert_units && units_a != units_b => {
+                    match conversion(units_b, units_a) {
+                        Ok(convert) => Some(convert),
+                        Err(e) => {
+                            skip(format!("units cannot be converted: {}", e));
+                            continue;
+                        }
+                    }
+                }
+                _ => None,
+            };
+            variables.push(compare_variable(a.as_ref(), b.as_ref(), var_a, options, convert)?);
+        }
+    }
+
+    let identical = metadata.is_empty() && skipped.is_empty() && variables.iter().all(|v| v.differing == 0);
+    Ok(DiffReport {
+        file_a: path_a.to_string(),
+        file_b: path_b.to_string(),
+        metadata,
+        variables,
+        skipped,
+        identical,
+    })
+}
+
+/// Structural differences, in the order of the first file with additions last
+fn metadata_changes(a: &FileMetadata, b: &FileMetadata) -> Vec<MetadataChange> {
+    let mut changes = Vec::new();
+
+    let dimensions = |meta: &FileMetadata| -> Vec<(String, String)> {
+        meta.dimensions.iter().map(|d| (d.name.clone(), describe_dimension(d))).collect()
+    };
+    compare_items(&mut changes, "dimension", None, &dimensions(a), &dimensions(b));
+
+    let variables = |meta: &FileMetadata| -> Vec<(String, String)> {
+        meta.variables.iter().map(|v| (v.name.clone(), describe_variable(v))).collect()
+    };
+    let (vars_a, vars_b) = (variables(a), variables(b));
+    compare_presence(&mut changes, "variable", &vars_a, &vars_b);
+
+    for var_a in &a.variables {
+        let Some(var_b) = b.variables.iter().find(|v| v.name == var_a.name) else {
+            continue;
+        };
+        let name = Some(var_a.name.as_str());
+        compare_items(
+            &mut changes,
+            "data_type",
+            name,
+            &[(var_a.name.clone(), var_a.data_type.clone())],
+            &[(var_b.name.clone(), var_b.data_type.clone())],
+        );
+        compare_items(
+            &mut changes,
+            "dimensions",
+            name,
+            &[(var_a.name.clone(), describe_shape(var_a))],
+            &[(var_b.name.clone(), describe_shape(var_b))],
+        );
+        compare_items(&mut changes, "attribute", name, &sorted(&var_a.attributes), &sorted(&var_b.attributes));
+    }
+
+    compare_items(&mut changes, "attribute", None, &sorted(&a.global_attrs), &sorted(&b.global_attrs));
+    changes
+}
+
+/// Record removed, changed and added items of two (name, value) lists
+fn compare_items(
+    changes: &mut Vec<MetadataChange>,
+    item: &str,
+    variable: Option<&str>,
+    a: &[(String, String)],
+    b: &[(String, String)],
+) {
+    let change = |name: &str, kind, old: Option<&String>, new: Option<&String>| MetadataChange {
+        item: item.to_string(),
+        name: name.to_string(),
+        variable: variable.map(str::to_string),
+        change: kind,
+        old_value: old.cloned(),
+        new_value: new.cloned(),
+    };
+    for (name, old) in a {
+        match b.iter().find(|(n, _)| n == name) {
+            None => changes.push(change(name, ChangeKind::Removed, Some(old), None)),
+            Some((_, new)) if new != old => changes.push(change(name, ChangeKind::Changed, Some(old), Some(new))),
+            Some(_) => {}
+        }
+    }
+    for (name, new) in b {
+        if !a.iter().any(|(n, _)| n == name) {
+            changes.push(change(name, ChangeKind::Added, None, Some(new)));
+        }
+    }
+}
+
+/// Record items present in only one of two lists; shared ones are compared in detail elsewhere
+fn compare_presence(changes: &mut Vec<MetadataChange>, item: &str, a: &[(String, String)], b: &[(String, String)]) {
+    let only_a: Vec<_> = a.iter().filter(|(n, _)| !b.iter().any(|(m, _)| m == n)).cloned().collect();
+    let only_b: Vec<_> = b.iter().filter(|(n, _)| !a.iter().any(|(m, _)| m == n)).cloned().collect();
+    compare_items(changes, item, None, &only_a, &only_b);
+}
+
+fn sorted(attrs: &HashMap<String, String>) -> Vec<(String, String)> {
+    attrs
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
+
+fn describe_dimension(dim: &Dimension) -> String {
+    match dim.is_unlimited {
+        true => format!("{} (unlimited)", dim.size),
+        false => dim.size.to_string(),
+    }
+}
+
+fn describe_shape(var: &Variable) -> String {
+    let dims: Vec<String> = var
+        .dimensions
+        .iter()
+        .zip(&var.shape)
+        .map(|(d, n)| format!("{}={}", d, n))
+        .collect();
+    format!("({})", dims.join(", "))
+}
+
+fn describe_variable(var: &Variable) -> String {
+    format!("{} {}", var.data_type, describe_shape(var))
+}
+
+/// Compare the data of a variable with the same shape in both files
+fn compare_variable(
+    a: &dyn DatasetBackend,
+    b: &dyn DatasetBackend,
+    var: &Variable,
+    options: &DiffOptions,
+    convert: Option<Conversion>,
+) -> Result<VariableDiff, NetCDFError> {
+    let mut accumulator = DiffAccumulator {
+        abs_tolerance: options.abs_tolerance,
+        rel_tolerance: options.rel_tolerance,
+        ..Default::default()
+    };
+    let start = vec![0; var.shape.len()];
+    if var.shape.iter().all(|&n| n > 0) {
+        for (block_start, block_count) in outer_blocks(&start, &var.shape) {
+            if is_text_type(&var.data_type) {
+                let x = a.read_text(&var.name, &block_start, &block_count)?;
+                let y = b.read_text(&var.name, &block_start, &block_count)?;
+                // Character arrays hold one string per row of the last dimension
+                let rank = match var.data_type.as_str() {
+                    "Basic(Char)" => block_count.len().saturating_sub(1),
+                    _ => block_count.len(),
+                };
+                accumulator.extend_text(&x, &y, &block_start[..rank], &block_count[..rank]);
+            } else {
+                let x = a.read_masked(&var.name, &block_start, &block_count)?;
+                let mut y = b.read_masked(&var.name, &block_start, &block_count)?;
+                if let Some(convert) = convert {
+                    y.iter_mut().for_each(|value| *value = convert.apply(*value));
+                }
+                accumulator.extend(&x, &y, &block_start, &block_count);
+            }
+        }
+    }
+    Ok(accumulator.finish(&var.name))
+}
+
+/// Running difference statistics of two arrays; NaN marks missing values
+#[derive(Debug, Default)]
+struct DiffAccumulator {
+    abs_tolerance: f64,
+    rel_tolerance: f64,
+    compared: usize,
+    differing: usize,
+    /// Pairs where both values are present
+    valid: usize,
+    max_abs: f64,
+    max_rel: f64,
+    sum_squares: f64,
+    first_difference: Option<Vec<usize>>,
+}
+
+impl DiffAccumulator {
+    /// Add a block of values starting at `start` with shape `count`
+    fn extend(&mut self, a: &[f64], b: &[f64], start: &[usize], count: &[usize]) {
+        for (offset, (&x, &y)) in a.iter().zip(b).enumerate() {
+            let equal = if x.is_nan() || y.is_nan() {
+                x.is_nan() && y.is_nan()
+            } else {
+                let diff = if x == y { 0.0 } else { (x - y).abs() };
+                let scale = x.abs().max(y.abs());
+                self.valid += 1;
+                self.sum_squares += diff * diff;
+                self.max_abs = self.max_abs.max(diff);
+                if scale > 0.0 {
+                    self.max_rel = self.max_rel.max(diff / scale);
+                }
+                diff <= self.abs_tolerance + self.rel_tolerance * scale
+            };
+            self.record(equal, offset, start, count);
+        }
+    }
+
+    fn extend_text(&mut self, a: &[String], b: &[String], start: &[usize], count: &[usize]) {
+        for (offset, (x, y)) in a.iter().zip(b).enumerate() {
+            self.record(x == y, offset, start, count);
+        }
+    }
+
+    fn record(&mut self, equal: bool, offset: usize, start: &[usize], count: &[usize]) {
+        self.compared += 1;
+        if equal {
+            return;
+        }
+        self.differing += 1;
+        if self.first_difference.is_none() {
+            self.first_difference = Some(unravel(offset, start, count));
+        }
+    }
+
+    fn finish(self, var_name: &str) -> VariableDiff {
+        let valid = self.valid > 0;
+        VariableDiff {
+            var_name: var_name.to_string(),
+            compared: self.compared,
+            differing: self.differing,
+            max_abs_diff: valid.then_some(self.max_abs),
+            max_rel_diff: valid.then_some(self.max_rel),
+            rmse: valid.then(|| (self.sum_squares / self.valid as f64).sqrt()),
+            first_difference: self.first_difference,
+        }
+    }
+}
+
+/// Absolute index of the element at `offset` in a row-major block
+fn unravel(mut offset: usize, start: &[usize], count: &[usize]) -> Vec<usize> {
+    let mut index = start.to_vec();
+    for d in (0..count.len()).rev() {
+        index[d] += offset % count[d];
+        offset /= count[d];
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulator_tolerances_and_missing_values() {
+        let mut acc = DiffAccumulator {
+            abs_tolerance: 0.01,
+            rel_tolerance: 0.1,
+            ..Default::default()
+        };
+        // Block of rows 2..4 of a (4, 3) array
+        let a = [1.0, 2.0, f64::NAN, 10.0, 0.0, 5.0];
+        let b = [1.0, 2.005, f64::NAN, 10.5, f64::NAN, 7.0];
+        acc.extend(&a, &b, &[2, 0], &[2, 3]);
+        let diff = acc.finish("x");
+
+        assert_eq!(diff.compared, 6);
+        // 0.5 is within 10% of 10.5; the one-sided NaN and 5 vs 7 are not
+        assert_eq!(diff.differing, 2);
+        assert_eq!(diff.first_difference, Some(vec![3, 1]));
+        assert_eq!(diff.max_abs_diff, Some(2.0));
+        assert!((diff.max_rel_diff.unwrap() - 2.0 / 7.0).abs() < 1e-12);
+        let rmse = ((0.005f64.powi(2) + 0.25 + 4.0) / 4.0).sqrt();
+        assert!((diff.rmse.unwrap() - rmse).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_skipped_variables_give_a_reason() {
+        use crate::netcdf::test_util::{write_array, write_store};
+        let temp = tempfile::tempdir().unwrap();
+        let (a, b) = (temp.path().join("a.zarr"), temp.path().join("b.zarr"));
+        write_store(&a, "days since 2000-01-01", &[0.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+        write_store(&b, "days since 2000-01-01", &[0.0, 1.0, 2.0], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        for (root, units) in [(&a, "K"), (&b, "m")] {
+            let attrs = format!(r#""units": "{}""#, units);
+            let bytes = [0f32, 1.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+            write_array(root, "x", &["x"], &[2], "<f4", &attrs, bytes);
+        }
+
+        let options = DiffOptions { convert_units: true, ..Default::default() };
+        let report = diff_files(a.to_str().unwrap(), b.to_str().unwrap(), &options).unwrap();
+        assert!(!report.identical);
+        let reasons: Vec<(&str, &str)> =
+            report.skipped.iter().map(|s| (s.var_name.as_str(), s.reason.as_str())).collect();
+        assert_eq!(reasons[0], ("tas", "shapes differ: [2, 2] vs [3, 2]"));
+        assert_eq!(reasons[1].0, "time");
+        assert_eq!(reasons[2].0, "x");
+        assert!(reasons[2].1.starts_with("units cannot be converted"), "{}", reasons[2].1);
+    }
+
+    #[test]
+    fn test_metadata_changes() {
+        let variable = |name: &str, data_type: &str, attrs: &[(&str, &str)]| Variable {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            dimensions: vec!["time".to_string()],
+            shape: vec![4],
+            attributes: attrs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        };
+        let metadata = |variables, size| FileMetadata {
+            file_path: String::new(),
+            dimensions: vec![Dimension { name: "time".to_string(), size, is_unlimited: true }],
+            variables,
+            global_attrs: HashMap::from([("title".to_string(), "run".to_string())]),
+            coordinates: None,
+        };
+        let a = metadata(
+            vec![variable("tas", "Basic(Float)", &[("units", "K")]), variable("pr", "Basic(Float)", &[])],
+            4,
+        );
+        let b = metadata(
+            vec![variable("tas", "Basic(Double)", &[("units", "degC")]), variable("ps", "Basic(Float)", &[])],
+            4,
+        );
+
+        let changes = metadata_changes(&a, &b);
+        let summary: Vec<(&str, &str, ChangeKind)> =
+            changes.iter().map(|c| (c.item.as_str(), c.name.as_str(), c.change)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("variable", "pr", ChangeKind::Removed),
+                ("variable", "ps", ChangeKind::Added),
+                ("data_type", "tas", ChangeKind::Changed),
+                ("attribute", "units", ChangeKind::Changed),
+            ]
+        );
+        assert_eq!(changes[3].variable.as_deref(), Some("tas"));
+        assert_eq!(changes[3].new_value.as_deref(), Some("degC"));
+        assert!(metadata_changes(&a, &a).is_empty());
+    }
+}
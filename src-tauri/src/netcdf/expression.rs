@@ -0,0 +1,383 @@
+//! Arithmetic expressions over variables, such as `sqrt(u10**2 + v10**2)`.
+//!
+//! Expressions are evaluated on whole arrays at once; missing values are NaN
+//! and propagate through every operator and function.
+
+use crate::errors::NetCDFError;
+use std::collections::HashMap;
+
+/// Functions callable in expressions, with their number of arguments
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("sqrt", 1), ("abs", 1), ("exp", 1), ("log", 1), ("log10", 1), ("sin", 1), ("cos", 1),
+    ("tan", 1), ("asin", 1), ("acos", 1), ("atan", 1), ("floor", 1), ("ceil", 1),
+    ("atan2", 2), ("hypot", 2), ("min", 2), ("max", 2),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+/// A parsed expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// A scalar or an array of the evaluated shape
+enum Value {
+    Scalar(f64),
+    Array(Vec<f64>),
+}
+
+impl Value {
+    fn map(self, f: impl Fn(f64) -> f64) -> Value {
+        match self {
+            Value::Scalar(x) => Value::Scalar(f(x)),
+            Value::Array(mut xs) => {
+                xs.iter_mut().for_each(|x| *x = f(*x));
+                Value::Array(xs)
+            }
+        }
+    }
+
+    fn zip(self, other: Value, f: impl Fn(f64, f64) -> f64) -> Value {
+        match (self, other) {
+            (Value::Scalar(x), Value::Scalar(y)) => Value::Scalar(f(x, y)),
+            (Value::Array(mut xs), Value::Scalar(y)) => {
+                xs.iter_mut().for_each(|x| *x = f(*x, y));
+                Value::Array(xs)
+            }
+            (Value::Scalar(x), Value::Array(mut ys)) => {
+                ys.iter_mut().for_each(|y| *y = f(x, *y));
+                Value::Array(ys)
+            }
+            (Value::Array(mut xs), Value::Array(ys)) => {
+                xs.iter_mut().zip(ys).for_each(|(x, y)| *x = f(*x, y));
+                Value::Array(xs)
+            }
+        }
+    }
+}
+
+impl Expr {
+    /// Names of the variables the expression reads, in order of first use
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_variables(&mut names);
+        names
+    }
+
+    fn collect_variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expr::Number(_) => {}
+            Expr::Variable(name) => {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+            Expr::Negate(inner) => inner.collect_variables(names),
+            Expr::Binary(_, left, right) => {
+                left.collect_variables(names);
+                right.collect_variables(names);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| arg.collect_variables(names)),
+        }
+    }
+
+    /// Evaluate with every variable given as an array of `len` values
+    pub fn evaluate(&self, operands: &HashMap<String, Vec<f64>>, len: usize) -> Vec<f64> {
+        match self.value(operands) {
+            Value::Scalar(x) => vec![x; len],
+            Value::Array(xs) => xs,
+        }
+    }
+
+    fn value(&self, operands: &HashMap<String, Vec<f64>>) -> Value {
+        match self {
+            Expr::Number(x) => Value::Scalar(*x),
+            Expr::Variable(name) => Value::Array(operands.get(name).cloned().unwrap_or_default()),
+            Expr::Negate(inner) => inner.value(operands).map(|x| -x),
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.value(operands), right.value(operands));
+                match op {
+                    Operator::Add => left.zip(right, |x, y| x + y),
+                    Operator::Subtract => left.zip(right, |x, y| x - y),
+                    Operator::Multiply => left.zip(right, |x, y| x * y),
+                    Operator::Divide => left.zip(right, |x, y| x / y),
+                    Operator::Power => left.zip(right, f64::powf),
+                }
+            }
+            Expr::Call(name, args) => {
+                let mut values = args.iter().map(|arg| arg.value(operands));
+                let first = values.next().unwrap_or(Value::Scalar(f64::NAN));
+                match (name.as_str(), values.next()) {
+                    ("atan2", Some(second)) => first.zip(second, f64::atan2),
+                    ("hypot", Some(second)) => first.zip(second, f64::hypot),
+                    // f64::min/max ignore NaN; missing values must stay missing
+                    ("min", Some(second)) => first.zip(second, |x, y| if x.is_nan() || y.is_nan() { f64::NAN } else { x.min(y) }),
+                    ("max", Some(second)) => first.zip(second, |x, y| if x.is_nan() || y.is_nan() { f64::NAN } else { x.max(y) }),
+                    ("sqrt", _) => first.map(f64::sqrt),
+                    ("abs", _) => first.map(f64::abs),
+                    ("exp", _) => first.map(f64::exp),
+                    ("log", _) => first.map(f64::ln),
+                    ("log10", _) => first.map(f64::log10),
+                    ("sin", _) => first.map(f64::sin),
+                    ("cos", _) => first.map(f64::cos),
+                    ("tan", _) => first.map(f64::tan),
+                    ("asin", _) => first.map(f64::asin),
+                    ("acos", _) => first.map(f64::acos),
+                    ("atan", _) => first.map(f64::atan),
+                    ("floor", _) => first.map(f64::floor),
+                    ("ceil", _) => first.map(f64::ceil),
+                    _ => first.map(|_| f64::NAN),
+                }
+            }
+        }
+    }
+}
+
+/// Parse an expression.
+///
+/// Supports numbers, variable names (in backticks when they are not plain
+/// identifiers), `+ - * /`, `**` or `^` for powers, parentheses, `pi` and
+/// the functions in [`FUNCTIONS`].
+pub fn parse_expression(text: &str) -> Result<Expr, NetCDFError> {
+    let error = |reason: String| NetCDFError::ExpressionError(format!("\"{}\": {}", text, reason));
+    let tokens = tokenize(text).map_err(error)?;
+    if tokens.is_empty() {
+        return Err(error("expression is empty".to_string()));
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.sum().map_err(error)?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(expr),
+        Some(token) => Err(error(format!("unexpected {}", token.describe()))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(x) => format!("number {}", x),
+            Token::Name(name) => format!("name \"{}\"", name),
+            Token::Symbol(symbol) => format!("'{}'", symbol),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                let sign = usize::from(matches!(chars.get(i + 1), Some('+' | '-')));
+                if chars.get(i + 1 + sign).is_some_and(|d| d.is_ascii_digit()) {
+                    i += 1 + sign;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(number.parse().map_err(|_| format!("invalid number \"{}\"", number))?));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else if c == '`' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&c| c == '`')
+                .ok_or_else(|| "unclosed backtick".to_string())?;
+            tokens.push(Token::Name(chars[i + 1..i + 1 + end].iter().collect()));
+            i += end + 2;
+        } else {
+            let symbol = match (c, chars.get(i + 1)) {
+                ('*', Some('*')) => "**",
+                ('+', _) => "+",
+                ('-', _) => "-",
+                ('*', _) => "*",
+                ('/', _) => "/",
+                ('^', _) => "^",
+                ('(', _) => "(",
+                (')', _) => ")",
+                (',', _) => ",",
+                _ => return Err(format!("unexpected character '{}'", c)),
+            };
+            i += symbol.len();
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Step over the next token if it is `symbol`
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.tokens.get(self.pos), Some(Token::Symbol(next)) if *next == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        match self.tokens.get(self.pos) {
+            Some(token) => Err(format!("expected '{}', found {}", symbol, token.describe())),
+            None => Err(format!("expected '{}' at the end", symbol)),
+        }
+    }
+
+    /// Terms joined by + and -
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        loop {
+            let op = if self.eat("+") {
+                Operator::Add
+            } else if self.eat("-") {
+                Operator::Subtract
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+        }
+    }
+
+    /// Factors joined by * and /
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                Operator::Multiply
+            } else if self.eat("/") {
+                Operator::Divide
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    /// Signs bind less tightly than powers, so -x**2 is -(x**2)
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat("+") {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    /// Powers are right-associative: a**b**c is a**(b**c)
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        if self.eat("**") || self.eat("^") {
+            return Ok(Expr::Binary(Operator::Power, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return Err("expression ends too early".to_string());
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(x) => Ok(Expr::Number(x)),
+            Token::Symbol("(") => {
+                let expr = self.sum()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Name(name) if self.eat("(") => {
+                let Some(&(_, arity)) = FUNCTIONS.iter().find(|(f, _)| *f == name) else {
+                    return Err(format!("unknown function \"{}\"", name));
+                };
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.sum()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                if args.len() != arity {
+                    return Err(format!("{} takes {} argument(s), got {}", name, arity, args.len()));
+                }
+                Ok(Expr::Call(name, args))
+            }
+            Token::Name(name) if name == "pi" => Ok(Expr::Number(std::f64::consts::PI)),
+            Token::Name(name) => Ok(Expr::Variable(name)),
+            Token::Symbol(symbol) => Err(format!("unexpected '{}'", symbol)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, operands: &[(&str, Vec<f64>)]) -> Vec<f64> {
+        let operands: HashMap<String, Vec<f64>> =
+            operands.iter().map(|(name, values)| (name.to_string(), values.clone())).collect();
+        parse_expression(text).unwrap().evaluate(&operands, 2)
+    }
+
+    #[test]
+    fn test_parse_and_evaluate() {
+        let wind = [("u10", vec![3.0, f64::NAN]), ("v10", vec![4.0, 1.0])];
+        let speed = eval("sqrt(u10**2 + v10^2)", &wind);
+        assert_eq!(speed[0], 5.0);
+        assert!(speed[1].is_nan());
+
+        assert_eq!(eval("-2**2 + 10 / 4 * 2", &[]), vec![1.0, 1.0]);
+        assert_eq!(eval("2 ** 3 ** 2", &[]), vec![512.0, 512.0]);
+        assert_eq!(eval("`tp` * 1e3", &[("tp", vec![0.001, 0.002])]), vec![1.0, 2.0]);
+        assert!(eval("max(u10, v10)", &wind)[1].is_nan());
+
+        let expr = parse_expression("atan2(u, v) * 180 / pi + u").unwrap();
+        assert_eq!(expr.variables(), vec!["u", "v"]);
+
+        for bad in ["", "u +", "sqrt(u, v)", "foo(u)", "(u", "u $ v", "u v"] {
+            assert!(parse_expression(bad).is_err(), "{}", bad);
+        }
+    }
+}
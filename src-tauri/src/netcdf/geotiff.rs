@@ -0,0 +1,486 @@
+use crate::errors::NetCDFError;
+use crate::models::{ExportSummary, FileMetadata, GeoTiffExportRequest, Variable};
+use super::backend::{open_dataset, DatasetBackend};
+use super::data_access::find_variable;
+use super::grid::{resolve_grid, LatLonGrid};
+use std::collections::HashMap;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+/// EPSG code used when a variable has plain latitude/longitude coordinates
+const WGS84: u16 = 4326;
+
+/// TIFF field types
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const DOUBLE: u16 = 12;
+
+/// Georeferencing of a north-up raster
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoTransform {
+    /// X coordinate of the outer edge of the first column
+    pub origin_x: f64,
+    /// Y coordinate of the outer edge of the first (northernmost) row
+    pub origin_y: f64,
+    /// Cell width
+    pub pixel_width: f64,
+    /// Cell height (positive; rows run north to south)
+    pub pixel_height: f64,
+}
+
+/// Everything needed to write the GeoTIFF header
+#[derive(Debug, Clone)]
+struct GeoTiffLayout {
+    width: usize,
+    height: usize,
+    bands: usize,
+    transform: GeoTransform,
+    epsg: u16,
+    geographic: bool,
+    nodata: Option<f64>,
+}
+
+/// Write a 2-D lat/lon (or projected x/y) slice of a variable as a GeoTIFF.
+///
+/// The affine transform is derived from regularly spaced coordinate vectors,
+/// the CRS is written to the GeoKeys (EPSG:4326 unless the variable's
+/// `grid_mapping` names an EPSG code), and the fill value becomes NoData.
+/// With `bands` set, successive steps along that dimension are written as
+/// separate bands.
+pub fn export_geotiff(path: &str, request: &GeoTiffExportRequest) -> Result<ExportSummary, NetCDFError> {
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    let var_meta = find_variable(&metadata, &request.var_name)?;
+
+    let (grid, epsg, geographic) = resolve_georeference(dataset.as_ref(), &metadata, &var_meta)?;
+    let transform = geo_transform(&grid)?;
+
+    let (band_dim, band_start, band_count) = match &request.bands {
+        Some(bands) => {
+            let d = var_meta
+                .dimensions
+                .iter()
+                .position(|name| name == &bands.dimension)
+                .ok_or_else(|| NetCDFError::DimensionNotFound(bands.dimension.clone()))?;
+            if d == grid.y_dim || d == grid.x_dim {
+                return Err(NetCDFError::ExportError(format!(
+                    "Band dimension '{}' is a spatial dimension",
+                    bands.dimension
+                )));
+            }
+            if bands.count == 0 || bands.start + bands.count > var_meta.shape[d] {
+                return Err(NetCDFError::InvalidSubsetRequest(format!(
+                    "Bands {}..{} out of range for dimension '{}' of size {}",
+                    bands.start,
+                    bands.start + bands.count,
+                    bands.dimension,
+                    var_meta.shape[d]
+                )));
+            }
+            (Some(bands.dimension.clone()), bands.start, bands.count)
+        }
+        None => (None, 0, 1),
+    };
+
+    let fill_value = dataset.fill_value(&request.var_name);
+    let layout = GeoTiffLayout {
+        width: grid.nx,
+        height: grid.ny,
+        bands: band_count,
+        transform,
+        epsg,
+        geographic,
+        nodata: fill_value,
+    };
+
+    let mut indices: HashMap<String, usize> = request.indices.clone();
+    write_geotiff(&request.output_path, &layout, |band| {
+        if let Some(dim) = &band_dim {
+            indices.insert(dim.clone(), band_start + band);
+        }
+        let (start, count) = grid.slice_extents(&var_meta, &indices)?;
+        let data = dataset.read_masked(&request.var_name, &start, &count)?;
+        Ok(north_up(&grid, &data, fill_value))
+    })?;
+
+    Ok(ExportSummary {
+        output_path: request.output_path.clone(),
+        variables: vec![request.var_name.clone()],
+        skipped: vec![],
+        records: grid.nx * grid.ny * band_count,
+        warnings: vec![],
+    })
+}
+
+/// Find the horizontal axes of a variable and the EPSG code of their CRS.
+///
+/// Projected grids are recognised through a `grid_mapping` variable that
+/// carries an EPSG code, with x/y axes marked by `standard_name` or `axis`.
+fn resolve_georeference(
+    dataset: &dyn DatasetBackend,
+    metadata: &FileMetadata,
+    var: &Variable,
+) -> Result<(LatLonGrid, u16, bool), NetCDFError> {
+    if let Some(epsg) = grid_mapping_epsg(metadata, var)? {
+        if epsg != WGS84 {
+            let grid = resolve_projected_grid(dataset, metadata, var)?;
+            return Ok((grid, epsg, false));
+        }
+    }
+
+    let grid = resolve_grid(dataset, metadata, var)?;
+    if grid.curvilinear {
+        return Err(NetCDFError::ExportError(format!(
+            "Variable '{}' is on a curvilinear grid; regrid it before exporting to GeoTIFF",
+            var.name
+        )));
+    }
+    Ok((grid, WGS84, true))
+}
+
+/// EPSG code declared by the variable's grid mapping, if any
+///
+/// GeoTIFF stores the code in a 16-bit GeoKey, so a declared code that is
+/// not a number or does not fit is an error rather than a silent WGS84.
+fn grid_mapping_epsg(metadata: &FileMetadata, var: &Variable) -> Result<Option<u16>, NetCDFError> {
+    // `grid_mapping` may use the extended "crs: x y" form
+    let Some(name) = var
+        .attributes
+        .get("grid_mapping")
+        .and_then(|value| value.split_whitespace().next())
+        .map(|name| name.trim_end_matches(':'))
+    else {
+        return Ok(None);
+    };
+    let Some(mapping) = metadata.variables.iter().find(|v| v.name == name) else {
+        return Ok(None);
+    };
+
+    if mapping.attributes.get("grid_mapping_name").map(|s| s.as_str()) == Some("latitude_longitude") {
+        return Ok(Some(WGS84));
+    }
+
+    let Some(value) = ["epsg_code", "EPSG_code", "epsg"].iter().find_map(|key| mapping.attributes.get(*key))
+    else {
+        return Ok(None);
+    };
+    let digits = value.trim().trim_start_matches("EPSG:").trim_start_matches("epsg:");
+    digits.parse::<u16>().map(Some).map_err(|_| {
+        NetCDFError::ExportError(format!(
+            "Grid mapping '{}' declares EPSG code '{}', which is not a 16-bit EPSG code GeoTIFF can store",
+            name, value
+        ))
+    })
+}
+
+/// Build a grid from projection x/y coordinate variables
+fn resolve_projected_grid(
+    dataset: &dyn DatasetBackend,
+    metadata: &FileMetadata,
+    var: &Variable,
+) -> Result<LatLonGrid, NetCDFError> {
+    let axis_dim = |standard_name: &str, axis: &str| {
+        var.dimensions.iter().position(|dim| {
+            metadata.variables.iter().any(|v| {
+                &v.name == dim
+                    && (v.attributes.get("standard_name").map(|s| s.as_str()) == Some(standard_name)
+                        || v.attributes.get("axis").map(|s| s.as_str()) == Some(axis))
+            })
+        })
+    };
+
+    let missing_axes = || {
+        NetCDFError::ExportError(format!(
+            "Variable '{}' has a projected grid mapping but no x/y coordinate variables",
+            var.name
+        ))
+    };
+    let x_dim = axis_dim("projection_x_coordinate", "X").ok_or_else(missing_axes)?;
+    let y_dim = axis_dim("projection_y_coordinate", "Y").ok_or_else(missing_axes)?;
+
+    let read_axis = |d: usize| dataset.read_masked(&var.dimensions[d], &[0], &[var.shape[d]]);
+
+    Ok(LatLonGrid {
+        y_dim,
+        x_dim,
+        ny: var.shape[y_dim],
+        nx: var.shape[x_dim],
+        lat: read_axis(y_dim)?,
+        lon: read_axis(x_dim)?,
+        curvilinear: false,
+    })
+}
+
+/// Affine transform of a regular grid, oriented north-up
+fn geo_transform(grid: &LatLonGrid) -> Result<GeoTransform, NetCDFError> {
+    let (x0, dx) = regular_spacing(&grid.lon).ok_or_else(|| {
+        NetCDFError::ExportError("Longitude/x coordinates are not regularly spaced".to_string())
+    })?;
+    let (y0, dy) = regular_spacing(&grid.lat).ok_or_else(|| {
+        NetCDFError::ExportError("Latitude/y coordinates are not regularly spaced".to_string())
+    })?;
+
+    let x_first = if dx > 0.0 { x0 } else { x0 + dx * (grid.nx - 1) as f64 };
+    let y_top = if dy > 0.0 { y0 + dy * (grid.ny - 1) as f64 } else { y0 };
+
+    Ok(GeoTransform {
+        origin_x: x_first - dx.abs() / 2.0,
+        origin_y: y_top + dy.abs() / 2.0,
+        pixel_width: dx.abs(),
+        pixel_height: dy.abs(),
+    })
+}
+
+/// First value and step of an evenly spaced coordinate vector
+fn regular_spacing(values: &[f64]) -> Option<(f64, f64)> {
+    if values.len() < 2 || values.iter().any(|v| !v.is_finite()) {
+        return None;
+    }
+    let step = (values[values.len() - 1] - values[0]) / (values.len() - 1) as f64;
+    if step == 0.0 {
+        return None;
+    }
+    let tolerance = step.abs() * 1e-3;
+    let regular = values
+        .windows(2)
+        .all(|w| ((w[1] - w[0]) - step).abs() <= tolerance);
+    regular.then_some((values[0], step))
+}
+
+/// Reorder a slice to north-up, west-left rows of f32, writing NoData for missing cells
+fn north_up(grid: &LatLonGrid, data: &[f64], fill_value: Option<f64>) -> Vec<f32> {
+    let flip_rows = grid.lat_ascending();
+    let flip_cols = grid.lon_descending();
+    let nodata = fill_value.unwrap_or(f64::NAN) as f32;
+
+    let mut band = Vec::with_capacity(grid.nx * grid.ny);
+    for row in 0..grid.ny {
+        let j = if flip_rows { grid.ny - 1 - row } else { row };
+        for col in 0..grid.nx {
+            let i = if flip_cols { grid.nx - 1 - col } else { col };
+            let value = data[grid.slice_index(j, i)];
+            band.push(if value.is_nan() { nodata } else { value as f32 });
+        }
+    }
+    band
+}
+
+/// A TIFF directory entry with its encoded value
+struct TiffEntry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    data: Vec<u8>,
+}
+
+impl TiffEntry {
+    fn shorts(tag: u16, values: &[u16]) -> Self {
+        let data = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        TiffEntry { tag, field_type: SHORT, count: values.len() as u32, data }
+    }
+
+    fn longs(tag: u16, values: &[u32]) -> Self {
+        let data = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        TiffEntry { tag, field_type: LONG, count: values.len() as u32, data }
+    }
+
+    fn doubles(tag: u16, values: &[f64]) -> Self {
+        let data = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        TiffEntry { tag, field_type: DOUBLE, count: values.len() as u32, data }
+    }
+
+    fn ascii(tag: u16, value: &str) -> Self {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        TiffEntry { tag, field_type: ASCII, count: data.len() as u32, data }
+    }
+}
+
+/// Write an uncompressed little-endian float32 GeoTIFF.
+///
+/// Bands are produced one at a time by `next_band` and stored as separate
+/// planes, so only a single band is held in memory.
+fn write_geotiff<F>(path: &str, layout: &GeoTiffLayout, mut next_band: F) -> Result<(), NetCDFError>
+where
+    F: FnMut(usize) -> Result<Vec<f32>, NetCDFError>,
+{
+    let band_bytes = layout.width * layout.height * 4;
+    if 8 + band_bytes as u64 * layout.bands as u64 + 4096 > u32::MAX as u64 {
+        return Err(NetCDFError::ExportError(
+            "Raster is too large for a classic TIFF file".to_string(),
+        ));
+    }
+
+    let mut out = BufWriter::new(std::fs::File::create(path)?);
+    out.write_all(b"II")?;
+    out.write_all(&42u16.to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?; // IFD offset, patched below
+
+    let mut strip_offsets = Vec::with_capacity(layout.bands);
+    let mut offset = 8u32;
+    for band in 0..layout.bands {
+        let values = next_band(band)?;
+        strip_offsets.push(offset);
+        for v in &values {
+            out.write_all(&v.to_le_bytes())?;
+        }
+        offset += band_bytes as u32;
+    }
+
+    let entries = geotiff_entries(layout, &strip_offsets, band_bytes as u32);
+    let ifd_offset = offset;
+    let extra_offset = ifd_offset + 2 + 12 * entries.len() as u32 + 4;
+
+    out.write_all(&(entries.len() as u16).to_le_bytes())?;
+    let mut extra = Vec::new();
+    for entry in &entries {
+        out.write_all(&entry.tag.to_le_bytes())?;
+        out.write_all(&entry.field_type.to_le_bytes())?;
+        out.write_all(&entry.count.to_le_bytes())?;
+        if entry.data.len() <= 4 {
+            let mut inline = entry.data.clone();
+            inline.resize(4, 0);
+            out.write_all(&inline)?;
+        } else {
+            out.write_all(&(extra_offset + extra.len() as u32).to_le_bytes())?;
+            extra.extend_from_slice(&entry.data);
+            if extra.len() % 2 == 1 {
+                extra.push(0);
+            }
+        }
+    }
+    out.write_all(&0u32.to_le_bytes())?; // no further IFDs
+    out.write_all(&extra)?;
+
+    out.seek(SeekFrom::Start(4))?;
+    out.write_all(&ifd_offset.to_le_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Baseline TIFF and GeoTIFF tags, in ascending tag order
+fn geotiff_entries(layout: &GeoTiffLayout, strip_offsets: &[u32], band_bytes: u32) -> Vec<TiffEntry> {
+    let bands = layout.bands as u16;
+    let t = &layout.transform;
+
+    let mut entries = vec![
+        TiffEntry::longs(256, &[layout.width as u32]),
+        TiffEntry::longs(257, &[layout.height as u32]),
+        TiffEntry::shorts(258, &vec![32; layout.bands]),
+        TiffEntry::shorts(259, &[1]), // no compression
+        TiffEntry::shorts(262, &[1]), // BlackIsZero
+        TiffEntry::longs(273, strip_offsets),
+        TiffEntry::shorts(277, &[bands]),
+        TiffEntry::longs(278, &[layout.height as u32]),
+        TiffEntry::longs(279, &vec![band_bytes; layout.bands]),
+        TiffEntry::shorts(284, &[if bands > 1 { 2 } else { 1 }]), // planar configuration
+    ];
+    if bands > 1 {
+        entries.push(TiffEntry::shorts(338, &vec![0; layout.bands - 1])); // extra samples
+    }
+    entries.push(TiffEntry::shorts(339, &vec![3; layout.bands])); // IEEE float samples
+
+    // ModelPixelScaleTag and ModelTiepointTag
+    entries.push(TiffEntry::doubles(33550, &[t.pixel_width, t.pixel_height, 0.0]));
+    entries.push(TiffEntry::doubles(33922, &[0.0, 0.0, 0.0, t.origin_x, t.origin_y, 0.0]));
+
+    // GeoKeyDirectoryTag: model type, raster type (PixelIsArea) and CRS
+    let (model_type, crs_key) = if layout.geographic { (2, 2048) } else { (1, 3072) };
+    entries.push(TiffEntry::shorts(
+        34735,
+        &[1, 1, 0, 3, 1024, 0, 1, model_type, 1025, 0, 1, 1, crs_key, 0, 1, layout.epsg],
+    ));
+
+    // GDAL_NODATA
+    let nodata = match layout.nodata {
+        Some(v) => format!("{}", v as f32),
+        None => "nan".to_string(),
+    };
+    entries.push(TiffEntry::ascii(42113, &nodata));
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(lat: Vec<f64>, lon: Vec<f64>) -> LatLonGrid {
+        LatLonGrid {
+            y_dim: 0,
+            x_dim: 1,
+            ny: lat.len(),
+            nx: lon.len(),
+            lat,
+            lon,
+            curvilinear: false,
+        }
+    }
+
+    #[test]
+    fn test_geo_transform_from_ascending_latitude() {
+        let transform = geo_transform(&grid(vec![-10.0, 0.0, 10.0], vec![100.0, 101.0])).unwrap();
+        assert_eq!(
+            transform,
+            GeoTransform { origin_x: 99.5, origin_y: 15.0, pixel_width: 1.0, pixel_height: 10.0 }
+        );
+
+        assert!(geo_transform(&grid(vec![0.0, 1.0, 5.0], vec![0.0, 1.0])).is_err());
+    }
+
+    #[test]
+    fn test_grid_mapping_epsg() {
+        let variable = |name: &str, attributes: &[(&str, &str)]| Variable {
+            name: name.to_string(),
+            data_type: "f32".to_string(),
+            dimensions: vec![],
+            shape: vec![],
+            attributes: attributes.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        };
+        let metadata = |epsg: &str| FileMetadata {
+            file_path: String::new(),
+            dimensions: vec![],
+            variables: vec![variable("crs", &[("epsg_code", epsg)])],
+            global_attrs: HashMap::new(),
+            coordinates: None,
+        };
+        let var = variable("t", &[("grid_mapping", "crs: x y")]);
+
+        assert_eq!(grid_mapping_epsg(&metadata("EPSG:32633"), &var).unwrap(), Some(32633));
+        assert_eq!(grid_mapping_epsg(&metadata("3413"), &variable("t", &[])).unwrap(), None);
+        // Codes beyond the 16-bit GeoKey range must not fall back to WGS84
+        assert!(grid_mapping_epsg(&metadata("EPSG:102100"), &var).is_err());
+        assert!(grid_mapping_epsg(&metadata("ESRI:54030"), &var).is_err());
+    }
+
+    #[test]
+    fn test_write_geotiff_header() {
+        let layout = GeoTiffLayout {
+            width: 2,
+            height: 1,
+            bands: 2,
+            transform: GeoTransform { origin_x: 0.0, origin_y: 1.0, pixel_width: 1.0, pixel_height: 1.0 },
+            epsg: WGS84,
+            geographic: true,
+            nodata: Some(-999.0),
+        };
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("test.tif");
+        let path = path.to_str().unwrap();
+
+        write_geotiff(path, &layout, |band| Ok(vec![band as f32, -999.0])).unwrap();
+        let bytes = std::fs::read(path).unwrap();
+
+        assert_eq!(&bytes[0..4], b"II*\0");
+        // Two bands of 2 floats follow the header, then the IFD
+        let ifd = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        assert_eq!(ifd, 8 + 2 * 8);
+        assert_eq!(f32::from_le_bytes(bytes[16..20].try_into().unwrap()), 1.0);
+
+        let entries = u16::from_le_bytes(bytes[ifd..ifd + 2].try_into().unwrap());
+        assert_eq!(entries, 16);
+        let first_tag = u16::from_le_bytes(bytes[ifd + 2..ifd + 4].try_into().unwrap());
+        let width = u32::from_le_bytes(bytes[ifd + 10..ifd + 14].try_into().unwrap());
+        assert_eq!((first_tag, width), (256, 2));
+    }
+}
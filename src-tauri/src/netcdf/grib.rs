@@ -0,0 +1,1336 @@
+use crate::errors::NetCDFError;
+use crate::models::{Dimension, FileMetadata, Variable};
+use super::backend::DatasetBackend;
+use super::data_access::NativeValues;
+use super::table::next_index;
+use chrono::{Duration, Months, NaiveDate, NaiveDateTime};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Fill value for grid points without data (the value cfgrib and NCL use)
+const GRIB_FILL: f32 = 9.999e20;
+
+/// How far into a file the first "GRIB" marker may be (after a WMO bulletin header)
+const MAX_HEADER_OFFSET: usize = 1024;
+
+/// Global attribute counting the fields left out by product definition template
+const SKIPPED_ATTR: &str = "GRIB_skippedFields";
+
+/// Whether a file holds GRIB messages
+pub fn is_grib(path: &Path) -> bool {
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    let mut header = vec![0u8; MAX_HEADER_OFFSET + 8];
+    let Ok(len) = file.read(&mut header) else {
+        return false;
+    };
+    header.truncate(len);
+
+    header
+        .windows(8)
+        .any(|w| &w[..4] == b"GRIB" && (w[7] == 1 || w[7] == 2))
+}
+
+/// GRIB2 files, indexed message by message.
+///
+/// Fields (submessages) are grouped into variables by parameter, level
+/// type, statistical process and ensemble member. Each variable gets a
+/// `time` dimension from the valid times of its fields, a level dimension
+/// when its level type carries a value (e.g. `isobaric` in Pa), and the
+/// `lat`/`lon` (or `y`/`x` for projected grids) dimensions of its grid.
+/// Combinations without a message read as fill values. Field data is only
+/// decoded when it is read, through the handle the file was indexed with.
+/// Messages whose product definition template is not supported are listed
+/// in the `GRIB_skippedFields` attribute.
+pub struct GribBackend {
+    file: Mutex<BufReader<File>>,
+    metadata: FileMetadata,
+    /// Values of the generated coordinate variables
+    coordinates: HashMap<String, Vec<f64>>,
+    variables: HashMap<String, GribVariable>,
+    fields: Vec<Field>,
+    grids: Vec<Grid>,
+}
+
+/// A data variable assembled from fields
+struct GribVariable {
+    grid: usize,
+    has_level: bool,
+    /// Field index for each (time index, level index)
+    fields: HashMap<(usize, usize), usize>,
+}
+
+/// Horizontal grid from section 3
+#[derive(Debug, Clone, PartialEq)]
+struct Grid {
+    template: u16,
+    ni: usize,
+    nj: usize,
+    scanning_mode: u8,
+    /// First and last latitude/longitude of a regular lat/lon grid (template 3.0)
+    lat_lon: Option<((f64, f64), (f64, f64))>,
+}
+
+/// Data representation from section 5
+#[derive(Debug, Clone, PartialEq)]
+enum Packing {
+    /// Template 5.0 (and the PNG variant 5.41)
+    Simple { reference: f32, binary_scale: i32, decimal_scale: i32, bits: usize, png: bool },
+    /// Templates 5.2 and 5.3
+    Complex { simple: Box<Packing>, params: ComplexParams },
+    /// Template 5.4
+    Ieee { double: bool },
+    /// Anything else; the field is listed but fails to read
+    Unsupported(String),
+}
+
+/// Group and spatial differencing parameters of complex packing
+#[derive(Debug, Clone, PartialEq)]
+struct ComplexParams {
+    missing_management: u8,
+    groups: usize,
+    width_reference: u64,
+    width_bits: usize,
+    length_reference: u64,
+    length_increment: u64,
+    last_group_length: u64,
+    length_bits: usize,
+    /// Order of spatial differencing (0 for template 5.2)
+    order: u8,
+    descriptor_octets: usize,
+}
+
+/// One field (submessage) and where its data sits in the file
+#[derive(Debug, Clone)]
+struct Field {
+    key: FieldKey,
+    valid_time: NaiveDateTime,
+    reference_time: NaiveDateTime,
+    level: Option<f64>,
+    grid: usize,
+    packing: Packing,
+    /// Number of packed values in section 7
+    values: usize,
+    /// Offset of the bitmap in the file, if the field has one
+    bitmap: Option<u64>,
+    data_offset: u64,
+    data_len: usize,
+}
+
+/// What makes fields belong to the same variable
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct FieldKey {
+    discipline: u8,
+    category: u8,
+    number: u8,
+    level_type: u8,
+    statistic: Option<u8>,
+    member: Option<u8>,
+}
+
+impl GribBackend {
+    /// Index every GRIB2 message in a file
+    pub fn open(path: &str) -> Result<Self, NetCDFError> {
+        let file = File::open(path).map_err(|e| {
+            NetCDFError::FileOpenError(format!("Failed to open {}: {}", path, e))
+        })?;
+        let mut reader = BufReader::new(file);
+        let (fields, grids, global_attrs) = index_messages(&mut reader)?;
+        if fields.is_empty() {
+            return Err(NetCDFError::InvalidFormat(match global_attrs.get(SKIPPED_ATTR) {
+                Some(skipped) => format!("No supported GRIB2 fields in {}: skipped {}", path, skipped),
+                None => format!("No GRIB2 fields found in {} (GRIB1 is not supported)", path),
+            }));
+        }
+
+        let mut backend = GribBackend {
+            file: Mutex::new(reader),
+            metadata: FileMetadata {
+                file_path: path.to_string(),
+                dimensions: Vec::new(),
+                variables: Vec::new(),
+                global_attrs,
+                coordinates: None, // Will be populated by coordinate detection
+            },
+            coordinates: HashMap::new(),
+            variables: HashMap::new(),
+            fields,
+            grids,
+        };
+        backend.build_variables();
+        Ok(backend)
+    }
+
+    /// Group fields into variables and create the shared dimensions
+    fn build_variables(&mut self) {
+        let epoch = self.fields.iter().map(|f| f.reference_time).min().expect("at least one field");
+        let time_units = format!("hours since {}", epoch.format("%Y-%m-%d %H:%M:%S"));
+
+        let mut groups: BTreeMap<(FieldKey, usize), Vec<usize>> = BTreeMap::new();
+        for (i, field) in self.fields.iter().enumerate() {
+            groups.entry((field.key.clone(), field.grid)).or_default().push(i);
+        }
+
+        let mut grid_dims: HashMap<usize, Vec<String>> = HashMap::new();
+        for ((key, grid), members) in groups {
+            let mut times: Vec<f64> = members
+                .iter()
+                .map(|&i| (self.fields[i].valid_time - epoch).num_seconds() as f64 / 3600.0)
+                .collect();
+            times.sort_by(f64::total_cmp);
+            times.dedup();
+            let time_dim = self.axis_dimension(
+                "time",
+                times.clone(),
+                HashMap::from([
+                    ("long_name".to_string(), "valid time".to_string()),
+                    ("standard_name".to_string(), "time".to_string()),
+                    ("units".to_string(), time_units.clone()),
+                    ("axis".to_string(), "T".to_string()),
+                    ("calendar".to_string(), "proleptic_gregorian".to_string()),
+                ]),
+            );
+
+            let (level_name, level_units, positive) = level_type_info(key.level_type);
+            let mut levels: Vec<f64> = members.iter().filter_map(|&i| self.fields[i].level).collect();
+            levels.sort_by(f64::total_cmp);
+            levels.dedup();
+            let has_level = !levels.is_empty();
+            let level_dim = has_level.then(|| {
+                let mut attrs = HashMap::from([
+                    ("long_name".to_string(), level_name.replace('_', " ")),
+                    ("axis".to_string(), "Z".to_string()),
+                    ("GRIB_typeOfLevel".to_string(), key.level_type.to_string()),
+                ]);
+                if let Some(units) = level_units {
+                    attrs.insert("units".to_string(), units.to_string());
+                }
+                if let Some(positive) = positive {
+                    attrs.insert("positive".to_string(), positive.to_string());
+                }
+                self.axis_dimension(&level_name, levels.clone(), attrs)
+            });
+
+            let horizontal = match grid_dims.get(&grid) {
+                Some(dims) => dims.clone(),
+                None => {
+                    let dims = self.grid_dimensions(grid, grid_dims.len());
+                    grid_dims.insert(grid, dims.clone());
+                    dims
+                }
+            };
+
+            let mut field_map = HashMap::new();
+            for &i in &members {
+                let field = &self.fields[i];
+                let t = (field.valid_time - epoch).num_seconds() as f64 / 3600.0;
+                let t_index = times.iter().position(|&v| v == t).unwrap_or(0);
+                let l_index = field
+                    .level
+                    .and_then(|l| levels.iter().position(|&v| v == l))
+                    .unwrap_or(0);
+                // Duplicate fields keep the first message
+                field_map.entry((t_index, l_index)).or_insert(i);
+            }
+
+            let mut dimensions = vec![time_dim];
+            dimensions.extend(level_dim);
+            dimensions.extend(horizontal);
+            let shape = dimensions.iter().map(|d| self.dimension_size(d)).collect();
+
+            let name = self.unique_name(variable_name(&key, &level_name));
+            let (long_name, units) = parameter_info(&key);
+            let mut attributes = HashMap::from([
+                ("long_name".to_string(), long_name),
+                ("_FillValue".to_string(), GRIB_FILL.to_string()),
+                ("GRIB_discipline".to_string(), key.discipline.to_string()),
+                ("GRIB_parameterCategory".to_string(), key.category.to_string()),
+                ("GRIB_parameterNumber".to_string(), key.number.to_string()),
+                ("GRIB_typeOfLevel".to_string(), key.level_type.to_string()),
+            ]);
+            if let Some(units) = units {
+                attributes.insert("units".to_string(), units.to_string());
+            }
+            if let Some(statistic) = key.statistic {
+                attributes.insert("GRIB_statisticalProcess".to_string(), statistic.to_string());
+            }
+            if let Some(member) = key.member {
+                attributes.insert("GRIB_perturbationNumber".to_string(), member.to_string());
+            }
+
+            self.metadata.variables.push(Variable {
+                name: name.clone(),
+                data_type: "Basic(Float)".to_string(),
+                dimensions,
+                shape,
+                attributes,
+            });
+            self.variables.insert(
+                name,
+                GribVariable {
+                    grid,
+                    has_level,
+                    fields: field_map,
+                },
+            );
+        }
+    }
+
+    /// Reuse a 1-D axis with the same values or add a new one named `base`, `base1`, ...
+    fn axis_dimension(
+        &mut self,
+        base: &str,
+        values: Vec<f64>,
+        attributes: HashMap<String, String>,
+    ) -> String {
+        let mut suffix = 0;
+        loop {
+            let name = if suffix == 0 { base.to_string() } else { format!("{}{}", base, suffix) };
+            match self.coordinates.get(&name) {
+                Some(existing) if *existing == values => return name,
+                Some(_) => suffix += 1,
+                None => {
+                    self.add_coordinate(&name, values, attributes);
+                    return name;
+                }
+            }
+        }
+    }
+
+    /// Dimensions (and coordinate variables, for lat/lon grids) of the `n`th grid in use
+    fn grid_dimensions(&mut self, grid: usize, n: usize) -> Vec<String> {
+        let suffix = if n == 0 { String::new() } else { n.to_string() };
+        let Grid { ni, nj, lat_lon, template, .. } = self.grids[grid].clone();
+
+        match lat_lon {
+            Some(((la1, la2), (lo1, lo2))) => {
+                let lat_name = format!("lat{}", suffix);
+                let lon_name = format!("lon{}", suffix);
+                let lon_span = match self.grids[grid].scanning_mode & 0x80 {
+                    0 if lo2 < lo1 => lo2 + 360.0 - lo1,
+                    0x80 if lo2 > lo1 => lo2 - 360.0 - lo1,
+                    _ => lo2 - lo1,
+                };
+                self.add_coordinate(
+                    &lat_name,
+                    linspace(la1, la2 - la1, nj),
+                    HashMap::from([
+                        ("long_name".to_string(), "latitude".to_string()),
+                        ("standard_name".to_string(), "latitude".to_string()),
+                        ("units".to_string(), "degrees_north".to_string()),
+                        ("axis".to_string(), "Y".to_string()),
+                    ]),
+                );
+                self.add_coordinate(
+                    &lon_name,
+                    linspace(lo1, lon_span, ni),
+                    HashMap::from([
+                        ("long_name".to_string(), "longitude".to_string()),
+                        ("standard_name".to_string(), "longitude".to_string()),
+                        ("units".to_string(), "degrees_east".to_string()),
+                        ("axis".to_string(), "X".to_string()),
+                    ]),
+                );
+                vec![lat_name, lon_name]
+            }
+            None => {
+                let y_name = format!("y{}", suffix);
+                let x_name = format!("x{}", suffix);
+                for (name, size) in [(&y_name, nj), (&x_name, ni)] {
+                    self.metadata.dimensions.push(Dimension {
+                        name: name.clone(),
+                        size,
+                        is_unlimited: false,
+                    });
+                }
+                self.metadata
+                    .global_attrs
+                    .insert(format!("GRIB_gridDefinitionTemplate_{}{}", y_name, x_name), template.to_string());
+                vec![y_name, x_name]
+            }
+        }
+    }
+
+    /// Add a dimension with a same-named double coordinate variable
+    fn add_coordinate(&mut self, name: &str, values: Vec<f64>, attributes: HashMap<String, String>) {
+        self.metadata.dimensions.push(Dimension {
+            name: name.to_string(),
+            size: values.len(),
+            is_unlimited: false,
+        });
+        self.metadata.variables.push(Variable {
+            name: name.to_string(),
+            data_type: "Basic(Double)".to_string(),
+            dimensions: vec![name.to_string()],
+            shape: vec![values.len()],
+            attributes,
+        });
+        self.coordinates.insert(name.to_string(), values);
+    }
+
+    fn dimension_size(&self, name: &str) -> usize {
+        self.metadata
+            .dimensions
+            .iter()
+            .find(|d| d.name == name)
+            .map(|d| d.size)
+            .unwrap_or(1)
+    }
+
+    fn unique_name(&self, base: String) -> String {
+        let taken = |name: &str| self.metadata.variables.iter().any(|v| v.name == name);
+        if !taken(&base) {
+            return base;
+        }
+        (2..)
+            .map(|n| format!("{}_{}", base, n))
+            .find(|name| !taken(name))
+            .expect("some suffix is free")
+    }
+
+    /// Decode a whole field to grid order (rows of `ni` points), with fill values
+    fn decode_field(&self, field: &Field) -> Result<Vec<f32>, NetCDFError> {
+        let grid = &self.grids[field.grid];
+        let points = grid.ni * grid.nj;
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+
+        let data = read_at(&mut *file, field.data_offset, field.data_len)?;
+        let packed = unpack(&field.packing, &data, field.values)?;
+
+        let values: Vec<f32> = match field.bitmap {
+            Some(offset) => {
+                let bitmap = read_at(&mut *file, offset, points.div_ceil(8))?;
+                let mut packed = packed.into_iter();
+                (0..points)
+                    .map(|i| match bitmap[i / 8] & (0x80 >> (i % 8)) {
+                        0 => GRIB_FILL,
+                        _ => packed.next().map(to_fill).unwrap_or(GRIB_FILL),
+                    })
+                    .collect()
+            }
+            None if packed.len() == points => packed.into_iter().map(to_fill).collect(),
+            None => {
+                return Err(NetCDFError::InvalidFormat(format!(
+                    "GRIB field has {} values for {} grid points",
+                    packed.len(),
+                    points
+                )))
+            }
+        };
+
+        Ok(grid.to_row_major(values))
+    }
+}
+
+impl Grid {
+    /// Reorder scanned values into rows of `ni` points
+    fn to_row_major(&self, mut values: Vec<f32>) -> Vec<f32> {
+        let (ni, nj) = (self.ni, self.nj);
+        // Adjacent points in the j direction are consecutive
+        let consecutive_j = self.scanning_mode & 0x20 != 0;
+        let (rows, row_len) = if consecutive_j { (ni, nj) } else { (nj, ni) };
+
+        // Boustrophedonic scanning reverses every other row
+        if self.scanning_mode & 0x10 != 0 {
+            for row in values.chunks_mut(row_len).skip(1).step_by(2) {
+                row.reverse();
+            }
+        }
+
+        if consecutive_j {
+            let mut out = vec![GRIB_FILL; values.len()];
+            for i in 0..rows {
+                for j in 0..row_len {
+                    out[j * ni + i] = values[i * nj + j];
+                }
+            }
+            values = out;
+        }
+        values
+    }
+}
+
+impl DatasetBackend for GribBackend {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        let var = self
+            .metadata
+            .variables
+            .iter()
+            .find(|v| v.name == var_name)
+            .ok_or_else(|| NetCDFError::VariableNotFound(var_name.to_string()))?;
+        let ndims = var.shape.len();
+        if start.len() != ndims
+            || count.len() != ndims
+            || (0..ndims).any(|d| start[d] + count[d] > var.shape[d])
+        {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Hyperslab start={:?} count={:?} does not fit variable '{}' of shape {:?}",
+                start, count, var_name, var.shape
+            )));
+        }
+
+        if let Some(values) = self.coordinates.get(var_name) {
+            return Ok(NativeValues::F64(values[start[0]..start[0] + count[0]].to_vec()));
+        }
+
+        let gv = &self.variables[var_name];
+        let ni = self.grids[gv.grid].ni;
+        let (y, x) = (ndims - 2, ndims - 1);
+        let total: usize = count.iter().product();
+        let mut out = Vec::with_capacity(total);
+        if total == 0 {
+            return Ok(NativeValues::F32(out));
+        }
+
+        // One decoded field per (time, level) in the hyperslab
+        let outer = &count[..y];
+        let mut offset = vec![0; outer.len()];
+        loop {
+            let t = start[0] + offset[0];
+            let l = if gv.has_level { start[1] + offset[1] } else { 0 };
+
+            match gv.fields.get(&(t, l)) {
+                Some(&index) => {
+                    let field = self.decode_field(&self.fields[index])?;
+                    for row in start[y]..start[y] + count[y] {
+                        out.extend_from_slice(&field[row * ni + start[x]..row * ni + start[x] + count[x]]);
+                    }
+                }
+                None => out.resize(out.len() + count[y] * count[x], GRIB_FILL),
+            }
+
+            if !next_index(&mut offset, outer) {
+                break;
+            }
+        }
+
+        Ok(NativeValues::F32(out))
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        _start: &[usize],
+        _count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        Err(NetCDFError::ConversionError(format!(
+            "Variable '{}' holds numbers, not strings",
+            var_name
+        )))
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        self.variables.contains_key(var_name).then_some(GRIB_FILL as f64)
+    }
+}
+
+/// Fields, distinct grids and global attributes found in a file
+type MessageIndex = (Vec<Field>, Vec<Grid>, HashMap<String, String>);
+
+/// Walk the file message by message, parsing sections 1 and 3-5 and
+/// remembering where the bitmap and data of each field are
+fn index_messages<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<MessageIndex, NetCDFError> {
+    let mut fields = Vec::new();
+    let mut grids: Vec<Grid> = Vec::new();
+    let mut global_attrs = HashMap::new();
+    let mut messages = 0;
+    // Fields with unsupported product definition templates, by template
+    let mut skipped: BTreeMap<u64, usize> = BTreeMap::new();
+
+    while let Some(start) = find_marker(reader)? {
+        let indicator = read_exact(reader, 12)?;
+        let edition = indicator[3];
+        if edition != 2 {
+            // GRIB1 stores a 3-byte length in place of the reserved octets
+            let length = u64::from(indicator[0]) << 16 | u64::from(indicator[1]) << 8 | u64::from(indicator[2]);
+            reader.seek(SeekFrom::Start(start + length.max(8)))?;
+            continue;
+        }
+        let discipline = indicator[2];
+        let length = be_uint(&indicator[4..12]);
+        let end = start + length;
+        messages += 1;
+
+        let mut reference_time = None;
+        let mut grid = None;
+        let mut product = None;
+        let mut packing = None;
+        let mut bitmap = None;
+
+        loop {
+            let pos = reader.stream_position()?;
+            if pos + 4 > end {
+                return Err(grib_error("message ends without '7777'"));
+            }
+            let head = read_exact(reader, 4)?;
+            if head == b"7777" {
+                break;
+            }
+            let section_len = be_uint(&head) as usize;
+            if section_len < 5 || pos + section_len as u64 > end {
+                return Err(grib_error("invalid section length"));
+            }
+            let number = read_exact(reader, 1)?[0];
+
+            match number {
+                1 | 3 | 4 | 5 => {
+                    let mut section = head;
+                    section.push(number);
+                    section.extend(read_exact(reader, section_len - 5)?);
+                    match number {
+                        1 => {
+                            global_attrs
+                                .entry("GRIB_centre".to_string())
+                                .or_insert_with(|| be_uint(octets(&section, 6, 7)).to_string());
+                            global_attrs
+                                .entry("GRIB_subCentre".to_string())
+                                .or_insert_with(|| be_uint(octets(&section, 8, 9)).to_string());
+                            reference_time = Some(parse_time(octets(&section, 13, 19))?);
+                        }
+                        3 => {
+                            let parsed = parse_grid(&section)?;
+                            let index = grids.iter().position(|g| *g == parsed).unwrap_or_else(|| {
+                                grids.push(parsed);
+                                grids.len() - 1
+                            });
+                            grid = Some(index);
+                        }
+                        4 => product = Some(section),
+                        _ => packing = Some((parse_packing(&section)?, be_uint(octets(&section, 6, 9)) as usize)),
+                    }
+                }
+                6 => {
+                    let indicator = read_exact(reader, 1)?[0];
+                    match indicator {
+                        0 => bitmap = Some(pos + 6),
+                        // 254 reuses the bitmap defined earlier in the message
+                        254 => {}
+                        255 => bitmap = None,
+                        _ => return Err(grib_error("predefined bitmaps are not supported")),
+                    }
+                    reader.seek(SeekFrom::Start(pos + section_len as u64))?;
+                }
+                7 => {
+                    let (Some(reference_time), Some(grid), Some(product), Some((packing, values))) =
+                        (reference_time, grid, product.as_ref(), packing.clone())
+                    else {
+                        return Err(grib_error("data section before its definitions"));
+                    };
+                    let parsed = parse_product(product, discipline, reference_time)?;
+                    if parsed.is_none() {
+                        *skipped.entry(be_uint(octets(product, 8, 9))).or_default() += 1;
+                    }
+                    if let Some((key, valid_time, level)) = parsed {
+                        fields.push(Field {
+                            key,
+                            valid_time,
+                            reference_time,
+                            level,
+                            grid,
+                            packing,
+                            values,
+                            bitmap,
+                            data_offset: pos + 5,
+                            data_len: section_len - 5,
+                        });
+                    }
+                    reader.seek(SeekFrom::Start(pos + section_len as u64))?;
+                }
+                _ => {
+                    reader.seek(SeekFrom::Start(pos + section_len as u64))?;
+                }
+            }
+        }
+    }
+
+    global_attrs.insert("GRIB_edition".to_string(), "2".to_string());
+    global_attrs.insert("GRIB_messageCount".to_string(), messages.to_string());
+    if !skipped.is_empty() {
+        let counts: Vec<String> = skipped
+            .iter()
+            .map(|(template, n)| {
+                let plural = if *n == 1 { "" } else { "s" };
+                format!("{} field{} with product definition template 4.{}", n, plural, template)
+            })
+            .collect();
+        global_attrs.insert(SKIPPED_ATTR.to_string(), counts.join(", "));
+    }
+    Ok((fields, grids, global_attrs))
+}
+
+/// Seek to the next "GRIB" marker, returning its offset
+fn find_marker<R: Read + Seek>(reader: &mut R) -> Result<Option<u64>, NetCDFError> {
+    let mut window = [0u8; 4];
+    let mut filled = 0;
+    let mut byte = [0u8; 1];
+    loop {
+        if reader.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        window.rotate_left(1);
+        window[3] = byte[0];
+        filled += 1;
+        if filled >= 4 && &window == b"GRIB" {
+            return Ok(Some(reader.stream_position()? - 4));
+        }
+    }
+}
+
+/// Grid definition (section 3). Only regular lat/lon grids get coordinates;
+/// the other common templates share the Ni/Nj/scanning layout.
+fn parse_grid(section: &[u8]) -> Result<Grid, NetCDFError> {
+    if section.len() < 72 {
+        return Err(grib_error("grid definition section is too short"));
+    }
+    if section[10] != 0 {
+        return Err(grib_error("quasi-regular (thinned) grids are not supported"));
+    }
+    let template = be_uint(octets(section, 13, 14)) as u16;
+    let scanning_mode = match template {
+        0 | 1 | 40 => section[71],
+        10 => section[59],
+        20 | 30 => section[64],
+        other => {
+            return Err(grib_error(format!("grid definition template 3.{} is not supported", other)))
+        }
+    };
+    let ni = be_uint(octets(section, 31, 34)) as usize;
+    let nj = be_uint(octets(section, 35, 38)) as usize;
+    if ni * nj != be_uint(octets(section, 7, 10)) as usize {
+        return Err(grib_error("grid size does not match the number of data points"));
+    }
+
+    let lat_lon = (template == 0).then(|| {
+        let basic_angle = be_uint(octets(section, 39, 42));
+        let subdivisions = be_uint(octets(section, 43, 46));
+        let unit = if basic_angle == 0 || basic_angle == 0xFFFF_FFFF || subdivisions == 0xFFFF_FFFF {
+            1e-6
+        } else {
+            basic_angle as f64 / subdivisions as f64
+        };
+        let angle = |first, last| be_int(octets(section, first, last)) as f64 * unit;
+        ((angle(47, 50), angle(56, 59)), (angle(51, 54), angle(60, 63)))
+    });
+
+    Ok(Grid {
+        template,
+        ni,
+        nj,
+        scanning_mode,
+        lat_lon,
+    })
+}
+
+/// Product definition (section 4): variable key, valid time and level.
+/// Returns `None` for templates that do not describe a plain, statistical
+/// or ensemble field.
+fn parse_product(
+    section: &[u8],
+    discipline: u8,
+    reference_time: NaiveDateTime,
+) -> Result<Option<(FieldKey, NaiveDateTime, Option<f64>)>, NetCDFError> {
+    let template = be_uint(octets(section, 8, 9));
+    // Octets of the end of the overall time interval and of the perturbation number
+    let (interval_end, member) = match template {
+        0 => (None, None),
+        1 => (None, Some(36)),
+        8 => (Some(35), None),
+        11 => (Some(38), Some(36)),
+        _ => return Ok(None),
+    };
+    if section.len() < interval_end.map_or(34, |o| o + 12) {
+        return Err(grib_error("product definition section is too short"));
+    }
+
+    let valid_time = match interval_end {
+        Some(octet) => parse_time(octets(section, octet, octet + 6))?,
+        None => {
+            let unit = section[17];
+            let offset = be_int(octets(section, 19, 22));
+            add_time(reference_time, unit, offset)
+                .ok_or_else(|| grib_error(format!("unsupported forecast time unit {}", unit)))?
+        }
+    };
+
+    let level_type = section[22];
+    let level = match (level_type, section[23], be_uint(octets(section, 25, 28))) {
+        (255, _, _) | (_, 255, _) | (_, _, 0xFFFF_FFFF) => None,
+        _ => {
+            let scale = sign_magnitude(u64::from(section[23]), 8);
+            let value = be_int(octets(section, 25, 28));
+            Some(value as f64 * 10f64.powi(-scale as i32))
+        }
+    };
+
+    let key = FieldKey {
+        discipline,
+        category: section[9],
+        number: section[10],
+        level_type,
+        statistic: interval_end.map(|o| section[o + 11]),
+        member: member.map(|o| section[o - 1]),
+    };
+    Ok(Some((key, valid_time, level)))
+}
+
+/// Data representation (section 5)
+fn parse_packing(section: &[u8]) -> Result<Packing, NetCDFError> {
+    let template = be_uint(octets(section, 10, 11));
+    let simple = || -> Result<Packing, NetCDFError> {
+        if section.len() < 20 {
+            return Err(grib_error("data representation section is too short"));
+        }
+        Ok(Packing::Simple {
+            reference: f32::from_bits(be_uint(octets(section, 12, 15)) as u32),
+            binary_scale: be_int(octets(section, 16, 17)) as i32,
+            decimal_scale: be_int(octets(section, 18, 19)) as i32,
+            bits: section[19] as usize,
+            png: template == 41,
+        })
+    };
+
+    match template {
+        0 | 41 => simple(),
+        2 | 3 => {
+            if section.len() < if template == 3 { 49 } else { 47 } {
+                return Err(grib_error("data representation section is too short"));
+            }
+            Ok(Packing::Complex {
+                simple: Box::new(simple()?),
+                params: ComplexParams {
+                    missing_management: section[22],
+                    groups: be_uint(octets(section, 32, 35)) as usize,
+                    width_reference: u64::from(section[35]),
+                    width_bits: section[36] as usize,
+                    length_reference: be_uint(octets(section, 38, 41)),
+                    length_increment: u64::from(section[41]),
+                    last_group_length: be_uint(octets(section, 43, 46)),
+                    length_bits: section[46] as usize,
+                    order: if template == 3 { section[47] } else { 0 },
+                    descriptor_octets: if template == 3 { section[48] as usize } else { 0 },
+                },
+            })
+        }
+        4 => Ok(Packing::Ieee { double: section.get(11) == Some(&2) }),
+        40 => Ok(Packing::Unsupported("JPEG2000 packing (template 5.40)".to_string())),
+        other => Ok(Packing::Unsupported(format!("data representation template 5.{}", other))),
+    }
+}
+
+/// Unpack the values of section 7. Missing values are NaN.
+fn unpack(packing: &Packing, data: &[u8], count: usize) -> Result<Vec<f64>, NetCDFError> {
+    match packing {
+        Packing::Unsupported(what) => Err(grib_error(format!("{} is not supported", what))),
+        Packing::Ieee { double } => {
+            let size = if *double { 8 } else { 4 };
+            if data.len() < count * size {
+                return Err(grib_error("data section is too short"));
+            }
+            Ok(data
+                .chunks_exact(size)
+                .take(count)
+                .map(|b| match double {
+                    true => f64::from_bits(be_uint(b)),
+                    false => f32::from_bits(be_uint(b) as u32) as f64,
+                })
+                .collect())
+        }
+        Packing::Simple { bits, png, .. } => {
+            let raw = if *png {
+                png_values(data, *bits)?
+            } else {
+                let mut reader = BitReader::new(data);
+                (0..count).map(|_| reader.read(*bits)).collect::<Result<Vec<_>, _>>()?
+            };
+            if raw.len() < count {
+                return Err(grib_error("data section is too short"));
+            }
+            Ok(raw.into_iter().take(count).map(|x| scale(packing, x as f64)).collect())
+        }
+        Packing::Complex { simple, params } => {
+            let Packing::Simple { bits, .. } = **simple else {
+                unreachable!("complex packing wraps simple packing");
+            };
+            let ints = unpack_complex(params, bits, data, count)?;
+            Ok(ints
+                .into_iter()
+                .map(|x| x.map_or(f64::NAN, |x| scale(simple, x as f64)))
+                .collect())
+        }
+    }
+}
+
+/// Y = (R + X * 2^E) / 10^D
+fn scale(packing: &Packing, x: f64) -> f64 {
+    match packing {
+        Packing::Simple { reference, binary_scale, decimal_scale, .. } => {
+            (*reference as f64 + x * 2f64.powi(*binary_scale)) * 10f64.powi(-decimal_scale)
+        }
+        _ => x,
+    }
+}
+
+/// Complex packing with optional spatial differencing (templates 5.2/5.3).
+/// Returns the packed integers, `None` where a value is missing.
+fn unpack_complex(
+    params: &ComplexParams,
+    bits: usize,
+    data: &[u8],
+    count: usize,
+) -> Result<Vec<Option<i64>>, NetCDFError> {
+    let mut reader = BitReader::new(data);
+
+    // Extra descriptors: the first `order` original values, then the minimum
+    let octet_bits = params.descriptor_octets * 8;
+    let mut first_values = Vec::new();
+    let mut minimum = 0;
+    if params.order > 2 {
+        return Err(grib_error(format!("spatial differencing of order {} is not supported", params.order)));
+    }
+    if params.order > 0 {
+        for _ in 0..params.order {
+            first_values.push(sign_magnitude(reader.read(octet_bits)?, octet_bits));
+        }
+        minimum = sign_magnitude(reader.read(octet_bits)?, octet_bits);
+    }
+
+    let groups = params.groups;
+    let references = (0..groups).map(|_| reader.read(bits)).collect::<Result<Vec<_>, _>>()?;
+    reader.align();
+    let widths = (0..groups)
+        .map(|_| reader.read(params.width_bits).map(|w| w + params.width_reference))
+        .collect::<Result<Vec<_>, _>>()?;
+    reader.align();
+    let mut lengths = (0..groups)
+        .map(|_| {
+            reader
+                .read(params.length_bits)
+                .map(|l| params.length_reference + l * params.length_increment)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    reader.align();
+    if let Some(last) = lengths.last_mut() {
+        *last = params.last_group_length;
+    }
+
+    let all_ones = |width: usize| if width >= 64 { u64::MAX } else { (1u64 << width) - 1 };
+    let is_missing = |value: u64, width: usize| match params.missing_management {
+        1 => value == all_ones(width),
+        2 => value == all_ones(width) || value == all_ones(width).wrapping_sub(1),
+        _ => false,
+    };
+
+    let mut values = Vec::with_capacity(count);
+    for g in 0..groups {
+        let width = widths[g] as usize;
+        for _ in 0..lengths[g] {
+            if width == 0 {
+                values.push((!is_missing(references[g], bits)).then_some(references[g] as i64));
+            } else {
+                let v = reader.read(width)?;
+                values.push((!is_missing(v, width)).then_some((references[g] + v) as i64));
+            }
+        }
+    }
+    values.truncate(count);
+
+    // Undo spatial differencing over the non-missing values
+    let (mut last, mut before_last) = (0, 0);
+    for (n, value) in values.iter_mut().flatten().enumerate() {
+        let restored = match (n < first_values.len(), params.order) {
+            (true, _) => first_values[n],
+            (false, 1) => *value + minimum + last,
+            (false, _) => *value + minimum + 2 * last - before_last,
+        };
+        *value = restored;
+        before_last = last;
+        last = restored;
+    }
+
+    Ok(values)
+}
+
+/// Values of a PNG-packed field (template 5.41): grey or RGB(A) samples of `bits` bits
+fn png_values(data: &[u8], bits: usize) -> Result<Vec<u64>, NetCDFError> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info().map_err(grib_error)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(grib_error)?;
+
+    let mut values = Vec::with_capacity((info.width * info.height) as usize);
+    for row in buf[..info.buffer_size()].chunks(info.line_size) {
+        let mut reader = BitReader::new(row);
+        for _ in 0..info.width {
+            values.push(reader.read(bits)?);
+        }
+    }
+    Ok(values)
+}
+
+/// Big-endian bit reader over packed data
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, pos: 0 }
+    }
+
+    fn read(&mut self, bits: usize) -> Result<u64, NetCDFError> {
+        if bits > 64 || self.pos + bits > self.data.len() * 8 {
+            return Err(grib_error("packed data is truncated"));
+        }
+        let mut value = 0u64;
+        let mut remaining = bits;
+        while remaining > 0 {
+            let offset = self.pos % 8;
+            let take = remaining.min(8 - offset);
+            let byte = self.data[self.pos / 8] >> (8 - offset - take);
+            value = (value << take) | u64::from(byte & ((1u16 << take) - 1) as u8);
+            self.pos += take;
+            remaining -= take;
+        }
+        Ok(value)
+    }
+
+    /// Skip to the next byte boundary
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+}
+
+/// `n` evenly spaced values from `first` covering `span`
+fn linspace(first: f64, span: f64, n: usize) -> Vec<f64> {
+    let step = if n > 1 { span / (n - 1) as f64 } else { 0.0 };
+    (0..n).map(|i| first + step * i as f64).collect()
+}
+
+fn to_fill(value: f64) -> f32 {
+    if value.is_nan() { GRIB_FILL } else { value as f32 }
+}
+
+/// Date and time from 7 octets (year as 2 octets, then month to second)
+fn parse_time(b: &[u8]) -> Result<NaiveDateTime, NetCDFError> {
+    NaiveDate::from_ymd_opt(be_uint(&b[..2]) as i32, u32::from(b[2]), u32::from(b[3]))
+        .and_then(|d| d.and_hms_opt(u32::from(b[4]), u32::from(b[5]), u32::from(b[6])))
+        .ok_or_else(|| grib_error("invalid reference time"))
+}
+
+/// Offset a time by a forecast time in Code Table 4.4 units
+fn add_time(time: NaiveDateTime, unit: u8, value: i64) -> Option<NaiveDateTime> {
+    let seconds = match unit {
+        0 => 60,
+        1 => 3600,
+        2 => 86400,
+        10 => 3 * 3600,
+        11 => 6 * 3600,
+        12 => 12 * 3600,
+        13 => 1,
+        3..=7 => {
+            let months = value * [1, 12, 120, 360, 1200][unit as usize - 3];
+            let delta = Months::new(months.unsigned_abs() as u32);
+            return if months >= 0 { time.checked_add_months(delta) } else { time.checked_sub_months(delta) };
+        }
+        _ => return None,
+    };
+    time.checked_add_signed(Duration::seconds(value * seconds))
+}
+
+/// Octets `first..=last` of a section, numbered from 1 as in the WMO manual
+fn octets(section: &[u8], first: usize, last: usize) -> &[u8] {
+    &section[first - 1..last]
+}
+
+fn be_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, &b| (acc << 8) | u64::from(b))
+}
+
+/// GRIB2 signed integers: the top bit is the sign, the rest the magnitude
+fn be_int(bytes: &[u8]) -> i64 {
+    sign_magnitude(be_uint(bytes), bytes.len() * 8)
+}
+
+fn sign_magnitude(value: u64, bits: usize) -> i64 {
+    if bits == 0 {
+        return 0;
+    }
+    let magnitude = (value & ((1u64 << (bits - 1)) - 1)) as i64;
+    if value >> (bits - 1) & 1 == 1 { -magnitude } else { magnitude }
+}
+
+fn read_exact<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, NetCDFError> {
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).map_err(|_| grib_error("file is truncated"))?;
+    Ok(buf)
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> Result<Vec<u8>, NetCDFError> {
+    reader.seek(SeekFrom::Start(offset))?;
+    read_exact(reader, len)
+}
+
+fn grib_error<E: std::fmt::Display>(err: E) -> NetCDFError {
+    NetCDFError::InvalidFormat(format!("GRIB2 decoding failed: {}", err))
+}
+
+/// Variable name from the parameter abbreviation, level type and statistic,
+/// e.g. `TMP_isobaric` or `APCP_surface_acc`
+fn variable_name(key: &FieldKey, level_name: &str) -> String {
+    let parameter = PARAMETERS
+        .iter()
+        .find(|p| (p.0, p.1, p.2) == (key.discipline, key.category, key.number))
+        .map(|p| p.3.to_string())
+        .unwrap_or_else(|| format!("param_{}_{}_{}", key.discipline, key.category, key.number));
+
+    let mut name = format!("{}_{}", parameter, level_name);
+    if let Some(statistic) = key.statistic {
+        name.push('_');
+        name.push_str(match statistic {
+            0 => "avg",
+            1 => "acc",
+            2 => "max",
+            3 => "min",
+            _ => "stat",
+        });
+    }
+    if let Some(member) = key.member {
+        name.push_str(&format!("_ens{}", member));
+    }
+    name
+}
+
+/// Long name and units from the parameter table
+fn parameter_info(key: &FieldKey) -> (String, Option<&'static str>) {
+    match PARAMETERS
+        .iter()
+        .find(|p| (p.0, p.1, p.2) == (key.discipline, key.category, key.number))
+    {
+        Some(p) => (p.4.to_string(), Some(p.5)),
+        None => (
+            format!(
+                "discipline {} category {} parameter {}",
+                key.discipline, key.category, key.number
+            ),
+            None,
+        ),
+    }
+}
+
+/// Name, units and CF `positive` direction of a fixed surface type (Code Table 4.5)
+fn level_type_info(level_type: u8) -> (String, Option<&'static str>, Option<&'static str>) {
+    let (name, units, positive) = match level_type {
+        1 => ("surface", None, None),
+        2 => ("cloud_base", None, None),
+        3 => ("cloud_top", None, None),
+        4 => ("isotherm_0C", None, None),
+        6 => ("max_wind", None, None),
+        7 => ("tropopause", None, None),
+        8 => ("top_of_atmosphere", None, None),
+        10 | 200 => ("entire_atmosphere", None, None),
+        100 => ("isobaric", Some("Pa"), Some("down")),
+        101 => ("mean_sea_level", None, None),
+        102 => ("altitude_above_msl", Some("m"), Some("up")),
+        103 => ("height_above_ground", Some("m"), Some("up")),
+        104 => ("sigma", Some("1"), Some("down")),
+        105 => ("hybrid", Some("1"), Some("down")),
+        106 => ("depth_below_land_surface", Some("m"), Some("down")),
+        108 => ("pressure_from_ground", Some("Pa"), Some("up")),
+        160 => ("depth_below_sea", Some("m"), Some("down")),
+        other => return (format!("level_{}", other), None, None),
+    };
+    (name.to_string(), units, positive)
+}
+
+/// Common parameters of Code Table 4.2 with NCEP abbreviations:
+/// (discipline, category, number, abbreviation, long name, units)
+const PARAMETERS: &[(u8, u8, u8, &str, &str, &str)] = &[
+    (0, 0, 0, "TMP", "Temperature", "K"),
+    (0, 0, 2, "POT", "Potential temperature", "K"),
+    (0, 0, 4, "TMAX", "Maximum temperature", "K"),
+    (0, 0, 5, "TMIN", "Minimum temperature", "K"),
+    (0, 0, 6, "DPT", "Dew point temperature", "K"),
+    (0, 1, 0, "SPFH", "Specific humidity", "kg kg-1"),
+    (0, 1, 1, "RH", "Relative humidity", "%"),
+    (0, 1, 3, "PWAT", "Precipitable water", "kg m-2"),
+    (0, 1, 7, "PRATE", "Precipitation rate", "kg m-2 s-1"),
+    (0, 1, 8, "APCP", "Total precipitation", "kg m-2"),
+    (0, 1, 11, "SNOD", "Snow depth", "m"),
+    (0, 1, 13, "WEASD", "Water equivalent of accumulated snow depth", "kg m-2"),
+    (0, 1, 52, "TPRATE", "Total precipitation rate", "kg m-2 s-1"),
+    (0, 2, 0, "WDIR", "Wind direction", "degree"),
+    (0, 2, 1, "WIND", "Wind speed", "m s-1"),
+    (0, 2, 2, "UGRD", "U-component of wind", "m s-1"),
+    (0, 2, 3, "VGRD", "V-component of wind", "m s-1"),
+    (0, 2, 8, "VVEL", "Vertical velocity (pressure)", "Pa s-1"),
+    (0, 2, 9, "DZDT", "Vertical velocity (geometric)", "m s-1"),
+    (0, 2, 10, "ABSV", "Absolute vorticity", "s-1"),
+    (0, 2, 22, "GUST", "Wind speed (gust)", "m s-1"),
+    (0, 3, 0, "PRES", "Pressure", "Pa"),
+    (0, 3, 1, "PRMSL", "Pressure reduced to MSL", "Pa"),
+    (0, 3, 4, "GP", "Geopotential", "m2 s-2"),
+    (0, 3, 5, "HGT", "Geopotential height", "gpm"),
+    (0, 3, 192, "MSLET", "MSLP (Eta model reduction)", "Pa"),
+    (0, 4, 7, "DSWRF", "Downward short-wave radiation flux", "W m-2"),
+    (0, 5, 3, "DLWRF", "Downward long-wave radiation flux", "W m-2"),
+    (0, 6, 1, "TCDC", "Total cloud cover", "%"),
+    (0, 7, 6, "CAPE", "Convective available potential energy", "J kg-1"),
+    (0, 7, 7, "CIN", "Convective inhibition", "J kg-1"),
+    (0, 14, 0, "TOZNE", "Total ozone", "DU"),
+    (0, 16, 196, "REFC", "Composite reflectivity", "dB"),
+    (0, 19, 0, "VIS", "Visibility", "m"),
+    (2, 0, 0, "LAND", "Land cover (1=land, 0=sea)", "1"),
+    (2, 0, 192, "SOILW", "Volumetric soil moisture content", "1"),
+    (2, 3, 18, "TSOIL", "Soil temperature", "K"),
+    (10, 0, 3, "HTSGW", "Significant height of combined wind waves and swell", "m"),
+    (10, 2, 0, "ICEC", "Ice cover", "1"),
+    (10, 3, 0, "WTMP", "Water temperature", "K"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A section with its length and number prepended
+    fn section(number: u8, body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 5) as u32).to_be_bytes().to_vec();
+        out.push(number);
+        out.extend(body);
+        out
+    }
+
+    /// One GRIB2 message of 8-bit simple-packed temperature on a 3x2 lat/lon grid
+    fn message(level_pa: u32, values: &[u8]) -> Vec<u8> {
+        message_with_template(0, level_pa, values)
+    }
+
+    /// The same message with another product definition template number
+    fn message_with_template(template: u8, level_pa: u32, values: &[u8]) -> Vec<u8> {
+        let mut sections = section(1, &[0, 7, 0, 0, 2, 1, 1, 0x07, 0xE8, 1, 2, 0, 0, 0, 0, 1]);
+
+        let mut grid = vec![0, 0, 0, 0, 6, 0, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        for value in [3u32, 2, 0, 0xFFFF_FFFF, 10_000_000, 0] {
+            grid.extend(value.to_be_bytes());
+        }
+        grid.push(0x30);
+        for value in [9_000_000u32, 2_000_000, 1_000_000, 1_000_000] {
+            grid.extend(value.to_be_bytes());
+        }
+        grid.push(0);
+        sections.extend(section(3, &grid));
+
+        let mut product = vec![0, 0, 0, template, 0, 0, 2, 0, 0, 0, 0, 0, 1, 0, 0, 0, 6, 100, 0];
+        product.extend(level_pa.to_be_bytes());
+        product.extend([255, 255, 255, 255, 255, 255]);
+        sections.extend(section(4, &product));
+
+        let mut packing = vec![0, 0, 0, 6, 0, 0];
+        packing.extend(200f32.to_be_bytes());
+        packing.extend([0, 0, 0, 0, 8, 0]);
+        sections.extend(section(5, &packing));
+        sections.extend(section(6, &[255]));
+        sections.extend(section(7, values));
+        sections.extend(b"7777");
+
+        let mut out = b"GRIB\0\0\0\x02".to_vec();
+        out.extend(((sections.len() + 16) as u64).to_be_bytes());
+        out.extend(sections);
+        out
+    }
+
+    #[test]
+    fn test_read_simple_packed_levels() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("test.grib2");
+        let mut bytes = b"TTAA00 KWBC 020000\r\r\n".to_vec();
+        bytes.extend(message(85_000, &[0, 1, 2, 3, 4, 5]));
+        bytes.extend(message(50_000, &[10, 11, 12, 13, 14, 15]));
+        std::fs::write(&path, bytes).unwrap();
+
+        assert!(is_grib(&path));
+        let backend = GribBackend::open(path.to_str().unwrap()).unwrap();
+        let metadata = backend.metadata().unwrap();
+        let var = metadata.variables.iter().find(|v| v.name == "TMP_isobaric").unwrap();
+        assert_eq!(var.dimensions, vec!["time", "isobaric", "lat", "lon"]);
+        assert_eq!(var.shape, vec![1, 2, 2, 3]);
+
+        let time = metadata.variables.iter().find(|v| v.name == "time").unwrap();
+        assert_eq!(time.attributes["units"], "hours since 2024-01-02 00:00:00");
+        assert_eq!(backend.read_f64("time", &[0], &[1]).unwrap(), vec![6.0]);
+        assert_eq!(backend.read_f64("isobaric", &[0], &[2]).unwrap(), vec![50_000.0, 85_000.0]);
+        assert_eq!(backend.read_f64("lat", &[0], &[2]).unwrap(), vec![10.0, 9.0]);
+        assert_eq!(backend.read_f64("lon", &[0], &[3]).unwrap(), vec![0.0, 1.0, 2.0]);
+
+        let data = backend.read_f64("TMP_isobaric", &[0, 0, 1, 1], &[1, 2, 1, 2]).unwrap();
+        assert_eq!(data, vec![214.0, 215.0, 204.0, 205.0]);
+        // Reads reuse the handle
+        assert_eq!(backend.read_f64("TMP_isobaric", &[0, 1, 0, 0], &[1, 1, 1, 1]).unwrap(), vec![200.0]);
+        assert!(!metadata.global_attrs.contains_key(SKIPPED_ATTR));
+    }
+
+    #[test]
+    fn test_unsupported_product_templates_are_reported() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("test.grib2");
+        let mut bytes = message(85_000, &[0, 1, 2, 3, 4, 5]);
+        bytes.extend(message_with_template(15, 50_000, &[10, 11, 12, 13, 14, 15]));
+        bytes.extend(message_with_template(15, 70_000, &[10, 11, 12, 13, 14, 15]));
+        std::fs::write(&path, &bytes).unwrap();
+
+        let metadata = GribBackend::open(path.to_str().unwrap()).unwrap().metadata().unwrap();
+        assert_eq!(metadata.global_attrs[SKIPPED_ATTR], "2 fields with product definition template 4.15");
+
+        // Nothing left to index is an error naming what was skipped
+        std::fs::write(&path, message_with_template(15, 50_000, &[0; 6])).unwrap();
+        let error = GribBackend::open(path.to_str().unwrap()).err().unwrap();
+        let message = error.to_string();
+        assert!(message.contains("skipped 1 field with product definition template 4.15"), "{}", message);
+    }
+
+    #[test]
+    fn test_complex_packing_with_spatial_differencing() {
+        // Original values 5, 7, 10, 14: first value 5, differences 2, 3, 4,
+        // minimum 2, so the packed differences are 0, 1, 2 in one group
+        let params = ComplexParams {
+            missing_management: 0,
+            groups: 1,
+            width_reference: 0,
+            width_bits: 8,
+            length_reference: 4,
+            length_increment: 1,
+            last_group_length: 4,
+            length_bits: 8,
+            order: 1,
+            descriptor_octets: 1,
+        };
+        // ival1, minsd, group reference (4 bits, aligned), width 2, length 0,
+        // then values 0 (placeholder for ival1), 0, 1, 2 in 2 bits each
+        let data = [5, 2, 0x00, 2, 0, 0b0000_0110];
+        let values = unpack_complex(&params, 4, &data, 4).unwrap();
+        assert_eq!(values, vec![Some(5), Some(7), Some(10), Some(14)]);
+    }
+
+    #[test]
+    fn test_sign_magnitude_integers() {
+        assert_eq!(be_int(&[0x80, 0x05]), -5);
+        assert_eq!(be_int(&[0x00, 0x05]), 5);
+        assert_eq!(sign_magnitude(0x81, 8), -1);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::errors::NetCDFError;
+use crate::models::{FileMetadata, GeoExtent, Variable};
+use super::backend::DatasetBackend;
+use super::coordinates::detect_coordinates;
+use std::collections::HashMap;
+
+/// Mean Earth radius in km
+pub const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// Horizontal latitude/longitude layout of a variable
+#[derive(Debug, Clone)]
+pub struct LatLonGrid {
+    /// Position of the latitude (y) dimension in the variable's dimensions
+    pub y_dim: usize,
+    /// Position of the longitude (x) dimension in the variable's dimensions
+    pub x_dim: usize,
+    /// Number of rows
+    pub ny: usize,
+    /// Number of columns
+    pub nx: usize,
+    /// Latitude values (length ny, or ny * nx for curvilinear grids)
+    pub lat: Vec<f64>,
+    /// Longitude values (length nx, or ny * nx for curvilinear grids)
+    pub lon: Vec<f64>,
+    /// Whether lat/lon are 2-D coordinate arrays
+    pub curvilinear: bool,
+}
+
+impl LatLonGrid {
+    /// Latitude of the cell at row `j`, column `i`
+    pub fn lat_at(&self, j: usize, i: usize) -> f64 {
+        if self.curvilinear {
+            self.lat[j * self.nx + i]
+        } else {
+            self.lat[j]
+        }
+    }
+
+    /// Longitude of the cell at row `j`, column `i`
+    pub fn lon_at(&self, j: usize, i: usize) -> f64 {
+        if self.curvilinear {
+            self.lon[j * self.nx + i]
+        } else {
+            self.lon[i]
+        }
+    }
+
+    /// Whether latitude increases with the row index
+    pub fn lat_ascending(&self) -> bool {
+        self.ny > 1 && self.lat_at(self.ny - 1, 0) > self.lat_at(0, 0)
+    }
+
+    /// Whether longitude decreases with the column index
+    pub fn lon_descending(&self) -> bool {
+        self.nx > 1 && self.lon_at(0, self.nx - 1) < self.lon_at(0, 0)
+    }
+
+    /// Geographic extent of the grid, measured to the outer cell edges
+    pub fn extent(&self) -> GeoExtent {
+        if self.curvilinear {
+            let (south, north) = min_max(&self.lat);
+            let (west, east) = min_max(&self.lon);
+            return GeoExtent { south, west, north, east };
+        }
+
+        let (south, north) = edge_range(&self.lat);
+        let (west, east) = edge_range(&self.lon);
+        GeoExtent { south, west, north, east }
+    }
+
+    /// Offsets in degrees of (longitude, latitude) across the cell at row `j`,
+    /// column `i`, along its row and along its column, from the neighbouring
+    /// centres
+    pub fn cell_steps(&self, j: usize, i: usize) -> ((f64, f64), (f64, f64)) {
+        let step = |(j1, i1): (usize, usize), (j2, i2): (usize, usize), cells: usize| {
+            let dlon = (self.lon_at(j2, i2) - self.lon_at(j1, i1) + 540.0).rem_euclid(360.0) - 180.0;
+            let dlat = self.lat_at(j2, i2) - self.lat_at(j1, i1);
+            (dlon / cells as f64, dlat / cells as f64)
+        };
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.nx - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.ny - 1));
+        let di = if i1 > i0 { step((j, i0), (j, i1), i1 - i0) } else { (0.0, 0.0) };
+        let dj = if j1 > j0 { step((j0, i), (j1, i), j1 - j0) } else { (0.0, 0.0) };
+        (di, dj)
+    }
+
+    /// Index into a 2-D slice read in the variable's dimension order
+    pub fn slice_index(&self, j: usize, i: usize) -> usize {
+        if self.y_dim < self.x_dim {
+            j * self.nx + i
+        } else {
+            i * self.ny + j
+        }
+    }
+
+    /// Build start/count for a single 2-D horizontal slice.
+    ///
+    /// Non-spatial dimensions are fixed at the index given in `indices`
+    /// (keyed by dimension name), defaulting to 0.
+    pub fn slice_extents(
+        &self,
+        var: &Variable,
+        indices: &HashMap<String, usize>,
+    ) -> Result<(Vec<usize>, Vec<usize>), NetCDFError> {
+        let mut start = Vec::with_capacity(var.dimensions.len());
+        let mut count = Vec::with_capacity(var.dimensions.len());
+
+        for (d, dim_name) in var.dimensions.iter().enumerate() {
+            if d == self.y_dim || d == self.x_dim {
+                start.push(0);
+                count.push(var.shape[d]);
+                continue;
+            }
+
+            let index = indices.get(dim_name).copied().unwrap_or(0);
+            if index >= var.shape[d] {
+                return Err(NetCDFError::InvalidSubsetRequest(format!(
+                    "Index {} out of range for dimension '{}' of size {}",
+                    index, dim_name, var.shape[d]
+                )));
+            }
+            start.push(index);
+            count.push(1);
+        }
+
+        Ok((start, count))
+    }
+}
+
+/// Locate the latitude/longitude dimensions of a variable and read their coordinates
+pub fn resolve_grid(
+    dataset: &dyn DatasetBackend,
+    metadata: &FileMetadata,
+    var: &Variable,
+) -> Result<LatLonGrid, NetCDFError> {
+    let coords = match &metadata.coordinates {
+        Some(coords) => coords.clone(),
+        None => detect_coordinates(metadata),
+    };
+
+    let no_grid = || {
+        NetCDFError::InvalidSubsetRequest(format!(
+            "Variable '{}' has no latitude/longitude dimensions",
+            var.name
+        ))
+    };
+
+    let lat_meta = coords
+        .lat_var
+        .as_ref()
+        .and_then(|name| metadata.variables.iter().find(|v| &v.name == name))
+        .ok_or_else(no_grid)?;
+    let lon_meta = coords
+        .lon_var
+        .as_ref()
+        .and_then(|name| metadata.variables.iter().find(|v| &v.name == name))
+        .ok_or_else(no_grid)?;
+
+    let position = |dim: &String| var.dimensions.iter().position(|d| d == dim);
+
+    let (y_dim, x_dim, curvilinear) = match (lat_meta.dimensions.len(), lon_meta.dimensions.len()) {
+        (1, 1) => (
+            position(&lat_meta.dimensions[0]).ok_or_else(no_grid)?,
+            position(&lon_meta.dimensions[0]).ok_or_else(no_grid)?,
+            false,
+        ),
+        (2, 2) if lat_meta.dimensions == lon_meta.dimensions => (
+            position(&lat_meta.dimensions[0]).ok_or_else(no_grid)?,
+            position(&lat_meta.dimensions[1]).ok_or_else(no_grid)?,
+            true,
+        ),
+        _ => return Err(no_grid()),
+    };
+
+    let lat = dataset.read_masked_all(lat_meta)?;
+    let lon = dataset.read_masked_all(lon_meta)?;
+
+    Ok(LatLonGrid {
+        y_dim,
+        x_dim,
+        ny: var.shape[y_dim],
+        nx: var.shape[x_dim],
+        lat,
+        lon,
+        curvilinear,
+    })
+}
+
+/// Minimum and maximum of the finite values
+fn min_max(values: &[f64]) -> (f64, f64) {
+    values
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+            (lo.min(v), hi.max(v))
+        })
+}
+
+/// Range of a 1-D coordinate vector extended by half a cell on each side
+fn edge_range(values: &[f64]) -> (f64, f64) {
+    let (lo, hi) = min_max(values);
+    if values.len() < 2 {
+        return (lo, hi);
+    }
+    let half_step = (hi - lo) / (values.len() - 1) as f64 / 2.0;
+    (lo - half_step, hi + half_step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regular_grid() -> LatLonGrid {
+        LatLonGrid {
+            y_dim: 1,
+            x_dim: 2,
+            ny: 3,
+            nx: 4,
+            lat: vec![-10.0, 0.0, 10.0],
+            lon: vec![0.0, 10.0, 20.0, 30.0],
+            curvilinear: false,
+        }
+    }
+
+    #[test]
+    fn test_extent_uses_cell_edges() {
+        let extent = regular_grid().extent();
+        assert_eq!(extent.south, -15.0);
+        assert_eq!(extent.north, 15.0);
+        assert_eq!(extent.west, -5.0);
+        assert_eq!(extent.east, 35.0);
+    }
+
+    #[test]
+    fn test_slice_extents_fixes_other_dimensions() {
+        let var = Variable {
+            name: "sst".to_string(),
+            data_type: "Float".to_string(),
+            dimensions: vec!["time".to_string(), "lat".to_string(), "lon".to_string()],
+            shape: vec![5, 3, 4],
+            attributes: HashMap::new(),
+        };
+        let mut indices = HashMap::new();
+        indices.insert("time".to_string(), 2);
+
+        let (start, count) = regular_grid().slice_extents(&var, &indices).unwrap();
+        assert_eq!(start, vec![2, 0, 0]);
+        assert_eq!(count, vec![1, 3, 4]);
+
+        indices.insert("time".to_string(), 5);
+        assert!(regular_grid().slice_extents(&var, &indices).is_err());
+    }
+}
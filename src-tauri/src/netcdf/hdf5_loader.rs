@@ -0,0 +1,645 @@
+use crate::errors::NetCDFError;
+use crate::models::{Dimension, FileMetadata, Variable};
+use super::backend::{numeric_type_name, DatasetBackend, NumberKind};
+use super::data_access::NativeValues;
+use hdf5::types::{FixedAscii, FloatSize, IntSize, TypeDescriptor, VarLenAscii, VarLenUnicode};
+use hdf5_sys::h5::{haddr_t, hssize_t, H5free_memory};
+use hdf5_sys::h5a::{H5Aclose, H5Aget_space, H5Aopen, H5Aread};
+use hdf5_sys::h5i::{hid_t, H5Iget_name};
+use hdf5_sys::h5o::{H5Oclose, H5Oopen_by_addr};
+use hdf5_sys::h5p::H5P_DEFAULT;
+use hdf5_sys::h5r::hobj_ref_t;
+use hdf5_sys::h5s::{H5Sclose, H5Sget_simple_extent_npoints};
+use hdf5_sys::h5t::{hvl_t, H5Tclose, H5Tvlen_create, H5T_STD_REF_OBJ};
+use ndarray::IxDyn;
+use netcdf::AttributeValue;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// HDF5 format signature
+const HDF5_SIGNATURE: &[u8; 8] = b"\x89HDF\r\n\x1a\n";
+
+/// `CLASS` attribute value of an HDF5 dimension scale
+const DIMENSION_SCALE: &str = "DIMENSION_SCALE";
+
+/// `NAME` netCDF-4 gives dimension scales that are not coordinate variables
+const NETCDF_DIM_WITHOUT_VARIABLE: &str = "This is a netCDF dimension but not a netCDF variable";
+
+/// Longest fixed-length string read from datasets and attributes
+const MAX_FIXED_STRING: usize = 1024;
+
+/// Whether a file starts with the HDF5 signature (also after a user block)
+pub fn is_hdf5(path: &Path) -> bool {
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let mut header = vec![0u8; 4096 + HDF5_SIGNATURE.len()];
+    let Ok(len) = file.read(&mut header) else {
+        return false;
+    };
+    header.truncate(len);
+
+    // The superblock is at 0 or at a power-of-two offset from 512
+    [0, 512, 1024, 2048, 4096]
+        .iter()
+        .any(|&offset| header.get(offset..offset + HDF5_SIGNATURE.len()) == Some(&HDF5_SIGNATURE[..]))
+}
+
+/// Arbitrary HDF5 files read through the HDF5 library directly.
+///
+/// Every dataset in every group becomes a variable named by its path
+/// (e.g. `HDFEOS/SWATHS/Swath/Data Fields/Temperature`). Group attributes
+/// are reported as global attributes prefixed with the group path.
+/// Dimensions come from the dimension scales attached to a dataset (its
+/// `DIMENSION_LIST`); datasets without attached scales take a scale of
+/// matching size in their group or an ancestor, else `phony_dim_N` like the
+/// NetCDF library names them.
+pub struct Hdf5Backend {
+    file: hdf5::File,
+    metadata: FileMetadata,
+    fill_values: HashMap<String, f64>,
+}
+
+/// A dataset found while walking the file
+struct DatasetInfo {
+    /// Path without the leading '/'
+    name: String,
+    /// Path of the containing group, with a trailing '/' unless it is the root
+    group: String,
+    shape: Vec<usize>,
+    data_type: String,
+    attributes: HashMap<String, String>,
+    /// Path of the scale attached to each dimension, when the dataset has a `DIMENSION_LIST`
+    scales: Option<Vec<Option<String>>>,
+}
+
+/// A 1-D dimension scale
+struct Scale {
+    /// Path of the scale dataset, without the leading '/'
+    path: String,
+    dim_name: String,
+    group: String,
+    size: usize,
+}
+
+impl Hdf5Backend {
+    /// Open an HDF5 file and read the structure of all its groups
+    pub fn open(path: &str) -> Result<Self, NetCDFError> {
+        let file = hdf5::File::open(path).map_err(|e| {
+            NetCDFError::FileOpenError(format!("Failed to open {}: {}", path, e))
+        })?;
+
+        let mut datasets = Vec::new();
+        let mut global_attrs = read_attributes(&file);
+        walk_group(&file, "", &mut datasets, &mut global_attrs)?;
+
+        let (dimensions, variables) = assign_dimensions(datasets);
+        let fill_values = variables
+            .iter()
+            .filter_map(|v| {
+                let fill = v.attributes.get("_FillValue")?.parse::<f64>().ok()?;
+                Some((v.name.clone(), fill))
+            })
+            .collect();
+
+        Ok(Hdf5Backend {
+            file,
+            metadata: FileMetadata {
+                file_path: path.to_string(),
+                dimensions,
+                variables,
+                global_attrs,
+                coordinates: None, // Will be populated by coordinate detection
+            },
+            fill_values,
+        })
+    }
+
+    /// Number of datasets that hold data (dimension-only scales excluded)
+    pub fn data_variable_count(&self) -> usize {
+        self.data_variables().count()
+    }
+
+    /// Number of datasets in the root group that hold data, comparable with
+    /// the variables the NetCDF library lists
+    pub fn root_variable_count(&self) -> usize {
+        self.data_variables().filter(|v| !v.name.contains('/')).count()
+    }
+
+    fn data_variables(&self) -> impl Iterator<Item = &Variable> {
+        self.metadata.variables.iter().filter(|v| {
+            !v.attributes
+                .get("NAME")
+                .is_some_and(|name| name.starts_with(NETCDF_DIM_WITHOUT_VARIABLE))
+        })
+    }
+
+    fn dataset(&self, var_name: &str) -> Result<(hdf5::Dataset, TypeDescriptor), NetCDFError> {
+        let dataset = self
+            .file
+            .dataset(var_name)
+            .map_err(|_| NetCDFError::VariableNotFound(var_name.to_string()))?;
+        let descriptor = dataset.dtype()?.to_descriptor()?;
+        Ok((dataset, descriptor))
+    }
+
+    /// Check a hyperslab against the dataset shape and turn it into a selection
+    fn selection(
+        &self,
+        var_name: &str,
+        shape: &[usize],
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<std::ops::Range<usize>>, NetCDFError> {
+        let ndims = shape.len();
+        if start.len() != ndims
+            || count.len() != ndims
+            || (0..ndims).any(|d| start[d] + count[d] > shape[d])
+        {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Hyperslab start={:?} count={:?} does not fit variable '{}' of shape {:?}",
+                start, count, var_name, shape
+            )));
+        }
+        Ok(start.iter().zip(count).map(|(&s, &c)| s..s + c).collect())
+    }
+}
+
+impl DatasetBackend for Hdf5Backend {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        let (dataset, descriptor) = self.dataset(var_name)?;
+        let shape = dataset.shape();
+        let selection = self.selection(var_name, &shape, start, count)?;
+
+        macro_rules! read {
+            ($t:ty, $variant:ident) => {
+                NativeValues::$variant(if shape.is_empty() {
+                    dataset.read_raw::<$t>()?
+                } else {
+                    dataset.read_slice::<$t, _, IxDyn>(selection)?.into_raw_vec()
+                })
+            };
+        }
+
+        let values = match descriptor {
+            TypeDescriptor::Integer(IntSize::U1) => read!(i8, I8),
+            TypeDescriptor::Integer(IntSize::U2) => read!(i16, I16),
+            TypeDescriptor::Integer(IntSize::U4) => read!(i32, I32),
+            TypeDescriptor::Integer(IntSize::U8) => read!(i64, I64),
+            TypeDescriptor::Unsigned(IntSize::U1) => read!(u8, U8),
+            TypeDescriptor::Unsigned(IntSize::U2) => read!(u16, U16),
+            TypeDescriptor::Unsigned(IntSize::U4) => read!(u32, U32),
+            TypeDescriptor::Unsigned(IntSize::U8) => read!(u64, U64),
+            TypeDescriptor::Float(FloatSize::U4) => read!(f32, F32),
+            TypeDescriptor::Float(FloatSize::U8) => read!(f64, F64),
+            TypeDescriptor::Boolean => {
+                let values: Vec<bool> = if shape.is_empty() {
+                    dataset.read_raw()?
+                } else {
+                    dataset.read_slice::<bool, _, IxDyn>(selection)?.into_raw_vec()
+                };
+                NativeValues::U8(values.into_iter().map(u8::from).collect())
+            }
+            other => {
+                return Err(NetCDFError::ConversionError(format!(
+                    "Unsupported HDF5 type for '{}': {}",
+                    var_name,
+                    type_name(&other)
+                )))
+            }
+        };
+
+        Ok(values)
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        let (dataset, descriptor) = self.dataset(var_name)?;
+        let shape = dataset.shape();
+        let selection = self.selection(var_name, &shape, start, count)?;
+
+        macro_rules! read_strings {
+            ($t:ty) => {{
+                let values: Vec<$t> = if shape.is_empty() {
+                    dataset.read_raw()?
+                } else {
+                    dataset.read_slice::<$t, _, IxDyn>(selection)?.into_raw_vec()
+                };
+                values.iter().map(|s| s.as_str().to_string()).collect()
+            }};
+        }
+
+        match descriptor {
+            TypeDescriptor::VarLenUnicode => Ok(read_strings!(VarLenUnicode)),
+            TypeDescriptor::VarLenAscii => Ok(read_strings!(VarLenAscii)),
+            TypeDescriptor::FixedAscii(len) if len <= MAX_FIXED_STRING => {
+                Ok(read_strings!(FixedAscii<MAX_FIXED_STRING>))
+            }
+            other => Err(NetCDFError::ConversionError(format!(
+                "Unsupported HDF5 string type for '{}': {}",
+                var_name,
+                type_name(&other)
+            ))),
+        }
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        self.fill_values.get(var_name).copied()
+    }
+
+    fn attributes(&self, var_name: Option<&str>) -> Result<Vec<(String, AttributeValue)>, NetCDFError> {
+        let mut attrs = match var_name {
+            Some(name) => read_typed_attributes(&self.dataset(name)?.0),
+            None => read_typed_attributes(&self.file),
+        };
+        // Group attributes are reported under their group path
+        if var_name.is_none() {
+            for name in self.metadata.global_attrs.keys() {
+                let Some((path, attr)) = name.rsplit_once('/') else {
+                    continue;
+                };
+                let value = self.file.group(path).ok().and_then(|g| g.attr(attr).ok()).and_then(|a| attribute_value(&a));
+                if let Some(value) = value {
+                    attrs.push((name.clone(), value));
+                }
+            }
+        }
+        attrs.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(attrs)
+    }
+}
+
+/// Collect the datasets of a group and its subgroups
+fn walk_group(
+    group: &hdf5::Group,
+    prefix: &str,
+    datasets: &mut Vec<DatasetInfo>,
+    global_attrs: &mut HashMap<String, String>,
+) -> Result<(), NetCDFError> {
+    for dataset in group.datasets()? {
+        let data_type = dataset
+            .dtype()
+            .and_then(|t| t.to_descriptor())
+            .map(|d| type_name(&d))
+            .unwrap_or_else(|_| "Unknown".to_string());
+
+        datasets.push(DatasetInfo {
+            name: format!("{}{}", prefix, base_name(&dataset.name())),
+            group: prefix.to_string(),
+            shape: dataset.shape(),
+            data_type,
+            attributes: read_attributes(&dataset),
+            scales: dimension_list(&dataset),
+        });
+    }
+
+    for subgroup in group.groups()? {
+        let path = format!("{}{}", prefix, base_name(&subgroup.name()));
+        for (name, value) in read_attributes(&subgroup) {
+            global_attrs.insert(format!("{}/{}", path, name), value);
+        }
+        walk_group(&subgroup, &format!("{}/", path), datasets, global_attrs)?;
+    }
+
+    Ok(())
+}
+
+/// Name each dataset dimension from a matching dimension scale or a phony dimension
+fn assign_dimensions(datasets: Vec<DatasetInfo>) -> (Vec<Dimension>, Vec<Variable>) {
+    let mut dimensions: Vec<Dimension> = Vec::new();
+
+    // Dimension scales, qualified by group path when the bare name is already taken
+    let mut scales: Vec<Scale> = Vec::new();
+    for info in &datasets {
+        if info.attributes.get("CLASS").map(|c| c.as_str()) != Some(DIMENSION_SCALE) || info.shape.len() != 1 {
+            continue;
+        }
+        let bare = match info.attributes.get("NAME") {
+            Some(name) if !name.is_empty() && !name.starts_with(NETCDF_DIM_WITHOUT_VARIABLE) => name.clone(),
+            _ => base_name(&info.name).to_string(),
+        };
+        let dim_name = if scales.iter().any(|s| s.dim_name == bare && s.size != info.shape[0]) {
+            format!("{}{}", info.group, bare)
+        } else {
+            bare
+        };
+        scales.push(Scale {
+            path: info.name.clone(),
+            dim_name,
+            group: info.group.clone(),
+            size: info.shape[0],
+        });
+    }
+
+    let mut phony: HashMap<(String, usize), Vec<String>> = HashMap::new();
+    let mut phony_count = 0;
+    let mut variables = Vec::with_capacity(datasets.len());
+
+    for info in datasets {
+        let mut dims: Vec<String> = Vec::with_capacity(info.shape.len());
+        for (d, &size) in info.shape.iter().enumerate() {
+            let attached = info.scales.as_ref().and_then(|paths| paths.get(d)?.as_ref());
+            let scale = match attached {
+                Some(path) => scales.iter().find(|s| &s.path == path && s.size == size),
+                // Without attached scales, guess from the size
+                None => scales.iter().find(|s| {
+                    s.size == size && info.group.starts_with(&s.group) && !dims.contains(&s.dim_name)
+                }),
+            };
+            let name = match scale {
+                Some(scale) => scale.dim_name.clone(),
+                None => {
+                    let names = phony.entry((info.group.clone(), size)).or_default();
+                    let used = dims.iter().filter(|d| names.contains(d)).count();
+                    if used == names.len() {
+                        names.push(format!("phony_dim_{}", phony_count));
+                        phony_count += 1;
+                    }
+                    names[used].clone()
+                }
+            };
+            if !dimensions.iter().any(|d| d.name == name) {
+                dimensions.push(Dimension { name: name.clone(), size, is_unlimited: false });
+            }
+            dims.push(name);
+        }
+
+        variables.push(Variable {
+            name: info.name,
+            data_type: info.data_type,
+            dimensions: dims,
+            shape: info.shape,
+            attributes: info.attributes,
+        });
+    }
+
+    (dimensions, variables)
+}
+
+/// Paths of the dimension scales attached to each dimension of a dataset,
+/// from the object references in its `DIMENSION_LIST` attribute
+fn dimension_list(dataset: &hdf5::Dataset) -> Option<Vec<Option<String>>> {
+    if !dataset.attr_names().ok()?.iter().any(|name| name == "DIMENSION_LIST") {
+        return None;
+    }
+    let ndims = dataset.shape().len();
+    // The hdf5 crate cannot read object references, so go to the C library,
+    // under the same lock the crate takes for its own calls
+    hdf5::sync::sync(|| unsafe { read_dimension_list(dataset.id(), ndims) })
+}
+
+/// Read the `DIMENSION_LIST` of a dataset: one variable-length list of
+/// object references per dimension, of which the first names the dimension.
+///
+/// # Safety
+///
+/// `dataset` must be an open dataset with `ndims` dimensions and the HDF5
+/// lock must be held.
+unsafe fn read_dimension_list(dataset: hid_t, ndims: usize) -> Option<Vec<Option<String>>> {
+    let attr = H5Aopen(dataset, c"DIMENSION_LIST".as_ptr(), H5P_DEFAULT);
+    if attr < 0 {
+        return None;
+    }
+    let space = H5Aget_space(attr);
+    let list_type = H5Tvlen_create(*H5T_STD_REF_OBJ);
+    let mut lists = vec![hvl_t { len: 0, p: std::ptr::null_mut() }; ndims];
+
+    let read = H5Sget_simple_extent_npoints(space) == ndims as hssize_t
+        && H5Aread(attr, list_type, lists.as_mut_ptr().cast()) >= 0;
+    let mut paths = Vec::with_capacity(ndims);
+    if read {
+        for list in &lists {
+            // An object reference is the address of the object's header
+            let path = match list.len {
+                0 => None,
+                _ => object_path(dataset, *(list.p as *const hobj_ref_t)),
+            };
+            paths.push(path);
+            if !list.p.is_null() {
+                H5free_memory(list.p);
+            }
+        }
+    }
+
+    H5Tclose(list_type);
+    H5Sclose(space);
+    H5Aclose(attr);
+    read.then_some(paths)
+}
+
+/// Path, without the leading '/', of the object at `addr` in the file of `location`
+///
+/// # Safety
+///
+/// `location` must be an open object and the HDF5 lock must be held.
+unsafe fn object_path(location: hid_t, addr: haddr_t) -> Option<String> {
+    let object = H5Oopen_by_addr(location, addr);
+    if object < 0 {
+        return None;
+    }
+    let len = H5Iget_name(object, std::ptr::null_mut(), 0);
+    let mut name = vec![0u8; len.max(0) as usize + 1];
+    let path = (len > 0 && H5Iget_name(object, name.as_mut_ptr().cast(), name.len()) > 0).then(|| {
+        name.truncate(len as usize);
+        String::from_utf8_lossy(&name).trim_start_matches('/').to_string()
+    });
+    H5Oclose(object);
+    path
+}
+
+/// Read all attributes of a group or dataset that can be shown as text
+fn read_attributes(location: &hdf5::Location) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    for name in location.attr_names().unwrap_or_default() {
+        if let Some(value) = location.attr(&name).ok().and_then(|attr| attribute_to_string(&attr)) {
+            attrs.insert(name, value);
+        }
+    }
+    attrs
+}
+
+/// Read all attributes of a group or dataset with their stored types
+fn read_typed_attributes(location: &hdf5::Location) -> Vec<(String, AttributeValue)> {
+    let mut attrs = Vec::new();
+    for name in location.attr_names().unwrap_or_default() {
+        if let Some(value) = location.attr(&name).ok().and_then(|attr| attribute_value(&attr)) {
+            attrs.push((name, value));
+        }
+    }
+    attrs
+}
+
+/// An HDF5 attribute as a NetCDF attribute value (booleans become `ubyte`)
+fn attribute_value(attr: &hdf5::Attribute) -> Option<AttributeValue> {
+    let descriptor = attr.dtype().ok()?.to_descriptor().ok()?;
+
+    macro_rules! typed {
+        (values $values:expr, $one:ident, $many:ident) => {{
+            let values = $values;
+            if values.len() == 1 {
+                AttributeValue::$one(values.into_iter().next()?)
+            } else {
+                AttributeValue::$many(values)
+            }
+        }};
+        ($t:ty, $one:ident, $many:ident) => {
+            typed!(values attr.read_raw::<$t>().ok()?, $one, $many)
+        };
+    }
+    macro_rules! strings {
+        ($t:ty) => {
+            typed!(values attr.read_raw::<$t>().ok()?.iter().map(|s| s.as_str().to_string()).collect::<Vec<_>>(), Str, Strs)
+        };
+    }
+
+    Some(match descriptor {
+        TypeDescriptor::Integer(IntSize::U1) => typed!(i8, Schar, Schars),
+        TypeDescriptor::Integer(IntSize::U2) => typed!(i16, Short, Shorts),
+        TypeDescriptor::Integer(IntSize::U4) => typed!(i32, Int, Ints),
+        TypeDescriptor::Integer(IntSize::U8) => typed!(i64, Longlong, Longlongs),
+        TypeDescriptor::Unsigned(IntSize::U1) => typed!(u8, Uchar, Uchars),
+        TypeDescriptor::Unsigned(IntSize::U2) => typed!(u16, Ushort, Ushorts),
+        TypeDescriptor::Unsigned(IntSize::U4) => typed!(u32, Uint, Uints),
+        TypeDescriptor::Unsigned(IntSize::U8) => typed!(u64, Ulonglong, Ulonglongs),
+        TypeDescriptor::Float(FloatSize::U4) => typed!(f32, Float, Floats),
+        TypeDescriptor::Float(FloatSize::U8) => typed!(f64, Double, Doubles),
+        TypeDescriptor::Boolean => {
+            typed!(values attr.read_raw::<bool>().ok()?.into_iter().map(u8::from).collect::<Vec<_>>(), Uchar, Uchars)
+        }
+        TypeDescriptor::VarLenUnicode => strings!(VarLenUnicode),
+        TypeDescriptor::VarLenAscii => strings!(VarLenAscii),
+        TypeDescriptor::FixedAscii(len) if len <= MAX_FIXED_STRING => strings!(FixedAscii<MAX_FIXED_STRING>),
+        _ => return None,
+    })
+}
+
+/// Convert an HDF5 attribute to a string representation
+fn attribute_to_string(attr: &hdf5::Attribute) -> Option<String> {
+    let descriptor = attr.dtype().ok()?.to_descriptor().ok()?;
+
+    fn join<T: ToString>(values: Vec<T>) -> Option<String> {
+        match values.len() {
+            1 => values.into_iter().next().map(|v| v.to_string()),
+            _ => Some(format!(
+                "[{}]",
+                values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+
+    match descriptor {
+        TypeDescriptor::Integer(_) | TypeDescriptor::Unsigned(_) | TypeDescriptor::Float(_) => {
+            join(attr.read_raw::<f64>().ok()?)
+        }
+        TypeDescriptor::Boolean => join(attr.read_raw::<bool>().ok()?),
+        TypeDescriptor::VarLenUnicode => {
+            join(attr.read_raw::<VarLenUnicode>().ok()?.iter().map(|s| s.as_str().to_string()).collect())
+        }
+        TypeDescriptor::VarLenAscii => {
+            join(attr.read_raw::<VarLenAscii>().ok()?.iter().map(|s| s.as_str().to_string()).collect())
+        }
+        TypeDescriptor::FixedAscii(len) if len <= MAX_FIXED_STRING => join(
+            attr.read_raw::<FixedAscii<MAX_FIXED_STRING>>()
+                .ok()?
+                .iter()
+                .map(|s| s.as_str().to_string())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Data type name for the models; booleans read as unsigned bytes and
+/// compound types by their class
+fn type_name(descriptor: &TypeDescriptor) -> String {
+    let bytes = |size: &IntSize| match size {
+        IntSize::U1 => 1,
+        IntSize::U2 => 2,
+        IntSize::U4 => 4,
+        IntSize::U8 => 8,
+    };
+    let name = match descriptor {
+        TypeDescriptor::Integer(size) => numeric_type_name(NumberKind::Signed, bytes(size)),
+        TypeDescriptor::Unsigned(size) => numeric_type_name(NumberKind::Unsigned, bytes(size)),
+        TypeDescriptor::Boolean => numeric_type_name(NumberKind::Unsigned, 1),
+        TypeDescriptor::Float(FloatSize::U4) => numeric_type_name(NumberKind::Float, 4),
+        TypeDescriptor::Float(FloatSize::U8) => numeric_type_name(NumberKind::Float, 8),
+        TypeDescriptor::VarLenAscii
+        | TypeDescriptor::VarLenUnicode
+        | TypeDescriptor::FixedAscii(_)
+        | TypeDescriptor::FixedUnicode(_) => "String",
+        TypeDescriptor::Enum(_) => "Enum",
+        TypeDescriptor::Compound(_) => "Compound",
+        TypeDescriptor::FixedArray(..) | TypeDescriptor::VarLenArray(_) => "Array",
+    };
+    name.to_string()
+}
+
+/// Last component of an HDF5 object path
+fn base_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset(name: &str, group: &str, shape: Vec<usize>, attributes: &[(&str, &str)]) -> DatasetInfo {
+        DatasetInfo {
+            name: name.to_string(),
+            group: group.to_string(),
+            shape,
+            data_type: "Basic(Float)".to_string(),
+            attributes: attributes.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            scales: None,
+        }
+    }
+
+    #[test]
+    fn test_assign_dimensions_from_scales_and_phony_dims() {
+        let datasets = vec![
+            dataset("lat", "", vec![3], &[("CLASS", "DIMENSION_SCALE"), ("NAME", "lat")]),
+            dataset("lon", "", vec![4], &[("CLASS", "DIMENSION_SCALE"), ("NAME", "lon")]),
+            dataset("grid/temp", "grid/", vec![3, 4], &[]),
+            dataset("swath/radiance", "swath/", vec![5, 5], &[]),
+        ];
+
+        let (dimensions, variables) = assign_dimensions(datasets);
+        assert_eq!(variables[2].dimensions, vec!["lat", "lon"]);
+        assert_eq!(variables[3].dimensions, vec!["phony_dim_0", "phony_dim_1"]);
+        assert_eq!(dimensions.len(), 4);
+    }
+
+    #[test]
+    fn test_attached_scales_win_over_sizes() {
+        let scale = |name: &str| dataset(name, "", vec![180], &[("CLASS", "DIMENSION_SCALE"), ("NAME", name)]);
+        let mut transposed = dataset("tas_t", "", vec![180, 180], &[]);
+        transposed.scales = Some(vec![Some("lon".to_string()), Some("lat".to_string())]);
+        let datasets = vec![scale("lat"), scale("lon"), dataset("tas", "", vec![180, 180], &[]), transposed];
+
+        let (_, variables) = assign_dimensions(datasets);
+        // Sizes alone cannot tell lat from lon
+        assert_eq!(variables[2].dimensions, vec!["lat", "lon"]);
+        assert_eq!(variables[3].dimensions, vec!["lon", "lat"]);
+    }
+
+    #[test]
+    fn test_base_name() {
+        assert_eq!(base_name("/HDFEOS/SWATHS/Swath"), "Swath");
+        assert_eq!(base_name("temp"), "temp");
+    }
+}
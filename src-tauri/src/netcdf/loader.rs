@@ -1,31 +1,26 @@
 use crate::errors::NetCDFError;
 use crate::models::{Dimension, FileMetadata, Variable};
+use super::backend::open_dataset;
+use super::cdl::VARIABLE_TYPED_ATTRS;
+use super::data_access::is_text_type;
+use netcdf::AttributeValue;
 use std::collections::HashMap;
-use std::path::Path;
 
-/// Open a NetCDF file and extract all metadata
+/// Open a NetCDF file (or any other supported dataset) and extract all metadata
 pub fn open_netcdf(path: &str) -> Result<FileMetadata, NetCDFError> {
-    // Validate file exists
-    if !Path::new(path).exists() {
-        return Err(NetCDFError::FileOpenError(format!(
-            "File not found: {}",
-            path
-        )));
-    }
-
-    // Open the NetCDF file
-    let file = netcdf::open(path).map_err(|e| {
-        NetCDFError::FileOpenError(format!("Failed to open {}: {}", path, e))
-    })?;
+    open_dataset(path)?.metadata()
+}
 
+/// Extract all metadata from an open NetCDF file
+pub(crate) fn read_metadata(path: &str, file: &netcdf::File) -> Result<FileMetadata, NetCDFError> {
     // Extract dimensions
-    let dimensions = extract_dimensions(&file)?;
+    let dimensions = extract_dimensions(file)?;
 
     // Extract variables
-    let variables = extract_variables(&file)?;
+    let variables = extract_variables(file)?;
 
     // Extract global attributes
-    let global_attrs = extract_global_attributes(&file)?;
+    let global_attrs = extract_global_attributes(file)?;
 
     Ok(FileMetadata {
         file_path: path.to_string(),
@@ -118,36 +113,120 @@ fn extract_global_attributes(file: &netcdf::File) -> Result<HashMap<String, Stri
 
 /// Convert a NetCDF attribute to a string representation
 fn attribute_to_string(attr: &netcdf::Attribute) -> Option<String> {
-    use netcdf::AttributeValue;
+    attr.value().ok().map(attribute_value_to_string)
+}
 
-    let value = attr.value().ok()?;
+/// Text form of an attribute value as reported in `FileMetadata`
+pub(crate) fn attribute_value_to_string(value: AttributeValue) -> String {
+    match value {
+        AttributeValue::Str(s) => s,
+        AttributeValue::Strs(v) => format!("{:?}", v),
+        AttributeValue::Uchar(v) => v.to_string(),
+        AttributeValue::Uchars(v) => format!("{:?}", v),
+        AttributeValue::Schar(v) => v.to_string(),
+        AttributeValue::Schars(v) => format!("{:?}", v),
+        AttributeValue::Ushort(v) => v.to_string(),
+        AttributeValue::Ushorts(v) => format!("{:?}", v),
+        AttributeValue::Short(v) => v.to_string(),
+        AttributeValue::Shorts(v) => format!("{:?}", v),
+        AttributeValue::Uint(v) => v.to_string(),
+        AttributeValue::Uints(v) => format!("{:?}", v),
+        AttributeValue::Int(v) => v.to_string(),
+        AttributeValue::Ints(v) => format!("{:?}", v),
+        AttributeValue::Ulonglong(v) => v.to_string(),
+        AttributeValue::Ulonglongs(v) => format!("{:?}", v),
+        AttributeValue::Longlong(v) => v.to_string(),
+        AttributeValue::Longlongs(v) => format!("{:?}", v),
+        AttributeValue::Float(v) => v.to_string(),
+        AttributeValue::Floats(v) => format!("{:?}", v),
+        AttributeValue::Double(v) => v.to_string(),
+        AttributeValue::Doubles(v) => format!("{:?}", v),
+    }
+}
 
+/// Numeric values of an attribute
+pub(crate) fn attribute_numbers(value: &AttributeValue) -> Vec<f64> {
     match value {
-        AttributeValue::Str(s) => Some(s),
-        AttributeValue::Strs(v) => Some(format!("{:?}", v)),
-        AttributeValue::Uchar(v) => Some(v.to_string()),
-        AttributeValue::Uchars(v) => Some(format!("{:?}", v)),
-        AttributeValue::Schar(v) => Some(v.to_string()),
-        AttributeValue::Schars(v) => Some(format!("{:?}", v)),
-        AttributeValue::Ushort(v) => Some(v.to_string()),
-        AttributeValue::Ushorts(v) => Some(format!("{:?}", v)),
-        AttributeValue::Short(v) => Some(v.to_string()),
-        AttributeValue::Shorts(v) => Some(format!("{:?}", v)),
-        AttributeValue::Uint(v) => Some(v.to_string()),
-        AttributeValue::Uints(v) => Some(format!("{:?}", v)),
-        AttributeValue::Int(v) => Some(v.to_string()),
-        AttributeValue::Ints(v) => Some(format!("{:?}", v)),
-        AttributeValue::Ulonglong(v) => Some(v.to_string()),
-        AttributeValue::Ulonglongs(v) => Some(format!("{:?}", v)),
-        AttributeValue::Longlong(v) => Some(v.to_string()),
-        AttributeValue::Longlongs(v) => Some(format!("{:?}", v)),
-        AttributeValue::Float(v) => Some(v.to_string()),
-        AttributeValue::Floats(v) => Some(format!("{:?}", v)),
-        AttributeValue::Double(v) => Some(v.to_string()),
-        AttributeValue::Doubles(v) => Some(format!("{:?}", v)),
+        AttributeValue::Uchar(v) => vec![*v as f64],
+        AttributeValue::Uchars(v) => v.iter().map(|&x| x as f64).collect(),
+        AttributeValue::Schar(v) => vec![*v as f64],
+        AttributeValue::Schars(v) => v.iter().map(|&x| x as f64).collect(),
+        AttributeValue::Ushort(v) => vec![*v as f64],
+        AttributeValue::Ushorts(v) => v.iter().map(|&x| x as f64).collect(),
+        AttributeValue::Short(v) => vec![*v as f64],
+        AttributeValue::Shorts(v) => v.iter().map(|&x| x as f64).collect(),
+        AttributeValue::Uint(v) => vec![*v as f64],
+        AttributeValue::Uints(v) => v.iter().map(|&x| x as f64).collect(),
+        AttributeValue::Int(v) => vec![*v as f64],
+        AttributeValue::Ints(v) => v.iter().map(|&x| x as f64).collect(),
+        AttributeValue::Ulonglong(v) => vec![*v as f64],
+        AttributeValue::Ulonglongs(v) => v.iter().map(|&x| x as f64).collect(),
+        AttributeValue::Longlong(v) => vec![*v as f64],
+        AttributeValue::Longlongs(v) => v.iter().map(|&x| x as f64).collect(),
+        AttributeValue::Float(v) => vec![*v as f64],
+        AttributeValue::Floats(v) => v.iter().map(|&x| x as f64).collect(),
+        AttributeValue::Double(v) => vec![*v],
+        AttributeValue::Doubles(v) => v.clone(),
+        AttributeValue::Str(_) | AttributeValue::Strs(_) => Vec::new(),
     }
 }
 
+/// Attribute of numbers in a variable's type, e.g. "Basic(Short)"
+pub(crate) fn numbers_attribute(data_type: &str, numbers: &[f64]) -> AttributeValue {
+    macro_rules! typed {
+        ($t:ty, $one:ident, $many:ident) => {
+            if numbers.len() == 1 {
+                AttributeValue::$one(numbers[0] as $t)
+            } else {
+                AttributeValue::$many(numbers.iter().map(|&x| x as $t).collect())
+            }
+        };
+    }
+    match data_type {
+        "Basic(Byte)" => typed!(i8, Schar, Schars),
+        "Basic(Ubyte)" => typed!(u8, Uchar, Uchars),
+        "Basic(Short)" => typed!(i16, Short, Shorts),
+        "Basic(Ushort)" => typed!(u16, Ushort, Ushorts),
+        "Basic(Int)" => typed!(i32, Int, Ints),
+        "Basic(Uint)" => typed!(u32, Uint, Uints),
+        "Basic(Int64)" => typed!(i64, Longlong, Longlongs),
+        "Basic(Uint64)" => typed!(u64, Ulonglong, Ulonglongs),
+        "Basic(Float)" => typed!(f32, Float, Floats),
+        _ => typed!(f64, Double, Doubles),
+    }
+}
+
+/// Attributes with their stored types, leaving out any that cannot be read
+pub(crate) fn read_typed_attributes<'a>(
+    attrs: impl Iterator<Item = netcdf::Attribute<'a>>,
+) -> Vec<(String, AttributeValue)> {
+    attrs.filter_map(|attr| Some((attr.name().to_string(), attr.value().ok()?))).collect()
+}
+
+/// Typed form of attributes known only as `FileMetadata` text: strings,
+/// except that fill and valid-range values take their variable's type
+pub(crate) fn text_attributes(
+    attrs: &HashMap<String, String>,
+    data_type: Option<&str>,
+) -> Vec<(String, AttributeValue)> {
+    let mut typed: Vec<(String, AttributeValue)> = attrs
+        .iter()
+        .map(|(name, text)| {
+            let numbers = || -> Option<Vec<f64>> {
+                let inner = text.trim();
+                let inner = inner.strip_prefix('[').and_then(|s| s.strip_suffix(']')).unwrap_or(inner);
+                inner.split(',').map(|x| x.trim().parse::<f64>().ok()).collect()
+            };
+            let typed = data_type
+                .filter(|t| !is_text_type(t) && VARIABLE_TYPED_ATTRS.contains(&name.as_str()))
+                .and_then(|t| Some(numbers_attribute(t, &numbers()?)));
+            (name.clone(), typed.unwrap_or_else(|| AttributeValue::Str(text.clone())))
+        })
+        .collect();
+    typed.sort_by(|a, b| a.0.cmp(&b.0));
+    typed
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,4 +236,23 @@ mod tests {
         let result = open_netcdf("/nonexistent/file.nc");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_text_attributes_keep_strings() {
+        let attrs: HashMap<String, String> = [("units", "1"), ("valid_range", "[0, 400]"), ("_FillValue", "-999")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let typed = text_attributes(&attrs, Some("Basic(Short)"));
+        assert_eq!(
+            typed,
+            vec![
+                ("_FillValue".to_string(), AttributeValue::Short(-999)),
+                ("units".to_string(), AttributeValue::Str("1".to_string())),
+                ("valid_range".to_string(), AttributeValue::Shorts(vec![0, 400])),
+            ]
+        );
+        // Global attributes have no variable type to follow
+        assert!(text_attributes(&attrs, None).iter().all(|(_, v)| matches!(v, AttributeValue::Str(_))));
+    }
 }
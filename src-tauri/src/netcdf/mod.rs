@@ -1,7 +1,60 @@
+pub mod aggregation;
+pub mod arrow_export;
+pub mod backend;
+pub mod catalog;
+pub mod cdl;
+pub mod cf_check;
+pub mod codecs;
+pub mod colormap;
 pub mod coordinates;
+pub mod csv_export;
 pub mod data_access;
+pub mod derived;
+pub mod diff;
+pub mod expression;
+pub mod geotiff;
+pub mod grib;
+pub mod grid;
+pub mod hdf5_loader;
 pub mod loader;
+pub mod nc_export;
+pub mod object_store;
+pub mod ncgen;
+pub mod ncml;
+pub mod opendap;
+pub mod polygons;
+pub mod regions;
+pub mod regrid;
+pub mod remote_file;
+pub mod render;
+pub mod stations;
+pub mod stats;
+pub mod table;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod time;
+pub mod timeseries;
+pub mod units;
+pub mod walk;
+pub mod watch;
+pub mod zarr;
 
+pub use arrow_export::{export_arrow, export_parquet};
+pub use catalog::{query_catalog, scan_directory};
+pub use cdl::dump_cdl;
+pub use cf_check::check_cf;
 pub use coordinates::detect_coordinates;
-pub use data_access::{get_variable_data, get_variable_subset};
+pub use csv_export::export_csv;
+pub use data_access::{get_variable_data, get_variable_subset, read_variable_data, read_variable_subset};
+pub use diff::diff_files;
+pub use geotiff::export_geotiff;
 pub use loader::open_netcdf;
+pub use nc_export::export_netcdf;
+pub use ncgen::{cdl_metadata, generate_netcdf};
+pub use regions::{export_region_series, read_region_stats, region_series, region_stats};
+pub use regrid::export_regridded;
+pub use render::render_slice;
+pub use stations::{export_stations, extract_stations};
+pub use stats::{read_stats, variable_stats};
+pub use timeseries::{extract_timeseries, read_timeseries};
+pub use units::read_in_units;
@@ -0,0 +1,438 @@
+use crate::errors::NetCDFError;
+use crate::models::{ExportSummary, FileMetadata, NetCDFExportRequest, NetCDFFormat, Variable};
+use super::backend::{open_dataset, DatasetBackend};
+use super::data_access::{resolve_hyperslab, NativeValues};
+use super::ncgen::variable_type;
+use super::table::next_index;
+use netcdf::types::{BasicType, VariableType};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of values copied per read/write call
+const CHUNK_ELEMENTS: usize = 1 << 22;
+
+/// Write selected variables and hyperslabs of a dataset to a new NetCDF file.
+///
+/// Coordinate variables, `coordinates` auxiliaries and `bounds` variables of
+/// the selection are carried over, together with all attributes. A `history`
+/// entry describing the export is prepended to the global attributes.
+/// Variables of a type the output format cannot hold are skipped, and so
+/// are attributes that cannot be written; both are listed in the summary
+/// (attributes as `var:name`, global ones as `:name`).
+pub fn export_netcdf(
+    path: &str,
+    request: &NetCDFExportRequest,
+) -> Result<ExportSummary, NetCDFError> {
+    check_output_path(path, &request.output_path)?;
+    let dataset = open_dataset(path)?;
+    write_dataset(dataset.as_ref(), request, &export_command(path, request))
+}
+
+/// Write selected variables and hyperslabs of an open dataset to a new
+/// NetCDF file, recording `command` in its `history` (see [`export_netcdf`]).
+///
+/// Virtual datasets, such as regridded variables, are exported through this.
+pub(crate) fn write_dataset(
+    dataset: &dyn DatasetBackend,
+    request: &NetCDFExportRequest,
+    command: &str,
+) -> Result<ExportSummary, NetCDFError> {
+    let metadata = dataset.metadata()?;
+    let classic = request.format == NetCDFFormat::Classic;
+    let mut skipped = Vec::new();
+    let mut selected = Vec::new();
+    for var in collect_variables(&metadata, &request.variables)? {
+        match output_type(&var.data_type, classic) {
+            Some(vartype) => selected.push((var, vartype)),
+            None => skipped.push(var.name),
+        }
+    }
+
+    let options = match request.format {
+        NetCDFFormat::Netcdf4 => netcdf::Options::NETCDF4,
+        NetCDFFormat::Classic => netcdf::Options::_64BIT_OFFSET,
+    };
+    let mut output = netcdf::create_with(&request.output_path, options).map_err(|e| {
+        NetCDFError::ExportError(format!("Failed to create {}: {}", request.output_path, e))
+    })?;
+
+    // Dimensions, in source order, restricted to those of the variables written
+    for dim in &metadata.dimensions {
+        if !selected.iter().any(|(v, _)| v.dimensions.contains(&dim.name)) {
+            continue;
+        }
+        let size = match request.slices.get(&dim.name) {
+            Some(slice) => slice.count,
+            None => dim.size,
+        };
+        if dim.is_unlimited {
+            output.add_unlimited_dimension(&dim.name)?;
+        } else {
+            output.add_dimension(&dim.name, size)?;
+        }
+    }
+
+    // Global attributes
+    for (name, value) in dataset.attributes(None)? {
+        if name != "history" && output.add_attribute(&name, value).is_err() {
+            skipped.push(format!(":{}", name));
+        }
+    }
+    output.add_attribute("history", history_entry(&metadata, command))?;
+
+    // Define every variable before writing any data (classic files
+    // cannot add variables once in data mode)
+    let mut written = Vec::new();
+    for (var_meta, vartype) in &selected {
+        let dims: Vec<&str> = var_meta.dimensions.iter().map(|d| d.as_str()).collect();
+        let mut dst = match vartype {
+            OutputType::Strings => output.add_string_variable(&var_meta.name, &dims)?,
+            OutputType::Typed(vartype) => output.add_variable_with_type(&var_meta.name, &dims, vartype)?,
+        };
+
+        for (name, value) in dataset.attributes(Some(&var_meta.name))? {
+            if dst.put_attribute(&name, value).is_err() {
+                skipped.push(format!("{}:{}", var_meta.name, name));
+            }
+        }
+        if let (Some(level), false) = (request.deflate_level, classic) {
+            if !dims.is_empty() {
+                dst.set_compression(level, true)?;
+            }
+        }
+        written.push(var_meta.clone());
+    }
+
+    let mut records = 0;
+    for var_meta in &written {
+        let mut dst = output
+            .variable_mut(&var_meta.name)
+            .ok_or_else(|| NetCDFError::VariableNotFound(var_meta.name.clone()))?;
+        let (start, count) = resolve_hyperslab(var_meta, &request.slices)?;
+        records += copy_variable(dataset, var_meta, &mut dst, &start, &count)?;
+    }
+
+    Ok(ExportSummary {
+        output_path: request.output_path.clone(),
+        variables: written.into_iter().map(|v| v.name).collect(),
+        skipped,
+        records,
+        warnings: vec![],
+    })
+}
+
+/// Refuse to write over the file being read, which creating the output would
+/// truncate. Paths are compared after resolving `.`/`..` and symlinks; an
+/// output that does not exist yet is resolved through its directory.
+pub(crate) fn check_output_path(source: &str, output: &str) -> Result<(), NetCDFError> {
+    let resolve = |path: &Path| -> PathBuf {
+        if let Ok(resolved) = path.canonicalize() {
+            return resolved;
+        }
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        match (parent.canonicalize(), path.file_name()) {
+            (Ok(parent), Some(name)) => parent.join(name),
+            _ => path.to_path_buf(),
+        }
+    };
+    if resolve(Path::new(output)) == resolve(Path::new(source)) {
+        return Err(NetCDFError::ExportError(
+            "Output path must differ from the source file".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Expand the requested variables with their coordinate, auxiliary
+/// coordinate and bounds variables, preserving source order
+fn collect_variables(
+    metadata: &FileMetadata,
+    requested: &[String],
+) -> Result<Vec<Variable>, NetCDFError> {
+    let mut names: HashSet<String> = HashSet::new();
+    let mut queue: Vec<String> = requested.to_vec();
+
+    while let Some(name) = queue.pop() {
+        if names.contains(&name) {
+            continue;
+        }
+        let var = metadata
+            .variables
+            .iter()
+            .find(|v| v.name == name)
+            .ok_or_else(|| NetCDFError::VariableNotFound(name.clone()))?;
+        names.insert(name);
+
+        let mut related: Vec<String> = var.dimensions.clone();
+        for attr in ["coordinates", "bounds"] {
+            if let Some(value) = var.attributes.get(attr) {
+                related.extend(value.split_whitespace().map(|s| s.to_string()));
+            }
+        }
+        for candidate in related {
+            if !names.contains(&candidate) && metadata.variables.iter().any(|v| v.name == candidate) {
+                queue.push(candidate);
+            }
+        }
+    }
+
+    Ok(metadata
+        .variables
+        .iter()
+        .filter(|v| names.contains(&v.name))
+        .cloned()
+        .collect())
+}
+
+/// The export as an `ncks`-style command line for the history
+fn export_command(path: &str, request: &NetCDFExportRequest) -> String {
+    let mut slices: Vec<String> = request
+        .slices
+        .iter()
+        .map(|(dim, s)| format!("{}={}:{}", dim, s.start, s.start + s.count))
+        .collect();
+    slices.sort();
+
+    let mut command = format!("export_netcdf -v {}", request.variables.join(","));
+    if !slices.is_empty() {
+        command.push_str(&format!(" -d {}", slices.join(" -d ")));
+    }
+    command.push_str(&format!(" {} {}", path, request.output_path));
+    command
+}
+
+/// Build the `history` attribute of an output file: a timestamped entry for
+/// `command` before the source's history, newest entry first
+pub(crate) fn history_entry(metadata: &FileMetadata, command: &str) -> String {
+    let entry = format!("{}: netcdf-viewer {}", chrono::Utc::now().format("%a %b %e %H:%M:%S %Y"), command);
+    match metadata.global_attrs.get("history") {
+        Some(previous) if !previous.is_empty() => format!("{}\n{}", entry, previous),
+        _ => entry,
+    }
+}
+
+/// How a variable is created in the output
+enum OutputType {
+    /// Variable-length strings, which only NetCDF-4 holds
+    Strings,
+    Typed(VariableType),
+}
+
+/// Output type of a variable, or `None` for types the output format cannot
+/// hold (including the unsigned and 64-bit integers in classic files)
+fn output_type(data_type: &str, classic: bool) -> Option<OutputType> {
+    if data_type == "String" {
+        return (!classic).then_some(OutputType::Strings);
+    }
+    let vartype = variable_type(data_type).ok()?;
+    let extended = matches!(
+        vartype,
+        VariableType::Basic(BasicType::Ubyte | BasicType::Ushort | BasicType::Uint | BasicType::Int64 | BasicType::Uint64)
+    );
+    (!(classic && extended)).then_some(OutputType::Typed(vartype))
+}
+
+/// Copy a hyperslab in blocks along the outermost dimension, returning the
+/// number of values written
+fn copy_variable(
+    dataset: &dyn DatasetBackend,
+    var: &Variable,
+    dst: &mut netcdf::VariableMut,
+    start: &[usize],
+    count: &[usize],
+) -> Result<usize, NetCDFError> {
+    if count.is_empty() {
+        write_block(dataset, var, dst, &[], &[], &[])?;
+        return Ok(1);
+    }
+
+    let inner: usize = count[1..].iter().product();
+    let rows_per_block = (CHUNK_ELEMENTS / inner.max(1)).max(1);
+
+    let mut written = 0;
+    let mut row = 0;
+    while row < count[0] {
+        let rows = rows_per_block.min(count[0] - row);
+
+        let mut block_start = start.to_vec();
+        block_start[0] += row;
+        let mut block_count = count.to_vec();
+        block_count[0] = rows;
+        let mut offset = vec![0; count.len()];
+        offset[0] = row;
+
+        written += write_block(dataset, var, dst, &block_start, &block_count, &offset)?;
+        row += rows;
+    }
+
+    Ok(written)
+}
+
+/// Copy one block read at `start` into the output at `offset`, in the
+/// variable's native type
+fn write_block(
+    dataset: &dyn DatasetBackend,
+    var: &Variable,
+    dst: &mut netcdf::VariableMut,
+    start: &[usize],
+    count: &[usize],
+    offset: &[usize],
+) -> Result<usize, NetCDFError> {
+    let extents: Vec<_> = offset.iter().zip(count).map(|(&o, &c)| o..o + c).collect();
+    let total: usize = count.iter().product();
+
+    if var.data_type == "String" {
+        let strings = dataset.read_text(&var.name, start, count)?;
+        let mut local = vec![0; count.len()];
+        for s in &strings {
+            let index: Vec<usize> = local.iter().zip(offset).map(|(i, o)| i + o).collect();
+            dst.put_string(s, index.as_slice())?;
+            next_index(&mut local, count);
+        }
+        return Ok(strings.len());
+    }
+
+    if var.data_type == "Basic(Char)" {
+        // One string per row of the last dimension, padded with NULs
+        let row = count.last().copied().unwrap_or(1).max(1);
+        let mut bytes = Vec::with_capacity(total);
+        for s in dataset.read_text(&var.name, start, count)? {
+            let mut text = s.into_bytes();
+            text.resize(row, 0);
+            bytes.extend(text);
+        }
+        bytes.resize(total, 0);
+        dst.put_raw_values(&bytes, extents)?;
+        return Ok(total);
+    }
+
+    macro_rules! put {
+        ($values:expr) => {{
+            if count.is_empty() {
+                dst.put_value($values[0], ..)?;
+            } else {
+                dst.put_values(&$values, extents)?;
+            }
+        }};
+    }
+    match dataset.read_native(&var.name, start, count)? {
+        NativeValues::I8(v) => put!(v),
+        NativeValues::U8(v) => put!(v),
+        NativeValues::I16(v) => put!(v),
+        NativeValues::U16(v) => put!(v),
+        NativeValues::I32(v) => put!(v),
+        NativeValues::U32(v) => put!(v),
+        NativeValues::I64(v) => put!(v),
+        NativeValues::U64(v) => put!(v),
+        NativeValues::F32(v) => put!(v),
+        NativeValues::F64(v) => put!(v),
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Dimension, DimensionSlice};
+    use std::collections::HashMap;
+
+    fn variable(name: &str, dims: &[&str], attrs: &[(&str, &str)]) -> Variable {
+        Variable {
+            name: name.to_string(),
+            data_type: "Basic(Float)".to_string(),
+            dimensions: dims.iter().map(|d| d.to_string()).collect(),
+            shape: vec![1; dims.len()],
+            attributes: attrs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_output_must_not_be_the_source() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        let source = dir.join("a.nc");
+        std::fs::write(&source, b"CDF").unwrap();
+        let source = source.to_str().unwrap();
+
+        assert!(check_output_path(source, dir.join("b.nc").to_str().unwrap()).is_ok());
+        assert!(check_output_path(source, dir.join("sub/../a.nc").to_str().unwrap()).is_err());
+        assert!(check_output_path(source, dir.join("sub/./../a.nc").to_str().unwrap()).is_err());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(source, dir.join("link.nc")).unwrap();
+            assert!(check_output_path(source, dir.join("link.nc").to_str().unwrap()).is_err());
+            std::os::unix::fs::symlink(dir, dir.join("sub/up")).unwrap();
+            assert!(check_output_path(source, dir.join("sub/up/a.nc").to_str().unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_collect_variables_adds_coordinates_and_bounds() {
+        let metadata = FileMetadata {
+            file_path: "test.nc".to_string(),
+            dimensions: vec![Dimension { name: "time".to_string(), size: 1, is_unlimited: true }],
+            variables: vec![
+                variable("time", &["time"], &[("bounds", "time_bnds")]),
+                variable("time_bnds", &["time", "nv"], &[]),
+                variable("station_name", &["station"], &[]),
+                variable("tas", &["time", "station"], &[("coordinates", "station_name")]),
+                variable("pr", &["time", "station"], &[]),
+            ],
+            global_attrs: HashMap::new(),
+            coordinates: None,
+        };
+
+        let selected = collect_variables(&metadata, &["tas".to_string()]).unwrap();
+        let names: Vec<&str> = selected.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["time", "time_bnds", "station_name", "tas"]);
+
+        assert!(collect_variables(&metadata, &["missing".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_export_zarr_store() {
+        let temp = tempfile::tempdir().unwrap();
+        let store = temp.path().join("in.zarr");
+        crate::netcdf::test_util::write_store(&store, "days since 2000-01-01", &[0.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+        let output = temp.path().join("out.nc");
+        let request = NetCDFExportRequest {
+            output_path: output.to_string_lossy().to_string(),
+            variables: vec!["tas".to_string()],
+            slices: HashMap::from([("time".to_string(), DimensionSlice { start: 1, count: 1 })]),
+            format: NetCDFFormat::Netcdf4,
+            deflate_level: None,
+        };
+
+        let summary = export_netcdf(&store.to_string_lossy(), &request).unwrap();
+        assert_eq!(summary.variables, vec!["tas", "time"]);
+        assert!(summary.skipped.is_empty(), "{:?}", summary.skipped);
+
+        let path = output.to_string_lossy().to_string();
+        let metadata = crate::netcdf::open_netcdf(&path).unwrap();
+        let time = metadata.variables.iter().find(|v| v.name == "time").unwrap();
+        assert_eq!(time.attributes["units"], "days since 2000-01-01");
+        let tas = metadata.variables.iter().find(|v| v.name == "tas").unwrap();
+        assert_eq!((tas.data_type.as_str(), tas.shape.as_slice()), ("Basic(Float)", &[1, 2][..]));
+        let values = crate::netcdf::get_variable_data(&path, "tas").unwrap().values;
+        assert!(matches!(values, crate::models::VariableData::Numeric(v) if v == vec![3.0, 4.0]));
+    }
+
+    #[test]
+    fn test_output_types_follow_the_format() {
+        let typed = |data_type: &str, classic: bool| match output_type(data_type, classic) {
+            Some(OutputType::Typed(vartype)) => Some(format!("{:?}", vartype)),
+            Some(OutputType::Strings) => Some("String".to_string()),
+            None => None,
+        };
+        assert_eq!(typed("Basic(Char)", true).as_deref(), Some("Basic(Char)"));
+        assert_eq!(typed("Basic(Int64)", false).as_deref(), Some("Basic(Int64)"));
+        assert_eq!(typed("Basic(Int64)", true), None);
+        assert_eq!(typed("String", false).as_deref(), Some("String"));
+        assert_eq!(typed("String", true), None);
+        assert_eq!(typed("Compound", false), None);
+    }
+}
@@ -0,0 +1,1183 @@
+use crate::errors::NetCDFError;
+use crate::models::{CdlGenerateRequest, Dimension, ExportSummary, FileMetadata, NetCDFFormat, Variable};
+use super::cdl::VARIABLE_TYPED_ATTRS;
+use super::data_access::is_text_type;
+use super::loader::{attribute_numbers, attribute_value_to_string, numbers_attribute};
+use netcdf::types::{BasicType, VariableType};
+use netcdf::AttributeValue;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Attributes ncgen treats as storage settings rather than data
+const SPECIAL_ATTRS: &[&str] = &[
+    "_Format",
+    "_Storage",
+    "_ChunkSizes",
+    "_DeflateLevel",
+    "_Shuffle",
+    "_Endianness",
+    "_NoFill",
+    "_Fletcher32",
+    "_NCProperties",
+    "_IsNetcdf4",
+    "_SuperblockVersion",
+];
+
+/// CDL type keywords and the data type strings used throughout the models
+const TYPE_KEYWORDS: &[(&str, &str)] = &[
+    ("char", "Basic(Char)"),
+    ("byte", "Basic(Byte)"),
+    ("ubyte", "Basic(Ubyte)"),
+    ("short", "Basic(Short)"),
+    ("ushort", "Basic(Ushort)"),
+    ("int", "Basic(Int)"),
+    ("integer", "Basic(Int)"),
+    ("long", "Basic(Int)"),
+    ("uint", "Basic(Uint)"),
+    ("int64", "Basic(Int64)"),
+    ("uint64", "Basic(Uint64)"),
+    ("float", "Basic(Float)"),
+    ("real", "Basic(Float)"),
+    ("double", "Basic(Double)"),
+    ("string", "String"),
+];
+
+/// A dataset parsed from CDL text
+#[derive(Debug, Clone)]
+pub struct CdlDataset {
+    /// Name after the `netcdf` keyword
+    pub name: String,
+    /// Root group
+    pub root: CdlGroup,
+}
+
+/// One group of a CDL dataset
+#[derive(Debug, Clone, Default)]
+pub struct CdlGroup {
+    /// Group name (empty for the root group)
+    pub name: String,
+    /// Dimensions defined in this group; unlimited sizes come from the data
+    pub dimensions: Vec<Dimension>,
+    /// Variables, with attribute values in the text form the loader reports
+    pub variables: Vec<Variable>,
+    /// Typed attributes of the group (no variable) and its variables, in CDL order
+    pub attributes: Vec<CdlAttribute>,
+    /// Values of the `data:` section by variable name
+    pub data: HashMap<String, Vec<CdlValue>>,
+    /// Nested groups
+    pub groups: Vec<CdlGroup>,
+}
+
+/// A typed attribute
+#[derive(Debug, Clone, PartialEq)]
+pub struct CdlAttribute {
+    /// Variable the attribute belongs to (`None` for group attributes)
+    pub variable: Option<String>,
+    /// Attribute name
+    pub name: String,
+    /// Typed value
+    pub value: AttributeValue,
+}
+
+/// One value of the `data:` section
+#[derive(Debug, Clone, PartialEq)]
+pub enum CdlValue {
+    Number(f64),
+    /// An integer literal, kept exact so 64-bit values survive
+    Integer(i128),
+    Text(String),
+    /// `_`: the variable's fill value
+    Fill,
+}
+
+impl CdlDataset {
+    /// Metadata of the root group, as the loader would report it for the generated file
+    pub fn metadata(&self, file_path: &str) -> FileMetadata {
+        let global_attrs = self
+            .root
+            .attributes
+            .iter()
+            .filter(|a| a.variable.is_none() && !SPECIAL_ATTRS.contains(&a.name.as_str()))
+            .map(|a| (a.name.clone(), attribute_value_to_string(a.value.clone())))
+            .collect();
+
+        FileMetadata {
+            file_path: file_path.to_string(),
+            dimensions: self.root.dimensions.clone(),
+            variables: self.root.variables.clone(),
+            global_attrs,
+            coordinates: None, // Will be populated by coordinate detection
+        }
+    }
+}
+
+/// Parse CDL text (the `ncdump` output format) into dimensions, variables,
+/// typed attributes, data and groups
+pub fn parse_cdl(text: &str) -> Result<CdlDataset, NetCDFError> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser { tokens, pos: 0 };
+
+    let keyword = parser.name()?;
+    if !keyword.eq_ignore_ascii_case("netcdf") {
+        return Err(parser.error("expected 'netcdf'"));
+    }
+    let name = parser.name()?;
+    parser.expect('{')?;
+    let mut root = CdlGroup::default();
+    parser.group_body(&mut root, &[])?;
+    parser.expect('}')?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.error("unexpected text after the closing '}'"));
+    }
+
+    resolve_unlimited(&mut root, &[]);
+    Ok(CdlDataset { name, root })
+}
+
+/// Metadata the loader would report for the file generated from CDL text
+pub fn cdl_metadata(cdl: &str) -> Result<FileMetadata, NetCDFError> {
+    let dataset = parse_cdl(cdl)?;
+    Ok(dataset.metadata(&format!("{}.nc", dataset.name)))
+}
+
+/// Create a NetCDF file from CDL text, like `ncgen`
+pub fn generate_netcdf(cdl: &str, request: &CdlGenerateRequest) -> Result<ExportSummary, NetCDFError> {
+    let dataset = parse_cdl(cdl)?;
+
+    let format = request.format.unwrap_or_else(|| {
+        let classic = dataset.root.attributes.iter().any(|a| {
+            a.variable.is_none()
+                && a.name == "_Format"
+                && matches!(&a.value, AttributeValue::Str(f) if !f.to_lowercase().contains("netcdf-4"))
+        });
+        if classic { NetCDFFormat::Classic } else { NetCDFFormat::Netcdf4 }
+    });
+    if format == NetCDFFormat::Classic && !dataset.root.groups.is_empty() {
+        return Err(NetCDFError::ExportError("Groups need NetCDF-4 output".to_string()));
+    }
+    let options = match format {
+        NetCDFFormat::Netcdf4 => netcdf::Options::NETCDF4,
+        NetCDFFormat::Classic => netcdf::Options::_64BIT_OFFSET,
+    };
+    let mut output = netcdf::create_with(&request.output_path, options).map_err(|e| {
+        NetCDFError::ExportError(format!("Failed to create {}: {}", request.output_path, e))
+    })?;
+
+    // Define everything before writing any data (classic files cannot add
+    // variables once in data mode)
+    define_group(&mut output, &dataset.root, format == NetCDFFormat::Netcdf4)?;
+
+    let mut written = Vec::new();
+    let mut records = 0;
+    write_group_data(&mut output, &dataset.root, "", &mut written, &mut records)?;
+
+    Ok(ExportSummary {
+        output_path: request.output_path.clone(),
+        variables: written,
+        skipped: Vec::new(),
+        records,
+        warnings: vec![],
+    })
+}
+
+/// The operations shared by the root (`FileMut`) and nested groups (`GroupMut`)
+trait GroupWriter {
+    fn define_dimension(&mut self, dim: &Dimension) -> Result<(), NetCDFError>;
+    fn define_attribute(&mut self, name: &str, value: AttributeValue) -> Result<(), NetCDFError>;
+    fn define_variable(&mut self, var: &Variable) -> Result<netcdf::VariableMut<'_>, NetCDFError>;
+    fn child(&mut self, name: &str) -> Result<netcdf::GroupMut<'_>, NetCDFError>;
+    fn existing_child(&mut self, name: &str) -> Result<netcdf::GroupMut<'_>, NetCDFError>;
+    fn existing_variable(&mut self, name: &str) -> Result<netcdf::VariableMut<'_>, NetCDFError>;
+}
+
+macro_rules! impl_group_writer {
+    ($t:ty, |$this:ident, $group:ident| $existing_child:expr) => {
+        impl GroupWriter for $t {
+            fn define_dimension(&mut self, dim: &Dimension) -> Result<(), NetCDFError> {
+                if dim.is_unlimited {
+                    self.add_unlimited_dimension(&dim.name)?;
+                } else {
+                    self.add_dimension(&dim.name, dim.size)?;
+                }
+                Ok(())
+            }
+
+            fn define_attribute(&mut self, name: &str, value: AttributeValue) -> Result<(), NetCDFError> {
+                self.add_attribute(name, value)?;
+                Ok(())
+            }
+
+            fn define_variable(&mut self, var: &Variable) -> Result<netcdf::VariableMut<'_>, NetCDFError> {
+                let dims: Vec<&str> = var.dimensions.iter().map(String::as_str).collect();
+                if var.data_type == "String" {
+                    return Ok(self.add_string_variable(&var.name, &dims)?);
+                }
+                Ok(self.add_variable_with_type(&var.name, &dims, &variable_type(&var.data_type)?)?)
+            }
+
+            fn child(&mut self, name: &str) -> Result<netcdf::GroupMut<'_>, NetCDFError> {
+                Ok(self.add_group(name)?)
+            }
+
+            fn existing_child(&mut self, $group: &str) -> Result<netcdf::GroupMut<'_>, NetCDFError> {
+                let $this = self;
+                $existing_child.ok_or_else(|| NetCDFError::ExportError(format!("Group '{}' was not created", $group)))
+            }
+
+            fn existing_variable(&mut self, name: &str) -> Result<netcdf::VariableMut<'_>, NetCDFError> {
+                self.variable_mut(name)
+                    .ok_or_else(|| NetCDFError::VariableNotFound(name.to_string()))
+            }
+        }
+    };
+}
+
+impl_group_writer!(netcdf::FileMut, |file, name| file.group_mut(name).ok().flatten());
+impl_group_writer!(netcdf::GroupMut<'_>, |group, name| group.group_mut(name));
+
+/// Define the dimensions, attributes and variables of a group and its children
+fn define_group<G: GroupWriter>(output: &mut G, group: &CdlGroup, netcdf4: bool) -> Result<(), NetCDFError> {
+    for dim in &group.dimensions {
+        output.define_dimension(dim)?;
+    }
+    for attr in group.attributes.iter().filter(|a| a.variable.is_none()) {
+        if !SPECIAL_ATTRS.contains(&attr.name.as_str()) {
+            output.define_attribute(&attr.name, attr.value.clone())?;
+        }
+    }
+
+    for var in &group.variables {
+        let mut dst = output.define_variable(var)?;
+        let attrs = group
+            .attributes
+            .iter()
+            .filter(|a| a.variable.as_deref() == Some(var.name.as_str()));
+
+        let mut deflate = None;
+        let mut shuffle = false;
+        for attr in attrs {
+            match (attr.name.as_str(), &attr.value) {
+                ("_ChunkSizes", value) if netcdf4 && !var.dimensions.is_empty() => {
+                    let chunks: Vec<usize> = attribute_numbers(value).iter().map(|&c| c as usize).collect();
+                    dst.set_chunking(&chunks)?;
+                }
+                ("_DeflateLevel", value) => deflate = attribute_numbers(value).first().map(|&l| l as i32),
+                ("_Shuffle", AttributeValue::Str(s)) => shuffle = s == "true",
+                (name, _) if SPECIAL_ATTRS.contains(&name) => {}
+                (name, value) => {
+                    dst.put_attribute(name, value.clone())?;
+                }
+            }
+        }
+        if let (Some(level), true) = (deflate, netcdf4 && !var.dimensions.is_empty()) {
+            dst.set_compression(level, shuffle)?;
+        }
+    }
+
+    for child in &group.groups {
+        let mut dst = output.child(&child.name)?;
+        define_group(&mut dst, child, netcdf4)?;
+    }
+    Ok(())
+}
+
+/// Write the `data:` values of a group and its children
+fn write_group_data<G: GroupWriter>(
+    output: &mut G,
+    group: &CdlGroup,
+    path: &str,
+    written: &mut Vec<String>,
+    records: &mut usize,
+) -> Result<(), NetCDFError> {
+    for var in &group.variables {
+        let Some(values) = group.data.get(&var.name) else {
+            continue;
+        };
+        let fill = group
+            .attributes
+            .iter()
+            .find(|a| a.variable.as_deref() == Some(var.name.as_str()) && a.name == "_FillValue")
+            .and_then(|a| fill_value(&a.value));
+
+        let mut dst = output.existing_variable(&var.name)?;
+        *records += write_values(&mut dst, var, values, fill)?;
+        written.push(format!("{}{}", path, var.name));
+    }
+
+    for child in &group.groups {
+        let mut dst = output.existing_child(&child.name)?;
+        write_group_data(&mut dst, child, &format!("{}{}/", path, child.name), written, records)?;
+    }
+    Ok(())
+}
+
+/// Write a variable's data, padding with fill values up to its shape
+fn write_values(
+    dst: &mut netcdf::VariableMut,
+    var: &Variable,
+    values: &[CdlValue],
+    fill: Option<CdlValue>,
+) -> Result<usize, NetCDFError> {
+    let extents: Vec<Range<usize>> = var.shape.iter().map(|&n| 0..n).collect();
+    let total: usize = var.shape.iter().product();
+
+    if var.data_type == "String" {
+        let mut index = vec![0; var.shape.len()];
+        for (i, value) in values.iter().take(total).enumerate() {
+            if let CdlValue::Text(s) = value {
+                dst.put_string(s, index.as_slice())?;
+            }
+            if i + 1 < total {
+                super::table::next_index(&mut index, &var.shape);
+            }
+        }
+        return Ok(values.len().min(total));
+    }
+
+    if var.data_type == "Basic(Char)" {
+        let mut bytes = char_bytes(values, var.shape.last().copied().unwrap_or(1));
+        bytes.resize(total, 0);
+        dst.put_raw_values(&bytes, extents)?;
+        return Ok(total);
+    }
+
+    let fill = fill.unwrap_or_else(|| default_fill(&var.data_type));
+    let mut numbers: Vec<CdlValue> = values
+        .iter()
+        .map(|v| match v {
+            CdlValue::Fill => Ok(fill.clone()),
+            CdlValue::Text(s) => Err(NetCDFError::ConversionError(format!(
+                "String \"{}\" in the data of numeric variable '{}'",
+                s, var.name
+            ))),
+            number => Ok(number.clone()),
+        })
+        .collect::<Result<_, _>>()?;
+    let count = numbers.len().min(total);
+    numbers.resize(total, fill);
+
+    // Integer literals are written exactly (and must fit the type); other
+    // numbers are cast
+    macro_rules! put {
+        ($t:ty, $integer:expr) => {{
+            let typed: Vec<$t> = numbers
+                .iter()
+                .map(|v| match v {
+                    CdlValue::Integer(i) => $integer(*i),
+                    CdlValue::Number(x) => Ok(*x as $t),
+                    _ => unreachable!("fill and text values were resolved above"),
+                })
+                .collect::<Result<_, NetCDFError>>()?;
+            if var.shape.is_empty() {
+                dst.put_value(typed[0], ..)?;
+            } else {
+                dst.put_values(&typed, extents)?;
+            }
+        }};
+        ($t:ty) => {
+            put!($t, |i: i128| <$t>::try_from(i).map_err(|_| {
+                NetCDFError::ConversionError(format!("{} is out of range for variable '{}'", i, var.name))
+            }))
+        };
+    }
+    match var.data_type.as_str() {
+        "Basic(Byte)" => put!(i8),
+        "Basic(Ubyte)" => put!(u8),
+        "Basic(Short)" => put!(i16),
+        "Basic(Ushort)" => put!(u16),
+        "Basic(Int)" => put!(i32),
+        "Basic(Uint)" => put!(u32),
+        "Basic(Int64)" => put!(i64),
+        "Basic(Uint64)" => put!(u64),
+        "Basic(Float)" => put!(f32, |i: i128| Ok(i as f32)),
+        _ => put!(f64, |i: i128| Ok(i as f64)),
+    }
+    Ok(count)
+}
+
+/// Character data: each string is padded with NULs to a whole number of rows
+fn char_bytes(values: &[CdlValue], row: usize) -> Vec<u8> {
+    let row = row.max(1);
+    let mut bytes = Vec::new();
+    for value in values {
+        if let CdlValue::Text(s) = value {
+            bytes.extend(s.bytes());
+            bytes.resize(bytes.len().div_ceil(row).max(1) * row, 0);
+        }
+    }
+    bytes
+}
+
+/// Number of elements a variable's data fills (strings count as padded rows)
+fn element_count(var: &Variable, values: &[CdlValue]) -> usize {
+    if var.data_type == "Basic(Char)" {
+        char_bytes(values, var.shape.last().copied().unwrap_or(1)).len()
+    } else {
+        values.len()
+    }
+}
+
+/// Set unlimited dimension sizes (and variable shapes) from the data.
+/// `visible` holds the dimensions of the enclosing groups.
+fn resolve_unlimited(group: &mut CdlGroup, visible: &[Dimension]) {
+    for child in group.groups.iter_mut() {
+        let mut scope = visible.to_vec();
+        scope.extend(group.dimensions.iter().cloned());
+        resolve_unlimited(child, &scope);
+    }
+
+    for d in 0..group.dimensions.len() {
+        if !group.dimensions[d].is_unlimited {
+            continue;
+        }
+        let name = group.dimensions[d].name.clone();
+        let size = group
+            .variables
+            .iter()
+            .filter(|v| v.dimensions.first() == Some(&name))
+            .filter_map(|v| {
+                let values = group.data.get(&v.name)?;
+                let inner: usize = v.shape[1..].iter().product();
+                Some(element_count(v, values).div_ceil(inner.max(1)))
+            })
+            .chain(nested_sizes(&group.groups, &name))
+            .max()
+            .unwrap_or(0);
+        group.dimensions[d].size = size;
+        set_dimension_size(group, &name, size);
+    }
+}
+
+/// Rows written along an unlimited dimension by variables of nested groups
+fn nested_sizes(groups: &[CdlGroup], name: &str) -> Vec<usize> {
+    let mut sizes = Vec::new();
+    for group in groups {
+        if group.dimensions.iter().any(|d| d.name == name) {
+            continue; // shadowed
+        }
+        for var in group.variables.iter().filter(|v| v.dimensions.first().map(String::as_str) == Some(name)) {
+            if let Some(values) = group.data.get(&var.name) {
+                let inner: usize = var.shape[1..].iter().product();
+                sizes.push(element_count(var, values).div_ceil(inner.max(1)));
+            }
+        }
+        sizes.extend(nested_sizes(&group.groups, name));
+    }
+    sizes
+}
+
+fn set_dimension_size(group: &mut CdlGroup, name: &str, size: usize) {
+    for var in group.variables.iter_mut() {
+        for (d, dim) in var.dimensions.iter().enumerate() {
+            if dim == name {
+                var.shape[d] = size;
+            }
+        }
+    }
+    for child in group.groups.iter_mut() {
+        if !child.dimensions.iter().any(|d| d.name == name) {
+            set_dimension_size(child, name, size);
+        }
+    }
+}
+
+/// NetCDF default fill value of a type
+fn default_fill(data_type: &str) -> CdlValue {
+    match data_type {
+        "Basic(Byte)" => CdlValue::Integer(-127),
+        "Basic(Ubyte)" => CdlValue::Integer(255),
+        "Basic(Short)" => CdlValue::Integer(-32767),
+        "Basic(Ushort)" => CdlValue::Integer(65535),
+        "Basic(Int)" => CdlValue::Integer(-2147483647),
+        "Basic(Uint)" => CdlValue::Integer(4294967295),
+        "Basic(Int64)" => CdlValue::Integer(-9223372036854775806),
+        "Basic(Uint64)" => CdlValue::Integer(18446744073709551614),
+        "Basic(Float)" => CdlValue::Number(9.969_21e36_f32 as f64),
+        _ => CdlValue::Number(9.969_209_968_386_869e36),
+    }
+}
+
+/// A `_FillValue` attribute as a data value (64-bit integers exactly)
+fn fill_value(value: &AttributeValue) -> Option<CdlValue> {
+    match value {
+        AttributeValue::Longlong(v) => Some(CdlValue::Integer(*v as i128)),
+        AttributeValue::Ulonglong(v) => Some(CdlValue::Integer(*v as i128)),
+        other => attribute_numbers(other).first().map(|&x| CdlValue::Number(x)),
+    }
+}
+
+pub(crate) fn variable_type(data_type: &str) -> Result<VariableType, NetCDFError> {
+    let basic = match data_type {
+        "Basic(Char)" => BasicType::Char,
+        "Basic(Byte)" => BasicType::Byte,
+        "Basic(Ubyte)" => BasicType::Ubyte,
+        "Basic(Short)" => BasicType::Short,
+        "Basic(Ushort)" => BasicType::Ushort,
+        "Basic(Int)" => BasicType::Int,
+        "Basic(Uint)" => BasicType::Uint,
+        "Basic(Int64)" => BasicType::Int64,
+        "Basic(Uint64)" => BasicType::Uint64,
+        "Basic(Float)" => BasicType::Float,
+        "Basic(Double)" => BasicType::Double,
+        other => return Err(NetCDFError::ExportError(format!("Unsupported CDL type '{}'", other))),
+    };
+    Ok(VariableType::Basic(basic))
+}
+
+/// Build a typed attribute value from CDL literals
+fn attribute_value(data_type: &str, values: &[Literal]) -> Result<AttributeValue, NetCDFError> {
+    if is_text_type(data_type) {
+        let strings: Vec<String> = values
+            .iter()
+            .map(|v| match v {
+                Literal::Text(s) => s.clone(),
+                Literal::Number(x, _) => x.to_string(),
+                Literal::Integer(i, _) => i.to_string(),
+            })
+            .collect();
+        return Ok(match data_type {
+            "String" => AttributeValue::Strs(strings),
+            _ => AttributeValue::Str(strings.concat()),
+        });
+    }
+
+    // 64-bit integers would lose precision through f64
+    let integers: Option<Vec<i128>> = values
+        .iter()
+        .map(|v| match v {
+            Literal::Integer(i, _) => Some(*i),
+            _ => None,
+        })
+        .collect();
+    let out_of_range = |i: &i128| NetCDFError::InvalidFormat(format!("CDL: {} is out of range for {}", i, data_type));
+    match (data_type, integers) {
+        ("Basic(Int64)", Some(integers)) => {
+            let typed = integers.iter().map(|i| i64::try_from(*i).map_err(|_| out_of_range(i)));
+            return Ok(AttributeValue::Longlongs(typed.collect::<Result<_, _>>()?));
+        }
+        ("Basic(Uint64)", Some(integers)) => {
+            let typed = integers.iter().map(|i| u64::try_from(*i).map_err(|_| out_of_range(i)));
+            return Ok(AttributeValue::Ulonglongs(typed.collect::<Result<_, _>>()?));
+        }
+        _ => {}
+    }
+
+    let numbers: Vec<f64> = values
+        .iter()
+        .map(|v| match v {
+            Literal::Number(x, _) => Ok(*x),
+            Literal::Integer(i, _) => Ok(*i as f64),
+            Literal::Text(s) => Err(NetCDFError::InvalidFormat(format!(
+                "CDL: string \"{}\" in a numeric attribute",
+                s
+            ))),
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(numbers_attribute(data_type, &numbers))
+}
+
+/// A CDL token and the line it starts on
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A name; escaped names are never keywords
+    Name { text: String, escaped: bool },
+    Number(String),
+    Text(String),
+    Punct(char),
+    /// `_`
+    Fill,
+}
+
+/// A constant in an attribute: a number with its data type, or a string
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Number(f64, &'static str),
+    /// An integer, kept exact so 64-bit values survive
+    Integer(i128, &'static str),
+    Text(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, NetCDFError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '+' | '@') || !c.is_ascii();
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            '\n' => {
+                line += 1;
+                i += 1;
+            }
+            c if c.is_whitespace() => i += 1,
+            '/' if next == Some('/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '{' | '}' | '(' | ')' | '=' | ',' | ';' | ':' => {
+                tokens.push((Token::Punct(c), line));
+                i += 1;
+            }
+            '"' | '\'' => {
+                let start_line = line;
+                let mut s = String::new();
+                i += 1;
+                loop {
+                    let Some(&ch) = chars.get(i) else {
+                        return Err(cdl_error(start_line, "unterminated string"));
+                    };
+                    i += 1;
+                    match ch {
+                        ch if ch == c => break,
+                        '\\' => {
+                            let escaped = chars.get(i).copied().unwrap_or('\\');
+                            i += 1;
+                            s.push(match escaped {
+                                'n' => '\n',
+                                't' => '\t',
+                                'r' => '\r',
+                                '0' => '\0',
+                                other => other,
+                            });
+                        }
+                        '\n' => {
+                            line += 1;
+                            s.push('\n');
+                        }
+                        ch => s.push(ch),
+                    }
+                }
+                tokens.push((Token::Text(s), start_line));
+            }
+            '_' if !next.is_some_and(|n| is_name_char(n) || n == '\\') => {
+                tokens.push((Token::Fill, line));
+                i += 1;
+            }
+            c if c.is_ascii_digit()
+                || (matches!(c, '-' | '+' | '.') && next.is_some_and(|n| n.is_ascii_digit() || n == '.' || n == 'I' || n == 'N')) =>
+            {
+                let start = i;
+                i += 1;
+                while let Some(&ch) = chars.get(i) {
+                    let exponent_sign = matches!(ch, '+' | '-') && matches!(chars[i - 1], 'e' | 'E');
+                    if ch.is_alphanumeric() || ch == '.' || exponent_sign {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push((Token::Number(chars[start..i].iter().collect()), line));
+            }
+            c if is_name_char(c) || c == '\\' => {
+                let mut s = String::new();
+                let mut escaped = false;
+                while let Some(&ch) = chars.get(i) {
+                    if ch == '\\' {
+                        let Some(&literal) = chars.get(i + 1) else {
+                            break;
+                        };
+                        s.push(literal);
+                        escaped = true;
+                        i += 2;
+                    } else if is_name_char(ch) {
+                        s.push(ch);
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push((Token::Name { text: s, escaped }, line));
+            }
+            other => return Err(cdl_error(line, format!("unexpected character '{}'", other))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// A number literal: its value and data type (from the suffix, else
+/// `double` when it has a fraction or exponent and `int` otherwise)
+fn parse_number(text: &str) -> Option<(f64, &'static str)> {
+    const SUFFIXES: &[(&str, &str)] = &[
+        ("ull", "Basic(Uint64)"),
+        ("ll", "Basic(Int64)"),
+        ("ub", "Basic(Ubyte)"),
+        ("us", "Basic(Ushort)"),
+        ("u", "Basic(Uint)"),
+        ("b", "Basic(Byte)"),
+        ("s", "Basic(Short)"),
+        ("l", "Basic(Int)"),
+        ("f", "Basic(Float)"),
+        ("d", "Basic(Double)"),
+    ];
+    let lower = text.to_lowercase();
+    for (suffix, data_type) in SUFFIXES {
+        if let Some(body) = lower.strip_suffix(suffix) {
+            if let Ok(value) = body.parse::<f64>() {
+                return Some((value, data_type));
+            }
+        }
+    }
+
+    let value = lower.parse::<f64>().ok()?;
+    let fractional = lower.contains(['.', 'e', 'n', 'i']);
+    Some((value, if fractional { "Basic(Double)" } else { "Basic(Int)" }))
+}
+
+/// An integer literal (with an optional integer type suffix) as an exact value
+fn parse_integer(text: &str) -> Option<i128> {
+    let lower = text.to_lowercase();
+    let body = ["ull", "ll", "ub", "us", "u", "b", "s", "l"]
+        .iter()
+        .find_map(|suffix| lower.strip_suffix(suffix))
+        .unwrap_or(&lower);
+    body.parse().ok()
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Result<Token, NetCDFError> {
+        let token = self.peek(0).cloned().ok_or_else(|| self.error("unexpected end of CDL"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn error(&self, message: &str) -> NetCDFError {
+        let line = self
+            .tokens
+            .get(self.pos.min(self.tokens.len().saturating_sub(1)))
+            .map_or(1, |(_, line)| *line);
+        cdl_error(line, message)
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), NetCDFError> {
+        match self.next()? {
+            Token::Punct(c) if c == punct => Ok(()),
+            _ => {
+                self.pos -= 1;
+                Err(self.error(&format!("expected '{}'", punct)))
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, NetCDFError> {
+        match self.next()? {
+            Token::Name { text, .. } => Ok(text),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected a name"))
+            }
+        }
+    }
+
+    fn is_punct(&self, offset: usize, punct: char) -> bool {
+        self.peek(offset) == Some(&Token::Punct(punct))
+    }
+
+    /// The unescaped name at `offset`, for keyword checks
+    fn keyword(&self, offset: usize) -> Option<&str> {
+        match self.peek(offset) {
+            Some(Token::Name { text, escaped: false }) => Some(text.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Whether a section header (`dimensions:`, `group:` ...) starts here
+    fn at_section(&self) -> bool {
+        matches!(self.keyword(0), Some("types" | "dimensions" | "variables" | "data" | "group")) && self.is_punct(1, ':')
+    }
+
+    fn type_keyword(&self, offset: usize) -> Option<&'static str> {
+        let keyword = self.keyword(offset)?;
+        TYPE_KEYWORDS.iter().find(|(k, _)| *k == keyword).map(|(_, t)| *t)
+    }
+
+    /// Sections of a group until its closing '}'. `scope` holds the
+    /// dimensions of the enclosing groups.
+    fn group_body(&mut self, group: &mut CdlGroup, scope: &[Dimension]) -> Result<(), NetCDFError> {
+        while !self.is_punct(0, '}') {
+            if !self.at_section() {
+                return Err(self.error("expected 'dimensions:', 'variables:', 'data:' or 'group:'"));
+            }
+            let section = self.name()?;
+            self.expect(':')?;
+            match section.as_str() {
+                "types" => return Err(self.error("user-defined types are not supported")),
+                "dimensions" => self.dimensions(group)?,
+                "variables" => {
+                    let mut visible = scope.to_vec();
+                    visible.extend(group.dimensions.iter().cloned());
+                    self.variables(group, &visible)?;
+                }
+                "data" => self.data(group)?,
+                _ => {
+                    let mut child = CdlGroup {
+                        name: self.name()?,
+                        ..CdlGroup::default()
+                    };
+                    self.expect('{')?;
+                    let mut visible = scope.to_vec();
+                    visible.extend(group.dimensions.iter().cloned());
+                    self.group_body(&mut child, &visible)?;
+                    self.expect('}')?;
+                    group.groups.push(child);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn dimensions(&mut self, group: &mut CdlGroup) -> Result<(), NetCDFError> {
+        while !self.at_section() && !self.is_punct(0, '}') {
+            let name = self.name()?;
+            self.expect('=')?;
+            let dim = match self.next()? {
+                Token::Name { text, .. } if text.eq_ignore_ascii_case("unlimited") => Dimension {
+                    name,
+                    size: 0,
+                    is_unlimited: true,
+                },
+                Token::Number(n) => Dimension {
+                    size: n.parse().map_err(|_| self.error("invalid dimension size"))?,
+                    name,
+                    is_unlimited: false,
+                },
+                _ => return Err(self.error("expected a dimension size or UNLIMITED")),
+            };
+            group.dimensions.push(dim);
+            if !self.is_punct(0, ',') {
+                self.expect(';')?;
+            } else {
+                self.pos += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn variables(&mut self, group: &mut CdlGroup, visible: &[Dimension]) -> Result<(), NetCDFError> {
+        while !self.at_section() && !self.is_punct(0, '}') {
+            let explicit_type = self.type_keyword(0);
+            let is_attribute = match explicit_type {
+                Some(_) => self.is_punct(1, ':') || self.is_punct(2, ':'),
+                None => true,
+            };
+            if explicit_type.is_some() {
+                self.pos += 1;
+            }
+
+            if is_attribute {
+                self.attribute(group, explicit_type)?;
+                continue;
+            }
+
+            let data_type = explicit_type.expect("declarations start with a type");
+            loop {
+                let name = self.name()?;
+                let mut dimensions = Vec::new();
+                if self.is_punct(0, '(') {
+                    self.pos += 1;
+                    while !self.is_punct(0, ')') {
+                        dimensions.push(self.name()?);
+                        if self.is_punct(0, ',') {
+                            self.pos += 1;
+                        }
+                    }
+                    self.expect(')')?;
+                }
+
+                let shape = dimensions
+                    .iter()
+                    .map(|d| {
+                        visible
+                            .iter()
+                            .rev()
+                            .find(|dim| &dim.name == d)
+                            .map(|dim| dim.size)
+                            .ok_or_else(|| self.error(&format!("undefined dimension '{}'", d)))
+                    })
+                    .collect::<Result<_, _>>()?;
+                group.variables.push(Variable {
+                    name,
+                    data_type: data_type.to_string(),
+                    dimensions,
+                    shape,
+                    attributes: HashMap::new(),
+                });
+
+                if self.is_punct(0, ',') {
+                    self.pos += 1;
+                } else {
+                    self.expect(';')?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `[var]:name = values ;` (after any type keyword)
+    fn attribute(&mut self, group: &mut CdlGroup, explicit_type: Option<&'static str>) -> Result<(), NetCDFError> {
+        let variable = if self.is_punct(0, ':') { None } else { Some(self.name()?) };
+        self.expect(':')?;
+        let name = self.name()?;
+        self.expect('=')?;
+
+        let mut literals = Vec::new();
+        loop {
+            let literal = match self.next()? {
+                Token::Text(s) => Literal::Text(s),
+                Token::Number(n) => {
+                    let (value, data_type) = parse_number(&n).ok_or_else(|| self.error("invalid number"))?;
+                    match parse_integer(&n) {
+                        Some(i) => Literal::Integer(i, data_type),
+                        None => Literal::Number(value, data_type),
+                    }
+                }
+                Token::Name { text, .. } => {
+                    let (value, data_type) = parse_number(&text).ok_or_else(|| self.error("expected a value"))?;
+                    Literal::Number(value, data_type)
+                }
+                _ => return Err(self.error("expected a value")),
+            };
+            literals.push(literal);
+            if self.is_punct(0, ',') {
+                self.pos += 1;
+            } else {
+                self.expect(';')?;
+                break;
+            }
+        }
+
+        let target = match &variable {
+            Some(v) => Some(
+                group
+                    .variables
+                    .iter_mut()
+                    .find(|var| &var.name == v)
+                    .ok_or_else(|| self.error(&format!("attribute of undeclared variable '{}'", v)))?,
+            ),
+            None => None,
+        };
+
+        // Fill and valid-range attributes take their variable's type, like ncgen
+        let variable_type = target
+            .as_ref()
+            .filter(|_| VARIABLE_TYPED_ATTRS.contains(&name.as_str()))
+            .map(|v| v.data_type.clone())
+            .filter(|t| !is_text_type(t));
+        let data_type = match (explicit_type, variable_type, &literals[0]) {
+            (Some(t), _, _) => t.to_string(),
+            (None, Some(t), _) => t,
+            (None, None, Literal::Text(_)) => "Basic(Char)".to_string(),
+            (None, None, Literal::Number(_, t) | Literal::Integer(_, t)) => t.to_string(),
+        };
+        let value = attribute_value(&data_type, &literals)?;
+
+        if let Some(var) = target {
+            if !SPECIAL_ATTRS.contains(&name.as_str()) {
+                var.attributes.insert(name.clone(), attribute_value_to_string(value.clone()));
+            }
+        }
+        group.attributes.push(CdlAttribute { variable, name, value });
+        Ok(())
+    }
+
+    fn data(&mut self, group: &mut CdlGroup) -> Result<(), NetCDFError> {
+        while !self.at_section() && !self.is_punct(0, '}') {
+            let name = self.name()?;
+            if !group.variables.iter().any(|v| v.name == name) {
+                return Err(self.error(&format!("data for undeclared variable '{}'", name)));
+            }
+            self.expect('=')?;
+
+            let mut values = Vec::new();
+            loop {
+                match self.next()? {
+                    // Braces only group values visually
+                    Token::Punct('{') | Token::Punct('}') | Token::Punct(',') => {}
+                    Token::Punct(';') => break,
+                    Token::Fill => values.push(CdlValue::Fill),
+                    Token::Text(s) => values.push(CdlValue::Text(s)),
+                    Token::Number(n) | Token::Name { text: n, .. } => {
+                        let (value, _) = parse_number(&n).ok_or_else(|| self.error("invalid number"))?;
+                        values.push(match parse_integer(&n) {
+                            Some(i) => CdlValue::Integer(i),
+                            None => CdlValue::Number(value),
+                        });
+                    }
+                    _ => return Err(self.error("expected a value")),
+                }
+            }
+            group.data.insert(name, values);
+        }
+        Ok(())
+    }
+}
+
+fn cdl_error<S: AsRef<str>>(line: usize, message: S) -> NetCDFError {
+    NetCDFError::InvalidFormat(format!("CDL line {}: {}", line, message.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netcdf::backend::open_dataset;
+    use crate::netcdf::data_access::NativeValues;
+
+    const SAMPLE: &str = r#"netcdf sample {
+dimensions:
+	time = UNLIMITED ; // (2 currently)
+	lat = 2, lon = 3 ;
+variables:
+	double time(time) ;
+		time:units = "hours since 2024-01-01" ;
+	float t(time, lat, lon) ;
+		t:_FillValue = -999 ;
+		t:valid_range = 200, 330 ;
+		t:_DeflateLevel = 4 ;
+	char station(lat, lon) ;
+	string names(lat) ;
+	int64 big(lat) ;
+		big:valid_max = 9223372036854775807 ;
+
+// global attributes:
+		:title = "CDL \"fixture\"" ;
+		string :keywords = "a", "b" ;
+		:version = 2s ;
+data:
+
+ time = 0, 6 ;
+
+ t = 280.5, _, 282, 283, 284, 285,
+    { 1e2f, 2, 3, 4, 5, 6 } ;
+
+ station = "ab", "c" ;
+
+ names = "north", "south" ;
+
+ big = 9223372036854775807, _ ;
+
+group: obs {
+  dimensions:
+  	n = 3 ;
+  variables:
+  	int count(time, n) ;
+  data:
+   count = 1, 2, 3, 4, 5, 6, 7, 8, 9 ;
+  } // group obs
+}
+"#;
+
+    #[test]
+    fn test_parse_sample() {
+        let dataset = parse_cdl(SAMPLE).unwrap();
+        assert_eq!(dataset.name, "sample");
+
+        let root = &dataset.root;
+        // The unlimited dimension grows to the longest data along it (the group's 3 rows)
+        assert_eq!(root.dimensions[0].size, 3);
+        assert!(root.dimensions[0].is_unlimited);
+        assert_eq!(root.dimensions[2].size, 3);
+
+        let t = &root.variables[1];
+        assert_eq!(t.data_type, "Basic(Float)");
+        assert_eq!(t.shape, vec![3, 2, 3]);
+        assert_eq!(t.attributes["_FillValue"], "-999");
+        assert_eq!(t.attributes["valid_range"], "[200.0, 330.0]");
+        assert!(!t.attributes.contains_key("_DeflateLevel"));
+        assert_eq!(root.data["t"][1], CdlValue::Fill);
+        assert_eq!(root.data["t"][6], CdlValue::Number(100.0));
+        assert_eq!(root.data["big"][0], CdlValue::Integer(i64::MAX as i128));
+
+        let metadata = dataset.metadata("sample.nc");
+        assert_eq!(metadata.global_attrs["title"], "CDL \"fixture\"");
+        assert_eq!(metadata.global_attrs["keywords"], "[\"a\", \"b\"]");
+        assert_eq!(metadata.global_attrs["version"], "2");
+
+        let obs = &root.groups[0];
+        assert_eq!(obs.name, "obs");
+        assert_eq!(obs.variables[0].shape, vec![3, 3]);
+    }
+
+    #[test]
+    fn test_generate_round_trip() {
+        let temp = tempfile::tempdir().unwrap();
+        let output = temp.path().join("sample.nc").to_string_lossy().to_string();
+        let request = CdlGenerateRequest { output_path: output.clone(), format: None };
+        let summary = generate_netcdf(SAMPLE, &request).unwrap();
+        assert_eq!(summary.variables, vec!["time", "t", "station", "names", "big", "obs/count"]);
+
+        let expected = cdl_metadata(SAMPLE).unwrap();
+        let metadata = crate::netcdf::open_netcdf(&output).unwrap();
+        for var in &expected.variables {
+            let found = metadata.variables.iter().find(|v| v.name == var.name).unwrap();
+            assert_eq!((&found.data_type, &found.shape), (&var.data_type, &var.shape));
+        }
+
+        // Attributes keep the types the CDL gave them
+        let dataset = open_dataset(&output).unwrap();
+        let parsed = parse_cdl(SAMPLE).unwrap();
+        for attr in parsed.root.attributes.iter().filter(|a| !SPECIAL_ATTRS.contains(&a.name.as_str())) {
+            let stored = dataset.attributes(attr.variable.as_deref()).unwrap();
+            assert!(stored.contains(&(attr.name.clone(), attr.value.clone())), "{:?} not in {:?}", attr, stored);
+        }
+        assert_eq!(
+            parsed.root.attributes.iter().find(|a| a.name == "valid_max").unwrap().value,
+            AttributeValue::Longlong(i64::MAX)
+        );
+
+        // Character rows, strings, fill padding and exact 64-bit integers
+        assert_eq!(dataset.read_text("station", &[0, 0], &[2, 3]).unwrap(), vec!["ab", "c"]);
+        assert_eq!(dataset.read_text("names", &[0], &[2]).unwrap(), vec!["north", "south"]);
+        let t = dataset.read_native("t", &[0, 0, 0], &[3, 2, 3]).unwrap();
+        assert!(matches!(t, NativeValues::F32(v) if v[..3] == [280.5, -999.0, 282.0] && v[12..] == [-999.0; 6]));
+        let big = dataset.read_native("big", &[0], &[2]).unwrap();
+        assert!(matches!(big, NativeValues::I64(v) if v == vec![i64::MAX, -9223372036854775806]));
+
+        // The unlimited dimension and the group written through it
+        let file = netcdf::open(&output).unwrap();
+        let time = file.dimension("time").unwrap();
+        assert!(time.is_unlimited());
+        assert_eq!(time.len(), 3);
+        let obs = file.group("obs").unwrap().unwrap();
+        let count = obs.variable("count").unwrap();
+        assert_eq!(count.get_values::<i32, _>(..).unwrap(), (1..=9).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_char_data_pads_rows() {
+        let values = vec![CdlValue::Text("ab".to_string()), CdlValue::Text("c".to_string())];
+        assert_eq!(char_bytes(&values, 3), b"ab\0c\0\0".to_vec());
+    }
+
+    #[test]
+    fn test_number_literals() {
+        assert_eq!(parse_number("2s"), Some((2.0, "Basic(Short)")));
+        assert_eq!(parse_number("1.5f"), Some((1.5, "Basic(Float)")));
+        assert_eq!(parse_number("-3"), Some((-3.0, "Basic(Int)")));
+        assert_eq!(parse_number("1e3"), Some((1000.0, "Basic(Double)")));
+        assert_eq!(parse_number("255ub"), Some((255.0, "Basic(Ubyte)")));
+        assert_eq!(parse_number("10ULL"), Some((10.0, "Basic(Uint64)")));
+        assert!(parse_number("NaNf").is_some_and(|(v, t)| v.is_nan() && t == "Basic(Float)"));
+        assert_eq!(parse_integer("18446744073709551615ULL"), Some(u64::MAX as i128));
+        assert_eq!(parse_integer("1.5"), None);
+    }
+
+    #[test]
+    fn test_parse_errors_report_lines() {
+        let err = parse_cdl("netcdf x {\ndimensions:\n  n = 2 ;\nvariables:\n  int v(m) ;\n}").unwrap_err();
+        assert!(err.to_string().contains("line 5"), "{}", err);
+    }
+}
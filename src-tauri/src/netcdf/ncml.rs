@@ -0,0 +1,1270 @@
+use crate::errors::NetCDFError;
+use crate::models::{Dimension, FileMetadata, Variable};
+use super::aggregation::{AggregatedDataset, JoinOptions, Layout};
+use super::backend::{open_dataset, DatasetBackend};
+use super::data_access::{find_variable, is_text_type, NativeValues};
+use super::derived::with_derived;
+use super::loader::attribute_value_to_string;
+use super::table::next_index;
+use super::zarr::ZarrStore;
+use netcdf::AttributeValue;
+use roxmltree::Node;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// NcML type names and the data type strings used throughout the models
+const NCML_TYPES: &[(&str, &str)] = &[
+    ("char", "Basic(Char)"),
+    ("byte", "Basic(Byte)"),
+    ("ubyte", "Basic(Ubyte)"),
+    ("short", "Basic(Short)"),
+    ("ushort", "Basic(Ushort)"),
+    ("int", "Basic(Int)"),
+    ("uint", "Basic(Uint)"),
+    ("long", "Basic(Int64)"),
+    ("ulong", "Basic(Uint64)"),
+    ("int64", "Basic(Int64)"),
+    ("uint64", "Basic(Uint64)"),
+    ("float", "Basic(Float)"),
+    ("double", "Basic(Double)"),
+    ("String", "String"),
+    ("string", "String"),
+];
+
+/// Directory NcML locations must stay inside, once set by `confine_locations`
+static ALLOWED_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Refuse NcML locations outside `root` for the rest of the process.
+///
+/// The server calls this so that a document below its root can reach
+/// neither other files, through absolute or `..` paths, nor other hosts,
+/// through URLs.
+pub fn confine_locations(root: &Path) {
+    let _ = ALLOWED_ROOT.set(root.to_path_buf());
+}
+
+/// Whether a path is an NcML document
+pub fn is_ncml(path: &str) -> bool {
+    !path.contains("://")
+        && Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ncml"))
+}
+
+/// Values of a variable given in the NcML document
+#[derive(Debug, Clone)]
+enum Values {
+    Numbers(Vec<f64>),
+    Text(Vec<String>),
+}
+
+impl Values {
+    fn len(&self) -> usize {
+        match self {
+            Values::Numbers(v) => v.len(),
+            Values::Text(v) => v.len(),
+        }
+    }
+}
+
+/// A virtual dataset described by an NcML document, as published by
+/// THREDDS.
+///
+/// The document wraps one dataset (`location`) or an aggregation of several
+/// (`joinExisting`, `joinNew` or `union`) and may rename variables and
+/// dimensions, add, override or remove attributes, and define variables of
+/// its own. Reads of the underlying variables go to the wrapped datasets.
+pub struct NcmlDataset {
+    metadata: FileMetadata,
+    source: Option<Box<dyn DatasetBackend>>,
+    /// Name in the wrapped dataset of each renamed variable
+    org_names: HashMap<String, String>,
+    /// Variables whose values are given in the document
+    values: HashMap<String, Values>,
+    /// Fill values set (or removed, as `None`) by the document
+    fill_values: HashMap<String, Option<f64>>,
+}
+
+impl NcmlDataset {
+    /// Open an NcML file; relative locations are resolved against its directory
+    pub fn open(path: &str) -> Result<Self, NetCDFError> {
+        Self::open_nested(path, ALLOWED_ROOT.get().map(PathBuf::as_path), &[])
+    }
+
+    /// Open an NcML file located by the documents in `parents`, which it may not refer back to
+    fn open_nested(path: &str, allowed: Option<&Path>, parents: &[PathBuf]) -> Result<Self, NetCDFError> {
+        let open_error = |e: std::io::Error| NetCDFError::FileOpenError(format!("Failed to open {}: {}", path, e));
+        let canonical = Path::new(path).canonicalize().map_err(open_error)?;
+        if parents.contains(&canonical) {
+            return Err(NetCDFError::InvalidFormat(format!("NcML {} refers back to itself", path)));
+        }
+        let text = std::fs::read_to_string(&canonical).map_err(open_error)?;
+        let document = roxmltree::Document::parse(&text)
+            .map_err(|e| NetCDFError::InvalidFormat(format!("NcML {}: {}", path, e)))?;
+        let root = document.root_element();
+        if !root.has_tag_name("netcdf") {
+            return Err(NetCDFError::InvalidFormat(format!("{} is not an NcML document", path)));
+        }
+
+        let documents: Vec<PathBuf> = parents.iter().cloned().chain([canonical.clone()]).collect();
+        let locations = Locations {
+            base: canonical.parent().unwrap_or(Path::new(".")),
+            allowed,
+            documents: &documents,
+        };
+        let mut dataset = Self::build(root, &locations)?;
+        dataset.metadata.file_path = path.to_string();
+        Ok(dataset)
+    }
+
+    /// Build the dataset described by a `<netcdf>` element
+    fn build(node: Node, locations: &Locations) -> Result<Self, NetCDFError> {
+        let source = match child(node, "aggregation") {
+            Some(aggregation) => Some(open_aggregation(aggregation, locations)?),
+            None => match node.attribute("location") {
+                Some(location) => Some(locations.open(&locations.resolve(location)?)?),
+                None => None,
+            },
+        };
+        let original = match &source {
+            Some(source) => source.metadata()?,
+            None => empty_metadata(),
+        };
+
+        // With <explicit/>, only what the document declares is kept
+        let explicit = child(node, "explicit").is_some();
+        let mut dataset = NcmlDataset {
+            metadata: if explicit { empty_metadata() } else { original.clone() },
+            source,
+            org_names: HashMap::new(),
+            values: HashMap::new(),
+            fill_values: HashMap::new(),
+        };
+        let fallback = explicit.then_some(&original);
+
+        for element in node.children().filter(Node::is_element) {
+            match element.tag_name().name() {
+                "attribute" => {
+                    set_attribute(&mut dataset.metadata.global_attrs, element)?;
+                }
+                "dimension" => dataset.apply_dimension(element, fallback)?,
+                "variable" => dataset.apply_variable(element, fallback)?,
+                "remove" => dataset.apply_remove(element)?,
+                "aggregation" | "explicit" | "readMetadata" => {}
+                "group" => {
+                    return Err(NetCDFError::InvalidFormat("NcML groups are not supported".to_string()));
+                }
+                other => {
+                    return Err(NetCDFError::InvalidFormat(format!("NcML: unexpected element <{}>", other)));
+                }
+            }
+        }
+        Ok(dataset)
+    }
+
+    /// Index of a dimension, copied from the wrapped dataset under `<explicit/>`
+    fn dimension_index(&mut self, name: &str, fallback: Option<&FileMetadata>) -> Option<usize> {
+        if let Some(index) = self.metadata.dimensions.iter().position(|d| d.name == name) {
+            return Some(index);
+        }
+        let dim = fallback?.dimensions.iter().find(|d| d.name == name)?;
+        self.metadata.dimensions.push(dim.clone());
+        Some(self.metadata.dimensions.len() - 1)
+    }
+
+    /// Index of a variable, copied with its dimensions from the wrapped
+    /// dataset under `<explicit/>`
+    fn variable_index(&mut self, name: &str, fallback: Option<&FileMetadata>) -> Option<usize> {
+        if let Some(index) = self.metadata.variables.iter().position(|v| v.name == name) {
+            return Some(index);
+        }
+        let var = fallback?.variables.iter().find(|v| v.name == name)?.clone();
+        for dim in &var.dimensions {
+            self.dimension_index(dim, fallback);
+        }
+        self.metadata.variables.push(var);
+        Some(self.metadata.variables.len() - 1)
+    }
+
+    fn apply_dimension(&mut self, element: Node, fallback: Option<&FileMetadata>) -> Result<(), NetCDFError> {
+        let name = required(element, "name")?;
+        if let Some(org_name) = element.attribute("orgName") {
+            let index = self
+                .dimension_index(org_name, fallback)
+                .ok_or_else(|| NetCDFError::DimensionNotFound(org_name.to_string()))?;
+            self.metadata.dimensions[index].name = name.to_string();
+            for var in &mut self.metadata.variables {
+                for dim in var.dimensions.iter_mut().filter(|d| d.as_str() == org_name) {
+                    *dim = name.to_string();
+                }
+            }
+        }
+
+        let length = element
+            .attribute("length")
+            .map(|l| {
+                l.trim()
+                    .parse::<usize>()
+                    .map_err(|_| NetCDFError::InvalidFormat(format!("NcML dimension {}: invalid length {}", name, l)))
+            })
+            .transpose()?;
+        match (self.dimension_index(name, fallback), length) {
+            (Some(index), Some(length)) if self.metadata.dimensions[index].size != length => {
+                Err(NetCDFError::InvalidFormat(format!(
+                    "NcML cannot change the length of dimension {}",
+                    name
+                )))
+            }
+            (Some(_), _) => Ok(()),
+            (None, Some(length)) => {
+                self.metadata.dimensions.push(Dimension {
+                    name: name.to_string(),
+                    size: length,
+                    is_unlimited: element.attribute("isUnlimited") == Some("true"),
+                });
+                Ok(())
+            }
+            (None, None) => Err(NetCDFError::DimensionNotFound(name.to_string())),
+        }
+    }
+
+    fn apply_variable(&mut self, element: Node, fallback: Option<&FileMetadata>) -> Result<(), NetCDFError> {
+        let name = required(element, "name")?;
+        if let Some(org_name) = element.attribute("orgName") {
+            self.rename_variable(org_name, name, fallback)?;
+        }
+
+        let index = match self.variable_index(name, fallback) {
+            Some(index) => index,
+            None => self.define_variable(element, name)?,
+        };
+        let values = child(element, "values");
+        if let (Some(type_name), Some(_)) = (element.attribute("type"), values) {
+            self.metadata.variables[index].data_type = ncml_type(type_name)?.to_string();
+        }
+
+        for item in element.children().filter(Node::is_element) {
+            let var = &mut self.metadata.variables[index];
+            match item.tag_name().name() {
+                "attribute" => {
+                    let fill = set_attribute(&mut var.attributes, item)?;
+                    if required(item, "name")? == "_FillValue" {
+                        self.fill_values.insert(name.to_string(), fill);
+                    }
+                }
+                "remove" => {
+                    let attr_name = required(item, "name")?;
+                    var.attributes.remove(attr_name);
+                    if attr_name == "_FillValue" {
+                        self.fill_values.insert(name.to_string(), None);
+                    }
+                }
+                "values" => {
+                    let values = parse_values(item, var)?;
+                    self.values.insert(name.to_string(), values);
+                }
+                other => {
+                    return Err(NetCDFError::InvalidFormat(format!(
+                        "NcML variable {}: unsupported element <{}>",
+                        name, other
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a variable that only exists in the document; its values must be given
+    fn define_variable(&mut self, element: Node, name: &str) -> Result<usize, NetCDFError> {
+        if child(element, "values").is_none() {
+            return Err(NetCDFError::VariableNotFound(name.to_string()));
+        }
+        let data_type = ncml_type(required(element, "type")?)?;
+        let dimensions: Vec<String> = element
+            .attribute("shape")
+            .unwrap_or("")
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        let shape = dimensions
+            .iter()
+            .map(|dim| {
+                self.metadata
+                    .dimensions
+                    .iter()
+                    .find(|d| &d.name == dim)
+                    .map(|d| d.size)
+                    .ok_or_else(|| NetCDFError::DimensionNotFound(dim.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.metadata.variables.push(Variable {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            dimensions,
+            shape,
+            attributes: HashMap::new(),
+        });
+        Ok(self.metadata.variables.len() - 1)
+    }
+
+    fn rename_variable(&mut self, org_name: &str, name: &str, fallback: Option<&FileMetadata>) -> Result<(), NetCDFError> {
+        let index = self
+            .variable_index(org_name, fallback)
+            .ok_or_else(|| NetCDFError::VariableNotFound(org_name.to_string()))?;
+        self.metadata.variables[index].name = name.to_string();
+
+        let source_name = self.org_names.remove(org_name).unwrap_or_else(|| org_name.to_string());
+        self.org_names.insert(name.to_string(), source_name);
+        if let Some(values) = self.values.remove(org_name) {
+            self.values.insert(name.to_string(), values);
+        }
+        if let Some(fill) = self.fill_values.remove(org_name) {
+            self.fill_values.insert(name.to_string(), fill);
+        }
+        Ok(())
+    }
+
+    fn apply_remove(&mut self, element: Node) -> Result<(), NetCDFError> {
+        let name = required(element, "name")?;
+        match element.attribute("type").unwrap_or("attribute") {
+            "attribute" => {
+                self.metadata.global_attrs.remove(name);
+            }
+            "variable" => {
+                self.metadata.variables.retain(|v| v.name != name);
+                self.values.remove(name);
+            }
+            "dimension" => {
+                if let Some(var) = self.metadata.variables.iter().find(|v| v.dimensions.iter().any(|d| d == name)) {
+                    return Err(NetCDFError::InvalidFormat(format!(
+                        "NcML cannot remove dimension {} used by variable {}",
+                        name, var.name
+                    )));
+                }
+                self.metadata.dimensions.retain(|d| d.name != name);
+            }
+            other => {
+                return Err(NetCDFError::InvalidFormat(format!("NcML: cannot remove a {}", other)));
+            }
+        }
+        Ok(())
+    }
+
+    /// The wrapped dataset and the name a variable has there
+    fn source<'a>(&'a self, var_name: &'a str) -> Result<(&'a dyn DatasetBackend, &'a str), NetCDFError> {
+        let not_found = || NetCDFError::VariableNotFound(var_name.to_string());
+        if !self.metadata.variables.iter().any(|v| v.name == var_name) {
+            return Err(not_found());
+        }
+        let source = self.source.as_deref().ok_or_else(not_found)?;
+        let name = self.org_names.get(var_name).map(String::as_str).unwrap_or(var_name);
+        Ok((source, name))
+    }
+}
+
+impl DatasetBackend for NcmlDataset {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        match self.values.get(var_name) {
+            Some(values) => read_values(&find_variable(&self.metadata, var_name)?, values, start, count),
+            None => {
+                let (source, name) = self.source(var_name)?;
+                source.read_native(name, start, count)
+            }
+        }
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        match self.values.get(var_name) {
+            Some(values) => read_values_text(&find_variable(&self.metadata, var_name)?, values, start, count),
+            None => {
+                let (source, name) = self.source(var_name)?;
+                source.read_text(name, start, count)
+            }
+        }
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        if let Some(fill) = self.fill_values.get(var_name) {
+            return *fill;
+        }
+        if self.values.contains_key(var_name) {
+            return None;
+        }
+        let (source, name) = self.source(var_name).ok()?;
+        source.fill_value(name)
+    }
+
+    fn read_f64(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<f64>, NetCDFError> {
+        match self.values.get(var_name) {
+            Some(_) => Ok(self.read_native(var_name, start, count)?.into_f64()),
+            None => {
+                let (source, name) = self.source(var_name)?;
+                source.read_f64(name, start, count)
+            }
+        }
+    }
+}
+
+/// Datasets stacked along a new outermost dimension (NcML `joinNew`)
+struct JoinNewDataset {
+    metadata: FileMetadata,
+    members: Vec<Box<dyn DatasetBackend>>,
+    dimension: String,
+    /// Variables that gain the new dimension
+    aggregated: Vec<String>,
+    /// Values of the new coordinate variable, one per member
+    coordinates: Values,
+}
+
+impl JoinNewDataset {
+    fn new(
+        members: Vec<(String, Box<dyn DatasetBackend>)>,
+        dimension: &str,
+        aggregated: Vec<String>,
+        coord_values: Vec<Option<String>>,
+    ) -> Result<Self, NetCDFError> {
+        let mut metadata = members[0].1.metadata()?;
+        if metadata.dimensions.iter().any(|d| d.name == dimension) {
+            return Err(NetCDFError::InvalidFormat(format!(
+                "NcML joinNew dimension {} already exists",
+                dimension
+            )));
+        }
+        for name in &aggregated {
+            let var = find_variable(&metadata, name)?;
+            for (path, dataset) in &members[1..] {
+                let other = find_variable(&dataset.metadata()?, name)?;
+                if other.shape != var.shape || other.data_type != var.data_type {
+                    return Err(NetCDFError::FileOpenError(format!(
+                        "Cannot join {}: variable '{}' differs from the first dataset",
+                        path, name
+                    )));
+                }
+            }
+        }
+
+        let n = members.len();
+        metadata.dimensions.insert(
+            0,
+            Dimension {
+                name: dimension.to_string(),
+                size: n,
+                is_unlimited: false,
+            },
+        );
+        for var in metadata.variables.iter_mut().filter(|v| aggregated.contains(&v.name)) {
+            var.dimensions.insert(0, dimension.to_string());
+            var.shape.insert(0, n);
+        }
+
+        // The coordinate comes from coordValue, or the member locations
+        let labels: Vec<String> = coord_values
+            .into_iter()
+            .zip(&members)
+            .map(|(value, (path, _))| value.unwrap_or_else(|| path.clone()))
+            .collect();
+        let coordinates = match labels.iter().map(|l| l.trim().parse::<f64>().ok()).collect() {
+            Some(numbers) => Values::Numbers(numbers),
+            None => Values::Text(labels),
+        };
+        if !metadata.variables.iter().any(|v| v.name == dimension) {
+            let data_type = match coordinates {
+                Values::Numbers(_) => "Basic(Double)",
+                Values::Text(_) => "String",
+            };
+            metadata.variables.push(Variable {
+                name: dimension.to_string(),
+                data_type: data_type.to_string(),
+                dimensions: vec![dimension.to_string()],
+                shape: vec![n],
+                attributes: HashMap::new(),
+            });
+        }
+
+        Ok(JoinNewDataset {
+            metadata,
+            members: members.into_iter().map(|(_, dataset)| dataset).collect(),
+            dimension: dimension.to_string(),
+            aggregated,
+            coordinates,
+        })
+    }
+
+    /// Read a hyperslab of an aggregated variable member by member
+    fn read_stacked<T>(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+        read: impl Fn(&dyn DatasetBackend, &[usize], &[usize]) -> Result<T, NetCDFError>,
+    ) -> Result<Vec<T>, NetCDFError> {
+        if start.is_empty() || count.len() != start.len() || start[0] + count[0] > self.members.len() {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Hyperslab start={:?} count={:?} does not fit variable '{}'",
+                start, count, var_name
+            )));
+        }
+        self.members[start[0]..start[0] + count[0]]
+            .iter()
+            .map(|member| read(member.as_ref(), &start[1..], &count[1..]))
+            .collect()
+    }
+
+    fn is_aggregated(&self, var_name: &str) -> bool {
+        self.aggregated.iter().any(|name| name == var_name)
+    }
+
+    fn coordinate_variable(&self) -> Result<Variable, NetCDFError> {
+        find_variable(&self.metadata, &self.dimension)
+    }
+}
+
+/// Stacked pieces are whole blocks, so they simply concatenate
+const STACKED: Layout = Layout { outer: 1, inner: 1, lengths: Vec::new() };
+
+impl DatasetBackend for JoinNewDataset {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        if var_name == self.dimension {
+            return read_values(&self.coordinate_variable()?, &self.coordinates, start, count);
+        }
+        if !self.is_aggregated(var_name) {
+            return self.members[0].read_native(var_name, start, count);
+        }
+        let pieces = self.read_stacked(var_name, start, count, |member, s, c| member.read_native(var_name, s, c))?;
+        STACKED.join_native(pieces, var_name)
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        if var_name == self.dimension {
+            return read_values_text(&self.coordinate_variable()?, &self.coordinates, start, count);
+        }
+        if !self.is_aggregated(var_name) {
+            return self.members[0].read_text(var_name, start, count);
+        }
+        let pieces = self.read_stacked(var_name, start, count, |member, s, c| member.read_text(var_name, s, c))?;
+        Ok(pieces.concat())
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        self.members[0].fill_value(var_name)
+    }
+
+    fn read_f64(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<f64>, NetCDFError> {
+        if var_name == self.dimension || !self.is_aggregated(var_name) {
+            return Ok(self.read_native(var_name, start, count)?.into_f64());
+        }
+        let pieces = self.read_stacked(var_name, start, count, |member, s, c| member.read_f64(var_name, s, c))?;
+        Ok(pieces.concat())
+    }
+}
+
+/// Variables of several datasets combined into one (NcML `union`); the
+/// first dataset holding a variable or attribute wins
+struct UnionDataset {
+    metadata: FileMetadata,
+    members: Vec<Box<dyn DatasetBackend>>,
+    /// Member each variable is read from
+    owners: HashMap<String, usize>,
+}
+
+impl UnionDataset {
+    fn new(members: Vec<(String, Box<dyn DatasetBackend>)>) -> Result<Self, NetCDFError> {
+        let mut metadata = empty_metadata();
+        let mut owners = HashMap::new();
+        for (index, (path, dataset)) in members.iter().enumerate() {
+            let member = dataset.metadata()?;
+            for dim in member.dimensions {
+                match metadata.dimensions.iter().find(|d| d.name == dim.name) {
+                    Some(existing) if existing.size != dim.size => {
+                        return Err(NetCDFError::FileOpenError(format!(
+                            "Cannot union {}: dimension '{}' has length {} instead of {}",
+                            path, dim.name, dim.size, existing.size
+                        )));
+                    }
+                    Some(_) => {}
+                    None => metadata.dimensions.push(dim),
+                }
+            }
+            for var in member.variables {
+                if !owners.contains_key(&var.name) {
+                    owners.insert(var.name.clone(), index);
+                    metadata.variables.push(var);
+                }
+            }
+            for (name, value) in member.global_attrs {
+                metadata.global_attrs.entry(name).or_insert(value);
+            }
+        }
+
+        Ok(UnionDataset {
+            metadata,
+            members: members.into_iter().map(|(_, dataset)| dataset).collect(),
+            owners,
+        })
+    }
+
+    fn member(&self, var_name: &str) -> Result<&dyn DatasetBackend, NetCDFError> {
+        self.owners
+            .get(var_name)
+            .map(|&index| self.members[index].as_ref())
+            .ok_or_else(|| NetCDFError::VariableNotFound(var_name.to_string()))
+    }
+}
+
+impl DatasetBackend for UnionDataset {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        self.member(var_name)?.read_native(var_name, start, count)
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        self.member(var_name)?.read_text(var_name, start, count)
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        self.member(var_name).ok()?.fill_value(var_name)
+    }
+
+    fn read_f64(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<f64>, NetCDFError> {
+        self.member(var_name)?.read_f64(var_name, start, count)
+    }
+}
+
+/// Open the datasets of an `<aggregation>` element and join them
+fn open_aggregation(element: Node, locations: &Locations) -> Result<Box<dyn DatasetBackend>, NetCDFError> {
+    let kind = required(element, "type")?;
+    let mut members: Vec<(String, Box<dyn DatasetBackend>)> = Vec::new();
+    let mut coord_values = Vec::new();
+    for item in element.children().filter(Node::is_element) {
+        match item.tag_name().name() {
+            "netcdf" => {
+                let location = item.attribute("location").map(|l| locations.resolve(l)).transpose()?;
+                // Members with content of their own are NcML datasets themselves
+                let dataset: Box<dyn DatasetBackend> = match (&location, item.children().any(|c| c.is_element())) {
+                    (_, true) => Box::new(NcmlDataset::build(item, locations)?),
+                    (Some(location), false) => locations.open(location)?,
+                    (None, false) => {
+                        return Err(NetCDFError::InvalidFormat(
+                            "NcML aggregation member has no location".to_string(),
+                        ));
+                    }
+                };
+                coord_values.push(item.attribute("coordValue").map(str::to_string));
+                members.push((location.unwrap_or_else(|| "NcML dataset".to_string()), dataset));
+            }
+            "scan" => {
+                for path in scan(item, locations)? {
+                    let dataset = locations.open(&path)?;
+                    coord_values.push(None);
+                    members.push((path, dataset));
+                }
+            }
+            "variableAgg" => {}
+            other => {
+                return Err(NetCDFError::InvalidFormat(format!(
+                    "NcML aggregation: unsupported element <{}>",
+                    other
+                )));
+            }
+        }
+    }
+    if members.is_empty() {
+        return Err(NetCDFError::FileOpenError("NcML aggregation has no datasets".to_string()));
+    }
+
+    let dimension = element.attribute("dimName");
+    match kind {
+        "joinExisting" => {
+            let options = JoinOptions {
+                dimension: dimension.map(str::to_string),
+                keep_order: true,
+            };
+            Ok(Box::new(AggregatedDataset::join(members, "NcML aggregation", &options)?))
+        }
+        "joinNew" => {
+            let dimension = dimension.ok_or_else(|| {
+                NetCDFError::InvalidFormat("NcML joinNew aggregation needs a dimName".to_string())
+            })?;
+            let variables = element
+                .children()
+                .filter(|c| c.has_tag_name("variableAgg"))
+                .map(|c| required(c, "name").map(str::to_string))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Box::new(JoinNewDataset::new(members, dimension, variables, coord_values)?))
+        }
+        "union" => Ok(Box::new(UnionDataset::new(members)?)),
+        other => Err(NetCDFError::InvalidFormat(format!(
+            "NcML aggregation type {} is not supported",
+            other
+        ))),
+    }
+}
+
+/// Files (and Zarr stores) matched by a `<scan>` element, sorted by path.
+///
+/// The scanning documents themselves are left out, as are files outside the
+/// allowed root.
+fn scan(element: Node, locations: &Locations) -> Result<Vec<String>, NetCDFError> {
+    if element.attribute("regExp").is_some() {
+        return Err(NetCDFError::InvalidFormat(
+            "NcML scan: regExp is not supported, use suffix".to_string(),
+        ));
+    }
+    let dir = locations.resolve(required(element, "location")?)?;
+    let suffix = element.attribute("suffix").unwrap_or("");
+    let subdirs = element.attribute("subdirs") != Some("false");
+
+    let mut found = Vec::new();
+    collect_files(Path::new(&dir), suffix, subdirs, &mut HashSet::new(), &mut found)?;
+    let mut paths = Vec::new();
+    for path in found {
+        let canonical = path.canonicalize()?;
+        let outside = locations.allowed.is_some_and(|root| !canonical.starts_with(root));
+        if !outside && !locations.documents.contains(&canonical) {
+            paths.push(path.to_string_lossy().into_owned());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Files below `dir` ending in `suffix`; `visited` holds the directories
+/// already listed, so symlinks leading back up the tree are not followed again
+fn collect_files(
+    dir: &Path,
+    suffix: &str,
+    subdirs: bool,
+    visited: &mut HashSet<PathBuf>,
+    paths: &mut Vec<PathBuf>,
+) -> Result<(), NetCDFError> {
+    if !visited.insert(dir.canonicalize()?) {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(suffix) && (path.is_file() || ZarrStore::is_store(&path)) {
+            paths.push(path);
+        } else if subdirs && path.is_dir() {
+            collect_files(&path, suffix, subdirs, visited, paths)?;
+        }
+    }
+    Ok(())
+}
+
+/// Where the locations of one NcML document lead
+struct Locations<'a> {
+    /// Directory of the document, which relative locations start from
+    base: &'a Path,
+    /// Directory every location must stay inside, when confined
+    allowed: Option<&'a Path>,
+    /// Canonical paths of the document and of the documents that opened it
+    documents: &'a [PathBuf],
+}
+
+impl Locations<'_> {
+    /// Resolve a `location`, refusing URLs, absolute paths and paths leaving
+    /// the allowed root when there is one
+    fn resolve(&self, location: &str) -> Result<String, NetCDFError> {
+        let resolved = resolve_location(location, self.base);
+        let Some(root) = self.allowed else {
+            return Ok(resolved);
+        };
+        let outside = || {
+            NetCDFError::FileOpenError(format!("NcML location {} is outside the served directory", location))
+        };
+        if resolved.contains("://") || Path::new(strip_file_scheme(location)).is_absolute() {
+            return Err(outside());
+        }
+        let canonical = Path::new(&resolved)
+            .canonicalize()
+            .map_err(|_| NetCDFError::FileOpenError(format!("File not found: {}", location)))?;
+        if !canonical.starts_with(root) {
+            return Err(outside());
+        }
+        Ok(canonical.to_string_lossy().into_owned())
+    }
+
+    /// Open a resolved location; NcML documents may not refer back to one that opened them
+    fn open(&self, path: &str) -> Result<Box<dyn DatasetBackend>, NetCDFError> {
+        if !is_ncml(path) {
+            return open_dataset(path);
+        }
+        let dataset = NcmlDataset::open_nested(path, self.allowed, self.documents)?;
+        with_derived(path, Box::new(dataset))
+    }
+}
+
+fn strip_file_scheme(location: &str) -> &str {
+    location
+        .strip_prefix("file://")
+        .or_else(|| location.strip_prefix("file:"))
+        .unwrap_or(location)
+}
+
+/// Resolve a `location` against the directory of the NcML file.
+///
+/// `file:` prefixes are dropped and `dods://` URLs become `http://` OPeNDAP URLs.
+fn resolve_location(location: &str, base: &Path) -> String {
+    if let Some(rest) = location.strip_prefix("dods:") {
+        return format!("http:{}", rest);
+    }
+    let location = strip_file_scheme(location);
+    if location.contains("://") || Path::new(location).is_absolute() {
+        location.to_string()
+    } else {
+        base.join(location).to_string_lossy().to_string()
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|c| c.has_tag_name(name))
+}
+
+fn required<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, NetCDFError> {
+    node.attribute(name).ok_or_else(|| {
+        NetCDFError::InvalidFormat(format!(
+            "NcML <{}> is missing the {} attribute",
+            node.tag_name().name(),
+            name
+        ))
+    })
+}
+
+fn empty_metadata() -> FileMetadata {
+    FileMetadata {
+        file_path: String::new(),
+        dimensions: Vec::new(),
+        variables: Vec::new(),
+        global_attrs: HashMap::new(),
+        coordinates: None,
+    }
+}
+
+/// Data type string for an NcML type name
+fn ncml_type(type_name: &str) -> Result<&'static str, NetCDFError> {
+    NCML_TYPES
+        .iter()
+        .find(|(name, _)| *name == type_name)
+        .map(|(_, data_type)| *data_type)
+        .ok_or_else(|| NetCDFError::InvalidFormat(format!("NcML type {} is not supported", type_name)))
+}
+
+/// Values of an attribute or `<values>` element, split on `separator` or whitespace
+fn split_values<'a>(text: &'a str, separator: Option<&str>) -> Vec<&'a str> {
+    match separator {
+        Some(separator) => text.split(separator).collect(),
+        None => text.split_whitespace().collect(),
+    }
+}
+
+/// Add, override or rename an attribute, returning its numeric value
+/// when it is a numeric `_FillValue`
+fn set_attribute(attrs: &mut HashMap<String, String>, element: Node) -> Result<Option<f64>, NetCDFError> {
+    let name = required(element, "name")?;
+    if let Some(org_name) = element.attribute("orgName") {
+        if let Some(value) = attrs.remove(org_name) {
+            attrs.insert(name.to_string(), value);
+        }
+    }
+    let Some(text) = element.attribute("value").or_else(|| element.text()) else {
+        return Ok(None);
+    };
+
+    let data_type = ncml_type(element.attribute("type").unwrap_or("String"))?;
+    let separator = element.attribute("separator");
+    let value = attribute_value(data_type, text, separator)?;
+    let fill = match (&value, name) {
+        (AttributeValue::Str(_) | AttributeValue::Strs(_), _) => None,
+        (_, "_FillValue") => split_values(text, separator).first().and_then(|v| v.trim().parse().ok()),
+        _ => None,
+    };
+    attrs.insert(name.to_string(), attribute_value_to_string(value));
+    Ok(fill)
+}
+
+/// Build a typed attribute value from NcML text
+fn attribute_value(data_type: &str, text: &str, separator: Option<&str>) -> Result<AttributeValue, NetCDFError> {
+    if is_text_type(data_type) {
+        return Ok(match separator {
+            Some(separator) => AttributeValue::Strs(text.split(separator).map(str::to_string).collect()),
+            None => AttributeValue::Str(text.to_string()),
+        });
+    }
+
+    let parts = split_values(text, separator);
+    macro_rules! typed {
+        ($t:ty, $one:ident, $many:ident) => {{
+            let parsed: Vec<$t> = parts
+                .iter()
+                .map(|v| {
+                    v.trim()
+                        .parse::<$t>()
+                        .map_err(|_| NetCDFError::InvalidFormat(format!("NcML: invalid {} value {}", data_type, v)))
+                })
+                .collect::<Result<_, _>>()?;
+            if parsed.len() == 1 {
+                AttributeValue::$one(parsed[0])
+            } else {
+                AttributeValue::$many(parsed)
+            }
+        }};
+    }
+    Ok(match data_type {
+        "Basic(Byte)" => typed!(i8, Schar, Schars),
+        "Basic(Ubyte)" => typed!(u8, Uchar, Uchars),
+        "Basic(Short)" => typed!(i16, Short, Shorts),
+        "Basic(Ushort)" => typed!(u16, Ushort, Ushorts),
+        "Basic(Int)" => typed!(i32, Int, Ints),
+        "Basic(Uint)" => typed!(u32, Uint, Uints),
+        "Basic(Int64)" => typed!(i64, Longlong, Longlongs),
+        "Basic(Uint64)" => typed!(u64, Ulonglong, Ulonglongs),
+        "Basic(Float)" => typed!(f32, Float, Floats),
+        _ => typed!(f64, Double, Doubles),
+    })
+}
+
+/// Parse a `<values>` element: a list, or `start`/`increment`/`npts`
+fn parse_values(element: Node, var: &Variable) -> Result<Values, NetCDFError> {
+    let invalid = |what: &str| NetCDFError::InvalidFormat(format!("NcML variable {}: invalid {}", var.name, what));
+    let expected: usize = text_shape(var).iter().product();
+
+    let values = if let Some(start) = element.attribute("start") {
+        if is_text_type(&var.data_type) {
+            return Err(invalid("start for a text variable"));
+        }
+        let start: f64 = start.trim().parse().map_err(|_| invalid("start"))?;
+        let increment: f64 = required(element, "increment")?
+            .trim()
+            .parse()
+            .map_err(|_| invalid("increment"))?;
+        let npts = match element.attribute("npts") {
+            Some(n) => n.trim().parse().map_err(|_| invalid("npts"))?,
+            None => expected,
+        };
+        Values::Numbers((0..npts).map(|i| start + i as f64 * increment).collect())
+    } else {
+        let parts = split_values(element.text().unwrap_or(""), element.attribute("separator"));
+        if is_text_type(&var.data_type) {
+            Values::Text(parts.into_iter().map(str::to_string).collect())
+        } else {
+            let numbers = parts
+                .iter()
+                .map(|v| v.trim().parse::<f64>().map_err(|_| invalid(v)))
+                .collect::<Result<_, _>>()?;
+            Values::Numbers(numbers)
+        }
+    };
+
+    if values.len() != expected {
+        return Err(NetCDFError::InvalidFormat(format!(
+            "NcML variable {} has {} values for {} elements",
+            var.name,
+            values.len(),
+            expected
+        )));
+    }
+    Ok(values)
+}
+
+/// Shape of a variable in strings: character arrays hold one per row
+fn text_shape(var: &Variable) -> &[usize] {
+    match var.data_type.as_str() {
+        "Basic(Char)" => &var.shape[..var.shape.len().saturating_sub(1)],
+        _ => &var.shape,
+    }
+}
+
+/// Hyperslab of values stored in row-major order
+fn subset<T: Clone>(
+    values: &[T],
+    var: &Variable,
+    shape: &[usize],
+    start: &[usize],
+    count: &[usize],
+) -> Result<Vec<T>, NetCDFError> {
+    let rank = shape.len();
+    if start.len() < rank
+        || count.len() < rank
+        || (0..rank).any(|d| start[d] + count[d] > shape[d])
+    {
+        return Err(NetCDFError::InvalidSubsetRequest(format!(
+            "Hyperslab start={:?} count={:?} does not fit variable '{}' of shape {:?}",
+            start, count, var.name, var.shape
+        )));
+    }
+    let count = &count[..rank];
+    if count.contains(&0) {
+        return Ok(Vec::new());
+    }
+
+    let mut strides = vec![1; rank];
+    for d in (0..rank.saturating_sub(1)).rev() {
+        strides[d] = strides[d + 1] * shape[d + 1];
+    }
+    let mut index = vec![0; rank];
+    let mut out = Vec::with_capacity(count.iter().product());
+    loop {
+        let offset: usize = (0..rank).map(|d| (start[d] + index[d]) * strides[d]).sum();
+        out.push(values[offset].clone());
+        if !next_index(&mut index, count) {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+/// Read a hyperslab of document values in the variable's type
+fn read_values(
+    var: &Variable,
+    values: &Values,
+    start: &[usize],
+    count: &[usize],
+) -> Result<NativeValues, NetCDFError> {
+    let Values::Numbers(numbers) = values else {
+        return Err(NetCDFError::VariableReadError(var.name.clone(), "not a numeric variable".to_string()));
+    };
+    let numbers = subset(numbers, var, &var.shape, start, count)?;
+
+    macro_rules! cast {
+        ($variant:ident, $t:ty) => {
+            NativeValues::$variant(numbers.iter().map(|&x| x as $t).collect())
+        };
+    }
+    Ok(match var.data_type.as_str() {
+        "Basic(Byte)" => cast!(I8, i8),
+        "Basic(Ubyte)" => cast!(U8, u8),
+        "Basic(Short)" => cast!(I16, i16),
+        "Basic(Ushort)" => cast!(U16, u16),
+        "Basic(Int)" => cast!(I32, i32),
+        "Basic(Uint)" => cast!(U32, u32),
+        "Basic(Int64)" => cast!(I64, i64),
+        "Basic(Uint64)" => cast!(U64, u64),
+        "Basic(Float)" => cast!(F32, f32),
+        _ => NativeValues::F64(numbers),
+    })
+}
+
+/// Read a hyperslab of document values as strings
+fn read_values_text(
+    var: &Variable,
+    values: &Values,
+    start: &[usize],
+    count: &[usize],
+) -> Result<Vec<String>, NetCDFError> {
+    match values {
+        Values::Text(text) => subset(text, var, text_shape(var), start, count),
+        Values::Numbers(numbers) => Ok(subset(numbers, var, &var.shape, start, count)?
+            .iter()
+            .map(f64::to_string)
+            .collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netcdf::test_util::write_store;
+
+    fn write_ncml(dir: &Path, text: &str) -> String {
+        let path = dir.join("dataset.ncml");
+        std::fs::write(&path, text).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_rename_and_override_attributes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write_store(&dir.join("obs.zarr"), "days since 2000-01-01", &[0.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+        let path = write_ncml(
+            dir,
+            r#"<netcdf xmlns="http://www.unidata.ucar.edu/namespaces/netcdf/ncml-2.2" location="obs.zarr">
+                 <attribute name="title" value="Station observations"/>
+                 <dimension name="station" orgName="x"/>
+                 <variable name="temperature" orgName="tas">
+                   <attribute name="units" value="K"/>
+                   <attribute name="_FillValue" type="float" value="2"/>
+                   <attribute name="valid_range" type="int" value="0 400"/>
+                 </variable>
+                 <variable name="station_id" type="int" shape="station">
+                   <values>101 102</values>
+                 </variable>
+               </netcdf>"#,
+        );
+
+        let dataset = NcmlDataset::open(&path).unwrap();
+        let metadata = dataset.metadata().unwrap();
+        assert_eq!(metadata.global_attrs["title"], "Station observations");
+        assert!(find_variable(&metadata, "tas").is_err());
+        let temperature = find_variable(&metadata, "temperature").unwrap();
+        assert_eq!(temperature.dimensions, vec!["time", "station"]);
+        assert_eq!(temperature.attributes["units"], "K");
+        assert_eq!(temperature.attributes["valid_range"], "[0, 400]");
+
+        let masked = dataset.read_masked("temperature", &[0, 0], &[2, 2]).unwrap();
+        assert!(masked[1].is_nan());
+        assert_eq!(masked[3], 4.0);
+        let NativeValues::I32(ids) = dataset.read_native("station_id", &[1], &[1]).unwrap() else {
+            panic!("station_id should be int");
+        };
+        assert_eq!(ids, vec![102]);
+    }
+
+    #[test]
+    fn test_join_new_and_join_existing() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write_store(&dir.join("runs/a.zarr"), "days since 2000-01-01", &[0.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+        write_store(&dir.join("runs/b.zarr"), "days since 2000-01-01", &[0.0, 1.0], &[5.0, 6.0, 7.0, 8.0]);
+
+        let path = write_ncml(
+            dir,
+            r#"<netcdf xmlns="http://www.unidata.ucar.edu/namespaces/netcdf/ncml-2.2">
+                 <variable name="member"><attribute name="long_name" value="Ensemble member"/></variable>
+                 <aggregation dimName="member" type="joinNew">
+                   <variableAgg name="tas"/>
+                   <netcdf location="runs/a.zarr" coordValue="10"/>
+                   <netcdf location="runs/b.zarr" coordValue="20"/>
+                 </aggregation>
+               </netcdf>"#,
+        );
+        let dataset = NcmlDataset::open(&path).unwrap();
+        let metadata = dataset.metadata().unwrap();
+        assert_eq!(find_variable(&metadata, "tas").unwrap().shape, vec![2, 2, 2]);
+        assert_eq!(find_variable(&metadata, "member").unwrap().attributes["long_name"], "Ensemble member");
+        assert_eq!(dataset.read_f64("member", &[0], &[2]).unwrap(), vec![10.0, 20.0]);
+        assert_eq!(dataset.read_f64("tas", &[0, 1, 0], &[2, 1, 2]).unwrap(), vec![3.0, 4.0, 7.0, 8.0]);
+        assert_eq!(dataset.read_f64("time", &[0], &[2]).unwrap(), vec![0.0, 1.0]);
+
+        // joinExisting keeps the scan order (by name) along the named dimension
+        write_store(&dir.join("runs/b.zarr"), "days since 2000-01-01", &[2.0], &[5.0, 6.0]);
+        let path = write_ncml(
+            dir,
+            r#"<netcdf xmlns="http://www.unidata.ucar.edu/namespaces/netcdf/ncml-2.2">
+                 <aggregation dimName="time" type="joinExisting">
+                   <scan location="runs" suffix=".zarr"/>
+                 </aggregation>
+               </netcdf>"#,
+        );
+        let dataset = NcmlDataset::open(&path).unwrap();
+        assert_eq!(dataset.read_f64("time", &[0], &[3]).unwrap(), vec![0.0, 1.0, 2.0]);
+        assert_eq!(dataset.read_f64("tas", &[1, 0], &[2, 2]).unwrap(), vec![3.0, 4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn test_scan_leaves_out_its_own_document() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write_store(&dir.join("runs/a.zarr"), "days since 2000-01-01", &[0.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir, dir.join("runs/loop")).unwrap();
+
+        // With no suffix, a scan of the document's own directory matches the document too
+        let path = write_ncml(
+            dir,
+            r#"<netcdf xmlns="http://www.unidata.ucar.edu/namespaces/netcdf/ncml-2.2">
+                 <aggregation dimName="time" type="joinExisting">
+                   <scan location="."/>
+                 </aggregation>
+               </netcdf>"#,
+        );
+        let dataset = NcmlDataset::open(&path).unwrap();
+        assert_eq!(dataset.read_f64("time", &[0], &[2]).unwrap(), vec![0.0, 1.0]);
+
+        let path = write_ncml(
+            dir,
+            r#"<netcdf xmlns="http://www.unidata.ucar.edu/namespaces/netcdf/ncml-2.2" location="dataset.ncml"/>"#,
+        );
+        let error = NcmlDataset::open(&path).err().unwrap();
+        assert!(error.to_string().contains("refers back to itself"), "{}", error);
+    }
+
+    #[test]
+    fn test_confined_locations() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let served = root.join("served");
+        write_store(&served.join("a.zarr"), "days since 2000-01-01", &[0.0], &[1.0, 2.0]);
+        std::fs::write(root.join("secret.nc"), "").unwrap();
+
+        let locations = Locations { base: &served, allowed: Some(&served), documents: &[] };
+        assert_eq!(locations.resolve("a.zarr").unwrap(), served.join("a.zarr").to_string_lossy());
+        for location in [
+            "../secret.nc",
+            "/etc/passwd",
+            "file:/etc/passwd",
+            "http://169.254.169.254/latest/meta-data",
+            "dods://host/dodsC/a.nc",
+        ] {
+            assert!(locations.resolve(location).is_err(), "{}", location);
+        }
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        let base = Path::new("/catalog");
+        assert_eq!(resolve_location("data/a.nc", base), "/catalog/data/a.nc");
+        assert_eq!(resolve_location("file:/data/a.nc", base), "/data/a.nc");
+        assert_eq!(resolve_location("dods://host/dodsC/a.nc", base), "http://host/dodsC/a.nc");
+        assert!(is_ncml("/catalog/model.ncml"));
+        assert!(!is_ncml("/catalog/model.nc"));
+    }
+}
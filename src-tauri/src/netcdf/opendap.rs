@@ -0,0 +1,1228 @@
+use crate::errors::NetCDFError;
+use crate::models::{Dimension, FileMetadata, Variable};
+use super::backend::{numeric_type_name, DatasetBackend, NumberKind};
+use super::data_access::NativeValues;
+use super::loader::attribute_value_to_string;
+use super::object_store::is_object_url;
+use netcdf::AttributeValue;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Timeout for a single request to an OPeNDAP server
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Largest response accepted from a server
+const MAX_RESPONSE_BYTES: u64 = 4 << 30;
+
+/// Marker between the DDS and the XDR data of a DAP2 `.dods` response
+const DAP2_DATA_MARKER: &[u8] = b"\nData:\n";
+
+/// DAP4 chunk flags
+const CHUNK_LAST: u8 = 0x01;
+const CHUNK_ERROR: u8 = 0x02;
+const CHUNK_LITTLE_ENDIAN: u8 = 0x04;
+
+/// Whether a path is an OPeNDAP URL rather than a local file or an
+/// object read with range requests (`#mode=bytes`)
+pub fn is_opendap_url(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    ["http://", "https://", "dap4://", "dap2://"]
+        .iter()
+        .any(|scheme| lower.starts_with(scheme))
+        && !is_object_url(path)
+}
+
+/// DAP protocol version spoken with a server
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocol {
+    Dap2,
+    Dap4,
+}
+
+/// Atomic DAP data types (DAP2 names are mapped onto their DAP4 equivalents)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DapType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+    String,
+}
+
+impl DapType {
+    /// Parse a DAP2 or DAP4 type name. DAP2 `Byte` is unsigned.
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "Int8" => DapType::Int8,
+            "Byte" | "UInt8" | "Char" => DapType::UInt8,
+            "Int16" => DapType::Int16,
+            "UInt16" => DapType::UInt16,
+            "Int32" => DapType::Int32,
+            "UInt32" => DapType::UInt32,
+            "Int64" => DapType::Int64,
+            "UInt64" => DapType::UInt64,
+            "Float32" => DapType::Float32,
+            "Float64" => DapType::Float64,
+            "String" | "Url" | "URL" => DapType::String,
+            _ => return None,
+        })
+    }
+
+    /// Data type name for the models
+    fn netcdf_name(&self) -> &'static str {
+        let kind = match self {
+            DapType::Int8 | DapType::Int16 | DapType::Int32 | DapType::Int64 => NumberKind::Signed,
+            DapType::UInt8 | DapType::UInt16 | DapType::UInt32 | DapType::UInt64 => NumberKind::Unsigned,
+            DapType::Float32 | DapType::Float64 => NumberKind::Float,
+            DapType::String => return "String",
+        };
+        numeric_type_name(kind, self.size())
+    }
+
+    /// Size of one value in a DAP4 response (DAP2 XDR widens 8/16-bit
+    /// integers differently, see `decode_xdr`)
+    fn size(&self) -> usize {
+        match self {
+            DapType::Int8 | DapType::UInt8 => 1,
+            DapType::Int16 | DapType::UInt16 => 2,
+            DapType::Int32 | DapType::UInt32 | DapType::Float32 => 4,
+            DapType::Int64 | DapType::UInt64 | DapType::Float64 => 8,
+            DapType::String => 0,
+        }
+    }
+}
+
+/// A variable as declared by the server
+#[derive(Debug, Clone)]
+struct RemoteVariable {
+    /// Name used in constraint expressions
+    path: String,
+    dap_type: DapType,
+    /// Attributes with their DAP types
+    attributes: Vec<(String, AttributeValue)>,
+}
+
+/// A dataset on an OPeNDAP (DAP2 or DAP4) server.
+///
+/// Metadata comes from the DDS/DAS (DAP2) or DMR (DAP4) responses; every
+/// hyperslab read becomes a constraint expression, so only the requested
+/// values cross the wire.
+pub struct OpendapBackend {
+    url: String,
+    protocol: Protocol,
+    agent: ureq::Agent,
+    metadata: FileMetadata,
+    variables: HashMap<String, RemoteVariable>,
+    fill_values: HashMap<String, f64>,
+    typed_global_attrs: Vec<(String, AttributeValue)>,
+}
+
+/// Metadata parsed from the server's description of a dataset
+struct RemoteStructure {
+    dimensions: Vec<Dimension>,
+    variables: Vec<(Variable, RemoteVariable)>,
+    global_attrs: HashMap<String, String>,
+    fill_values: HashMap<String, f64>,
+    /// Global attributes with their DAP types
+    typed_global_attrs: Vec<(String, AttributeValue)>,
+}
+
+impl OpendapBackend {
+    /// Connect to a dataset URL.
+    ///
+    /// `dap4://` URLs, a `#dap4` fragment or a `/dap4/` path select DAP4;
+    /// otherwise DAP2 is tried first and DAP4 second.
+    pub fn open(url: &str) -> Result<Self, NetCDFError> {
+        let (base, forced) = parse_url(url);
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(REQUEST_TIMEOUT))
+            .http_status_as_error(false)
+            .build()
+            .into();
+
+        let attempts: &[Protocol] = match forced {
+            Some(Protocol::Dap4) => &[Protocol::Dap4],
+            Some(Protocol::Dap2) => &[Protocol::Dap2],
+            None => &[Protocol::Dap2, Protocol::Dap4],
+        };
+
+        let mut last_error = None;
+        for &protocol in attempts {
+            let structure = match protocol {
+                Protocol::Dap2 => open_dap2(&agent, &base),
+                Protocol::Dap4 => open_dap4(&agent, &base),
+            };
+            match structure {
+                Ok(structure) => {
+                    let (variables, remote): (Vec<Variable>, HashMap<String, RemoteVariable>) = structure
+                        .variables
+                        .into_iter()
+                        .map(|(var, remote)| {
+                            let name = var.name.clone();
+                            (var, (name, remote))
+                        })
+                        .unzip();
+                    return Ok(OpendapBackend {
+                        metadata: FileMetadata {
+                            file_path: url.to_string(),
+                            dimensions: structure.dimensions,
+                            variables,
+                            global_attrs: structure.global_attrs,
+                            coordinates: None, // Will be populated by coordinate detection
+                        },
+                        url: base,
+                        protocol,
+                        agent,
+                        variables: remote,
+                        fill_values: structure.fill_values,
+                        typed_global_attrs: structure.typed_global_attrs,
+                    });
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(NetCDFError::FileOpenError(format!(
+            "Failed to open {}: {}",
+            url,
+            last_error.map(|e| e.to_string()).unwrap_or_default()
+        )))
+    }
+
+    fn remote(&self, var_name: &str) -> Result<&RemoteVariable, NetCDFError> {
+        self.variables
+            .get(var_name)
+            .ok_or_else(|| NetCDFError::VariableNotFound(var_name.to_string()))
+    }
+
+    /// Fetch a hyperslab and decode it
+    fn read_values(&self, var_name: &str, start: &[usize], count: &[usize]) -> Result<Values, NetCDFError> {
+        let remote = self.remote(var_name)?;
+        let n: usize = count.iter().product();
+        if n == 0 {
+            return Ok(Values::empty(remote.dap_type));
+        }
+
+        let hyperslab: String = start
+            .iter()
+            .zip(count)
+            .map(|(&s, &c)| format!("[{}:1:{}]", s, s + c - 1))
+            .collect();
+        let read_error = |message: String| NetCDFError::VariableReadError(var_name.to_string(), message);
+
+        match self.protocol {
+            Protocol::Dap2 => {
+                let ce = encode_constraint(&format!("{}{}", remote.path, hyperslab));
+                let body = fetch(&self.agent, &format!("{}.dods?{}", self.url, ce)).map_err(read_error)?;
+                let data = dap2_data(&body).map_err(read_error)?;
+                decode_xdr(data, remote.dap_type, n, !count.is_empty()).map_err(read_error)
+            }
+            Protocol::Dap4 => {
+                let ce = encode_constraint(&format!("{}{}", remote.path, hyperslab));
+                let url = format!("{}.dap?dap4.ce={}&dap4.checksum=false", self.url, ce);
+                let body = fetch(&self.agent, &url).map_err(read_error)?;
+                let (data, little_endian) = dap4_data(&body).map_err(read_error)?;
+                decode_dap4(&data, remote.dap_type, n, little_endian).map_err(read_error)
+            }
+        }
+    }
+}
+
+impl DatasetBackend for OpendapBackend {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        match self.read_values(var_name, start, count)? {
+            Values::Numeric(values) => Ok(values),
+            Values::Text(_) => Err(NetCDFError::ConversionError(format!(
+                "Variable '{}' holds strings",
+                var_name
+            ))),
+        }
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        match self.read_values(var_name, start, count)? {
+            Values::Text(values) => Ok(values),
+            Values::Numeric(_) => Err(NetCDFError::ConversionError(format!(
+                "Variable '{}' is not a string variable",
+                var_name
+            ))),
+        }
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        self.fill_values.get(var_name).copied()
+    }
+
+    fn attributes(&self, var_name: Option<&str>) -> Result<Vec<(String, AttributeValue)>, NetCDFError> {
+        Ok(match var_name {
+            Some(name) => self.remote(name)?.attributes.clone(),
+            None => self.typed_global_attrs.clone(),
+        })
+    }
+}
+
+/// Decoded values of a response
+enum Values {
+    Numeric(NativeValues),
+    Text(Vec<String>),
+}
+
+impl Values {
+    fn empty(dap_type: DapType) -> Self {
+        match dap_type {
+            DapType::String => Values::Text(Vec::new()),
+            _ => Values::Numeric(NativeValues::F64(Vec::new())),
+        }
+    }
+}
+
+/// Split a user URL into the dataset URL and a forced protocol
+fn parse_url(url: &str) -> (String, Option<Protocol>) {
+    let (mut base, fragment) = match url.split_once('#') {
+        Some((base, fragment)) => (base.to_string(), Some(fragment.to_lowercase())),
+        None => (url.to_string(), None),
+    };
+    let mut protocol = match fragment.as_deref() {
+        Some(f) if f.contains("dap4") => Some(Protocol::Dap4),
+        Some(f) if f.contains("dap2") || f.contains("dods") => Some(Protocol::Dap2),
+        _ => None,
+    };
+
+    for (scheme, forced) in [("dap4://", Protocol::Dap4), ("dap2://", Protocol::Dap2)] {
+        if base.to_lowercase().starts_with(scheme) {
+            base = format!("http://{}", &base[scheme.len()..]);
+            protocol = Some(forced);
+        }
+    }
+    if let Some(query) = base.find('?') {
+        base.truncate(query);
+    }
+    if protocol.is_none() && base.contains("/dap4/") {
+        protocol = Some(Protocol::Dap4);
+    }
+    // Users often paste the URL of one of the responses
+    for suffix in [".html", ".dds", ".das", ".dods", ".dmr.xml", ".dmr", ".dap"] {
+        if let Some(stripped) = base.strip_suffix(suffix) {
+            base = stripped.to_string();
+            break;
+        }
+    }
+    (base, protocol)
+}
+
+/// GET a URL, returning the body or a message for non-success responses
+fn fetch(agent: &ureq::Agent, url: &str) -> Result<Vec<u8>, String> {
+    let mut response = agent.get(url).call().map_err(|e| e.to_string())?;
+    let status = response.status();
+    let body = response
+        .body_mut()
+        .with_config()
+        .limit(MAX_RESPONSE_BYTES)
+        .read_to_vec()
+        .map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        let text = String::from_utf8_lossy(&body);
+        let detail = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+        return Err(format!("HTTP {} for {} {}", status.as_u16(), url, detail));
+    }
+    Ok(body)
+}
+
+/// Percent-encode a constraint expression for use in a query string
+fn encode_constraint(ce: &str) -> String {
+    let mut encoded = String::with_capacity(ce.len());
+    for byte in ce.bytes() {
+        if byte.is_ascii_alphanumeric() || b"_-.~:/,".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Build a typed attribute value from DAP attribute literals
+fn attribute_value(dap_type: DapType, values: &[String]) -> Option<AttributeValue> {
+    macro_rules! typed {
+        ($t:ty, $one:ident, $many:ident) => {{
+            let parsed: Vec<$t> = values.iter().map(|v| v.trim().parse::<$t>().ok()).collect::<Option<_>>()?;
+            if parsed.len() == 1 {
+                AttributeValue::$one(parsed[0])
+            } else {
+                AttributeValue::$many(parsed)
+            }
+        }};
+    }
+    Some(match dap_type {
+        DapType::Int8 => typed!(i8, Schar, Schars),
+        DapType::UInt8 => typed!(u8, Uchar, Uchars),
+        DapType::Int16 => typed!(i16, Short, Shorts),
+        DapType::UInt16 => typed!(u16, Ushort, Ushorts),
+        DapType::Int32 => typed!(i32, Int, Ints),
+        DapType::UInt32 => typed!(u32, Uint, Uints),
+        DapType::Int64 => typed!(i64, Longlong, Longlongs),
+        DapType::UInt64 => typed!(u64, Ulonglong, Ulonglongs),
+        DapType::Float32 => typed!(f32, Float, Floats),
+        DapType::Float64 => typed!(f64, Double, Doubles),
+        DapType::String if values.len() == 1 => AttributeValue::Str(values[0].clone()),
+        DapType::String => AttributeValue::Strs(values.to_vec()),
+    })
+}
+
+/// Record an attribute in text and typed form, keeping numeric fill values
+fn add_attribute(
+    attrs: &mut HashMap<String, String>,
+    typed: &mut Vec<(String, AttributeValue)>,
+    fill: &mut Option<f64>,
+    name: &str,
+    dap_type: DapType,
+    values: &[String],
+) {
+    let Some(value) = attribute_value(dap_type, values) else {
+        return;
+    };
+    if name == "_FillValue" && dap_type != DapType::String {
+        *fill = values.first().and_then(|v| v.trim().parse().ok());
+    }
+    attrs.insert(name.to_string(), attribute_value_to_string(value.clone()));
+    typed.push((name.to_string(), value));
+}
+
+/// Add the dimensions of a variable, naming anonymous ones after it
+fn register_dimensions(
+    dimensions: &mut Vec<Dimension>,
+    var_name: &str,
+    dims: &[(Option<String>, usize)],
+) -> Vec<String> {
+    dims.iter()
+        .enumerate()
+        .map(|(i, (name, size))| {
+            let name = name.clone().unwrap_or_else(|| format!("{}_dim{}", var_name, i));
+            if !dimensions.iter().any(|d| d.name == name) {
+                dimensions.push(Dimension {
+                    name: name.clone(),
+                    size: *size,
+                    is_unlimited: false,
+                });
+            }
+            name
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------- DAP2
+
+/// A variable declared in a DDS
+#[derive(Debug, Clone, PartialEq)]
+struct DdsVariable {
+    name: String,
+    dap_type: DapType,
+    dims: Vec<(Option<String>, usize)>,
+}
+
+/// A DAS attribute container
+#[derive(Debug, Clone, Default, PartialEq)]
+struct DasContainer {
+    name: String,
+    attributes: Vec<(String, DapType, Vec<String>)>,
+    containers: Vec<DasContainer>,
+}
+
+fn open_dap2(agent: &ureq::Agent, url: &str) -> Result<RemoteStructure, NetCDFError> {
+    let format_error = |e: String| NetCDFError::InvalidFormat(e);
+    let dds = fetch(agent, &format!("{}.dds", url)).map_err(format_error)?;
+    let das = fetch(agent, &format!("{}.das", url)).map_err(format_error)?;
+    let variables = parse_dds(&String::from_utf8_lossy(&dds))?;
+    let das = parse_das(&String::from_utf8_lossy(&das))?;
+    Ok(dap2_structure(variables, &das))
+}
+
+/// Combine DDS variables with DAS attributes
+fn dap2_structure(dds: Vec<DdsVariable>, das: &[DasContainer]) -> RemoteStructure {
+    let mut dimensions = Vec::new();
+    let mut variables = Vec::new();
+    let mut global_attrs = HashMap::new();
+    let mut typed_global_attrs = Vec::new();
+    let mut fill_values = HashMap::new();
+    let mut unlimited = None;
+
+    let containers: HashMap<&str, &DasContainer> = das.iter().map(|c| (c.name.as_str(), c)).collect();
+
+    for var in dds {
+        let dim_names = register_dimensions(&mut dimensions, &var.name, &var.dims);
+        let mut attributes = HashMap::new();
+        let mut typed = Vec::new();
+        let mut fill = None;
+        if let Some(container) = containers.get(var.name.as_str()) {
+            for (name, dap_type, values) in &container.attributes {
+                add_attribute(&mut attributes, &mut typed, &mut fill, name, *dap_type, values);
+            }
+        }
+        if let Some(fv) = fill {
+            fill_values.insert(var.name.clone(), fv);
+        }
+        variables.push((
+            Variable {
+                name: var.name.clone(),
+                data_type: var.dap_type.netcdf_name().to_string(),
+                dimensions: dim_names,
+                shape: var.dims.iter().map(|(_, n)| *n).collect(),
+                attributes,
+            },
+            RemoteVariable {
+                path: var.name.clone(),
+                dap_type: var.dap_type,
+                attributes: typed,
+            },
+        ));
+    }
+
+    for container in das {
+        if variables.iter().any(|(v, _)| v.name == container.name) {
+            continue;
+        }
+        if container.name == "DODS_EXTRA" {
+            unlimited = container
+                .attributes
+                .iter()
+                .find(|(name, _, _)| name == "Unlimited_Dimension")
+                .and_then(|(_, _, values)| values.first().cloned());
+            continue;
+        }
+        // NC_GLOBAL, HDF_GLOBAL, ... hold the global attributes; other
+        // containers are kept with their name as a prefix
+        let global = container.name.to_uppercase().ends_with("GLOBAL");
+        for (name, dap_type, values) in &container.attributes {
+            let key = if global { name.clone() } else { format!("{}.{}", container.name, name) };
+            let mut ignored = None;
+            add_attribute(&mut global_attrs, &mut typed_global_attrs, &mut ignored, &key, *dap_type, values);
+        }
+    }
+
+    if let Some(name) = unlimited {
+        for dim in dimensions.iter_mut().filter(|d| d.name == name) {
+            dim.is_unlimited = true;
+        }
+    }
+
+    RemoteStructure {
+        dimensions,
+        variables,
+        global_attrs,
+        fill_values,
+        typed_global_attrs,
+    }
+}
+
+/// Tokens of DDS and DAS text
+#[derive(Debug, Clone, PartialEq)]
+enum DapToken {
+    Word(String),
+    Quoted(String),
+    Punct(char),
+}
+
+fn tokenize_dap(text: &str) -> Vec<DapToken> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '{' | '}' | '[' | ']' | ';' | '=' | ',' | ':' => {
+                tokens.push(DapToken::Punct(c));
+                i += 1;
+            }
+            '"' => {
+                let mut s = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        i += 1;
+                    }
+                    s.push(chars[i]);
+                    i += 1;
+                }
+                i += 1;
+                tokens.push(DapToken::Quoted(s));
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !"{}[];=,:\"".contains(chars[i]) {
+                    i += 1;
+                }
+                tokens.push(DapToken::Word(chars[start..i].iter().collect()));
+            }
+        }
+    }
+    tokens
+}
+
+/// Cursor over DAP tokens
+struct TokenCursor {
+    tokens: Vec<DapToken>,
+    pos: usize,
+    what: &'static str,
+}
+
+impl TokenCursor {
+    fn peek(&self) -> Option<&DapToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<DapToken, NetCDFError> {
+        let token = self.peek().cloned().ok_or_else(|| self.error("unexpected end"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn error(&self, message: &str) -> NetCDFError {
+        NetCDFError::InvalidFormat(format!("{} (token {}): {}", self.what, self.pos, message))
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), NetCDFError> {
+        match self.next()? {
+            DapToken::Punct(c) if c == punct => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", punct))),
+        }
+    }
+
+    fn word(&mut self) -> Result<String, NetCDFError> {
+        match self.next()? {
+            DapToken::Word(w) | DapToken::Quoted(w) => Ok(w),
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    fn at(&self, punct: char) -> bool {
+        self.peek() == Some(&DapToken::Punct(punct))
+    }
+}
+
+/// Parse a DDS into its atomic variables. Grid arrays and maps become
+/// plain variables, Structure members are named `structure.member`, and
+/// Sequences (tabular data) are skipped.
+fn parse_dds(text: &str) -> Result<Vec<DdsVariable>, NetCDFError> {
+    let mut cursor = TokenCursor {
+        tokens: tokenize_dap(text),
+        pos: 0,
+        what: "DDS",
+    };
+    if !cursor.word()?.eq_ignore_ascii_case("dataset") {
+        return Err(cursor.error("expected 'Dataset'"));
+    }
+    cursor.expect('{')?;
+    let mut variables = Vec::new();
+    dds_declarations(&mut cursor, "", &mut variables)?;
+    cursor.expect('}')?;
+    Ok(variables)
+}
+
+/// Declarations up to the closing '}' of the enclosing constructor
+fn dds_declarations(cursor: &mut TokenCursor, prefix: &str, out: &mut Vec<DdsVariable>) -> Result<(), NetCDFError> {
+    while !cursor.at('}') {
+        let keyword = cursor.word()?;
+        match keyword.to_lowercase().as_str() {
+            "grid" => {
+                cursor.expect('{')?;
+                let mut members = Vec::new();
+                while !cursor.at('}') {
+                    // "ARRAY:" and "MAPS:" section labels
+                    if let Some(DapToken::Word(w)) = cursor.peek() {
+                        if matches!(w.to_lowercase().as_str(), "array" | "maps")
+                            && cursor.tokens.get(cursor.pos + 1) == Some(&DapToken::Punct(':'))
+                        {
+                            cursor.pos += 2;
+                            continue;
+                        }
+                    }
+                    let type_name = cursor.word()?;
+                    members.push(dds_array(cursor, &type_name, prefix)?);
+                }
+                cursor.expect('}')?;
+                let name = cursor.word()?;
+                cursor.expect(';')?;
+
+                // The grid's array takes the grid's name; maps are usually
+                // also declared at the top level
+                let mut members = members.into_iter();
+                if let Some(mut array) = members.next() {
+                    array.name = format!("{}{}", prefix, name);
+                    out.push(array);
+                }
+                for map in members {
+                    if !out.iter().any(|v| v.name == map.name) {
+                        out.push(map);
+                    }
+                }
+            }
+            "structure" => {
+                cursor.expect('{')?;
+                let mut members = Vec::new();
+                // Member names are resolved once the structure's name is known
+                dds_declarations(cursor, "", &mut members)?;
+                cursor.expect('}')?;
+                let name = cursor.word()?;
+                cursor.expect(';')?;
+                for mut member in members {
+                    member.name = format!("{}{}.{}", prefix, name, member.name);
+                    out.push(member);
+                }
+            }
+            "sequence" => {
+                cursor.expect('{')?;
+                let mut ignored = Vec::new();
+                dds_declarations(cursor, "", &mut ignored)?;
+                cursor.expect('}')?;
+                cursor.word()?;
+                cursor.expect(';')?;
+            }
+            _ => {
+                let var = dds_array(cursor, &keyword, prefix)?;
+                if !out.iter().any(|v| v.name == var.name) {
+                    out.push(var);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `Type name[dim = n][m];`
+fn dds_array(cursor: &mut TokenCursor, type_name: &str, prefix: &str) -> Result<DdsVariable, NetCDFError> {
+    let dap_type = DapType::from_name(type_name)
+        .ok_or_else(|| cursor.error(&format!("unsupported type '{}'", type_name)))?;
+    let name = cursor.word()?;
+    let mut dims = Vec::new();
+    while cursor.at('[') {
+        cursor.pos += 1;
+        let first = cursor.word()?;
+        let dim = if cursor.at('=') {
+            cursor.pos += 1;
+            let size = cursor.word()?;
+            (Some(first), size.parse().map_err(|_| cursor.error("invalid dimension size"))?)
+        } else {
+            (None, first.parse().map_err(|_| cursor.error("invalid dimension size"))?)
+        };
+        dims.push(dim);
+        cursor.expect(']')?;
+    }
+    cursor.expect(';')?;
+    Ok(DdsVariable {
+        name: format!("{}{}", prefix, name),
+        dap_type,
+        dims,
+    })
+}
+
+/// Parse a DAS into its top-level attribute containers
+fn parse_das(text: &str) -> Result<Vec<DasContainer>, NetCDFError> {
+    let mut cursor = TokenCursor {
+        tokens: tokenize_dap(text),
+        pos: 0,
+        what: "DAS",
+    };
+    if !cursor.word()?.eq_ignore_ascii_case("attributes") {
+        return Err(cursor.error("expected 'Attributes'"));
+    }
+    cursor.expect('{')?;
+    let mut root = DasContainer::default();
+    das_body(&mut cursor, &mut root)?;
+    cursor.expect('}')?;
+    Ok(root.containers)
+}
+
+fn das_body(cursor: &mut TokenCursor, container: &mut DasContainer) -> Result<(), NetCDFError> {
+    while !cursor.at('}') {
+        let first = cursor.word()?;
+        if cursor.at('{') {
+            cursor.pos += 1;
+            let mut child = DasContainer {
+                name: first,
+                ..DasContainer::default()
+            };
+            das_body(cursor, &mut child)?;
+            cursor.expect('}')?;
+            container.containers.push(child);
+            continue;
+        }
+
+        let name = cursor.word()?;
+        let mut values = Vec::new();
+        loop {
+            match cursor.next()? {
+                DapToken::Word(w) | DapToken::Quoted(w) => values.push(w),
+                DapToken::Punct(',') => {}
+                DapToken::Punct(';') => break,
+                _ => return Err(cursor.error("unexpected token in attribute")),
+            }
+        }
+        // Aliases and unknown types are skipped
+        if let Some(dap_type) = DapType::from_name(&first) {
+            container.attributes.push((name, dap_type, values));
+        }
+    }
+    Ok(())
+}
+
+/// The XDR part of a `.dods` response
+fn dap2_data(body: &[u8]) -> Result<&[u8], String> {
+    body.windows(DAP2_DATA_MARKER.len())
+        .position(|w| w == DAP2_DATA_MARKER)
+        .map(|p| &body[p + DAP2_DATA_MARKER.len()..])
+        .ok_or_else(|| {
+            let text = String::from_utf8_lossy(&body[..body.len().min(512)]).into_owned();
+            format!("unexpected response: {}", text.trim())
+        })
+}
+
+/// Decode the first variable of XDR-encoded DAP2 data. Arrays carry a
+/// length prefix (twice for numeric arrays); 8- and 16-bit integers are
+/// widened to 32 bits except for Byte arrays, which are packed.
+fn decode_xdr(data: &[u8], dap_type: DapType, n: usize, is_array: bool) -> Result<Values, String> {
+    let mut reader = ByteReader { data, pos: 0, little_endian: false };
+
+    if dap_type == DapType::String {
+        if is_array {
+            reader.u32()?;
+        }
+        let mut strings = Vec::with_capacity(n);
+        for _ in 0..n {
+            let len = reader.u32()? as usize;
+            let bytes = reader.take(len)?;
+            strings.push(String::from_utf8_lossy(bytes).into_owned());
+            reader.take((4 - len % 4) % 4)?;
+        }
+        return Ok(Values::Text(strings));
+    }
+
+    if is_array {
+        reader.u32()?;
+        reader.u32()?;
+    }
+    let values = match dap_type {
+        DapType::UInt8 | DapType::Int8 if is_array => {
+            let bytes = reader.take(n)?;
+            if dap_type == DapType::UInt8 {
+                NativeValues::U8(bytes.to_vec())
+            } else {
+                NativeValues::I8(bytes.iter().map(|&b| b as i8).collect())
+            }
+        }
+        DapType::UInt8 => NativeValues::U8(vec![reader.u32()? as u8]),
+        DapType::Int8 => NativeValues::I8(vec![reader.u32()? as i8]),
+        DapType::Int16 => NativeValues::I16((0..n).map(|_| reader.u32().map(|v| v as i32 as i16)).collect::<Result<_, _>>()?),
+        DapType::UInt16 => NativeValues::U16((0..n).map(|_| reader.u32().map(|v| v as u16)).collect::<Result<_, _>>()?),
+        DapType::Int32 => NativeValues::I32((0..n).map(|_| reader.u32().map(|v| v as i32)).collect::<Result<_, _>>()?),
+        DapType::UInt32 => NativeValues::U32((0..n).map(|_| reader.u32()).collect::<Result<_, _>>()?),
+        DapType::Int64 => NativeValues::I64((0..n).map(|_| reader.u64().map(|v| v as i64)).collect::<Result<_, _>>()?),
+        DapType::UInt64 => NativeValues::U64((0..n).map(|_| reader.u64()).collect::<Result<_, _>>()?),
+        DapType::Float32 => NativeValues::F32((0..n).map(|_| reader.u32().map(f32::from_bits)).collect::<Result<_, _>>()?),
+        DapType::Float64 => NativeValues::F64((0..n).map(|_| reader.u64().map(f64::from_bits)).collect::<Result<_, _>>()?),
+        DapType::String => unreachable!("strings are decoded above"),
+    };
+    Ok(Values::Numeric(values))
+}
+
+/// Sequential reader over response bytes
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    little_endian: bool,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos + n;
+        let bytes = self.data.get(self.pos..end).ok_or("response ended early")?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut bytes: [u8; N] = self.take(N)?.try_into().expect("take returns N bytes");
+        if self.little_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+}
+
+// ---------------------------------------------------------------- DAP4
+
+fn open_dap4(agent: &ureq::Agent, url: &str) -> Result<RemoteStructure, NetCDFError> {
+    let dmr = fetch(agent, &format!("{}.dmr", url)).map_err(NetCDFError::InvalidFormat)?;
+    parse_dmr(&String::from_utf8_lossy(&dmr))
+}
+
+/// Parse a DMR document. Variables in groups are named by their path
+/// without the leading '/' (e.g. `forecast/t2m`); Structures, Sequences
+/// and other constructor types are skipped.
+fn parse_dmr(text: &str) -> Result<RemoteStructure, NetCDFError> {
+    let doc = roxmltree::Document::parse(text)
+        .map_err(|e| NetCDFError::InvalidFormat(format!("DMR: {}", e)))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "Dataset" {
+        return Err(NetCDFError::InvalidFormat("DMR: expected a Dataset element".to_string()));
+    }
+
+    let mut structure = RemoteStructure {
+        dimensions: Vec::new(),
+        variables: Vec::new(),
+        global_attrs: HashMap::new(),
+        fill_values: HashMap::new(),
+        typed_global_attrs: Vec::new(),
+    };
+    dmr_group(root, "", &mut structure);
+    Ok(structure)
+}
+
+/// Dimensions, variables, attributes and subgroups of a DMR group;
+/// `path` is the group path with a trailing '/' (empty for the root)
+fn dmr_group(group: roxmltree::Node, path: &str, out: &mut RemoteStructure) {
+    for node in group.children().filter(|n| n.is_element()) {
+        let name = node.attribute("name").unwrap_or_default();
+        match node.tag_name().name() {
+            "Dimension" => {
+                out.dimensions.push(Dimension {
+                    name: format!("{}{}", path, name),
+                    size: node.attribute("size").and_then(|s| s.parse().ok()).unwrap_or(0),
+                    is_unlimited: false,
+                });
+            }
+            "Group" => dmr_group(node, &format!("{}{}/", path, name), out),
+            "Attribute" => {
+                let mut ignored = None;
+                let key = format!("{}{}", path, name);
+                if let Some((dap_type, values)) = dmr_attribute(node) {
+                    add_attribute(&mut out.global_attrs, &mut out.typed_global_attrs, &mut ignored, &key, dap_type, &values);
+                }
+            }
+            tag => {
+                if let Some(dap_type) = DapType::from_name(tag) {
+                    dmr_variable(node, dap_type, path, out);
+                }
+            }
+        }
+    }
+}
+
+fn dmr_variable(node: roxmltree::Node, dap_type: DapType, path: &str, out: &mut RemoteStructure) {
+    let full_name = format!("{}{}", path, node.attribute("name").unwrap_or_default());
+
+    let mut dims = Vec::new();
+    for dim in node.children().filter(|n| n.has_tag_name("Dim")) {
+        match (dim.attribute("name"), dim.attribute("size")) {
+            (Some(dim_name), _) => {
+                let dim_name = dim_name.trim_start_matches('/').to_string();
+                let size = out
+                    .dimensions
+                    .iter()
+                    .find(|d| d.name == dim_name)
+                    .map_or(0, |d| d.size);
+                dims.push((Some(dim_name), size));
+            }
+            (None, Some(size)) => dims.push((None, size.parse().unwrap_or(0))),
+            (None, None) => {}
+        }
+    }
+    let dim_names = register_dimensions(&mut out.dimensions, &full_name, &dims);
+
+    let mut attributes = HashMap::new();
+    let mut typed = Vec::new();
+    let mut fill = None;
+    for attr in node.children().filter(|n| n.has_tag_name("Attribute")) {
+        if let (Some(name), Some((attr_type, values))) = (attr.attribute("name"), dmr_attribute(attr)) {
+            add_attribute(&mut attributes, &mut typed, &mut fill, name, attr_type, &values);
+        }
+    }
+    if let Some(fv) = fill {
+        out.fill_values.insert(full_name.clone(), fv);
+    }
+
+    out.variables.push((
+        Variable {
+            name: full_name.clone(),
+            data_type: dap_type.netcdf_name().to_string(),
+            dimensions: dim_names,
+            shape: dims.iter().map(|(_, n)| *n).collect(),
+            attributes,
+        },
+        RemoteVariable {
+            path: format!("/{}", full_name),
+            dap_type,
+            attributes: typed,
+        },
+    ));
+}
+
+/// Type and values of a DMR attribute (containers and unknown types are skipped)
+fn dmr_attribute(node: roxmltree::Node) -> Option<(DapType, Vec<String>)> {
+    let dap_type = DapType::from_name(node.attribute("type")?)?;
+    let mut values: Vec<String> = node
+        .children()
+        .filter(|n| n.has_tag_name("Value"))
+        .map(|v| v.attribute("value").or(v.text()).unwrap_or_default().to_string())
+        .collect();
+    if let Some(value) = node.attribute("value") {
+        values.push(value.to_string());
+    }
+    (!values.is_empty()).then_some((dap_type, values))
+}
+
+/// Split a chunked `.dap` response into its data bytes and byte order.
+/// The first chunk holds the DMR and is skipped.
+fn dap4_data(body: &[u8]) -> Result<(Vec<u8>, bool), String> {
+    let mut pos = 0;
+    let mut chunks = Vec::new();
+    let mut little_endian = false;
+    while pos + 4 <= body.len() {
+        let flags = body[pos];
+        let len = u32::from_be_bytes([0, body[pos + 1], body[pos + 2], body[pos + 3]]) as usize;
+        let payload = body.get(pos + 4..pos + 4 + len).ok_or("truncated DAP4 chunk")?;
+        pos += 4 + len;
+
+        if flags & CHUNK_ERROR != 0 {
+            return Err(String::from_utf8_lossy(payload).trim().to_string());
+        }
+        little_endian = flags & CHUNK_LITTLE_ENDIAN != 0;
+        chunks.push(payload);
+        if flags & CHUNK_LAST != 0 {
+            break;
+        }
+    }
+    if chunks.is_empty() {
+        return Err("empty DAP4 response".to_string());
+    }
+    Ok((chunks[1..].concat(), little_endian))
+}
+
+/// Decode one DAP4 variable: fixed-size values, or strings as a 64-bit
+/// length followed by the bytes. A trailing checksum is ignored.
+fn decode_dap4(data: &[u8], dap_type: DapType, n: usize, little_endian: bool) -> Result<Values, String> {
+    let mut reader = ByteReader { data, pos: 0, little_endian };
+    let values = match dap_type {
+        DapType::String => {
+            let mut strings = Vec::with_capacity(n);
+            for _ in 0..n {
+                let len = reader.u64()? as usize;
+                strings.push(String::from_utf8_lossy(reader.take(len)?).into_owned());
+            }
+            return Ok(Values::Text(strings));
+        }
+        DapType::Int8 => NativeValues::I8(reader.take(n)?.iter().map(|&b| b as i8).collect()),
+        DapType::UInt8 => NativeValues::U8(reader.take(n)?.to_vec()),
+        DapType::Int16 => NativeValues::I16((0..n).map(|_| reader.u16().map(|v| v as i16)).collect::<Result<_, _>>()?),
+        DapType::UInt16 => NativeValues::U16((0..n).map(|_| reader.u16()).collect::<Result<_, _>>()?),
+        DapType::Int32 => NativeValues::I32((0..n).map(|_| reader.u32().map(|v| v as i32)).collect::<Result<_, _>>()?),
+        DapType::UInt32 => NativeValues::U32((0..n).map(|_| reader.u32()).collect::<Result<_, _>>()?),
+        DapType::Int64 => NativeValues::I64((0..n).map(|_| reader.u64().map(|v| v as i64)).collect::<Result<_, _>>()?),
+        DapType::UInt64 => NativeValues::U64((0..n).map(|_| reader.u64()).collect::<Result<_, _>>()?),
+        DapType::Float32 => NativeValues::F32((0..n).map(|_| reader.u32().map(f32::from_bits)).collect::<Result<_, _>>()?),
+        DapType::Float64 => NativeValues::F64((0..n).map(|_| reader.u64().map(f64::from_bits)).collect::<Result<_, _>>()?),
+    };
+    debug_assert!(reader.pos == n * dap_type.size());
+    Ok(Values::Numeric(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const DDS: &str = "Dataset {
+    Float64 time[time = 2];
+    Float32 lat[lat = 3];
+    Grid {
+      ARRAY:
+        Int16 sst[time = 2][lat = 3];
+      MAPS:
+        Float64 time[time = 2];
+        Float32 lat[lat = 3];
+    } sst;
+    String station[station = 2];
+    Sequence { Int32 id; } obs;
+} example.nc;";
+
+    const DAS: &str = r#"Attributes {
+    time { String units "days since 2000-01-01"; }
+    sst {
+        Int16 _FillValue -999;
+        Float32 valid_range 0.5, 35;
+        String long_name "sea \"surface\" temperature";
+    }
+    NC_GLOBAL { String title "canned"; }
+    DODS_EXTRA { String Unlimited_Dimension "time"; }
+}"#;
+
+    const DMR: &str = r#"<?xml version="1.0" encoding="ISO-8859-1"?>
+<Dataset xmlns="http://xml.opendap.org/ns/DAP/4.0#" name="example.nc" dapVersion="4.0" dmrVersion="1.0">
+  <Dimension name="time" size="2"/>
+  <Float64 name="time"><Dim name="/time"/>
+    <Attribute name="units" type="String"><Value>days since 2000-01-01</Value></Attribute>
+  </Float64>
+  <Group name="ocean">
+    <Dimension name="depth" size="3"/>
+    <Int32 name="temp"><Dim name="/time"/><Dim name="/ocean/depth"/>
+      <Attribute name="_FillValue" type="Int32"><Value>-1</Value></Attribute>
+    </Int32>
+  </Group>
+  <Attribute name="title" type="String"><Value>canned</Value></Attribute>
+</Dataset>"#;
+
+    /// XDR response for `sst[0:1:1][1:1:2]` (4 Int16 values widened to 32 bits)
+    fn dods_response() -> Vec<u8> {
+        let mut body = b"Dataset {\n    Int16 sst[time = 2][lat = 2];\n} example.nc;\n\nData:\n".to_vec();
+        body.extend(4u32.to_be_bytes());
+        body.extend(4u32.to_be_bytes());
+        for v in [10i32, -999, 12, 13] {
+            body.extend(v.to_be_bytes());
+        }
+        body
+    }
+
+    /// DAP4 response for `/ocean/temp[0:1:0][0:1:2]` in little-endian order
+    fn dap_response() -> Vec<u8> {
+        let dmr = b"<Dataset name=\"example.nc\"/>\r\n";
+        let mut data = Vec::new();
+        for v in [1i32, -1, 3] {
+            data.extend(v.to_le_bytes());
+        }
+        let mut body = Vec::new();
+        body.push(0);
+        body.extend(&(dmr.len() as u32).to_be_bytes()[1..]);
+        body.extend(dmr);
+        body.push(CHUNK_LAST | CHUNK_LITTLE_ENDIAN);
+        body.extend(&(data.len() as u32).to_be_bytes()[1..]);
+        body.extend(data);
+        body
+    }
+
+    /// Serve canned DAP responses on a local port; returns the dataset URL
+    /// and the log of requested URLs
+    fn serve_canned() -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let port = server.server_addr().to_ip().unwrap().port();
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let requests = Arc::clone(&log);
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let url = request.url().to_string();
+                requests.lock().unwrap().push(url.clone());
+                let path = url.split('?').next().unwrap_or_default();
+                let body = match path.rsplit_once('.').map(|(_, ext)| ext) {
+                    Some("dds") => DDS.as_bytes().to_vec(),
+                    Some("das") => DAS.as_bytes().to_vec(),
+                    Some("dods") => dods_response(),
+                    Some("dmr") => DMR.as_bytes().to_vec(),
+                    Some("dap") => dap_response(),
+                    _ => {
+                        let _ = request.respond(tiny_http::Response::empty(404));
+                        continue;
+                    }
+                };
+                let _ = request.respond(tiny_http::Response::from_data(body));
+            }
+        });
+        (format!("http://127.0.0.1:{}/opendap/example.nc", port), log)
+    }
+
+    #[test]
+    fn test_parse_dds_and_das() {
+        let dds = parse_dds(DDS).unwrap();
+        let names: Vec<&str> = dds.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["time", "lat", "sst", "station"]);
+        assert_eq!(dds[2].dap_type, DapType::Int16);
+
+        let das = parse_das(DAS).unwrap();
+        let structure = dap2_structure(dds, &das);
+        assert!(structure.dimensions.iter().find(|d| d.name == "time").unwrap().is_unlimited);
+        let sst = &structure.variables[2].0;
+        assert_eq!(sst.dimensions, ["time", "lat"]);
+        assert_eq!(sst.attributes["valid_range"], "[0.5, 35.0]");
+        assert!(structure.variables[2].1.attributes.contains(&("valid_range".to_string(), AttributeValue::Floats(vec![0.5, 35.0]))));
+        assert_eq!(sst.attributes["long_name"], "sea \"surface\" temperature");
+        assert_eq!(structure.fill_values["sst"], -999.0);
+        assert_eq!(structure.global_attrs["title"], "canned");
+    }
+
+    #[test]
+    fn test_dap2_reads_use_constraints() {
+        let (url, log) = serve_canned();
+        let backend = OpendapBackend::open(&url).unwrap();
+        let metadata = backend.metadata().unwrap();
+        assert_eq!(metadata.variables.len(), 4);
+
+        let data = backend.read_masked("sst", &[0, 1], &[2, 2]).unwrap();
+        assert_eq!(data[0], 10.0);
+        assert!(data[1].is_nan());
+        assert!(log.lock().unwrap().iter().any(|u| u.ends_with(".dods?sst%5B0:1:1%5D%5B1:1:2%5D")));
+    }
+
+    #[test]
+    fn test_dap4_dmr_and_chunked_data() {
+        let (url, _log) = serve_canned();
+        let backend = OpendapBackend::open(&format!("{}#dap4", url)).unwrap();
+        let metadata = backend.metadata().unwrap();
+        let temp = metadata.variables.iter().find(|v| v.name == "ocean/temp").unwrap();
+        assert_eq!(temp.dimensions, ["time", "ocean/depth"]);
+        assert_eq!(temp.shape, [2, 3]);
+        assert_eq!(metadata.global_attrs["title"], "canned");
+
+        let data = backend.read_masked("ocean/temp", &[0, 0], &[1, 3]).unwrap();
+        assert_eq!(data[0], 1.0);
+        assert!(data[1].is_nan());
+        assert_eq!(data[2], 3.0);
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("https://host/thredds/dodsC/x.nc.html"),
+            ("https://host/thredds/dodsC/x.nc".to_string(), None)
+        );
+        assert_eq!(
+            parse_url("dap4://host/x.nc"),
+            ("http://host/x.nc".to_string(), Some(Protocol::Dap4))
+        );
+        assert_eq!(parse_url("https://host/thredds/dap4/x.nc").1, Some(Protocol::Dap4));
+    }
+}
@@ -0,0 +1,466 @@
+//! Polygon features read from GeoJSON files and ESRI shapefiles.
+//!
+//! Coordinates must be longitude/latitude in degrees. Rings are stored
+//! oriented so exteriors run counter-clockwise and holes clockwise, which
+//! lets areas be summed with their signs and points be tested by winding
+//! number whatever the source's own convention.
+
+use crate::errors::NetCDFError;
+use crate::models::{GeoExtent, RegionFeature};
+use serde_json::Value;
+use std::path::Path;
+
+/// A closed ring of (longitude, latitude) vertices, without the repeated first vertex
+pub type Ring = Vec<(f64, f64)>;
+
+/// A named feature made of one or more polygons, possibly with holes
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub name: String,
+    pub rings: Vec<Ring>,
+}
+
+impl Region {
+    /// Bounding box of all rings
+    pub fn extent(&self) -> GeoExtent {
+        bounding_box(self.rings.iter().flatten())
+    }
+
+    /// Whether a point lies inside (by non-zero winding number)
+    pub fn contains(&self, lon: f64, lat: f64) -> bool {
+        self.rings.iter().map(|ring| winding_number(ring, lon, lat)).sum::<i32>() != 0
+    }
+}
+
+/// Bounding box of a set of (longitude, latitude) points
+pub fn bounding_box<'a>(points: impl IntoIterator<Item = &'a (f64, f64)>) -> GeoExtent {
+    let mut extent = GeoExtent {
+        south: f64::INFINITY,
+        west: f64::INFINITY,
+        north: f64::NEG_INFINITY,
+        east: f64::NEG_INFINITY,
+    };
+    for &(lon, lat) in points {
+        extent.south = extent.south.min(lat);
+        extent.north = extent.north.max(lat);
+        extent.west = extent.west.min(lon);
+        extent.east = extent.east.max(lon);
+    }
+    extent
+}
+
+/// Signed area of a ring in square degrees, positive when counter-clockwise
+pub fn signed_area(ring: &[(f64, f64)]) -> f64 {
+    let n = ring.len();
+    (0..n)
+        .map(|k| {
+            let (a, b) = (ring[k], ring[(k + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f64>()
+        / 2.0
+}
+
+/// How many times a ring winds counter-clockwise around a point
+fn winding_number(ring: &[(f64, f64)], x: f64, y: f64) -> i32 {
+    let n = ring.len();
+    let mut winding = 0;
+    for k in 0..n {
+        let (a, b) = (ring[k], ring[(k + 1) % n]);
+        let side = (b.0 - a.0) * (y - a.1) - (x - a.0) * (b.1 - a.1);
+        if a.1 <= y {
+            if b.1 > y && side > 0.0 {
+                winding += 1;
+            }
+        } else if b.1 <= y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+/// Drop the closing vertex and orient a ring as an exterior or a hole
+fn oriented(mut ring: Ring, exterior: bool) -> Option<Ring> {
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    if ring.len() < 3 {
+        return None;
+    }
+    if (signed_area(&ring) > 0.0) != exterior {
+        ring.reverse();
+    }
+    Some(ring)
+}
+
+/// Read every feature of a GeoJSON file or shapefile (`.shp`, with names from the `.dbf`)
+pub fn read_regions(path: &str) -> Result<Vec<Region>, NetCDFError> {
+    let is_shapefile = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("shp"));
+    if is_shapefile {
+        read_shapefile(path)
+    } else {
+        parse_geojson(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// Names and extents of the features in a polygon file
+pub fn list_regions(path: &str) -> Result<Vec<RegionFeature>, NetCDFError> {
+    Ok(read_regions(path)?
+        .iter()
+        .enumerate()
+        .map(|(index, region)| RegionFeature {
+            index,
+            name: region.name.clone(),
+            extent: region.extent(),
+        })
+        .collect())
+}
+
+/// Parse a GeoJSON FeatureCollection, Feature or bare geometry.
+///
+/// Features without a polygonal geometry are skipped. Each feature is named
+/// by its `name` property (in any case), else a property ending in "name",
+/// else its `id`, else its position.
+pub fn parse_geojson(text: &str) -> Result<Vec<Region>, NetCDFError> {
+    let root: Value = serde_json::from_str(text).map_err(|e| NetCDFError::InvalidFormat(format!("GeoJSON: {}", e)))?;
+    let features: Vec<&Value> = match root["type"].as_str() {
+        Some("FeatureCollection") => root["features"]
+            .as_array()
+            .ok_or_else(|| NetCDFError::InvalidFormat("GeoJSON FeatureCollection has no features".to_string()))?
+            .iter()
+            .collect(),
+        Some(_) => vec![&root],
+        None => return Err(NetCDFError::InvalidFormat("GeoJSON object has no type".to_string())),
+    };
+
+    let mut regions = Vec::new();
+    for (index, feature) in features.into_iter().enumerate() {
+        let geometry = match feature["type"].as_str() {
+            Some("Feature") => &feature["geometry"],
+            _ => feature,
+        };
+        let mut rings = Vec::new();
+        collect_rings(geometry, &mut rings)?;
+        if rings.is_empty() {
+            continue;
+        }
+        regions.push(Region { name: feature_name(feature, index), rings });
+    }
+    if regions.is_empty() {
+        return Err(NetCDFError::InvalidFormat("GeoJSON has no polygons".to_string()));
+    }
+    Ok(regions)
+}
+
+fn feature_name(feature: &Value, index: usize) -> String {
+    let text = |value: &Value| match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    };
+    let properties = feature["properties"].as_object();
+    let property = |matches: &dyn Fn(&str) -> bool| {
+        properties?
+            .iter()
+            .find(|(key, value)| matches(&key.to_lowercase()) && text(value).is_some())
+            .and_then(|(_, value)| text(value))
+    };
+    property(&|key| key == "name")
+        .or_else(|| property(&|key| key.ends_with("name")))
+        .or_else(|| text(&feature["id"]))
+        .unwrap_or_else(|| format!("feature {}", index + 1))
+}
+
+fn collect_rings(geometry: &Value, rings: &mut Vec<Ring>) -> Result<(), NetCDFError> {
+    let polygon = |coordinates: &Value, rings: &mut Vec<Ring>| -> Result<(), NetCDFError> {
+        let parts = coordinates.as_array().ok_or_else(|| invalid_coordinates(coordinates))?;
+        for (k, part) in parts.iter().enumerate() {
+            if let Some(ring) = oriented(positions(part)?, k == 0) {
+                rings.push(ring);
+            }
+        }
+        Ok(())
+    };
+    match geometry["type"].as_str() {
+        Some("Polygon") => polygon(&geometry["coordinates"], rings),
+        Some("MultiPolygon") => {
+            let coordinates = &geometry["coordinates"];
+            for part in coordinates.as_array().ok_or_else(|| invalid_coordinates(coordinates))? {
+                polygon(part, rings)?;
+            }
+            Ok(())
+        }
+        Some("GeometryCollection") => {
+            for member in geometry["geometries"].as_array().into_iter().flatten() {
+                collect_rings(member, rings)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn positions(value: &Value) -> Result<Ring, NetCDFError> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid_coordinates(value))?
+        .iter()
+        .map(|position| match (position[0].as_f64(), position[1].as_f64()) {
+            (Some(lon), Some(lat)) => Ok((lon, lat)),
+            _ => Err(invalid_coordinates(position)),
+        })
+        .collect()
+}
+
+fn invalid_coordinates(value: &Value) -> NetCDFError {
+    let text: String = value.to_string().chars().take(60).collect();
+    NetCDFError::InvalidFormat(format!("GeoJSON: invalid coordinates {}", text))
+}
+
+/// Read the polygons of a shapefile, named from the sibling `.dbf` when present
+fn read_shapefile(path: &str) -> Result<Vec<Region>, NetCDFError> {
+    let prj = Path::new(path).with_extension("prj");
+    if let Ok(wkt) = std::fs::read_to_string(&prj) {
+        if wkt.trim_start().to_uppercase().starts_with("PROJCS") {
+            return Err(NetCDFError::InvalidFormat(
+                "Shapefile uses a projected coordinate system; reproject it to longitude/latitude".to_string(),
+            ));
+        }
+    }
+    let shapes = parse_shp(&std::fs::read(path)?)?;
+    let names = match std::fs::read(Path::new(path).with_extension("dbf")) {
+        Ok(bytes) => parse_dbf_names(&bytes)?,
+        Err(_) => Vec::new(),
+    };
+    Ok(shapes
+        .into_iter()
+        .enumerate()
+        .filter(|(_, rings)| !rings.is_empty())
+        .map(|(index, rings)| Region {
+            name: names.get(index).cloned().unwrap_or_else(|| format!("feature {}", index + 1)),
+            rings,
+        })
+        .collect())
+}
+
+/// Rings of each record of a `.shp` file (empty for null shapes).
+///
+/// Shapefile exteriors run clockwise and holes counter-clockwise.
+fn parse_shp(bytes: &[u8]) -> Result<Vec<Vec<Ring>>, NetCDFError> {
+    let invalid = |what: &str| NetCDFError::InvalidFormat(format!("Shapefile: {}", what));
+    let be_i32 = |at: usize| bytes.get(at..at + 4).map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let le_i32 = |at: usize| bytes.get(at..at + 4).map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let le_f64 = |at: usize| {
+        bytes
+            .get(at..at + 8)
+            .map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    };
+
+    if be_i32(0) != Some(9994) {
+        return Err(invalid("not a .shp file"));
+    }
+    let shape_type = le_i32(32).ok_or_else(|| invalid("truncated header"))?;
+    if !matches!(shape_type, 5 | 15 | 25) {
+        return Err(invalid(&format!("shape type {} is not a polygon type", shape_type)));
+    }
+
+    // Counts and offsets come from the file, so they are checked before any
+    // arithmetic: a corrupt record must not overflow or run past the file
+    let count = |value: Option<i32>| {
+        let value = value.ok_or_else(|| invalid("truncated record"))?;
+        u32::try_from(value)
+            .map(|v| v as usize)
+            .map_err(|_| invalid(&format!("negative count {}", value)))
+    };
+
+    let mut shapes = Vec::new();
+    let mut at: usize = 100;
+    while at + 8 <= bytes.len() {
+        let content = at + 8;
+        let end = count(be_i32(at + 4))?
+            .checked_mul(2)
+            .and_then(|length| content.checked_add(length))
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| invalid("record runs past the end of the file"))?;
+        at = end;
+        let record_type = le_i32(content).ok_or_else(|| invalid("truncated record"))?;
+        if record_type == 0 {
+            shapes.push(Vec::new());
+            continue;
+        }
+        let num_parts = count(le_i32(content + 36))?;
+        let num_points = count(le_i32(content + 40))?;
+        let points_at = num_parts.checked_mul(4).and_then(|size| (content + 44).checked_add(size));
+        let points_end = num_points.checked_mul(16).and_then(|size| points_at?.checked_add(size));
+        let (Some(points_at), Some(_)) = (points_at, points_end.filter(|&points_end| points_end <= end)) else {
+            return Err(invalid("record is shorter than its parts and points"));
+        };
+        let parts: Vec<usize> = (0..num_parts)
+            .map(|p| le_i32(content + 44 + 4 * p).and_then(|v| usize::try_from(v).ok()))
+            .collect::<Option<_>>()
+            .ok_or_else(|| invalid("ring indices out of range"))?;
+        let points: Ring = (0..num_points)
+            .map(|k| Some((le_f64(points_at + 16 * k)?, le_f64(points_at + 16 * k + 8)?)))
+            .collect::<Option<_>>()
+            .ok_or_else(|| invalid("truncated record"))?;
+
+        let mut rings = Vec::with_capacity(num_parts);
+        for (p, &first) in parts.iter().enumerate() {
+            let last = parts.get(p + 1).copied().unwrap_or(num_points);
+            let Some(ring) = points.get(first..last) else {
+                return Err(invalid("ring indices out of range"));
+            };
+            let exterior = signed_area(ring) < 0.0;
+            if let Some(ring) = oriented(ring.to_vec(), exterior) {
+                rings.push(ring);
+            }
+        }
+        shapes.push(rings);
+    }
+    Ok(shapes)
+}
+
+/// One name per record of a `.dbf` table, from its first field called NAME
+/// (or ending in NAME), else its first character field
+fn parse_dbf_names(bytes: &[u8]) -> Result<Vec<String>, NetCDFError> {
+    let invalid = || NetCDFError::InvalidFormat("Shapefile: invalid .dbf table".to_string());
+    if bytes.len() < 32 {
+        return Err(invalid());
+    }
+    let records = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    let header_length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    let record_length = u16::from_le_bytes([bytes[10], bytes[11]]) as usize;
+
+    // Field descriptors: name, type and width; each record starts with a deletion flag
+    let mut fields = Vec::new();
+    let mut offset = 1;
+    let mut at = 32;
+    while at + 32 <= bytes.len() && bytes[at] != 0x0D {
+        let descriptor = &bytes[at..at + 32];
+        let end = descriptor[..11].iter().position(|&b| b == 0).unwrap_or(11);
+        let name = String::from_utf8_lossy(&descriptor[..end]).to_uppercase();
+        let width = descriptor[16] as usize;
+        fields.push((name, descriptor[11], offset, width));
+        offset += width;
+        at += 32;
+    }
+
+    let Some(&(_, _, start, width)) = fields
+        .iter()
+        .find(|f| f.0 == "NAME")
+        .or_else(|| fields.iter().find(|f| f.0.ends_with("NAME") && f.1 == b'C'))
+        .or_else(|| fields.iter().find(|f| f.1 == b'C'))
+    else {
+        return Ok(Vec::new());
+    };
+    (0..records)
+        .map(|r| {
+            let record = header_length + r * record_length;
+            bytes
+                .get(record + start..record + start + width)
+                .map(|field| String::from_utf8_lossy(field).trim().to_string())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geojson_rings_are_oriented_and_named() {
+        // Exterior given clockwise, hole counter-clockwise: both get flipped
+        let text = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"BASIN_NAME": "Rhine", "area": 1},
+             "geometry": {"type": "Polygon", "coordinates": [
+                [[0, 0], [0, 10], [10, 10], [10, 0], [0, 0]],
+                [[4, 4], [6, 4], [6, 6], [4, 6], [4, 4]]]}},
+            {"type": "Feature", "properties": null, "geometry": {"type": "Point", "coordinates": [1, 1]}},
+            {"type": "Feature", "id": 7, "geometry": {"type": "MultiPolygon", "coordinates": [
+                [[[20, 0], [21, 0], [21, 1], [20, 0]]]]}}
+        ]}"#;
+        let regions = parse_geojson(text).unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].name, "Rhine");
+        assert_eq!(regions[1].name, "7");
+        assert_eq!(signed_area(&regions[0].rings[0]), 100.0);
+        assert_eq!(signed_area(&regions[0].rings[1]), -4.0);
+        assert!(regions[0].contains(2.0, 2.0));
+        assert!(!regions[0].contains(5.0, 5.0));
+        assert!(!regions[0].contains(12.0, 5.0));
+    }
+
+    /// A polygon `.shp` file with one record of `words` 16-bit words
+    fn shp_file(words: i32, content: &[u8]) -> Vec<u8> {
+        let mut shp = vec![0u8; 100];
+        shp[..4].copy_from_slice(&9994i32.to_be_bytes());
+        shp[32..36].copy_from_slice(&5i32.to_le_bytes());
+        shp.extend(1i32.to_be_bytes());
+        shp.extend(words.to_be_bytes());
+        shp.extend(content);
+        shp
+    }
+
+    #[test]
+    fn test_shapefile_polygon_and_names() {
+        // One record: a clockwise square with a counter-clockwise hole
+        let points = [
+            (0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0), (0.0, 0.0),
+            (4.0, 4.0), (6.0, 4.0), (6.0, 6.0), (4.0, 6.0), (4.0, 4.0),
+        ];
+        let mut content = Vec::new();
+        content.extend(5i32.to_le_bytes());
+        content.extend([0u8; 32]);
+        content.extend(2i32.to_le_bytes());
+        content.extend((points.len() as i32).to_le_bytes());
+        content.extend(0i32.to_le_bytes());
+        content.extend(5i32.to_le_bytes());
+        for (x, y) in points {
+            content.extend(f64::to_le_bytes(x));
+            content.extend(f64::to_le_bytes(y));
+        }
+        let shapes = parse_shp(&shp_file(content.len() as i32 / 2, &content)).unwrap();
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(signed_area(&shapes[0][0]), 100.0);
+        assert_eq!(signed_area(&shapes[0][1]), -4.0);
+
+        let mut dbf = vec![0u8; 32];
+        dbf[4] = 1;
+        dbf[8] = 65;
+        dbf[10] = 9;
+        let mut field = [0u8; 32];
+        field[..4].copy_from_slice(b"NAME");
+        field[11] = b'C';
+        field[16] = 8;
+        dbf.extend(field);
+        dbf.push(0x0D);
+        dbf.extend(b" Meuse   ");
+        assert_eq!(parse_dbf_names(&dbf).unwrap(), vec!["Meuse".to_string()]);
+    }
+
+    #[test]
+    fn test_corrupt_shapefile_counts_are_errors() {
+        let record = |num_parts: i32, num_points: i32| {
+            let mut content = Vec::new();
+            content.extend(5i32.to_le_bytes());
+            content.extend([0u8; 32]);
+            content.extend(num_parts.to_le_bytes());
+            content.extend(num_points.to_le_bytes());
+            content.extend(0i32.to_le_bytes());
+            content
+        };
+        let content = record(1, 0);
+        for (words, content) in [
+            (i32::MAX, content.clone()),
+            (-1, content.clone()),
+            (content.len() as i32 / 2, record(-1, 0)),
+            (content.len() as i32 / 2, record(1, i32::MAX)),
+            (content.len() as i32 / 2, record(i32::MAX, i32::MAX)),
+        ] {
+            assert!(matches!(parse_shp(&shp_file(words, &content)), Err(NetCDFError::InvalidFormat(_))));
+        }
+    }
+}
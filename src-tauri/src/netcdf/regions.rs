@@ -0,0 +1,487 @@
+use crate::errors::NetCDFError;
+use crate::models::{
+    DimensionSlice, ExportSummary, FileMetadata, GeoExtent, RegionExportRequest, RegionSelection, RegionSeries,
+    RegionSeriesRequest, RegionStep, Variable, VariableStats,
+};
+use super::backend::{open_dataset, DatasetBackend};
+use super::coordinates::detect_coordinates;
+use super::csv_export::csv_error;
+use super::data_access::{find_variable, is_text_type, resolve_hyperslab};
+use super::grid::{resolve_grid, LatLonGrid, EARTH_RADIUS_KM};
+use super::polygons::{bounding_box, read_regions, signed_area, Region};
+use super::regrid::{read_axis, AxisCells};
+use super::stats::StatsAccumulator;
+use super::table::{next_index, outer_blocks};
+use super::timeseries::{time_dimension, time_labels};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+/// Longitude shifts tried so polygons and grids in either convention meet
+const SHIFTS: [f64; 3] = [-360.0, 0.0, 360.0];
+
+/// Sample points per cell side when measuring coverage of curvilinear cells
+const SAMPLES: usize = 5;
+
+/// Polygons rasterised onto a variable's latitude/longitude grid
+pub struct RegionMask {
+    pub grid: LatLonGrid,
+    /// Names of the features making up the region
+    pub features: Vec<String>,
+    /// Share of each cell inside the polygons, in (row, column) row-major order
+    pub fraction: Vec<f64>,
+    /// Area of each cell in km²
+    pub area: Vec<f64>,
+}
+
+impl RegionMask {
+    /// Rasterise the selected features onto `var`'s grid.
+    ///
+    /// Rectilinear cells are clipped against the polygons exactly; on
+    /// curvilinear grids coverage is sampled on a grid of points across each
+    /// cell, whose shape is estimated from the neighbouring centres.
+    pub fn new(
+        dataset: &dyn DatasetBackend,
+        metadata: &FileMetadata,
+        var: &Variable,
+        selection: &RegionSelection,
+    ) -> Result<RegionMask, NetCDFError> {
+        let grid = resolve_grid(dataset, metadata, var)?;
+        let (region, features) = select_region(selection)?;
+        let (fraction, area) = if grid.curvilinear {
+            rasterise_curvilinear(&grid, &region, selection.fractional)
+        } else {
+            let coords = match &metadata.coordinates {
+                Some(coords) => coords.clone(),
+                None => detect_coordinates(metadata),
+            };
+            let cells = |name: Option<&String>, centres: &[f64], latitude: bool| -> Result<AxisCells, NetCDFError> {
+                match name.and_then(|name| metadata.variables.iter().find(|v| &v.name == name)) {
+                    Some(axis) if axis.dimensions.len() == 1 => read_axis(dataset, metadata, axis, latitude),
+                    _ => Ok(AxisCells::from_centres(centres.to_vec())),
+                }
+            };
+            let lat = cells(coords.lat_var.as_ref(), &grid.lat, true)?;
+            let lon = cells(coords.lon_var.as_ref(), &grid.lon, false)?;
+            rasterise_rectilinear(&lat, &lon, &region, selection.fractional)
+        };
+        Ok(RegionMask { grid, features, fraction, area })
+    }
+
+    /// Weight of the cell at row `j`, column `i`: its area inside the region in km²
+    pub fn weight(&self, j: usize, i: usize) -> f64 {
+        let k = j * self.grid.nx + i;
+        self.fraction[k] * self.area[k]
+    }
+
+    /// Number of cells at least partly inside the region
+    pub fn cells(&self) -> usize {
+        self.fraction.iter().filter(|&&f| f > 0.0).count()
+    }
+
+    /// Area of the region as covered by the grid, in km²
+    pub fn area_km2(&self) -> f64 {
+        self.fraction.iter().zip(&self.area).map(|(f, a)| f * a).sum()
+    }
+
+    /// Smallest block of rows and columns holding every covered cell
+    pub fn window(&self) -> Option<(RangeInclusive<usize>, RangeInclusive<usize>)> {
+        let nx = self.grid.nx;
+        let covered = || (0..self.fraction.len()).filter(|&k| self.fraction[k] > 0.0);
+        let rows = covered().map(|k| k / nx);
+        let (first_row, last_row) = (rows.clone().min()?, rows.max()?);
+        let columns = covered().map(|k| k % nx);
+        let (first_column, last_column) = (columns.clone().min()?, columns.max()?);
+        Some((first_row..=last_row, first_column..=last_column))
+    }
+}
+
+/// The selected features of a region file merged into one region, and their names
+fn select_region(selection: &RegionSelection) -> Result<(Region, Vec<String>), NetCDFError> {
+    let regions = read_regions(&selection.path)?;
+    let chosen: Vec<&Region> = if selection.features.is_empty() {
+        regions.iter().collect()
+    } else {
+        selection
+            .features
+            .iter()
+            .map(|wanted| {
+                regions
+                    .iter()
+                    .find(|r| &r.name == wanted)
+                    .or_else(|| wanted.parse::<usize>().ok().and_then(|k| regions.get(k)))
+                    .ok_or_else(|| {
+                        NetCDFError::InvalidSubsetRequest(format!(
+                            "Region '{}' not found in {}",
+                            wanted, selection.path
+                        ))
+                    })
+            })
+            .collect::<Result<_, _>>()?
+    };
+    let names: Vec<String> = chosen.iter().map(|r| r.name.clone()).collect();
+    let region = Region {
+        name: names.join(", "),
+        rings: chosen.iter().flat_map(|r| r.rings.iter().cloned()).collect(),
+    };
+    Ok((region, names))
+}
+
+/// Whether a point is inside the region, trying longitudes a turn apart
+fn contains_wrapped(region: &Region, extent: &GeoExtent, lon: f64, lat: f64) -> bool {
+    if lat < extent.south || lat > extent.north {
+        return false;
+    }
+    SHIFTS.iter().any(|shift| {
+        let lon = lon + shift;
+        extent.west <= lon && lon <= extent.east && region.contains(lon, lat)
+    })
+}
+
+/// Coverage and area of the cells of a grid with 1-D coordinates
+fn rasterise_rectilinear(
+    lat: &AxisCells,
+    lon: &AxisCells,
+    region: &Region,
+    fractional: bool,
+) -> (Vec<f64>, Vec<f64>) {
+    let (ny, nx) = (lat.len(), lon.len());
+    let extent = region.extent();
+    let ring_extents: Vec<_> = region.rings.iter().map(bounding_box).collect();
+
+    let mut fraction = vec![0.0; ny * nx];
+    let mut area = vec![0.0; ny * nx];
+    for j in 0..ny {
+        let (south, north) = lat.bounds[j];
+        let band = EARTH_RADIUS_KM * EARTH_RADIUS_KM * (north.to_radians().sin() - south.to_radians().sin()).abs();
+        for i in 0..nx {
+            let (west, east) = lon.bounds[i];
+            area[j * nx + i] = band * (east - west).abs().to_radians();
+            if north < extent.south || south > extent.north {
+                continue;
+            }
+
+            fraction[j * nx + i] = if fractional {
+                let cell = (east - west) * (north - south);
+                let covered: f64 = SHIFTS
+                    .iter()
+                    .map(|shift| (west + shift, east + shift))
+                    .filter(|&(w, e)| e > extent.west && w < extent.east)
+                    .flat_map(|(w, e)| {
+                        region.rings.iter().zip(&ring_extents).map(move |(ring, r)| {
+                            let overlaps = e > r.west && w < r.east && north > r.south && south < r.north;
+                            if overlaps { clipped_area(ring, w, e, south, north) } else { 0.0 }
+                        })
+                    })
+                    .sum();
+                if cell > 0.0 { (covered / cell).clamp(0.0, 1.0) } else { 0.0 }
+            } else if contains_wrapped(region, &extent, lon.centres[i], lat.centres[j]) {
+                1.0
+            } else {
+                0.0
+            };
+        }
+    }
+    (fraction, area)
+}
+
+/// Signed area of the part of a ring inside a rectangle (Sutherland-Hodgman
+/// clipping), in square degrees
+pub(crate) fn clipped_area(ring: &[(f64, f64)], west: f64, east: f64, south: f64, north: f64) -> f64 {
+    // Work relative to the rectangle's corner to keep precision
+    let (width, height) = (east - west, north - south);
+    let mut points: Vec<(f64, f64)> = ring.iter().map(|&(x, y)| (x - west, y - south)).collect();
+    for edge in 0..4 {
+        if points.is_empty() {
+            break;
+        }
+        let inside = |p: (f64, f64)| match edge {
+            0 => p.0 >= 0.0,
+            1 => p.0 <= width,
+            2 => p.1 >= 0.0,
+            _ => p.1 <= height,
+        };
+        let crossing = |a: (f64, f64), b: (f64, f64)| match edge {
+            0 | 1 => {
+                let x = if edge == 0 { 0.0 } else { width };
+                (x, a.1 + (x - a.0) / (b.0 - a.0) * (b.1 - a.1))
+            }
+            _ => {
+                let y = if edge == 2 { 0.0 } else { height };
+                (a.0 + (y - a.1) / (b.1 - a.1) * (b.0 - a.0), y)
+            }
+        };
+        let mut clipped = Vec::with_capacity(points.len() + 4);
+        for k in 0..points.len() {
+            let (a, b) = (points[(k + points.len() - 1) % points.len()], points[k]);
+            match (inside(a), inside(b)) {
+                (true, true) => clipped.push(b),
+                (true, false) => clipped.push(crossing(a, b)),
+                (false, true) => {
+                    clipped.push(crossing(a, b));
+                    clipped.push(b);
+                }
+                (false, false) => {}
+            }
+        }
+        points = clipped;
+    }
+    signed_area(&points)
+}
+
+/// Coverage and approximate area of the cells of a grid with 2-D coordinates
+fn rasterise_curvilinear(grid: &LatLonGrid, region: &Region, fractional: bool) -> (Vec<f64>, Vec<f64>) {
+    let (ny, nx) = (grid.ny, grid.nx);
+    let extent = region.extent();
+    let km_per_degree = EARTH_RADIUS_KM.to_radians();
+
+    let mut fraction = vec![0.0; ny * nx];
+    let mut area = vec![0.0; ny * nx];
+    for j in 0..ny {
+        for i in 0..nx {
+            let (lat, lon) = (grid.lat_at(j, i), grid.lon_at(j, i));
+            if !lat.is_finite() || !lon.is_finite() {
+                continue;
+            }
+            let (di, dj) = grid.cell_steps(j, i);
+            let cell = (di.0 * dj.1 - di.1 * dj.0).abs();
+            if !cell.is_finite() {
+                continue;
+            }
+            area[j * nx + i] = cell * lat.to_radians().cos() * km_per_degree * km_per_degree;
+
+            fraction[j * nx + i] = if fractional {
+                let offset = |k: usize| (k as f64 + 0.5) / SAMPLES as f64 - 0.5;
+                let inside = (0..SAMPLES * SAMPLES)
+                    .filter(|&k| {
+                        let (a, b) = (offset(k % SAMPLES), offset(k / SAMPLES));
+                        let point_lon = lon + a * di.0 + b * dj.0;
+                        let point_lat = lat + a * di.1 + b * dj.1;
+                        contains_wrapped(region, &extent, point_lon, point_lat)
+                    })
+                    .count();
+                inside as f64 / (SAMPLES * SAMPLES) as f64
+            } else if contains_wrapped(region, &extent, lon, lat) {
+                1.0
+            } else {
+                0.0
+            };
+        }
+    }
+    (fraction, area)
+}
+
+/// Area-weighted statistics of a variable (or a hyperslab of it) over a region.
+///
+/// Cells outside the region are left out; the others count in proportion
+/// to their area inside it.
+pub fn region_stats(
+    path: &str,
+    var_name: &str,
+    slices: &HashMap<String, DimensionSlice>,
+    selection: &RegionSelection,
+) -> Result<VariableStats, NetCDFError> {
+    read_region_stats(open_dataset(path)?.as_ref(), var_name, slices, selection)
+}
+
+/// Area-weighted statistics of a variable of an open dataset over a region
+pub fn read_region_stats(
+    dataset: &dyn DatasetBackend,
+    var_name: &str,
+    slices: &HashMap<String, DimensionSlice>,
+    selection: &RegionSelection,
+) -> Result<VariableStats, NetCDFError> {
+    let metadata = dataset.metadata()?;
+    let var = numeric_variable(&metadata, var_name)?;
+    let mask = RegionMask::new(dataset, &metadata, &var, selection)?;
+    let (y_dim, x_dim) = (mask.grid.y_dim, mask.grid.x_dim);
+
+    // Only read the part of the hyperslab around the region
+    let (mut start, mut count) = resolve_hyperslab(&var, slices)?;
+    let mut accumulator = StatsAccumulator::default();
+    if let Some((rows, columns)) = mask.window() {
+        for (d, range) in [(y_dim, rows), (x_dim, columns)] {
+            let first = start[d].max(*range.start());
+            let end = (start[d] + count[d]).min(range.end() + 1);
+            (start[d], count[d]) = (first, end.saturating_sub(first));
+        }
+        if count.iter().all(|&n| n > 0) {
+            for (block_start, block_count) in outer_blocks(&start, &count) {
+                let values = dataset.read_masked(&var.name, &block_start, &block_count)?;
+                let mut index = vec![0; block_count.len()];
+                for value in values {
+                    let weight = mask.weight(block_start[y_dim] + index[y_dim], block_start[x_dim] + index[x_dim]);
+                    if weight > 0.0 {
+                        accumulator.push(value, weight);
+                    }
+                    next_index(&mut index, &block_count);
+                }
+            }
+        }
+    }
+    Ok(accumulator.finish(&var.name))
+}
+
+/// Area-weighted mean, extremes and spread of a variable over a region at
+/// every time step, e.g. the mean precipitation over a river basin
+pub fn region_series(path: &str, request: &RegionSeriesRequest) -> Result<RegionSeries, NetCDFError> {
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    let var = numeric_variable(&metadata, &request.var_name)?;
+    let mask = RegionMask::new(dataset.as_ref(), &metadata, &var, &request.region)?;
+    let (rows, columns) = mask.window().ok_or_else(|| {
+        NetCDFError::InvalidSubsetRequest(format!("The region covers no grid cell of '{}'", var.name))
+    })?;
+    let grid = &mask.grid;
+
+    let time_axis = time_dimension(&metadata, &var);
+    let times = match time_axis {
+        Some((time_var, _)) => time_labels(dataset.as_ref(), time_var)?,
+        None => vec![String::new()],
+    };
+    let (mut start, mut count) = grid.slice_extents(&var, &request.indices)?;
+    (start[grid.y_dim], count[grid.y_dim]) = (*rows.start(), rows.clone().count());
+    (start[grid.x_dim], count[grid.x_dim]) = (*columns.start(), columns.clone().count());
+    let total = mask.area_km2();
+
+    let mut steps = Vec::with_capacity(times.len());
+    for (t, time) in times.into_iter().enumerate() {
+        if let Some((_, d)) = time_axis {
+            start[d] = t;
+        }
+        let values = dataset.read_masked(&var.name, &start, &count)?;
+        let mut accumulator = StatsAccumulator::default();
+        let mut valid = 0.0;
+        for (k, value) in values.into_iter().enumerate() {
+            // The slab varies only along the two spatial dimensions, in the variable's order
+            let (j, i) = if grid.y_dim < grid.x_dim {
+                (rows.start() + k / count[grid.x_dim], columns.start() + k % count[grid.x_dim])
+            } else {
+                (rows.start() + k % count[grid.y_dim], columns.start() + k / count[grid.y_dim])
+            };
+            let weight = mask.weight(j, i);
+            if weight > 0.0 {
+                accumulator.push(value, weight);
+                if value.is_finite() {
+                    valid += weight;
+                }
+            }
+        }
+        let stats = accumulator.finish(&var.name);
+        steps.push(RegionStep {
+            time,
+            mean: stats.mean,
+            min: stats.min,
+            max: stats.max,
+            std_dev: stats.std_dev,
+            valid_fraction: if total > 0.0 { valid / total } else { 0.0 },
+        });
+    }
+
+    Ok(RegionSeries {
+        var_name: var.name.clone(),
+        features: mask.features.clone(),
+        cells: mask.cells(),
+        area_km2: total,
+        steps,
+    })
+}
+
+/// Write regional statistics over time to a CSV table with one row per time step
+pub fn export_region_series(path: &str, request: &RegionExportRequest) -> Result<ExportSummary, NetCDFError> {
+    let series = region_series(path, &request.series)?;
+    let mut writer = csv::Writer::from_path(&request.output_path).map_err(csv_error)?;
+    writer
+        .write_record(["time", "mean", "min", "max", "std_dev", "valid_fraction"])
+        .map_err(csv_error)?;
+    let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+    for step in &series.steps {
+        writer
+            .write_record([
+                step.time.clone(),
+                optional(step.mean),
+                optional(step.min),
+                optional(step.max),
+                optional(step.std_dev),
+                step.valid_fraction.to_string(),
+            ])
+            .map_err(csv_error)?;
+    }
+    writer.flush()?;
+
+    Ok(ExportSummary {
+        output_path: request.output_path.clone(),
+        variables: vec![series.var_name],
+        skipped: vec![],
+        records: series.steps.len(),
+        warnings: vec![],
+    })
+}
+
+fn numeric_variable(metadata: &FileMetadata, var_name: &str) -> Result<Variable, NetCDFError> {
+    let var = find_variable(metadata, var_name)?;
+    if is_text_type(&var.data_type) {
+        return Err(NetCDFError::ConversionError(format!(
+            "Variable '{}' is not numeric",
+            var.name
+        )));
+    }
+    Ok(var)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(west: f64, south: f64, east: f64, north: f64) -> Region {
+        Region {
+            name: "square".to_string(),
+            rings: vec![vec![(west, south), (east, south), (east, north), (west, north)]],
+        }
+    }
+
+    #[test]
+    fn test_fractional_coverage_of_rectilinear_cells() {
+        // 1-degree cells centred on 0.5..3.5; the square covers 60% of the
+        // first column, all of the second and a quarter of the third
+        let lat = AxisCells::from_centres(vec![0.5, 1.5]);
+        let lon = AxisCells::from_centres(vec![0.5, 1.5, 2.5, 3.5]);
+        let region = square(0.4, 0.0, 2.25, 2.0);
+
+        let (fraction, area) = rasterise_rectilinear(&lat, &lon, &region, true);
+        for (actual, expected) in fraction.iter().zip([0.6, 1.0, 0.25, 0.0, 0.6, 1.0, 0.25, 0.0]) {
+            assert!((actual - expected).abs() < 1e-9, "{:?}", fraction);
+        }
+        assert!((area[0] - 12364.0).abs() < 10.0);
+
+        let (centres, _) = rasterise_rectilinear(&lat, &lon, &region, false);
+        assert_eq!(centres, vec![1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_longitude_conventions_and_holes() {
+        // A grid in 0..360 and a polygon west of Greenwich with a hole
+        let lat = AxisCells { centres: vec![0.5], bounds: vec![(0.0, 1.0)] };
+        let lon = AxisCells::from_centres(vec![356.5, 357.5, 358.5, 359.5]);
+        let mut region = square(-4.0, 0.0, 0.0, 1.0);
+        region.rings.push(vec![(-3.0, 0.0), (-3.0, 1.0), (-2.0, 1.0), (-2.0, 0.0)]);
+
+        let (fraction, _) = rasterise_rectilinear(&lat, &lon, &region, true);
+        assert_eq!(fraction, vec![1.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_curvilinear_sampling() {
+        let grid = LatLonGrid {
+            y_dim: 0,
+            x_dim: 1,
+            ny: 2,
+            nx: 2,
+            lat: vec![0.5, 0.5, 1.5, 1.5],
+            lon: vec![0.5, 1.5, 0.5, 1.5],
+            curvilinear: true,
+        };
+        let (fraction, area) = rasterise_curvilinear(&grid, &square(0.0, 0.0, 1.2, 2.0), true);
+        assert_eq!(fraction, vec![1.0, 0.2, 1.0, 0.2]);
+        assert!((area[0] - 12364.0).abs() < 10.0);
+    }
+}
@@ -0,0 +1,1106 @@
+//! Regridding variables onto another latitude/longitude grid.
+//!
+//! A [`Remap`] holds, for every target cell, the source cells it draws from
+//! and their weights; it is built once per pair of grids and applied to as
+//! many 2-D slices as needed. On rectilinear source grids all methods work
+//! axis by axis. On curvilinear source grids bilinear weights come from the
+//! quadrilateral of source centres around each target centre, and
+//! conservative weights from the overlap of each source cell (a
+//! parallelogram spanned by its neighbouring centres) with the target cells.
+//!
+//! Masked (NaN) source cells are left out and the remaining weights
+//! renormalised; a target cell is masked when less than half of its weight
+//! falls on valid source cells.
+
+use crate::errors::NetCDFError;
+use crate::models::{
+    Dimension, ExportSummary, FileMetadata, GeoExtent, NetCDFExportRequest, RegridExportRequest, RegridMethod,
+    RegridOptions, RegridTarget, Variable,
+};
+use super::backend::{open_dataset, DatasetBackend};
+use super::coordinates::detect_coordinates;
+use super::data_access::{find_variable, is_text_type, NativeValues};
+use super::grid::{resolve_grid, LatLonGrid};
+use super::loader::text_attributes;
+use super::nc_export::{check_output_path, write_dataset};
+use super::regions::clipped_area;
+use super::table::next_index;
+use super::timeseries::angular_distance;
+use netcdf::AttributeValue;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Most cells along one axis of a regular target grid
+const MAX_AXIS_CELLS: usize = 100_000;
+
+/// Most cells of a regular target grid, about a 0.1 degree global grid
+const MAX_GRID_CELLS: usize = 10_000_000;
+
+/// Grid cell (row, column) and its interpolation weight
+pub(crate) type CellWeights = Vec<((usize, usize), f64)>;
+
+/// Cells of a grid along one axis, in stored order
+#[derive(Debug, Clone)]
+pub struct AxisCells {
+    pub centres: Vec<f64>,
+    /// Lower and upper edge of each cell
+    pub bounds: Vec<(f64, f64)>,
+}
+
+impl AxisCells {
+    /// Cells with edges halfway between neighbouring centres
+    pub(crate) fn from_centres(centres: Vec<f64>) -> AxisCells {
+        let n = centres.len();
+        let edge = |k: usize| (centres[k] + centres[k + 1]) / 2.0;
+        let bounds = (0..n)
+            .map(|k| {
+                let lower = if k > 0 { edge(k - 1) } else if n > 1 { 2.0 * centres[0] - edge(0) } else { centres[0] };
+                let upper = if k + 1 < n { edge(k) } else if n > 1 { 2.0 * centres[n - 1] - edge(n - 2) } else { centres[0] };
+                (lower.min(upper), lower.max(upper))
+            })
+            .collect();
+        AxisCells { centres, bounds }
+    }
+
+    /// Regularly spaced cells of width `step` filling `start..end`
+    fn regular(start: f64, end: f64, step: f64, axis: &str) -> Result<AxisCells, NetCDFError> {
+        let valid = step.is_finite() && step > 0.0 && start.is_finite() && end.is_finite() && end > start;
+        if !valid {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Invalid {} range {}..{} with step {}",
+                axis, start, end, step
+            )));
+        }
+        let n = ((end - start) / step).round().max(1.0);
+        if n > MAX_AXIS_CELLS as f64 {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "A {} step of {} makes {} cells, more than the limit of {}",
+                axis, step, n, MAX_AXIS_CELLS
+            )));
+        }
+        let n = n as usize;
+        let centres = (0..n).map(|k| start + (k as f64 + 0.5) * step).collect();
+        let bounds = (0..n).map(|k| (start + k as f64 * step, start + (k + 1) as f64 * step)).collect();
+        Ok(AxisCells { centres, bounds })
+    }
+
+    pub fn len(&self) -> usize {
+        self.centres.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centres.is_empty()
+    }
+}
+
+/// A rectilinear latitude/longitude grid to regrid onto
+#[derive(Debug, Clone)]
+pub struct TargetGrid {
+    pub lat: AxisCells,
+    pub lon: AxisCells,
+}
+
+impl TargetGrid {
+    pub fn resolve(target: &RegridTarget) -> Result<TargetGrid, NetCDFError> {
+        match target {
+            RegridTarget::Regular { extent, lat_step, lon_step } => TargetGrid::regular(extent, *lat_step, *lon_step),
+            RegridTarget::File { path } => TargetGrid::from_file(path),
+        }
+    }
+
+    /// Regular grid with cells of `lat_step` by `lon_step` degrees covering `extent`
+    pub fn regular(extent: &GeoExtent, lat_step: f64, lon_step: f64) -> Result<TargetGrid, NetCDFError> {
+        if extent.south < -90.0 || extent.north > 90.0 {
+            return Err(NetCDFError::InvalidSubsetRequest(
+                "Latitudes must lie within -90..90".to_string(),
+            ));
+        }
+        let lat = AxisCells::regular(extent.south, extent.north, lat_step, "latitude")?;
+        let lon = AxisCells::regular(extent.west, extent.east, lon_step, "longitude")?;
+        if lat.len() * lon.len() > MAX_GRID_CELLS {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "A {} by {} grid has more than the limit of {} cells; use larger steps",
+                lat.len(),
+                lon.len(),
+                MAX_GRID_CELLS
+            )));
+        }
+        Ok(TargetGrid { lat, lon })
+    }
+
+    /// Grid of the 1-D latitude/longitude coordinates of another file
+    pub fn from_file(path: &str) -> Result<TargetGrid, NetCDFError> {
+        let dataset = open_dataset(path)?;
+        let metadata = dataset.metadata()?;
+        let coords = detect_coordinates(&metadata);
+        let axis = |name: Option<&String>, latitude: bool| -> Result<AxisCells, NetCDFError> {
+            let var = name
+                .and_then(|name| metadata.variables.iter().find(|v| &v.name == name))
+                .filter(|var| var.dimensions.len() == 1)
+                .ok_or_else(|| {
+                    NetCDFError::InvalidSubsetRequest(format!(
+                        "{} has no 1-D latitude/longitude coordinates to regrid onto",
+                        path
+                    ))
+                })?;
+            read_axis(dataset.as_ref(), &metadata, var, latitude)
+        };
+        Ok(TargetGrid {
+            lat: axis(coords.lat_var.as_ref(), true)?,
+            lon: axis(coords.lon_var.as_ref(), false)?,
+        })
+    }
+
+    /// The grid as a [`LatLonGrid`] of (lat, lon) slices, for rendering
+    pub fn lat_lon_grid(&self) -> LatLonGrid {
+        LatLonGrid {
+            y_dim: 0,
+            x_dim: 1,
+            ny: self.lat.len(),
+            nx: self.lon.len(),
+            lat: self.lat.centres.clone(),
+            lon: self.lon.centres.clone(),
+            curvilinear: false,
+        }
+    }
+}
+
+/// Read a 1-D coordinate and its cell bounds (from its `bounds` variable, or
+/// halfway between centres)
+pub(crate) fn read_axis(
+    dataset: &dyn DatasetBackend,
+    metadata: &FileMetadata,
+    var: &Variable,
+    latitude: bool,
+) -> Result<AxisCells, NetCDFError> {
+    let centres = dataset.read_masked_all(var)?;
+    let bounds_var = var
+        .attributes
+        .get("bounds")
+        .and_then(|name| metadata.variables.iter().find(|v| &v.name == name))
+        .filter(|b| b.shape == [centres.len(), 2]);
+    let mut cells = match bounds_var {
+        Some(bounds_var) => {
+            let edges = dataset.read_masked_all(bounds_var)?;
+            let bounds = edges.chunks(2).map(|pair| (pair[0].min(pair[1]), pair[0].max(pair[1]))).collect();
+            AxisCells { centres, bounds }
+        }
+        None => AxisCells::from_centres(centres),
+    };
+    if latitude {
+        for bound in &mut cells.bounds {
+            *bound = (bound.0.max(-90.0), bound.1.min(90.0));
+        }
+    }
+    Ok(cells)
+}
+
+/// Source axis sorted ascending, remembering each cell's stored index
+pub(crate) struct SortedAxis {
+    centres: Vec<f64>,
+    bounds: Vec<(f64, f64)>,
+    index: Vec<usize>,
+    longitude: bool,
+    /// Whether the cells wrap around the globe
+    periodic: bool,
+}
+
+impl SortedAxis {
+    pub(crate) fn new(cells: &AxisCells, longitude: bool) -> SortedAxis {
+        let mut order: Vec<usize> = (0..cells.len()).filter(|&k| cells.centres[k].is_finite()).collect();
+        // Longitudes are brought into one turn starting at the westernmost
+        // stored centre, so grids crossing the antimeridian become monotonic
+        let descending = order.len() > 1 && {
+            let step = cells.centres[order[1]] - cells.centres[order[0]];
+            (step + 540.0).rem_euclid(360.0) - 180.0 < 0.0
+        };
+        let base = match (descending, order.first(), order.last()) {
+            (false, Some(&first), _) => cells.centres[first],
+            (true, _, Some(&last)) => cells.centres[last],
+            _ => 0.0,
+        };
+        let shift = |k: usize| match longitude {
+            true => (cells.centres[k] - base).rem_euclid(360.0) + base - cells.centres[k],
+            false => 0.0,
+        };
+        let centre = |k: usize| cells.centres[k] + shift(k);
+        order.sort_by(|&a, &b| centre(a).total_cmp(&centre(b)));
+
+        let centres: Vec<f64> = order.iter().map(|&k| centre(k)).collect();
+        let bounds: Vec<(f64, f64)> = order
+            .iter()
+            .map(|&k| (cells.bounds[k].0 + shift(k), cells.bounds[k].1 + shift(k)))
+            .collect();
+        let periodic = longitude
+            && !bounds.is_empty()
+            && bounds[bounds.len() - 1].1 - bounds[0].0 >= 360.0 - 1e-6;
+        SortedAxis { centres, bounds, index: order, longitude, periodic }
+    }
+
+    /// Bring a longitude into the turn the axis starts in
+    fn wrap(&self, x: f64) -> f64 {
+        match (self.longitude, self.bounds.first()) {
+            (true, Some(&(lower, _))) => lower + (x - lower).rem_euclid(360.0),
+            _ => x,
+        }
+    }
+
+    /// The cell containing `x`
+    fn nearest(&self, x: f64) -> Vec<(usize, f64)> {
+        let x = self.wrap(x);
+        let k = self.bounds.partition_point(|b| b.1 <= x);
+        match self.bounds.get(k) {
+            Some(b) if b.0 <= x => vec![(self.index[k], 1.0)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Linear interpolation between the centres on either side of `x`
+    pub(crate) fn linear(&self, x: f64) -> Vec<(usize, f64)> {
+        let n = self.centres.len();
+        if n == 0 {
+            return Vec::new();
+        }
+        let x = self.wrap(x);
+        let k = self.centres.partition_point(|&c| c <= x);
+        let between = |a: usize, ca: f64, b: usize, cb: f64| {
+            let t = if cb > ca { (x - ca) / (cb - ca) } else { 0.0 };
+            vec![(self.index[a], 1.0 - t), (self.index[b], t)]
+        };
+        if k == 0 || k == n {
+            // Beyond the outermost centres: across the seam of a global
+            // axis, else the edge cell as far as its bounds reach
+            if self.periodic && n > 1 {
+                return match k {
+                    0 => between(n - 1, self.centres[n - 1] - 360.0, 0, self.centres[0]),
+                    _ => between(n - 1, self.centres[n - 1], 0, self.centres[0] + 360.0),
+                };
+            }
+            let edge = if k == 0 { 0 } else { n - 1 };
+            let (lower, upper) = self.bounds[edge];
+            return if lower <= x && x <= upper { vec![(self.index[edge], 1.0)] } else { Vec::new() };
+        }
+        between(k - 1, self.centres[k - 1], k, self.centres[k])
+    }
+
+    /// Overlap of every cell with `lower..upper`, measured by `measure`
+    fn overlaps(&self, lower: f64, upper: f64, measure: impl Fn(f64, f64) -> f64) -> Vec<(usize, f64)> {
+        let shifts: &[f64] = if self.longitude { &[-360.0, 0.0, 360.0] } else { &[0.0] };
+        let mut weights = Vec::new();
+        for (k, &(a, b)) in self.bounds.iter().enumerate() {
+            let weight: f64 = shifts
+                .iter()
+                .map(|shift| {
+                    let (a, b) = (lower.max(a + shift), upper.min(b + shift));
+                    if b > a { measure(a, b) } else { 0.0 }
+                })
+                .sum();
+            if weight > 0.0 {
+                weights.push((self.index[k], weight));
+            }
+        }
+        weights
+    }
+
+    /// Stored index and bounds of the cells overlapping `lower..upper`
+    fn spanning(&self, lower: f64, upper: f64) -> impl Iterator<Item = (usize, (f64, f64))> + '_ {
+        let first = self.bounds.partition_point(|b| b.1 <= lower);
+        let last = self.bounds.partition_point(|b| b.0 < upper).max(first);
+        (first..last).map(|k| (self.index[k], self.bounds[k]))
+    }
+
+    fn weights(&self, method: RegridMethod, centre: f64, bounds: (f64, f64)) -> Vec<(usize, f64)> {
+        match method {
+            RegridMethod::Nearest => self.nearest(centre),
+            RegridMethod::Bilinear => self.linear(centre),
+            RegridMethod::Conservative if self.longitude => self.overlaps(bounds.0, bounds.1, |a, b| b - a),
+            // Area between two latitudes is proportional to the difference of their sines
+            RegridMethod::Conservative => {
+                self.overlaps(bounds.0, bounds.1, |a, b| b.to_radians().sin() - a.to_radians().sin())
+            }
+        }
+    }
+}
+
+/// Source cells and weights for every cell of a target grid
+pub struct Remap {
+    pub ny: usize,
+    pub nx: usize,
+    /// Per target cell, in (lat, lon) row-major order: source slice indices and weights
+    weights: Vec<Vec<(usize, f64)>>,
+}
+
+impl Remap {
+    /// Weights from `var`'s grid to `target`
+    pub fn new(
+        dataset: &dyn DatasetBackend,
+        metadata: &FileMetadata,
+        grid: &LatLonGrid,
+        target: &TargetGrid,
+        method: RegridMethod,
+    ) -> Result<Remap, NetCDFError> {
+        if grid.curvilinear {
+            return Ok(match method {
+                RegridMethod::Conservative => Remap::conservative_curvilinear(grid, target),
+                _ => Remap::points_curvilinear(grid, target, method),
+            });
+        }
+
+        let coords = match &metadata.coordinates {
+            Some(coords) => coords.clone(),
+            None => detect_coordinates(metadata),
+        };
+        let cells = |name: Option<&String>, centres: &[f64], latitude: bool| -> Result<AxisCells, NetCDFError> {
+            match name.and_then(|name| metadata.variables.iter().find(|v| &v.name == name)) {
+                Some(var) if method == RegridMethod::Conservative => read_axis(dataset, metadata, var, latitude),
+                _ => Ok(AxisCells::from_centres(centres.to_vec())),
+            }
+        };
+        let lat = SortedAxis::new(&cells(coords.lat_var.as_ref(), &grid.lat, true)?, false);
+        let lon = SortedAxis::new(&cells(coords.lon_var.as_ref(), &grid.lon, false)?, true);
+
+        let lat_weights: Vec<_> = (0..target.lat.len())
+            .map(|j| lat.weights(method, target.lat.centres[j], target.lat.bounds[j]))
+            .collect();
+        let lon_weights: Vec<_> = (0..target.lon.len())
+            .map(|i| lon.weights(method, target.lon.centres[i], target.lon.bounds[i]))
+            .collect();
+
+        let mut weights = Vec::with_capacity(target.lat.len() * target.lon.len());
+        for lat_cells in &lat_weights {
+            for lon_cells in &lon_weights {
+                let mut cell = Vec::with_capacity(lat_cells.len() * lon_cells.len());
+                for &(j, wy) in lat_cells {
+                    for &(i, wx) in lon_cells {
+                        cell.push((grid.slice_index(j, i), wy * wx));
+                    }
+                }
+                weights.push(cell);
+            }
+        }
+        Ok(Remap { ny: target.lat.len(), nx: target.lon.len(), weights })
+    }
+
+    /// Nearest source point within about one grid spacing of each target
+    /// centre or, for bilinear, the quadrilateral of source centres around it
+    /// (the nearest point where no quadrilateral holds it, as at the edges)
+    fn points_curvilinear(grid: &LatLonGrid, target: &TargetGrid, method: RegridMethod) -> Remap {
+        let index = PointIndex::new(grid);
+        let mut weights = Vec::with_capacity(target.lat.len() * target.lon.len());
+        for &lat in &target.lat.centres {
+            for &lon in &target.lon.centres {
+                let Some(nearest) = index.nearest(lat, lon) else {
+                    weights.push(Vec::new());
+                    continue;
+                };
+                let cells = match method {
+                    RegridMethod::Bilinear => curvilinear_weights(grid, lat, lon, nearest),
+                    _ => None,
+                };
+                let cells = cells.unwrap_or_else(|| vec![(nearest, 1.0)]);
+                weights.push(cells.into_iter().map(|((j, i), w)| (grid.slice_index(j, i), w)).collect());
+            }
+        }
+        Remap { ny: target.lat.len(), nx: target.lon.len(), weights }
+    }
+
+    /// Overlap of each source cell with the target cells, weighted by the
+    /// cosine of the source latitude to approximate its area on the sphere
+    fn conservative_curvilinear(grid: &LatLonGrid, target: &TargetGrid) -> Remap {
+        let (ny, nx) = (target.lat.len(), target.lon.len());
+        let lat_axis = SortedAxis::new(&target.lat, false);
+        let lon_axis = SortedAxis::new(&target.lon, true);
+        let mut weights = vec![Vec::new(); ny * nx];
+        for j in 0..grid.ny {
+            for i in 0..grid.nx {
+                let (lat, lon) = (grid.lat_at(j, i), lon_axis.wrap(grid.lon_at(j, i)));
+                let (di, dj) = grid.cell_steps(j, i);
+                let corners = [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)]
+                    .map(|(a, b)| (lon + a * di.0 + b * dj.0, lat + a * di.1 + b * dj.1));
+                if corners.iter().any(|p| !p.0.is_finite() || !p.1.is_finite()) {
+                    continue;
+                }
+                let (south, north) = corners.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)));
+                let (west, east) = corners.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+                let scale = lat.to_radians().cos().max(0.0);
+                let source = grid.slice_index(j, i);
+
+                // A cell past the end of the target's turn also overlaps its start
+                for shift in [-360.0, 0.0, 360.0] {
+                    let ring: Vec<(f64, f64)> = corners.iter().map(|&(x, y)| (x + shift, y)).collect();
+                    for (tj, (t_south, t_north)) in lat_axis.spanning(south, north) {
+                        for (ti, (t_west, t_east)) in lon_axis.spanning(west + shift, east + shift) {
+                            let area = clipped_area(&ring, t_west, t_east, t_south, t_north).abs() * scale;
+                            if area > 0.0 {
+                                weights[tj * nx + ti].push((source, area));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Remap { ny, nx, weights }
+    }
+
+    /// Regrid one 2-D slice read in the source variable's dimension order
+    pub fn apply(&self, data: &[f64]) -> Vec<f64> {
+        self.weights
+            .iter()
+            .map(|cell| {
+                let (mut sum, mut valid, mut total) = (0.0, 0.0, 0.0);
+                for &(k, weight) in cell {
+                    total += weight;
+                    let value = data.get(k).copied().unwrap_or(f64::NAN);
+                    if value.is_finite() {
+                        sum += value * weight;
+                        valid += weight;
+                    }
+                }
+                if valid > 0.0 && valid >= total / 2.0 {
+                    sum / valid
+                } else {
+                    f64::NAN
+                }
+            })
+            .collect()
+    }
+}
+
+/// Latitude, longitude and (row, column) of source points
+type Points = Vec<(f64, f64, (usize, usize))>;
+
+/// Source points of a curvilinear grid bucketed by latitude and longitude
+struct PointIndex {
+    /// Bucket size in degrees, about twice the grid spacing
+    size: f64,
+    columns: i64,
+    buckets: HashMap<(i64, i64), Points>,
+}
+
+impl PointIndex {
+    fn new(grid: &LatLonGrid) -> PointIndex {
+        let points: Points = (0..grid.ny)
+            .flat_map(|j| (0..grid.nx).map(move |i| (j, i)))
+            .map(|(j, i)| (grid.lat_at(j, i), grid.lon_at(j, i).rem_euclid(360.0), (j, i)))
+            .filter(|(lat, lon, _)| lat.is_finite() && lon.is_finite())
+            .collect();
+        let (south, north) = points.iter().fold((90.0f64, -90.0f64), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+        let (west, east) = points.iter().fold((360.0f64, 0.0f64), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)));
+        let area = ((north - south).max(0.0) + 1e-3) * ((east - west).max(0.0) + 1e-3);
+        let spacing = (area / points.len().max(1) as f64).sqrt();
+        let columns = (360.0 / (2.0 * spacing).clamp(1e-3, 30.0)).ceil() as i64;
+        let size = 360.0 / columns as f64;
+
+        let mut buckets: HashMap<(i64, i64), Points> = HashMap::new();
+        for point in points {
+            let key = ((point.0 / size).floor() as i64, (point.1 / size).floor() as i64);
+            buckets.entry(key).or_default().push(point);
+        }
+        PointIndex { size, columns, buckets }
+    }
+
+    fn nearest(&self, lat: f64, lon: f64) -> Option<(usize, usize)> {
+        let row = (lat / self.size).floor() as i64;
+        let column = (lon.rem_euclid(360.0) / self.size).floor() as i64;
+        let mut best = None;
+        let mut best_distance = self.size * self.size;
+        for dr in -1..=1 {
+            for dc in -1..=1 {
+                let Some(points) = self.buckets.get(&(row + dr, (column + dc).rem_euclid(self.columns))) else {
+                    continue;
+                };
+                for &(plat, plon, cell) in points {
+                    let distance = angular_distance(plat, plon, lat, lon);
+                    if distance <= best_distance {
+                        best_distance = distance;
+                        best = Some(cell);
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Bilinear weights from the grid quadrilateral around `nearest` that
+/// contains the point (`lat`, `lon`), found by inverting the bilinear map of
+/// each of the four quads sharing that corner
+pub(crate) fn curvilinear_weights(grid: &LatLonGrid, lat: f64, lon: f64, nearest: (usize, usize)) -> Option<CellWeights> {
+    let (j, i) = nearest;
+    let scale = lat.to_radians().cos();
+    // Local plane centred on the point, in degrees of latitude
+    let local = |j: usize, i: usize| {
+        let dlon = (grid.lon_at(j, i) - lon + 540.0).rem_euclid(360.0) - 180.0;
+        (dlon * scale, grid.lat_at(j, i) - lat)
+    };
+
+    for j0 in j.saturating_sub(1)..=j.min(grid.ny.saturating_sub(2)) {
+        for i0 in i.saturating_sub(1)..=i.min(grid.nx.saturating_sub(2)) {
+            let corners = [(j0, i0), (j0, i0 + 1), (j0 + 1, i0), (j0 + 1, i0 + 1)];
+            let [p00, p10, p01, p11] = corners.map(|(j, i)| local(j, i));
+            if [p00, p10, p01, p11].iter().any(|p| !p.0.is_finite() || !p.1.is_finite()) {
+                continue;
+            }
+            let Some((s, t)) = invert_bilinear(p00, p10, p01, p11) else {
+                continue;
+            };
+            let weights = [(1.0 - s) * (1.0 - t), s * (1.0 - t), (1.0 - s) * t, s * t];
+            return Some(corners.into_iter().zip(weights).filter(|&(_, w)| w > 0.0).collect());
+        }
+    }
+    None
+}
+
+/// Coordinates (s, t) in the unit square that a bilinear quad maps to the
+/// origin, if the origin lies inside the quad
+fn invert_bilinear(p00: (f64, f64), p10: (f64, f64), p01: (f64, f64), p11: (f64, f64)) -> Option<(f64, f64)> {
+    let b = (p10.0 - p00.0, p10.1 - p00.1);
+    let c = (p01.0 - p00.0, p01.1 - p00.1);
+    let d = (p11.0 - p10.0 - p01.0 + p00.0, p11.1 - p10.1 - p01.1 + p00.1);
+    let (mut s, mut t) = (0.5, 0.5);
+    for _ in 0..20 {
+        let f = (p00.0 + b.0 * s + c.0 * t + d.0 * s * t, p00.1 + b.1 * s + c.1 * t + d.1 * s * t);
+        let (ds, dt) = ((b.0 + d.0 * t, b.1 + d.1 * t), (c.0 + d.0 * s, c.1 + d.1 * s));
+        let det = ds.0 * dt.1 - dt.0 * ds.1;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let step_s = (f.0 * dt.1 - dt.0 * f.1) / det;
+        let step_t = (ds.0 * f.1 - f.0 * ds.1) / det;
+        s -= step_s;
+        t -= step_t;
+        if step_s.abs() < 1e-10 && step_t.abs() < 1e-10 {
+            break;
+        }
+    }
+    let inside = |x: f64| (-1e-9..=1.0 + 1e-9).contains(&x);
+    (inside(s) && inside(t)).then(|| (s.clamp(0.0, 1.0), t.clamp(0.0, 1.0)))
+}
+
+/// Regrid one 2-D slice of a variable; returns the target grid and the
+/// values in (lat, lon) order
+pub fn regrid_slice(
+    dataset: &dyn DatasetBackend,
+    metadata: &FileMetadata,
+    var: &Variable,
+    indices: &HashMap<String, usize>,
+    options: &RegridOptions,
+) -> Result<(LatLonGrid, Vec<f64>), NetCDFError> {
+    let grid = resolve_grid(dataset, metadata, var)?;
+    let target = TargetGrid::resolve(&options.target)?;
+    let remap = Remap::new(dataset, metadata, &grid, &target, options.method)?;
+    let (start, count) = grid.slice_extents(var, indices)?;
+    let data = dataset.read_masked(&var.name, &start, &count)?;
+    Ok((target.lat_lon_grid(), remap.apply(&data)))
+}
+
+/// Attributes that describe the source grid or encoding rather than the values
+const DROPPED_ATTRIBUTES: &[&str] = &[
+    "_FillValue", "missing_value", "valid_min", "valid_max", "valid_range", "scale_factor",
+    "add_offset", "coordinates", "grid_mapping", "bounds", "_ChunkSizes", "_Storage",
+];
+
+/// Write variables regridded onto another grid to a new NetCDF file.
+///
+/// The output has `lat` and `lon` coordinates with bounds; non-spatial
+/// dimensions and their coordinate variables are carried over (restricted to
+/// the requested slices). Values are written as doubles with NaN as fill,
+/// by the NetCDF export reading a [`RegriddedDataset`].
+pub fn export_regridded(path: &str, request: &RegridExportRequest) -> Result<ExportSummary, NetCDFError> {
+    check_output_path(path, &request.output_path)?;
+    let source = open_dataset(path)?;
+    let dataset = RegriddedDataset::new(source.as_ref(), &request.variables, &request.regrid)?;
+    let export = NetCDFExportRequest {
+        output_path: request.output_path.clone(),
+        variables: request.variables.clone(),
+        slices: request.slices.clone(),
+        format: request.format,
+        deflate_level: None,
+    };
+    write_dataset(&dataset, &export, &regrid_command(path, request))
+}
+
+/// The regridding as a command line for the history
+fn regrid_command(path: &str, request: &RegridExportRequest) -> String {
+    let target = match &request.regrid.target {
+        RegridTarget::Regular { extent, lat_step, lon_step } => format!(
+            "{},{},{},{} by {}x{} degrees",
+            extent.south, extent.west, extent.north, extent.east, lat_step, lon_step
+        ),
+        RegridTarget::File { path } => format!("the grid of {}", path),
+    };
+    format!(
+        "regrid -v {} ({:?} onto {}) {} {}",
+        request.variables.join(","),
+        request.regrid.method,
+        target,
+        path,
+        request.output_path
+    )
+}
+
+/// A regridded variable: its source, the source grid and its [`Remap`]
+struct Regridded {
+    source: Variable,
+    grid: LatLonGrid,
+    remap: usize,
+}
+
+/// Variables of a dataset regridded onto a target grid, read like a dataset.
+///
+/// Regridded variables keep their other dimensions, followed by `lat` and
+/// `lon`, and read as doubles with NaN where the target cell is masked. The
+/// target's `lat`, `lon`, `lat_bnds` and `lon_bnds` and the coordinate
+/// variables of the other dimensions are the only other variables.
+pub struct RegriddedDataset<'a> {
+    source: &'a dyn DatasetBackend,
+    metadata: FileMetadata,
+    target: TargetGrid,
+    regridded: HashMap<String, Regridded>,
+    remaps: Vec<Remap>,
+    /// Last 2-D slice regridded, which reads of neighbouring rows reuse
+    last_slice: Mutex<Option<RegriddedSlice>>,
+}
+
+/// Values of a regridded variable at some indices of its other dimensions
+struct RegriddedSlice {
+    var_name: String,
+    others: Vec<usize>,
+    values: Vec<f64>,
+}
+
+impl<'a> RegriddedDataset<'a> {
+    pub fn new(
+        source: &'a dyn DatasetBackend,
+        variables: &[String],
+        options: &RegridOptions,
+    ) -> Result<Self, NetCDFError> {
+        let mut source_metadata = source.metadata()?;
+        source_metadata.coordinates = Some(detect_coordinates(&source_metadata));
+        let target = TargetGrid::resolve(&options.target)?;
+
+        // Each variable with its grid; variables on the same grid share a remap
+        let mut regridded = HashMap::new();
+        let mut remaps = Vec::new();
+        let mut remap_keys: Vec<(usize, usize, String)> = Vec::new();
+        for name in variables {
+            if ["lat", "lon", "lat_bnds", "lon_bnds"].contains(&name.as_str()) {
+                return Err(NetCDFError::ExportError(format!(
+                    "'{}' is the name of an output coordinate",
+                    name
+                )));
+            }
+            let var = find_variable(&source_metadata, name)?;
+            if is_text_type(&var.data_type) {
+                return Err(NetCDFError::ExportError(format!("Variable '{}' is not numeric", name)));
+            }
+            let grid = resolve_grid(source, &source_metadata, &var)?;
+            let key = (grid.y_dim, grid.x_dim, var.dimensions.join(","));
+            let remap = match remap_keys.iter().position(|k| k == &key) {
+                Some(index) => index,
+                None => {
+                    remaps.push(Remap::new(source, &source_metadata, &grid, &target, options.method)?);
+                    remap_keys.push(key);
+                    remaps.len() - 1
+                }
+            };
+            regridded.insert(name.clone(), Regridded { source: var, grid, remap });
+        }
+
+        // Non-spatial dimensions in source order
+        let mut other_dims: Vec<String> = Vec::new();
+        for name in variables {
+            let Regridded { source, grid, .. } = &regridded[name];
+            for (d, dim) in source.dimensions.iter().enumerate() {
+                if d != grid.y_dim && d != grid.x_dim && !other_dims.contains(dim) {
+                    other_dims.push(dim.clone());
+                }
+            }
+        }
+        let mut dimensions: Vec<Dimension> =
+            source_metadata.dimensions.iter().filter(|d| other_dims.contains(&d.name)).cloned().collect();
+        for (name, size) in [("lat", target.lat.len()), ("lon", target.lon.len()), ("nv", 2)] {
+            dimensions.push(Dimension { name: name.to_string(), size, is_unlimited: false });
+        }
+
+        let double = |name: &str, dims: &[&str], attributes: HashMap<String, String>| Variable {
+            name: name.to_string(),
+            data_type: "Basic(Double)".to_string(),
+            dimensions: dims.iter().map(|d| d.to_string()).collect(),
+            shape: dims.iter().map(|d| dimensions.iter().find(|dim| &dim.name == d).map_or(0, |dim| dim.size)).collect(),
+            attributes,
+        };
+        let mut output_variables = Vec::new();
+        for (axis, units, standard_name) in [("lat", "degrees_north", "latitude"), ("lon", "degrees_east", "longitude")] {
+            let attributes = HashMap::from([
+                ("units".to_string(), units.to_string()),
+                ("standard_name".to_string(), standard_name.to_string()),
+                ("bounds".to_string(), format!("{}_bnds", axis)),
+            ]);
+            output_variables.push(double(axis, &[axis], attributes));
+            output_variables.push(double(&format!("{}_bnds", axis), &[axis, "nv"], HashMap::new()));
+        }
+        for var in &source_metadata.variables {
+            let is_coordinate = other_dims.iter().any(|dim| &var.name == dim && var.dimensions == [dim.clone()]);
+            if is_coordinate && !is_text_type(&var.data_type) {
+                output_variables.push(Variable { attributes: kept_attributes(&var.attributes), ..var.clone() });
+            }
+        }
+        let method = format!("{:?}", options.method).to_lowercase();
+        for name in variables {
+            let Regridded { source, grid, .. } = &regridded[name];
+            let mut dims: Vec<&str> = source
+                .dimensions
+                .iter()
+                .enumerate()
+                .filter(|&(d, _)| d != grid.y_dim && d != grid.x_dim)
+                .map(|(_, dim)| dim.as_str())
+                .collect();
+            dims.extend(["lat", "lon"]);
+            let mut attributes = kept_attributes(&source.attributes);
+            attributes.insert("regrid_method".to_string(), method.clone());
+            output_variables.push(double(name, &dims, attributes));
+        }
+
+        let metadata = FileMetadata {
+            file_path: source_metadata.file_path.clone(),
+            dimensions,
+            variables: output_variables,
+            global_attrs: source_metadata.global_attrs.clone(),
+            coordinates: None,
+        };
+        Ok(RegriddedDataset { source, metadata, target, regridded, remaps, last_slice: Mutex::new(None) })
+    }
+
+    /// The target grid's 2-D slice of `var_name` at the given indices of the other dimensions
+    fn regrid_slice(&self, var_name: &str, regridded: &Regridded, others: &[usize]) -> Result<Vec<f64>, NetCDFError> {
+        let mut last = self.last_slice.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(slice) = last.as_ref().filter(|s| s.var_name == var_name && s.others == others) {
+            return Ok(slice.values.clone());
+        }
+
+        let Regridded { source, grid, remap } = regridded;
+        let mut start = vec![0; source.dimensions.len()];
+        let mut count = source.shape.clone();
+        let other_positions = (0..source.dimensions.len()).filter(|&d| d != grid.y_dim && d != grid.x_dim);
+        for (d, &index) in other_positions.zip(others) {
+            start[d] = index;
+            count[d] = 1;
+        }
+        let values = self.remaps[*remap].apply(&self.source.read_masked(&source.name, &start, &count)?);
+        *last = Some(RegriddedSlice { var_name: var_name.to_string(), others: others.to_vec(), values: values.clone() });
+        Ok(values)
+    }
+}
+
+/// Attributes of a source variable that still describe the regridded values
+fn kept_attributes(attributes: &HashMap<String, String>) -> HashMap<String, String> {
+    attributes
+        .iter()
+        .filter(|(key, _)| !DROPPED_ATTRIBUTES.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+impl DatasetBackend for RegriddedDataset<'_> {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn read_native(&self, var_name: &str, start: &[usize], count: &[usize]) -> Result<NativeValues, NetCDFError> {
+        let var = find_variable(&self.metadata, var_name)?;
+        match var.data_type.as_str() {
+            "Basic(Double)" => Ok(NativeValues::F64(self.read_f64(var_name, start, count)?)),
+            _ => self.source.read_native(var_name, start, count),
+        }
+    }
+
+    fn read_text(&self, var_name: &str, _: &[usize], _: &[usize]) -> Result<Vec<String>, NetCDFError> {
+        // Every variable of a regridded dataset is numeric
+        find_variable(&self.metadata, var_name)?;
+        Err(NetCDFError::ConversionError(format!("'{}' is numeric", var_name)))
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        // Masked target cells are NaN
+        match self.regridded.contains_key(var_name) {
+            true => None,
+            false => self.source.fill_value(var_name),
+        }
+    }
+
+    fn attributes(&self, var_name: Option<&str>) -> Result<Vec<(String, AttributeValue)>, NetCDFError> {
+        let Some(name) = var_name else {
+            return self.source.attributes(None);
+        };
+        let var = find_variable(&self.metadata, name)?;
+        let mut attributes = text_attributes(&var.attributes, Some(&var.data_type));
+        if self.regridded.contains_key(name) {
+            attributes.push(("_FillValue".to_string(), AttributeValue::Double(f64::NAN)));
+        }
+        Ok(attributes)
+    }
+
+    fn read_f64(&self, var_name: &str, start: &[usize], count: &[usize]) -> Result<Vec<f64>, NetCDFError> {
+        let var = find_variable(&self.metadata, var_name)?;
+        if start.len() != var.shape.len() || count.len() != var.shape.len() {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Variable has {} dimensions, but got start={} and count={}",
+                var.shape.len(),
+                start.len(),
+                count.len()
+            )));
+        }
+        if start.iter().zip(count).zip(&var.shape).any(|((&s, &c), &n)| s + c > n) {
+            return Err(NetCDFError::InvalidSubsetRequest(format!("Hyperslab outside '{}'", var_name)));
+        }
+        let range = |d: usize| start[d]..start[d] + count[d];
+
+        let axis = match var_name {
+            "lat" | "lat_bnds" => Some(&self.target.lat),
+            "lon" | "lon_bnds" => Some(&self.target.lon),
+            _ => None,
+        };
+        if let Some(cells) = axis {
+            return Ok(match var_name.ends_with("_bnds") {
+                false => cells.centres[range(0)].to_vec(),
+                true => cells.bounds[range(0)].iter().flat_map(|&(a, b)| [a, b][range(1)].to_vec()).collect(),
+            });
+        }
+
+        let Some(regridded) = self.regridded.get(var_name) else {
+            return self.source.read_f64(var_name, start, count);
+        };
+        let n = var.shape.len();
+        let nx = self.target.lon.len();
+        let mut values = Vec::with_capacity(count.iter().product());
+        if count.contains(&0) {
+            return Ok(values);
+        }
+        let other_count = &count[..n - 2];
+        let mut index = vec![0; n - 2];
+        loop {
+            let others: Vec<usize> = index.iter().zip(start).map(|(i, s)| i + s).collect();
+            let slice = self.regrid_slice(var_name, regridded, &others)?;
+            for j in range(n - 2) {
+                values.extend_from_slice(&slice[j * nx..(j + 1) * nx][range(n - 1)]);
+            }
+            if !next_index(&mut index, other_count) {
+                break;
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CoordinateInfo;
+
+    fn source_grid() -> LatLonGrid {
+        // 2 x 4 cells of 10 degrees, latitude descending, lon 0..40
+        LatLonGrid {
+            y_dim: 0,
+            x_dim: 1,
+            ny: 2,
+            nx: 4,
+            lat: vec![5.0, -5.0],
+            lon: vec![5.0, 15.0, 25.0, 35.0],
+            curvilinear: false,
+        }
+    }
+
+    fn empty_metadata() -> FileMetadata {
+        FileMetadata {
+            file_path: String::new(),
+            dimensions: Vec::new(),
+            variables: Vec::new(),
+            global_attrs: HashMap::new(),
+            coordinates: Some(CoordinateInfo { time_var: None, lat_var: None, lon_var: None, time_units: None }),
+        }
+    }
+
+    struct NoData;
+
+    impl DatasetBackend for NoData {
+        fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+            Ok(empty_metadata())
+        }
+        fn read_native(&self, name: &str, _: &[usize], _: &[usize]) -> Result<crate::netcdf::data_access::NativeValues, NetCDFError> {
+            Err(NetCDFError::VariableNotFound(name.to_string()))
+        }
+        fn read_text(&self, name: &str, _: &[usize], _: &[usize]) -> Result<Vec<String>, NetCDFError> {
+            Err(NetCDFError::VariableNotFound(name.to_string()))
+        }
+        fn fill_value(&self, _: &str) -> Option<f64> {
+            None
+        }
+    }
+
+    fn regrid(method: RegridMethod, target: &TargetGrid, data: &[f64]) -> Vec<f64> {
+        let remap = Remap::new(&NoData, &empty_metadata(), &source_grid(), target, method).unwrap();
+        remap.apply(data)
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_methods_on_rectilinear_grid() {
+        // Rows north to south: 1 2 3 4 / 5 6 7 8
+        let data = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        let extent = GeoExtent { south: -10.0, west: 0.0, north: 10.0, east: 40.0 };
+
+        // Conservative onto 20-degree cells averages blocks of four
+        let coarse = TargetGrid::regular(&extent, 20.0, 20.0).unwrap();
+        assert_close(&regrid(RegridMethod::Conservative, &coarse, &data), &[3.5, 5.5]);
+
+        // The same cells in -180..180 longitudes: 0..40 is unaffected, but
+        // a target spanning 350..10 only overlaps the first column
+        let shifted = TargetGrid::regular(&GeoExtent { south: -10.0, west: -10.0, north: 10.0, east: 10.0 }, 20.0, 20.0).unwrap();
+        assert_close(&regrid(RegridMethod::Conservative, &shifted, &data), &[3.0]);
+
+        // Bilinear halfway between the first two columns of the southern row
+        let point = TargetGrid {
+            lat: AxisCells::from_centres(vec![-5.0]),
+            lon: AxisCells::from_centres(vec![10.0]),
+        };
+        assert_eq!(regrid(RegridMethod::Bilinear, &point, &data), vec![5.5]);
+        assert_eq!(regrid(RegridMethod::Nearest, &point, &data), vec![6.0]);
+
+        // Masked cells are left out unless they carry most of the weight
+        let mut masked = data;
+        masked[4] = f64::NAN;
+        assert_eq!(regrid(RegridMethod::Bilinear, &point, &masked), vec![6.0]);
+        assert_close(&regrid(RegridMethod::Conservative, &coarse, &masked)[..1], &[3.0]);
+        masked[5] = f64::NAN;
+        assert!(regrid(RegridMethod::Bilinear, &point, &masked)[0].is_nan());
+
+        // Outside the source grid
+        let outside = TargetGrid::regular(&GeoExtent { south: 40.0, west: 100.0, north: 50.0, east: 110.0 }, 10.0, 10.0).unwrap();
+        assert!(regrid(RegridMethod::Nearest, &outside, &data)[0].is_nan());
+    }
+
+    #[test]
+    fn test_global_longitudes_wrap() {
+        let lon = SortedAxis::new(&AxisCells::from_centres(vec![0.0, 90.0, 180.0, 270.0]), true);
+        assert!(lon.periodic);
+        // Between 270 and 360 (= 0)
+        let weights = lon.linear(-45.0);
+        assert_eq!(weights, vec![(3, 0.5), (0, 0.5)]);
+        assert_eq!(lon.nearest(-170.0), vec![(2, 1.0)]);
+
+        // Descending and antimeridian-crossing longitudes sort into one turn
+        let descending = SortedAxis::new(&AxisCells::from_centres(vec![35.0, 25.0, 15.0, 5.0]), true);
+        assert_eq!(descending.centres, vec![5.0, 15.0, 25.0, 35.0]);
+        assert_eq!(descending.index, vec![3, 2, 1, 0]);
+        let crossing = SortedAxis::new(&AxisCells::from_centres(vec![170.0, 180.0, -170.0, -160.0]), true);
+        assert_eq!(crossing.centres, vec![170.0, 180.0, 190.0, 200.0]);
+        assert_eq!(crossing.nearest(-165.0), vec![(2, 1.0)]);
+    }
+
+    #[test]
+    fn test_regular_grid_size_is_capped() {
+        let extent = GeoExtent { south: -90.0, west: -180.0, north: 90.0, east: 180.0 };
+        assert!(TargetGrid::regular(&extent, 0.1, 0.1).is_ok());
+        for (lat_step, lon_step) in [(1e-9, 1.0), (1.0, 1e-9), (0.01, 0.01)] {
+            let error = TargetGrid::regular(&extent, lat_step, lon_step).unwrap_err();
+            assert!(error.to_string().contains("limit"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_methods_on_curvilinear_grid() {
+        // The rectilinear source grid spelled out as 2-D coordinates gives
+        // the same bilinear and conservative results
+        let flat = source_grid();
+        let grid = LatLonGrid {
+            lat: (0..flat.ny * flat.nx).map(|k| flat.lat[k / flat.nx]).collect(),
+            lon: (0..flat.ny * flat.nx).map(|k| flat.lon[k % flat.nx]).collect(),
+            curvilinear: true,
+            ..flat
+        };
+        let data = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        let remap = |method, target: &TargetGrid| {
+            Remap::new(&NoData, &empty_metadata(), &grid, target, method).unwrap().apply(&data)
+        };
+
+        let coarse = TargetGrid::regular(&GeoExtent { south: -10.0, west: 0.0, north: 10.0, east: 40.0 }, 20.0, 20.0).unwrap();
+        assert_close(&remap(RegridMethod::Conservative, &coarse), &[3.5, 5.5]);
+        let shifted = TargetGrid::regular(&GeoExtent { south: -10.0, west: -10.0, north: 10.0, east: 10.0 }, 20.0, 20.0).unwrap();
+        assert_close(&remap(RegridMethod::Conservative, &shifted), &[3.0]);
+
+        let point = TargetGrid {
+            lat: AxisCells::from_centres(vec![-5.0, 0.0]),
+            lon: AxisCells::from_centres(vec![10.0]),
+        };
+        assert_close(&remap(RegridMethod::Bilinear, &point), &[5.5, 3.5]);
+    }
+
+    #[test]
+    fn test_nearest_on_curvilinear_grid() {
+        let grid = LatLonGrid {
+            y_dim: 0,
+            x_dim: 1,
+            ny: 2,
+            nx: 2,
+            lat: vec![0.0, 1.0, 10.0, 11.0],
+            lon: vec![0.0, 10.0, 1.0, 11.0],
+            curvilinear: true,
+        };
+        let target = TargetGrid {
+            lat: AxisCells::from_centres(vec![10.5]),
+            lon: AxisCells::from_centres(vec![1.5, 60.0]),
+        };
+        let remap = Remap::points_curvilinear(&grid, &target, RegridMethod::Nearest);
+        let values = remap.apply(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(values[0], 3.0);
+        assert!(values[1].is_nan());
+    }
+
+    #[test]
+    fn test_regridded_dataset_reads_like_the_output() {
+        use crate::netcdf::test_util::write_array;
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("grid.zarr");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".zgroup"), r#"{"zarr_format": 2}"#).unwrap();
+        let f64s = |values: &[f64]| values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        write_array(&root, "time", &["time"], &[2], "<f8", r#""units": "days since 2000-01-01""#, f64s(&[0.0, 1.0]));
+        write_array(&root, "lat", &["lat"], &[2], "<f8", r#""units": "degrees_north""#, f64s(&[-5.0, 5.0]));
+        write_array(&root, "lon", &["lon"], &[4], "<f8", r#""units": "degrees_east""#, f64s(&[5.0, 15.0, 25.0, 35.0]));
+        let tas: Vec<f64> = (1..=8).chain(11..=18).map(f64::from).collect();
+        write_array(&root, "tas", &["time", "lat", "lon"], &[2, 2, 4], "<f8", r#""units": "K""#, f64s(&tas));
+
+        let source = open_dataset(root.to_str().unwrap()).unwrap();
+        let extent = GeoExtent { south: -10.0, west: 0.0, north: 10.0, east: 40.0 };
+        let options = RegridOptions {
+            target: RegridTarget::Regular { extent, lat_step: 20.0, lon_step: 20.0 },
+            method: RegridMethod::Conservative,
+        };
+        let dataset = RegriddedDataset::new(source.as_ref(), &["tas".to_string()], &options).unwrap();
+        let metadata = dataset.metadata().unwrap();
+        let names: Vec<&str> = metadata.variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["lat", "lat_bnds", "lon", "lon_bnds", "time", "tas"]);
+        let var = find_variable(&metadata, "tas").unwrap();
+        assert_eq!((var.dimensions.clone(), var.shape.clone()), (vec!["time".into(), "lat".into(), "lon".into()], vec![2, 1, 2]));
+
+        let values = dataset.read_f64("tas", &[0, 0, 0], &[2, 1, 2]).unwrap();
+        for (value, expected) in values.iter().zip([3.5, 5.5, 13.5, 15.5]) {
+            assert!((value - expected).abs() < 1e-9, "{:?}", values);
+        }
+        assert_eq!(dataset.read_f64("tas", &[1, 0, 1], &[1, 1, 1]).unwrap().len(), 1);
+        assert_eq!(dataset.read_f64("lon_bnds", &[0, 0], &[2, 2]).unwrap(), vec![0.0, 20.0, 20.0, 40.0]);
+        assert_eq!(dataset.read_f64("time", &[0], &[2]).unwrap(), vec![0.0, 1.0]);
+
+        let attributes = dataset.attributes(Some("tas")).unwrap();
+        let attribute = |name: &str| attributes.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone());
+        assert!(matches!(attribute("_FillValue"), Some(AttributeValue::Double(v)) if v.is_nan()));
+        assert!(matches!(attribute("regrid_method"), Some(AttributeValue::Str(m)) if m == "conservative"));
+    }
+}
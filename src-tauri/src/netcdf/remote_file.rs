@@ -0,0 +1,366 @@
+use crate::errors::NetCDFError;
+use crate::models::FileMetadata;
+use super::backend::{DatasetBackend, NetCDFBackend};
+use super::data_access::NativeValues;
+use super::object_store::{ObjectLocation, RangeReader};
+use super::zarr::ZarrStore;
+use netcdf::AttributeValue;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Open a dataset in object storage (`s3://bucket/key`) or behind an HTTP
+/// server that honours range requests (`https://host/file.nc#mode=bytes`).
+///
+/// Keys ending in `.zarr`, and keys that are not objects but hold Zarr
+/// metadata, are read as Zarr stores; anything else as a NetCDF/HDF5 file.
+pub fn open_object(url: &str) -> Result<Box<dyn DatasetBackend>, NetCDFError> {
+    let open_error = |e: std::io::Error| NetCDFError::FileOpenError(format!("Failed to open {}: {}", url, e));
+    let location = ObjectLocation::parse(url).map_err(open_error)?;
+
+    if location.name().ends_with(".zarr") {
+        return Ok(Box::new(ZarrStore::open_object(location, url)?));
+    }
+    match location.size().map_err(open_error)? {
+        Some(_) => Ok(Box::new(RemoteFileBackend::open(location, url)?)),
+        None if ZarrStore::is_object_store(&location) => Ok(Box::new(ZarrStore::open_object(location, url)?)),
+        None => Err(NetCDFError::FileOpenError(format!("File not found: {}", url))),
+    }
+}
+
+/// A NetCDF-3/4 or HDF5 object read through the NetCDF library.
+///
+/// The library fetches the file with HTTP range requests (`#mode=bytes`)
+/// from a loopback proxy, which answers them from a `RangeReader`. This
+/// keeps credentials, request signing and the block cache on our side and
+/// means only the parts of the file the library touches are downloaded.
+pub struct RemoteFileBackend {
+    url: String,
+    inner: NetCDFBackend,
+    /// Unregisters the object from the proxy when the backend is dropped
+    _registration: ProxyRegistration,
+}
+
+impl RemoteFileBackend {
+    pub fn open(location: ObjectLocation, url: &str) -> Result<Self, NetCDFError> {
+        let reader = RangeReader::open(location)
+            .map_err(|e| NetCDFError::FileOpenError(format!("Failed to open {}: {}", url, e)))?;
+        let registration = RangeProxy::global()?.register(reader)?;
+        let inner = NetCDFBackend::open(&registration.url)
+            .map_err(|e| NetCDFError::FileOpenError(format!("Failed to open {}: {}", url, e)))?;
+
+        Ok(RemoteFileBackend {
+            url: url.to_string(),
+            inner,
+            _registration: registration,
+        })
+    }
+}
+
+impl DatasetBackend for RemoteFileBackend {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        let mut metadata = self.inner.metadata()?;
+        metadata.file_path = self.url.clone();
+        Ok(metadata)
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        self.inner.read_native(var_name, start, count)
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        self.inner.read_text(var_name, start, count)
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        self.inner.fill_value(var_name)
+    }
+
+    fn attributes(&self, var_name: Option<&str>) -> Result<Vec<(String, AttributeValue)>, NetCDFError> {
+        self.inner.attributes(var_name)
+    }
+
+    fn read_f64(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<f64>, NetCDFError> {
+        self.inner.read_f64(var_name, start, count)
+    }
+}
+
+/// Registered objects by their token, with the name they are served under
+type Readers = Arc<Mutex<HashMap<String, (String, Arc<RangeReader>)>>>;
+
+/// HTTP server on the loopback interface serving registered objects at
+/// `/object/<token>/<name>`, with HEAD and single-range GET support.
+///
+/// Objects may have been fetched with the user's credentials and the port is
+/// open to every local process, so each object gets an unguessable random
+/// token and every other path is refused.
+struct RangeProxy {
+    port: u16,
+    readers: Readers,
+}
+
+/// An object served by the proxy while this value is alive
+struct ProxyRegistration {
+    token: String,
+    /// URL handed to the NetCDF library
+    url: String,
+    readers: Readers,
+}
+
+impl Drop for ProxyRegistration {
+    fn drop(&mut self) {
+        self.readers.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.token);
+    }
+}
+
+impl RangeProxy {
+    /// The proxy shared by all remote files, started on first use
+    fn global() -> Result<&'static RangeProxy, NetCDFError> {
+        static PROXY: OnceLock<Result<RangeProxy, String>> = OnceLock::new();
+        PROXY
+            .get_or_init(RangeProxy::start)
+            .as_ref()
+            .map_err(|e| NetCDFError::FileOpenError(format!("Cannot start range proxy: {}", e)))
+    }
+
+    fn start() -> Result<RangeProxy, String> {
+        let server = tiny_http::Server::http("127.0.0.1:0").map_err(|e| e.to_string())?;
+        let port = server
+            .server_addr()
+            .to_ip()
+            .map(|a| a.port())
+            .ok_or("proxy is not listening on an IP address")?;
+        let readers: Readers = Arc::new(Mutex::new(HashMap::new()));
+
+        let shared = Arc::clone(&readers);
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let readers = Arc::clone(&shared);
+                std::thread::spawn(move || serve_range(request, &readers));
+            }
+        });
+
+        Ok(RangeProxy { port, readers })
+    }
+
+    fn register(&self, reader: RangeReader) -> Result<ProxyRegistration, NetCDFError> {
+        let token = random_token()?;
+        // The name only lets the library see the file's extension; keep it URL-safe
+        let name: String = match reader.location().name() {
+            "" => "data".to_string(),
+            name => name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' })
+                .collect(),
+        };
+        let url = format!("http://127.0.0.1:{}/object/{}/{}#mode=bytes", self.port, token, name);
+        self.readers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(token.clone(), (name, Arc::new(reader)));
+        Ok(ProxyRegistration {
+            token,
+            url,
+            readers: Arc::clone(&self.readers),
+        })
+    }
+}
+
+/// 256 random bits from the operating system, as hex
+fn random_token() -> Result<String, NetCDFError> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| NetCDFError::FileOpenError(format!("Cannot create a range proxy token: {}", e)))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Answer one proxy request
+fn serve_range(request: tiny_http::Request, readers: &Mutex<HashMap<String, (String, Arc<RangeReader>)>>) {
+    let reader = request
+        .url()
+        .strip_prefix("/object/")
+        .and_then(|rest| rest.split_once('/'))
+        .and_then(|(token, name)| {
+            let readers = readers.lock().unwrap_or_else(|e| e.into_inner());
+            readers
+                .get(token)
+                .filter(|(served_name, _)| served_name == name)
+                .map(|(_, reader)| Arc::clone(reader))
+        });
+    let Some(reader) = reader else {
+        let _ = request.respond(tiny_http::Response::empty(404));
+        return;
+    };
+
+    let size = reader.size();
+    let accept_ranges = tiny_http::Header::from_bytes("Accept-Ranges", "bytes").expect("valid header");
+    if request.method() == &tiny_http::Method::Head {
+        let response = tiny_http::Response::new(
+            tiny_http::StatusCode(200),
+            vec![accept_ranges],
+            std::io::empty(),
+            Some(size as usize),
+            None,
+        );
+        let _ = request.respond(response);
+        return;
+    }
+
+    let range = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Range"))
+        .and_then(|h| parse_range(h.value.as_str(), size));
+    let Some((start, end)) = range else {
+        // The whole object is passed through as it arrives rather than
+        // gathered in memory, and left out of the block cache
+        match reader.location().get_stream() {
+            Ok((stream, length)) => {
+                let length = length.unwrap_or(size) as usize;
+                let response =
+                    tiny_http::Response::new(tiny_http::StatusCode(200), vec![accept_ranges], stream, Some(length), None);
+                let _ = request.respond(response);
+            }
+            Err(e) => {
+                let _ = request.respond(tiny_http::Response::from_string(e.to_string()).with_status_code(502));
+            }
+        }
+        return;
+    };
+
+    let response = match reader.read_at(start, end - start) {
+        Ok(data) => {
+            let content_range = format!("bytes {}-{}/{}", start, end.saturating_sub(1), size);
+            tiny_http::Response::from_data(data)
+                .with_status_code(206)
+                .with_header(accept_ranges)
+                .with_header(tiny_http::Header::from_bytes("Content-Range", content_range).expect("valid header"))
+        }
+        Err(e) => tiny_http::Response::from_string(e.to_string()).with_status_code(502),
+    };
+    let _ = request.respond(response);
+}
+
+/// Half-open byte range of a `Range: bytes=a-b`, `bytes=a-` or `bytes=-n` header
+fn parse_range(header: &str, size: u64) -> Option<(u64, u64)> {
+    let (first, last) = header.trim().strip_prefix("bytes=")?.split(',').next()?.split_once('-')?;
+    let (start, end) = match (first.trim(), last.trim()) {
+        ("", n) => (size.saturating_sub(n.parse().ok()?), size),
+        (a, "") => (a.parse().ok()?, size),
+        (a, b) => (a.parse().ok()?, b.parse::<u64>().ok()?.saturating_add(1).min(size)),
+    };
+    (start < end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netcdf::data_access::find_variable;
+    use crate::netcdf::object_store::tests::fake_s3;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-9", 100), Some((0, 10)));
+        assert_eq!(parse_range("bytes=90-", 100), Some((90, 100)));
+        assert_eq!(parse_range("bytes=-5", 100), Some((95, 100)));
+        assert_eq!(parse_range("bytes=50-500", 100), Some((50, 100)));
+        assert_eq!(parse_range("bytes=200-", 100), None);
+    }
+
+    #[test]
+    fn test_proxy_serves_ranges() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(3000).collect();
+        let (settings, _) = fake_s3("bucket", HashMap::from([("data/file.nc".to_string(), data.clone())]));
+        let reader = RangeReader::open(ObjectLocation::s3("bucket", "data/file.nc", settings)).unwrap();
+        let registration = RangeProxy::global().unwrap().register(reader).unwrap();
+        let url = registration.url.trim_end_matches("#mode=bytes").to_string();
+        assert!(url.ends_with("/file.nc"));
+
+        // Only the exact token and name are served
+        let agent = ureq::Agent::new_with_defaults();
+        let port = RangeProxy::global().unwrap().port;
+        for path in ["/object/1/file.nc", "/object/0/file.nc", "/"] {
+            assert!(agent.get(&format!("http://127.0.0.1:{}{}", port, path)).call().is_err(), "{}", path);
+        }
+        assert!(agent.get(&url.replace("/file.nc", "/other.nc")).call().is_err());
+        let token = url.rsplit('/').nth(1).unwrap();
+        assert_eq!(token.len(), 64);
+        assert!(agent.get(&url.replace(token, &"0".repeat(64))).call().is_err());
+
+        let head = agent.head(&url).call().unwrap();
+        assert_eq!(head.headers()["content-length"], "3000");
+
+        let mut response = agent.get(&url).header("Range", "bytes=1000-1099").call().unwrap();
+        assert_eq!(response.status().as_u16(), 206);
+        assert_eq!(response.headers()["content-range"], "bytes 1000-1099/3000");
+        assert_eq!(response.body_mut().read_to_vec().unwrap(), data[1000..1100]);
+
+        let mut whole = agent.get(&url).call().unwrap();
+        assert_eq!(whole.status().as_u16(), 200);
+        assert_eq!(whole.body_mut().read_to_vec().unwrap(), data);
+
+        drop(registration);
+        let missing = agent.get(&url).call();
+        assert!(missing.is_err());
+    }
+
+    /// A classic (CDF-1) NetCDF file holding one double variable `x(x)`
+    fn classic_file(values: &[f64]) -> Vec<u8> {
+        let word = |bytes: &mut Vec<u8>, n: usize| bytes.extend((n as u32).to_be_bytes());
+        let name = |bytes: &mut Vec<u8>, name: &str| {
+            bytes.extend((name.len() as u32).to_be_bytes());
+            bytes.extend(name.as_bytes());
+            bytes.resize(bytes.len().div_ceil(4) * 4, 0);
+        };
+        let mut bytes = b"CDF\x01".to_vec();
+        word(&mut bytes, 0); // records
+        word(&mut bytes, 10); // NC_DIMENSION
+        word(&mut bytes, 1);
+        name(&mut bytes, "x");
+        word(&mut bytes, values.len());
+        bytes.extend([0; 8]); // no global attributes
+        word(&mut bytes, 11); // NC_VARIABLE
+        word(&mut bytes, 1);
+        name(&mut bytes, "x");
+        word(&mut bytes, 1); // one dimension, with id 0
+        word(&mut bytes, 0);
+        bytes.extend([0; 8]); // no attributes
+        word(&mut bytes, 6); // NC_DOUBLE
+        word(&mut bytes, values.len() * 8);
+        let begin = bytes.len() + 4;
+        word(&mut bytes, begin);
+        for value in values {
+            bytes.extend(value.to_be_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_netcdf_library_reads_through_proxy() {
+        let values = [1.5, -2.0, 4.25];
+        let (settings, gets) = fake_s3("bucket", HashMap::from([("data/file.nc".to_string(), classic_file(&values))]));
+        let url = "s3://bucket/data/file.nc";
+        let location = ObjectLocation::s3("bucket", "data/file.nc", settings);
+        let backend = RemoteFileBackend::open(location, url).unwrap();
+
+        let metadata = backend.metadata().unwrap();
+        assert_eq!(metadata.file_path, url);
+        assert_eq!(find_variable(&metadata, "x").unwrap().shape, vec![3]);
+        assert_eq!(backend.read_f64("x", &[0], &[3]).unwrap(), values);
+        assert!(gets.load(std::sync::atomic::Ordering::Relaxed) > 0);
+    }
+}
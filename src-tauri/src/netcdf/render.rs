@@ -0,0 +1,215 @@
+use crate::errors::NetCDFError;
+use crate::models::{
+    FileMetadata, GeoExtent, RegridMethod, RegridOptions, RegridTarget, RenderOptions, RenderedImage, Variable,
+};
+use super::backend::{open_dataset, DatasetBackend};
+use super::colormap::Colormap;
+use super::data_access::find_variable;
+use super::grid::{resolve_grid, LatLonGrid};
+use super::regrid::regrid_slice;
+use base64::Engine;
+use std::collections::HashMap;
+
+/// Render a 2-D lat/lon slice of a variable as a colormapped PNG.
+///
+/// `indices` selects the position along every non-spatial dimension
+/// (keyed by dimension name, defaulting to 0). With `regrid`, the slice is
+/// first regridded onto another grid. Curvilinear slices are always
+/// regridded, without `regrid` onto a regular grid of the same size.
+pub fn render_slice(
+    path: &str,
+    var_name: &str,
+    indices: &HashMap<String, usize>,
+    options: &RenderOptions,
+    regrid: Option<&RegridOptions>,
+) -> Result<RenderedImage, NetCDFError> {
+    let colormap = Colormap::from_name(&options.colormap).ok_or_else(|| {
+        NetCDFError::RenderError(format!("Unknown colormap: {}", options.colormap))
+    })?;
+
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    let var_meta = find_variable(&metadata, var_name)?;
+
+    let (grid, data) = read_slice(dataset.as_ref(), &metadata, &var_meta, indices, regrid)?;
+
+    let (data_min, data_max) = finite_range(&data);
+    let vmin = options.vmin.unwrap_or(data_min);
+    let vmax = options.vmax.unwrap_or(data_max);
+    let nan_color = options.nan_color.unwrap_or([0, 0, 0, 0]);
+
+    let pixels = colorize(&grid, &data, &colormap, vmin, vmax, nan_color);
+    let png = encode_png(&pixels, grid.nx, grid.ny)?;
+
+    Ok(RenderedImage {
+        var_name: var_name.to_string(),
+        png_base64: base64::engine::general_purpose::STANDARD.encode(png),
+        width: grid.nx,
+        height: grid.ny,
+        extent: grid.extent(),
+        vmin,
+        vmax,
+        legend: colormap.hex_stops(11),
+    })
+}
+
+/// Grid and values of the slice to draw. A curvilinear grid drawn as it is
+/// would stretch its index space over the lat/lon box and put every cell in
+/// the wrong place, so it is regridded with [`native_regrid`] by default.
+fn read_slice(
+    dataset: &dyn DatasetBackend,
+    metadata: &FileMetadata,
+    var_meta: &Variable,
+    indices: &HashMap<String, usize>,
+    regrid: Option<&RegridOptions>,
+) -> Result<(LatLonGrid, Vec<f64>), NetCDFError> {
+    if let Some(regrid) = regrid {
+        return regrid_slice(dataset, metadata, var_meta, indices, regrid);
+    }
+    let grid = resolve_grid(dataset, metadata, var_meta)?;
+    if grid.curvilinear {
+        return regrid_slice(dataset, metadata, var_meta, indices, &native_regrid(&grid));
+    }
+    let (start, count) = grid.slice_extents(var_meta, indices)?;
+    let data = dataset.read_masked(&var_meta.name, &start, &count)?;
+    Ok((grid, data))
+}
+
+/// Nearest-neighbour regridding onto a regular grid over the lat/lon box of
+/// a curvilinear one, with as many rows and columns as it has
+fn native_regrid(grid: &LatLonGrid) -> RegridOptions {
+    let extent = grid.extent();
+    let extent = GeoExtent { south: extent.south.max(-90.0), north: extent.north.min(90.0), ..extent };
+    RegridOptions {
+        target: RegridTarget::Regular {
+            lat_step: (extent.north - extent.south) / grid.ny as f64,
+            lon_step: (extent.east - extent.west) / grid.nx as f64,
+            extent,
+        },
+        method: RegridMethod::Nearest,
+    }
+}
+
+/// Map a 2-D slice to RGBA pixels, north-up and west-left
+fn colorize(
+    grid: &LatLonGrid,
+    data: &[f64],
+    colormap: &Colormap,
+    vmin: f64,
+    vmax: f64,
+    nan_color: [u8; 4],
+) -> Vec<u8> {
+    let flip_rows = grid.lat_ascending();
+    let flip_cols = grid.lon_descending();
+    let span = if vmax > vmin { vmax - vmin } else { 1.0 };
+
+    let mut pixels = Vec::with_capacity(grid.nx * grid.ny * 4);
+    for row in 0..grid.ny {
+        let j = if flip_rows { grid.ny - 1 - row } else { row };
+        for col in 0..grid.nx {
+            let i = if flip_cols { grid.nx - 1 - col } else { col };
+            let value = data[grid.slice_index(j, i)];
+
+            if value.is_finite() {
+                let [r, g, b] = colormap.sample((value - vmin) / span);
+                pixels.extend_from_slice(&[r, g, b, 255]);
+            } else {
+                pixels.extend_from_slice(&nan_color);
+            }
+        }
+    }
+
+    pixels
+}
+
+/// Encode RGBA pixels as a PNG
+fn encode_png(pixels: &[u8], width: usize, height: usize) -> Result<Vec<u8>, NetCDFError> {
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| NetCDFError::RenderError(e.to_string()))?;
+        writer
+            .write_image_data(pixels)
+            .map_err(|e| NetCDFError::RenderError(e.to_string()))?;
+    }
+    Ok(buffer)
+}
+
+/// Minimum and maximum of the finite values, or (0, 1) if there are none
+fn finite_range(data: &[f64]) -> (f64, f64) {
+    let (lo, hi) = data
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+            (lo.min(v), hi.max(v))
+        });
+
+    if lo > hi {
+        (0.0, 1.0)
+    } else {
+        (lo, hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colorize_flips_ascending_latitude() {
+        let grid = LatLonGrid {
+            y_dim: 0,
+            x_dim: 1,
+            ny: 2,
+            nx: 2,
+            lat: vec![0.0, 10.0],
+            lon: vec![0.0, 10.0],
+            curvilinear: false,
+        };
+        let gray = Colormap::from_name("gray").unwrap();
+        let data = vec![0.0, f64::NAN, 1.0, 1.0];
+
+        let pixels = colorize(&grid, &data, &gray, 0.0, 1.0, [1, 2, 3, 4]);
+
+        // The northern row (lat = 10) comes first
+        assert_eq!(&pixels[0..8], &[255, 255, 255, 255, 255, 255, 255, 255]);
+        assert_eq!(&pixels[8..12], &[0, 0, 0, 255]);
+        assert_eq!(&pixels[12..16], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_curvilinear_slices_are_regridded() {
+        use crate::netcdf::test_util::write_array;
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("curvilinear.zarr");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".zgroup"), r#"{"zarr_format": 2}"#).unwrap();
+        let f64s = |values: &[f64]| values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        // A rectilinear grid spelled out as 2-D coordinates
+        let lat = [-5.0, -5.0, -5.0, -5.0, 5.0, 5.0, 5.0, 5.0];
+        let lon = [5.0, 15.0, 25.0, 35.0, 5.0, 15.0, 25.0, 35.0];
+        write_array(&root, "lat", &["y", "x"], &[2, 4], "<f8", r#""units": "degrees_north""#, f64s(&lat));
+        write_array(&root, "lon", &["y", "x"], &[2, 4], "<f8", r#""units": "degrees_east""#, f64s(&lon));
+        let tas: Vec<f64> = (1..=8).map(f64::from).collect();
+        write_array(&root, "tas", &["y", "x"], &[2, 4], "<f8", r#""coordinates": "lat lon""#, f64s(&tas));
+
+        let dataset = open_dataset(root.to_str().unwrap()).unwrap();
+        let metadata = dataset.metadata().unwrap();
+        let var_meta = find_variable(&metadata, "tas").unwrap();
+        let (grid, data) = read_slice(dataset.as_ref(), &metadata, &var_meta, &HashMap::new(), None).unwrap();
+        assert!(!grid.curvilinear);
+        assert_eq!((grid.ny, grid.nx), (2, 4));
+        assert_eq!(data, tas);
+    }
+
+    #[test]
+    fn test_finite_range_ignores_nan() {
+        assert_eq!(finite_range(&[f64::NAN, 2.0, -1.0]), (-1.0, 2.0));
+        assert_eq!(finite_range(&[f64::NAN]), (0.0, 1.0));
+    }
+}
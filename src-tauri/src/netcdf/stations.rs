@@ -0,0 +1,468 @@
+use crate::errors::NetCDFError;
+use crate::models::{
+    ExportSummary, PointMethod, Station, StationExportRequest, StationExtraction, StationMatch, StationRecord,
+    StationRequest, Variable,
+};
+use super::backend::{open_dataset, DatasetBackend};
+use super::csv_export::csv_error;
+use super::data_access::{find_variable, is_text_type};
+use super::grid::{resolve_grid, LatLonGrid, EARTH_RADIUS_KM};
+use super::regrid::{curvilinear_weights, AxisCells, CellWeights, SortedAxis};
+use super::timeseries::{nearest_cell, time_dimension, time_labels};
+use std::collections::{HashMap, HashSet};
+
+/// Extract the time series of a variable at each of a list of stations.
+///
+/// Values are taken from the nearest grid cell or interpolated bilinearly
+/// from the surrounding cells, on regular and curvilinear grids alike.
+/// Missing cells are left out of the interpolation; when none is valid and
+/// `search_radius_km` is set, the nearest valid cell within that distance
+/// is used instead.
+pub fn extract_stations(path: &str, request: &StationRequest) -> Result<StationExtraction, NetCDFError> {
+    let dataset = open_dataset(path)?;
+    let metadata = dataset.metadata()?;
+    let var = find_variable(&metadata, &request.var_name)?;
+    if is_text_type(&var.data_type) {
+        return Err(NetCDFError::ConversionError(format!(
+            "Variable '{}' is not numeric",
+            var.name
+        )));
+    }
+    if request.stations.is_empty() {
+        return Err(NetCDFError::InvalidSubsetRequest("No stations given".to_string()));
+    }
+    // Records refer to their station by id, so ids must be unique
+    let mut ids = HashSet::new();
+    for station in &request.stations {
+        if !(-90.0..=90.0).contains(&station.lat) || !station.lon.is_finite() {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Station '{}' has an invalid position ({}, {})",
+                station.id, station.lat, station.lon
+            )));
+        }
+        if !ids.insert(station.id.as_str()) {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Station id '{}' is used more than once",
+                station.id
+            )));
+        }
+    }
+
+    let grid = resolve_grid(dataset.as_ref(), &metadata, &var)?;
+    let time = time_dimension(&metadata, &var);
+    let times = match time {
+        Some((time_var, _)) => time_labels(dataset.as_ref(), time_var)?,
+        None => vec![String::new()],
+    };
+
+    let mut reader = CellReader::new(dataset.as_ref(), &var, &grid, time.map(|(_, d)| d), &request.indices)?;
+    let weigher = Weigher::new(&grid, request.method);
+
+    let mut stations = Vec::with_capacity(request.stations.len());
+    let mut records = Vec::with_capacity(request.stations.len() * times.len());
+    for station in &request.stations {
+        let (j, i) = nearest_cell(&grid, station.lat, station.lon)
+            .ok_or_else(|| NetCDFError::InvalidSubsetRequest("Grid has no valid coordinates".to_string()))?;
+        let (grid_lat, grid_lon) = (grid.lat_at(j, i), grid.lon_at(j, i));
+        stations.push(StationMatch {
+            id: station.id.clone(),
+            lat: station.lat,
+            lon: station.lon,
+            elevation: station.elevation,
+            grid_lat,
+            grid_lon,
+            distance_km: haversine_km(station.lat, station.lon, grid_lat, grid_lon),
+        });
+
+        let weights = weigher.weights(station, (j, i));
+        let mut values = vec![(0.0, 0.0); times.len()];
+        for &(cell, weight) in &weights {
+            for (sum, value) in values.iter_mut().zip(reader.series(cell)?) {
+                if value.is_finite() {
+                    sum.0 += value * weight;
+                    sum.1 += weight;
+                }
+            }
+        }
+
+        let mut neighbours = None;
+        for (t, &(sum, valid)) in values.iter().enumerate() {
+            let mut value = if valid > 0.0 { sum / valid } else { f64::NAN };
+            let mut from_neighbour = false;
+            if value.is_nan() {
+                if let Some(radius) = request.search_radius_km {
+                    let candidates = neighbours.get_or_insert_with(|| cells_within(&grid, station, radius));
+                    for &cell in candidates.iter() {
+                        let candidate = reader.series(cell)?[t];
+                        if candidate.is_finite() {
+                            value = candidate;
+                            from_neighbour = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            records.push(StationRecord {
+                station_id: station.id.clone(),
+                time: times[t].clone(),
+                value,
+                from_neighbour,
+            });
+        }
+    }
+
+    Ok(StationExtraction {
+        var_name: var.name.clone(),
+        method: request.method,
+        stations,
+        records,
+    })
+}
+
+/// Write station time series to a CSV table with one row per station and time
+pub fn export_stations(path: &str, request: &StationExportRequest) -> Result<ExportSummary, NetCDFError> {
+    let extraction = extract_stations(path, &request.extraction)?;
+    let stations: HashMap<&str, &StationMatch> = extraction.stations.iter().map(|s| (s.id.as_str(), s)).collect();
+
+    let mut writer = csv::Writer::from_path(&request.output_path).map_err(csv_error)?;
+    writer
+        .write_record(["station_id", "lat", "lon", "elevation", "time", &extraction.var_name, "from_neighbour"])
+        .map_err(csv_error)?;
+    let optional = |value: Option<f64>| value.filter(|v| !v.is_nan()).map(|v| v.to_string()).unwrap_or_default();
+    for record in &extraction.records {
+        let station = stations[record.station_id.as_str()];
+        writer
+            .write_record([
+                record.station_id.clone(),
+                station.lat.to_string(),
+                station.lon.to_string(),
+                optional(station.elevation),
+                record.time.clone(),
+                optional(Some(record.value)),
+                record.from_neighbour.to_string(),
+            ])
+            .map_err(csv_error)?;
+    }
+    writer.flush()?;
+
+    Ok(ExportSummary {
+        output_path: request.output_path.clone(),
+        variables: vec![extraction.var_name],
+        skipped: vec![],
+        records: extraction.records.len(),
+        warnings: vec![],
+    })
+}
+
+/// Read a station list from a CSV file
+pub fn read_stations(path: &str) -> Result<Vec<Station>, NetCDFError> {
+    parse_stations(&std::fs::read_to_string(path)?)
+}
+
+/// Parse a station list from CSV text.
+///
+/// Columns are taken from a header row when there is one (recognising
+/// `id`/`station`/`name`, `lat`/`latitude`, `lon`/`longitude`/`lng` and
+/// `elevation`/`elev`/`alt`), else they are `id, lat, lon[, elevation]`.
+/// Comma, semicolon and tab delimiters are accepted; `#` starts a comment.
+pub fn parse_stations(text: &str) -> Result<Vec<Station>, NetCDFError> {
+    let first_line = text.lines().find(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#')).unwrap_or("");
+    let delimiter = if first_line.contains(',') {
+        b','
+    } else if first_line.contains(';') {
+        b';'
+    } else {
+        b'\t'
+    };
+    let rows = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .comment(Some(b'#'))
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes())
+        .into_records()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| NetCDFError::InvalidFormat(format!("Station list: {}", e)))?;
+
+    let mut columns = [Some(0), Some(1), Some(2), Some(3)];
+    let mut body = &rows[..];
+    if let Some(header) = rows.first().filter(|row| row.get(1).is_some_and(|f| f.parse::<f64>().is_err())) {
+        let find = |names: &[&str]| header.iter().position(|f| names.contains(&f.to_lowercase().as_str()));
+        columns = [
+            find(&["id", "station", "station_id", "name"]),
+            find(&["lat", "latitude"]),
+            find(&["lon", "longitude", "lng", "long"]),
+            find(&["elevation", "elev", "alt", "altitude", "height"]),
+        ];
+        if columns[1].is_none() || columns[2].is_none() {
+            return Err(NetCDFError::InvalidFormat(
+                "Station list header needs latitude and longitude columns".to_string(),
+            ));
+        }
+        body = &rows[1..];
+    }
+
+    let mut stations = Vec::with_capacity(body.len());
+    for (n, row) in body.iter().enumerate() {
+        let line = row.position().map(|p| p.line()).unwrap_or(n as u64 + 1);
+        let field = |column: Option<usize>| column.and_then(|c| row.get(c)).filter(|f| !f.is_empty());
+        let number = |column: Option<usize>, name: &str| -> Result<Option<f64>, NetCDFError> {
+            field(column)
+                .map(|f| {
+                    f.parse::<f64>().map_err(|_| {
+                        NetCDFError::InvalidFormat(format!("Station list line {}: invalid {} '{}'", line, name, f))
+                    })
+                })
+                .transpose()
+        };
+        let missing = |name: &str| NetCDFError::InvalidFormat(format!("Station list line {}: missing {}", line, name));
+        stations.push(Station {
+            id: field(columns[0]).map(str::to_string).unwrap_or_else(|| (n + 1).to_string()),
+            lat: number(columns[1], "latitude")?.ok_or_else(|| missing("latitude"))?,
+            lon: number(columns[2], "longitude")?.ok_or_else(|| missing("longitude"))?,
+            elevation: number(columns[3], "elevation")?,
+        });
+    }
+    Ok(stations)
+}
+
+/// Interpolation weights of grid cells around a station
+enum Weigher {
+    Nearest,
+    Rectilinear { lat: SortedAxis, lon: SortedAxis },
+    Curvilinear(LatLonGrid),
+}
+
+impl Weigher {
+    fn new(grid: &LatLonGrid, method: PointMethod) -> Weigher {
+        match method {
+            PointMethod::Nearest => Weigher::Nearest,
+            PointMethod::Bilinear if grid.curvilinear => Weigher::Curvilinear(grid.clone()),
+            PointMethod::Bilinear => Weigher::Rectilinear {
+                lat: SortedAxis::new(&AxisCells::from_centres(grid.lat.clone()), false),
+                lon: SortedAxis::new(&AxisCells::from_centres(grid.lon.clone()), true),
+            },
+        }
+    }
+
+    /// Cells and weights for `station`, whose nearest cell is `nearest`
+    fn weights(&self, station: &Station, nearest: (usize, usize)) -> CellWeights {
+        match self {
+            Weigher::Nearest => vec![(nearest, 1.0)],
+            Weigher::Rectilinear { lat, lon } => {
+                let rows = lat.linear(station.lat);
+                let columns = lon.linear(station.lon);
+                rows.iter()
+                    .flat_map(|&(j, wy)| columns.iter().map(move |&(i, wx)| ((j, i), wy * wx)))
+                    .filter(|&(_, weight)| weight > 0.0)
+                    .collect()
+            }
+            Weigher::Curvilinear(grid) => {
+                curvilinear_weights(grid, station.lat, station.lon, nearest).unwrap_or_else(|| vec![(nearest, 1.0)])
+            }
+        }
+    }
+}
+
+/// Grid cells within `radius_km` of a station, nearest first
+fn cells_within(grid: &LatLonGrid, station: &Station, radius_km: f64) -> Vec<(usize, usize)> {
+    let max_dlat = (radius_km / EARTH_RADIUS_KM).to_degrees();
+    let mut cells: Vec<((usize, usize), f64)> = (0..grid.ny)
+        .flat_map(|j| (0..grid.nx).map(move |i| (j, i)))
+        .filter(|&(j, i)| (grid.lat_at(j, i) - station.lat).abs() <= max_dlat)
+        .map(|(j, i)| ((j, i), haversine_km(station.lat, station.lon, grid.lat_at(j, i), grid.lon_at(j, i))))
+        .filter(|&(_, distance)| distance <= radius_km)
+        .collect();
+    cells.sort_by(|a, b| a.1.total_cmp(&b.1));
+    cells.into_iter().map(|(cell, _)| cell).collect()
+}
+
+/// Great-circle distance between two points in km
+fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = phi2 - phi1;
+    let dlambda = (lon2 - lon1).to_radians();
+    let a = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+/// Reads the series of a variable along time at single grid cells, caching
+/// each cell so neighbouring stations and fill searches share reads
+struct CellReader<'a> {
+    dataset: &'a dyn DatasetBackend,
+    var_name: String,
+    y_dim: usize,
+    x_dim: usize,
+    start: Vec<usize>,
+    count: Vec<usize>,
+    cache: HashMap<(usize, usize), Vec<f64>>,
+}
+
+impl<'a> CellReader<'a> {
+    fn new(
+        dataset: &'a dyn DatasetBackend,
+        var: &Variable,
+        grid: &LatLonGrid,
+        time_dim: Option<usize>,
+        indices: &HashMap<String, usize>,
+    ) -> Result<CellReader<'a>, NetCDFError> {
+        let mut start = Vec::with_capacity(var.dimensions.len());
+        let mut count = Vec::with_capacity(var.dimensions.len());
+        for (d, dim_name) in var.dimensions.iter().enumerate() {
+            if Some(d) == time_dim {
+                start.push(0);
+                count.push(var.shape[d]);
+                continue;
+            }
+            let index = match d == grid.y_dim || d == grid.x_dim {
+                true => 0,
+                false => indices.get(dim_name).copied().unwrap_or(0),
+            };
+            if index >= var.shape[d] {
+                return Err(NetCDFError::InvalidSubsetRequest(format!(
+                    "Index {} out of range for dimension '{}' of size {}",
+                    index, dim_name, var.shape[d]
+                )));
+            }
+            start.push(index);
+            count.push(1);
+        }
+        Ok(CellReader {
+            dataset,
+            var_name: var.name.clone(),
+            y_dim: grid.y_dim,
+            x_dim: grid.x_dim,
+            start,
+            count,
+            cache: HashMap::new(),
+        })
+    }
+
+    fn series(&mut self, cell: (usize, usize)) -> Result<&[f64], NetCDFError> {
+        if !self.cache.contains_key(&cell) {
+            let mut start = self.start.clone();
+            (start[self.y_dim], start[self.x_dim]) = cell;
+            let values = self.dataset.read_masked(&self.var_name, &start, &self.count)?;
+            self.cache.insert(cell, values);
+        }
+        Ok(&self.cache[&cell])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn station(lat: f64, lon: f64) -> Station {
+        Station { id: "s".to_string(), lat, lon, elevation: None }
+    }
+
+    #[test]
+    fn test_extract_stations_from_store() {
+        use crate::netcdf::test_util::write_array;
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("grid.zarr");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".zgroup"), r#"{"zarr_format": 2}"#).unwrap();
+        let f64s = |values: &[f64]| values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        write_array(&root, "time", &["time"], &[2], "<f8", r#""units": "days since 2000-01-01""#, f64s(&[0.0, 1.0]));
+        write_array(&root, "lat", &["lat"], &[2], "<f8", r#""units": "degrees_north""#, f64s(&[0.0, 1.0]));
+        write_array(&root, "lon", &["lon"], &[3], "<f8", r#""units": "degrees_east""#, f64s(&[0.0, 1.0, 2.0]));
+        let nan = f32::NAN;
+        let tas = [1.0, 2.0, 3.0, 4.0, 5.0, nan, 11.0, 12.0, 13.0, 14.0, nan, nan];
+        let bytes = tas.iter().flat_map(|v: &f32| v.to_le_bytes()).collect();
+        write_array(&root, "tas", &["time", "lat", "lon"], &[2, 2, 3], "<f4", "", bytes);
+
+        let path = root.to_str().unwrap();
+        let mut request = StationRequest {
+            var_name: "tas".to_string(),
+            stations: vec![
+                Station { id: "a".to_string(), lat: 0.1, lon: 0.1, elevation: Some(10.0) },
+                // Nearest cell (1, 2) is missing at both times
+                Station { id: "b".to_string(), lat: 1.0, lon: 1.9, elevation: None },
+            ],
+            method: PointMethod::Nearest,
+            indices: HashMap::new(),
+            search_radius_km: None,
+        };
+        let extraction = extract_stations(path, &request).unwrap();
+        assert_eq!(extraction.stations.len(), 2);
+        assert_eq!((extraction.stations[1].grid_lat, extraction.stations[1].grid_lon), (1.0, 2.0));
+        let series = |extraction: &StationExtraction, id: &str| -> Vec<(f64, bool)> {
+            extraction.records.iter().filter(|r| r.station_id == id).map(|r| (r.value, r.from_neighbour)).collect()
+        };
+        assert_eq!(series(&extraction, "a"), vec![(1.0, false), (11.0, false)]);
+        assert!(series(&extraction, "b").iter().all(|(value, _)| value.is_nan()));
+        assert_eq!(extraction.records[0].time, extraction.records[2].time);
+
+        // Within 120 km are (1, 1) at ~100 km, then (0, 2) at ~111 km
+        request.search_radius_km = Some(120.0);
+        let extraction = extract_stations(path, &request).unwrap();
+        assert_eq!(series(&extraction, "b"), vec![(5.0, true), (13.0, true)]);
+
+        request.stations[1].id = "a".to_string();
+        let error = extract_stations(path, &request).err().unwrap();
+        assert!(error.to_string().contains("'a' is used more than once"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_stations_with_and_without_header() {
+        let stations = parse_stations("Name;Longitude;Latitude\n# comment\nOslo; 10.75; 59.91\n").unwrap();
+        assert_eq!(stations.len(), 1);
+        assert_eq!(stations[0].id, "Oslo");
+        assert_eq!((stations[0].lat, stations[0].lon), (59.91, 10.75));
+
+        let stations = parse_stations("a,1,2,350\nb,-3,4\n").unwrap();
+        assert_eq!(stations[0].elevation, Some(350.0));
+        assert_eq!((stations[1].id.as_str(), stations[1].lat, stations[1].elevation), ("b", -3.0, None));
+
+        assert!(parse_stations("a,north,2\n").is_err());
+        assert!(parse_stations("id,x,y\na,1,2\n").is_err());
+    }
+
+    #[test]
+    fn test_bilinear_weights_on_regular_and_curvilinear_grids() {
+        let regular = LatLonGrid {
+            y_dim: 0,
+            x_dim: 1,
+            ny: 2,
+            nx: 3,
+            lat: vec![10.0, 0.0],
+            lon: vec![0.0, 10.0, 20.0],
+            curvilinear: false,
+        };
+        let mut weights = Weigher::new(&regular, PointMethod::Bilinear).weights(&station(2.5, 15.0), (1, 1));
+        weights.sort_by_key(|w| w.0);
+        assert_eq!(weights, vec![((0, 1), 0.125), ((0, 2), 0.125), ((1, 1), 0.375), ((1, 2), 0.375)]);
+
+        // The same grid rotated by 90 degrees, stored as 2-D coordinates
+        let curvilinear = LatLonGrid {
+            lat: vec![0.0, 10.0, 20.0, 0.0, 10.0, 20.0],
+            lon: vec![0.0, 0.0, 0.0, 10.0, 10.0, 10.0],
+            curvilinear: true,
+            ..regular
+        };
+        let weights = Weigher::new(&curvilinear, PointMethod::Bilinear).weights(&station(15.0, 2.5), (0, 2));
+        let total: f64 = weights.iter().map(|w| w.1).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        let weight = |cell| weights.iter().find(|w| w.0 == cell).map_or(0.0, |w| w.1);
+        assert!((weight((0, 1)) - 0.375).abs() < 1e-3);
+        assert!((weight((1, 2)) - 0.125).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_cells_within_radius_nearest_first() {
+        let grid = LatLonGrid {
+            y_dim: 0,
+            x_dim: 1,
+            ny: 1,
+            nx: 4,
+            lat: vec![0.0],
+            lon: vec![0.0, 1.0, 2.0, 3.0],
+            curvilinear: false,
+        };
+        assert!((haversine_km(0.0, 0.0, 0.0, 1.0) - 111.195).abs() < 0.01);
+        assert_eq!(cells_within(&grid, &station(0.0, 1.8), 150.0), vec![(0, 2), (0, 1), (0, 3)]);
+    }
+}
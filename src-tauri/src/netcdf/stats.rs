@@ -0,0 +1,140 @@
+use crate::errors::NetCDFError;
+use crate::models::{DimensionSlice, VariableStats};
+use super::backend::{open_dataset, DatasetBackend};
+use super::data_access::{find_variable, is_text_type, resolve_hyperslab};
+use super::table::outer_blocks;
+use std::collections::HashMap;
+
+/// Compute summary statistics of a variable (or a hyperslab of it).
+///
+/// The data is read block by block, so variables larger than memory work.
+pub fn variable_stats(
+    path: &str,
+    var_name: &str,
+    slices: &HashMap<String, DimensionSlice>,
+) -> Result<VariableStats, NetCDFError> {
+    read_stats(open_dataset(path)?.as_ref(), var_name, slices)
+}
+
+/// Summary statistics of a variable (or a hyperslab of it) of an open dataset
+pub fn read_stats(
+    dataset: &dyn DatasetBackend,
+    var_name: &str,
+    slices: &HashMap<String, DimensionSlice>,
+) -> Result<VariableStats, NetCDFError> {
+    let var = find_variable(&dataset.metadata()?, var_name)?;
+    if is_text_type(&var.data_type) {
+        return Err(NetCDFError::ConversionError(format!(
+            "Variable '{}' is not numeric",
+            var_name
+        )));
+    }
+
+    let (start, count) = resolve_hyperslab(&var, slices)?;
+    let mut accumulator = StatsAccumulator::default();
+    if count.iter().all(|&n| n > 0) {
+        for (block_start, block_count) in outer_blocks(&start, &count) {
+            accumulator.extend(&dataset.read_masked(var_name, &block_start, &block_count)?);
+        }
+    }
+
+    Ok(accumulator.finish(var_name))
+}
+
+/// Running count, extremes, mean and variance (Welford's algorithm, in
+/// West's weighted form)
+#[derive(Debug, Default)]
+pub(crate) struct StatsAccumulator {
+    count: usize,
+    missing: usize,
+    weight: f64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl StatsAccumulator {
+    pub(crate) fn extend(&mut self, values: &[f64]) {
+        for &x in values {
+            self.push(x, 1.0);
+        }
+    }
+
+    /// Add one value counting `weight` times towards the mean and spread
+    pub(crate) fn push(&mut self, x: f64, weight: f64) {
+        if !x.is_finite() {
+            self.missing += 1;
+            return;
+        }
+        if self.count == 0 {
+            self.min = x;
+            self.max = x;
+        } else {
+            self.min = self.min.min(x);
+            self.max = self.max.max(x);
+        }
+        self.count += 1;
+        self.weight += weight;
+        let delta = x - self.mean;
+        self.mean += delta * weight / self.weight;
+        self.m2 += weight * delta * (x - self.mean);
+    }
+
+    pub(crate) fn finish(self, var_name: &str) -> VariableStats {
+        let valid = self.count > 0 && self.weight > 0.0;
+        VariableStats {
+            var_name: var_name.to_string(),
+            count: self.count,
+            missing_count: self.missing,
+            min: valid.then_some(self.min),
+            max: valid.then_some(self.max),
+            mean: valid.then_some(self.mean),
+            std_dev: valid.then(|| (self.m2 / self.weight).max(0.0).sqrt()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulator_matches_direct_computation() {
+        let mut acc = StatsAccumulator::default();
+        acc.extend(&[2.0, 4.0, f64::NAN]);
+        acc.extend(&[4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        let stats = acc.finish("x");
+
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.missing_count, 1);
+        assert_eq!(stats.min, Some(2.0));
+        assert_eq!(stats.max, Some(9.0));
+        assert!((stats.mean.unwrap() - 5.0).abs() < 1e-12);
+        assert!((stats.std_dev.unwrap() - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_all_missing() {
+        let mut acc = StatsAccumulator::default();
+        acc.extend(&[f64::NAN, f64::NAN]);
+        let stats = acc.finish("x");
+        assert_eq!(stats.count, 0);
+        assert_eq!(stats.missing_count, 2);
+        assert!(stats.mean.is_none());
+    }
+
+    #[test]
+    fn test_weights_count_like_repeated_values() {
+        let mut weighted = StatsAccumulator::default();
+        weighted.push(1.0, 3.0);
+        weighted.push(5.0, 1.0);
+        let mut repeated = StatsAccumulator::default();
+        repeated.extend(&[1.0, 1.0, 1.0, 5.0]);
+
+        let (weighted, repeated) = (weighted.finish("x"), repeated.finish("x"));
+        assert_eq!(weighted.count, 2);
+        assert!((weighted.mean.unwrap() - repeated.mean.unwrap()).abs() < 1e-12);
+        assert!((weighted.std_dev.unwrap() - repeated.std_dev.unwrap()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::errors::NetCDFError;
+use crate::models::{DimensionSlice, FileMetadata, Variable};
+use super::backend::DatasetBackend;
+use super::coordinates::detect_coordinates;
+use super::data_access::{is_text_type, resolve_hyperslab};
+use super::time::{is_standard_calendar, CfTimeUnits};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+
+/// Target number of table rows materialised per block
+const BLOCK_ROWS: usize = 1 << 18;
+
+/// Values of a dimension column in a long-form table
+#[derive(Debug, Clone)]
+pub enum CoordinateValues {
+    /// No coordinate variable: the column holds the index along the dimension
+    Index,
+    /// Numeric coordinate values
+    Numeric(Vec<f64>),
+    /// Decoded CF time values
+    Time(Vec<Option<NaiveDateTime>>),
+}
+
+/// A dimension column of a long-form table
+#[derive(Debug, Clone)]
+pub struct CoordinateColumn {
+    /// Column (dimension) name
+    pub name: String,
+    /// First index of the selection along this dimension
+    pub start: usize,
+    /// Coordinate values for the selected range
+    pub values: CoordinateValues,
+}
+
+/// A coordinate variable that is not a dimension's own, such as the 2-D
+/// latitude or longitude of a curvilinear grid
+#[derive(Debug, Clone)]
+pub struct AuxiliaryColumn {
+    /// Column (variable) name
+    pub name: String,
+    /// Position of each of the variable's dimensions among the table's
+    pub dims: Vec<usize>,
+}
+
+/// Layout of a long-form ("tidy") table built from variables sharing dimensions
+#[derive(Debug, Clone)]
+pub struct TablePlan {
+    /// One column per dimension
+    pub coordinates: Vec<CoordinateColumn>,
+    /// Auxiliary coordinate columns, after the dimension columns
+    pub auxiliary: Vec<AuxiliaryColumn>,
+    /// Value columns
+    pub variables: Vec<Variable>,
+    /// Start of the hyperslab along each dimension
+    pub start: Vec<usize>,
+    /// Size of the hyperslab along each dimension
+    pub count: Vec<usize>,
+    /// What the table does not show as asked, such as times left undecoded
+    pub warnings: Vec<String>,
+}
+
+impl TablePlan {
+    /// Resolve the variables, hyperslab and coordinate columns of a table.
+    ///
+    /// All variables must share the same dimensions in the same order.
+    pub fn new(
+        dataset: &dyn DatasetBackend,
+        metadata: &FileMetadata,
+        var_names: &[String],
+        slices: &HashMap<String, DimensionSlice>,
+        decode_times: bool,
+    ) -> Result<Self, NetCDFError> {
+        let variables: Vec<Variable> = var_names
+            .iter()
+            .map(|name| {
+                metadata
+                    .variables
+                    .iter()
+                    .find(|v| &v.name == name)
+                    .cloned()
+                    .ok_or_else(|| NetCDFError::VariableNotFound(name.clone()))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let first = variables.first().ok_or_else(|| {
+            NetCDFError::ExportError("No variables selected".to_string())
+        })?;
+        if let Some(other) = variables.iter().find(|v| v.dimensions != first.dimensions) {
+            return Err(NetCDFError::ExportError(format!(
+                "Variable '{}' has dimensions ({}) but '{}' has ({})",
+                other.name,
+                other.dimensions.join(", "),
+                first.name,
+                first.dimensions.join(", ")
+            )));
+        }
+
+        let (start, count) = resolve_hyperslab(first, slices)?;
+
+        let mut coordinates = Vec::with_capacity(first.dimensions.len());
+        let mut warnings = Vec::new();
+        for (d, dim_name) in first.dimensions.iter().enumerate() {
+            let values =
+                read_coordinate_values(dataset, metadata, dim_name, start[d], count[d], decode_times, &mut warnings)?;
+            coordinates.push(CoordinateColumn {
+                name: dim_name.clone(),
+                start: start[d],
+                values,
+            });
+        }
+        let auxiliary = auxiliary_columns(metadata, first, var_names);
+
+        Ok(TablePlan {
+            coordinates,
+            auxiliary,
+            variables,
+            start,
+            count,
+            warnings,
+        })
+    }
+
+    /// Split the hyperslab into blocks along the outermost dimension.
+    ///
+    /// Each block is returned as absolute (start, count).
+    pub fn blocks(&self) -> Vec<(Vec<usize>, Vec<usize>)> {
+        outer_blocks(&self.start, &self.count)
+    }
+
+    /// Read every value column of a block as f64 (fill values become NaN)
+    pub fn read_block(
+        &self,
+        dataset: &dyn DatasetBackend,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<Vec<f64>>, NetCDFError> {
+        self.variables
+            .iter()
+            .map(|meta| dataset.read_masked(&meta.name, start, count))
+            .collect()
+    }
+
+    /// Read every auxiliary column of a block as f64, repeated to one value
+    /// per row of the block
+    pub fn read_auxiliary(
+        &self,
+        dataset: &dyn DatasetBackend,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<Vec<f64>>, NetCDFError> {
+        let rows: usize = count.iter().product();
+        self.auxiliary
+            .iter()
+            .map(|aux| {
+                let aux_start: Vec<usize> = aux.dims.iter().map(|&d| start[d]).collect();
+                let aux_count: Vec<usize> = aux.dims.iter().map(|&d| count[d]).collect();
+                let values = dataset.read_masked(&aux.name, &aux_start, &aux_count)?;
+
+                let mut column = Vec::with_capacity(rows);
+                let mut index = vec![0; count.len()];
+                for _ in 0..rows {
+                    let offset = aux.dims.iter().zip(&aux_count).fold(0, |offset, (&d, &n)| offset * n + index[d]);
+                    column.push(values[offset]);
+                    next_index(&mut index, count);
+                }
+                Ok(column)
+            })
+            .collect()
+    }
+}
+
+/// Numeric coordinates named in the first variable's `coordinates` attribute,
+/// or detected as its latitude and longitude, that span some of its
+/// dimensions without being a dimension's own coordinate variable
+fn auxiliary_columns(metadata: &FileMetadata, first: &Variable, var_names: &[String]) -> Vec<AuxiliaryColumn> {
+    let detected = detect_coordinates(metadata);
+    let named = first.attributes.get("coordinates").map_or("", |c| c.as_str()).split_whitespace();
+    let mut columns: Vec<AuxiliaryColumn> = Vec::new();
+    for name in named.chain(detected.lat_var.as_deref()).chain(detected.lon_var.as_deref()) {
+        if var_names.iter().any(|v| v == name) || columns.iter().any(|c| c.name == name) {
+            continue;
+        }
+        let Some(meta) = metadata.variables.iter().find(|v| v.name == name) else { continue };
+        let own = meta.dimensions.len() == 1 && meta.dimensions[0] == meta.name;
+        if own || meta.dimensions.is_empty() || is_text_type(&meta.data_type) {
+            continue;
+        }
+        let dims: Option<Vec<usize>> =
+            meta.dimensions.iter().map(|dim| first.dimensions.iter().position(|d| d == dim)).collect();
+        if let Some(dims) = dims {
+            columns.push(AuxiliaryColumn { name: name.to_string(), dims });
+        }
+    }
+    columns
+}
+
+/// Split a hyperslab into blocks of about `BLOCK_ROWS` values along its
+/// outermost dimension, each returned as absolute (start, count)
+pub fn outer_blocks(start: &[usize], count: &[usize]) -> Vec<(Vec<usize>, Vec<usize>)> {
+    if count.is_empty() {
+        return vec![(vec![], vec![])];
+    }
+
+    let inner: usize = count[1..].iter().product();
+    let rows_per_block = (BLOCK_ROWS / inner.max(1)).max(1);
+
+    let mut blocks = Vec::new();
+    let mut offset = 0;
+    while offset < count[0] {
+        let rows = rows_per_block.min(count[0] - offset);
+        let mut block_start = start.to_vec();
+        let mut block_count = count.to_vec();
+        block_start[0] += offset;
+        block_count[0] = rows;
+        blocks.push((block_start, block_count));
+        offset += rows;
+    }
+    blocks
+}
+
+/// Advance a row-major multi-index within `count`, returning false when exhausted
+pub fn next_index(index: &mut [usize], count: &[usize]) -> bool {
+    for d in (0..index.len()).rev() {
+        index[d] += 1;
+        if index[d] < count[d] {
+            return true;
+        }
+        index[d] = 0;
+    }
+    false
+}
+
+/// Read the coordinate variable for a dimension, if there is one
+fn read_coordinate_values(
+    dataset: &dyn DatasetBackend,
+    metadata: &FileMetadata,
+    dim_name: &str,
+    start: usize,
+    count: usize,
+    decode_times: bool,
+    warnings: &mut Vec<String>,
+) -> Result<CoordinateValues, NetCDFError> {
+    let Some(meta) = metadata
+        .variables
+        .iter()
+        .find(|v| v.name == dim_name && v.dimensions.len() == 1 && v.dimensions[0] == dim_name)
+    else {
+        return Ok(CoordinateValues::Index);
+    };
+
+    if is_text_type(&meta.data_type) {
+        return Ok(CoordinateValues::Index);
+    }
+
+    let values = dataset.read_masked(&meta.name, &[start], &[count])?;
+
+    let calendar = meta.attributes.get("calendar").map(|c| c.as_str());
+    let mut time_units = meta.attributes.get("units").and_then(|u| CfTimeUnits::parse(u)).filter(|_| decode_times);
+    if time_units.is_some() && !is_standard_calendar(calendar) {
+        warnings.push(format!(
+            "Times of '{}' are raw offsets ({}): the {} calendar is not decoded",
+            dim_name,
+            meta.attributes["units"],
+            calendar.unwrap_or_default()
+        ));
+        time_units = None;
+    }
+
+    Ok(match time_units {
+        Some(units) => CoordinateValues::Time(values.iter().map(|&v| units.decode(v)).collect()),
+        None => CoordinateValues::Numeric(values),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_index_row_major() {
+        let count = [2, 3];
+        let mut index = [0, 0];
+        let mut visited = vec![index];
+        while next_index(&mut index, &count) {
+            visited.push(index);
+        }
+        assert_eq!(visited, vec![[0, 0], [0, 1], [0, 2], [1, 0], [1, 1], [1, 2]]);
+    }
+
+    #[test]
+    fn test_blocks_cover_hyperslab() {
+        let plan = TablePlan {
+            coordinates: vec![],
+            auxiliary: vec![],
+            variables: vec![],
+            start: vec![10, 0],
+            count: vec![5, BLOCK_ROWS / 2],
+            warnings: vec![],
+        };
+        let blocks = plan.blocks();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0], (vec![10, 0], vec![2, BLOCK_ROWS / 2]));
+        assert_eq!(blocks[2], (vec![14, 0], vec![1, BLOCK_ROWS / 2]));
+    }
+
+    #[test]
+    fn test_curvilinear_coordinates_and_calendars() {
+        use crate::netcdf::backend::open_dataset;
+        use crate::netcdf::test_util::write_array;
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("model.zarr");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".zgroup"), r#"{"zarr_format": 2}"#).unwrap();
+        let f64s = |values: &[f64]| values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let time_attrs = r#""units": "days since 2000-01-01", "calendar": "noleap""#;
+        write_array(&root, "time", &["time"], &[2], "<f8", time_attrs, f64s(&[0.0, 365.0]));
+        let lat_attrs = r#""units": "degrees_north""#;
+        write_array(&root, "lat", &["y", "x"], &[2, 2], "<f8", lat_attrs, f64s(&[10.0, 11.0, 20.0, 21.0]));
+        let lon_attrs = r#""units": "degrees_east""#;
+        write_array(&root, "lon", &["y", "x"], &[2, 2], "<f8", lon_attrs, f64s(&[100.0, 110.0, 101.0, 111.0]));
+        let tas: Vec<f64> = (0..8).map(f64::from).collect();
+        write_array(&root, "tas", &["time", "y", "x"], &[2, 2, 2], "<f8", r#""coordinates": "lat lon""#, f64s(&tas));
+
+        let dataset = open_dataset(root.to_str().unwrap()).unwrap();
+        let metadata = dataset.metadata().unwrap();
+        let slices = HashMap::from([("time".to_string(), DimensionSlice { start: 1, count: 1 })]);
+        let plan = TablePlan::new(dataset.as_ref(), &metadata, &["tas".to_string()], &slices, true).unwrap();
+
+        let names: Vec<&str> = plan.auxiliary.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["lat", "lon"]);
+        let (start, count) = plan.blocks().remove(0);
+        let auxiliary = plan.read_auxiliary(dataset.as_ref(), &start, &count).unwrap();
+        assert_eq!(auxiliary, vec![vec![10.0, 11.0, 20.0, 21.0], vec![100.0, 110.0, 101.0, 111.0]]);
+
+        assert!(matches!(&plan.coordinates[0].values, CoordinateValues::Numeric(values) if values == &[365.0]));
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].contains("noleap"), "{}", plan.warnings[0]);
+    }
+}
@@ -0,0 +1,35 @@
+//! Fixtures shared by the tests of several modules.
+//!
+//! The stores are plain uncompressed Zarr v2 directories, which every test
+//! can read without the NetCDF or HDF5 libraries being installed.
+
+use std::path::Path;
+
+/// Add an uncompressed single-chunk Zarr v2 array to a store
+pub(crate) fn write_array(root: &Path, name: &str, dims: &[&str], shape: &[usize], dtype: &str, attrs: &str, bytes: Vec<u8>) {
+    std::fs::create_dir_all(root.join(name)).unwrap();
+    std::fs::write(
+        root.join(name).join(".zarray"),
+        format!(
+            r#"{{"zarr_format": 2, "shape": {shape:?}, "chunks": {shape:?}, "dtype": "{dtype}",
+                "compressor": null, "fill_value": null, "order": "C", "filters": null}}"#
+        ),
+    )
+    .unwrap();
+    let attrs = if attrs.is_empty() { String::new() } else { format!(", {}", attrs) };
+    std::fs::write(root.join(name).join(".zattrs"), format!(r#"{{"_ARRAY_DIMENSIONS": {:?}{}}}"#, dims, attrs)).unwrap();
+    let chunk = vec!["0"; dims.len()].join(".");
+    std::fs::write(root.join(name).join(chunk), bytes).unwrap();
+}
+
+/// Zarr v2 store with `time(time)` and `tas(time, x)` where x has size 2
+pub(crate) fn write_store(root: &Path, time_units: &str, times: &[f64], values: &[f32]) {
+    let _ = std::fs::remove_dir_all(root);
+    std::fs::create_dir_all(root).unwrap();
+    std::fs::write(root.join(".zgroup"), r#"{"zarr_format": 2}"#).unwrap();
+
+    let n = times.len();
+    let units = format!(r#""units": "{}""#, time_units);
+    write_array(root, "time", &["time"], &[n], "<f8", &units, times.iter().flat_map(|t| t.to_le_bytes()).collect());
+    write_array(root, "tas", &["time", "x"], &[n, 2], "<f4", "", values.iter().flat_map(|v| v.to_le_bytes()).collect());
+}
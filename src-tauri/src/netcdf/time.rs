@@ -0,0 +1,159 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+/// A parsed CF time units string such as "days since 1970-01-01"
+#[derive(Debug, Clone, PartialEq)]
+pub struct CfTimeUnits {
+    /// Length of one unit in seconds
+    pub unit_seconds: f64,
+    /// Reference date/time the offsets are counted from
+    pub epoch: NaiveDateTime,
+}
+
+impl CfTimeUnits {
+    /// Parse a "<unit> since <reference time>" string.
+    ///
+    /// Returns `None` for anything that is not a recognisable CF time unit.
+    pub fn parse(units: &str) -> Option<Self> {
+        let lower = units.trim().to_lowercase();
+        let (unit, reference) = lower.split_once(" since ")?;
+        let unit_seconds = unit_seconds(unit.trim())?;
+        let epoch = parse_reference_time(reference.trim())?;
+        Some(CfTimeUnits { unit_seconds, epoch })
+    }
+
+    /// Convert an offset in these units to a date/time
+    pub fn decode(&self, value: f64) -> Option<NaiveDateTime> {
+        if !value.is_finite() {
+            return None;
+        }
+        let millis = (value * self.unit_seconds * 1000.0).round();
+        if millis.abs() > i64::MAX as f64 {
+            return None;
+        }
+        self.epoch.checked_add_signed(Duration::milliseconds(millis as i64))
+    }
+}
+
+/// Whether a calendar attribute describes the standard Gregorian calendar
+pub fn is_standard_calendar(calendar: Option<&str>) -> bool {
+    match calendar {
+        None => true,
+        Some(c) => matches!(
+            c.trim().to_lowercase().as_str(),
+            "standard" | "gregorian" | "proleptic_gregorian" | ""
+        ),
+    }
+}
+
+/// Format a decoded time as ISO 8601
+pub fn format_iso(time: NaiveDateTime) -> String {
+    if time.nanosecond() == 0 {
+        time.format("%Y-%m-%dT%H:%M:%S").to_string()
+    } else {
+        time.format("%Y-%m-%dT%H:%M:%S%.3f").to_string()
+    }
+}
+
+/// Parse an ISO 8601 date or date/time such as "2015-06-01T12:00:00Z"
+pub(crate) fn parse_iso(text: &str) -> Option<NaiveDateTime> {
+    parse_reference_time(&text.trim().to_lowercase())
+}
+
+/// Seconds per CF/UDUNITS time unit
+fn unit_seconds(unit: &str) -> Option<f64> {
+    let seconds = match unit {
+        "ms" | "msec" | "msecs" | "millisecond" | "milliseconds" => 0.001,
+        "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+        "min" | "mins" | "minute" | "minutes" => 60.0,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3600.0,
+        "d" | "day" | "days" => 86400.0,
+        "week" | "weeks" => 7.0 * 86400.0,
+        // UDUNITS defines months and years from the mean tropical year
+        "month" | "months" => 365.242198781 * 86400.0 / 12.0,
+        "year" | "years" | "yr" => 365.242198781 * 86400.0,
+        _ => return None,
+    };
+    Some(seconds)
+}
+
+/// Parse the reference time of a CF units string, e.g. "1970-01-01 00:00:00"
+fn parse_reference_time(reference: &str) -> Option<NaiveDateTime> {
+    let cleaned = reference
+        .trim_end_matches(" utc")
+        .trim_end_matches('z')
+        .replace('t', " ");
+    let mut parts = cleaned.split_whitespace();
+
+    let date = parse_date(parts.next()?)?;
+    let time = match parts.next() {
+        Some(t) => parse_time(t)?,
+        None => NaiveTime::MIN,
+    };
+
+    // Accept an explicit zero UTC offset ("+00:00", "0:00", "+0")
+    if let Some(offset) = parts.next() {
+        let digits: String = offset.chars().filter(|c| c.is_ascii_digit()).collect();
+        if digits.chars().any(|c| c != '0') {
+            return None;
+        }
+    }
+
+    Some(NaiveDateTime::new(date, time))
+}
+
+/// Parse "YYYY-M-D" allowing unpadded fields
+fn parse_date(s: &str) -> Option<NaiveDate> {
+    let mut fields = s.split('-');
+    let year: i32 = fields.next()?.parse().ok()?;
+    let month: u32 = fields.next().unwrap_or("1").parse().ok()?;
+    let day: u32 = fields.next().unwrap_or("1").parse().ok()?;
+    if fields.next().is_some() {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// Parse "H:M[:S[.fff]]" allowing unpadded fields
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    let (s, _) = s.split_once('+').unwrap_or((s, ""));
+    let mut fields = s.split(':');
+    let hour: u32 = fields.next()?.parse().ok()?;
+    let minute: u32 = fields.next().unwrap_or("0").parse().ok()?;
+    let seconds: f64 = fields.next().unwrap_or("0").parse().ok()?;
+    let whole = seconds.trunc() as u32;
+    let millis = ((seconds - seconds.trunc()) * 1000.0).round() as u32;
+    NaiveTime::from_hms_milli_opt(hour, minute, whole, millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_common_units() {
+        let units = CfTimeUnits::parse("days since 1970-01-01").unwrap();
+        assert_eq!(units.unit_seconds, 86400.0);
+        assert_eq!(format_iso(units.decode(1.5).unwrap()), "1970-01-02T12:00:00");
+
+        let units = CfTimeUnits::parse("hours since 1900-1-1 0:0:0").unwrap();
+        assert_eq!(format_iso(units.decode(24.0).unwrap()), "1900-01-02T00:00:00");
+
+        let units = CfTimeUnits::parse("seconds since 2000-01-01T00:00:00Z").unwrap();
+        assert_eq!(format_iso(units.decode(0.25).unwrap()), "2000-01-01T00:00:00.250");
+    }
+
+    #[test]
+    fn test_reject_malformed_units() {
+        assert!(CfTimeUnits::parse("m s-1").is_none());
+        assert!(CfTimeUnits::parse("fortnights since 1970-01-01").is_none());
+        assert!(CfTimeUnits::parse("days since 1970-13-01").is_none());
+        assert!(CfTimeUnits::parse("days since 1970-01-01 00:00 +05:00").is_none());
+    }
+
+    #[test]
+    fn test_calendars() {
+        assert!(is_standard_calendar(None));
+        assert!(is_standard_calendar(Some("proleptic_gregorian")));
+        assert!(!is_standard_calendar(Some("noleap")));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::errors::NetCDFError;
+use crate::models::{DataPoint, FileMetadata, TimeSeriesRequest, Variable};
+use super::backend::{open_dataset, DatasetBackend};
+use super::coordinates::detect_coordinates;
+use super::data_access::{find_variable, is_text_type};
+use super::grid::{resolve_grid, LatLonGrid};
+use super::time::{format_iso, is_standard_calendar, CfTimeUnits};
+
+/// Extract the full time series of a variable at one grid point.
+///
+/// The point is given either by indices along the non-time dimensions or
+/// by a latitude/longitude, which selects the nearest grid cell. Times are
+/// decoded to ISO 8601 when the time units allow it.
+pub fn extract_timeseries(path: &str, request: &TimeSeriesRequest) -> Result<Vec<DataPoint>, NetCDFError> {
+    read_timeseries(open_dataset(path)?.as_ref(), request)
+}
+
+/// Extract the time series of a variable of an open dataset at one grid point
+pub fn read_timeseries(dataset: &dyn DatasetBackend, request: &TimeSeriesRequest) -> Result<Vec<DataPoint>, NetCDFError> {
+    let metadata = dataset.metadata()?;
+    let var = find_variable(&metadata, &request.var_name)?;
+    if is_text_type(&var.data_type) {
+        return Err(NetCDFError::ConversionError(format!(
+            "Variable '{}' is not numeric",
+            var.name
+        )));
+    }
+
+    let coords = detect_coordinates(&metadata);
+    let time_var = coords
+        .time_var
+        .as_ref()
+        .and_then(|name| metadata.variables.iter().find(|v| &v.name == name))
+        .filter(|t| t.dimensions.len() == 1)
+        .ok_or_else(|| NetCDFError::InvalidSubsetRequest("File has no time coordinate".to_string()))?;
+    let time_dim = var
+        .dimensions
+        .iter()
+        .position(|d| d == &time_var.dimensions[0])
+        .ok_or_else(|| {
+            NetCDFError::InvalidSubsetRequest(format!(
+                "Variable '{}' has no '{}' dimension",
+                var.name, time_var.dimensions[0]
+            ))
+        })?;
+
+    let mut indices = request.indices.clone();
+    match (request.lat, request.lon) {
+        (Some(lat), Some(lon)) => {
+            let grid = resolve_grid(dataset, &metadata, &var)?;
+            let (j, i) = nearest_cell(&grid, lat, lon).ok_or_else(|| {
+                NetCDFError::InvalidSubsetRequest("Grid has no valid coordinates".to_string())
+            })?;
+            indices.insert(var.dimensions[grid.y_dim].clone(), j);
+            indices.insert(var.dimensions[grid.x_dim].clone(), i);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(NetCDFError::InvalidSubsetRequest(
+                "Both lat and lon are needed to select a point".to_string(),
+            ))
+        }
+    }
+
+    let mut start = Vec::with_capacity(var.dimensions.len());
+    let mut count = Vec::with_capacity(var.dimensions.len());
+    for (d, dim_name) in var.dimensions.iter().enumerate() {
+        if d == time_dim {
+            start.push(0);
+            count.push(var.shape[d]);
+            continue;
+        }
+        let index = indices.get(dim_name).copied().unwrap_or(0);
+        if index >= var.shape[d] {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Index {} out of range for dimension '{}' of size {}",
+                index, dim_name, var.shape[d]
+            )));
+        }
+        start.push(index);
+        count.push(1);
+    }
+
+    let values = dataset.read_masked(&var.name, &start, &count)?;
+    let times = time_labels(dataset, time_var)?;
+
+    Ok(times
+        .into_iter()
+        .zip(values)
+        .map(|(time, value)| DataPoint { time, value })
+        .collect())
+}
+
+/// The 1-D time coordinate of a file and its position among `var`'s
+/// dimensions, if `var` varies in time
+pub(crate) fn time_dimension<'a>(metadata: &'a FileMetadata, var: &Variable) -> Option<(&'a Variable, usize)> {
+    let coords = match &metadata.coordinates {
+        Some(coords) => coords.clone(),
+        None => detect_coordinates(metadata),
+    };
+    let time_var = coords
+        .time_var
+        .as_ref()
+        .and_then(|name| metadata.variables.iter().find(|v| &v.name == name))
+        .filter(|t| t.dimensions.len() == 1)?;
+    let position = var.dimensions.iter().position(|d| d == &time_var.dimensions[0])?;
+    Some((time_var, position))
+}
+
+/// Values of a time coordinate as ISO 8601 strings when its units and
+/// calendar allow decoding them, else as raw numbers
+pub(crate) fn time_labels(dataset: &dyn DatasetBackend, time_var: &Variable) -> Result<Vec<String>, NetCDFError> {
+    let times = dataset.read_masked_all(time_var)?;
+    let units = time_var
+        .attributes
+        .get("units")
+        .filter(|_| is_standard_calendar(time_var.attributes.get("calendar").map(String::as_str)))
+        .and_then(|u| CfTimeUnits::parse(u));
+
+    Ok(times
+        .iter()
+        .map(|&t| {
+            units
+                .as_ref()
+                .and_then(|u| u.decode(t))
+                .map(format_iso)
+                .unwrap_or_else(|| t.to_string())
+        })
+        .collect())
+}
+
+/// Row and column of the grid cell closest to a point
+pub fn nearest_cell(grid: &LatLonGrid, lat: f64, lon: f64) -> Option<(usize, usize)> {
+    let mut best = None;
+    let mut best_distance = f64::INFINITY;
+    for j in 0..grid.ny {
+        for i in 0..grid.nx {
+            let distance = angular_distance(grid.lat_at(j, i), grid.lon_at(j, i), lat, lon);
+            if distance < best_distance {
+                best_distance = distance;
+                best = Some((j, i));
+            }
+        }
+    }
+    best
+}
+
+/// Squared distance in degrees, with longitude wrapped and scaled by latitude
+pub(crate) fn angular_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let dlon = (lon1 - lon2 + 540.0).rem_euclid(360.0) - 180.0;
+    let dlat = lat1 - lat2;
+    let scale = ((lat1 + lat2) / 2.0).to_radians().cos();
+    dlat * dlat + (dlon * scale) * (dlon * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_cell_wraps_longitude() {
+        let grid = LatLonGrid {
+            y_dim: 1,
+            x_dim: 2,
+            ny: 3,
+            nx: 4,
+            lat: vec![-10.0, 0.0, 10.0],
+            lon: vec![0.0, 90.0, 180.0, 270.0],
+            curvilinear: false,
+        };
+        assert_eq!(nearest_cell(&grid, 8.0, -80.0), Some((2, 3)));
+        assert_eq!(nearest_cell(&grid, -1.0, 359.0), Some((1, 0)));
+    }
+}
@@ -0,0 +1,604 @@
+//! UDUNITS-style unit strings: parsing, compatibility and conversion.
+//!
+//! A unit is reduced to a scale and offset relative to the SI base units plus
+//! the exponents of those base units, so "hPa" becomes 100 kg m-1 s-2 and
+//! "degC" becomes K with an offset of 273.15. Units can be written as
+//! products ("kg m-2 s-1", "kg.m-2.s-1", "kg*m^-2"), quotients ("mm/day",
+//! "J/(kg K)"), with SI prefixes and with a leading numeric factor ("1e-3 kg").
+
+use crate::errors::NetCDFError;
+use crate::models::{DataPoint, FileMetadata, VariableData, VariableDataResponse, VariableStats};
+use super::backend::{open_dataset, DatasetBackend};
+use super::data_access::find_variable;
+use super::loader::attribute_numbers;
+
+/// Exponents of metre, kilogram, second, kelvin, ampere, mole and candela
+type Dims = [i32; 7];
+
+const NONE: Dims = [0, 0, 0, 0, 0, 0, 0];
+const LENGTH: Dims = [1, 0, 0, 0, 0, 0, 0];
+const MASS: Dims = [0, 1, 0, 0, 0, 0, 0];
+const TIME: Dims = [0, 0, 1, 0, 0, 0, 0];
+const TEMPERATURE: Dims = [0, 0, 0, 1, 0, 0, 0];
+const CURRENT: Dims = [0, 0, 0, 0, 1, 0, 0];
+const AMOUNT: Dims = [0, 0, 0, 0, 0, 1, 0];
+const LUMINOSITY: Dims = [0, 0, 0, 0, 0, 0, 1];
+const AREA: Dims = [2, 0, 0, 0, 0, 0, 0];
+const VOLUME: Dims = [3, 0, 0, 0, 0, 0, 0];
+const FREQUENCY: Dims = [0, 0, -1, 0, 0, 0, 0];
+const VELOCITY: Dims = [1, 0, -1, 0, 0, 0, 0];
+const FORCE: Dims = [1, 1, -2, 0, 0, 0, 0];
+const PRESSURE: Dims = [-1, 1, -2, 0, 0, 0, 0];
+const ENERGY: Dims = [2, 1, -2, 0, 0, 0, 0];
+const POWER: Dims = [2, 1, -3, 0, 0, 0, 0];
+const CHARGE: Dims = [0, 0, 1, 0, 1, 0, 0];
+const VOLTAGE: Dims = [2, 1, -3, 0, -1, 0, 0];
+const RESISTANCE: Dims = [2, 1, -3, 0, -2, 0, 0];
+const CONDUCTANCE: Dims = [-2, -1, 3, 0, 2, 0, 0];
+/// Mass of water per area (an amount, or a flux with a time exponent) and the
+/// depth of water it makes; only these pair up through the density of water
+const WATER_AMOUNT: Dims = [-2, 1, 0, 0, 0, 0, 0];
+const WATER_DEPTH: Dims = [1, 0, 0, 0, 0, 0, 0];
+
+const BASE_SYMBOLS: [&str; 7] = ["m", "kg", "s", "K", "A", "mol", "cd"];
+
+/// Density of liquid water used for water-equivalent conversions, kg m-3
+const WATER_DENSITY: f64 = 1000.0;
+
+/// Seconds in a UDUNITS year (mean tropical year), as in CF time units
+const YEAR: f64 = 365.242198781 * 86400.0;
+
+/// A named unit: `names` are exact spellings; `prefixable` ones take SI prefixes
+struct Definition {
+    names: &'static [&'static str],
+    scale: f64,
+    offset: f64,
+    dims: Dims,
+    prefixable: bool,
+}
+
+const fn def(names: &'static [&'static str], scale: f64, dims: Dims, prefixable: bool) -> Definition {
+    Definition { names, scale, offset: 0.0, dims, prefixable }
+}
+
+const DEFINITIONS: &[Definition] = &[
+    def(&["m", "meter", "metre"], 1.0, LENGTH, true),
+    def(&["g", "gram"], 1e-3, MASS, true),
+    def(&["s", "sec", "second"], 1.0, TIME, true),
+    def(&["K", "kelvin", "degK", "deg_K", "degree_K", "degrees_K"], 1.0, TEMPERATURE, true),
+    def(&["A", "ampere"], 1.0, CURRENT, true),
+    def(&["mol", "mole"], 1.0, AMOUNT, true),
+    def(&["cd", "candela"], 1.0, LUMINOSITY, true),
+    def(&["rad", "radian"], 1.0, NONE, true),
+    def(&["sr", "steradian"], 1.0, NONE, false),
+    def(&["Hz", "hertz"], 1.0, FREQUENCY, true),
+    def(&["N", "newton"], 1.0, FORCE, true),
+    def(&["Pa", "pascal"], 1.0, PRESSURE, true),
+    def(&["J", "joule"], 1.0, ENERGY, true),
+    def(&["W", "watt"], 1.0, POWER, true),
+    def(&["C", "coulomb"], 1.0, CHARGE, true),
+    def(&["V", "volt"], 1.0, VOLTAGE, true),
+    def(&["ohm"], 1.0, RESISTANCE, true),
+    def(&["S", "siemens"], 1.0, CONDUCTANCE, true),
+    def(&["bar"], 1e5, PRESSURE, true),
+    def(&["atm", "atmosphere"], 101325.0, PRESSURE, false),
+    def(&["min", "minute"], 60.0, TIME, false),
+    def(&["h", "hr", "hour"], 3600.0, TIME, false),
+    def(&["d", "day"], 86400.0, TIME, false),
+    def(&["week"], 7.0 * 86400.0, TIME, false),
+    def(&["month"], YEAR / 12.0, TIME, false),
+    def(&["yr", "year"], YEAR, TIME, false),
+    def(&["L", "l", "liter", "litre"], 1e-3, VOLUME, true),
+    def(&["t", "tonne", "metric_ton"], 1e3, MASS, true),
+    def(&["ha", "hectare"], 1e4, AREA, false),
+    def(&["in", "inch"], 0.0254, LENGTH, false),
+    def(&["ft", "foot", "feet"], 0.3048, LENGTH, false),
+    def(&["mi", "mile"], 1609.344, LENGTH, false),
+    def(&["nautical_mile"], 1852.0, LENGTH, false),
+    def(&["knot"], 1852.0 / 3600.0, VELOCITY, false),
+    def(
+        &[
+            "deg", "degree", "degrees", "arc_degree", "degree_north", "degrees_north", "degree_N",
+            "degrees_N", "degreeN", "degreesN", "degree_east", "degrees_east", "degree_E", "degrees_E",
+            "degreeE", "degreesE",
+        ],
+        std::f64::consts::PI / 180.0,
+        NONE,
+        false,
+    ),
+    def(&["%", "percent"], 0.01, NONE, false),
+    def(&["ppm"], 1e-6, NONE, false),
+    def(&["ppb"], 1e-9, NONE, false),
+    Definition {
+        names: &[
+            "degC", "deg_C", "degree_C", "degrees_C", "degree_Celsius", "degrees_Celsius", "celsius",
+            "Celsius", "°C",
+        ],
+        scale: 1.0,
+        offset: 273.15,
+        dims: TEMPERATURE,
+        prefixable: false,
+    },
+    Definition {
+        names: &[
+            "degF", "deg_F", "degree_F", "degrees_F", "degree_Fahrenheit", "degrees_Fahrenheit",
+            "fahrenheit", "Fahrenheit", "°F",
+        ],
+        scale: 5.0 / 9.0,
+        offset: 459.67 * 5.0 / 9.0,
+        dims: TEMPERATURE,
+        prefixable: false,
+    },
+];
+
+/// SI prefixes, symbols before names, longer symbols first so "da" wins over "d"
+const PREFIXES: &[(&str, f64)] = &[
+    ("da", 1e1), ("Y", 1e24), ("Z", 1e21), ("E", 1e18), ("P", 1e15), ("T", 1e12), ("G", 1e9),
+    ("M", 1e6), ("k", 1e3), ("h", 1e2), ("d", 1e-1), ("c", 1e-2), ("m", 1e-3), ("u", 1e-6),
+    ("µ", 1e-6), ("n", 1e-9), ("p", 1e-12), ("f", 1e-15), ("a", 1e-18),
+    ("yotta", 1e24), ("zetta", 1e21), ("exa", 1e18), ("peta", 1e15), ("tera", 1e12),
+    ("giga", 1e9), ("mega", 1e6), ("kilo", 1e3), ("hecto", 1e2), ("deka", 1e1), ("deca", 1e1),
+    ("deci", 1e-1), ("centi", 1e-2), ("milli", 1e-3), ("micro", 1e-6), ("nano", 1e-9),
+    ("pico", 1e-12), ("femto", 1e-15), ("atto", 1e-18),
+];
+
+/// A parsed unit: a value `x` in this unit is `x * scale + offset` in SI base units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    pub scale: f64,
+    pub offset: f64,
+    dims: Dims,
+}
+
+impl Unit {
+    fn dimensionless(scale: f64) -> Self {
+        Unit { scale, offset: 0.0, dims: NONE }
+    }
+
+    /// Products and powers are intervals, so an offset (as in degC) only
+    /// survives when the unit stands alone
+    fn multiply(self, other: Unit, exponent: i32) -> Self {
+        let mut dims = self.dims;
+        for (dim, other) in dims.iter_mut().zip(other.dims) {
+            *dim += other * exponent;
+        }
+        Unit { scale: self.scale * other.scale.powi(exponent), offset: 0.0, dims }
+    }
+
+    fn power(self, exponent: i32) -> Self {
+        if exponent == 1 {
+            return self;
+        }
+        Unit::dimensionless(1.0).multiply(self, exponent)
+    }
+
+    /// Whether values in the two units measure the same kind of quantity
+    pub fn is_compatible(&self, other: &Unit) -> bool {
+        self.dims == other.dims
+    }
+
+    /// Whether the dimensions are `base` apart from the time exponent
+    fn matches_over_time(&self, base: &Dims) -> bool {
+        self.dims.iter().zip(base).enumerate().all(|(i, (dim, base))| i == 2 || dim == base)
+    }
+}
+
+/// Base-unit form of dimension exponents, e.g. "kg m-2 s-1"
+fn describe_dims(dims: &Dims) -> String {
+    let terms: Vec<String> = dims
+        .iter()
+        .zip(BASE_SYMBOLS)
+        .filter(|(&exponent, _)| exponent != 0)
+        .map(|(&exponent, symbol)| if exponent == 1 { symbol.to_string() } else { format!("{}{}", symbol, exponent) })
+        .collect();
+    if terms.is_empty() {
+        "1".to_string()
+    } else {
+        terms.join(" ")
+    }
+}
+
+/// Parse a UDUNITS-style unit string
+pub fn parse_unit(text: &str) -> Result<Unit, NetCDFError> {
+    let error = |reason: String| NetCDFError::UnitError(format!("cannot parse \"{}\": {}", text, reason));
+    if text.to_lowercase().contains(" since ") {
+        return Err(error("reference times are not units of measure".to_string()));
+    }
+    let mut parser = Parser { chars: text.trim().chars().collect(), pos: 0 };
+    if parser.chars.is_empty() {
+        return Err(error("it is empty".to_string()));
+    }
+    let unit = parser.product().map_err(error)?;
+    match parser.peek() {
+        None => Ok(unit),
+        Some(c) => Err(error(format!("unexpected '{}'", c))),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    /// Terms joined by juxtaposition, '*', '.' or '·' and '/', left to right
+    fn product(&mut self) -> Result<Unit, String> {
+        self.skip_spaces();
+        let mut unit = self.power()?;
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                None | Some(')') => return Ok(unit),
+                Some('/') => {
+                    self.pos += 1;
+                    self.skip_spaces();
+                    unit = unit.multiply(self.power()?, -1);
+                }
+                Some('*' | '.' | '·') => {
+                    self.pos += 1;
+                    self.skip_spaces();
+                    unit = unit.multiply(self.power()?, 1);
+                }
+                Some(_) => unit = unit.multiply(self.power()?, 1),
+            }
+        }
+    }
+
+    /// A base followed by an optional exponent: "m2", "m-2", "m^2", "m**-2" or "m²"
+    fn power(&mut self) -> Result<Unit, String> {
+        let base = self.base()?;
+        let exponent = match self.peek() {
+            Some('^') => {
+                self.pos += 1;
+                self.integer()?
+            }
+            Some('*') if self.chars.get(self.pos + 1) == Some(&'*') => {
+                self.pos += 2;
+                self.integer()?
+            }
+            Some(c) if c.is_ascii_digit() || ((c == '-' || c == '+') && self.next_is_digit()) => self.integer()?,
+            Some('⁻' | '¹' | '²' | '³') => self.superscript(),
+            _ => 1,
+        };
+        Ok(base.power(exponent))
+    }
+
+    fn next_is_digit(&self) -> bool {
+        self.chars.get(self.pos + 1).is_some_and(|c| c.is_ascii_digit())
+    }
+
+    fn integer(&mut self) -> Result<i32, String> {
+        let start = self.pos;
+        if matches!(self.peek(), Some('-' | '+')) {
+            self.pos += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().map_err(|_| format!("invalid exponent \"{}\"", text))
+    }
+
+    fn superscript(&mut self) -> i32 {
+        let mut sign = 1;
+        if self.peek() == Some('⁻') {
+            sign = -1;
+            self.pos += 1;
+        }
+        let value = match self.peek() {
+            Some('¹') => 1,
+            Some('²') => 2,
+            Some('³') => 3,
+            _ => return sign,
+        };
+        self.pos += 1;
+        sign * value
+    }
+
+    fn base(&mut self) -> Result<Unit, String> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let unit = self.product()?;
+                if self.peek() != Some(')') {
+                    return Err("unclosed parenthesis".to_string());
+                }
+                self.pos += 1;
+                Ok(unit)
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => self.number(),
+            Some(c) if is_name_char(c) => {
+                let start = self.pos;
+                while self.peek().is_some_and(is_name_char) {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                lookup(&name).ok_or_else(|| format!("unknown unit \"{}\"", name))
+            }
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Err("missing unit at the end".to_string()),
+        }
+    }
+
+    /// A numeric factor such as "1000", "0.001" or "1e-3"
+    fn number(&mut self) -> Result<Unit, String> {
+        let start = self.pos;
+        if matches!(self.peek(), Some('-' | '+')) {
+            self.pos += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            let exponent_start = self.pos;
+            self.pos += 1;
+            if matches!(self.peek(), Some('-' | '+')) {
+                self.pos += 1;
+            }
+            if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+            } else {
+                // Not an exponent after all, e.g. "2 em"
+                self.pos = exponent_start;
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().map(Unit::dimensionless).map_err(|_| format!("invalid number \"{}\"", text))
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '%' || c == '°'
+}
+
+/// Resolve a unit name, trying exact spellings, SI prefixes and plurals in that order
+fn lookup(name: &str) -> Option<Unit> {
+    let exact = |name: &str| {
+        DEFINITIONS
+            .iter()
+            .find(|d| d.names.contains(&name))
+            .map(|d| Unit { scale: d.scale, offset: d.offset, dims: d.dims })
+    };
+    let prefixed = |name: &str| {
+        PREFIXES.iter().find_map(|(prefix, factor)| {
+            let rest = name.strip_prefix(prefix).filter(|rest| !rest.is_empty())?;
+            let definition = DEFINITIONS.iter().find(|d| d.prefixable && d.names.contains(&rest))?;
+            Some(Unit { scale: definition.scale * factor, offset: 0.0, dims: definition.dims })
+        })
+    };
+    let singular = name.strip_suffix('s').filter(|s| s.len() > 1);
+    exact(name)
+        .or_else(|| prefixed(name))
+        .or_else(|| singular.and_then(exact))
+        .or_else(|| singular.and_then(prefixed))
+}
+
+/// Linear map from values in one unit to another: `x * scale + offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conversion {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl Conversion {
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+
+    /// Convert a value unless it is one of the `missing` markers, which stay
+    /// as they are so that they are still recognised as missing
+    fn apply_valid(&self, value: f64, missing: &[f64]) -> f64 {
+        if missing.iter().any(|m| (value - m).abs() < 1e-10) {
+            value
+        } else {
+            self.apply(value)
+        }
+    }
+
+    /// Convert numeric data in place, leaving fill and missing values alone;
+    /// text data is left alone
+    pub fn apply_to_data(&self, response: &mut VariableDataResponse, missing: &[f64]) {
+        if let VariableData::Numeric(values) = &mut response.values {
+            for value in values.iter_mut() {
+                *value = self.apply_valid(*value, missing);
+            }
+        }
+    }
+
+    /// Convert statistics; the spread only scales
+    pub fn apply_to_stats(&self, stats: &mut VariableStats) {
+        let (min, max) = (stats.min.map(|v| self.apply(v)), stats.max.map(|v| self.apply(v)));
+        (stats.min, stats.max) = if self.scale < 0.0 { (max, min) } else { (min, max) };
+        stats.mean = stats.mean.map(|v| self.apply(v));
+        stats.std_dev = stats.std_dev.map(|v| v * self.scale.abs());
+    }
+
+    pub fn apply_to_points(&self, points: &mut [DataPoint], missing: &[f64]) {
+        for point in points {
+            point.value = self.apply_valid(point.value, missing);
+        }
+    }
+}
+
+/// How to convert values from `from` units to `to` units.
+///
+/// A mass of water per area and a depth of water over the same time, such as
+/// a precipitation flux in kg m-2 s-1 and a rate in mm/day, or an amount in
+/// kg m-2 and a depth in mm, are converted as water equivalents using the
+/// density of liquid water. Other quantities must have the same dimensions.
+pub fn conversion(from: &str, to: &str) -> Result<Conversion, NetCDFError> {
+    let (source, target) = (parse_unit(from)?, parse_unit(to)?);
+
+    let same_time = source.dims[2] == target.dims[2];
+    let density = if same_time && source.matches_over_time(&WATER_AMOUNT) && target.matches_over_time(&WATER_DEPTH) {
+        1.0 / WATER_DENSITY
+    } else if same_time && source.matches_over_time(&WATER_DEPTH) && target.matches_over_time(&WATER_AMOUNT) {
+        WATER_DENSITY
+    } else if source.is_compatible(&target) {
+        1.0
+    } else {
+        return Err(NetCDFError::UnitError(format!(
+            "cannot convert \"{}\" ({}) to \"{}\" ({})",
+            from,
+            describe_dims(&source.dims),
+            to,
+            describe_dims(&target.dims)
+        )));
+    };
+
+    // Offsets only apply between temperatures on different scales
+    let offset = if density == 1.0 { (source.offset - target.offset) / target.scale } else { 0.0 };
+    Ok(Conversion { scale: source.scale * density / target.scale, offset })
+}
+
+/// Conversion of a variable's values from its `units` attribute to `target`
+pub fn variable_conversion(metadata: &FileMetadata, var_name: &str, target: &str) -> Result<Conversion, NetCDFError> {
+    let variable = find_variable(metadata, var_name)?;
+    let units = variable
+        .attributes
+        .get("units")
+        .ok_or_else(|| NetCDFError::UnitError(format!("'{}' has no units attribute to convert from", var_name)))?;
+    conversion(units, target)
+}
+
+/// Values read from a variable that can be converted to other units.
+///
+/// `missing` holds the variable's fill and missing values, which raw reads
+/// may contain and which must not be converted.
+pub trait Convert {
+    fn convert(&mut self, conversion: &Conversion, missing: &[f64]);
+}
+
+impl Convert for VariableDataResponse {
+    fn convert(&mut self, conversion: &Conversion, missing: &[f64]) {
+        conversion.apply_to_data(self, missing);
+    }
+}
+
+impl Convert for VariableStats {
+    /// Statistics are computed from masked data, so there is nothing to skip
+    fn convert(&mut self, conversion: &Conversion, _missing: &[f64]) {
+        conversion.apply_to_stats(self);
+    }
+}
+
+impl Convert for Vec<DataPoint> {
+    fn convert(&mut self, conversion: &Conversion, missing: &[f64]) {
+        conversion.apply_to_points(self, missing);
+    }
+}
+
+/// `_FillValue` and `missing_value`s of a variable
+fn missing_values(dataset: &dyn DatasetBackend, var_name: &str) -> Result<Vec<f64>, NetCDFError> {
+    let mut missing: Vec<f64> = dataset.fill_value(var_name).into_iter().collect();
+    for (name, value) in dataset.attributes(Some(var_name))? {
+        if name == "_FillValue" || name == "missing_value" {
+            missing.extend(attribute_numbers(&value));
+        }
+    }
+    Ok(missing)
+}
+
+/// Open `path` once, run `read` on it and, when `target` units are given,
+/// convert the result from the units of `var_name`. The conversion is checked
+/// before anything is read.
+pub fn read_in_units<T: Convert>(
+    path: &str,
+    var_name: &str,
+    target: Option<&str>,
+    read: impl FnOnce(&dyn DatasetBackend) -> Result<T, NetCDFError>,
+) -> Result<T, NetCDFError> {
+    let dataset = open_dataset(path)?;
+    let conversion = match target {
+        Some(target) => Some(variable_conversion(&dataset.metadata()?, var_name, target)?),
+        None => None,
+    };
+    let mut result = read(dataset.as_ref())?;
+    if let Some(conversion) = conversion {
+        result.convert(&conversion, &missing_values(dataset.as_ref(), var_name)?);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(value: f64, from: &str, to: &str) -> f64 {
+        conversion(from, to).unwrap().apply(value)
+    }
+
+    #[test]
+    fn test_parse_unit_spellings() {
+        let flux = parse_unit("kg m-2 s-1").unwrap();
+        for spelling in ["kg.m-2.s-1", "kg m^-2 s^-1", "kg*m**-2*s**-1", "kg/m2/s", "kg/(m2 s)", "kg m⁻² s⁻¹"] {
+            let unit = parse_unit(spelling).unwrap();
+            assert_eq!(unit.dims, flux.dims, "{}", spelling);
+            assert!((unit.scale - flux.scale).abs() < 1e-12, "{}", spelling);
+        }
+        assert_eq!(parse_unit("hPa").unwrap().scale, 100.0);
+        assert_eq!(parse_unit("ms").unwrap().scale, 1e-3);
+        assert_eq!(parse_unit("days").unwrap().scale, 86400.0);
+        assert_eq!(parse_unit("1e-3 kilometers").unwrap().scale, 1.0);
+        assert_eq!(describe_dims(&parse_unit("W m-2").unwrap().dims), "kg s-3");
+        assert!(parse_unit("furlongs").is_err());
+        assert!(parse_unit("days since 2000-01-01").is_err());
+        assert!(parse_unit("kg/(m2").is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert!((convert(273.15, "K", "degC")).abs() < 1e-9);
+        assert!((convert(100.0, "degC", "degF") - 212.0).abs() < 1e-9);
+        assert!((convert(1013.25, "hPa", "Pa") - 101325.0).abs() < 1e-9);
+        assert!((convert(10.0, "m s-1", "km/h") - 36.0).abs() < 1e-9);
+        // Water equivalents: 1 kg m-2 s-1 is 86400 mm/day, 1 m of water is 1000 kg m-2
+        assert!((convert(1.0, "kg m-2 s-1", "mm/day") - 86400.0).abs() < 1e-6);
+        assert!((convert(1.0, "m", "kg m-2") - 1000.0).abs() < 1e-9);
+        // Temperature differences have no offset
+        assert!((convert(1.0, "degC/day", "K/day") - 1.0).abs() < 1e-12);
+        assert!((convert(50.0, "%", "1") - 0.5).abs() < 1e-12);
+
+        let error = conversion("K", "m").unwrap_err().to_string();
+        assert!(error.contains("cannot convert \"K\" (K) to \"m\" (m)"), "{}", error);
+        // Other mass and volume differences are not water equivalents
+        for (from, to) in [("kg m-3", "1"), ("kg", "m3"), ("kg m-2 s-1", "mm"), ("kg m-2 K", "mm")] {
+            assert!(conversion(from, to).is_err(), "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn test_fill_values_are_not_converted() {
+        use crate::netcdf::test_util::write_array;
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("filled.zarr");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".zgroup"), r#"{"zarr_format": 2}"#).unwrap();
+        let values = [273.15f32, -9999.0, 283.15, 1e20];
+        let attrs = r#""units": "K", "_FillValue": 1e20, "missing_value": -9999.0"#;
+        write_array(&root, "tas", &["x"], &[4], "<f4", attrs, values.iter().flat_map(|v| v.to_le_bytes()).collect());
+
+        let path = root.to_str().unwrap();
+        let response = read_in_units(path, "tas", Some("degC"), |dataset| {
+            crate::netcdf::data_access::read_variable_data(dataset, "tas")
+        })
+        .unwrap();
+        let VariableData::Numeric(values) = response.values else { panic!("tas is numeric") };
+        assert!(values[0].abs() < 1e-4 && (values[2] - 10.0).abs() < 1e-4, "{:?}", values);
+        assert_eq!((values[1], values[3] as f32), (-9999.0, 1e20));
+    }
+}
@@ -0,0 +1,79 @@
+//! Walking directory trees of datasets.
+//!
+//! The catalog scan and the file watcher both look below a directory that
+//! may contain linked directories. They share this walker, which follows
+//! links but reads each directory once, so a link back up the tree cannot
+//! send either of them round in circles.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Call `visit` for every entry below `dir`, in path order within each
+/// directory. `visit` returns whether to descend when the entry is a
+/// directory; directories that cannot be read are passed to `failed`.
+pub(crate) fn walk_dir(
+    dir: &Path,
+    visit: &mut dyn FnMut(&Path) -> bool,
+    failed: &mut dyn FnMut(&Path, std::io::Error),
+) {
+    walk_within(dir, &mut HashSet::new(), visit, failed);
+}
+
+/// `visited` holds the canonical paths of the directories already read
+fn walk_within(
+    dir: &Path,
+    visited: &mut HashSet<PathBuf>,
+    visit: &mut dyn FnMut(&Path) -> bool,
+    failed: &mut dyn FnMut(&Path, std::io::Error),
+) {
+    if !visited.insert(std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())) {
+        return;
+    }
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            failed(dir, e);
+            return;
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect();
+    paths.sort();
+
+    for path in paths {
+        if visit(&path) && path.is_dir() {
+            walk_within(&path, visited, visit, failed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_each_directory_once() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::write(dir.join("a/b/file.nc"), b"").unwrap();
+        std::fs::write(dir.join("top.nc"), b"").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir, dir.join("a/b/loop")).unwrap();
+
+        let mut seen = Vec::new();
+        walk_dir(
+            dir,
+            &mut |path| {
+                seen.push(path.strip_prefix(dir).unwrap().to_path_buf());
+                true
+            },
+            &mut |path, e| panic!("{}: {}", path.display(), e),
+        );
+        let mut expected = vec!["a", "a/b", "a/b/file.nc"];
+        if cfg!(unix) {
+            expected.push("a/b/loop");
+        }
+        expected.push("top.nc");
+        assert_eq!(seen, expected.iter().map(PathBuf::from).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,296 @@
+//! Watching files that grow while a model writes them.
+//!
+//! A watch polls the size and modification time of a dataset (or of every
+//! dataset under a directory) and reopens only the ones whose stamp changed,
+//! reporting the dimensions whose length differs from the previous look.
+//! Data reads open datasets afresh for every request; only the metadata of
+//! the files shown in the viewer is kept, in [`OpenFiles`], and an event
+//! drops the entry of the file it is about so the next open rereads it.
+
+use crate::errors::NetCDFError;
+use crate::models::{Dimension, FileMetadata, WatchChange, WatchEvent};
+use super::backend::open_dataset;
+use super::coordinates::detect_coordinates;
+use super::catalog::find_datasets;
+use super::walk::walk_dir;
+use super::zarr::ZarrStore;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// Poll interval used when none is given
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Total size, entry count and newest modification time of a file or store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    size: u64,
+    entries: usize,
+    modified: Option<SystemTime>,
+}
+
+impl Stamp {
+    fn read(path: &Path) -> Option<Stamp> {
+        let meta = std::fs::metadata(path).ok()?;
+        let mut stamp = Stamp {
+            size: if meta.is_dir() { 0 } else { meta.len() },
+            entries: 1,
+            modified: meta.modified().ok(),
+        };
+        if meta.is_dir() {
+            // Zarr writers add chunks and rewrite .zarray files deep inside the store
+            walk_dir(
+                path,
+                &mut |entry| {
+                    if let Ok(meta) = std::fs::metadata(entry) {
+                        stamp.size += if meta.is_dir() { 0 } else { meta.len() };
+                        stamp.entries += 1;
+                        stamp.modified = stamp.modified.max(meta.modified().ok());
+                    }
+                    true
+                },
+                &mut |_, _| {},
+            );
+        }
+        Some(stamp)
+    }
+}
+
+struct Tracked {
+    stamp: Stamp,
+    dimensions: Vec<Dimension>,
+}
+
+/// Last known state of a watched file or directory
+pub struct WatchState {
+    root: PathBuf,
+    /// Whether `root` is a directory of datasets rather than a dataset itself
+    directory: bool,
+    files: BTreeMap<PathBuf, Tracked>,
+    /// Whether the first poll, which only records what exists, has run
+    primed: bool,
+}
+
+impl WatchState {
+    /// Record the current state of `path` without reporting anything
+    pub fn new(path: &str) -> Result<Self, NetCDFError> {
+        let root = PathBuf::from(path);
+        if !root.exists() {
+            return Err(NetCDFError::FileOpenError(format!("File not found: {}", path)));
+        }
+        let directory = root.is_dir() && !ZarrStore::is_store(&root);
+        let mut state = WatchState { root, directory, files: BTreeMap::new(), primed: false };
+        state.poll();
+        Ok(state)
+    }
+
+    /// Compare against the last poll and report what changed since
+    pub fn poll(&mut self) -> Vec<WatchEvent> {
+        let report = self.primed;
+        self.primed = true;
+        let mut events = Vec::new();
+
+        let present = self.datasets();
+        let removed: Vec<PathBuf> =
+            self.files.keys().filter(|path| !present.contains(path)).cloned().collect();
+        for path in removed {
+            self.files.remove(&path);
+            events.push(WatchEvent {
+                path: path.to_string_lossy().into_owned(),
+                change: WatchChange::Removed,
+                dimensions: Vec::new(),
+            });
+        }
+
+        for path in present {
+            let Some(stamp) = Stamp::read(&path) else { continue };
+            let previous = self.files.get(&path);
+            if previous.is_some_and(|tracked| tracked.stamp == stamp) {
+                continue;
+            }
+            // A file caught halfway through a write fails to open; it is
+            // retried on the next poll because its stamp is not recorded
+            let Ok(dimensions) = read_dimensions(&path) else { continue };
+
+            let (change, changed) = match previous {
+                Some(tracked) => {
+                    let changed: Vec<Dimension> = dimensions
+                        .iter()
+                        .filter(|dim| !tracked.dimensions.iter().any(|old| old.name == dim.name && old.size == dim.size))
+                        .cloned()
+                        .collect();
+                    (WatchChange::Changed, changed)
+                }
+                None => (WatchChange::Created, dimensions.clone()),
+            };
+            if report {
+                events.push(WatchEvent { path: path.to_string_lossy().into_owned(), change, dimensions: changed });
+            }
+            self.files.insert(path, Tracked { stamp, dimensions });
+        }
+        events
+    }
+
+    fn datasets(&self) -> Vec<PathBuf> {
+        if !self.directory {
+            return if self.root.exists() { vec![self.root.clone()] } else { Vec::new() };
+        }
+        let mut found = Vec::new();
+        find_datasets(&self.root, &mut found, &mut Vec::new());
+        found
+    }
+}
+
+fn read_dimensions(path: &Path) -> Result<Vec<Dimension>, NetCDFError> {
+    Ok(open_dataset(&path.to_string_lossy())?.metadata()?.dimensions)
+}
+
+/// Metadata of the files open in the viewer, with their coordinates
+/// detected, read once and kept until the file is closed or a watch event
+/// reports that it changed
+#[derive(Default)]
+pub struct OpenFiles {
+    files: Mutex<HashMap<String, FileMetadata>>,
+}
+
+impl OpenFiles {
+    /// Metadata of `path`, opening the file unless it is already open
+    pub fn open(&self, path: &str) -> Result<FileMetadata, NetCDFError> {
+        if let Some(metadata) = self.files.lock().unwrap_or_else(|e| e.into_inner()).get(path) {
+            return Ok(metadata.clone());
+        }
+        let mut metadata = open_dataset(path)?.metadata()?;
+        metadata.coordinates = Some(detect_coordinates(&metadata));
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).insert(path.to_string(), metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Forget a file, so the next [`OpenFiles::open`] reads it again
+    pub fn close(&self, path: &str) {
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).remove(path);
+    }
+
+    /// Forget the file an event is about; a created file was not open yet
+    pub fn refresh(&self, event: &WatchEvent) {
+        if event.change != WatchChange::Created {
+            self.close(&event.path);
+        }
+    }
+}
+
+/// Background thread polling a [`WatchState`]; stops when dropped
+pub struct Watcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    /// Watch `path` and call `on_event` for every change found
+    pub fn start<F>(path: &str, interval: Duration, mut on_event: F) -> Result<Self, NetCDFError>
+    where
+        F: FnMut(WatchEvent) + Send + 'static,
+    {
+        let mut state = WatchState::new(path)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let thread = std::thread::spawn(move || {
+            // Sleep in short steps so dropping the watcher does not wait a whole interval
+            let step = interval.min(Duration::from_millis(100));
+            let mut waited = Duration::ZERO;
+            while !stopped.load(Ordering::Relaxed) {
+                std::thread::sleep(step);
+                waited += step;
+                if waited < interval {
+                    continue;
+                }
+                waited = Duration::ZERO;
+                for event in state.poll() {
+                    on_event(event);
+                }
+            }
+        });
+        Ok(Watcher { stop, thread: Some(thread) })
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netcdf::test_util::write_store;
+
+    #[test]
+    fn test_reports_appended_records() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let run = dir.join("run.zarr");
+        write_store(&run, "days since 2000-01-01", &[0.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+
+        let mut state = WatchState::new(&run.to_string_lossy()).unwrap();
+        assert!(state.poll().is_empty());
+
+        write_store(&run, "days since 2000-01-01", &[0.0, 1.0, 2.0], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let events = state.poll();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].change, WatchChange::Changed);
+        assert_eq!(events[0].dimensions.len(), 1);
+        assert_eq!(events[0].dimensions[0].name, "time");
+        assert_eq!(events[0].dimensions[0].size, 3);
+        assert!(state.poll().is_empty());
+    }
+
+    #[test]
+    fn test_changed_files_are_reopened() {
+        let temp = tempfile::tempdir().unwrap();
+        let run = temp.path().join("run.zarr");
+        let path = run.to_string_lossy().into_owned();
+        write_store(&run, "days since 2000-01-01", &[0.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+        let time_size = |metadata: FileMetadata| metadata.dimensions.iter().find(|d| d.name == "time").unwrap().size;
+
+        let files = OpenFiles::default();
+        let mut state = WatchState::new(&path).unwrap();
+        assert_eq!(time_size(files.open(&path).unwrap()), 2);
+
+        // Kept until the watch sees the change
+        write_store(&run, "days since 2000-01-01", &[0.0, 1.0, 2.0], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(time_size(files.open(&path).unwrap()), 2);
+        for event in state.poll() {
+            files.refresh(&event);
+        }
+        assert_eq!(time_size(files.open(&path).unwrap()), 3);
+    }
+
+    #[test]
+    fn test_reports_new_and_removed_files_in_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write_store(&dir.join("day1.zarr"), "days since 2000-01-01", &[0.0], &[1.0, 2.0]);
+
+        let mut state = WatchState::new(&dir.to_string_lossy()).unwrap();
+        assert!(state.poll().is_empty());
+
+        write_store(&dir.join("day2.zarr"), "days since 2000-01-02", &[0.0], &[3.0, 4.0]);
+        let events = state.poll();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].change, WatchChange::Created);
+        assert!(events[0].path.ends_with("day2.zarr"));
+        assert_eq!(events[0].dimensions.len(), 2);
+
+        std::fs::remove_dir_all(dir.join("day1.zarr")).unwrap();
+        let events = state.poll();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].change, WatchChange::Removed);
+        assert!(events[0].path.ends_with("day1.zarr"));
+    }
+}
@@ -0,0 +1,1005 @@
+use crate::errors::NetCDFError;
+use crate::models::{Dimension, FileMetadata, Variable};
+use super::backend::{numeric_type_name, DatasetBackend, NumberKind};
+use super::codecs::Codec;
+use super::data_access::NativeValues;
+use super::loader::numbers_attribute;
+use super::object_store::ObjectLocation;
+use super::table::next_index;
+use netcdf::AttributeValue;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Attribute xarray uses to record the dimension names of a Zarr v2 array
+const ARRAY_DIMENSIONS: &str = "_ARRAY_DIMENSIONS";
+
+/// Files whose presence marks a Zarr store or array
+const METADATA_KEYS: [&str; 4] = [".zgroup", ".zarray", ".zmetadata", "zarr.json"];
+
+/// Directory of a store, group or array: local or in object storage
+#[derive(Debug, Clone)]
+enum StoreDir {
+    Local(PathBuf),
+    Object(ObjectLocation),
+}
+
+impl StoreDir {
+    fn join(&self, name: &str) -> StoreDir {
+        match self {
+            StoreDir::Local(path) => StoreDir::Local(path.join(name)),
+            StoreDir::Object(location) => StoreDir::Object(location.join(name)),
+        }
+    }
+
+    /// Contents of a file in the directory, or `None` if it does not exist
+    fn read(&self, key: &str) -> std::io::Result<Option<Vec<u8>>> {
+        match self {
+            StoreDir::Local(path) => match std::fs::read(path.join(key)) {
+                Ok(data) => Ok(Some(data)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            },
+            StoreDir::Object(location) => location.join(key).get(),
+        }
+    }
+
+    /// Names of the subdirectories
+    fn child_dirs(&self) -> Result<Vec<String>, NetCDFError> {
+        match self {
+            StoreDir::Local(path) => {
+                let mut names = Vec::new();
+                for entry in std::fs::read_dir(path)? {
+                    let entry = entry?;
+                    if entry.file_type()?.is_dir() {
+                        names.push(entry.file_name().to_string_lossy().to_string());
+                    }
+                }
+                Ok(names)
+            }
+            StoreDir::Object(location) => Ok(location.child_dirs()?),
+        }
+    }
+
+    /// Name for a store that is a single array: the directory name without ".zarr"
+    fn store_name(&self) -> String {
+        let name = match self {
+            StoreDir::Local(path) => path.file_name().map(|n| n.to_string_lossy().to_string()),
+            StoreDir::Object(location) => Some(location.name().to_string()).filter(|n| !n.is_empty()),
+        };
+        name.map(|n| n.trim_end_matches(".zarr").to_string())
+            .unwrap_or_else(|| "data".to_string())
+    }
+
+    fn display(&self, key: &str) -> String {
+        match self {
+            StoreDir::Local(path) => path.join(key).display().to_string(),
+            StoreDir::Object(location) => location.join(key).key,
+        }
+    }
+}
+
+/// Kind of element stored in a Zarr array
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ElementKind {
+    Bool,
+    Int,
+    Uint,
+    Float,
+    /// Fixed-length byte strings (NumPy "S")
+    Bytes,
+    /// Fixed-length UTF-32 strings (NumPy "U")
+    Unicode,
+}
+
+/// Element type of a Zarr array
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ElementType {
+    kind: ElementKind,
+    /// Size of one element in bytes
+    size: usize,
+    big_endian: bool,
+}
+
+impl ElementType {
+    /// Parse a Zarr v2 (NumPy) dtype such as "<f4", "|u1" or "|S8"
+    fn from_v2(dtype: &str) -> Option<Self> {
+        let mut chars = dtype.chars();
+        let big_endian = match chars.next()? {
+            '>' => true,
+            '<' | '|' | '=' => false,
+            _ => return None,
+        };
+        let kind = match chars.next()? {
+            'b' => ElementKind::Bool,
+            'i' => ElementKind::Int,
+            'u' => ElementKind::Uint,
+            'f' => ElementKind::Float,
+            'S' => ElementKind::Bytes,
+            'U' => ElementKind::Unicode,
+            _ => return None,
+        };
+        let count: usize = chars.as_str().parse().ok()?;
+        let size = if kind == ElementKind::Unicode { count * 4 } else { count };
+
+        let element = ElementType { kind, size, big_endian };
+        element.is_supported().then_some(element)
+    }
+
+    /// Parse a Zarr v3 data type name such as "float32"
+    fn from_v3(name: &str, big_endian: bool) -> Option<Self> {
+        let (kind, size) = match name {
+            "bool" => (ElementKind::Bool, 1),
+            "int8" => (ElementKind::Int, 1),
+            "int16" => (ElementKind::Int, 2),
+            "int32" => (ElementKind::Int, 4),
+            "int64" => (ElementKind::Int, 8),
+            "uint8" => (ElementKind::Uint, 1),
+            "uint16" => (ElementKind::Uint, 2),
+            "uint32" => (ElementKind::Uint, 4),
+            "uint64" => (ElementKind::Uint, 8),
+            "float32" => (ElementKind::Float, 4),
+            "float64" => (ElementKind::Float, 8),
+            _ => return None,
+        };
+        Some(ElementType { kind, size, big_endian })
+    }
+
+    fn is_supported(&self) -> bool {
+        match self.kind {
+            ElementKind::Bool => self.size == 1,
+            ElementKind::Int | ElementKind::Uint => matches!(self.size, 1 | 2 | 4 | 8),
+            ElementKind::Float => matches!(self.size, 4 | 8),
+            ElementKind::Bytes | ElementKind::Unicode => self.size > 0,
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self.kind, ElementKind::Bytes | ElementKind::Unicode)
+    }
+
+    /// Data type name for the models; booleans read as unsigned bytes
+    fn netcdf_name(&self) -> &'static str {
+        match self.kind {
+            ElementKind::Bool => numeric_type_name(NumberKind::Unsigned, 1),
+            ElementKind::Int => numeric_type_name(NumberKind::Signed, self.size),
+            ElementKind::Uint => numeric_type_name(NumberKind::Unsigned, self.size),
+            ElementKind::Float => numeric_type_name(NumberKind::Float, self.size),
+            ElementKind::Bytes | ElementKind::Unicode => "String",
+        }
+    }
+
+    /// Encode a fill value as one element
+    fn encode(&self, value: Option<f64>) -> Vec<u8> {
+        let Some(value) = value.filter(|_| !self.is_text()) else {
+            return vec![0; self.size];
+        };
+
+        let (le, be): (Vec<u8>, Vec<u8>) = match self.kind {
+            ElementKind::Float if self.size == 4 => {
+                ((value as f32).to_le_bytes().to_vec(), (value as f32).to_be_bytes().to_vec())
+            }
+            ElementKind::Float => (value.to_le_bytes().to_vec(), value.to_be_bytes().to_vec()),
+            ElementKind::Int => {
+                let v = value as i64;
+                (v.to_le_bytes()[..self.size].to_vec(), v.to_be_bytes()[8 - self.size..].to_vec())
+            }
+            _ => {
+                let v = value as u64;
+                (v.to_le_bytes()[..self.size].to_vec(), v.to_be_bytes()[8 - self.size..].to_vec())
+            }
+        };
+        if self.big_endian { be } else { le }
+    }
+}
+
+/// How chunk indices map to keys (file paths) inside an array directory
+#[derive(Debug, Clone, PartialEq)]
+struct ChunkKeyEncoding {
+    /// Zarr v3 "default" encoding prefixes keys with "c"
+    prefixed: bool,
+    separator: char,
+}
+
+impl ChunkKeyEncoding {
+    fn key(&self, index: &[usize]) -> String {
+        let parts: Vec<String> = index.iter().map(|i| i.to_string()).collect();
+        let joined = parts.join(&self.separator.to_string());
+
+        match (self.prefixed, index.is_empty()) {
+            (true, true) => "c".to_string(),
+            (true, false) => format!("c{}{}", self.separator, joined),
+            (false, true) => "0".to_string(),
+            (false, false) => joined,
+        }
+    }
+}
+
+/// One array of a Zarr store
+#[derive(Debug, Clone)]
+struct ZarrArray {
+    name: String,
+    dir: StoreDir,
+    shape: Vec<usize>,
+    chunks: Vec<usize>,
+    element: ElementType,
+    fill_value: Option<f64>,
+    /// Bytes-to-bytes codecs in encoding order
+    codecs: Vec<Codec>,
+    key_encoding: ChunkKeyEncoding,
+    dimensions: Vec<String>,
+    attributes: HashMap<String, String>,
+    /// The attributes with the types of their JSON values
+    typed_attributes: Vec<(String, AttributeValue)>,
+}
+
+impl ZarrArray {
+    /// Build an array from Zarr v2 `.zarray` and `.zattrs` documents
+    fn from_v2(name: &str, dir: StoreDir, zarray: &Value, zattrs: Option<&Value>) -> Result<Self, NetCDFError> {
+        let invalid = |what: &str| invalid_metadata(name, what);
+
+        let shape = usize_list(zarray.get("shape")).ok_or_else(|| invalid("shape"))?;
+        let chunks = usize_list(zarray.get("chunks")).ok_or_else(|| invalid("chunks"))?;
+        let element = zarray
+            .get("dtype")
+            .and_then(Value::as_str)
+            .and_then(ElementType::from_v2)
+            .ok_or_else(|| invalid("dtype"))?;
+
+        if zarray.get("order").and_then(Value::as_str).unwrap_or("C") != "C" {
+            return Err(invalid("order (only C order is supported)"));
+        }
+        if zarray.get("filters").and_then(Value::as_array).is_some_and(|f| !f.is_empty()) {
+            return Err(invalid("filters (numcodecs filters are not supported)"));
+        }
+
+        let codecs = match zarray.get("compressor") {
+            Some(Value::Object(compressor)) => {
+                let id = compressor.get("id").and_then(Value::as_str).ok_or_else(|| invalid("compressor"))?;
+                vec![Codec::from_name(id)?]
+            }
+            _ => vec![],
+        };
+
+        let separator = match zarray.get("dimension_separator").and_then(Value::as_str) {
+            Some("/") => '/',
+            _ => '.',
+        };
+
+        let mut attributes = attribute_map(zattrs);
+        let mut typed_attributes = typed_attributes(zattrs);
+        typed_attributes.retain(|(name, _)| name != ARRAY_DIMENSIONS);
+        let dimensions = match attributes.remove(ARRAY_DIMENSIONS) {
+            Some(_) => string_list(zattrs.and_then(|a| a.get(ARRAY_DIMENSIONS)))
+                .ok_or_else(|| invalid(ARRAY_DIMENSIONS))?,
+            None => default_dimensions(name, shape.len()),
+        };
+
+        ZarrArray {
+            name: name.to_string(),
+            dir,
+            shape,
+            chunks,
+            element,
+            fill_value: parse_fill_value(zarray.get("fill_value")),
+            codecs,
+            key_encoding: ChunkKeyEncoding { prefixed: false, separator },
+            dimensions,
+            attributes,
+            typed_attributes,
+        }
+        .validated()
+    }
+
+    /// Build an array from a Zarr v3 `zarr.json` document
+    fn from_v3(name: &str, dir: StoreDir, meta: &Value) -> Result<Self, NetCDFError> {
+        let invalid = |what: &str| invalid_metadata(name, what);
+
+        let shape = usize_list(meta.get("shape")).ok_or_else(|| invalid("shape"))?;
+
+        let grid = meta.get("chunk_grid").ok_or_else(|| invalid("chunk_grid"))?;
+        if grid.get("name").and_then(Value::as_str) != Some("regular") {
+            return Err(invalid("chunk_grid (only regular grids are supported)"));
+        }
+        let chunks = usize_list(grid.pointer("/configuration/chunk_shape")).ok_or_else(|| invalid("chunk_shape"))?;
+
+        let key_encoding = match meta.get("chunk_key_encoding") {
+            Some(encoding) => {
+                let prefixed = encoding.get("name").and_then(Value::as_str) != Some("v2");
+                let separator = match encoding.pointer("/configuration/separator").and_then(Value::as_str) {
+                    Some(".") => '.',
+                    Some(_) => '/',
+                    None if prefixed => '/',
+                    None => '.',
+                };
+                ChunkKeyEncoding { prefixed, separator }
+            }
+            None => ChunkKeyEncoding { prefixed: true, separator: '/' },
+        };
+
+        let mut big_endian = false;
+        let mut codecs = Vec::new();
+        for codec in meta.get("codecs").and_then(Value::as_array).ok_or_else(|| invalid("codecs"))? {
+            let codec_name = codec.get("name").and_then(Value::as_str).ok_or_else(|| invalid("codecs"))?;
+            match codec_name {
+                "bytes" => {
+                    big_endian = codec.pointer("/configuration/endian").and_then(Value::as_str) == Some("big");
+                }
+                "transpose" | "sharding_indexed" => {
+                    return Err(invalid(&format!("codecs ('{}' is not supported)", codec_name)));
+                }
+                other => codecs.push(Codec::from_name(other)?),
+            }
+        }
+
+        let element = meta
+            .get("data_type")
+            .and_then(Value::as_str)
+            .and_then(|t| ElementType::from_v3(t, big_endian))
+            .ok_or_else(|| invalid("data_type"))?;
+
+        let dimensions = match meta.get("dimension_names").and_then(Value::as_array) {
+            Some(names) => names
+                .iter()
+                .enumerate()
+                .map(|(i, n)| n.as_str().map(str::to_string).unwrap_or_else(|| format!("{}_dim_{}", name, i)))
+                .collect(),
+            None => default_dimensions(name, shape.len()),
+        };
+
+        ZarrArray {
+            name: name.to_string(),
+            dir,
+            shape,
+            chunks,
+            element,
+            fill_value: parse_fill_value(meta.get("fill_value")),
+            codecs,
+            key_encoding,
+            dimensions,
+            attributes: attribute_map(meta.get("attributes")),
+            typed_attributes: typed_attributes(meta.get("attributes")),
+        }
+        .validated()
+    }
+
+    /// Check shapes agree and record the fill value as `_FillValue`
+    fn validated(mut self) -> Result<Self, NetCDFError> {
+        if self.chunks.len() != self.shape.len()
+            || self.dimensions.len() != self.shape.len()
+            || self.chunks.contains(&0)
+        {
+            return Err(invalid_metadata(&self.name, "shape, chunks and dimensions disagree"));
+        }
+
+        if let Some(fv) = self.attributes.get("_FillValue").and_then(|v| v.parse::<f64>().ok()) {
+            self.fill_value = Some(fv);
+        }
+        if self.element.is_text() {
+            self.fill_value = None;
+        } else if let Some(fv) = self.fill_value {
+            self.attributes.entry("_FillValue".to_string()).or_insert_with(|| fv.to_string());
+            // The fill value has the array's type, however the JSON spelled it
+            self.typed_attributes.retain(|(name, _)| name != "_FillValue");
+            self.typed_attributes.push(("_FillValue".to_string(), numbers_attribute(self.element.netcdf_name(), &[fv])));
+        }
+
+        Ok(self)
+    }
+
+    fn variable(&self) -> Variable {
+        Variable {
+            name: self.name.clone(),
+            data_type: self.element.netcdf_name().to_string(),
+            dimensions: self.dimensions.clone(),
+            shape: self.shape.clone(),
+            attributes: self.attributes.clone(),
+        }
+    }
+
+    /// Read and decode one chunk; `None` if it was never written
+    fn read_chunk(&self, index: &[usize]) -> Result<Option<Vec<u8>>, NetCDFError> {
+        let key = self.key_encoding.key(index);
+        let mut data = match self.dir.read(&key) {
+            Ok(Some(data)) => data,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(NetCDFError::VariableReadError(self.name.clone(), format!("chunk {}: {}", key, e))),
+        };
+
+        for codec in self.codecs.iter().rev() {
+            data = codec.decode(data)?;
+        }
+
+        let expected = self.chunks.iter().product::<usize>() * self.element.size;
+        if data.len() != expected {
+            return Err(NetCDFError::VariableReadError(
+                self.name.clone(),
+                format!("chunk {} decoded to {} bytes, expected {}", key, data.len(), expected),
+            ));
+        }
+
+        Ok(Some(data))
+    }
+
+    /// Read a hyperslab as raw element bytes in C order
+    fn read_bytes(&self, start: &[usize], count: &[usize]) -> Result<Vec<u8>, NetCDFError> {
+        let ndims = self.shape.len();
+        if start.len() != ndims
+            || count.len() != ndims
+            || (0..ndims).any(|d| start[d] + count[d] > self.shape[d])
+        {
+            return Err(NetCDFError::InvalidSubsetRequest(format!(
+                "Hyperslab start={:?} count={:?} does not fit variable '{}' of shape {:?}",
+                start, count, self.name, self.shape
+            )));
+        }
+
+        let size = self.element.size;
+        let total: usize = count.iter().product();
+        let mut out = self.element.encode(self.fill_value).repeat(total);
+        if total == 0 {
+            return Ok(out);
+        }
+        if ndims == 0 {
+            if let Some(chunk) = self.read_chunk(&[])? {
+                out.copy_from_slice(&chunk[..size]);
+            }
+            return Ok(out);
+        }
+
+        let out_strides = strides(count);
+        let chunk_strides = strides(&self.chunks);
+        let first: Vec<usize> = (0..ndims).map(|d| start[d] / self.chunks[d]).collect();
+        let chunk_counts: Vec<usize> = (0..ndims)
+            .map(|d| (start[d] + count[d] - 1) / self.chunks[d] - first[d] + 1)
+            .collect();
+
+        let mut chunk_offset = vec![0; ndims];
+        loop {
+            let chunk_index: Vec<usize> = (0..ndims).map(|d| first[d] + chunk_offset[d]).collect();
+
+            if let Some(chunk) = self.read_chunk(&chunk_index)? {
+                // Intersection of the chunk with the hyperslab, in array coordinates
+                let lo: Vec<usize> = (0..ndims)
+                    .map(|d| start[d].max(chunk_index[d] * self.chunks[d]))
+                    .collect();
+                let hi: Vec<usize> = (0..ndims)
+                    .map(|d| (start[d] + count[d]).min((chunk_index[d] + 1) * self.chunks[d]))
+                    .collect();
+
+                // Copy contiguous runs along the last dimension
+                let run = hi[ndims - 1] - lo[ndims - 1];
+                let mut row_count: Vec<usize> = (0..ndims).map(|d| hi[d] - lo[d]).collect();
+                row_count[ndims - 1] = 1;
+                let mut row = vec![0; ndims];
+                loop {
+                    let mut src = 0;
+                    let mut dst = 0;
+                    for d in 0..ndims {
+                        let i = lo[d] + row[d];
+                        src += (i - chunk_index[d] * self.chunks[d]) * chunk_strides[d];
+                        dst += (i - start[d]) * out_strides[d];
+                    }
+                    out[dst * size..(dst + run) * size].copy_from_slice(&chunk[src * size..(src + run) * size]);
+
+                    if !next_index(&mut row, &row_count) {
+                        break;
+                    }
+                }
+            }
+
+            if !next_index(&mut chunk_offset, &chunk_counts) {
+                break;
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+/// A Zarr v2 or v3 store in a local directory or under an object storage prefix
+pub struct ZarrStore {
+    path: String,
+    global_attrs: Option<Value>,
+    arrays: Vec<ZarrArray>,
+}
+
+impl ZarrStore {
+    /// Whether a path is a directory holding Zarr metadata
+    pub fn is_store(path: &Path) -> bool {
+        path.is_dir()
+            && METADATA_KEYS.iter().any(|name| path.join(name).is_file())
+    }
+
+    /// Whether an object storage prefix holds Zarr metadata
+    pub fn is_object_store(location: &ObjectLocation) -> bool {
+        METADATA_KEYS
+            .iter()
+            .any(|name| location.join(name).size().is_ok_and(|size| size.is_some()))
+    }
+
+    /// Read the metadata of every array in the store's root group
+    pub fn open(path: &Path) -> Result<Self, NetCDFError> {
+        Self::open_dir(StoreDir::Local(path.to_path_buf()), path.to_string_lossy().to_string())
+    }
+
+    /// Open a store in object storage. Chunks are fetched as they are read;
+    /// stores without consolidated metadata need a server that can list keys.
+    pub fn open_object(location: ObjectLocation, url: &str) -> Result<Self, NetCDFError> {
+        Self::open_dir(StoreDir::Object(location), url.to_string())
+    }
+
+    fn open_dir(dir: StoreDir, path: String) -> Result<Self, NetCDFError> {
+        let (global_attrs, mut arrays) = match read_json(&dir, "zarr.json")? {
+            Some(root) => open_v3(&dir, root)?,
+            None => open_v2(&dir)?,
+        };
+        arrays.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(ZarrStore {
+            path,
+            global_attrs,
+            arrays,
+        })
+    }
+
+    fn array(&self, var_name: &str) -> Result<&ZarrArray, NetCDFError> {
+        self.arrays
+            .iter()
+            .find(|a| a.name == var_name)
+            .ok_or_else(|| NetCDFError::VariableNotFound(var_name.to_string()))
+    }
+}
+
+impl DatasetBackend for ZarrStore {
+    fn metadata(&self) -> Result<FileMetadata, NetCDFError> {
+        let mut dimensions: Vec<Dimension> = Vec::new();
+        for array in &self.arrays {
+            for (name, &size) in array.dimensions.iter().zip(&array.shape) {
+                match dimensions.iter().find(|d| &d.name == name) {
+                    Some(existing) if existing.size != size => {
+                        return Err(NetCDFError::InvalidFormat(format!(
+                            "Dimension '{}' has size {} in '{}' but {} elsewhere",
+                            name, size, array.name, existing.size
+                        )));
+                    }
+                    Some(_) => {}
+                    None => dimensions.push(Dimension {
+                        name: name.clone(),
+                        size,
+                        is_unlimited: false,
+                    }),
+                }
+            }
+        }
+
+        Ok(FileMetadata {
+            file_path: self.path.clone(),
+            dimensions,
+            variables: self.arrays.iter().map(ZarrArray::variable).collect(),
+            global_attrs: attribute_map(self.global_attrs.as_ref()),
+            coordinates: None, // Will be populated by coordinate detection
+        })
+    }
+
+    fn read_native(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<NativeValues, NetCDFError> {
+        let array = self.array(var_name)?;
+        if array.element.is_text() {
+            return Err(NetCDFError::ConversionError(format!(
+                "Variable '{}' holds strings, not numbers",
+                var_name
+            )));
+        }
+
+        let bytes = array.read_bytes(start, count)?;
+        Ok(decode_native(&array.element, &bytes))
+    }
+
+    fn read_text(
+        &self,
+        var_name: &str,
+        start: &[usize],
+        count: &[usize],
+    ) -> Result<Vec<String>, NetCDFError> {
+        let array = self.array(var_name)?;
+        let bytes = array.read_bytes(start, count)?;
+        let element = array.element;
+
+        let strings = bytes
+            .chunks_exact(element.size)
+            .map(|item| match element.kind {
+                ElementKind::Unicode => item
+                    .chunks_exact(4)
+                    .map(|c| {
+                        let code = [c[0], c[1], c[2], c[3]];
+                        if element.big_endian { u32::from_be_bytes(code) } else { u32::from_le_bytes(code) }
+                    })
+                    .filter_map(char::from_u32)
+                    .collect::<String>()
+                    .trim_end_matches('\0')
+                    .to_string(),
+                ElementKind::Bytes => String::from_utf8_lossy(item).trim_end_matches('\0').to_string(),
+                _ => decode_native(&element, item).into_f64()[0].to_string(),
+            })
+            .collect();
+
+        Ok(strings)
+    }
+
+    fn fill_value(&self, var_name: &str) -> Option<f64> {
+        self.array(var_name).ok().and_then(|a| a.fill_value)
+    }
+
+    fn attributes(&self, var_name: Option<&str>) -> Result<Vec<(String, AttributeValue)>, NetCDFError> {
+        match var_name {
+            Some(name) => Ok(self.array(name)?.typed_attributes.clone()),
+            None => Ok(typed_attributes(self.global_attrs.as_ref())),
+        }
+    }
+}
+
+/// Read a Zarr v2 store: consolidated `.zmetadata` if present, else per-array files
+fn open_v2(dir: &StoreDir) -> Result<(Option<Value>, Vec<ZarrArray>), NetCDFError> {
+    let consolidated = read_json(dir, ".zmetadata")?.and_then(|v| v.get("metadata").cloned());
+    let lookup = |key: &str| -> Result<Option<Value>, NetCDFError> {
+        match &consolidated {
+            Some(metadata) => Ok(metadata.get(key).cloned()),
+            None => read_json(dir, key),
+        }
+    };
+
+    let root_attrs = lookup(".zattrs")?;
+
+    // A store may itself be a single array
+    if let Some(zarray) = lookup(".zarray")? {
+        let name = dir.store_name();
+        let array = ZarrArray::from_v2(&name, dir.clone(), &zarray, root_attrs.as_ref())?;
+        return Ok((None, vec![array]));
+    }
+
+    let names: Vec<String> = match &consolidated {
+        Some(Value::Object(metadata)) => metadata
+            .keys()
+            .filter_map(|key| key.strip_suffix("/.zarray"))
+            .filter(|name| !name.contains('/'))
+            .map(str::to_string)
+            .collect(),
+        _ => {
+            let mut names = Vec::new();
+            for name in dir.child_dirs()? {
+                if read_json(&dir.join(&name), ".zarray")?.is_some() {
+                    names.push(name);
+                }
+            }
+            names
+        }
+    };
+
+    let mut arrays = Vec::with_capacity(names.len());
+    for name in names {
+        let zarray = lookup(&format!("{}/.zarray", name))?.ok_or_else(|| invalid_metadata(&name, ".zarray"))?;
+        let zattrs = lookup(&format!("{}/.zattrs", name))?;
+        arrays.push(ZarrArray::from_v2(&name, dir.join(&name), &zarray, zattrs.as_ref())?);
+    }
+
+    Ok((root_attrs, arrays))
+}
+
+/// Read a Zarr v3 store from the `zarr.json` documents of the root group and its children.
+///
+/// Arrays recorded in the root's `consolidated_metadata` are used as is, which
+/// avoids listing keys (not possible over plain HTTP).
+fn open_v3(dir: &StoreDir, root: Value) -> Result<(Option<Value>, Vec<ZarrArray>), NetCDFError> {
+    if root.get("node_type").and_then(Value::as_str) == Some("array") {
+        let array = ZarrArray::from_v3(&dir.store_name(), dir.clone(), &root)?;
+        return Ok((None, vec![array]));
+    }
+
+    let mut arrays = Vec::new();
+    match root.pointer("/consolidated_metadata/metadata").and_then(Value::as_object) {
+        Some(consolidated) => {
+            for (name, meta) in consolidated {
+                if !name.contains('/') && meta.get("node_type").and_then(Value::as_str) == Some("array") {
+                    arrays.push(ZarrArray::from_v3(name, dir.join(name), meta)?);
+                }
+            }
+        }
+        None => {
+            for name in dir.child_dirs()? {
+                let Some(meta) = read_json(&dir.join(&name), "zarr.json")? else {
+                    continue;
+                };
+                if meta.get("node_type").and_then(Value::as_str) == Some("array") {
+                    arrays.push(ZarrArray::from_v3(&name, dir.join(&name), &meta)?);
+                }
+            }
+        }
+    }
+
+    Ok((root.get("attributes").cloned(), arrays))
+}
+
+/// Parse a JSON document, or `None` if the file does not exist
+fn read_json(dir: &StoreDir, key: &str) -> Result<Option<Value>, NetCDFError> {
+    match dir.read(key)? {
+        Some(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|e| {
+            NetCDFError::InvalidFormat(format!("Invalid JSON in {}: {}", dir.display(key), e))
+        }),
+        None => Ok(None),
+    }
+}
+
+fn default_dimensions(name: &str, ndims: usize) -> Vec<String> {
+    (0..ndims).map(|i| format!("{}_dim_{}", name, i)).collect()
+}
+
+fn usize_list(value: Option<&Value>) -> Option<Vec<usize>> {
+    value?
+        .as_array()?
+        .iter()
+        .map(|v| v.as_u64().map(|n| n as usize))
+        .collect()
+}
+
+fn string_list(value: Option<&Value>) -> Option<Vec<String>> {
+    value?
+        .as_array()?
+        .iter()
+        .map(|v| v.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Convert a JSON attribute object to strings
+fn attribute_map(attrs: Option<&Value>) -> HashMap<String, String> {
+    let Some(Value::Object(attrs)) = attrs else {
+        return HashMap::new();
+    };
+
+    attrs
+        .iter()
+        .map(|(name, value)| {
+            let text = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            (name.clone(), text)
+        })
+        .collect()
+}
+
+/// Typed form of a JSON attribute object: strings and lists of strings,
+/// whole numbers as `int` (`int64` when they do not fit) and other numbers
+/// as `double`; anything else keeps its JSON text
+fn typed_attributes(attrs: Option<&Value>) -> Vec<(String, AttributeValue)> {
+    let Some(Value::Object(attrs)) = attrs else {
+        return Vec::new();
+    };
+
+    let typed = |value: &Value| {
+        let (items, list) = match value {
+            Value::Array(items) if !items.is_empty() => (items.iter().collect::<Vec<_>>(), true),
+            other => (vec![other], false),
+        };
+        if let Some(strings) = items.iter().map(|v| v.as_str().map(str::to_string)).collect::<Option<Vec<_>>>() {
+            return if list { AttributeValue::Strs(strings) } else { AttributeValue::Str(strings.concat()) };
+        }
+        if let Some(ints) = items.iter().map(|v| v.as_i64()).collect::<Option<Vec<_>>>() {
+            return match (ints.iter().map(|&x| i32::try_from(x)).collect::<Result<Vec<_>, _>>(), list) {
+                (Ok(small), true) => AttributeValue::Ints(small),
+                (Ok(small), false) => AttributeValue::Int(small[0]),
+                (Err(_), true) => AttributeValue::Longlongs(ints),
+                (Err(_), false) => AttributeValue::Longlong(ints[0]),
+            };
+        }
+        match items.iter().map(|v| v.as_f64()).collect::<Option<Vec<_>>>() {
+            Some(numbers) if list => AttributeValue::Doubles(numbers),
+            Some(numbers) => AttributeValue::Double(numbers[0]),
+            None => AttributeValue::Str(value.to_string()),
+        }
+    };
+    attrs.iter().map(|(name, value)| (name.clone(), typed(value))).collect()
+}
+
+/// Parse a Zarr fill value: a number, a boolean, or "NaN"/"Infinity"/"-Infinity"
+fn parse_fill_value(value: Option<&Value>) -> Option<f64> {
+    match value? {
+        Value::Number(n) => n.as_f64(),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Decode raw element bytes into native values
+fn decode_native(element: &ElementType, bytes: &[u8]) -> NativeValues {
+    macro_rules! decode {
+        ($t:ty, $variant:ident) => {
+            NativeValues::$variant(
+                bytes
+                    .chunks_exact(std::mem::size_of::<$t>())
+                    .map(|b| {
+                        let b = b.try_into().expect("chunk has the element size");
+                        if element.big_endian { <$t>::from_be_bytes(b) } else { <$t>::from_le_bytes(b) }
+                    })
+                    .collect(),
+            )
+        };
+    }
+
+    match (element.kind, element.size) {
+        (ElementKind::Int, 1) => decode!(i8, I8),
+        (ElementKind::Int, 2) => decode!(i16, I16),
+        (ElementKind::Int, 4) => decode!(i32, I32),
+        (ElementKind::Int, _) => decode!(i64, I64),
+        (ElementKind::Uint, 2) => decode!(u16, U16),
+        (ElementKind::Uint, 4) => decode!(u32, U32),
+        (ElementKind::Uint, 8) => decode!(u64, U64),
+        (ElementKind::Float, 4) => decode!(f32, F32),
+        (ElementKind::Float, _) => decode!(f64, F64),
+        _ => NativeValues::U8(bytes.to_vec()),
+    }
+}
+
+fn invalid_metadata(name: &str, what: &str) -> NetCDFError {
+    NetCDFError::InvalidFormat(format!("Zarr array '{}' has invalid or unsupported {}", name, what))
+}
+
+/// Strides (in elements) of a C-order array
+fn strides(shape: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; shape.len()];
+    for d in (0..shape.len().saturating_sub(1)).rev() {
+        strides[d] = strides[d + 1] * shape[d + 1];
+    }
+    strides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_element_type_from_v2_dtype() {
+        let element = ElementType::from_v2(">i2").unwrap();
+        assert_eq!(element, ElementType { kind: ElementKind::Int, size: 2, big_endian: true });
+        assert_eq!(element.netcdf_name(), "Basic(Short)");
+        assert_eq!(ElementType::from_v2("<U4").unwrap().size, 16);
+        assert!(ElementType::from_v2("<f2").is_none());
+    }
+
+    #[test]
+    fn test_chunk_keys() {
+        let v2 = ChunkKeyEncoding { prefixed: false, separator: '.' };
+        let v3 = ChunkKeyEncoding { prefixed: true, separator: '/' };
+        assert_eq!(v2.key(&[1, 0]), "1.0");
+        assert_eq!(v3.key(&[1, 0]), "c/1/0");
+        assert_eq!(v2.key(&[]), "0");
+        assert_eq!(v3.key(&[]), "c");
+    }
+
+    #[test]
+    fn test_read_v2_store_across_chunks() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("test.zarr");
+        let array_dir = root.join("t");
+        std::fs::create_dir_all(&array_dir).unwrap();
+
+        std::fs::write(root.join(".zgroup"), r#"{"zarr_format": 2}"#).unwrap();
+        std::fs::write(root.join(".zattrs"), r#"{"title": "test"}"#).unwrap();
+        std::fs::write(
+            array_dir.join(".zarray"),
+            r#"{"zarr_format": 2, "shape": [3, 3], "chunks": [2, 2], "dtype": "<f4",
+                "compressor": {"id": "gzip", "level": 1}, "fill_value": -1.0,
+                "order": "C", "filters": null}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            array_dir.join(".zattrs"),
+            r#"{"_ARRAY_DIMENSIONS": ["y", "x"], "units": "1", "valid_range": [0, 30], "scale_factor": 0.5}"#,
+        )
+        .unwrap();
+
+        // Value at (y, x) is 10 * y + x; chunk (1, 1) is left unwritten
+        for (cy, cx) in [(0, 0), (0, 1), (1, 0)] {
+            let mut values = Vec::new();
+            for y in 0..2 {
+                for x in 0..2 {
+                    let value = (10 * (2 * cy + y) + 2 * cx + x) as f32;
+                    values.extend(value.to_le_bytes());
+                }
+            }
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+            encoder.write_all(&values).unwrap();
+            std::fs::write(array_dir.join(format!("{}.{}", cy, cx)), encoder.finish().unwrap()).unwrap();
+        }
+
+        let store = ZarrStore::open(&root).unwrap();
+        let metadata = store.metadata().unwrap();
+        assert_eq!(metadata.global_attrs["title"], "test");
+        assert_eq!(metadata.variables[0].dimensions, vec!["y", "x"]);
+        assert_eq!(metadata.variables[0].attributes["_FillValue"], "-1");
+
+        // Typed attributes follow the JSON types; the fill value takes the array's type
+        let mut typed = store.attributes(Some("t")).unwrap();
+        typed.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            typed,
+            vec![
+                ("_FillValue".to_string(), AttributeValue::Float(-1.0)),
+                ("scale_factor".to_string(), AttributeValue::Double(0.5)),
+                ("units".to_string(), AttributeValue::Str("1".to_string())),
+                ("valid_range".to_string(), AttributeValue::Ints(vec![0, 30])),
+            ]
+        );
+        assert_eq!(store.attributes(None).unwrap(), vec![("title".to_string(), AttributeValue::Str("test".to_string()))]);
+
+        let values = store.read_f64("t", &[1, 1], &[2, 2]).unwrap();
+        assert_eq!(values, vec![11.0, 12.0, 21.0, -1.0]);
+    }
+
+    /// A v3 store with a 4x3 int16 array `v` (value 10 * y + x) in 2x3 chunks
+    fn v3_store_objects(prefix: &str) -> HashMap<String, Vec<u8>> {
+        let mut objects = HashMap::from([
+            (format!("{}/zarr.json", prefix), br#"{"zarr_format": 3, "node_type": "group", "attributes": {"title": "remote"}}"#.to_vec()),
+            (
+                format!("{}/v/zarr.json", prefix),
+                br#"{"zarr_format": 3, "node_type": "array", "shape": [4, 3], "data_type": "int16",
+                    "chunk_grid": {"name": "regular", "configuration": {"chunk_shape": [2, 3]}},
+                    "codecs": [{"name": "bytes", "configuration": {"endian": "little"}}],
+                    "fill_value": 0, "dimension_names": ["y", "x"]}"#
+                    .to_vec(),
+            ),
+        ]);
+        for cy in 0..2 {
+            let chunk: Vec<u8> = (0..6i16).flat_map(|i| (10 * (2 * cy + i / 3) + i % 3).to_le_bytes()).collect();
+            objects.insert(format!("{}/v/c/{}/0", prefix, cy), chunk);
+        }
+        objects
+    }
+
+    #[test]
+    fn test_read_v3_store_from_object_storage() {
+        use crate::netcdf::object_store::tests::fake_s3;
+        let (settings, _) = fake_s3("bucket", v3_store_objects("runs/out.zarr"));
+        let location = ObjectLocation::s3("bucket", "runs/out.zarr", settings);
+        assert!(ZarrStore::is_object_store(&location));
+
+        let store = ZarrStore::open_object(location, "s3://bucket/runs/out.zarr").unwrap();
+        let metadata = store.metadata().unwrap();
+        assert_eq!(metadata.global_attrs["title"], "remote");
+        assert_eq!(metadata.variables[0].name, "v");
+        assert_eq!(store.read_f64("v", &[1, 1], &[2, 2]).unwrap(), vec![11.0, 12.0, 21.0, 22.0]);
+    }
+
+    /// Runs against a real S3-compatible server (e.g. `minio server`) when
+    /// NCV_TEST_S3_BUCKET is set; the endpoint and keys come from the usual
+    /// AWS_ENDPOINT_URL, AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY variables
+    #[test]
+    fn test_minio_store_round_trip() {
+        use crate::netcdf::object_store::S3Settings;
+        let Ok(bucket) = std::env::var("NCV_TEST_S3_BUCKET") else {
+            return;
+        };
+        let bucket_location = ObjectLocation::s3(&bucket, "", S3Settings::from_env());
+        // Creates the bucket; fails harmlessly if it already exists
+        let _ = bucket_location.put(&[]);
+        for (key, data) in v3_store_objects("netcdf-viewer-test/out.zarr") {
+            bucket_location.join(&key).put(&data).unwrap();
+        }
+
+        let url = format!("s3://{}/netcdf-viewer-test/out.zarr", bucket);
+        let dataset = crate::netcdf::backend::open_dataset(&url).unwrap();
+        assert_eq!(dataset.metadata().unwrap().file_path, url);
+        assert_eq!(dataset.read_f64("v", &[2, 0], &[2, 3]).unwrap(), vec![20.0, 21.0, 22.0, 30.0, 31.0, 32.0]);
+    }
+}
@@ -0,0 +1,535 @@
+//! HTTP/JSON access to the viewer's commands for remote clients.
+//!
+//! Every command is a `POST /api/<command>` whose JSON body holds the same
+//! arguments the webview passes to `invoke`. File paths are resolved inside
+//! the configured root directory and reported relative to it.
+//!
+//! There is no authentication, so browsers are kept out: requests that carry
+//! an `Origin` header are refused unless the origin is configured, and only
+//! configured origins get CORS headers.
+
+use crate::errors::NetCDFError;
+use crate::models::{
+    CdlDumpOptions, DiffOptions, DimensionSlice, DirectoryEntry, RegridOptions, RegridTarget,
+    RegionSelection, RegionSeriesRequest, RenderOptions, StationRequest, TimeSeriesRequest, VariableData, VariableDataResponse,
+};
+use crate::netcdf;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Largest request body accepted
+const MAX_BODY_BYTES: u64 = 16 << 20;
+
+/// Requests handled at the same time when no worker count is configured
+pub const DEFAULT_WORKERS: usize = 8;
+
+/// Settings for `serve`
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Address to listen on, e.g. "127.0.0.1:8765"
+    pub address: String,
+    /// Directory whose files are served; nothing outside it is reachable
+    pub root: PathBuf,
+    /// Web origins (e.g. "http://localhost:1420") allowed to call the API
+    /// from a browser; none by default
+    pub allowed_origins: Vec<String>,
+    /// Number of requests handled at the same time; more wait in the queue
+    pub workers: usize,
+}
+
+/// What `respond` needs from the configuration
+struct Context {
+    root: PathBuf,
+    allowed_origins: Vec<String>,
+}
+
+/// Serve the read-only commands over HTTP until the process is stopped
+pub fn serve(config: &ServerConfig) -> Result<(), NetCDFError> {
+    let root = config.root.canonicalize().map_err(|e| {
+        NetCDFError::FileOpenError(format!("Root directory {}: {}", config.root.display(), e))
+    })?;
+    if !root.is_dir() {
+        return Err(NetCDFError::FileOpenError(format!(
+            "Root {} is not a directory",
+            root.display()
+        )));
+    }
+
+    // NcML documents below the root may only refer to files below it
+    netcdf::ncml::confine_locations(&root);
+
+    let server = Arc::new(
+        Server::http(&config.address)
+            .map_err(|e| NetCDFError::IoError(std::io::Error::other(e.to_string())))?,
+    );
+    let context = Arc::new(Context { root, allowed_origins: config.allowed_origins.clone() });
+
+    // A fixed pool of workers takes requests from the server's queue
+    let workers: Vec<_> = (0..config.workers.max(1))
+        .map(|_| {
+            let (server, context) = (Arc::clone(&server), Arc::clone(&context));
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    respond(&context, request);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+/// Whether a request may be answered, and the CORS headers to send with it
+fn check_origin(allowed: &[String], origin: Option<&str>) -> Option<Vec<(&'static str, String)>> {
+    let Some(origin) = origin else {
+        // Not sent by a browser on another origin (curl, scripts, the app)
+        return Some(Vec::new());
+    };
+    if !allowed.iter().any(|a| a == origin) {
+        return None;
+    }
+    Some(vec![
+        ("Access-Control-Allow-Origin", origin.to_string()),
+        ("Vary", "Origin".to_string()),
+        ("Access-Control-Allow-Methods", "POST, OPTIONS".to_string()),
+        ("Access-Control-Allow-Headers", "Content-Type, Accept".to_string()),
+        ("Access-Control-Expose-Headers", "X-Shape, X-Missing-Count".to_string()),
+    ])
+}
+
+/// Route one request and send its response
+fn respond(context: &Context, mut request: Request) {
+    let root = context.root.as_path();
+    let origin = request.headers().iter().find(|h| h.field.equiv("Origin")).map(|h| h.value.to_string());
+    let Some(cors) = check_origin(&context.allowed_origins, origin.as_deref()) else {
+        let reply = Reply::json(403, &ErrorBody { error: "Origin not allowed".to_string() });
+        let response = Response::from_data(reply.body).with_status_code(reply.status);
+        let _ = request.respond(response);
+        return;
+    };
+
+    let reply = match (request.method(), request.url().split('?').next().unwrap_or("")) {
+        // CORS preflight from a webview on an allowed origin
+        (Method::Options, _) => Reply::empty(204),
+        (Method::Post, url) if url.starts_with("/api/") => {
+            let command = url["/api/".len()..].to_string();
+            let binary = accepts_binary(&request);
+            let mut body = Vec::new();
+            match request.as_reader().take(MAX_BODY_BYTES).read_to_end(&mut body) {
+                Ok(_) => dispatch(root, &command, &body, binary).unwrap_or_else(|e| Reply::error(root, e)),
+                Err(e) => Reply::error(root, e.into()),
+            }
+        }
+        _ => Reply::json(404, &ErrorBody { error: "Not found".to_string() }),
+    };
+
+    let mut response = Response::from_data(reply.body).with_status_code(reply.status);
+    let mut headers = vec![("Content-Type", reply.content_type.to_string())];
+    headers.extend(cors);
+    headers.extend(reply.headers);
+    for (name, value) in headers {
+        if let Ok(header) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            response.add_header(header);
+        }
+    }
+    // The client may have gone away; nothing more to do then
+    let _ = request.respond(response);
+}
+
+fn accepts_binary(request: &Request) -> bool {
+    request.headers().iter().any(|h| {
+        h.field.equiv("Accept") && h.value.as_str().contains("application/octet-stream")
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PathArgs {
+    path: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VariableArgs {
+    path: String,
+    var_name: String,
+    /// Units to convert the values to
+    units: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubsetArgs {
+    path: String,
+    var_name: String,
+    start: Vec<usize>,
+    count: Vec<usize>,
+    units: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatsArgs {
+    path: String,
+    var_name: String,
+    #[serde(default)]
+    slices: HashMap<String, DimensionSlice>,
+    units: Option<String>,
+    region: Option<RegionSelection>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiffArgs {
+    path_a: String,
+    path_b: String,
+    #[serde(default)]
+    options: DiffOptions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TimeSeriesArgs {
+    path: String,
+    request: TimeSeriesRequest,
+    units: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StationArgs {
+    path: String,
+    request: StationRequest,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegionArgs {
+    path: String,
+    request: RegionSeriesRequest,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextArgs {
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RenderArgs {
+    path: String,
+    var_name: String,
+    #[serde(default)]
+    indices: HashMap<String, usize>,
+    options: RenderOptions,
+    #[serde(default)]
+    regrid: Option<RegridOptions>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DumpArgs {
+    path: String,
+    #[serde(default)]
+    options: CdlDumpOptions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CdlArgs {
+    cdl: String,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// Run a command. Commands that write files are not available remotely.
+fn dispatch(root: &Path, command: &str, body: &[u8], binary: bool) -> Result<Reply, NetCDFError> {
+    match command {
+        "list_files" => {
+            let args: PathArgs = parse_args(body)?;
+            let dir = resolve_path(root, &args.path)?;
+            Ok(Reply::json(200, &list_directory(root, &dir)?))
+        }
+        "open_netcdf_file" => {
+            let args: PathArgs = parse_args(body)?;
+            let mut metadata = netcdf::open_netcdf(&resolve(root, &args.path)?)?;
+            metadata.coordinates = Some(netcdf::detect_coordinates(&metadata));
+            metadata.file_path = args.path;
+            Ok(Reply::json(200, &metadata))
+        }
+        "get_variable_data" => {
+            let args: VariableArgs = parse_args(body)?;
+            let path = resolve(root, &args.path)?;
+            let response = netcdf::read_in_units(&path, &args.var_name, args.units.as_deref(), |dataset| {
+                netcdf::read_variable_data(dataset, &args.var_name)
+            })?;
+            Ok(Reply::data(response, binary))
+        }
+        "get_variable_subset" => {
+            let args: SubsetArgs = parse_args(body)?;
+            let path = resolve(root, &args.path)?;
+            let response = netcdf::read_in_units(&path, &args.var_name, args.units.as_deref(), |dataset| {
+                netcdf::read_variable_subset(dataset, &args.var_name, &args.start, &args.count)
+            })?;
+            Ok(Reply::data(response, binary))
+        }
+        "get_variable_stats" => {
+            let mut args: StatsArgs = parse_args(body)?;
+            let path = resolve(root, &args.path)?;
+            if let Some(region) = &mut args.region {
+                region.path = resolve(root, &region.path)?;
+            }
+            let stats = netcdf::read_in_units(&path, &args.var_name, args.units.as_deref(), |dataset| {
+                match &args.region {
+                    Some(region) => netcdf::read_region_stats(dataset, &args.var_name, &args.slices, region),
+                    None => netcdf::read_stats(dataset, &args.var_name, &args.slices),
+                }
+            })?;
+            Ok(Reply::json(200, &stats))
+        }
+        "diff_files" => {
+            let args: DiffArgs = parse_args(body)?;
+            let (path_a, path_b) = (resolve(root, &args.path_a)?, resolve(root, &args.path_b)?);
+            let mut report = netcdf::diff_files(&path_a, &path_b, &args.options)?;
+            (report.file_a, report.file_b) = (args.path_a, args.path_b);
+            Ok(Reply::json(200, &report))
+        }
+        "check_cf" => {
+            let args: PathArgs = parse_args(body)?;
+            let mut report = netcdf::check_cf(&resolve(root, &args.path)?)?;
+            report.file_path = args.path;
+            Ok(Reply::json(200, &report))
+        }
+        "get_timeseries" => {
+            let args: TimeSeriesArgs = parse_args(body)?;
+            let path = resolve(root, &args.path)?;
+            let points = netcdf::read_in_units(&path, &args.request.var_name, args.units.as_deref(), |dataset| {
+                netcdf::read_timeseries(dataset, &args.request)
+            })?;
+            Ok(Reply::json(200, &points))
+        }
+        "extract_stations" => {
+            let args: StationArgs = parse_args(body)?;
+            let extraction = netcdf::extract_stations(&resolve(root, &args.path)?, &args.request)?;
+            Ok(Reply::json(200, &extraction))
+        }
+        "list_regions" => {
+            let args: PathArgs = parse_args(body)?;
+            Ok(Reply::json(200, &netcdf::polygons::list_regions(&resolve(root, &args.path)?)?))
+        }
+        "region_series" => {
+            let mut args: RegionArgs = parse_args(body)?;
+            let path = resolve(root, &args.path)?;
+            args.request.region.path = resolve(root, &args.request.region.path)?;
+            Ok(Reply::json(200, &netcdf::region_series(&path, &args.request)?))
+        }
+        "parse_station_list" => {
+            let args: TextArgs = parse_args(body)?;
+            Ok(Reply::json(200, &netcdf::stations::parse_stations(&args.text)?))
+        }
+        "render_variable_slice" => {
+            let mut args: RenderArgs = parse_args(body)?;
+            let path = resolve(root, &args.path)?;
+            if let Some(RegridOptions { target: RegridTarget::File { path }, .. }) = &mut args.regrid {
+                *path = resolve(root, path)?;
+            }
+            let image = netcdf::render_slice(&path, &args.var_name, &args.indices, &args.options, args.regrid.as_ref())?;
+            Ok(Reply::json(200, &image))
+        }
+        "list_colormaps" => {
+            let names: Vec<&str> = netcdf::colormap::COLORMAP_NAMES.to_vec();
+            Ok(Reply::json(200, &names))
+        }
+        "dump_cdl" => {
+            let args: DumpArgs = parse_args(body)?;
+            let cdl = netcdf::dump_cdl(&resolve(root, &args.path)?, &args.options)?;
+            Ok(Reply::json(200, &cdl))
+        }
+        "cdl_metadata" => {
+            let args: CdlArgs = parse_args(body)?;
+            Ok(Reply::json(200, &netcdf::cdl_metadata(&args.cdl)?))
+        }
+        "close_netcdf_file" => Ok(Reply::json(200, &())),
+        _ => Ok(Reply::json(404, &ErrorBody {
+            error: format!("Unknown or unavailable command: {}", command),
+        })),
+    }
+}
+
+fn parse_args<T: DeserializeOwned>(body: &[u8]) -> Result<T, NetCDFError> {
+    let body = if body.is_empty() { b"{}".as_slice() } else { body };
+    serde_json::from_slice(body)
+        .map_err(|e| NetCDFError::InvalidSubsetRequest(format!("Invalid arguments: {}", e)))
+}
+
+/// Resolve a client path to a path string for the readers
+fn resolve(root: &Path, requested: &str) -> Result<String, NetCDFError> {
+    Ok(resolve_path(root, requested)?.to_string_lossy().into_owned())
+}
+
+/// Resolve a client path (relative to the root; a leading '/' is ignored)
+/// and make sure it does not escape the root, also through symlinks
+fn resolve_path(root: &Path, requested: &str) -> Result<PathBuf, NetCDFError> {
+    let relative = Path::new(requested.trim_start_matches(['/', '\\']));
+    if relative.components().any(|c| matches!(c, Component::Prefix(_) | Component::RootDir)) {
+        return Err(NetCDFError::FileOpenError(format!("Invalid path: {}", requested)));
+    }
+
+    let not_found = || NetCDFError::FileOpenError(format!("File not found: {}", requested));
+    let resolved = root.join(relative).canonicalize().map_err(|_| not_found())?;
+    if !resolved.starts_with(root) {
+        return Err(not_found());
+    }
+    Ok(resolved)
+}
+
+/// Entries of a directory inside the root, directories first
+fn list_directory(root: &Path, dir: &Path) -> Result<Vec<DirectoryEntry>, NetCDFError> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        entries.push(DirectoryEntry {
+            name,
+            path: relative.to_string_lossy().replace('\\', "/"),
+            is_dir: path.is_dir(),
+            size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+/// A response ready to send
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl Reply {
+    fn empty(status: u16) -> Self {
+        Reply {
+            status,
+            content_type: "text/plain",
+            body: Vec::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Reply {
+                status,
+                content_type: "application/json",
+                body,
+                headers: Vec::new(),
+            },
+            Err(e) => Reply::json(500, &ErrorBody { error: e.to_string() }),
+        }
+    }
+
+    /// Variable data as JSON, or as little-endian f64 values when the client
+    /// asked for binary and the data is numeric
+    fn data(response: VariableDataResponse, binary: bool) -> Self {
+        match (&response.values, binary) {
+            (VariableData::Numeric(values), true) => Reply {
+                status: 200,
+                content_type: "application/octet-stream",
+                body: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+                headers: vec![
+                    ("X-Shape", join(&response.shape)),
+                    ("X-Missing-Count", response.missing_count.to_string()),
+                ],
+            },
+            _ => Reply::json(200, &response),
+        }
+    }
+
+    /// Error reply; server paths are shown relative to the root
+    fn error(root: &Path, err: NetCDFError) -> Self {
+        let status = match err {
+            NetCDFError::FileOpenError(_)
+            | NetCDFError::VariableNotFound(_)
+            | NetCDFError::DimensionNotFound(_) => 404,
+            NetCDFError::InvalidSubsetRequest(_)
+            | NetCDFError::InvalidFormat(_)
+            | NetCDFError::ConversionError(_) => 400,
+            _ => 500,
+        };
+        let prefix = format!("{}{}", root.display(), std::path::MAIN_SEPARATOR);
+        Reply::json(status, &ErrorBody { error: err.to_string().replace(&prefix, "") })
+    }
+}
+
+fn join(values: &[usize]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_stay_inside_root() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+        let root = base.join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/a.nc"), b"x").unwrap();
+        std::fs::write(base.join("secret.nc"), b"x").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        assert_eq!(resolve_path(&root, "sub/a.nc").unwrap(), root.join("sub/a.nc"));
+        assert_eq!(resolve_path(&root, "/sub/a.nc").unwrap(), root.join("sub/a.nc"));
+        assert!(resolve_path(&root, "../secret.nc").is_err());
+        assert!(resolve_path(&root, "sub/../../secret.nc").is_err());
+
+        let entries = list_directory(&root, &root).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "sub");
+        assert!(entries[0].is_dir);
+    }
+
+    #[test]
+    fn test_only_configured_origins_get_cors_headers() {
+        let allowed = vec!["http://localhost:1420".to_string()];
+        assert_eq!(check_origin(&allowed, None), Some(vec![]));
+        assert_eq!(check_origin(&[], Some("http://localhost:1420")), None);
+        assert_eq!(check_origin(&allowed, Some("https://evil.example")), None);
+
+        let headers = check_origin(&allowed, Some("http://localhost:1420")).unwrap();
+        assert!(headers.contains(&("Access-Control-Allow-Origin", "http://localhost:1420".to_string())));
+        assert!(!headers.iter().any(|(_, value)| value == "*"));
+    }
+
+    #[test]
+    fn test_error_status_codes() {
+        let root = Path::new("/data/root");
+        let reply = Reply::error(root, NetCDFError::VariableNotFound("t".to_string()));
+        assert_eq!(reply.status, 404);
+        let reply = Reply::error(root, NetCDFError::InvalidSubsetRequest("bad".to_string()));
+        assert_eq!(reply.status, 400);
+        assert!(String::from_utf8(reply.body).unwrap().contains("bad"));
+
+        let reply = Reply::error(root, NetCDFError::FileOpenError("/data/root/sub/a.nc: corrupt".to_string()));
+        assert!(String::from_utf8(reply.body).unwrap().contains("open NetCDF file: sub/a.nc: corrupt"));
+    }
+}
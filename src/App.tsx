@@ -1,22 +1,41 @@
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
 import { FileLoader } from './components/FileLoader';
 import { MetadataPanel } from './components/MetadataPanel';
 import { TimeSeriesChart } from './components/TimeSeriesChart';
 import { DataTable } from './components/DataTable';
 import { StatsPanel } from './components/StatsPanel';
 import { MapView } from './components/MapView';
+import { StationsPanel } from './components/StationsPanel';
+import { RegionsPanel } from './components/RegionsPanel';
 import { ChartControls, type ChartSettings } from './components/ChartControls';
 import { ExportDialog } from './components/ExportDialog';
-import type { FileMetadata, Variable } from './types/netcdf';
+import { watchDataset } from './utils/backend';
+import type { Dimension, FileMetadata, Variable } from './types/netcdf';
 import './App.css';
 
-type TabType = 'chart' | 'table' | 'stats' | 'map';
+type TabType = 'chart' | 'table' | 'stats' | 'map' | 'stations' | 'regions';
+
+/**
+ * Apply new dimension sizes to a variable's shape
+ */
+function resizeVariable(variable: Variable, dimensions: Dimension[]): Variable {
+  const sizes = new Map(dimensions.map((dim) => [dim.name, dim.size]));
+  if (!variable.dimensions.some((name) => sizes.has(name))) {
+    return variable;
+  }
+  return {
+    ...variable,
+    shape: variable.dimensions.map((name, i) => sizes.get(name) ?? variable.shape[i] ?? 0),
+  };
+}
 
 function App(): React.JSX.Element {
   const [metadata, setMetadata] = useState<FileMetadata | null>(null);
   const [selectedVariable, setSelectedVariable] = useState<Variable | null>(null);
   const [activeTab, setActiveTab] = useState<TabType>('chart');
   const [showExportDialog, setShowExportDialog] = useState<boolean>(false);
+  // Bumped whenever the watched file changes so charts reload their data
+  const [revision, setRevision] = useState<number>(0);
   const [chartSettings, setChartSettings] = useState<ChartSettings>({
     chartType: 'line',
     showGrid: true,
@@ -24,9 +43,51 @@ function App(): React.JSX.Element {
     yAxisLabel: '',
   });
 
+  const filePath = metadata?.file_path ?? null;
+
+  // Follow files that are still being written, e.g. by a running simulation
+  useEffect(() => {
+    if (filePath === null) {
+      return;
+    }
+
+    let stop: (() => void) | null = null;
+    let cancelled = false;
+    watchDataset(filePath, (event) => {
+      if (event.path !== filePath || event.change !== 'changed') {
+        return;
+      }
+      setMetadata((current) => current === null ? null : {
+        ...current,
+        dimensions: current.dimensions.map(
+          (dim) => event.dimensions.find((changed) => changed.name === dim.name) ?? dim,
+        ),
+        variables: current.variables.map((variable) => resizeVariable(variable, event.dimensions)),
+      });
+      setSelectedVariable((current) => current === null ? null : resizeVariable(current, event.dimensions));
+      setRevision((current) => current + 1);
+    })
+      .then((unwatch) => {
+        if (cancelled) {
+          unwatch();
+        } else {
+          stop = unwatch;
+        }
+      })
+      .catch((err: unknown) => {
+        console.warn('Could not watch file for changes:', err);
+      });
+
+    return () => {
+      cancelled = true;
+      stop?.();
+    };
+  }, [filePath]);
+
   const handleFileLoaded = (loadedMetadata: FileMetadata): void => {
     setMetadata(loadedMetadata);
     setSelectedVariable(null);
+    setRevision(0);
     setActiveTab('chart');
   };
 
@@ -58,6 +119,7 @@ function App(): React.JSX.Element {
               metadata={metadata}
               onVariableSelect={handleVariableSelect}
               selectedVariable={selectedVariable?.name ?? null}
+              onMetadataChange={setMetadata}
             />
           </aside>
 
@@ -106,6 +168,18 @@ function App(): React.JSX.Element {
                   >
                     Map
                   </button>
+                  <button
+                    className={activeTab === 'stations' ? 'tab active' : 'tab'}
+                    onClick={() => { setActiveTab('stations'); }}
+                  >
+                    Stations
+                  </button>
+                  <button
+                    className={activeTab === 'regions' ? 'tab active' : 'tab'}
+                    onClick={() => { setActiveTab('regions'); }}
+                  >
+                    Regions
+                  </button>
                 </div>
 
                 <div className="tab-content">
@@ -119,6 +193,7 @@ function App(): React.JSX.Element {
                         filePath={metadata.file_path}
                         variable={selectedVariable}
                         metadata={metadata}
+                        revision={revision}
                       />
                     </div>
                   )}
@@ -138,6 +213,14 @@ function App(): React.JSX.Element {
                       metadata={metadata}
                     />
                   )}
+
+                  {activeTab === 'stations' && (
+                    <StationsPanel filePath={metadata.file_path} variable={selectedVariable} />
+                  )}
+
+                  {activeTab === 'regions' && (
+                    <RegionsPanel filePath={metadata.file_path} variable={selectedVariable} />
+                  )}
                 </div>
               </div>
             ) : (
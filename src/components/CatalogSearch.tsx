@@ -0,0 +1,131 @@
+import { useState } from 'react';
+import { open } from '@tauri-apps/plugin-dialog';
+import { Database, File as FileIcon, Loader2, Search } from 'lucide-react';
+import { callBackend } from '../utils/backend';
+import type { CatalogEntry, CatalogQuery, CatalogScanSummary, GeoExtent } from '../types/netcdf';
+
+interface CatalogSearchProps {
+  onOpen: (path: string) => void;
+  disabled: boolean;
+}
+
+/**
+ * Parse "south,west,north,east"; null for an empty box, undefined when invalid
+ */
+function parseBbox(text: string): GeoExtent | null | undefined {
+  if (text.trim() === '') return null;
+  const values = text.split(',').map((v) => Number(v.trim()));
+  if (values.length !== 4 || values.some((v) => Number.isNaN(v))) return undefined;
+  const [south, west, north, east] = values;
+  return { south, west, north, east };
+}
+
+/**
+ * Scan directories into the local catalog index and search it by variable,
+ * time coverage and area
+ */
+export function CatalogSearch({ onOpen, disabled }: CatalogSearchProps): React.JSX.Element {
+  const [variable, setVariable] = useState('');
+  const [timeStart, setTimeStart] = useState('');
+  const [timeEnd, setTimeEnd] = useState('');
+  const [bbox, setBbox] = useState('');
+  const [results, setResults] = useState<CatalogEntry[] | null>(null);
+  const [status, setStatus] = useState<string | null>(null);
+  const [busy, setBusy] = useState(false);
+
+  const run = async (task: () => Promise<void>): Promise<void> => {
+    setBusy(true);
+    setStatus(null);
+    try {
+      await task();
+    } catch (err) {
+      setStatus(err instanceof Error ? err.message : String(err));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleScan = (): void => {
+    void run(async () => {
+      const root = await open({ multiple: false, directory: true });
+      if (root === null || typeof root !== 'string') return;
+      setStatus(`Scanning ${root}...`);
+      const summary = await callBackend<CatalogScanSummary>('scan_catalog', { root, indexPath: null });
+      const failed = summary.failed.length > 0 ? `, ${summary.failed.length} could not be read` : '';
+      setStatus(
+        `Indexed ${summary.indexed} files (${summary.unchanged} unchanged, ${summary.removed} removed${failed})`
+      );
+    });
+  };
+
+  const handleSearch = (): void => {
+    const extent = parseBbox(bbox);
+    if (extent === undefined) {
+      setStatus('Area must be south,west,north,east');
+      return;
+    }
+    const query: CatalogQuery = {
+      variable: variable.trim() === '' ? null : variable.trim(),
+      time_start: timeStart === '' ? null : timeStart,
+      time_end: timeEnd === '' ? null : timeEnd,
+      extent,
+      attributes: {},
+    };
+    void run(async () => {
+      setResults(await callBackend<CatalogEntry[]>('query_catalog', { indexPath: null, query }));
+    });
+  };
+
+  return (
+    <div className="catalog-search">
+      <div className="catalog-filters">
+        <input
+          type="text"
+          value={variable}
+          onChange={(e) => setVariable(e.target.value)}
+          placeholder="Variable or standard_name"
+        />
+        <input type="date" value={timeStart} onChange={(e) => setTimeStart(e.target.value)} title="From" />
+        <input type="date" value={timeEnd} onChange={(e) => setTimeEnd(e.target.value)} title="To" />
+        <input
+          type="text"
+          value={bbox}
+          onChange={(e) => setBbox(e.target.value)}
+          placeholder="S,W,N,E"
+        />
+      </div>
+      <div className="catalog-actions">
+        <button className="btn-secondary" onClick={handleSearch} disabled={disabled || busy}>
+          {busy ? <Loader2 className="animate-spin" size={16} /> : <Search size={16} />} Search Catalog
+        </button>
+        <button className="btn-secondary" onClick={handleScan} disabled={disabled || busy}>
+          <Database size={16} /> Scan Directory
+        </button>
+      </div>
+      {status !== null && <div className="catalog-status">{status}</div>}
+      {results !== null && (
+        <div className="remote-browser">
+          {results.length === 0 ? (
+            <div className="catalog-status">No matching files</div>
+          ) : (
+            <ul>
+              {results.map((entry) => (
+                <li key={entry.path}>
+                  <button onClick={() => onOpen(entry.path)} disabled={disabled}>
+                    <FileIcon size={16} />
+                    <span>
+                      {entry.path}
+                      {entry.time_start !== null && (
+                        <small> {entry.time_start} – {entry.time_end}</small>
+                      )}
+                    </span>
+                  </button>
+                </li>
+              ))}
+            </ul>
+          )}
+        </div>
+      )}
+    </div>
+  );
+}
@@ -9,7 +9,7 @@ import {
   type SortingState,
   type ColumnFiltersState,
 } from '@tanstack/react-table';
-import { invoke } from '@tauri-apps/api/core';
+import { callBackend } from '../utils/backend';
 import type { VariableDataResponse, Variable } from '../types/netcdf';
 
 interface DataTableProps {
@@ -36,7 +36,7 @@ export function DataTable({ filePath, variable }: DataTableProps): React.JSX.Ele
       setError(null);
 
       try {
-        const response = await invoke<VariableDataResponse>('get_variable_data', {
+        const response = await callBackend<VariableDataResponse>('get_variable_data', {
           path: filePath,
           varName: variable.name,
         });
@@ -1,7 +1,18 @@
 import { useState } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { callBackend } from '../utils/backend';
+import { save } from '@tauri-apps/plugin-dialog';
 import { exportToCSV, exportToJSON, type ExportSettings, defaultExportSettings } from '../utils/export';
-import type { Variable, VariableDataResponse } from '../types/netcdf';
+import type {
+  ExportSummary,
+  GeoTiffExportRequest,
+  NetCDFExportRequest,
+  NetCDFFormat,
+  RegridExportRequest,
+  RegridMethod,
+  TableExportRequest,
+  Variable,
+  VariableDataResponse,
+} from '../types/netcdf';
 
 interface ExportDialogProps {
   filePath: string;
@@ -10,20 +21,134 @@ interface ExportDialogProps {
 }
 
 export function ExportDialog({ filePath, variable, onClose }: ExportDialogProps): React.JSX.Element {
-  const [format, setFormat] = useState<'csv' | 'json'>('csv');
+  const [format, setFormat] = useState<'csv' | 'json' | 'netcdf' | 'table' | 'parquet' | 'arrow' | 'geotiff'>('csv');
+  const [ncFormat, setNcFormat] = useState<NetCDFFormat>('netcdf4');
+  // Optional regridding of NetCDF exports onto a global regular grid
+  const [regridMethod, setRegridMethod] = useState<RegridMethod | ''>('');
+  const [resolution, setResolution] = useState<number>(1);
+  const [bandsFromOuterDim, setBandsFromOuterDim] = useState<boolean>(false);
   const [settings, setSettings] = useState<ExportSettings>(defaultExportSettings);
   const [exporting, setExporting] = useState<boolean>(false);
   const [error, setError] = useState<string | null>(null);
   const [success, setSuccess] = useState<boolean>(false);
+  const [warnings, setWarnings] = useState<string[]>([]);
 
   const handleExport = async (): Promise<void> => {
     setExporting(true);
     setError(null);
     setSuccess(false);
+    setWarnings([]);
 
     try {
+      // NetCDF export is written entirely by the backend
+      if (format === 'netcdf') {
+        const outputPath = await save({
+          defaultPath: `${variable.name}.nc`,
+          filters: [{ name: 'NetCDF', extensions: ['nc'] }],
+        });
+        if (outputPath === null) return;
+
+        if (regridMethod !== '') {
+          const request: RegridExportRequest = {
+            output_path: outputPath,
+            variables: [variable.name],
+            regrid: {
+              method: regridMethod,
+              target: {
+                kind: 'regular',
+                extent: { south: -90, west: -180, north: 90, east: 180 },
+                lat_step: resolution,
+                lon_step: resolution,
+              },
+            },
+            slices: {},
+            format: ncFormat,
+          };
+          await callBackend<ExportSummary>('export_regridded', { path: filePath, request });
+        } else {
+          const request: NetCDFExportRequest = {
+            output_path: outputPath,
+            variables: [variable.name],
+            slices: {},
+            format: ncFormat,
+            deflate_level: ncFormat === 'netcdf4' ? 4 : null,
+          };
+          await callBackend<ExportSummary>('export_netcdf', { path: filePath, request });
+        }
+
+        setSuccess(true);
+        setTimeout(() => {
+          onClose();
+        }, 1500);
+        return;
+      }
+
+      // GeoTIFF export writes the first 2-D slice, optionally one band per outer step
+      if (format === 'geotiff') {
+        const outputPath = await save({
+          defaultPath: `${variable.name}.tif`,
+          filters: [{ name: 'GeoTIFF', extensions: ['tif', 'tiff'] }],
+        });
+        if (outputPath === null) return;
+
+        const outerDim = variable.dimensions[0];
+        const request: GeoTiffExportRequest = {
+          output_path: outputPath,
+          var_name: variable.name,
+          indices: {},
+          bands:
+            bandsFromOuterDim && outerDim !== undefined
+              ? { dimension: outerDim, start: 0, count: variable.shape[0] ?? 1 }
+              : null,
+        };
+        await callBackend<ExportSummary>('export_geotiff', { path: filePath, request });
+
+        setSuccess(true);
+        setTimeout(() => {
+          onClose();
+        }, 1500);
+        return;
+      }
+
+      // Long-form tables are streamed to disk by the backend
+      if (format === 'table' || format === 'parquet' || format === 'arrow') {
+        const extension =
+          format === 'parquet' ? 'parquet'
+            : format === 'arrow' ? 'arrow'
+              : settings.csvDelimiter === '\t' ? 'tsv' : 'csv';
+        const command =
+          format === 'parquet' ? 'export_parquet'
+            : format === 'arrow' ? 'export_arrow'
+              : 'export_csv';
+        const outputPath = await save({
+          defaultPath: `${variable.name}.${extension}`,
+          filters: [{ name: extension.toUpperCase(), extensions: [extension] }],
+        });
+        if (outputPath === null) return;
+
+        const request: TableExportRequest = {
+          output_path: outputPath,
+          variables: [variable.name],
+          slices: {},
+          decode_times: true,
+          delimiter: settings.csvDelimiter,
+          missing_value: settings.missingValuePlaceholder,
+        };
+        const summary = await callBackend<ExportSummary>(command, { path: filePath, request });
+
+        setSuccess(true);
+        // Leave the dialog open so warnings can be read
+        setWarnings(summary.warnings);
+        if (summary.warnings.length === 0) {
+          setTimeout(() => {
+            onClose();
+          }, 1500);
+        }
+        return;
+      }
+
       // Fetch variable data
-      const data = await invoke<VariableDataResponse>('get_variable_data', {
+      const data = await callBackend<VariableDataResponse>('get_variable_data', {
         path: filePath,
         varName: variable.name,
       });
@@ -84,10 +209,90 @@ export function ExportDialog({ filePath, variable, onClose }: ExportDialogProps)
                 >
                 JSON
               </button>
+              <button
+                className={format === 'netcdf' ? 'format-button active' : 'format-button'}
+                onClick={() => { setFormat('netcdf'); }}
+              >
+                NetCDF
+              </button>
+              <button
+                className={format === 'table' ? 'format-button active' : 'format-button'}
+                onClick={() => { setFormat('table'); }}
+              >
+                Table (all dimensions)
+              </button>
+              <button
+                className={format === 'parquet' ? 'format-button active' : 'format-button'}
+                onClick={() => { setFormat('parquet'); }}
+              >
+                Parquet
+              </button>
+              <button
+                className={format === 'arrow' ? 'format-button active' : 'format-button'}
+                onClick={() => { setFormat('arrow'); }}
+              >
+                Arrow
+              </button>
+              {variable.dimensions.length >= 2 && (
+                <button
+                  className={format === 'geotiff' ? 'format-button active' : 'format-button'}
+                  onClick={() => { setFormat('geotiff'); }}
+                >
+                  GeoTIFF
+                </button>
+              )}
             </div>
           </div>
 
-          {format === 'csv' && (
+          {format === 'netcdf' && (
+            <div className="form-group">
+              <label>NetCDF Format:</label>
+              <select
+                value={ncFormat}
+                onChange={(e) => { setNcFormat(e.target.value as NetCDFFormat); }}
+              >
+                <option value="netcdf4">NetCDF-4</option>
+                <option value="classic">Classic (64-bit offset)</option>
+              </select>
+              <label>Regrid:</label>
+              <select
+                value={regridMethod}
+                onChange={(e) => { setRegridMethod(e.target.value as RegridMethod | ''); }}
+              >
+                <option value="">No (native grid)</option>
+                <option value="nearest">Global regular grid, nearest</option>
+                <option value="bilinear">Global regular grid, bilinear</option>
+                <option value="conservative">Global regular grid, conservative</option>
+              </select>
+              {regridMethod !== '' && (
+                <>
+                  <label>Resolution (degrees):</label>
+                  <input
+                    type="number"
+                    min={0.01}
+                    step={0.25}
+                    value={resolution}
+                    onChange={(e) => { setResolution(Number(e.target.value)); }}
+                  />
+                </>
+              )}
+            </div>
+          )}
+
+          {format === 'geotiff' && variable.dimensions.length > 2 && (
+            <div className="form-group">
+              <label>
+                <input
+                  type="checkbox"
+                  checked={bandsFromOuterDim}
+                  onChange={(e) => { setBandsFromOuterDim(e.target.checked); }}
+                />
+                Write each {variable.dimensions[0]} step as a band
+              </label>
+            </div>
+          )}
+
+          {(format === 'csv' || format === 'table') && (
             <div className="csv-settings">
               <div className="form-group">
                 <label>CSV Delimiter:</label>
@@ -103,18 +308,20 @@ export function ExportDialog({ filePath, variable, onClose }: ExportDialogProps)
                 </select>
               </div>
 
-              <div className="form-group">
-                <label>Decimal Precision:</label>
-                <input
-                  type="number"
-                  min="0"
-                  max="10"
-                  value={settings.decimalPrecision}
-                  onChange={(e) =>
-                    { setSettings({ ...settings, decimalPrecision: parseInt(e.target.value) }); }
-                  }
-                />
-              </div>
+              {format === 'csv' && (
+                <div className="form-group">
+                  <label>Decimal Precision:</label>
+                  <input
+                    type="number"
+                    min="0"
+                    max="10"
+                    value={settings.decimalPrecision}
+                    onChange={(e) =>
+                      { setSettings({ ...settings, decimalPrecision: parseInt(e.target.value) }); }
+                    }
+                  />
+                </div>
+              )}
 
               <div className="form-group">
                 <label>Missing Value Placeholder:</label>
@@ -127,23 +334,28 @@ export function ExportDialog({ filePath, variable, onClose }: ExportDialogProps)
                 />
               </div>
 
-              <div className="form-group">
-                <label>
-                  <input
-                    type="checkbox"
-                    checked={settings.includeMetadataComments}
-                    onChange={(e) =>
-                      { setSettings({ ...settings, includeMetadataComments: e.target.checked }); }
-                    }
-                  />
-                  Include Metadata Comments
-                </label>
-              </div>
+              {format === 'csv' && (
+                <div className="form-group">
+                  <label>
+                    <input
+                      type="checkbox"
+                      checked={settings.includeMetadataComments}
+                      onChange={(e) =>
+                        { setSettings({ ...settings, includeMetadataComments: e.target.checked }); }
+                      }
+                    />
+                    Include Metadata Comments
+                  </label>
+                </div>
+              )}
             </div>
           )}
 
           {error !== null && <div className="error-message">{error}</div>}
           {success && <div className="success-message">Export completed successfully!</div>}
+          {warnings.map((warning) => (
+            <div key={warning} className="warning-message">{warning}</div>
+          ))}
         </div>
 
         <div className="modal-footer">
@@ -1,8 +1,10 @@
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
 import { open } from '@tauri-apps/plugin-dialog';
-import { FileUp, Loader2 } from 'lucide-react';
+import { File as FileIcon, FileStack, FileUp, Folder, FolderOpen, Globe, Loader2, Server } from 'lucide-react';
 import { useTauriCommand } from '../hooks/useTauriCommand';
-import type { FileMetadata } from '../types/netcdf';
+import { CatalogSearch } from './CatalogSearch';
+import { getServerUrl, listRemoteFiles, setServerUrl } from '../utils/backend';
+import type { DirectoryEntry, FileMetadata } from '../types/netcdf';
 
 interface FileLoaderProps {
   onFileLoaded: (metadata: FileMetadata) => void;
@@ -11,6 +13,39 @@ interface FileLoaderProps {
 export function FileLoader({ onFileLoaded }: FileLoaderProps): React.JSX.Element {
   const [selectedFile, setSelectedFile] = useState<string | null>(null);
   const { loading, error, execute } = useTauriCommand<FileMetadata>('open_netcdf_file');
+  const [server, setServer] = useState<string | null>(getServerUrl());
+  const [serverInput, setServerInput] = useState(server ?? 'http://127.0.0.1:8765');
+  const [remoteDir, setRemoteDir] = useState('');
+  const [remoteEntries, setRemoteEntries] = useState<DirectoryEntry[]>([]);
+  const [remoteError, setRemoteError] = useState<string | null>(null);
+  const [datasetUrl, setDatasetUrl] = useState('');
+
+  useEffect(() => {
+    if (server === null) return;
+    listRemoteFiles(remoteDir)
+      .then((entries) => {
+        setRemoteEntries(entries);
+        setRemoteError(null);
+      })
+      .catch((err: unknown) => {
+        setRemoteEntries([]);
+        setRemoteError(err instanceof Error ? err.message : String(err));
+      });
+  }, [server, remoteDir]);
+
+  const connect = (url: string | null): void => {
+    setServerUrl(url);
+    setServer(getServerUrl());
+    setRemoteDir('');
+  };
+
+  const loadPath = async (path: string): Promise<void> => {
+    setSelectedFile(path);
+    const metadata = await execute({ path });
+    if (metadata !== null) {
+      onFileLoaded(metadata);
+    }
+  };
 
   const handleFileSelect = async (): Promise<void> => {
     try {
@@ -21,6 +56,14 @@ export function FileLoader({ onFileLoaded }: FileLoaderProps): React.JSX.Element
             name: 'NetCDF Files',
             extensions: ['nc', 'nc4', 'netcdf', 'cdf'],
           },
+          {
+            name: 'GRIB2 Files',
+            extensions: ['grib2', 'grb2', 'grib', 'grb'],
+          },
+          {
+            name: 'NcML Files',
+            extensions: ['ncml'],
+          },
           {
             name: 'All Files',
             extensions: ['*'],
@@ -29,17 +72,43 @@ export function FileLoader({ onFileLoaded }: FileLoaderProps): React.JSX.Element
       });
 
       if (selected !== null && typeof selected === 'string') {
-        setSelectedFile(selected);
-        const metadata = await execute({ path: selected });
-        if (metadata !== null) {
-          onFileLoaded(metadata);
-        }
+        await loadPath(selected);
       }
     } catch (err) {
       console.error('Error selecting file:', err);
     }
   };
 
+  // Several files (e.g. one per month) open as one dataset joined along time;
+  // the backend takes them as a newline-separated list
+  const handleMultiSelect = async (): Promise<void> => {
+    try {
+      const selected = await open({
+        multiple: true,
+        filters: [{ name: 'NetCDF Files', extensions: ['nc', 'nc4', 'netcdf', 'cdf'] }],
+      });
+
+      if (Array.isArray(selected) && selected.length > 0) {
+        await loadPath(selected.length === 1 ? selected[0] : [...selected].sort().join('\n'));
+      }
+    } catch (err) {
+      console.error('Error selecting files:', err);
+    }
+  };
+
+  // Zarr stores are directories rather than single files
+  const handleStoreSelect = async (): Promise<void> => {
+    try {
+      const selected = await open({ multiple: false, directory: true });
+
+      if (selected !== null && typeof selected === 'string') {
+        await loadPath(selected);
+      }
+    } catch (err) {
+      console.error('Error selecting Zarr store:', err);
+    }
+  };
+
   return (
     <div className="file-loader">
       <div className="file-loader-content">
@@ -47,25 +116,117 @@ export function FileLoader({ onFileLoaded }: FileLoaderProps): React.JSX.Element
           <FileUp size={48} />
         </div>
         <h2>Open NetCDF File</h2>
-        <p>Select a NetCDF file to visualize and analyze</p>
-
-        <button
-          onClick={() => { void handleFileSelect(); }}
-          disabled={loading}
-          className="btn-primary"
-        >
-          {loading ? (
-            <>
-              <Loader2 className="animate-spin" size={20} />
-              Loading...
-            </>
+        <p>
+          {server === null
+            ? 'Select a NetCDF file or Zarr store to visualize and analyze'
+            : 'Choose a file on the remote server'}
+        </p>
+
+        {server === null ? (
+          <>
+            <button
+              onClick={() => { void handleFileSelect(); }}
+              disabled={loading}
+              className="btn-primary"
+            >
+              {loading ? (
+                <>
+                  <Loader2 className="animate-spin" size={20} />
+                  Loading...
+                </>
+              ) : (
+                <>
+                  <FileUp size={20} />
+                  Select File
+                </>
+              )}
+            </button>
+            <button
+              onClick={() => { void handleStoreSelect(); }}
+              disabled={loading}
+              className="btn-secondary"
+            >
+              <FolderOpen size={20} />
+              Open Zarr Store
+            </button>
+            <button
+              onClick={() => { void handleMultiSelect(); }}
+              disabled={loading}
+              className="btn-secondary"
+            >
+              <FileStack size={20} />
+              Join Files Along Time
+            </button>
+            <div className="remote-server">
+              <input
+                type="text"
+                value={datasetUrl}
+                onChange={(e) => setDatasetUrl(e.target.value)}
+                placeholder="OPeNDAP, s3:// or https://…#mode=bytes URL"
+              />
+              <button
+                className="btn-secondary"
+                onClick={() => { void loadPath(datasetUrl.trim()); }}
+                disabled={loading || datasetUrl.trim() === ''}
+              >
+                <Globe size={16} /> Open URL
+              </button>
+            </div>
+            <CatalogSearch onOpen={(path) => { void loadPath(path); }} disabled={loading} />
+          </>
+        ) : (
+          <div className="remote-browser">
+            <div className="remote-path">
+              <Server size={16} /> {server}/{remoteDir}
+            </div>
+            <ul>
+              {remoteDir !== '' && (
+                <li>
+                  <button onClick={() => setRemoteDir(remoteDir.split('/').slice(0, -1).join('/'))}>
+                    <Folder size={16} /> ..
+                  </button>
+                </li>
+              )}
+              {remoteEntries.map((entry) => (
+                <li key={entry.path}>
+                  <button
+                    onClick={() => {
+                      if (entry.is_dir && !entry.name.endsWith('.zarr')) {
+                        setRemoteDir(entry.path);
+                      } else {
+                        void loadPath(entry.path);
+                      }
+                    }}
+                    disabled={loading}
+                  >
+                    {entry.is_dir && !entry.name.endsWith('.zarr') ? <Folder size={16} /> : <FileIcon size={16} />}
+                    {entry.name}
+                  </button>
+                </li>
+              ))}
+            </ul>
+            {remoteError !== null && <div className="error-message">{remoteError}</div>}
+          </div>
+        )}
+
+        <div className="remote-server">
+          <input
+            type="text"
+            value={serverInput}
+            onChange={(e) => setServerInput(e.target.value)}
+            placeholder="http://host:8765"
+            disabled={server !== null}
+          />
+          {server === null ? (
+            <button className="btn-secondary" onClick={() => connect(serverInput)}>
+              <Server size={16} /> Connect to server
+            </button>
           ) : (
-            <>
-              <FileUp size={20} />
-              Select File
-            </>
+            <button className="btn-secondary" onClick={() => connect(null)}>
+              Use local files
+            </button>
           )}
-        </button>
+        </div>
 
         {selectedFile !== null && (
           <div className="selected-file">
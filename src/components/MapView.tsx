@@ -1,8 +1,14 @@
-import { useEffect, useState } from 'react';
-import { MapContainer, TileLayer, Marker, Popup, CircleMarker } from 'react-leaflet';
-import { invoke } from '@tauri-apps/api/core';
-import type { VariableDataResponse, Variable, FileMetadata } from '../types/netcdf';
-import { isNumericData } from '../types/netcdf';
+import { useEffect, useMemo, useState } from 'react';
+import { MapContainer, TileLayer, ImageOverlay } from 'react-leaflet';
+import { callBackend } from '../utils/backend';
+import type {
+  FileMetadata,
+  GeoExtent,
+  RegridMethod,
+  RegridOptions,
+  RenderedImage,
+  Variable,
+} from '../types/netcdf';
 import 'leaflet/dist/leaflet.css';
 
 interface MapViewProps {
@@ -11,87 +17,75 @@ interface MapViewProps {
   metadata: FileMetadata;
 }
 
-interface MapPoint {
-  lat: number;
-  lon: number;
-  value: number;
-  index: number;
-}
-
 export function MapView({ filePath, variable, metadata }: MapViewProps): React.JSX.Element {
-  const [data, setData] = useState<VariableDataResponse | null>(null);
+  const [image, setImage] = useState<RenderedImage | null>(null);
   const [loading, setLoading] = useState<boolean>(false);
   const [error, setError] = useState<string | null>(null);
-  const [mapPoints, setMapPoints] = useState<MapPoint[]>([]);
+  const [colormaps, setColormaps] = useState<string[]>(['viridis']);
+  const [colormap, setColormap] = useState<string>('viridis');
+  const [indices, setIndices] = useState<Record<string, number>>({});
+  // Regridding onto a regular grid covering the native grid's extent
+  const [regridMethod, setRegridMethod] = useState<RegridMethod | ''>('');
+  const [resolution, setResolution] = useState<number>(1);
+  const [nativeExtent, setNativeExtent] = useState<GeoExtent | null>(null);
 
   // Check if coordinates are available
   const coords = metadata.coordinates;
   const hasLatLon = coords?.lat_var !== null && coords?.lon_var !== null;
 
+  // Dimensions of the variable that are not latitude/longitude
+  const sliceDims = useMemo(() => {
+    const spatial = new Set<string>();
+    for (const name of [coords?.lat_var, coords?.lon_var]) {
+      const coordVar = metadata.variables.find((v) => v.name === name);
+      coordVar?.dimensions.forEach((d) => spatial.add(d));
+    }
+    return variable.dimensions
+      .map((name, i) => ({ name, size: variable.shape[i] ?? 1 }))
+      .filter((d) => !spatial.has(d.name));
+  }, [coords, metadata.variables, variable]);
+
+  useEffect(() => {
+    void callBackend<string[]>('list_colormaps').then(setColormaps);
+  }, []);
+
+  useEffect(() => {
+    setIndices({});
+    setNativeExtent(null);
+  }, [variable.name]);
+
+  const regrid = useMemo((): RegridOptions | null => {
+    if (regridMethod === '' || !(resolution > 0)) return null;
+    const extent = nativeExtent ?? { south: -90, west: -180, north: 90, east: 180 };
+    return {
+      method: regridMethod,
+      target: {
+        kind: 'regular',
+        extent: { ...extent, south: Math.max(extent.south, -90), north: Math.min(extent.north, 90) },
+        lat_step: resolution,
+        lon_step: resolution,
+      },
+    };
+  }, [regridMethod, resolution, nativeExtent]);
+
   useEffect(() => {
     if (!hasLatLon) return;
 
-    const loadData = async (): Promise<void> => {
+    const loadImage = async (): Promise<void> => {
       setLoading(true);
       setError(null);
 
       try {
-        // Load variable data
-        const varResponse = await invoke<VariableDataResponse>('get_variable_data', {
+        const rendered = await callBackend<RenderedImage>('render_variable_slice', {
           path: filePath,
           varName: variable.name,
+          indices,
+          options: { colormap, vmin: null, vmax: null, nan_color: null },
+          regrid,
         });
-
-        // Load latitude data
-        const latResponse =
-          coords?.lat_var !== null && coords?.lat_var !== undefined
-            ? await invoke<VariableDataResponse>('get_variable_data', {
-                path: filePath,
-                varName: coords.lat_var,
-              })
-            : null;
-
-        // Load longitude data
-        const lonResponse =
-          coords?.lon_var !== null && coords?.lon_var !== undefined
-            ? await invoke<VariableDataResponse>('get_variable_data', {
-                path: filePath,
-                varName: coords.lon_var,
-              })
-            : null;
-
-        setData(varResponse);
-
-        // Create map points - only for numeric data
-        if (latResponse !== null && lonResponse !== null &&
-            isNumericData(varResponse.values) &&
-            isNumericData(latResponse.values) &&
-            isNumericData(lonResponse.values)) {
-          const points: MapPoint[] = [];
-          const maxPoints = 1000; // Limit for performance
-
-          const varData = varResponse.values.data;
-          const latData = latResponse.values.data;
-          const lonData = lonResponse.values.data;
-
-          for (let i = 0; i < Math.min(varData.length, maxPoints); i++) {
-            const value = varData[i];
-            const lat = latData[i];
-            const lon = lonData[i];
-
-            if (
-              value !== undefined &&
-              lat !== undefined &&
-              lon !== undefined &&
-              !isNaN(value) &&
-              !isNaN(lat) &&
-              !isNaN(lon)
-            ) {
-              points.push({ lat, lon, value, index: i });
-            }
-          }
-
-          setMapPoints(points);
+        setImage(rendered);
+        if (regrid === null) {
+          setNativeExtent(rendered.extent);
         }
       } catch (err) {
         setError(err instanceof Error ? err.message : String(err));
@@ -100,8 +94,8 @@ export function MapView({ filePath, variable, metadata }: MapViewProps): React.J
       }
     };
 
-    void loadData();
-  }, [filePath, variable.name, coords, hasLatLon]);
+    void loadImage();
+  }, [filePath, variable.name, hasLatLon, indices, colormap, regrid]);
 
   if (!hasLatLon) {
     return (
@@ -112,116 +106,105 @@ export function MapView({ filePath, variable, metadata }: MapViewProps): React.J
     );
   }
 
-  if (loading) {
-    return <div className="loading">Loading map data...</div>;
-  }
-
   if (error !== null) {
     return <div className="error">Error loading map data: {error}</div>;
   }
 
-  if (mapPoints.length === 0) {
-    return <div className="no-data">No valid geographic data points found</div>;
+  if (image === null) {
+    return <div className="loading">Loading map data...</div>;
   }
 
-  // Calculate bounds and center
-  const lats = mapPoints.map((p) => p.lat);
-  const lons = mapPoints.map((p) => p.lon);
-  const centerLat = (Math.min(...lats) + Math.max(...lats)) / 2;
-  const centerLon = (Math.min(...lons) + Math.max(...lons)) / 2;
-
+  const { south, west, north, east } = image.extent;
   const units = variable.attributes['units'] ?? variable.attributes['unit'] ?? '';
 
-  // Determine color based on value (simple heatmap coloring)
-  const values = mapPoints.map((p) => p.value);
-  const minVal = Math.min(...values);
-  const maxVal = Math.max(...values);
-
-  const getColor = (value: number): string => {
-    if (maxVal === minVal) return '#3388ff';
-
-    const normalized = (value - minVal) / (maxVal - minVal);
-
-    // Blue to red gradient
-    const r = Math.floor(255 * normalized);
-    const b = Math.floor(255 * (1 - normalized));
-    return `rgb(${r}, 0, ${b})`;
-  };
-
   return (
     <div className="map-view">
-      <div className="map-info">
-        <p>
-          Showing {mapPoints.length} points
-          {data !== null && data.values.data.length > mapPoints.length && ` (limited from ${data.values.data.length})`}
-        </p>
+      <div className="map-controls">
+        <label>
+          Colormap{' '}
+          <select value={colormap} onChange={(e) => { setColormap(e.target.value); }}>
+            {colormaps.map((name) => (
+              <option key={name} value={name}>{name}</option>
+            ))}
+          </select>
+        </label>
+        <label>
+          Grid{' '}
+          <select
+            value={regridMethod}
+            onChange={(e) => { setRegridMethod(e.target.value as RegridMethod | ''); }}
+          >
+            <option value="">Native</option>
+            <option value="nearest">Regular (nearest)</option>
+            <option value="bilinear">Regular (bilinear)</option>
+            <option value="conservative">Regular (conservative)</option>
+          </select>
+        </label>
+        {regridMethod !== '' && (
+          <label>
+            Resolution (°){' '}
+            <input
+              type="number"
+              min={0.01}
+              step={0.25}
+              value={resolution}
+              onChange={(e) => { setResolution(Number(e.target.value)); }}
+            />
+          </label>
+        )}
+        {sliceDims.map((dim) => (
+          <label key={dim.name}>
+            {dim.name}{' '}
+            <input
+              type="range"
+              min={0}
+              max={dim.size - 1}
+              value={indices[dim.name] ?? 0}
+              onChange={(e) => {
+                setIndices({ ...indices, [dim.name]: Number(e.target.value) });
+              }}
+            />{' '}
+            {indices[dim.name] ?? 0}
+          </label>
+        ))}
+        {loading && <span className="loading-inline">Rendering...</span>}
       </div>
 
       <MapContainer
-        center={[centerLat, centerLon]}
-        zoom={4}
+        bounds={[[south, west], [north, east]]}
         style={{ height: '500px', width: '100%' }}
       >
         <TileLayer
           attribution='&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a>'
           url="https://{s}.tile.openstreetmap.org/{z}/{x}/{y}.png"
         />
-
-        {mapPoints.length === 1 ? (
-          // Single point - use marker
-          <Marker position={[mapPoints[0]?.lat ?? 0, mapPoints[0]?.lon ?? 0]}>
-            <Popup>
-              <strong>{variable.name}</strong>
-              <br />
-              Value: {String(mapPoints[0]?.value.toFixed(4))}
-              {units !== '' && ` ${units}`}
-              <br />
-              Lat: {String(mapPoints[0]?.lat.toFixed(4))}, Lon:{' '}
-              {String(mapPoints[0]?.lon.toFixed(4))}
-            </Popup>
-          </Marker>
-        ) : (
-          // Multiple points - use circle markers with color coding
-          mapPoints.map((point) => (
-            <CircleMarker
-              key={point.index}
-              center={[point.lat, point.lon]}
-              radius={6}
-              fillColor={getColor(point.value)}
-              color="#fff"
-              weight={1}
-              opacity={1}
-              fillOpacity={0.7}
-            >
-              <Popup>
-                <strong>{variable.name}</strong>
-                <br />
-                Value: {point.value.toFixed(4)}
-                {units !== '' && ` ${units}`}
-                <br />
-                Lat: {point.lat.toFixed(4)}, Lon: {point.lon.toFixed(4)}
-                <br />
-                Index: {point.index}
-              </Popup>
-            </CircleMarker>
-          ))
-        )}
+        <ImageOverlay
+          url={`data:image/png;base64,${image.png_base64}`}
+          bounds={[[south, west], [north, east]]}
+          opacity={0.8}
+        />
       </MapContainer>
 
+      <div className="map-info">
+        <p>
+          {image.width} × {image.height} grid cells
+        </p>
+      </div>
+
       <div className="map-legend">
-        <div className="legend-title">Color Scale</div>
+        <div className="legend-title">Color Scale{units !== '' && ` (${units})`}</div>
         <div className="legend-gradient">
-          <span>Low: {String(minVal.toFixed(2))}</span>
+          <span>Low: {image.vmin.toFixed(2)}</span>
           <div
             style={{
-              background: 'linear-gradient(to right, rgb(0,0,255), rgb(255,0,0))',
+              background: `linear-gradient(to right, ${image.legend.join(', ')})`,
               height: '20px',
               width: '200px',
               marginLeft: '10px',
               marginRight: '10px',
             }}
           />
-          <span>High: {String(maxVal.toFixed(2))}</span>
+          <span>High: {image.vmax.toFixed(2)}</span>
         </div>
       </div>
     </div>
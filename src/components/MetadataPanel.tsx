@@ -1,24 +1,41 @@
-import { useState } from 'react';
-import { ChevronDown, ChevronRight, Search } from 'lucide-react';
-import type { FileMetadata, Variable } from '../types/netcdf';
+import { useEffect, useState } from 'react';
+import { callBackend } from '../utils/backend';
+import { ChevronDown, ChevronRight, Copy, Search, X } from 'lucide-react';
+import type {
+  CdlDumpOptions,
+  DerivedVariableDefinition,
+  FileMetadata,
+  Variable,
+} from '../types/netcdf';
 
 interface MetadataPanelProps {
   metadata: FileMetadata;
   onVariableSelect: (variable: Variable) => void;
   selectedVariable: string | null;
+  /** Called with the new metadata after derived variables change */
+  onMetadataChange: (metadata: FileMetadata) => void;
 }
 
 export function MetadataPanel({
   metadata,
   onVariableSelect,
   selectedVariable,
+  onMetadataChange,
 }: MetadataPanelProps): React.JSX.Element {
   const [searchTerm, setSearchTerm] = useState('');
   const [expandedSections, setExpandedSections] = useState({
     dimensions: true,
     variables: true,
     attributes: false,
+    derived: false,
+    cdl: false,
   });
+  const [cdl, setCdl] = useState<string | null>(null);
+  const [cdlError, setCdlError] = useState<string | null>(null);
+  const [derivedName, setDerivedName] = useState('');
+  const [derivedExpression, setDerivedExpression] = useState('');
+  const [derivedUnits, setDerivedUnits] = useState('');
+  const [derivedError, setDerivedError] = useState<string | null>(null);
 
   const toggleSection = (section: keyof typeof expandedSections): void => {
     setExpandedSections((prev) => ({
@@ -27,6 +44,64 @@ export function MetadataPanel({
     }));
   };
 
+  // Fetch the CDL header when the section is first opened for this file
+  useEffect(() => {
+    setCdl(null);
+    setCdlError(null);
+  }, [metadata.file_path]);
+
+  useEffect(() => {
+    if (!expandedSections.cdl || cdl !== null || cdlError !== null) return;
+
+    const options: CdlDumpOptions = { data_variables: [], max_values: null };
+    callBackend<string>('dump_cdl', { path: metadata.file_path, options })
+      .then(setCdl)
+      .catch((err: unknown) => {
+        setCdlError(String(err));
+      });
+  }, [expandedSections.cdl, metadata.file_path, cdl, cdlError]);
+
+  const copyCdl = (): void => {
+    if (cdl !== null) {
+      void navigator.clipboard.writeText(cdl);
+    }
+  };
+
+  const defineDerived = (e: React.FormEvent): void => {
+    e.preventDefault();
+    const definition: DerivedVariableDefinition = {
+      name: derivedName.trim(),
+      expression: derivedExpression,
+      units: derivedUnits.trim() === '' ? null : derivedUnits.trim(),
+      long_name: null,
+    };
+    callBackend<FileMetadata>('define_derived_variable', { path: metadata.file_path, definition })
+      .then((updated) => {
+        setDerivedName('');
+        setDerivedExpression('');
+        setDerivedUnits('');
+        setDerivedError(null);
+        onMetadataChange(updated);
+      })
+      .catch((err: unknown) => {
+        setDerivedError(String(err));
+      });
+  };
+
+  const removeDerived = (name: string): void => {
+    callBackend<FileMetadata>('remove_derived_variable', { path: metadata.file_path, name })
+      .then((updated) => {
+        setDerivedError(null);
+        onMetadataChange(updated);
+      })
+      .catch((err: unknown) => {
+        setDerivedError(String(err));
+      });
+  };
+
+  // Derived variables carry the expression they were defined with
+  const derivedVariables = metadata.variables.filter((v) => v.attributes['expression'] !== undefined);
+
   const filteredVariables = metadata.variables.filter((v) =>
     v.name.toLowerCase().includes(searchTerm.toLowerCase())
   );
@@ -122,6 +197,88 @@ export function MetadataPanel({
           </div>
         )}
       </div>
+
+      {/* Derived Variables Section */}
+      <div className="metadata-section">
+        <button
+          className="section-header"
+          onClick={() => { toggleSection('derived'); }}
+        >
+          {expandedSections.derived ? <ChevronDown size={16} /> : <ChevronRight size={16} />}
+          <span>Derived Variables ({derivedVariables.length})</span>
+        </button>
+        {expandedSections.derived && (
+          <div className="section-content">
+            {derivedVariables.map((variable) => (
+              <div key={variable.name} className="derived-item">
+                <span className="derived-definition">
+                  {variable.name} = {variable.attributes['expression']}
+                </span>
+                <button
+                  className="derived-remove"
+                  onClick={() => { removeDerived(variable.name); }}
+                  title="Remove"
+                >
+                  <X size={14} />
+                </button>
+              </div>
+            ))}
+            <form className="derived-form" onSubmit={defineDerived}>
+              <input
+                type="text"
+                placeholder="Name, e.g. wind_speed"
+                value={derivedName}
+                onChange={(e) => { setDerivedName(e.target.value); }}
+              />
+              <input
+                type="text"
+                placeholder="Expression, e.g. sqrt(u10**2 + v10**2)"
+                value={derivedExpression}
+                onChange={(e) => { setDerivedExpression(e.target.value); }}
+              />
+              <input
+                type="text"
+                placeholder="Units (optional)"
+                value={derivedUnits}
+                onChange={(e) => { setDerivedUnits(e.target.value); }}
+              />
+              <button
+                type="submit"
+                disabled={derivedName.trim() === '' || derivedExpression.trim() === ''}
+              >
+                Add
+              </button>
+            </form>
+            {derivedError !== null && <div className="cdl-error">{derivedError}</div>}
+          </div>
+        )}
+      </div>
+
+      {/* CDL Header Section */}
+      <div className="metadata-section">
+        <button
+          className="section-header"
+          onClick={() => { toggleSection('cdl'); }}
+        >
+          {expandedSections.cdl ? <ChevronDown size={16} /> : <ChevronRight size={16} />}
+          <span>CDL Header (ncdump -h)</span>
+        </button>
+        {expandedSections.cdl && (
+          <div className="section-content">
+            {cdlError !== null && <div className="cdl-error">{cdlError}</div>}
+            {cdl === null && cdlError === null && <div className="cdl-loading">Loading...</div>}
+            {cdl !== null && (
+              <>
+                <button className="cdl-copy" onClick={copyCdl} title="Copy to clipboard">
+                  <Copy size={14} />
+                  <span>Copy</span>
+                </button>
+                <pre className="cdl-text">{cdl}</pre>
+              </>
+            )}
+          </div>
+        )}
+      </div>
     </div>
   );
 }
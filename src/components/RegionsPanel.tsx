@@ -0,0 +1,234 @@
+import { useEffect, useState } from 'react';
+import Plot from 'react-plotly.js';
+import { open, save } from '@tauri-apps/plugin-dialog';
+import { callBackend, getServerUrl } from '../utils/backend';
+import type {
+  ExportSummary,
+  RegionFeature,
+  RegionSelection,
+  RegionSeries,
+  RegionSeriesRequest,
+  Variable,
+  VariableStats,
+} from '../types/netcdf';
+
+interface RegionsPanelProps {
+  filePath: string;
+  variable: Variable;
+}
+
+function formatValue(value: number | null): string {
+  return value === null ? 'N/A' : value.toPrecision(6);
+}
+
+export function RegionsPanel({ filePath, variable }: RegionsPanelProps): React.JSX.Element {
+  const [regionPath, setRegionPath] = useState<string | null>(null);
+  const [features, setFeatures] = useState<RegionFeature[]>([]);
+  const [selected, setSelected] = useState<Set<string>>(new Set());
+  const [fractional, setFractional] = useState<boolean>(true);
+  const [series, setSeries] = useState<RegionSeries | null>(null);
+  const [stats, setStats] = useState<VariableStats | null>(null);
+  const [loading, setLoading] = useState<boolean>(false);
+  const [error, setError] = useState<string | null>(null);
+  const [message, setMessage] = useState<string | null>(null);
+
+  useEffect(() => {
+    setSeries(null);
+    setStats(null);
+  }, [filePath, variable.name]);
+
+  const selection = (): RegionSelection => ({
+    path: regionPath ?? '',
+    features: [...selected],
+    fractional,
+  });
+
+  const seriesRequest = (): RegionSeriesRequest => ({
+    var_name: variable.name,
+    region: selection(),
+    indices: {},
+  });
+
+  const handleLoadFile = async (): Promise<void> => {
+    setError(null);
+    try {
+      const path = await open({
+        multiple: false,
+        filters: [
+          { name: 'GeoJSON', extensions: ['geojson', 'json'] },
+          { name: 'Shapefile', extensions: ['shp'] },
+        ],
+      });
+      if (path !== null && typeof path === 'string') {
+        setFeatures(await callBackend<RegionFeature[]>('list_regions', { path }));
+        setRegionPath(path);
+        setSelected(new Set());
+        setSeries(null);
+        setStats(null);
+      }
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    }
+  };
+
+  const toggleFeature = (name: string): void => {
+    const next = new Set(selected);
+    if (next.has(name)) {
+      next.delete(name);
+    } else {
+      next.add(name);
+    }
+    setSelected(next);
+  };
+
+  const handleCompute = async (): Promise<void> => {
+    setLoading(true);
+    setError(null);
+    setMessage(null);
+    try {
+      const [regionSeries, regionStats] = await Promise.all([
+        callBackend<RegionSeries>('region_series', { path: filePath, request: seriesRequest() }),
+        callBackend<VariableStats>('get_variable_stats', {
+          path: filePath,
+          varName: variable.name,
+          slices: {},
+          region: selection(),
+        }),
+      ]);
+      setSeries(regionSeries);
+      setStats(regionStats);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleExport = async (): Promise<void> => {
+    setError(null);
+    setMessage(null);
+    try {
+      const outputPath = await save({
+        defaultPath: `${variable.name}_region.csv`,
+        filters: [{ name: 'CSV', extensions: ['csv'] }],
+      });
+      if (outputPath === null) return;
+
+      const request = { output_path: outputPath, series: seriesRequest() };
+      const summary = await callBackend<ExportSummary>('export_region_series', { path: filePath, request });
+      setMessage(`Wrote ${summary.records.toLocaleString()} rows to ${summary.output_path}`);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    }
+  };
+
+  if (getServerUrl() !== null) {
+    return <div className="no-data">Region masks need a local polygon file and are not available remotely</div>;
+  }
+
+  const units = variable.attributes['units'] ?? '';
+  const regionName = series?.features.join(', ') ?? '';
+
+  return (
+    <div className="regions-panel">
+      <div className="stations-options">
+        <button type="button" onClick={() => { void handleLoadFile(); }}>
+          Load GeoJSON / Shapefile...
+        </button>
+        {regionPath !== null && <span className="region-path">{regionPath}</span>}
+      </div>
+
+      {features.length > 0 && (
+        <>
+          <div className="region-features">
+            {features.map((feature) => (
+              <label key={feature.index}>
+                <input
+                  type="checkbox"
+                  checked={selected.has(feature.name)}
+                  onChange={() => { toggleFeature(feature.name); }}
+                />
+                {feature.name}
+              </label>
+            ))}
+          </div>
+          <div className="stations-options">
+            <label>
+              <input
+                type="checkbox"
+                checked={fractional}
+                onChange={(e) => { setFractional(e.target.checked); }}
+              />
+              Weight cells by the fraction inside
+            </label>
+            <button type="button" disabled={loading} onClick={() => { void handleCompute(); }}>
+              {loading ? 'Computing...' : selected.size === 0 ? 'Mean over all features' : 'Mean over selection'}
+            </button>
+            <button type="button" onClick={() => { void handleExport(); }}>
+              Export CSV...
+            </button>
+          </div>
+        </>
+      )}
+
+      {error !== null && <div className="error">{error}</div>}
+      {message !== null && <div className="success-message">{message}</div>}
+
+      {series !== null && (
+        <>
+          <div className="stats-summary">
+            <p>
+              {regionName}: {series.cells.toLocaleString()} grid cells,{' '}
+              {Math.round(series.area_km2).toLocaleString()} km²
+            </p>
+            {stats !== null && (
+              <p>
+                Overall mean {formatValue(stats.mean)}, min {formatValue(stats.min)}, max{' '}
+                {formatValue(stats.max)}, std dev {formatValue(stats.std_dev)}
+                {units !== '' && ` (${units})`}
+              </p>
+            )}
+          </div>
+          <Plot
+            data={[
+              {
+                x: series.steps.map((s) => s.time),
+                y: series.steps.map((s) => s.mean),
+                type: 'scatter',
+                mode: 'lines+markers',
+                marker: { size: 4 },
+                name: 'Mean',
+              },
+              {
+                x: series.steps.map((s) => s.time),
+                y: series.steps.map((s) => s.min),
+                type: 'scatter',
+                mode: 'lines',
+                line: { dash: 'dot', width: 1 },
+                name: 'Min',
+              },
+              {
+                x: series.steps.map((s) => s.time),
+                y: series.steps.map((s) => s.max),
+                type: 'scatter',
+                mode: 'lines',
+                line: { dash: 'dot', width: 1 },
+                name: 'Max',
+              },
+            ]}
+            layout={{
+              title: `${variable.name} over ${regionName}`,
+              xaxis: { title: 'Time', showgrid: true },
+              yaxis: { title: units !== '' ? `${variable.name} (${units})` : variable.name, showgrid: true },
+              hovermode: 'closest',
+              autosize: true,
+              margin: { l: 60, r: 40, t: 60, b: 60 },
+            }}
+            style={{ width: '100%', height: '450px' }}
+            config={{ responsive: true, displayModeBar: true }}
+          />
+        </>
+      )}
+    </div>
+  );
+}
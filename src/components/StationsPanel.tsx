@@ -0,0 +1,233 @@
+import { useEffect, useState } from 'react';
+import { open, save } from '@tauri-apps/plugin-dialog';
+import { callBackend, getServerUrl } from '../utils/backend';
+import type {
+  ExportSummary,
+  PointMethod,
+  Station,
+  StationExtraction,
+  StationRequest,
+  Variable,
+} from '../types/netcdf';
+
+interface StationsPanelProps {
+  filePath: string;
+  variable: Variable;
+}
+
+// Rows shown in the results table; exports always hold every record
+const MAX_ROWS = 500;
+
+function formatStations(stations: Station[]): string {
+  return stations
+    .map((s) => [s.id, s.lat, s.lon, ...(s.elevation !== null ? [s.elevation] : [])].join(','))
+    .join('\n');
+}
+
+export function StationsPanel({ filePath, variable }: StationsPanelProps): React.JSX.Element {
+  const [stationText, setStationText] = useState<string>('');
+  const [method, setMethod] = useState<PointMethod>('bilinear');
+  const [radius, setRadius] = useState<string>('');
+  const [extraction, setExtraction] = useState<StationExtraction | null>(null);
+  const [loading, setLoading] = useState<boolean>(false);
+  const [error, setError] = useState<string | null>(null);
+  const [message, setMessage] = useState<string | null>(null);
+
+  useEffect(() => {
+    setExtraction(null);
+  }, [filePath, variable.name]);
+
+  const buildRequest = async (): Promise<StationRequest> => {
+    const stations = await callBackend<Station[]>('parse_station_list', { text: stationText });
+    const radiusKm = parseFloat(radius);
+    return {
+      var_name: variable.name,
+      stations,
+      method,
+      indices: {},
+      search_radius_km: isNaN(radiusKm) ? null : radiusKm,
+    };
+  };
+
+  const handleLoadFile = async (): Promise<void> => {
+    setError(null);
+    try {
+      const selected = await open({
+        multiple: false,
+        filters: [{ name: 'Station list', extensions: ['csv', 'txt', 'tsv'] }],
+      });
+      if (selected !== null && typeof selected === 'string') {
+        const stations = await callBackend<Station[]>('read_station_file', { path: selected });
+        setStationText(formatStations(stations));
+      }
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    }
+  };
+
+  const handleExtract = async (): Promise<void> => {
+    setLoading(true);
+    setError(null);
+    setMessage(null);
+    try {
+      const request = await buildRequest();
+      setExtraction(await callBackend<StationExtraction>('extract_stations', { path: filePath, request }));
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleExport = async (): Promise<void> => {
+    setError(null);
+    setMessage(null);
+    try {
+      const outputPath = await save({
+        defaultPath: `${variable.name}_stations.csv`,
+        filters: [{ name: 'CSV', extensions: ['csv'] }],
+      });
+      if (outputPath === null) return;
+
+      const extractionRequest = await buildRequest();
+      const summary = await callBackend<ExportSummary>('export_stations', {
+        path: filePath,
+        request: { output_path: outputPath, extraction: extractionRequest },
+      });
+      setMessage(`Wrote ${summary.records.toLocaleString()} rows to ${summary.output_path}`);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    }
+  };
+
+  // One row per time step, one column per station
+  const ids = extraction?.stations.map((s) => s.id) ?? [];
+  const rows = new Map<string, Map<string, number | null>>();
+  for (const record of extraction?.records ?? []) {
+    let row = rows.get(record.time);
+    if (row === undefined) {
+      row = new Map();
+      rows.set(record.time, row);
+    }
+    row.set(record.station_id, record.value);
+  }
+  const times = [...rows.keys()];
+  const units = variable.attributes['units'] ?? '';
+
+  return (
+    <div className="stations-panel">
+      <div className="stations-input">
+        <label>
+          Stations (id, lat, lon[, elevation] per line)
+          <textarea
+            rows={6}
+            value={stationText}
+            placeholder={'oslo,59.91,10.75,23\nbergen,60.39,5.32'}
+            onChange={(e) => { setStationText(e.target.value); }}
+          />
+        </label>
+        <div className="stations-options">
+          {getServerUrl() === null && (
+            <button type="button" onClick={() => { void handleLoadFile(); }}>
+              Load CSV...
+            </button>
+          )}
+          <label>
+            Method
+            <select value={method} onChange={(e) => { setMethod(e.target.value as PointMethod); }}>
+              <option value="bilinear">Bilinear</option>
+              <option value="nearest">Nearest cell</option>
+            </select>
+          </label>
+          <label>
+            Fill search radius (km)
+            <input
+              type="number"
+              min="0"
+              value={radius}
+              placeholder="off"
+              onChange={(e) => { setRadius(e.target.value); }}
+            />
+          </label>
+          <button
+            type="button"
+            disabled={loading || stationText.trim() === ''}
+            onClick={() => { void handleExtract(); }}
+          >
+            {loading ? 'Extracting...' : 'Extract'}
+          </button>
+          {getServerUrl() === null && (
+            <button
+              type="button"
+              disabled={stationText.trim() === ''}
+              onClick={() => { void handleExport(); }}
+            >
+              Export CSV...
+            </button>
+          )}
+        </div>
+      </div>
+
+      {error !== null && <div className="error">{error}</div>}
+      {message !== null && <div className="success-message">{message}</div>}
+
+      {extraction !== null && (
+        <>
+          <table className="stations-matches">
+            <thead>
+              <tr>
+                <th>Station</th>
+                <th>Lat</th>
+                <th>Lon</th>
+                <th>Elevation</th>
+                <th>Nearest cell</th>
+                <th>Distance (km)</th>
+              </tr>
+            </thead>
+            <tbody>
+              {extraction.stations.map((s) => (
+                <tr key={s.id}>
+                  <td>{s.id}</td>
+                  <td>{s.lat}</td>
+                  <td>{s.lon}</td>
+                  <td>{s.elevation ?? ''}</td>
+                  <td>{s.grid_lat.toFixed(3)}, {s.grid_lon.toFixed(3)}</td>
+                  <td>{s.distance_km.toFixed(1)}</td>
+                </tr>
+              ))}
+            </tbody>
+          </table>
+
+          <div className="stations-table">
+            <table>
+              <thead>
+                <tr>
+                  <th>Time</th>
+                  {ids.map((id) => (
+                    <th key={id}>{id}{units !== '' && ` (${units})`}</th>
+                  ))}
+                </tr>
+              </thead>
+              <tbody>
+                {times.slice(0, MAX_ROWS).map((time) => (
+                  <tr key={time}>
+                    <td>{time}</td>
+                    {ids.map((id) => {
+                      const value = rows.get(time)?.get(id) ?? null;
+                      return <td key={id}>{value === null ? '' : value.toPrecision(6)}</td>;
+                    })}
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          </div>
+          {times.length > MAX_ROWS && (
+            <div className="table-limit-notice">
+              Showing the first {MAX_ROWS} of {times.length.toLocaleString()} time steps; export for all of them
+            </div>
+          )}
+        </>
+      )}
+    </div>
+  );
+}
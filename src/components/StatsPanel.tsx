@@ -1,5 +1,5 @@
 import { useEffect, useState } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { callBackend } from '../utils/backend';
 import type { VariableDataResponse, Variable } from '../types/netcdf';
 import { isNumericData } from '../types/netcdf';
 
@@ -71,6 +71,14 @@ export function StatsPanel({ filePath, variable }: StatsPanelProps): React.JSX.E
   const [loading, setLoading] = useState<boolean>(false);
   const [error, setError] = useState<string | null>(null);
   const [stats, setStats] = useState<Statistics | null>(null);
+  // Units typed by the user, and the ones the shown values are converted to
+  const [unitsInput, setUnitsInput] = useState<string>('');
+  const [targetUnits, setTargetUnits] = useState<string>('');
+
+  useEffect(() => {
+    setUnitsInput('');
+    setTargetUnits('');
+  }, [filePath, variable.name]);
 
   useEffect(() => {
     const loadData = async (): Promise<void> => {
@@ -78,9 +86,10 @@ export function StatsPanel({ filePath, variable }: StatsPanelProps): React.JSX.E
       setError(null);
 
       try {
-        const response = await invoke<VariableDataResponse>('get_variable_data', {
+        const response = await callBackend<VariableDataResponse>('get_variable_data', {
           path: filePath,
           varName: variable.name,
+          units: targetUnits === '' ? undefined : targetUnits,
         });
         setData(response);
 
@@ -99,21 +108,48 @@ export function StatsPanel({ filePath, variable }: StatsPanelProps): React.JSX.E
     };
 
     void loadData();
-  }, [filePath, variable.name]);
+  }, [filePath, variable.name, targetUnits]);
+
+  const nativeUnits = variable.attributes['units'] ?? variable.attributes['unit'] ?? '';
+  const unitsForm = (
+    <form
+      className="stats-units"
+      onSubmit={(e) => {
+        e.preventDefault();
+        setTargetUnits(unitsInput.trim());
+      }}
+    >
+      <label>
+        Show in
+        <input
+          type="text"
+          value={unitsInput}
+          placeholder={nativeUnits !== '' ? nativeUnits : 'units'}
+          onChange={(e) => { setUnitsInput(e.target.value); }}
+        />
+      </label>
+      <button type="submit">Convert</button>
+    </form>
+  );
 
   if (loading) {
     return <div className="loading">Calculating statistics...</div>;
   }
 
   if (error !== null) {
-    return <div className="error">Error loading data: {error}</div>;
+    return (
+      <div className="stats-panel">
+        {nativeUnits !== '' && unitsForm}
+        <div className="error">Error loading data: {error}</div>
+      </div>
+    );
   }
 
   if (stats === null || data === null) {
     return <div className="no-data">No statistics available</div>;
   }
 
-  const units = variable.attributes['units'] ?? variable.attributes['unit'] ?? '';
+  const units = targetUnits !== '' ? targetUnits : nativeUnits;
   const formatValue = (value: number): string => {
     if (isNaN(value) || !isFinite(value)) return 'N/A';
     return value.toFixed(4);
@@ -122,6 +158,7 @@ export function StatsPanel({ filePath, variable }: StatsPanelProps): React.JSX.E
   return (
     <div className="stats-panel">
       <h3>Statistics</h3>
+      {nativeUnits !== '' && unitsForm}
 
       <div className="stats-grid">
         <div className="stat-item">
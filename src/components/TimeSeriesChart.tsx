@@ -1,6 +1,6 @@
 import { useEffect, useState } from 'react';
 import Plot from 'react-plotly.js';
-import { invoke } from '@tauri-apps/api/core';
+import { callBackend } from '../utils/backend';
 import type { VariableDataResponse, Variable, FileMetadata } from '../types/netcdf';
 import { isNumericData } from '../types/netcdf';
 
@@ -8,20 +8,25 @@ interface TimeSeriesChartProps {
   filePath: string;
   variable: Variable;
   metadata: FileMetadata;
+  /** Changes when the file has grown and the data should be reloaded */
+  revision?: number;
 }
 
-export function TimeSeriesChart({ filePath, variable, metadata }: TimeSeriesChartProps): React.JSX.Element {
+export function TimeSeriesChart({ filePath, variable, metadata, revision = 0 }: TimeSeriesChartProps): React.JSX.Element {
   const [data, setData] = useState<VariableDataResponse | null>(null);
   const [loading, setLoading] = useState<boolean>(false);
   const [error, setError] = useState<string | null>(null);
 
   useEffect(() => {
     const loadData = async (): Promise<void> => {
-      setLoading(true);
+      // Reloads after the file grew keep showing the old chart until new data arrives
+      if (revision === 0) {
+        setLoading(true);
+      }
       setError(null);
 
       try {
-        const response = await invoke<VariableDataResponse>('get_variable_data', {
+        const response = await callBackend<VariableDataResponse>('get_variable_data', {
           path: filePath,
           varName: variable.name,
         });
@@ -34,7 +39,7 @@ export function TimeSeriesChart({ filePath, variable, metadata }: TimeSeriesChar
     };
 
     void loadData();
-  }, [filePath, variable.name]);
+  }, [filePath, variable.name, revision]);
 
   if (loading) {
     return <div className="loading">Loading data...</div>;
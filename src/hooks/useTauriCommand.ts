@@ -1,5 +1,6 @@
 import { useState, useCallback } from 'react';
-import { invoke, type InvokeArgs } from '@tauri-apps/api/core';
+import type { InvokeArgs } from '@tauri-apps/api/core';
+import { callBackend } from '../utils/backend';
 
 interface UseTauriCommandResult<T> {
   data: T | null;
@@ -22,7 +23,7 @@ export function useTauriCommand<T>(
       setError(null);
 
       try {
-        const result = await invoke<T>(command, args);
+        const result = await callBackend<T>(command, args);
         setData(result);
         return result;
       } catch (err) {
@@ -62,3 +62,327 @@ export interface VariableSubsetRequest {
   start: number[];
   count: number[];
 }
+
+export interface GeoExtent {
+  south: number;
+  west: number;
+  north: number;
+  east: number;
+}
+
+export interface RenderOptions {
+  colormap: string;
+  vmin: number | null;
+  vmax: number | null;
+  nan_color: [number, number, number, number] | null;
+}
+
+export interface RenderedImage {
+  var_name: string;
+  png_base64: string;
+  width: number;
+  height: number;
+  extent: GeoExtent;
+  vmin: number;
+  vmax: number;
+  legend: string[];
+}
+
+export interface DimensionSlice {
+  start: number;
+  count: number;
+}
+
+export type NetCDFFormat = 'netcdf4' | 'classic';
+
+export interface NetCDFExportRequest {
+  output_path: string;
+  variables: string[];
+  slices: Record<string, DimensionSlice>;
+  format: NetCDFFormat;
+  deflate_level: number | null;
+}
+
+export interface ExportSummary {
+  output_path: string;
+  variables: string[];
+  skipped: string[];
+  records: number;
+  /** What the output does not show as asked, such as times left undecoded */
+  warnings: string[];
+}
+
+export interface TableExportRequest {
+  output_path: string;
+  variables: string[];
+  slices: Record<string, DimensionSlice>;
+  decode_times: boolean;
+  delimiter: string | null;
+  missing_value: string | null;
+}
+
+export interface BandSelection {
+  dimension: string;
+  start: number;
+  count: number;
+}
+
+export interface GeoTiffExportRequest {
+  output_path: string;
+  var_name: string;
+  indices: Record<string, number>;
+  bands: BandSelection | null;
+}
+
+export interface CdlDumpOptions {
+  data_variables: string[];
+  max_values: number | null;
+}
+
+export interface CdlGenerateRequest {
+  output_path: string;
+  format: NetCDFFormat | null;
+}
+
+export interface VariableStats {
+  var_name: string;
+  count: number;
+  missing_count: number;
+  min: number | null;
+  max: number | null;
+  mean: number | null;
+  std_dev: number | null;
+}
+
+export interface DiffOptions {
+  abs_tolerance: number;
+  rel_tolerance: number;
+  variables: string[];
+  metadata_only: boolean;
+  /** Convert the second file's values to the first file's units */
+  convert_units: boolean;
+}
+
+export type ChangeKind = 'added' | 'removed' | 'changed';
+
+export interface MetadataChange {
+  item: 'dimension' | 'variable' | 'attribute' | 'data_type' | 'dimensions';
+  name: string;
+  variable: string | null;
+  change: ChangeKind;
+  old_value: string | null;
+  new_value: string | null;
+}
+
+export interface VariableDiff {
+  var_name: string;
+  compared: number;
+  differing: number;
+  max_abs_diff: number | null;
+  max_rel_diff: number | null;
+  rmse: number | null;
+  first_difference: number[] | null;
+}
+
+export interface SkippedVariable {
+  var_name: string;
+  reason: string;
+}
+
+export interface DiffReport {
+  file_a: string;
+  file_b: string;
+  metadata: MetadataChange[];
+  variables: VariableDiff[];
+  skipped: SkippedVariable[];
+  identical: boolean;
+}
+
+export interface TimeSeriesRequest {
+  var_name: string;
+  indices: Record<string, number>;
+  lat: number | null;
+  lon: number | null;
+}
+
+export interface CatalogScanSummary {
+  root: string;
+  index_path: string;
+  indexed: number;
+  unchanged: number;
+  removed: number;
+  failed: string[];
+}
+
+export interface CatalogQuery {
+  variable: string | null;
+  time_start: string | null;
+  time_end: string | null;
+  extent: GeoExtent | null;
+  attributes: Record<string, string>;
+}
+
+export interface CatalogVariable {
+  name: string;
+  standard_name: string | null;
+  long_name: string | null;
+  units: string | null;
+  dimensions: string[];
+}
+
+export interface CatalogEntry {
+  path: string;
+  time_start: string | null;
+  time_end: string | null;
+  extent: GeoExtent | null;
+  variables: CatalogVariable[];
+  global_attrs: Record<string, string>;
+}
+
+export interface DirectoryEntry {
+  name: string;
+  path: string;
+  is_dir: boolean;
+  size: number;
+}
+
+export type WatchChange = 'created' | 'changed' | 'removed';
+
+/** Payload of the `dataset-changed` event sent while a path is watched */
+export interface WatchEvent {
+  path: string;
+  change: WatchChange;
+  dimensions: Dimension[];
+}
+
+export type CfSeverity = 'error' | 'warning';
+
+export interface CfIssue {
+  severity: CfSeverity;
+  /** Section of the CF conventions, e.g. "3.3" */
+  section: string;
+  variable: string | null;
+  message: string;
+}
+
+export interface CfReport {
+  file_path: string;
+  conventions: string | null;
+  standard_name_table: string;
+  issues: CfIssue[];
+  errors: number;
+  warnings: number;
+}
+
+export interface DerivedVariableDefinition {
+  name: string;
+  /** Expression over other variables, e.g. "sqrt(u10**2 + v10**2)" */
+  expression: string;
+  units: string | null;
+  long_name: string | null;
+}
+
+export type RegridMethod = 'nearest' | 'bilinear' | 'conservative';
+
+export type RegridTarget =
+  | { kind: 'regular'; extent: GeoExtent; lat_step: number; lon_step: number }
+  | { kind: 'file'; path: string };
+
+export interface RegridOptions {
+  target: RegridTarget;
+  method: RegridMethod;
+}
+
+export interface RegridExportRequest {
+  output_path: string;
+  variables: string[];
+  regrid: RegridOptions;
+  slices: Record<string, DimensionSlice>;
+  format: NetCDFFormat;
+}
+
+export interface Station {
+  id: string;
+  lat: number;
+  lon: number;
+  elevation: number | null;
+}
+
+export type PointMethod = 'nearest' | 'bilinear';
+
+export interface StationRequest {
+  var_name: string;
+  stations: Station[];
+  method: PointMethod;
+  indices: Record<string, number>;
+  search_radius_km: number | null;
+}
+
+export interface StationMatch {
+  id: string;
+  lat: number;
+  lon: number;
+  elevation: number | null;
+  grid_lat: number;
+  grid_lon: number;
+  distance_km: number;
+}
+
+export interface StationRecord {
+  station_id: string;
+  time: string;
+  value: number | null;
+  from_neighbour: boolean;
+}
+
+export interface StationExtraction {
+  var_name: string;
+  method: PointMethod;
+  stations: StationMatch[];
+  records: StationRecord[];
+}
+
+export interface StationExportRequest {
+  output_path: string;
+  extraction: StationRequest;
+}
+
+export interface RegionSelection {
+  path: string;
+  features: string[];
+  fractional: boolean;
+}
+
+export interface RegionFeature {
+  index: number;
+  name: string;
+  extent: GeoExtent;
+}
+
+export interface RegionSeriesRequest {
+  var_name: string;
+  region: RegionSelection;
+  indices: Record<string, number>;
+}
+
+export interface RegionStep {
+  time: string;
+  mean: number | null;
+  min: number | null;
+  max: number | null;
+  std_dev: number | null;
+  valid_fraction: number;
+}
+
+export interface RegionSeries {
+  var_name: string;
+  features: string[];
+  cells: number;
+  area_km2: number;
+  steps: RegionStep[];
+}
+
+export interface RegionExportRequest {
+  output_path: string;
+  series: RegionSeriesRequest;
+}
@@ -0,0 +1,78 @@
+import { invoke, type InvokeArgs } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import type { DirectoryEntry, WatchEvent } from '../types/netcdf';
+
+const SERVER_KEY = 'netcdf-viewer.server-url';
+
+/**
+ * URL of a remote `ncv serve` instance, or null for the in-process backend
+ */
+export function getServerUrl(): string | null {
+  return localStorage.getItem(SERVER_KEY);
+}
+
+export function setServerUrl(url: string | null): void {
+  if (url === null || url.trim() === '') {
+    localStorage.removeItem(SERVER_KEY);
+  } else {
+    localStorage.setItem(SERVER_KEY, url.trim().replace(/\/+$/, ''));
+  }
+}
+
+/**
+ * Run a backend command, either through Tauri or on the configured server.
+ * Arguments are the same in both cases; errors are thrown as messages.
+ */
+export async function callBackend<T>(command: string, args?: InvokeArgs): Promise<T> {
+  const serverUrl = getServerUrl();
+  if (serverUrl === null) {
+    return invoke<T>(command, args);
+  }
+
+  const response = await fetch(`${serverUrl}/api/${command}`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify(args ?? {}),
+  });
+  const body: unknown = await response.json();
+  if (!response.ok) {
+    const message = (body as { error?: string }).error;
+    throw new Error(message ?? `Server returned ${response.status}`);
+  }
+  return body as T;
+}
+
+/**
+ * List a directory under the server root (only available in remote mode)
+ */
+export async function listRemoteFiles(path: string): Promise<DirectoryEntry[]> {
+  return callBackend<DirectoryEntry[]>('list_files', { path });
+}
+
+/**
+ * Watch a file or directory for appended records and new files.
+ * Resolves to a function that stops watching. Remote servers do not push
+ * events, so there this is a no-op.
+ */
+export async function watchDataset(
+  path: string,
+  onEvent: (event: WatchEvent) => void,
+): Promise<() => void> {
+  if (getServerUrl() !== null) {
+    return () => {};
+  }
+
+  const unlisten = await listen<WatchEvent>('dataset-changed', (event) => {
+    onEvent(event.payload);
+  });
+  try {
+    await invoke('watch_path', { path });
+  } catch (err) {
+    unlisten();
+    throw err;
+  }
+  return () => {
+    unlisten();
+    void invoke('unwatch_path', { path });
+  };
+}